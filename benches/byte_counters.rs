@@ -0,0 +1,78 @@
+//! Compares the per-connection atomic byte counters used by the speed test with the
+//! single shared mutex they replaced, under the same 50-connection fan-in.
+//!
+//! Run with `cargo bench --bench byte_counters`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use netrunner_cli::modules::speed_test::ConnectionCounters;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+
+const CONNECTIONS: usize = 50;
+const CHUNKS_PER_CONNECTION: usize = 2_000;
+/// Typical size of a chunk from a download body stream
+const CHUNK_SIZE: usize = 16 * 1024;
+
+fn shared_mutex(runtime: &Runtime) -> usize {
+    runtime.block_on(async {
+        let total = Arc::new(Mutex::new(0usize));
+        let tasks: Vec<_> = (0..CONNECTIONS)
+            .map(|_| {
+                let total = Arc::clone(&total);
+                tokio::spawn(async move {
+                    for _ in 0..CHUNKS_PER_CONNECTION {
+                        *total.lock().await += CHUNK_SIZE;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let total = *total.lock().await;
+        total
+    })
+}
+
+fn connection_counters(runtime: &Runtime) -> u64 {
+    runtime.block_on(async {
+        let counters = Arc::new(ConnectionCounters::new(CONNECTIONS));
+        let tasks: Vec<_> = (0..CONNECTIONS)
+            .map(|connection| {
+                let counters = Arc::clone(&counters);
+                tokio::spawn(async move {
+                    for _ in 0..CHUNKS_PER_CONNECTION {
+                        counters.add(connection, CHUNK_SIZE);
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        counters.total()
+    })
+}
+
+fn byte_counters(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("byte_counters");
+    group.throughput(Throughput::Elements(
+        (CONNECTIONS * CHUNKS_PER_CONNECTION) as u64,
+    ));
+    group.bench_function(BenchmarkId::new("shared_mutex", CONNECTIONS), |b| {
+        b.iter(|| shared_mutex(&runtime))
+    });
+    group.bench_function(BenchmarkId::new("connection_counters", CONNECTIONS), |b| {
+        b.iter(|| connection_counters(&runtime))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, byte_counters);
+criterion_main!(benches);
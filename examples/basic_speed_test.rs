@@ -36,6 +36,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: true,
         detail_level: DetailLevel::Standard,
         max_servers: 3,
+        ..Default::default()
     };
 
     println!("📋 Configuration:");
@@ -45,6 +45,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: false, // Faster without animations
         detail_level: DetailLevel::Standard,
         max_servers: 1, // Test only 1 server
+        ..Default::default()
     };
 
     println!("Configuration:");
@@ -71,6 +72,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: true, // Full experience
         detail_level: DetailLevel::Debug,
         max_servers: 5, // Test multiple servers
+        ..Default::default()
     };
 
     println!("Configuration:");
@@ -101,6 +103,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: false, // No UI in CI/CD
         detail_level: DetailLevel::Standard,
         max_servers: 2,
+        ..Default::default()
     };
 
     println!("Configuration:");
@@ -127,6 +130,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: true,
         detail_level: DetailLevel::Standard,
         max_servers: 1,
+        ..Default::default()
     };
 
     println!("Configuration:");
@@ -155,6 +159,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: true,
         detail_level: DetailLevel::Debug,
         max_servers: 3,
+        ..Default::default()
     };
 
     println!("Configuration:");
@@ -49,6 +49,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled: false,
         detail_level: DetailLevel::Standard,
         max_servers: 2,
+        ..Default::default()
     };
 
     println!("🚀 Running speed test...");
@@ -163,6 +163,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 quality: *quality,
                 test_duration_seconds: *test_duration_seconds,
                 isp: Some("Demo ISP".to_string()),
+                ..Default::default()
             };
 
             storage.save_result(&result)?;
@@ -177,7 +178,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!();
 
     // ── Launch TUI ────────────────────────────────────────────────────────────
-    show_statistics_tui()?;
+    show_statistics_tui(None)?;
 
     // ── Post-exit message ─────────────────────────────────────────────────────
     println!();
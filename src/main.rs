@@ -1,20 +1,51 @@
 mod modules;
 
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{
+    builder::BoolishValueParser, parser::ValueSource, value_parser, Arg, ArgAction, ArgMatches,
+    Command,
+};
+use clap_complete::Shell;
 use colored::*;
 use dialoguer::{theme::ColorfulTheme, Select};
 
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::signal;
 
 use modules::{
+    analysis,
+    bloatcheck::BloatCheckTool,
+    bug_report::BugReport,
+    cloudcheck::CloudCheckTool,
+    config::{AppConfig, MenuSettings},
     diagnostics::NetworkDiagnosticsTool,
-    history::HistoryStorage,
+    email,
+    evidence::{self, EvidenceBundle},
+    history::{ExportFilter, ExportFormat, HistoryStorage},
+    history_browser::show_history_browser,
+    httpcheck::{HttpCheckTool, HttpThresholds},
     intro::{show_intro, show_simple_intro},
-    speed_test::SpeedTest,
+    lanscan::LanScanner,
+    localnet::Ipv4Subnet,
+    pingmon::{self, PingMonitor},
+    portcheck::{self, PortCheckRequest, PortCheckTool},
+    portmap::PortMapTool,
+    push,
+    rate_limit::CourtesyCap,
+    reporting,
+    service::{self, ServiceSpec},
+    share_card, signing,
+    speed_test::{ProviderCheck, SpeedTest, TestProfile},
     stats_ui::show_statistics_tui,
-    types::{DetailLevel, TestConfig},
+    telemetry::{self, TelemetrySample},
+    terminal::{self, TerminalCapabilities},
+    types::{
+        AdaptiveInterval, AdvertisedPlan, AggregateResult, AlertKind, Baseline, BitRate,
+        ConnectionQuality, DataSize, DetailLevel, Dscp, FullAnalysisResult, MonitorAlert,
+        ProbeOptions, QuietAction, ReportTarget, ServerList, ServerProvider, SpeedProvider,
+        TestConfig, ThemeName, TraceOptions, TraceProtocol, VisualStyle, DEFAULT_DNS_DOMAINS,
+    },
     ui::UI,
 };
 
@@ -38,23 +69,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
-async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
-    let matches = Command::new("Netrunner Speed Test")
+/// Command line definition, shared by argument parsing, `completions` and `manpage`
+fn build_cli() -> Command {
+    Command::new("Netrunner Speed Test")
         .version(env!("CARGO_PKG_VERSION"))
         .about("A feature-rich internet speed test & network diagnostics tool")
         .author(env!("CARGO_PKG_AUTHORS"))
         .arg(
             Arg::new("server")
+                .global(true)
                 .short('s')
                 .long("server")
+                .env("NETRUNNER_SERVER")
                 .value_name("URL")
                 .help("Custom test server URL")
                 .default_value("https://httpbin.org"),
         )
         .arg(
             Arg::new("size")
+                .global(true)
                 .short('z')
                 .long("size")
+                .env("NETRUNNER_SIZE")
                 .value_name("MB")
                 .help("Test file size in MB")
                 .value_parser(value_parser!(u64))
@@ -62,8 +98,10 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
         )
         .arg(
             Arg::new("timeout")
+                .global(true)
                 .short('t')
                 .long("timeout")
+                .env("NETRUNNER_TIMEOUT")
                 .value_name("SECONDS")
                 .help("Timeout for each test in seconds")
                 .value_parser(value_parser!(u64))
@@ -71,36 +109,480 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
         )
         .arg(
             Arg::new("json")
+                .global(true)
                 .short('j')
                 .long("json")
+                .env("NETRUNNER_JSON")
+                .value_parser(BoolishValueParser::new())
                 .help("Output results in JSON format")
                 .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("no-animation")
+                .global(true)
                 .short('n')
                 .long("no-animation")
+                .env("NETRUNNER_NO_ANIMATION")
+                .value_parser(BoolishValueParser::new())
                 .help("Disable animations")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("non-interactive")
+                .global(true)
+                .long("non-interactive")
+                .env("NETRUNNER_NON_INTERACTIVE")
+                .value_parser(BoolishValueParser::new())
+                .help("Never prompt or take over the screen (implied when stdin is not a terminal)")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("detail")
+                .global(true)
                 .short('d')
                 .long("detail")
+                .env("NETRUNNER_DETAIL")
                 .value_name("LEVEL")
                 .help("Detail level (basic, standard, detailed, debug)")
                 .default_value("standard"),
         )
+        .arg(
+            Arg::new("visual")
+                .global(true)
+                .long("visual")
+                .env("NETRUNNER_VISUAL")
+                .value_name("STYLE")
+                .help("Live throughput display (graph, gauge, minimal)")
+                .value_parser(["graph", "gauge", "minimal"])
+                .default_value("graph"),
+        )
+        .arg(
+            Arg::new("theme")
+                .global(true)
+                .long("theme")
+                .env("NETRUNNER_THEME")
+                .value_name("THEME")
+                .help("Color theme (cyberpunk, minimal, light-terminal, colorblind-safe)")
+                .value_parser(["cyberpunk", "minimal", "light-terminal", "colorblind-safe"]),
+        )
+        .arg(
+            Arg::new("export-image")
+                .global(true)
+                .long("export-image")
+                .value_name("FILE")
+                .help("Save a shareable results card (.svg or .png)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("sign")
+                .global(true)
+                .long("sign")
+                .env("NETRUNNER_SIGN")
+                .value_parser(BoolishValueParser::new())
+                .help("Sign evidence bundles, history backups and share cards, writing <file>.sig beside them")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-tests-per-hour")
+                .global(true)
+                .long("max-tests-per-hour")
+                .env("NETRUNNER_MAX_TESTS_PER_HOUR")
+                .value_name("N")
+                .help("Courtesy cap on speed tests per hour against public servers (0 disables)")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("plan")
+                .global(true)
+                .long("plan")
+                .env("NETRUNNER_PLAN")
+                .value_name("DOWN/UP")
+                .help("Advertised plan speeds in Mbps to compare against (e.g. 500/50)")
+                .value_parser(value_parser!(AdvertisedPlan)),
+        )
+        .arg(
+            Arg::new("runs")
+                .global(true)
+                .long("runs")
+                .env("NETRUNNER_RUNS")
+                .value_name("N")
+                .help("Run N complete speed tests and summarise them by their medians")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("pause")
+                .global(true)
+                .long("pause")
+                .env("NETRUNNER_PAUSE")
+                .value_name("SECONDS")
+                .help("Pause between runs when --runs is above 1")
+                .value_parser(value_parser!(u64))
+                .default_value("30"),
+        )
+        .arg(
+            Arg::new("retries")
+                .global(true)
+                .long("retries")
+                .env("NETRUNNER_RETRIES")
+                .value_name("N")
+                .help("Retry a failed test N times before exiting with an error")
+                .value_parser(value_parser!(u32))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("retry-backoff")
+                .global(true)
+                .long("retry-backoff")
+                .env("NETRUNNER_RETRY_BACKOFF")
+                .value_name("SECONDS")
+                .help("Wait before the first retry, doubled for each further retry")
+                .value_parser(value_parser!(u64))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("report-to")
+                .global(true)
+                .long("report-to")
+                .env("NETRUNNER_REPORT_TO")
+                .value_name("URL")
+                .help("POST each result to an aggregation server (token from NETRUNNER_REPORT_TOKEN)")
+                .value_parser(value_parser!(reqwest::Url)),
+        )
         .arg(
             Arg::new("mode")
                 .short('m')
                 .long("mode")
+                .env("NETRUNNER_MODE")
                 .value_name("MODE")
-                .help("Test mode (speed, diag, history, full, servers)")
+                .help("Test mode (speed, diag, history, full, servers, evidence, monitor, pingmon, portcheck, portmap, http-check, lanscan, cloudcheck, bloatcheck)")
                 .default_value("speed"),
         )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .value_name("DURATION")
+                .help("Time between tests in monitor mode (e.g. 30m, 1h), or between probes in pingmon mode (default 1s)")
+                .value_parser(humantime::parse_duration)
+                .default_value("30m"),
+        )
+        .arg(
+            Arg::new("min-interval")
+                .long("min-interval")
+                .env("NETRUNNER_MIN_INTERVAL")
+                .value_name("DURATION")
+                .help("Adapt the monitor interval: shortest time between tests, used after an anomaly (defaults to --interval)")
+                .value_parser(humantime::parse_duration),
+        )
+        .arg(
+            Arg::new("max-interval")
+                .long("max-interval")
+                .env("NETRUNNER_MAX_INTERVAL")
+                .value_name("DURATION")
+                .help("Adapt the monitor interval: longest time between tests while results are steady (defaults to --interval)")
+                .value_parser(humantime::parse_duration),
+        )
+        .arg(
+            Arg::new("once")
+                .long("once")
+                .help("With --mode monitor, run a single cycle and exit (for cron and service timers)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("evidence-runs")
+                .long("evidence-runs")
+                .value_name("N")
+                .help("Number of spaced speed tests in an evidence bundle")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("evidence-interval")
+                .long("evidence-interval")
+                .value_name("DURATION")
+                .help("Pause between evidence tests (e.g. 10m, 1h)")
+                .value_parser(humantime::parse_duration)
+                .default_value("10m"),
+        )
+        .arg(
+            Arg::new("evidence-output")
+                .long("evidence-output")
+                .value_name("FILE")
+                .help("Evidence bundle to write (.zip or .json)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .value_name("HOST")
+                .help("Host to check in portcheck mode, or HOST[:PORT] to probe in pingmon mode or as the second LAN host in bloatcheck mode"),
+        )
+        .arg(
+            Arg::new("ports")
+                .long("ports")
+                .value_name("LIST")
+                .help("Ports to check in portcheck mode (e.g. 22,80,443 or 8000-8010)")
+                .value_parser(portcheck::parse_ports)
+                .default_value("22,80,443,3389"),
+        )
+        .arg(
+            Arg::new("udp")
+                .long("udp")
+                .help("Also send UDP probes in portcheck mode")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("port-timeout")
+                .long("port-timeout")
+                .value_name("DURATION")
+                .help("How long to wait for each port before calling it filtered")
+                .value_parser(humantime::parse_duration)
+                .default_value("2s"),
+        )
+        .arg(
+            Arg::new("map-port")
+                .long("map-port")
+                .value_name("PORT")
+                .help("In portmap mode, map this TCP port on the router for two minutes and check it from the internet")
+                .value_parser(value_parser!(u16).range(1..)),
+        )
+        .arg(
+            Arg::new("subnet")
+                .long("subnet")
+                .value_name("CIDR")
+                .help("Subnet to sweep in lanscan mode, defaults to this machine's (e.g. 192.168.1.0/24)")
+                .value_parser(value_parser!(Ipv4Subnet)),
+        )
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .value_name("URL")
+                .help("Endpoint to fetch in http-check mode")
+                .value_parser(value_parser!(reqwest::Url)),
+        )
+        .arg(
+            Arg::new("cloud-fetch")
+                .long("cloud-fetch")
+                .help("In cloudcheck mode, also time fetching each region's endpoint over a new connection")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-ttfb")
+                .long("max-ttfb")
+                .value_name("DURATION")
+                .help("Time to first byte above which http-check reports the endpoint as slow")
+                .value_parser(humantime::parse_duration),
+        )
+        .arg(
+            Arg::new("max-total")
+                .long("max-total")
+                .value_name("DURATION")
+                .help("Total request time above which http-check reports the endpoint as slow")
+                .value_parser(humantime::parse_duration),
+        )
+        .arg(
+            Arg::new("tls-host")
+                .long("tls-host")
+                .value_name("HOST[:PORT]")
+                .help("Extra host to check TLS against during diagnostics (repeatable)")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("trace-targets")
+                .long("trace-targets")
+                .value_name("LIST")
+                .help("Hosts to trace in diagnostics (e.g. 8.8.8.8,1.1.1.1,server), server meaning the speed test servers in full mode")
+                .value_delimiter(',')
+                .default_value("8.8.8.8"),
+        )
+        .arg(
+            Arg::new("dns-domains")
+                .long("dns-domains")
+                .value_name("LIST")
+                .help("Names diagnostics resolves to time DNS (e.g. google.com,intranet.example.com) [default: dns_domains in config.toml, or five popular sites]")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("max-hops")
+                .long("max-hops")
+                .value_name("N")
+                .help("Furthest hop the diagnostics traceroute probes")
+                .value_parser(value_parser!(u8).range(1..=64))
+                .default_value("30"),
+        )
+        .arg(
+            Arg::new("probes-per-hop")
+                .long("probes-per-hop")
+                .value_name("N")
+                .help("Traceroute probes sent per hop, the hop shows their median time")
+                .value_parser(value_parser!(u8).range(1..=10))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("probe-timeout")
+                .long("probe-timeout")
+                .value_name("DURATION")
+                .help("How long each traceroute probe waits for an answer")
+                .value_parser(humantime::parse_duration)
+                .default_value("1s"),
+        )
+        .arg(
+            Arg::new("trace-protocol")
+                .long("trace-protocol")
+                .value_name("PROTO")
+                .help("Traceroute probe packets: icmp, udp[:PORT] or tcp[:PORT] (tcp:443 passes most ICMP filters)")
+                .value_parser(value_parser!(TraceProtocol))
+                .default_value("icmp"),
+        )
+        .arg(
+            Arg::new("provider")
+                .long("provider")
+                .env("NETRUNNER_PROVIDER")
+                .value_name("NAME")
+                .help("Speed test servers: auto (the best of the built-in pool), mlab (Measurement Lab's ndt7 servers) or fast (the Netflix caches fast.com uses)")
+                .value_parser(["auto", "mlab", "fast"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("latency-probes")
+                .long("latency-probes")
+                .env("NETRUNNER_LATENCY_PROBES")
+                .value_name("N")
+                .help("Requests timing the speed test's latency")
+                .value_parser(value_parser!(u32).range(1..=1000))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("jitter-probes")
+                .long("jitter-probes")
+                .env("NETRUNNER_JITTER_PROBES")
+                .value_name("N")
+                .help("Requests timing the speed test's jitter and packet loss")
+                .value_parser(value_parser!(u32).range(2..=1000))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("probe-spacing")
+                .long("probe-spacing")
+                .env("NETRUNNER_PROBE_SPACING")
+                .value_name("DURATION")
+                .help("Pause after each latency probe and between the starts of jitter probes (e.g. 500ms) [default: 100ms for latency, 50ms for jitter]")
+                .value_parser(humantime::parse_duration),
+        )
+        .arg(
+            Arg::new("probe-rate")
+                .long("probe-rate")
+                .env("NETRUNNER_PROBE_RATE")
+                .value_name("N")
+                .help("Most latency and jitter probes per minute to one server, for proxies that flag request bursts")
+                .value_parser(value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("max-data")
+                .long("max-data")
+                .env("NETRUNNER_MAX_DATA")
+                .value_name("SIZE")
+                .help("Stop a speed test once it has transferred this much data (e.g. 200MB), for metered connections")
+                .value_parser(value_parser!(DataSize)),
+        )
+        .arg(
+            Arg::new("max-rate")
+                .long("max-rate")
+                .env("NETRUNNER_MAX_RATE")
+                .value_name("RATE")
+                .help("Throttle each speed test phase to this rate (e.g. 50mbps)")
+                .value_parser(value_parser!(BitRate)),
+        )
+        .arg(
+            Arg::new("server-list")
+                .long("server-list")
+                .env("NETRUNNER_SERVER_LIST")
+                .value_name("FILE")
+                .help("TOML file of your own test servers, added to the public pool or replacing it")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("reselect-server")
+                .long("reselect-server")
+                .env("NETRUNNER_RESELECT_SERVER")
+                .value_parser(BoolishValueParser::new())
+                .help("Select the best server from the full pool instead of reusing the last one")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("require-idle")
+                .long("require-idle")
+                .env("NETRUNNER_REQUIRE_IDLE")
+                .value_parser(BoolishValueParser::new())
+                .help("Abort instead of warning when other traffic is using the line before the test (Linux)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("progress-events")
+                .long("progress-events")
+                .env("NETRUNNER_PROGRESS_EVENTS")
+                .value_parser(BoolishValueParser::new())
+                .help("Write the download and upload countdown to stderr as JSON lines")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .env("NETRUNNER_TAGS")
+                .value_name("TAG")
+                .help("Label recorded with the results (e.g. wired), repeatable, added to tags in config.toml")
+                .value_delimiter(',')
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("only-provider")
+                .long("only-provider")
+                .env("NETRUNNER_ONLY_PROVIDER")
+                .value_name("PROVIDER")
+                .help("Test only against these providers (e.g. cloudflare,self-hosted), repeatable")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(ServerProvider)),
+        )
+        .arg(
+            Arg::new("exclude-provider")
+                .long("exclude-provider")
+                .env("NETRUNNER_EXCLUDE_PROVIDER")
+                .value_name("PROVIDER")
+                .help("Never test against these providers (e.g. google), repeatable")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(ServerProvider)),
+        )
+        .arg(
+            Arg::new("dscp")
+                .long("dscp")
+                .env("NETRUNNER_DSCP")
+                .value_name("CLASS")
+                .help("Mark speed test traffic with a DSCP class (EF, CS0-CS7, AF11-AF43 or 0-63) to check QoS policies")
+                .value_parser(value_parser!(Dscp)),
+        )
+        .arg(
+            Arg::new("data-dir")
+                .global(true)
+                .long("data-dir")
+                .env("NETRUNNER_DATA_DIR")
+                .value_name("DIR")
+                .help("Directory of the history database (default: netrunner in the platform data directory)")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("no-history")
+                .global(true)
+                .long("no-history")
+                .env("NETRUNNER_NO_HISTORY")
+                .value_parser(BoolishValueParser::new())
+                .help("Neither read nor save history, e.g. for a second run while a monitor holds it")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("debug-servers")
+                .global(true)
                 .long("debug-servers")
                 .help("Show detailed server testing information")
                 .action(ArgAction::SetTrue),
@@ -111,23 +593,386 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Show test history (shorthand for --mode history)")
                 .action(ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            Arg::new("last")
+                .long("last")
+                .help("Print the most recent stored result without running a test, as JSON with --json")
+                .conflicts_with("history")
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run a speed test (shorthand for --mode speed)")
+                .arg(
+                    Arg::new("max-age")
+                        .long("max-age")
+                        .value_name("DURATION")
+                        .help("Reuse the latest stored result if it is newer than this (e.g. 15m, 1h)")
+                        .value_parser(humantime::parse_duration),
+                ),
+        )
+        .subcommand(
+            Command::new("install-service")
+                .about("Schedule monitor mode as a user service (systemd, launchd or Task Scheduler)")
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_name("DURATION")
+                        .help("Time between tests (e.g. 30m, 1h)")
+                        .value_parser(humantime::parse_duration)
+                        .default_value("30m"),
+                ),
+        )
+        .subcommand(
+            Command::new("uninstall-service").about("Remove the scheduled monitor service"),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script, e.g. `netrunner_cli completions bash > /etc/bash_completion.d/netrunner_cli`")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(value_parser!(Shell)),
+                ),
+        )
+        .subcommand(
+            Command::new("bug-report")
+                .about("Write version, platform, redacted config, monitor log and the last failed test to a JSON file for issue reports")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Where to write the report (default: netrunner-bug-report-<time>.json, --json prints it)")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("log-lines")
+                        .long("log-lines")
+                        .value_name("N")
+                        .help("Monitor service log lines to include")
+                        .value_parser(value_parser!(usize))
+                        .default_value("50"),
+                ),
+        )
+        .subcommand(
+            Command::new("telemetry")
+                .about("Share anonymous results (speeds, country, quality, no IPs) for a public dataset; off unless switched on")
+                .subcommand(Command::new("on").about("Start sending anonymous results after each speed test"))
+                .subcommand(Command::new("off").about("Stop sending results"))
+                .subcommand(
+                    Command::new("status")
+                        .about("Show whether results are sent, where to, and what the latest one would look like (the default)"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that an exported file matches its signature, i.e. is unmodified since it was signed")
+                .arg(
+                    Arg::new("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("signature")
+                        .long("signature")
+                        .value_name("FILE")
+                        .help("Signature to check against (default: <FILE>.sig)")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("KEY")
+                        .help("Public key or fingerprint the file must be signed with"),
+                ),
+        )
+        .subcommand(
+            Command::new("manpage")
+                .about("Print the man page in roff format, e.g. `netrunner_cli manpage > netrunner_cli.1`"),
+        )
+        .subcommand(
+            Command::new("baseline")
+                .about("Run a longer calibration test, multi and single stream, and compare later tests with it")
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("Name to keep the baseline under, one of the same name is replaced")
+                        .default_value("default"),
+                )
+                .subcommand(
+                    Command::new("list").about("List saved baselines, marking the active one"),
+                )
+                .subcommand(
+                    Command::new("use")
+                        .about("Compare tests with another saved baseline")
+                        .arg(Arg::new("name").required(true).value_name("NAME")),
+                ),
+        )
+        .subcommand(
+            Command::new("servers")
+                .about("Inspect the test server pool (defaults to --mode servers)")
+                .subcommand(
+                    Command::new("verify")
+                        .about("Run conformance checks against each server provider and list dead pool entries"),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Show test history (defaults to --mode history)")
+                .arg(
+                    Arg::new("tui")
+                        .long("tui")
+                        .help("Browse results full-screen: filter them, see details and charts, delete or tag them")
+                        .action(ArgAction::SetTrue),
+                )
+                .subcommand(
+                    Command::new("backup")
+                        .about("Write the history to a ZIP archive with a SHA-256 checksum")
+                        .arg(
+                            Arg::new("file")
+                                .required(true)
+                                .value_name("FILE")
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("congestion")
+                        .about("Show average speeds per hour and weekday as a heatmap, to show evening congestion to your ISP")
+                        .arg(
+                            Arg::new("days")
+                                .long("days")
+                                .value_name("N")
+                                .help("Only use results from the last N days [default: all]")
+                                .value_parser(value_parser!(u32).range(1..)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("dedupe")
+                        .about("Remove results stored more than once, keeping the first copy"),
+                )
+                .subcommand(
+                    Command::new("diag")
+                        .about("List stored diagnostics runs to trend DNS latency, route length and IPv6")
+                        .arg(
+                            Arg::new("limit")
+                                .long("limit")
+                                .value_name("N")
+                                .help("Most recent runs to show")
+                                .value_parser(value_parser!(usize))
+                                .default_value("20"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Write stored results as JSON or CSV, to a file or stdout, optionally filtered")
+                        .arg(
+                            Arg::new("file")
+                                .value_name("FILE")
+                                .help("File to write [default: stdout]")
+                                .value_parser(value_parser!(PathBuf)),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_name("FORMAT")
+                                .value_parser(["json", "csv"])
+                                .default_value("json"),
+                        )
+                        .arg(
+                            Arg::new("since")
+                                .long("since")
+                                .value_name("DATE")
+                                .help("Only results from this day on, e.g. 2024-01-01")
+                                .value_parser(value_parser!(chrono::NaiveDate)),
+                        )
+                        .arg(
+                            Arg::new("until")
+                                .long("until")
+                                .value_name("DATE")
+                                .help("Only results up to and including this day")
+                                .value_parser(value_parser!(chrono::NaiveDate)),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .long("tag")
+                                .value_name("TAG")
+                                .help("Only results carrying this tag, repeatable to require several")
+                                .value_delimiter(',')
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            Arg::new("quality")
+                                .long("quality")
+                                .value_name("QUALITY")
+                                .help("Only results rated this quality")
+                                .value_parser(["excellent", "good", "average", "poor", "very-poor", "failed"]),
+                        )
+                        .arg(
+                            Arg::new("server")
+                                .long("server")
+                                .value_name("TEXT")
+                                .help("Only results whose server location contains this text"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("restore")
+                        .about("Merge a history backup into this machine's history after checking its checksum")
+                        .arg(
+                            Arg::new("file")
+                                .required(true)
+                                .value_name("FILE")
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                ),
+        )
+}
+
+async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = build_cli().get_matches();
+
+    if let Some(("completions", sub_matches)) = matches.subcommand() {
+        let shell = *sub_matches.get_one::<Shell>("shell").unwrap();
+        // Generated into memory first, clap_complete panics when stdout closes early
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut build_cli(), env!("CARGO_PKG_NAME"), &mut script);
+        return match io::stdout().write_all(&script) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+
+    if matches.subcommand_name() == Some("manpage") {
+        let command = build_cli().name(env!("CARGO_PKG_NAME"));
+        let mut page = Vec::new();
+        clap_mangen::Man::new(command).render(&mut page)?;
+        return match io::stdout().write_all(&page) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+
+    // Settled before anything opens the history, a broken config falls back to the default
+    if matches.get_flag("no-history") {
+        HistoryStorage::disable();
+    }
+    if let Some(dir) = matches
+        .get_one::<PathBuf>("data-dir")
+        .cloned()
+        .or_else(|| AppConfig::load().ok()?.data_dir)
+    {
+        HistoryStorage::set_data_dir(dir);
+    }
+
+    // Before the config is loaded, a broken config file is worth reporting too
+    if let Some(("bug-report", sub_matches)) = matches.subcommand() {
+        return write_bug_report(
+            *sub_matches.get_one::<usize>("log-lines").unwrap(),
+            sub_matches.get_one::<PathBuf>("output"),
+            matches.get_flag("json"),
+        );
+    }
+
+    if let Some(("verify", sub_matches)) = matches.subcommand() {
+        return verify_signature(
+            sub_matches.get_one::<PathBuf>("file").unwrap(),
+            sub_matches.get_one::<PathBuf>("signature"),
+            sub_matches.get_one::<String>("key").map(String::as_str),
+            matches.get_flag("json"),
+        );
+    }
+
+    let app_config = AppConfig::load()?;
+    // Settings saved from the interactive menu apply when their flag isn't given
+    let defaulted = |id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
 
     let server_url = matches.get_one::<String>("server").unwrap().clone();
-    let test_size_mb = *matches.get_one::<u64>("size").unwrap();
+    let test_size_mb = match app_config.size_mb {
+        Some(size_mb) if defaulted("size") => size_mb,
+        _ => *matches.get_one::<u64>("size").unwrap(),
+    };
     let timeout_seconds = *matches.get_one::<u64>("timeout").unwrap();
     let json_output = matches.get_flag("json");
-    let animation_enabled = !matches.get_flag("no-animation");
+    // Respect NO_COLOR and fall back to plain, static output when piped
+    let terminal = TerminalCapabilities::get();
+    terminal.apply();
+    let animation_wanted = match app_config.animation {
+        Some(animation) if defaulted("no-animation") => animation,
+        _ => !matches.get_flag("no-animation"),
+    };
+    let animation_enabled = animation_wanted && terminal.supports_animation();
+    // Cron jobs, CI and pipes cannot answer prompts or drive full-screen views
+    let interactive = !matches.get_flag("non-interactive") && io::stdin().is_terminal();
 
-    let detail_level = match matches.get_one::<String>("detail").unwrap().as_str() {
-        "basic" => DetailLevel::Basic,
-        "detailed" => DetailLevel::Detailed,
-        "debug" => DetailLevel::Debug,
-        _ => DetailLevel::Standard,
+    let detail_level = match app_config.detail.as_deref() {
+        Some(detail) if defaulted("detail") => parse_detail_level(detail),
+        _ => parse_detail_level(matches.get_one::<String>("detail").unwrap()),
     };
 
     let debug_servers = matches.get_flag("debug-servers");
 
+    let max_tests_per_hour = matches
+        .get_one::<u32>("max-tests-per-hour")
+        .copied()
+        .unwrap_or(app_config.max_tests_per_hour);
+    let theme = match matches.get_one::<String>("theme") {
+        Some(name) => name.parse::<ThemeName>()?,
+        None => app_config.theme,
+    };
+
+    // --plan overrides the speeds but keeps the alert threshold from config.toml
+    let plan = match (matches.get_one::<AdvertisedPlan>("plan"), app_config.plan) {
+        (Some(cli), Some(configured)) => Some(AdvertisedPlan {
+            alert_below_percent: configured.alert_below_percent,
+            ..*cli
+        }),
+        (Some(cli), None) => Some(*cli),
+        (None, configured) => configured,
+    };
+
+    // --report-to overrides the URL but keeps the token and client id from config.toml,
+    // and NETRUNNER_REPORT_TOKEN keeps the token out of the process list
+    let mut report_to = match (
+        matches.get_one::<reqwest::Url>("report-to"),
+        app_config.report,
+    ) {
+        (Some(url), configured) => Some(ReportTarget {
+            url: url.to_string(),
+            ..configured.unwrap_or(ReportTarget {
+                url: String::new(),
+                token: None,
+                client_id: None,
+            })
+        }),
+        (None, configured) => configured,
+    };
+    if let (Some(target), Ok(token)) = (&mut report_to, std::env::var("NETRUNNER_REPORT_TOKEN")) {
+        target.token = Some(token);
+    }
+
+    let server_list = matches
+        .get_one::<PathBuf>("server-list")
+        .or(app_config.server_list.as_ref())
+        .map(|path| ServerList::load_from(path))
+        .transpose()?;
+
+    // Provider flags replace the matching list from the `[servers]` config table
+    let mut server_filter = app_config.servers;
+    if let Some(providers) = matches.get_many::<ServerProvider>("only-provider") {
+        server_filter.only_providers = providers.cloned().collect();
+    }
+    if let Some(providers) = matches.get_many::<ServerProvider>("exclude-provider") {
+        server_filter.exclude_providers = providers.cloned().collect();
+    }
+
+    let visual_style = matches
+        .get_one::<String>("visual")
+        .unwrap()
+        .parse::<VisualStyle>()?;
+
     let config = TestConfig {
         server_url,
         test_size_mb,
@@ -136,26 +981,255 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
         animation_enabled,
         detail_level,
         max_servers: 3,
+        visual_style,
+        max_tests_per_hour,
+        theme,
+        export_image: matches.get_one::<PathBuf>("export-image").cloned(),
+        sign_exports: matches.get_flag("sign") || app_config.sign_exports,
+        plan,
+        runs: *matches.get_one::<u32>("runs").unwrap(),
+        pause_seconds: *matches.get_one::<u64>("pause").unwrap(),
+        retries: *matches.get_one::<u32>("retries").unwrap(),
+        retry_backoff_seconds: *matches.get_one::<u64>("retry-backoff").unwrap(),
+        report_to,
+        telemetry_url: telemetry::endpoint(app_config.telemetry_url.as_deref()),
+        email: app_config.email,
+        snmp: app_config.snmp,
+        push_targets: push::targets(app_config.ntfy.as_deref(), app_config.pushover.as_deref())?,
+        quiet_hours: app_config.quiet_hours,
+        host_label: app_config.host_label,
+        site: app_config.site,
+        tags: {
+            let mut tags = app_config.tags;
+            for tag in matches.get_many::<String>("tag").into_iter().flatten() {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            tags
+        },
+        tls_hosts: matches
+            .get_many::<String>("tls-host")
+            .map(|hosts| hosts.cloned().collect())
+            .unwrap_or_default(),
+        server_hosts: Vec::new(),
+        trace_targets: matches
+            .get_many::<String>("trace-targets")
+            .map(|targets| targets.cloned().collect())
+            .unwrap_or_default(),
+        trace: TraceOptions {
+            max_hops: *matches.get_one::<u8>("max-hops").unwrap(),
+            probes_per_hop: *matches.get_one::<u8>("probes-per-hop").unwrap(),
+            probe_timeout: *matches.get_one::<Duration>("probe-timeout").unwrap(),
+            protocol: *matches.get_one::<TraceProtocol>("trace-protocol").unwrap(),
+        },
+        dns_domains: match matches.get_many::<String>("dns-domains") {
+            Some(domains) => domains.cloned().collect(),
+            None if !app_config.dns_domains.is_empty() => app_config.dns_domains,
+            None => DEFAULT_DNS_DOMAINS.map(str::to_string).to_vec(),
+        },
+        dscp: matches.get_one::<Dscp>("dscp").copied(),
+        provider: matches
+            .get_one::<String>("provider")
+            .unwrap()
+            .parse::<SpeedProvider>()?,
+        probes: ProbeOptions {
+            latency_probes: *matches.get_one::<u32>("latency-probes").unwrap(),
+            jitter_probes: *matches.get_one::<u32>("jitter-probes").unwrap(),
+            spacing: matches.get_one::<Duration>("probe-spacing").copied(),
+            max_per_minute: matches.get_one::<u32>("probe-rate").copied(),
+        },
+        max_data: matches.get_one::<DataSize>("max-data").copied(),
+        max_rate: matches.get_one::<BitRate>("max-rate").copied(),
+        monthly_data_budget: app_config.monthly_data_budget,
+        endpoints: app_config.endpoints,
+        medians_url: app_config.medians_url,
+        geo_service_timeout_seconds: 5,
+        geo_timeout_seconds: 8,
+        geolocation: app_config.geolocation,
+        server_list,
+        server_filter,
+        pinned_server: app_config.pinned_server,
+        reselect_server: matches.get_flag("reselect-server"),
+        require_idle: matches.get_flag("require-idle"),
+        progress_events: matches.get_flag("progress-events"),
+        interactive,
+    };
+
+    // `--last` only reads history, before any banner so scripts get the result alone
+    if matches.get_flag("last") {
+        return show_last_result(&config);
+    }
+
+    // `test --max-age` answers from history when a fresh enough result exists,
+    // so wrappers can poll without triggering a new measurement each time
+    if let Some(("test", sub_matches)) = matches.subcommand() {
+        if let Some(max_age) = sub_matches.get_one::<Duration>("max-age") {
+            if show_cached_result(&config, *max_age)? {
+                return Ok(());
+            }
+        }
+    }
+
+    match matches.subcommand() {
+        Some(("install-service", sub_matches)) => {
+            let interval = *sub_matches.get_one::<Duration>("interval").unwrap();
+            return install_service(&config, interval);
+        }
+        Some(("uninstall-service", _)) => return uninstall_service(),
+        Some(("telemetry", sub_matches)) => {
+            return telemetry_command(
+                &config,
+                sub_matches.subcommand_name(),
+                app_config.telemetry_url.as_deref(),
+            );
+        }
+        _ => {}
+    }
+
+    if let Some(("servers", sub_matches)) = matches.subcommand() {
+        if sub_matches.subcommand_name() == Some("verify") {
+            return verify_servers(&config).await;
+        }
+    }
+
+    if let Some(("baseline", sub_matches)) = matches.subcommand() {
+        return match sub_matches.subcommand() {
+            Some(("list", _)) => list_baselines(&config),
+            Some(("use", use_matches)) => {
+                let name = use_matches.get_one::<String>("name").unwrap();
+                HistoryStorage::new()?.set_active_baseline(name)?;
+                if !config.json_output {
+                    println!(
+                        "{} tests are now compared with baseline '{}'",
+                        "✓".bright_green(),
+                        name
+                    );
+                }
+                Ok(())
+            }
+            _ => run_baseline(&config, sub_matches.get_one::<String>("name").unwrap()).await,
+        };
+    }
+
+    if let Some(("history", sub_matches)) = matches.subcommand() {
+        match sub_matches.subcommand() {
+            Some(("backup", backup)) => {
+                return backup_history(&config, backup.get_one::<PathBuf>("file").unwrap());
+            }
+            Some(("restore", restore)) => {
+                return restore_history(&config, restore.get_one::<PathBuf>("file").unwrap());
+            }
+            Some(("diag", diag)) => {
+                return show_diagnostics_history(&config, *diag.get_one::<usize>("limit").unwrap());
+            }
+            Some(("congestion", congestion)) => {
+                return show_congestion(&config, congestion.get_one::<u32>("days").copied());
+            }
+            Some(("dedupe", _)) => return dedupe_history(&config),
+            Some(("export", export)) => return export_history(&config, export),
+            None if sub_matches.get_flag("tui") => {
+                if !config.interactive || config.json_output {
+                    return Err("history --tui needs an interactive terminal".into());
+                }
+                return Ok(show_history_browser()?);
+            }
+            _ => {}
+        }
+    }
+
+    let port_check = || PortCheckRequest {
+        target: matches
+            .get_one::<String>("target")
+            .cloned()
+            .unwrap_or_default(),
+        ports: matches.get_one::<Vec<u16>>("ports").unwrap().clone(),
+        udp: matches.get_flag("udp"),
+        timeout: *matches.get_one::<Duration>("port-timeout").unwrap(),
     };
 
+    let http_thresholds = || {
+        let defaults = HttpThresholds::default();
+        let ms = |arg: &str, default: f64| {
+            matches
+                .get_one::<Duration>(arg)
+                .map_or(default, |d| d.as_secs_f64() * 1000.0)
+        };
+        HttpThresholds {
+            ttfb_ms: ms("max-ttfb", defaults.ttfb_ms),
+            total_ms: ms("max-total", defaults.total_ms),
+            ..defaults
+        }
+    };
+    let http_url = matches.get_one::<reqwest::Url>("url");
+
+    let ping_target = matches.get_one::<String>("target").map(String::as_str);
+    // monitor mode's 30 minute default is far too slow for latency probes
+    let ping_interval = match matches.value_source("interval") {
+        Some(ValueSource::DefaultValue) => pingmon::DEFAULT_INTERVAL,
+        _ => *matches.get_one::<Duration>("interval").unwrap(),
+    };
+
+    let lan_subnet = matches.get_one::<Ipv4Subnet>("subnet").copied();
+    let map_port = matches.get_one::<u16>("map-port").copied();
+    let cloud_fetch = matches.get_flag("cloud-fetch");
+
     // If JSON output is requested, skip the interactive menu and intro
     if json_output {
-        return run_speed_test(&config).await;
+        return match matches.get_one::<String>("mode").map(String::as_str) {
+            Some("portcheck") => run_port_check(&config, &port_check()).await,
+            Some("http-check") => run_http_check(&config, http_url, http_thresholds()).await,
+            Some("pingmon") => run_ping_monitor(&config, ping_target, ping_interval).await,
+            Some("lanscan") => run_lan_scan(&config, lan_subnet).await,
+            Some("portmap") => run_port_map(&config, map_port).await,
+            Some("cloudcheck") => run_cloud_check(&config, cloud_fetch).await,
+            Some("bloatcheck") => run_bloat_check(&config, ping_target).await,
+            // Its cycles log a line each and never end, one JSON document can't hold them
+            Some("monitor") => Err("--json is not supported in monitor mode".into()),
+            Some("evidence") => {
+                run_evidence(
+                    &config,
+                    *matches.get_one::<u32>("evidence-runs").unwrap(),
+                    *matches.get_one::<Duration>("evidence-interval").unwrap(),
+                    matches.get_one::<PathBuf>("evidence-output").cloned(),
+                )
+                .await
+            }
+            _ => run_speed_test(&config).await,
+        };
+    }
+
+    // A single scheduled monitor cycle logs one line, skip the intro and banner
+    if matches.get_flag("once")
+        && matches.get_one::<String>("mode").map(String::as_str) == Some("monitor")
+    {
+        return run_monitor(
+            &config,
+            *matches.get_one::<Duration>("interval").unwrap(),
+            None,
+            true,
+        )
+        .await;
     }
 
-    // Show animated intro with glow effects (skip if animations disabled)
-    if animation_enabled {
-        // Try to show animated intro, fallback to simple if it fails
-        if show_intro().is_err() {
+    // Show animated intro with glow effects (skip if animations disabled or the
+    // terminal cannot render its RGB glyphs), and no intro at all when headless
+    if interactive {
+        if animation_enabled && terminal.unicode && terminal.truecolor {
+            // Try to show animated intro, fallback to simple if it fails
+            if show_intro().is_err() {
+                let _ = show_simple_intro();
+            }
+        } else {
             let _ = show_simple_intro();
         }
-    } else {
-        let _ = show_simple_intro();
     }
 
     // Initialize UI
     let ui = UI::new(config.clone());
-    ui.clear_screen()?;
+    if interactive {
+        ui.clear_screen()?;
+    }
     ui.show_welcome_banner()?;
 
     // Check for --history flag first (shorthand)
@@ -164,14 +1238,51 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Parse command line mode or show interactive menu
-    let mode = matches.get_one::<String>("mode").unwrap();
-    match mode.as_str() {
+    let mode = match matches.subcommand_name() {
+        Some("test") => "speed",
+        Some("servers") => "servers",
+        Some("history") => "history",
+        _ => matches.get_one::<String>("mode").unwrap().as_str(),
+    };
+    match mode {
         "speed" => run_speed_test(&config).await?,
         "diag" => run_diagnostics(&config).await?,
         "history" => show_history(&config).await?,
         "full" => run_full_test(&config).await?,
         "servers" => test_all_servers(&config, debug_servers).await?,
-        _ => show_interactive_menu(&config).await?,
+        "monitor" => {
+            run_monitor(
+                &config,
+                *matches.get_one::<Duration>("interval").unwrap(),
+                adaptive_interval(&matches)?,
+                false,
+            )
+            .await?
+        }
+        "portcheck" => run_port_check(&config, &port_check()).await?,
+        "http-check" => run_http_check(&config, http_url, http_thresholds()).await?,
+        "pingmon" => run_ping_monitor(&config, ping_target, ping_interval).await?,
+        "lanscan" => run_lan_scan(&config, lan_subnet).await?,
+        "portmap" => run_port_map(&config, map_port).await?,
+        "cloudcheck" => run_cloud_check(&config, cloud_fetch).await?,
+        "bloatcheck" => run_bloat_check(&config, ping_target).await?,
+        "evidence" => {
+            run_evidence(
+                &config,
+                *matches.get_one::<u32>("evidence-runs").unwrap(),
+                *matches.get_one::<Duration>("evidence-interval").unwrap(),
+                matches.get_one::<PathBuf>("evidence-output").cloned(),
+            )
+            .await?
+        }
+        _ if interactive => show_interactive_menu(&config).await?,
+        other => {
+            return Err(format!(
+                "Unknown mode '{}' (the interactive menu needs a terminal on stdin)",
+                other
+            )
+            .into())
+        }
     }
 
     Ok(())
@@ -305,15 +1416,24 @@ async fn test_all_servers(
 }
 
 async fn show_interactive_menu(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    // The server picked with "Choose Server" stays pinned for the rest of the session,
+    // while "Settings" also writes its changes to the config file
+    let mut config = config.clone();
     loop {
+        let server_entry = match &config.pinned_server {
+            Some(server) => format!("📌 Choose Server (pinned: {})", server.name),
+            None => "📌 Choose Server".to_string(),
+        };
         let options = vec![
-            "🚀 Run Speed Test",
-            "🔍 Run Network Diagnostics",
-            "📈 View Test History",
-            "🌐 Full Network Analysis",
-            "🛠️ Test All Servers",
-            "🎮 Animation Showcase",
-            "❌ Exit",
+            "🚀 Run Speed Test".to_string(),
+            "🔍 Run Network Diagnostics".to_string(),
+            "📈 View Test History".to_string(),
+            "🌐 Full Network Analysis".to_string(),
+            "🛠️ Test All Servers".to_string(),
+            server_entry,
+            "⚙️ Settings".to_string(),
+            "🎮 Animation Showcase".to_string(),
+            "❌ Exit".to_string(),
         ];
 
         let selection = Select::with_theme(&ColorfulTheme::default())
@@ -323,12 +1443,14 @@ async fn show_interactive_menu(config: &TestConfig) -> Result<(), Box<dyn std::e
             .interact()?;
 
         match selection {
-            0 => run_speed_test(config).await?,
-            1 => run_diagnostics(config).await?,
-            2 => show_history(config).await?,
-            3 => run_full_test(config).await?,
-            4 => test_all_servers(config, true).await?,
-            5 => show_animation_showcase(config).await?,
+            0 => run_speed_test(&config).await?,
+            1 => run_diagnostics(&config).await?,
+            2 => show_history(&config).await?,
+            3 => run_full_test(&config).await?,
+            4 => test_all_servers(&config, true).await?,
+            5 => choose_server(&mut config).await?,
+            6 => edit_settings(&mut config).await?,
+            7 => show_animation_showcase(&config).await?,
             _ => {
                 println!("{}", "Goodbye!".bright_blue());
                 return Ok(());
@@ -340,10 +1462,11 @@ async fn show_interactive_menu(config: &TestConfig) -> Result<(), Box<dyn std::e
         print!("{} ", "Return to main menu? [Y/n]:".bright_blue());
         io::stdout().flush()?;
 
+        // A closed stdin (Ctrl-D) ends the session instead of redrawing the menu forever
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let read = io::stdin().read_line(&mut input)?;
 
-        if input.trim().to_lowercase() == "n" {
+        if read == 0 || input.trim().to_lowercase() == "n" {
             println!("{}", "Goodbye!".bright_blue());
             break;
         }
@@ -352,20 +1475,186 @@ async fn show_interactive_menu(config: &TestConfig) -> Result<(), Box<dyn std::e
     Ok(())
 }
 
-async fn run_speed_test(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
-    // Create speed test
-    let speed_test = SpeedTest::new(config.clone())?;
+/// List the discovered pool with latency and distance and pin the chosen server,
+/// or go back to automatic selection
+async fn choose_server(config: &mut TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut discovery = config.clone();
+    discovery.pinned_server = None;
+    let servers = SpeedTest::new(discovery)?.discover_servers().await?;
+
+    let mut items = vec!["⚡ Automatic (best server)".to_string()];
+    items.extend(servers.iter().map(|server| {
+        let latency = server
+            .latency_ms
+            .map(|ms| format!("{:.1} ms", ms))
+            .unwrap_or_else(|| "no reply".to_string());
+        format!(
+            "{} - {} ({}, {:.0} km)",
+            server.name,
+            server.location,
+            latency,
+            server.distance_km.unwrap_or(0.0)
+        )
+    }));
+    let current = config
+        .pinned_server
+        .as_ref()
+        .and_then(|pinned| servers.iter().position(|s| s.url == pinned.url))
+        .map_or(0, |i| i + 1);
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Choose a server for the following tests")
+        .default(current)
+        .max_length(10)
+        .items(&items)
+        .interact_opt()?;
+
+    match selection {
+        Some(0) => {
+            config.pinned_server = None;
+            println!("{}", "✓ Automatic server selection".bright_green());
+        }
+        Some(i) => {
+            let server = servers[i - 1].clone();
+            println!(
+                "{} {} ({})",
+                "📌 Pinned:".bright_green().bold(),
+                server.name,
+                server.url
+            );
+            config.pinned_server = Some(server);
+        }
+        None => {}
+    }
+    Ok(())
+}
 
-    // Run the test
-    let result = speed_test.run_full_test().await?;
+/// Change animation, server, test size and detail level for the session and
+/// save each change to the config file
+async fn edit_settings(config: &mut TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    const SIZES_MB: [u64; 6] = [1, 5, 10, 25, 50, 100];
+    const DETAIL_LEVELS: [DetailLevel; 4] = [
+        DetailLevel::Basic,
+        DetailLevel::Standard,
+        DetailLevel::Detailed,
+        DetailLevel::Debug,
+    ];
 
-    // Save result to history if not in JSON mode
-    if !config.json_output {
-        match HistoryStorage::new() {
-            Ok(storage) => {
+    // Remembered separately, the terminal may not be able to show animations
+    let mut animation_wanted = config.animation_enabled;
+    loop {
+        let server = match &config.pinned_server {
+            Some(server) => server.name.clone(),
+            None => "automatic".to_string(),
+        };
+        let items = vec![
+            format!(
+                "Animations: {}",
+                if animation_wanted { "on" } else { "off" }
+            ),
+            format!("Server: {}", server),
+            format!("Test size: {} MB", config.test_size_mb),
+            format!("Detail level: {}", config.detail_level),
+            "↩ Back".to_string(),
+        ];
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Settings")
+            .default(0)
+            .items(&items)
+            .interact_opt()?;
+
+        match selection {
+            Some(0) => {
+                animation_wanted = !animation_wanted;
+                config.animation_enabled =
+                    animation_wanted && TerminalCapabilities::get().supports_animation();
+            }
+            Some(1) => choose_server(config).await?,
+            Some(2) => {
+                let labels: Vec<String> = SIZES_MB.iter().map(|mb| format!("{} MB", mb)).collect();
+                let current = SIZES_MB
+                    .iter()
+                    .position(|&mb| mb == config.test_size_mb)
+                    .unwrap_or(2);
+                let Some(i) = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Test size")
+                    .default(current)
+                    .items(&labels)
+                    .interact_opt()?
+                else {
+                    continue;
+                };
+                config.test_size_mb = SIZES_MB[i];
+            }
+            Some(3) => {
+                let current = DETAIL_LEVELS
+                    .iter()
+                    .position(|&level| level == config.detail_level)
+                    .unwrap_or(1);
+                let Some(i) = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Detail level")
+                    .default(current)
+                    .items(DETAIL_LEVELS)
+                    .interact_opt()?
+                else {
+                    continue;
+                };
+                config.detail_level = DETAIL_LEVELS[i];
+            }
+            _ => return Ok(()),
+        }
+
+        let settings = MenuSettings {
+            animation: animation_wanted,
+            size_mb: config.test_size_mb,
+            detail: config.detail_level,
+            pinned_server: config.pinned_server.clone(),
+        };
+        match AppConfig::config_path().and_then(|path| {
+            settings.save_to(&path)?;
+            Ok(path)
+        }) {
+            Ok(path) => println!("{} {}", "✓ Saved to".bright_green(), path.display()),
+            Err(e) => eprintln!("{} {}", "⚠️ Settings not saved:".yellow(), e),
+        }
+    }
+}
+
+/// Map a `--detail` value or a saved `detail` setting to its level
+fn parse_detail_level(detail: &str) -> DetailLevel {
+    match detail {
+        "basic" => DetailLevel::Basic,
+        "detailed" => DetailLevel::Detailed,
+        "debug" => DetailLevel::Debug,
+        _ => DetailLevel::Standard,
+    }
+}
+
+async fn run_speed_test(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if config.runs > 1 {
+        return run_speed_test_series(config).await;
+    }
+
+    enforce_courtesy_cap(config)?;
+
+    // Create speed test
+    let speed_test = SpeedTest::new(config.clone())?;
+
+    // Run the test
+    let result = speed_test.run_full_test().await?;
+
+    // Save result to history if not in JSON mode
+    if !config.json_output {
+        match HistoryStorage::new() {
+            Ok(storage) => {
                 if let Err(e) = storage.save_result(&result) {
                     eprintln!("Failed to save test result: {}", e);
                 }
+                if let Ok(Some(baseline)) = storage.get_active_baseline() {
+                    show_baseline_deviation(&baseline, &result);
+                }
+                warn_over_data_budget(config, &storage);
             }
             Err(e) => {
                 eprintln!("Failed to initialize history storage: {}", e);
@@ -373,7 +1662,553 @@ async fn run_speed_test(config: &TestConfig) -> Result<(), Box<dyn std::error::E
         }
     } else {
         // If JSON output is requested, print the result
-        println!("{}", serde_json::to_string_pretty(&result)?);
+        println!("{}", serde_json::to_string_pretty(&result.report())?);
+    }
+
+    export_share_card(config, &result);
+    report_result(config, &result).await;
+
+    check_phases(&result)
+}
+
+/// Calibrate a baseline: a longer multi-stream test over more servers, then a
+/// single-stream one, kept under `name` and made the one tests are compared with
+async fn run_baseline(config: &TestConfig, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if !config.json_output {
+        let ui = UI::new(config.clone());
+        ui.show_section_header("Baseline Calibration")?;
+        println!(
+            "Two tests: one with twice the usual duration over twice the servers, then a single-stream one."
+        );
+        println!();
+        println!("{}", "▶ Multi-stream test".bright_cyan().bold());
+    }
+    let profile = TestProfile::calibration();
+
+    // Choose servers afresh so the baseline doesn't hang on a cached one
+    enforce_courtesy_cap(config)?;
+    let calibration = TestConfig {
+        reselect_server: true,
+        ..config.clone()
+    };
+    let multi_stream = SpeedTest::new(calibration)?
+        .with_profile(profile)
+        .run_full_test()
+        .await?;
+    check_phases(&multi_stream)?;
+
+    if !config.json_output {
+        println!("\n{}", "▶ Single-stream test".bright_cyan().bold());
+    }
+    enforce_courtesy_cap(config)?;
+    let single_stream = SpeedTest::new(config.clone())?
+        .with_profile(profile.single_stream())
+        .run_full_test()
+        .await?;
+    check_phases(&single_stream)?;
+
+    let baseline = Baseline {
+        name: name.to_string(),
+        created_at: chrono::Utc::now(),
+        multi_stream,
+        single_stream,
+    };
+    HistoryStorage::new()?.save_baseline(&baseline)?;
+
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&baseline)?);
+        return Ok(());
+    }
+    println!(
+        "\n{} baseline '{}': {:.1} Mbps down, {:.1} Mbps up, {:.1} ms ping",
+        "✓ Saved".bright_green(),
+        baseline.name,
+        baseline.multi_stream.download_mbps,
+        baseline.multi_stream.upload_mbps,
+        baseline.multi_stream.ping_ms
+    );
+    if let Some(share) = baseline.single_stream_percent() {
+        println!(
+            "  A single stream reached {:.1} Mbps, {:.0}% of the multi-stream download",
+            baseline.single_stream.download_mbps, share
+        );
+    }
+    println!(
+        "{}",
+        "Later tests show how far they are from this baseline.".bright_black()
+    );
+    Ok(())
+}
+
+/// Saved baselines with their multi-stream figures, the active one marked
+fn list_baselines(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = HistoryStorage::new()?;
+    let baselines = storage.get_baselines()?;
+    let active = storage.get_active_baseline()?.map(|baseline| baseline.name);
+
+    if config.json_output {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "active": active,
+                "baselines": baselines,
+            }))?
+        );
+        return Ok(());
+    }
+
+    if baselines.is_empty() {
+        println!(
+            "{}",
+            "No baselines saved, run `netrunner_cli baseline` first.".yellow()
+        );
+        return Ok(());
+    }
+    let mut table = prettytable::Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![bF=>
+        "", "Name", "Date", "Down (Mbps)", "Up (Mbps)", "Ping (ms)", "Single stream"
+    ]);
+    for baseline in &baselines {
+        table.add_row(prettytable::row![
+            if active.as_deref() == Some(baseline.name.as_str()) {
+                "*"
+            } else {
+                ""
+            },
+            baseline.name,
+            baseline
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            format!("{:.1}", baseline.multi_stream.download_mbps),
+            format!("{:.1}", baseline.multi_stream.upload_mbps),
+            format!("{:.1}", baseline.multi_stream.ping_ms),
+            baseline
+                .single_stream_percent()
+                .map_or("-".to_string(), |share| format!("{:.0}%", share))
+        ]);
+    }
+    terminal::print_table(&table)?;
+    Ok(())
+}
+
+/// One line comparing a result with the active baseline
+fn show_baseline_deviation(baseline: &Baseline, result: &modules::types::SpeedTestResult) {
+    let deviation = baseline.deviation(result);
+    let percent = |value: Option<f64>| match value {
+        Some(percent) if percent <= -10.0 => format!("{:+.1}%", percent).bright_red(),
+        Some(percent) => format!("{:+.1}%", percent).bright_green(),
+        None => "-".normal(),
+    };
+    let ping = format!("{:+.1} ms", deviation.ping_ms);
+    println!(
+        "{} '{}': download {}, upload {}, ping {}",
+        "📏 Against baseline".bright_cyan(),
+        deviation.baseline,
+        percent(deviation.download_percent),
+        percent(deviation.upload_percent),
+        if deviation.ping_ms > 10.0 {
+            ping.bright_red()
+        } else {
+            ping.bright_green()
+        }
+    );
+}
+
+/// Fail the command when a throughput phase failed, once the result has been saved and shown
+fn check_phases(
+    result: &modules::types::SpeedTestResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !result.has_failures() {
+        return Ok(());
+    }
+    Err(result
+        .failures
+        .iter()
+        .map(|failure| format!("{} failed: {}", failure.phase, failure.reason))
+        .collect::<Vec<_>>()
+        .join("; ")
+        .into())
+}
+
+/// Run `config.runs` complete tests and summarise them by their medians.
+/// Every run is saved to history, plus one aggregate record with the medians.
+async fn run_speed_test_series(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = if config.json_output {
+        None
+    } else {
+        match HistoryStorage::new() {
+            Ok(storage) => Some(storage),
+            Err(e) => {
+                eprintln!("Failed to initialize history storage: {}", e);
+                None
+            }
+        }
+    };
+
+    let mut runs = Vec::new();
+    for run in 1..=config.runs {
+        if run > 1 {
+            if !config.json_output {
+                println!(
+                    "{} {}",
+                    "⏳ Next run in".bright_blue(),
+                    format!("{}s", config.pause_seconds).bright_cyan()
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(config.pause_seconds)).await;
+        }
+
+        if !config.json_output {
+            println!(
+                "{}",
+                format!("Run {} of {}", run, config.runs)
+                    .bright_magenta()
+                    .bold()
+            );
+        }
+
+        enforce_courtesy_cap(config)?;
+        let result = SpeedTest::new(config.clone())?.run_full_test().await?;
+        if let Some(storage) = &storage {
+            if let Err(e) = storage.save_result(&result) {
+                eprintln!("Failed to save test result: {}", e);
+            }
+        }
+        report_result(config, &result).await;
+        runs.push(result);
+    }
+
+    let aggregate = AggregateResult::from_runs(&runs).ok_or("No test runs completed")?;
+    if let Some(storage) = &storage {
+        if let Err(e) = storage.save_aggregate(&aggregate) {
+            eprintln!("Failed to save aggregate result: {}", e);
+        }
+    }
+
+    if config.json_output {
+        let output = serde_json::json!({
+            "runs": runs.iter().map(|r| r.report()).collect::<Vec<_>>(),
+            "median": aggregate.median.report(),
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        print_series_summary(&runs, &aggregate)?;
+    }
+    if let Some(storage) = &storage {
+        warn_over_data_budget(config, storage);
+    }
+
+    export_share_card(config, &aggregate.median);
+
+    runs.iter().try_for_each(check_phases)
+}
+
+fn print_series_summary(
+    runs: &[modules::types::SpeedTestResult],
+    aggregate: &AggregateResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!();
+    println!(
+        "{}",
+        format!(" 📊 COMBINED SUMMARY ({} RUNS) 📊 ", aggregate.run_count)
+            .on_bright_blue()
+            .white()
+            .bold()
+    );
+
+    let mut table = prettytable::Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![bF=>
+        "Run", "Download (Mbps)", "Upload (Mbps)", "Ping (ms)", "Jitter (ms)", "Loss (%)"
+    ]);
+    for (i, run) in runs.iter().enumerate() {
+        table.add_row(prettytable::row![
+            i + 1,
+            format!("{:.2}", run.download_mbps),
+            format!("{:.2}", run.upload_mbps),
+            format!("{:.1}", run.ping_ms),
+            format!("{:.1}", run.jitter_ms),
+            format!("{:.1}", run.packet_loss_percent)
+        ]);
+    }
+    let median = &aggregate.median;
+    table.add_row(prettytable::row![bFg=>
+        "Median",
+        format!("{:.2}", median.download_mbps),
+        format!("{:.2}", median.upload_mbps),
+        format!("{:.1}", median.ping_ms),
+        format!("{:.1}", median.jitter_ms),
+        format!("{:.1}", median.packet_loss_percent)
+    ]);
+    terminal::print_table(&table)?;
+
+    println!("{}: {}", "Quality (median)".bold(), median.quality);
+
+    Ok(())
+}
+
+/// Write the share card requested with --export-image. Failures are reported
+/// but don't fail the run, the measurement itself is already saved.
+fn export_share_card(config: &TestConfig, result: &modules::types::SpeedTestResult) {
+    let Some(path) = &config.export_image else {
+        return;
+    };
+
+    match share_card::export_image(result, path) {
+        Ok(()) => {
+            if !config.json_output {
+                println!(
+                    "{} {}",
+                    "✓ Share card saved to".bright_green(),
+                    path.display().to_string().bright_cyan()
+                );
+            }
+            if let Err(e) = sign_export(config, path) {
+                eprintln!("Failed to sign share card: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to export share card: {}", e),
+    }
+}
+
+/// Sign an exported file when --sign or `sign_exports` asks for it, returning
+/// where the signature went
+fn sign_export(
+    config: &TestConfig,
+    path: &Path,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    if !config.sign_exports {
+        return Ok(None);
+    }
+
+    let (signature_path, signature) = signing::sign_file(path)?;
+    if !config.json_output {
+        println!(
+            "{} {} {}",
+            "✓ Signed, signature saved to".bright_green(),
+            signature_path.display().to_string().bright_cyan(),
+            format!("(key {})", signature.fingerprint()).bright_blue()
+        );
+    }
+    Ok(Some(signature_path))
+}
+
+/// Upload a result to the configured aggregation server, and its anonymous sample
+/// when telemetry is on. A failed upload is reported but never fails the test
+/// itself; telemetry fails quietly, outside of NETRUNNER_DEBUG.
+async fn report_result(config: &TestConfig, result: &modules::types::SpeedTestResult) {
+    if let Some(url) = &config.telemetry_url {
+        if let Err(e) = telemetry::submit(url, result).await {
+            if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                eprintln!("[TRACE] telemetry submission failed: {}", e);
+            }
+        }
+    }
+
+    let Some(target) = &config.report_to else {
+        return;
+    };
+
+    match reporting::upload(target, config.plan, result).await {
+        Ok(()) if !config.json_output => println!(
+            "{} {}",
+            "✓ Result reported to".bright_green(),
+            target.url.bright_cyan()
+        ),
+        Ok(()) => {}
+        Err(e) => eprintln!("Failed to report result: {}", e),
+    }
+}
+
+/// Warn when this month's speed tests have used more data than `monthly_data_budget`
+fn warn_over_data_budget(config: &TestConfig, storage: &HistoryStorage) {
+    let Some(budget) = config.monthly_data_budget else {
+        return;
+    };
+    let usage = match storage.get_data_usage_for_month(chrono::Utc::now()) {
+        Ok(usage) => usage,
+        Err(e) => {
+            eprintln!("Failed to read data usage: {}", e);
+            return;
+        }
+    };
+    if usage.data_used.total() > budget.0 {
+        eprintln!(
+            "{}",
+            format!(
+                "⚠ Speed tests used {} in {}, over the {} monthly data budget",
+                DataSize(usage.data_used.total()),
+                usage.month,
+                budget
+            )
+            .bright_red()
+        );
+    }
+}
+
+/// Refuse to start another test when the hourly courtesy cap is used up.
+/// The cap is best effort: if the history database cannot be opened the test runs anyway.
+fn enforce_courtesy_cap(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    // The cap spares public servers, tests against only your own need no courtesy
+    if config.server_list.as_ref().is_some_and(|list| list.replace) {
+        return Ok(());
+    }
+    match HistoryStorage::new() {
+        Ok(storage) => CourtesyCap::new(config.max_tests_per_hour).check_and_record(&storage),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Print the latest stored result if it is younger than `max_age`.
+/// Returns false when no fresh result exists and a new test should run.
+fn show_cached_result(
+    config: &TestConfig,
+    max_age: Duration,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let latest = match HistoryStorage::new().and_then(|storage| storage.get_latest_result()) {
+        Ok(latest) => latest,
+        Err(_) => return Ok(false), // No usable history - fall back to a fresh test
+    };
+
+    let result = match latest {
+        Some(result) if result.is_fresher_than(max_age) => result,
+        _ => return Ok(false),
+    };
+
+    print_stored_result(config, &result, "ℹ Using cached result from")?;
+    Ok(true)
+}
+
+/// Print the most recent stored result, for `--last`
+fn show_last_result(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let result = HistoryStorage::new()?
+        .get_latest_result()?
+        .ok_or("No stored results yet, run a speed test first")?;
+    print_stored_result(config, &result, "ℹ Last stored result, age")
+}
+
+/// Print a result from history as a test would have, its age after `note`
+fn print_stored_result(
+    config: &TestConfig,
+    result: &modules::types::SpeedTestResult,
+    note: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&result.report())?);
+    } else {
+        let age = chrono::Utc::now().signed_duration_since(result.timestamp);
+        println!(
+            "{} {}",
+            note.bright_blue(),
+            humantime::format_duration(Duration::from_secs(age.num_seconds().max(0) as u64))
+                .to_string()
+                .bright_cyan()
+        );
+        SpeedTest::new(config.clone())?.display_results(result)?;
+    }
+
+    export_share_card(config, result);
+
+    Ok(())
+}
+
+/// Check that every provider in the pool still speaks the protocol we rely on, and
+/// list the pool entries whose test endpoints are dead
+async fn verify_servers(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let speed_test = SpeedTest::new(config.clone())?;
+    let verification = speed_test.verify_servers().await?;
+
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&verification)?);
+        return Ok(());
+    }
+    let checks = &verification.providers;
+
+    let mark = |ok: bool| if ok { "✓ OK" } else { "✗ FAIL" };
+
+    let mut table = prettytable::Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![bF=>
+        "Provider", "Server", "Latency", "Download", "Upload", "Status"
+    ]);
+    for check in checks {
+        table.add_row(prettytable::row![
+            check.provider,
+            check.server_name,
+            match check.latency_ms {
+                Some(ms) => format!("{:.0} ms", ms),
+                None => mark(false).to_string(),
+            },
+            match check.download_bytes {
+                _ if check.download_ok => mark(true).to_string(),
+                Some(bytes) => format!("✗ {} of {} bytes", bytes, ProviderCheck::PROBE_BYTES),
+                None => mark(false).to_string(),
+            },
+            match check.upload_ok {
+                Some(ok) => mark(ok).to_string(),
+                None => "n/a".to_string(),
+            },
+            if check.is_functional() {
+                "FUNCTIONAL"
+            } else {
+                "BROKEN"
+            }
+        ]);
+    }
+    terminal::print_table(&table)?;
+
+    let functional = checks.iter().filter(|c| c.is_functional()).count();
+    println!(
+        "\n{} {}/{} providers functional from this network",
+        if functional == checks.len() {
+            "✓".bright_green()
+        } else {
+            "⚠".bright_yellow()
+        },
+        functional,
+        checks.len()
+    );
+
+    let direction = |ok: Option<bool>| match ok {
+        Some(ok) => mark(ok).to_string(),
+        None => "n/a".to_string(),
+    };
+    let mut pool = prettytable::Table::new();
+    pool.set_format(*prettytable::format::consts::FORMAT_BORDERS_ONLY);
+    pool.add_row(prettytable::row![bF=>
+        "Server", "URL", "Download", "Upload", "Status"
+    ]);
+    for health in &verification.pool {
+        pool.add_row(prettytable::row![
+            health.server_name,
+            health.server_url,
+            direction(health.download_ok),
+            direction(health.upload_ok),
+            if health.is_alive() { "ALIVE" } else { "DEAD" }
+        ]);
+    }
+    println!();
+    terminal::print_table(&pool)?;
+
+    let dead: Vec<&str> = verification
+        .pool
+        .iter()
+        .filter(|health| !health.is_alive())
+        .map(|health| health.server_name.as_str())
+        .collect();
+    if dead.is_empty() {
+        println!("\n{} every pool entry answers", "✓".bright_green());
+    } else {
+        println!(
+            "\n{} {} of {} pool entries are dead and left out of tests: {}",
+            "⚠".bright_yellow(),
+            dead.len(),
+            verification.pool.len(),
+            dead.join(", ")
+        );
     }
 
     Ok(())
@@ -416,7 +2251,17 @@ async fn show_history(config: &TestConfig) -> Result<(), Box<dyn std::error::Err
                         "min_ping_ms": stats.min_ping_ms,
                         "max_ping_ms": stats.max_ping_ms,
                         "test_count": stats.test_count,
-                    }
+                    },
+                    "aggregates": storage.get_recent_aggregates(10)?,
+                    "ping_events": pingmon::find_events(&storage.get_ping_samples_since(
+                        chrono::Utc::now() - chrono::Duration::days(7),
+                    )?),
+                    "plan_attainment": match &config.plan {
+                        Some(plan) => Some(storage.get_plan_attainment(plan)?),
+                        None => None,
+                    },
+                    "data_usage": storage.get_monthly_data_usage()?,
+                    "monthly_data_budget_bytes": config.monthly_data_budget.map(|budget| budget.0),
                 });
                 println!("{}", serde_json::to_string_pretty(&output)?);
             }
@@ -431,7 +2276,12 @@ async fn show_history(config: &TestConfig) -> Result<(), Box<dyn std::error::Err
     // Interactive TUI statistics dashboard with pie charts
     ui.show_section_header("Test History & Statistics")?;
 
-    if let Err(e) = show_statistics_tui() {
+    let dashboard = if config.interactive {
+        show_statistics_tui(config.plan.as_ref())
+    } else {
+        Err(io::Error::other("non-interactive session"))
+    };
+    if let Err(e) = dashboard {
         // If the TUI fails (e.g. terminal too small), fall back to plain text
         ui.show_error(&format!(
             "TUI unavailable ({}), falling back to text output",
@@ -461,7 +2311,7 @@ async fn show_history(config: &TestConfig) -> Result<(), Box<dyn std::error::Err
                             format!("{}", result.quality)
                         ]);
                     }
-                    table.printstd();
+                    terminal::print_table(&table)?;
 
                     println!("\n{}", " 📊 STATISTICS 📊 ".on_bright_blue().white().bold());
                     println!("{}", "═════════════════════════".bright_blue());
@@ -487,6 +2337,65 @@ async fn show_history(config: &TestConfig) -> Result<(), Box<dyn std::error::Err
                         stats.min_ping_ms,
                         stats.max_ping_ms
                     );
+
+                    if let Some(plan) = &config.plan {
+                        let attainment = storage.get_plan_attainment(plan)?;
+                        println!(
+                            "{}: {:.0}% down / {:.0}% up of {} on average",
+                            "Plan Attainment".bold(),
+                            attainment.avg_download_percent,
+                            attainment.avg_upload_percent,
+                            plan
+                        );
+                        for day in attainment.daily.iter().rev().take(7) {
+                            println!(
+                                "  {}  {:>4.0}% down  {:>4.0}% up  ({} tests)",
+                                day.date,
+                                day.avg_download_percent,
+                                day.avg_upload_percent,
+                                day.test_count
+                            );
+                        }
+                        if attainment.below_alert_count > 0 {
+                            println!(
+                                "{}",
+                                format!(
+                                    "⚠ {} of {} tests below {:.0}% of your advertised plan",
+                                    attainment.below_alert_count,
+                                    attainment.test_count,
+                                    plan.alert_below_percent
+                                )
+                                .bright_red()
+                            );
+                        }
+                    }
+
+                    let months = storage.get_monthly_data_usage()?;
+                    if !months.is_empty() {
+                        println!("{}:", "Data Used by Tests".bold());
+                        for month in months.iter().take(6) {
+                            let total = month.data_used.total();
+                            let budget = match config.monthly_data_budget {
+                                Some(budget) if total > budget.0 => {
+                                    format!("  over the {} budget", budget).bright_red()
+                                }
+                                Some(budget) => format!(
+                                    "  {:.0}% of {}",
+                                    total as f64 * 100.0 / budget.0.max(1) as f64,
+                                    budget
+                                )
+                                .normal(),
+                                None => "".normal(),
+                            };
+                            println!(
+                                "  {}  {:>10}  ({} tests){}",
+                                month.month,
+                                DataSize(total).to_string(),
+                                month.test_count,
+                                budget
+                            );
+                        }
+                    }
                 }
             }
             Err(e) => ui.show_error(&format!("Failed to access history: {}", e))?,
@@ -508,50 +2417,1133 @@ async fn run_full_test(config: &TestConfig) -> Result<(), Box<dyn std::error::Er
     }
 
     // Run speed test
+    enforce_courtesy_cap(config)?;
     let speed_test = SpeedTest::new(config.clone())?;
     let speed_result = speed_test.run_full_test().await?;
 
-    // Run diagnostics
-    let diagnostics_tool = NetworkDiagnosticsTool::new(config.clone());
+    // Run diagnostics, checking TLS and networks of the servers the speed test used too
+    let mut diagnostics_config = config.clone();
+    for server in speed_test.selected_servers().await {
+        if let Some(host) = reqwest::Url::parse(&server.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+        {
+            diagnostics_config.tls_hosts.push(host.clone());
+            diagnostics_config.server_hosts.push(host);
+        }
+    }
+    let diagnostics_tool = NetworkDiagnosticsTool::new(diagnostics_config).without_causes();
     let diag_result = diagnostics_tool.run_diagnostics().await?;
+    let mut analysis = FullAnalysisResult::new(speed_result, diag_result);
+
+    // Rank probable causes against past results, then save to history
+    let storage = HistoryStorage::new();
+    let history = storage
+        .as_ref()
+        .ok()
+        .and_then(|storage| storage.get_recent_results(analysis::HISTORY_RESULTS).ok())
+        .unwrap_or_default();
+    analysis.causes = analysis::diagnose(&analysis, &history);
 
-    // Save result to history
     if !config.json_output {
-        match HistoryStorage::new() {
+        analysis::show_causes(&analysis.causes);
+        match storage {
             Ok(storage) => {
-                if let Err(e) = storage.save_result(&speed_result) {
+                if let Err(e) = storage.save_result(&analysis.speed) {
                     eprintln!("Failed to save test result: {}", e);
                 }
+                if let Err(e) = storage.save_full_analysis(&analysis) {
+                    eprintln!("Failed to save full analysis: {}", e);
+                }
+                warn_over_data_budget(config, &storage);
             }
             Err(e) => {
                 eprintln!("Failed to initialize history storage: {}", e);
             }
         }
     } else {
-        // If JSON output is requested, print combined results
-        let combined_result = serde_json::json!({
-            "speed_test": speed_result,
-            "diagnostics": diag_result
-        });
-        println!("{}", serde_json::to_string_pretty(&combined_result)?);
+        println!("{}", serde_json::to_string_pretty(&analysis.report())?);
     }
 
-    Ok(())
-}
+    export_share_card(config, &analysis.speed);
+    report_result(config, &analysis.speed).await;
 
-async fn show_animation_showcase(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
-    let ui = UI::new(config.clone());
+    check_phases(&analysis.speed)
+}
 
-    ui.clear_screen()?;
-    ui.show_section_header("Animation Showcase")?;
+/// `--min-interval` and `--max-interval` as an adaptive interval starting from
+/// `--interval`, None when neither is given
+fn adaptive_interval(
+    matches: &ArgMatches,
+) -> Result<Option<AdaptiveInterval>, Box<dyn std::error::Error>> {
+    let interval = *matches.get_one::<Duration>("interval").unwrap();
+    let min = matches.get_one::<Duration>("min-interval").copied();
+    let max = matches.get_one::<Duration>("max-interval").copied();
+    if min.is_none() && max.is_none() {
+        return Ok(None);
+    }
 
-    println!(
-        "{}",
-        "Demonstrating NetRunner's cyberpunk animations...".bright_cyan()
-    );
-    println!();
+    let (min, max) = (min.unwrap_or(interval), max.unwrap_or(interval));
+    if min > max {
+        return Err(format!(
+            "--min-interval {} is longer than --max-interval {}",
+            humantime::format_duration(min),
+            humantime::format_duration(max)
+        )
+        .into());
+    }
+    Ok(Some(AdaptiveInterval::new(interval, min, max)))
+}
 
-    // Show typing effect
+/// Test on a fixed interval, saving every result to history. With `once` a single
+/// cycle runs and failures exit non-zero, which is what service timers expect.
+async fn run_monitor(
+    config: &TestConfig,
+    interval: Duration,
+    mut adaptive: Option<AdaptiveInterval>,
+    once: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !once {
+        let ui = UI::new(config.clone());
+        ui.show_section_header("Connection Monitor")?;
+        match &adaptive {
+            Some(adaptive) => println!(
+                "Testing every {} to {}, longer while results are steady, press Ctrl+C to stop.",
+                humantime::format_duration(adaptive.min),
+                humantime::format_duration(adaptive.max)
+            ),
+            None => println!(
+                "Testing every {}, press Ctrl+C to stop.",
+                humantime::format_duration(interval)
+            ),
+        }
+        for window in &config.quiet_hours {
+            println!("Quiet hours: {}", window);
+        }
+        println!();
+    }
+
+    loop {
+        let mut anomaly = false;
+        let now = chrono::Local::now();
+        let quiet = config
+            .quiet_hours
+            .iter()
+            .find(|window| window.contains(now.naive_local()));
+        let cycle = match quiet {
+            Some(window) if window.action == QuietAction::Skip => {
+                println!(
+                    "{}  quiet hours {}, test skipped",
+                    now.format("%Y-%m-%d %H:%M:%S"),
+                    window
+                );
+                Ok(())
+            }
+            Some(_) => monitor_probe(config).await,
+            None => {
+                let outcome = monitor_cycle(config).await;
+                if let Some(adaptive) = &mut adaptive {
+                    let result = outcome.as_ref().ok();
+                    let below_plan =
+                        result.is_some_and(|result| below_plan_message(config, result).is_some());
+                    anomaly = adaptive.record(result, below_plan);
+                }
+                outcome.map(|_| ())
+            }
+        };
+        match cycle {
+            Ok(()) => {}
+            Err(e) if once => return Err(e),
+            Err(e) => eprintln!(
+                "{} {} {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                "✗ Monitor test failed:".bright_red(),
+                e
+            ),
+        }
+
+        if once {
+            return Ok(());
+        }
+        match &adaptive {
+            // Probes between the closer tests show whether the anomaly lasts
+            Some(adaptive) if anomaly => watch_latency(adaptive.current()).await,
+            Some(adaptive) => tokio::time::sleep(adaptive.current()).await,
+            None => tokio::time::sleep(interval).await,
+        }
+    }
+}
+
+/// Wait out `wait` with a latency probe every minute or so, logged and saved with
+/// the pingmon samples; the test that follows decides about alerts
+async fn watch_latency(wait: Duration) {
+    let deadline = tokio::time::Instant::now() + wait;
+    let spacing = (wait / 5).min(Duration::from_secs(60));
+    loop {
+        tokio::time::sleep_until((tokio::time::Instant::now() + spacing).min(deadline)).await;
+        if tokio::time::Instant::now() >= deadline {
+            return;
+        }
+        match pingmon::probe_target(pingmon::MONITOR_PROBE_TARGET).await {
+            Ok(sample) => log_probe(&sample, "follow-up"),
+            Err(e) => eprintln!("Latency probe failed: {}", e),
+        }
+    }
+}
+
+/// One monitor test: run, save and log a single summary line, then send alerts
+/// about outages and results below the plan by email and push when configured
+async fn monitor_cycle(
+    config: &TestConfig,
+) -> Result<modules::types::SpeedTestResult, Box<dyn std::error::Error>> {
+    enforce_courtesy_cap(config)?;
+    let outcome = monitor_test(config).await;
+
+    let alert = match &outcome {
+        Err(e) => Some((AlertKind::Outage, e.to_string())),
+        Ok(result) => match check_phases(result) {
+            Err(e) => Some((AlertKind::Outage, e.to_string())),
+            Ok(()) => {
+                below_plan_message(config, result).map(|message| (AlertKind::BelowPlan, message))
+            }
+        },
+    }
+    .map(|(kind, message)| MonitorAlert {
+        timestamp: chrono::Utc::now(),
+        kind,
+        message,
+    });
+    send_alerts(config, alert).await;
+
+    let result = outcome?;
+    check_phases(&result)?;
+    Ok(result)
+}
+
+/// The latency probe that stands in for a monitor test during quiet hours, saved
+/// with the pingmon samples and alerting as an outage when it gets no answer
+async fn monitor_probe(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let sample = pingmon::probe_target(pingmon::MONITOR_PROBE_TARGET).await?;
+    log_probe(&sample, "quiet hours");
+    let lost = sample.latency_ms.is_none();
+
+    let message = format!("No answer from {} to a quiet hours probe", sample.target);
+    let alert = lost.then(|| MonitorAlert {
+        timestamp: sample.timestamp,
+        kind: AlertKind::Outage,
+        message: message.clone(),
+    });
+    send_alerts(config, alert).await;
+
+    if lost {
+        Err(message.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Save a monitor mode latency probe with the pingmon samples and log it tagged `label`
+fn log_probe(sample: &modules::types::PingSample, label: &str) {
+    match HistoryStorage::new() {
+        Ok(storage) => {
+            if let Err(e) = storage.save_ping_sample(sample) {
+                eprintln!("Failed to save ping sample: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to initialize history storage: {}", e),
+    }
+
+    let timestamp = sample.timestamp.with_timezone(&chrono::Local);
+    match sample.latency_ms {
+        Some(ms) => println!(
+            "{}  ping {:.1} ms  [{}]",
+            timestamp.format("%Y-%m-%d %H:%M:%S"),
+            ms,
+            label
+        ),
+        None => println!(
+            "{}  no answer from {}  [{}]",
+            timestamp.format("%Y-%m-%d %H:%M:%S"),
+            sample.target,
+            label
+        ),
+    }
+}
+
+/// Mail and push `alert` where configured; mail is also sent without one, so a
+/// pending digest goes out on time
+async fn send_alerts(config: &TestConfig, alert: Option<MonitorAlert>) {
+    let host_label = config.host_label.as_deref();
+
+    if let Some(settings) = &config.email {
+        if let Err(e) = email::notify(settings, host_label, alert.clone()).await {
+            eprintln!("Failed to send email alert: {}", e);
+        }
+    }
+    if let Some(alert) = &alert {
+        let client = reqwest::Client::new();
+        for target in &config.push_targets {
+            if let Err(e) = push::notify(&client, target, host_label, alert).await {
+                eprintln!("Failed to send push notification: {}", e);
+            }
+        }
+    }
+}
+
+/// What a result below the plan's alert threshold fell short of, None when it didn't
+fn below_plan_message(
+    config: &TestConfig,
+    result: &modules::types::SpeedTestResult,
+) -> Option<String> {
+    let plan = config.plan.as_ref()?;
+    let attainment = plan.attainment(result);
+    plan.is_below_alert(&attainment).then(|| {
+        format!(
+            "download {:.1} Mbps ({:.0}%), upload {:.1} Mbps ({:.0}%) of {}, alert below {:.0}%",
+            result.download_mbps,
+            attainment.download_percent,
+            result.upload_mbps,
+            attainment.upload_percent,
+            plan,
+            plan.alert_below_percent
+        )
+    })
+}
+
+/// Run, save and log the test of one monitor cycle
+async fn monitor_test(
+    config: &TestConfig,
+) -> Result<modules::types::SpeedTestResult, Box<dyn std::error::Error>> {
+    let result = SpeedTest::new(config.clone())?.run_full_test().await?;
+
+    match HistoryStorage::new() {
+        Ok(storage) => {
+            if let Err(e) = storage.save_result(&result) {
+                eprintln!("Failed to save test result: {}", e);
+            }
+            warn_over_data_budget(config, &storage);
+        }
+        Err(e) => eprintln!("Failed to initialize history storage: {}", e),
+    }
+
+    let below_plan = below_plan_message(config, &result).is_some();
+    println!(
+        "{}  down {:.1} Mbps  up {:.1} Mbps  ping {:.1} ms  {}{}",
+        result
+            .timestamp
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        result.download_mbps,
+        result.upload_mbps,
+        result.ping_ms,
+        result.quality,
+        if below_plan { "  [below plan]" } else { "" }
+    );
+    report_result(config, &result).await;
+
+    Ok(result)
+}
+
+fn install_service(
+    config: &TestConfig,
+    interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = ServiceSpec::new(interval)?;
+
+    // Tests the service would start but the courtesy cap would refuse
+    if config.max_tests_per_hour > 0
+        && interval < Duration::from_secs(3600 / config.max_tests_per_hour as u64)
+    {
+        println!(
+            "{}",
+            format!(
+                "⚠ Testing every {} exceeds the courtesy cap of {} tests per hour, some runs will be skipped",
+                humantime::format_duration(interval),
+                config.max_tests_per_hour
+            )
+            .bright_yellow()
+        );
+    }
+
+    let report = service::install(&spec)?;
+    for file in &report.files {
+        println!("{} {}", "✓ Wrote".bright_green(), file.display());
+    }
+    for command in &report.failed_commands {
+        eprintln!("{} {}", "⚠ Please run manually:".bright_yellow(), command);
+    }
+    println!(
+        "{} every {}",
+        "✓ Monitor service scheduled".bright_green(),
+        humantime::format_duration(interval)
+    );
+
+    Ok(())
+}
+
+/// Collect a bug report and write it to `output`, or print it with `--json`
+fn write_bug_report(
+    log_lines: usize,
+    output: Option<&PathBuf>,
+    json_output: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = BugReport::collect(log_lines);
+    let json = serde_json::to_string_pretty(&report)?;
+
+    if json_output && output.is_none() {
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let path = output.cloned().unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "netrunner-bug-report-{}.json",
+            report.generated_at.format("%Y%m%d-%H%M%S")
+        ))
+    });
+    std::fs::write(&path, json)?;
+
+    println!("{} {}", "✓ Wrote".bright_green(), path.display());
+    for note in &report.notes {
+        println!("  {} {}", "• Not included:".bright_yellow(), note);
+    }
+    println!(
+        "{}",
+        "Secrets, names and your public IP are removed, but please read the file before attaching it."
+            .bright_blue()
+    );
+    Ok(())
+}
+
+/// `telemetry on|off|status`: switch anonymous results on or off, disclosing
+/// exactly what is sent, or show the current state
+fn telemetry_command(
+    config: &TestConfig,
+    action: Option<&str>,
+    configured_url: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let consent = match action {
+        Some("on") => Some(telemetry::set_enabled(true)?),
+        Some("off") => Some(telemetry::set_enabled(false)?),
+        _ => telemetry::load_consent()?,
+    };
+    let enabled = consent.as_ref().is_some_and(|consent| consent.enabled);
+    let do_not_track = telemetry::do_not_track(|key| std::env::var(key).ok());
+    let url = configured_url.unwrap_or(telemetry::COMMUNITY_ENDPOINT);
+    // The latest stored test shows exactly what a sample looks like
+    let preview = if HistoryStorage::is_disabled() {
+        None
+    } else {
+        HistoryStorage::new()
+            .and_then(|storage| storage.get_latest_result())
+            .ok()
+            .flatten()
+            .and_then(|result| TelemetrySample::from_result(&result))
+    };
+
+    if config.json_output {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "enabled": enabled && !do_not_track,
+                "changed_at": consent.map(|consent| consent.changed_at),
+                "do_not_track": do_not_track,
+                "endpoint": url,
+                "sample": preview,
+            }))?
+        );
+        return Ok(());
+    }
+
+    match (&consent, enabled) {
+        (Some(consent), true) => println!(
+            "{} since {}",
+            "✓ Telemetry is on".bright_green(),
+            consent
+                .changed_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        ),
+        (Some(_), false) => println!("{}", "Telemetry is off".bright_blue()),
+        (None, _) => println!(
+            "{}",
+            "Telemetry is off, it has never been switched on".bright_blue()
+        ),
+    }
+    if enabled && do_not_track {
+        println!(
+            "{}",
+            "DO_NOT_TRACK is set, so nothing is sent until it is unset".bright_yellow()
+        );
+    }
+
+    if action == Some("on") || (action != Some("off") && enabled) {
+        println!();
+        println!(
+            "After each completed speed test netrunner sends to {}:",
+            url.bright_cyan()
+        );
+        println!("  • download, upload, ping, jitter, packet loss and quality rating");
+        println!("  • the country geolocation reported, the netrunner version and the OS");
+        println!("  • the hour of the test, rounded down");
+        println!(
+            "{}",
+            "Never sent: IP addresses, ISP, servers, host label, site or any identifier."
+                .bright_green()
+        );
+        if let Some(sample) = &preview {
+            println!();
+            println!("{}", "Your latest test would be sent as:".bold());
+            println!("{}", serde_json::to_string_pretty(sample)?);
+        }
+        println!();
+        println!("Run `netrunner_cli telemetry off` to stop at any time.");
+    } else if action.is_none() || action == Some("status") {
+        println!(
+            "Run `netrunner_cli telemetry on` to share anonymous results for a public dataset."
+        );
+    }
+    Ok(())
+}
+
+/// Check an exported file against its signature, and optionally against the key
+/// it is expected to be signed with
+fn verify_signature(
+    path: &Path,
+    signature_path: Option<&PathBuf>,
+    expected_key: Option<&str>,
+    json_output: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let signature_path = signature_path
+        .cloned()
+        .unwrap_or_else(|| signing::signature_path(path));
+    let signature = signing::verify_file(path, &signature_path)?;
+    let fingerprint = signature.fingerprint();
+
+    // Either form of the key is accepted, with or without the fingerprint's colons
+    if let Some(expected) = expected_key {
+        let expected = expected.trim().replace(':', "").to_ascii_lowercase();
+        if expected != signature.public_key && expected != fingerprint.replace(':', "") {
+            return Err(format!(
+                "{} is signed with key {}, not the expected one",
+                path.display(),
+                fingerprint
+            )
+            .into());
+        }
+    }
+    let own_key = signing::local_public_key().as_deref() == Some(signature.public_key.as_str());
+
+    if json_output {
+        println!(
+            "{}",
+            serde_json::json!({
+                "path": path,
+                "valid": true,
+                "sha256": signature.sha256,
+                "signed_at": signature.signed_at,
+                "public_key": signature.public_key,
+                "fingerprint": fingerprint,
+                "this_machine": own_key,
+            })
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {} is unmodified since it was signed",
+        "✓ Valid signature:".bright_green(),
+        path.display()
+    );
+    println!(
+        "  {} {}",
+        "Signed at:".bright_blue(),
+        signature
+            .signed_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+    println!("  {} {}", "Key:".bright_blue(), fingerprint);
+    if own_key {
+        println!("  {}", "Signed on this machine".bright_blue());
+    } else if expected_key.is_none() {
+        println!(
+            "  {}",
+            "Compare the key with the one the signer shared to know who signed it".bright_yellow()
+        );
+    }
+    Ok(())
+}
+
+/// Table of stored diagnostics runs, newest first, with averages over them
+fn show_diagnostics_history(
+    config: &TestConfig,
+    limit: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let records = HistoryStorage::new()?.get_recent_diagnostics(limit)?;
+
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    let ui = UI::new(config.clone());
+    ui.show_section_header("Diagnostics History")?;
+    if records.is_empty() {
+        println!(
+            "{}",
+            "No diagnostics runs found in history, run --mode diag first.".yellow()
+        );
+        return Ok(());
+    }
+
+    let ms = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |ms| format!("{:.1}", ms));
+    let mut table = prettytable::Table::new();
+    table.set_format(*prettytable::format::consts::FORMAT_BORDERS_ONLY);
+    table.add_row(prettytable::row![bF=>
+        "Date", "DNS (ms)", "Hops", "IPv6", "Gateway (ms)", "Internet (ms)", "Connection"
+    ]);
+    for record in &records {
+        let diagnostics = &record.diagnostics;
+        table.add_row(prettytable::row![
+            record
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            format!("{:.1}", diagnostics.dns_response_time_ms),
+            record.route_length().to_string(),
+            if diagnostics.is_ipv6_available {
+                "yes"
+            } else {
+                "no"
+            },
+            ms(record.gateway_ms()),
+            ms(record.internet_ms()),
+            diagnostics.connection_type.as_deref().unwrap_or("-")
+        ]);
+    }
+    terminal::print_table(&table)?;
+
+    let count = records.len() as f64;
+    println!(
+        "\n{}: {:.1} ms DNS, {:.1} hops, IPv6 in {} of {} runs",
+        "Average".bold(),
+        records
+            .iter()
+            .map(|r| r.diagnostics.dns_response_time_ms)
+            .sum::<f64>()
+            / count,
+        records.iter().map(|r| r.route_length() as f64).sum::<f64>() / count,
+        records
+            .iter()
+            .filter(|r| r.diagnostics.is_ipv6_available)
+            .count(),
+        records.len()
+    );
+    Ok(())
+}
+
+/// Heatmap of average download per weekday and hour, with the hours that stand out
+fn show_congestion(
+    config: &TestConfig,
+    days: Option<u32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let since = days.map(|days| chrono::Utc::now() - chrono::Duration::days(days.into()));
+    let stats = HistoryStorage::new()?.get_congestion_stats(since)?;
+
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let ui = UI::new(config.clone());
+    ui.show_section_header("Congestion by Time of Day")?;
+    if stats.test_count == 0 {
+        println!(
+            "{}",
+            "No results found in history, run a few speed tests at different times first.".yellow()
+        );
+        return Ok(());
+    }
+
+    // Each cell is two columns wide, label every third hour
+    let header: String = (0..24)
+        .map(|hour| {
+            if hour % 3 == 0 {
+                format!("{:02}", hour)
+            } else {
+                "  ".to_string()
+            }
+        })
+        .collect();
+    println!("     {}", header.bright_black());
+    for day in 0..7u8 {
+        let weekday = chrono::Weekday::try_from(day)?;
+        let cells: String = (0..24)
+            .map(|hour| match stats.slot(weekday, hour) {
+                // Glyphs differ too, so the map reads without colour
+                Some(slot) => {
+                    let share = slot.avg_download_mbps / stats.avg_download_mbps.max(f64::EPSILON);
+                    if share >= 0.9 {
+                        "██".bright_green().to_string()
+                    } else if share >= 0.75 {
+                        "▓▓".bright_yellow().to_string()
+                    } else if share >= 0.5 {
+                        "▒▒".yellow().to_string()
+                    } else {
+                        "░░".bright_red().to_string()
+                    }
+                }
+                None => "··".bright_black().to_string(),
+            })
+            .collect();
+        println!("  {} {}", weekday, cells);
+    }
+    println!(
+        "
+  {} ≥90%  {} ≥75%  {} ≥50%  {} <50% of the {:.1} Mbps average  {} no tests",
+        "██".bright_green(),
+        "▓▓".bright_yellow(),
+        "▒▒".yellow(),
+        "░░".bright_red(),
+        stats.avg_download_mbps,
+        "··".bright_black()
+    );
+
+    if let (Some(fastest), Some(slowest)) = (stats.fastest_hour(), stats.slowest_hour()) {
+        println!(
+            "
+{} {:02}:00 averages {:.1} Mbps over {} tests",
+            "Fastest hour:".bold(),
+            fastest.hour,
+            fastest.avg_download_mbps,
+            fastest.test_count
+        );
+        println!(
+            "{} {:02}:00 averages {:.1} Mbps over {} tests, {:.0}% below the average",
+            "Slowest hour:".bold(),
+            slowest.hour,
+            slowest.avg_download_mbps,
+            slowest.test_count,
+            (1.0 - slowest.avg_download_mbps / stats.avg_download_mbps.max(f64::EPSILON)) * 100.0
+        );
+    }
+    println!(
+        "{}",
+        format!(
+            "Based on {} tests in local time. Use --json for the figures behind the map.",
+            stats.test_count
+        )
+        .bright_black()
+    );
+    Ok(())
+}
+
+fn backup_history(config: &TestConfig, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (backup, digest) = HistoryStorage::new()?.backup(path)?;
+
+    if config.json_output {
+        let signature = sign_export(config, path)?;
+        println!(
+            "{}",
+            serde_json::json!({
+                "path": path,
+                "sha256": digest,
+                "results": backup.results.len(),
+                "aggregates": backup.aggregates.len(),
+                "ping_samples": backup.ping_samples.len(),
+                "routes": backup.routes.len(),
+                "signature": signature,
+            })
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {} results to {}",
+        "✓ Backed up".bright_green(),
+        backup.results.len(),
+        path.display()
+    );
+    println!("  {} {}", "SHA-256 of history.json:".bright_blue(), digest);
+    sign_export(config, path)?;
+    Ok(())
+}
+
+/// `history export`: stream the filtered results to a file or stdout
+fn export_history(
+    config: &TestConfig,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = match matches.get_one::<String>("format").unwrap().as_str() {
+        "csv" => ExportFormat::Csv,
+        _ => ExportFormat::Json,
+    };
+    let filter = export_filter(matches);
+
+    let storage = HistoryStorage::new()?;
+    let Some(path) = matches.get_one::<PathBuf>("file") else {
+        return match storage.export(io::stdout().lock(), format, &filter) {
+            Err(e)
+                if e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+            {
+                Ok(())
+            }
+            other => other.map(|_| ()),
+        };
+    };
+    let count = storage.export(std::fs::File::create(path)?, format, &filter)?;
+
+    if config.json_output {
+        println!("{}", serde_json::json!({ "path": path, "results": count }));
+    } else {
+        println!(
+            "{} {} results to {}",
+            "✓ Exported".bright_green(),
+            count,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Which results `history export` writes, from its arguments
+fn export_filter(matches: &ArgMatches) -> ExportFilter {
+    // Whole days in local time, --until including its day
+    let start_of = |date: chrono::NaiveDate| {
+        date.and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .map(|start| start.with_timezone(&chrono::Utc))
+    };
+    ExportFilter {
+        since: matches
+            .get_one::<chrono::NaiveDate>("since")
+            .and_then(|&date| start_of(date)),
+        until: matches
+            .get_one::<chrono::NaiveDate>("until")
+            .and_then(|date| date.succ_opt())
+            .and_then(start_of),
+        tags: matches
+            .get_many::<String>("tag")
+            .map(|tags| tags.cloned().collect())
+            .unwrap_or_default(),
+        quality: matches
+            .get_one::<String>("quality")
+            .map(|quality| match quality.as_str() {
+                "excellent" => ConnectionQuality::Excellent,
+                "good" => ConnectionQuality::Good,
+                "average" => ConnectionQuality::Average,
+                "poor" => ConnectionQuality::Poor,
+                "very-poor" => ConnectionQuality::VeryPoor,
+                _ => ConnectionQuality::Failed,
+            }),
+        // The filter shares its id with the global --server URL, whose default or
+        // NETRUNNER_SERVER would otherwise filter every result out
+        server: matches
+            .get_one::<String>("server")
+            .filter(|_| matches.value_source("server") == Some(ValueSource::CommandLine))
+            .cloned(),
+    }
+}
+
+fn restore_history(config: &TestConfig, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (backup, duplicates) = HistoryStorage::new()?.restore(path)?;
+
+    if config.json_output {
+        println!(
+            "{}",
+            serde_json::json!({
+                "path": path,
+                "created_at": backup.created_at,
+                "results": backup.results.len(),
+                "duplicates": duplicates,
+                "aggregates": backup.aggregates.len(),
+                "ping_samples": backup.ping_samples.len(),
+                "routes": backup.routes.len(),
+            })
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {} results from {} (backed up {} by v{})",
+        "✓ Restored".bright_green(),
+        backup.results.len(),
+        path.display(),
+        backup
+            .created_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M"),
+        backup.netrunner_version
+    );
+    if duplicates > 0 {
+        println!(
+            "  {} of them were already in the history and skipped",
+            duplicates
+        );
+    }
+    Ok(())
+}
+
+fn dedupe_history(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let removed = HistoryStorage::new()?.dedupe_results()?;
+
+    if config.json_output {
+        println!("{}", serde_json::json!({ "removed": removed }));
+    } else if removed == 0 {
+        println!("{}", "✓ No duplicate results found".bright_green());
+    } else {
+        println!(
+            "{} {} duplicate results",
+            "✓ Removed".bright_green(),
+            removed
+        );
+    }
+    Ok(())
+}
+
+fn uninstall_service() -> Result<(), Box<dyn std::error::Error>> {
+    let report = service::uninstall()?;
+    for file in &report.files {
+        println!("{} {}", "✓ Removed".bright_green(), file.display());
+    }
+    for command in &report.failed_commands {
+        eprintln!("{} {}", "⚠ Command failed:".bright_yellow(), command);
+    }
+    println!("{}", "✓ Monitor service removed".bright_green());
+
+    Ok(())
+}
+
+async fn run_port_check(
+    config: &TestConfig,
+    request: &PortCheckRequest,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if request.target.is_empty() {
+        return Err("portcheck mode needs a host, pass --target HOST".into());
+    }
+
+    let report = PortCheckTool::new(config.clone()).run(request).await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+async fn run_ping_monitor(
+    config: &TestConfig,
+    target: Option<&str>,
+    interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let target = target.ok_or("pingmon mode needs a host, pass --target HOST[:PORT]")?;
+    PingMonitor::new(config.clone()).run(target, interval).await
+}
+
+async fn run_lan_scan(
+    config: &TestConfig,
+    subnet: Option<Ipv4Subnet>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = LanScanner::new(config.clone()).run(subnet).await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+async fn run_port_map(
+    config: &TestConfig,
+    map_port: Option<u16>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = PortMapTool::new(config.clone()).run(map_port).await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+/// Time latency to the router, a LAN host and the internet idle and under load
+async fn run_bloat_check(
+    config: &TestConfig,
+    lan_host: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let lan_host = lan_host.ok_or(
+        "bloatcheck mode needs a second LAN host, ideally wired to the router, pass --target HOST[:PORT]",
+    )?;
+    let report = BloatCheckTool::new(config.clone()).run(lan_host).await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+async fn run_cloud_check(
+    config: &TestConfig,
+    fetch: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = CloudCheckTool::new(config.clone()).run(fetch).await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+/// Fetch one URL with a timing breakdown; fails when the endpoint is slow or errors
+/// so the mode can be used as a health check in scripts
+async fn run_http_check(
+    config: &TestConfig,
+    url: Option<&reqwest::Url>,
+    thresholds: HttpThresholds,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = url.ok_or("http-check mode needs an endpoint, pass --url URL")?;
+
+    let report = HttpCheckTool::new(config.clone())
+        .run(url, thresholds)
+        .await?;
+    if config.json_output {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    if report.is_healthy() {
+        Ok(())
+    } else {
+        Err(format!("{} is unhealthy", report.url).into())
+    }
+}
+
+/// Run spaced speed tests plus diagnostics and write them out as an evidence bundle
+async fn run_evidence(
+    config: &TestConfig,
+    runs: u32,
+    interval: Duration,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !config.json_output {
+        let ui = UI::new(config.clone());
+        ui.show_section_header("Collecting ISP Evidence")?;
+        println!(
+            "Running {} speed tests, {} apart, followed by network diagnostics.",
+            runs,
+            humantime::format_duration(interval)
+        );
+        if config.plan.is_none() {
+            println!(
+                "{}",
+                "ℹ No advertised plan configured, pass --plan DOWN/UP or set [plan] in config.toml \
+                 to include plan attainment"
+                    .bright_blue()
+            );
+        }
+        println!();
+    }
+
+    let mut results = Vec::new();
+    for run in 1..=runs {
+        if run > 1 {
+            if !config.json_output {
+                println!(
+                    "{} {}",
+                    "⏳ Next test in".bright_blue(),
+                    humantime::format_duration(interval)
+                        .to_string()
+                        .bright_cyan()
+                );
+            }
+            tokio::time::sleep(interval).await;
+        }
+
+        if !config.json_output {
+            println!(
+                "{}",
+                format!("Test {} of {}", run, runs).bright_magenta().bold()
+            );
+        }
+        enforce_courtesy_cap(config)?;
+        let result = SpeedTest::new(config.clone())?.run_full_test().await?;
+        // Opened per run, not held through the pauses, so other runs can save too
+        if let Err(e) = HistoryStorage::new().and_then(|storage| storage.save_result(&result)) {
+            eprintln!("Failed to save test result: {}", e);
+        }
+        report_result(config, &result).await;
+        results.push(result);
+    }
+
+    // Diagnostics help rule out problems on the local network, but a bundle
+    // without them is still useful
+    let diagnostics = match NetworkDiagnosticsTool::new(config.clone())
+        .run_diagnostics()
+        .await
+    {
+        Ok(diagnostics) => Some(diagnostics),
+        Err(e) => {
+            eprintln!("Failed to collect diagnostics: {}", e);
+            None
+        }
+    };
+
+    let bundle = EvidenceBundle::new(results, diagnostics, config.plan);
+    let path = output.unwrap_or_else(|| evidence::default_output_path(bundle.generated_at));
+    let digest = bundle.write(&path)?;
+
+    if config.json_output {
+        let signature = sign_export(config, &path)?;
+        println!(
+            "{}",
+            serde_json::json!({
+                "path": path,
+                "sha256": digest,
+                "runs": bundle.results.len(),
+                "download_percent_of_plan": bundle.summary.download_percent_of_plan,
+                "upload_percent_of_plan": bundle.summary.upload_percent_of_plan,
+                "signature": signature,
+            })
+        );
+        return Ok(());
+    }
+
+    println!();
+    println!(
+        "{} {}",
+        "✓ Evidence bundle saved to".bright_green(),
+        path.display().to_string().bright_cyan()
+    );
+    println!("{}: {}", "SHA-256".bold(), digest);
+    if let (Some(down), Some(up)) = (
+        bundle.summary.download_percent_of_plan,
+        bundle.summary.upload_percent_of_plan,
+    ) {
+        println!(
+            "{}: download {:.0}%, upload {:.0}% of advertised speed (median)",
+            "Plan attainment".bold(),
+            down,
+            up
+        );
+    }
+    sign_export(config, &path)?;
+
+    Ok(())
+}
+
+async fn show_animation_showcase(config: &TestConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let ui = UI::new(config.clone());
+
+    ui.clear_screen()?;
+    ui.show_section_header("Animation Showcase")?;
+
+    println!(
+        "{}",
+        "Demonstrating NetRunner's cyberpunk animations...".bright_cyan()
+    );
+    println!();
+
+    // Show typing effect
     println!("{}", "1. Typing Effect:".bright_yellow().bold());
     ui.show_typing_effect("⟨⟨⟨ NEURAL INTERFACE ACTIVATED ⟩⟩⟩")?;
     tokio::time::sleep(Duration::from_millis(1000)).await;
@@ -615,3 +3607,38 @@ async fn show_animation_showcase(config: &TestConfig) -> Result<(), Box<dyn std:
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modules::types::SpeedTestResult;
+
+    /// The arguments `history export` got on the command line
+    fn export_matches(args: &[&str]) -> ArgMatches {
+        let matches = build_cli()
+            .try_get_matches_from(["netrunner_cli", "history", "export"].iter().chain(args))
+            .unwrap();
+        let (_, history) = matches.subcommand().unwrap();
+        let (_, export) = history.subcommand().unwrap();
+        export.clone()
+    }
+
+    #[test]
+    fn test_export_filter_server() {
+        let result = SpeedTestResult {
+            server_location: "Berlin, Germany".to_string(),
+            ..Default::default()
+        };
+
+        // The global --server URL's default is not a filter
+        let unfiltered = export_filter(&export_matches(&[]));
+        assert_eq!(unfiltered.server, None);
+        assert!(unfiltered.matches(&result));
+
+        let berlin = export_filter(&export_matches(&["--server", "Berlin"]));
+        assert_eq!(berlin.server.as_deref(), Some("Berlin"));
+        assert!(berlin.matches(&result));
+        let paris = export_filter(&export_matches(&["--server", "Paris"]));
+        assert!(!paris.matches(&result));
+    }
+}
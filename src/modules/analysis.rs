@@ -0,0 +1,693 @@
+//! Heuristic root-cause analysis: weighs the figures of a speed test, the
+//! diagnostics next to it and recent history into ranked probable causes.
+
+use chrono::{Local, Timelike};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::modules::types::{
+    median, Bottleneck, ConnectionQuality, Correlation, FullAnalysisResult, NetworkDiagnostics,
+    OtherTraffic, SpeedTestResult,
+};
+
+/// History results the analysis looks back on
+pub const HISTORY_RESULTS: usize = 200;
+
+/// Causes scoring below this are left out
+const MIN_SCORE: f64 = 0.2;
+/// History results needed before time-of-day patterns count
+const MIN_HISTORY: usize = 5;
+/// Share of the usual download below which a result counts as slow
+const SLOW_SHARE: f64 = 0.75;
+/// Hours either side of now that make up the current time-of-day window
+const WINDOW_HOURS: u32 = 1;
+/// Spread between the fastest and the 95th percentile ping that points at bufferbloat
+const BLOAT_SPREAD_MS: f64 = 100.0;
+/// Smaller spread that still hints at queueing
+const QUEUE_SPREAD_MS: f64 = 30.0;
+/// Jitter in the speed test above this suggests buffers filling up
+const BLOAT_JITTER_MS: f64 = 30.0;
+
+/// What a slow or unsteady connection is most likely down to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum Cause {
+    #[strum(to_string = "Wi-Fi signal")]
+    WifiSignal,
+    #[strum(to_string = "Bufferbloat")]
+    Bufferbloat,
+    #[strum(to_string = "Slow DNS")]
+    Dns,
+    #[strum(to_string = "ISP congestion")]
+    IspCongestion,
+    #[strum(to_string = "Routing change")]
+    RoutingChange,
+    #[strum(to_string = "Other devices")]
+    OtherDevices,
+}
+
+impl Cause {
+    /// What to try when this is the cause
+    pub fn advice(&self) -> &'static str {
+        match self {
+            Cause::WifiSignal => "Move closer to the access point, switch to 5 GHz or use Ethernet, or restart the router.",
+            Cause::Bufferbloat => "Latency climbs while the line is busy. Enable SQM or smart queue management on the router, or cap its speed slightly below the plan.",
+            Cause::Dns => "Name lookups are slow. Switch to a faster resolver such as Cloudflare (1.1.1.1) or Google (8.8.8.8).",
+            Cause::IspCongestion => "The delay is on your line or at your ISP, so local changes will not help. Keep testing at different times and report the pattern to your ISP.",
+            Cause::RoutingChange => "Your ISP now routes traffic differently. If speeds changed around then, report the new route to your ISP.",
+            Cause::OtherDevices => "Other devices used the connection during the test. Pause downloads, backups and streams elsewhere and test again before blaming the line.",
+        }
+    }
+}
+
+/// How sure the analysis is about a cause
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    #[strum(to_string = "low")]
+    Low,
+    #[strum(to_string = "medium")]
+    Medium,
+    #[strum(to_string = "high")]
+    High,
+}
+
+impl Confidence {
+    fn from_score(score: f64) -> Self {
+        if score >= 0.7 {
+            Confidence::High
+        } else if score >= 0.4 {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+/// A cause with how strongly the figures point at it and which ones do
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProbableCause {
+    pub cause: Cause,
+    pub confidence: Confidence,
+    /// Weight of the evidence, 0 to 1
+    pub score: f64,
+    pub evidence: Vec<String>,
+}
+
+/// Evidence collected for one cause
+#[derive(Default)]
+struct Findings {
+    score: f64,
+    evidence: Vec<String>,
+}
+
+impl Findings {
+    fn add(&mut self, weight: f64, evidence: String) {
+        self.score += weight;
+        self.evidence.push(evidence);
+    }
+}
+
+/// Ranked probable causes of a full analysis, most likely first
+pub fn diagnose(analysis: &FullAnalysisResult, history: &[SpeedTestResult]) -> Vec<ProbableCause> {
+    rank(
+        Some(&analysis.speed),
+        &analysis.diagnostics,
+        &analysis.correlations,
+        history,
+    )
+}
+
+/// Ranked probable causes from diagnostics alone, when no speed test ran alongside
+pub fn diagnose_network(
+    diagnostics: &NetworkDiagnostics,
+    history: &[SpeedTestResult],
+) -> Vec<ProbableCause> {
+    rank(None, diagnostics, &[], history)
+}
+
+fn rank(
+    speed: Option<&SpeedTestResult>,
+    diagnostics: &NetworkDiagnostics,
+    correlations: &[Correlation],
+    history: &[SpeedTestResult],
+) -> Vec<ProbableCause> {
+    // Failed runs say nothing about the usual speed, and neither do runs that
+    // shared the line with other devices
+    let history: Vec<&SpeedTestResult> = history
+        .iter()
+        .filter(|result| result.quality != ConnectionQuality::Failed && !result.shared_line())
+        .collect();
+
+    let mut causes: Vec<ProbableCause> = [
+        (Cause::WifiSignal, wifi_signal(speed, diagnostics)),
+        (Cause::Bufferbloat, bufferbloat(speed, diagnostics)),
+        (Cause::Dns, dns(diagnostics, correlations)),
+        (
+            Cause::IspCongestion,
+            isp_congestion(speed, diagnostics, correlations, &history),
+        ),
+        (
+            Cause::RoutingChange,
+            routing_change(speed, diagnostics, &history),
+        ),
+        (Cause::OtherDevices, other_devices(speed)),
+    ]
+    .into_iter()
+    .filter(|(_, findings)| findings.score >= MIN_SCORE)
+    .map(|(cause, findings)| {
+        let score = findings.score.min(1.0);
+        ProbableCause {
+            cause,
+            confidence: Confidence::from_score(score),
+            score,
+            evidence: findings.evidence,
+        }
+    })
+    .collect();
+
+    causes.sort_by(|a, b| b.score.total_cmp(&a.score));
+    causes
+}
+
+fn wifi_signal(speed: Option<&SpeedTestResult>, diagnostics: &NetworkDiagnostics) -> Findings {
+    let mut findings = Findings::default();
+    let kind = diagnostics
+        .connection_type
+        .as_deref()
+        .map(str::to_ascii_lowercase);
+    let wireless = kind
+        .as_deref()
+        .is_some_and(|kind| kind.contains("wireless") || kind.contains("wi-fi"));
+    // A known wired link rules the radio out, an unknown one leaves it open
+    if kind.is_some() && !wireless {
+        return findings;
+    }
+    if wireless {
+        findings.add(0.2, "Connected over Wi-Fi".to_string());
+    }
+
+    if diagnostics.latency_baseline.bottleneck() == Some(Bottleneck::Home) {
+        let gateway = diagnostics.latency_baseline.gateway.as_ref();
+        findings.add(
+            0.4,
+            format!(
+                "The router answers in {} with {:.1}ms jitter and {} lost probes",
+                gateway
+                    .and_then(|link| link.median_ms)
+                    .map_or("-".to_string(), |ms| format!("{:.1}ms", ms)),
+                gateway.map_or(0.0, |link| link.jitter_ms),
+                gateway.map_or(0, |link| link.lost)
+            ),
+        );
+    }
+
+    if let Some(speed) = speed.filter(|speed| {
+        wireless
+            && !speed.shared_line()
+            && speed.download_mbps < FullAnalysisResult::SLOW_WIRELESS_MBPS
+    }) {
+        findings.add(
+            0.2,
+            format!("Download of {:.1} Mbps over Wi-Fi", speed.download_mbps),
+        );
+    }
+
+    findings
+}
+
+fn bufferbloat(speed: Option<&SpeedTestResult>, diagnostics: &NetworkDiagnostics) -> Findings {
+    let mut findings = Findings::default();
+    let Some(speed) = speed else {
+        return findings;
+    };
+
+    if let Some(stats) = &speed.latency_stats {
+        let spread = stats.p95_ms - stats.min_ms;
+        let weight = if spread >= BLOAT_SPREAD_MS {
+            0.5
+        } else if spread >= QUEUE_SPREAD_MS {
+            0.25
+        } else {
+            0.0
+        };
+        if weight > 0.0 {
+            findings.add(
+                weight,
+                format!(
+                    "Ping ranged from {:.1}ms to {:.1}ms at the 95th percentile",
+                    stats.min_ms, stats.p95_ms
+                ),
+            );
+        }
+    }
+
+    if speed.jitter_ms > BLOAT_JITTER_MS {
+        findings.add(
+            0.2,
+            format!("Jitter of {:.1}ms during the test", speed.jitter_ms),
+        );
+    }
+
+    // Idle latency from the diagnostics against the ping around the speed test
+    if let Some(idle_ms) = diagnostics
+        .latency_baseline
+        .internet
+        .as_ref()
+        .and_then(|link| link.median_ms)
+        .filter(|idle_ms| speed.ping_ms > idle_ms * 2.0 + 20.0)
+    {
+        findings.add(
+            0.2,
+            format!(
+                "Ping of {:.1}ms in the speed test against {:.1}ms idle",
+                speed.ping_ms, idle_ms
+            ),
+        );
+    }
+
+    findings
+}
+
+fn dns(diagnostics: &NetworkDiagnostics, correlations: &[Correlation]) -> Findings {
+    let mut findings = Findings::default();
+    let lookup_ms = diagnostics.dns_response_time_ms;
+    if lookup_ms <= FullAnalysisResult::SLOW_DNS_MS {
+        return findings;
+    }
+
+    findings.add(0.4, format!("Name lookups took {:.1}ms", lookup_ms));
+    if lookup_ms > FullAnalysisResult::SLOW_DNS_MS * 3.0 {
+        findings.score += 0.2;
+    }
+    if let Some(fastest) = diagnostics
+        .resolver_benchmarks
+        .iter()
+        .filter(|benchmark| benchmark.median_ms.is_some_and(|ms| ms < lookup_ms / 2.0))
+        .min_by(|a, b| {
+            a.median_ms
+                .unwrap_or(f64::MAX)
+                .total_cmp(&b.median_ms.unwrap_or(f64::MAX))
+        })
+    {
+        findings.add(
+            0.2,
+            format!(
+                "{} over {} answered in {:.1}ms",
+                fastest.provider,
+                fastest.transport,
+                fastest.median_ms.unwrap_or_default()
+            ),
+        );
+    }
+    if correlations.contains(&Correlation::SlowResolver) {
+        findings.add(0.2, Correlation::SlowResolver.to_string());
+    }
+
+    findings
+}
+
+fn isp_congestion(
+    speed: Option<&SpeedTestResult>,
+    diagnostics: &NetworkDiagnostics,
+    correlations: &[Correlation],
+    history: &[&SpeedTestResult],
+) -> Findings {
+    let mut findings = Findings::default();
+
+    if diagnostics.latency_baseline.bottleneck() == Some(Bottleneck::Upstream) {
+        findings.add(
+            0.3,
+            "The router answers quickly but the internet beyond it does not".to_string(),
+        );
+    }
+    if correlations.contains(&Correlation::UpstreamLoss) {
+        findings.add(0.1, Correlation::UpstreamLoss.to_string());
+    }
+
+    if history.len() < MIN_HISTORY {
+        return findings;
+    }
+    let usual = median(
+        &history
+            .iter()
+            .map(|result| result.download_mbps)
+            .collect::<Vec<_>>(),
+    );
+
+    // Results from around this hour of day against all of them
+    let hour = speed
+        .map_or_else(Local::now, |speed| speed.timestamp.with_timezone(&Local))
+        .hour();
+    let in_window: Vec<f64> = history
+        .iter()
+        .filter(|result| {
+            let distance = result.timestamp.with_timezone(&Local).hour().abs_diff(hour);
+            distance.min(24 - distance) <= WINDOW_HOURS
+        })
+        .map(|result| result.download_mbps)
+        .collect();
+    if in_window.len() >= 2 {
+        let window = median(&in_window);
+        if window < usual * SLOW_SHARE {
+            findings.add(
+                0.4,
+                format!(
+                    "Tests around {:02}:00 average {:.1} Mbps against {:.1} Mbps overall",
+                    hour, window, usual
+                ),
+            );
+        }
+    }
+
+    if let Some(speed) =
+        speed.filter(|speed| !speed.shared_line() && speed.download_mbps < usual * SLOW_SHARE)
+    {
+        findings.add(
+            0.3,
+            format!(
+                "Download of {:.1} Mbps against a usual {:.1} Mbps",
+                speed.download_mbps, usual
+            ),
+        );
+    }
+
+    findings
+}
+
+fn routing_change(
+    speed: Option<&SpeedTestResult>,
+    diagnostics: &NetworkDiagnostics,
+    history: &[&SpeedTestResult],
+) -> Findings {
+    let mut findings = Findings::default();
+    let changed: Vec<_> = diagnostics
+        .routes
+        .iter()
+        .filter_map(|route| route.change.as_ref().map(|change| (route, change)))
+        .collect();
+    if changed.is_empty() {
+        return findings;
+    }
+
+    for (route, change) in &changed {
+        findings.add(
+            0.4 / changed.len() as f64,
+            format!(
+                "The path to {} changed at {} hop(s) since {}",
+                route.target,
+                change.changed_hops.len(),
+                change
+                    .previous_timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+            ),
+        );
+    }
+
+    // A route change matters when the speed moved with it
+    if history.len() >= MIN_HISTORY {
+        let usual = median(
+            &history
+                .iter()
+                .map(|result| result.download_mbps)
+                .collect::<Vec<_>>(),
+        );
+        if let Some(speed) = speed.filter(|speed| {
+            !speed.shared_line()
+                && usual > 0.0
+                && (speed.download_mbps - usual).abs() / usual > 1.0 - SLOW_SHARE
+        }) {
+            findings.add(
+                0.3,
+                format!(
+                    "Download of {:.1} Mbps differs from a usual {:.1} Mbps",
+                    speed.download_mbps, usual
+                ),
+            );
+        }
+    }
+
+    findings
+}
+
+/// Other devices' WAN traffic during the test, which the slow figures are then
+/// down to rather than the line
+fn other_devices(speed: Option<&SpeedTestResult>) -> Findings {
+    let mut findings = Findings::default();
+    let Some((speed, other)) = speed
+        .filter(|speed| speed.shared_line())
+        .and_then(|speed| speed.other_traffic.as_ref().map(|other| (speed, other)))
+    else {
+        return findings;
+    };
+
+    for (direction, other_mbps, test_mbps) in [
+        ("download", other.download_mbps, speed.download_mbps),
+        ("upload", other.upload_mbps, speed.upload_mbps),
+    ] {
+        let Some(other_mbps) = other_mbps.filter(|&mbps| OtherTraffic::took_share(mbps, test_mbps))
+        else {
+            continue;
+        };
+        // Weighed by the share of the line the others took
+        let share = other_mbps / (other_mbps + test_mbps);
+        findings.add(
+            0.3 + 0.5 * share,
+            format!(
+                "Other traffic of {:.1} Mbps on the WAN during the {} of {:.1} Mbps",
+                other_mbps, direction, test_mbps
+            ),
+        );
+    }
+    if let Some(devices) = other.lan_devices.filter(|&devices| devices > 0) {
+        findings
+            .evidence
+            .push(format!("{} other devices seen on the LAN", devices));
+    }
+
+    findings
+}
+
+/// Ranked causes with the evidence behind them and what to try
+pub fn show_causes(causes: &[ProbableCause]) {
+    if causes.is_empty() {
+        return;
+    }
+    println!(
+        "\n{}",
+        " 🎯 PROBABLE CAUSES 🎯 ".on_bright_blue().white().bold()
+    );
+    for (rank, cause) in causes.iter().enumerate() {
+        let confidence = match cause.confidence {
+            Confidence::High => format!("{} confidence", cause.confidence).bright_red(),
+            Confidence::Medium => format!("{} confidence", cause.confidence).bright_yellow(),
+            Confidence::Low => format!("{} confidence", cause.confidence).bright_blue(),
+        };
+        println!(
+            "  {}. {} ({})",
+            rank + 1,
+            cause.cause.to_string().bold(),
+            confidence
+        );
+        for evidence in &cause.evidence {
+            println!("     {} {}", "•".bright_black(), evidence.dimmed());
+        }
+        println!("     {} {}", "→".bright_green(), cause.cause.advice());
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::{
+        DnsSecurityCheck, LatencyBaseline, LatencyStats, LinkLatency, RouteChange, TracedRoute,
+    };
+    use chrono::{Duration, Utc};
+    use std::net::IpAddr;
+
+    fn diagnostics(kind: &str, gateway_probes: &[Option<f64>]) -> NetworkDiagnostics {
+        NetworkDiagnostics {
+            gateway_ip: None,
+            dns_servers: vec![],
+            dns_response_time_ms: 20.0,
+            dns_lookups: vec![],
+            dns_records: vec![],
+            route_hops: vec![],
+            is_ipv6_available: true,
+            connection_type: Some(kind.to_string()),
+            network_interface: None,
+            tls_checks: vec![],
+            resolver_benchmarks: vec![],
+            dns_security: DnsSecurityCheck::default(),
+            routes: vec![],
+            server_networks: vec![],
+            latency_baseline: LatencyBaseline {
+                gateway: Some(LinkLatency::from_probes(
+                    IpAddr::from([192, 168, 1, 1]),
+                    80,
+                    gateway_probes,
+                )),
+                internet: None,
+            },
+            clock_check: None,
+            happy_eyeballs: vec![],
+            windows_stack: None,
+        }
+    }
+
+    fn speed(download_mbps: f64, ping_ms: f64) -> SpeedTestResult {
+        SpeedTestResult {
+            download_mbps,
+            ping_ms,
+            quality: ConnectionQuality::Good,
+            ..Default::default()
+        }
+    }
+
+    const HEALTHY_GATEWAY: [Option<f64>; 2] = [Some(1.0), Some(1.5)];
+
+    #[test]
+    fn test_healthy_connection_has_no_causes() {
+        let analysis = FullAnalysisResult::new(
+            speed(300.0, 12.0),
+            diagnostics("Wired (Ethernet)", &HEALTHY_GATEWAY),
+        );
+        assert!(diagnose(&analysis, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_causes_are_ranked() {
+        let mut speed = speed(12.0, 80.0);
+        speed.latency_stats = LatencyStats::from_samples(&[20.0, 25.0, 180.0, 200.0]);
+        let mut diagnostics = diagnostics("Wireless (Wi-Fi)", &[Some(40.0), Some(90.0)]);
+        diagnostics.dns_response_time_ms = 150.0;
+        let analysis = FullAnalysisResult::new(speed, diagnostics);
+
+        let causes = diagnose(&analysis, &[]);
+        let ranked: Vec<Cause> = causes.iter().map(|cause| cause.cause).collect();
+        assert_eq!(
+            ranked,
+            vec![Cause::WifiSignal, Cause::Dns, Cause::Bufferbloat]
+        );
+        assert_eq!(causes[0].confidence, Confidence::High);
+        assert_eq!(causes[0].evidence.len(), 3);
+        assert!(causes.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn test_wired_link_rules_out_wifi() {
+        let causes = diagnose_network(
+            &diagnostics("Wired (Ethernet)", &[Some(40.0), Some(90.0)]),
+            &[],
+        );
+        assert!(causes.iter().all(|cause| cause.cause != Cause::WifiSignal));
+    }
+
+    #[test]
+    fn test_congestion_window_from_history() {
+        let now = Utc::now();
+        // Slow around this hour, fast twelve hours away
+        let history: Vec<SpeedTestResult> = (0..6)
+            .map(|day| SpeedTestResult {
+                timestamp: now - Duration::days(day + 1),
+                ..speed(40.0, 15.0)
+            })
+            .chain((0..6).map(|day| SpeedTestResult {
+                timestamp: now - Duration::days(day + 1) - Duration::hours(12),
+                ..speed(300.0, 15.0)
+            }))
+            .chain(std::iter::once(SpeedTestResult {
+                quality: ConnectionQuality::Failed,
+                ..speed(0.0, 0.0)
+            }))
+            .collect();
+        let analysis = FullAnalysisResult::new(
+            SpeedTestResult {
+                timestamp: now,
+                ..speed(35.0, 15.0)
+            },
+            diagnostics("Wired (Ethernet)", &HEALTHY_GATEWAY),
+        );
+
+        let causes = diagnose(&analysis, &history);
+        assert_eq!(causes.len(), 1);
+        assert_eq!(causes[0].cause, Cause::IspCongestion);
+        assert_eq!(causes[0].confidence, Confidence::High);
+
+        // Too little history to see a pattern
+        assert!(diagnose(&analysis, &history[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_routing_change_with_speed_shift() {
+        let now = Utc::now();
+        let mut diagnostics = diagnostics("Wired (Ethernet)", &HEALTHY_GATEWAY);
+        diagnostics.routes.push(TracedRoute {
+            target: "8.8.8.8".to_string(),
+            hops: vec![],
+            change: Some(RouteChange {
+                previous_timestamp: now - Duration::days(1),
+                previous_hops: vec![],
+                changed_hops: vec![3, 4],
+            }),
+        });
+
+        let route_only = diagnose_network(&diagnostics, &[]);
+        assert_eq!(route_only[0].cause, Cause::RoutingChange);
+        assert_eq!(route_only[0].confidence, Confidence::Medium);
+
+        let history: Vec<SpeedTestResult> = (0..5)
+            .map(|hour| SpeedTestResult {
+                timestamp: now - Duration::hours(hour * 5 + 3),
+                ..speed(200.0, 15.0)
+            })
+            .collect();
+        let analysis = FullAnalysisResult::new(
+            SpeedTestResult {
+                timestamp: now,
+                ..speed(400.0, 15.0)
+            },
+            diagnostics,
+        );
+        let causes = diagnose(&analysis, &history);
+        assert_eq!(causes[0].cause, Cause::RoutingChange);
+        assert_eq!(causes[0].confidence, Confidence::High);
+    }
+
+    #[test]
+    fn test_other_devices_explain_slow_download() {
+        let now = Utc::now();
+        let history: Vec<SpeedTestResult> = (0..6)
+            .map(|day| SpeedTestResult {
+                timestamp: now - Duration::days(day + 1),
+                ..speed(300.0, 15.0)
+            })
+            .collect();
+        let analysis = FullAnalysisResult::new(
+            SpeedTestResult {
+                timestamp: now,
+                other_traffic: Some(OtherTraffic {
+                    download_mbps: Some(180.0),
+                    upload_mbps: Some(1.0),
+                    lan_devices: Some(4),
+                }),
+                ..speed(110.0, 15.0)
+            },
+            diagnostics("Wired (Ethernet)", &HEALTHY_GATEWAY),
+        );
+
+        let causes = diagnose(&analysis, &history);
+        assert_eq!(causes.len(), 1);
+        assert_eq!(causes[0].cause, Cause::OtherDevices);
+        assert_eq!(causes[0].evidence.len(), 2);
+
+        // The same speed without other traffic is a slow line
+        let mut alone = analysis.clone();
+        alone.speed.other_traffic = None;
+        let causes = diagnose(&alone, &history);
+        assert_eq!(causes[0].cause, Cause::IspCongestion);
+    }
+}
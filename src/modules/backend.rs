@@ -0,0 +1,376 @@
+//! Test Backends
+//!
+//! Every kind of speed test server asks for the same three things under its own
+//! URLs: a download of roughly N bytes, a sink for uploaded bytes and a cheap
+//! request to time. [`TestProvider`] hands those out, one implementation per
+//! [`ServerProtocol`], so the transfer engine only ever asks a server's backend
+//! for its requests and a new kind of server is a new implementation here.
+//!
+//! Measurement Lab servers are the exception: ndt7 runs whole transfers over a
+//! WebSocket and reports the server's own counters, so `ndt7` drives them itself.
+
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder};
+
+use crate::modules::fast;
+use crate::modules::types::{ServerProtocol, TestServer};
+
+/// The requests one kind of server answers
+pub trait TestProvider: Send + Sync {
+    /// URL answering with roughly `bytes` of data
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String;
+
+    /// POST accepting and discarding whatever body the caller attaches
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder;
+
+    /// Request answered quickly, timed by latency probes up to the response headers
+    fn latency_probe(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.head(&server.url)
+    }
+
+    /// `wss://` URL of a WebSocket the server keeps open, None when it has none
+    fn websocket_url(&self, _server: &TestServer) -> Option<String> {
+        None
+    }
+
+    /// When the server says it received a latency probe, in Unix milliseconds of
+    /// its clock, from the answer's headers. None for servers that don't say.
+    fn server_clock(&self, _headers: &HeaderMap) -> Option<f64> {
+        None
+    }
+}
+
+/// Backend serving `protocol`
+pub fn for_protocol(protocol: ServerProtocol) -> &'static dyn TestProvider {
+    match protocol {
+        ServerProtocol::Cloudflare => &Cloudflare,
+        ServerProtocol::LibreSpeed => &LibreSpeed,
+        ServerProtocol::Fast => &Fast,
+        ServerProtocol::Ookla => &Ookla,
+        ServerProtocol::Custom => &Custom,
+    }
+}
+
+fn base(server: &TestServer) -> &str {
+    server.url.trim_end_matches('/')
+}
+
+/// `__down?bytes=N` and `__up`, as served by speed.cloudflare.com and its clones
+struct Cloudflare;
+
+impl TestProvider for Cloudflare {
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String {
+        format!("{}/__down?bytes={}", base(server), bytes)
+    }
+
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.post(format!("{}/__up", base(server)))
+    }
+
+    // An empty download, as speed.cloudflare.com times its own latency
+    fn latency_probe(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.get(format!("{}/__down?bytes=0", base(server)))
+    }
+
+    fn server_clock(&self, headers: &HeaderMap) -> Option<f64> {
+        headers
+            .get("cf-meta-request-time")?
+            .to_str()
+            .ok()?
+            .parse::<u64>()
+            .ok()
+            .map(|millis| millis as f64)
+    }
+}
+
+/// LibreSpeed's garbage.php serves whole chunks of this size
+const LIBRESPEED_CHUNK_BYTES: u64 = 1024 * 1024;
+
+/// `garbage.php` and `empty.php` of a LibreSpeed backend
+struct LibreSpeed;
+
+impl TestProvider for LibreSpeed {
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String {
+        let chunks = bytes.div_ceil(LIBRESPEED_CHUNK_BYTES).clamp(1, 1024);
+        format!("{}/garbage.php?ckSize={}", base(server), chunks)
+    }
+
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.post(format!("{}/empty.php", base(server)))
+    }
+}
+
+/// Signed URLs of Netflix caches, see `fast`
+struct Fast;
+
+impl TestProvider for Fast {
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String {
+        let bytes = bytes.clamp(1, fast::MAX_RANGE_BYTES);
+        fast::range_url(&server.url, bytes - 1)
+    }
+
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.post(fast::range_url(&server.url, 0))
+    }
+
+    // The bare URL of a cache is no file, the empty range is
+    fn latency_probe(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.head(fast::range_url(&server.url, 0))
+    }
+}
+
+/// HTTP endpoints of Ookla's server software behind speedtest.net, on the port
+/// the server list names
+struct Ookla;
+
+impl TestProvider for Ookla {
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String {
+        format!("{}/download?size={}", base(server), bytes)
+    }
+
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.post(format!("{}/upload", base(server)))
+    }
+
+    // The server answers HEAD only for its files, `/hello` is its greeting
+    fn latency_probe(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        client.get(format!("{}/hello", base(server)))
+    }
+
+    // The speedtest.net web client runs its tests over this socket
+    fn websocket_url(&self, server: &TestServer) -> Option<String> {
+        websocket_scheme(&format!("{}/ws", base(server)))
+    }
+}
+
+/// Paths from the server's `[servers.endpoints]` table in a server list
+struct Custom;
+
+impl Custom {
+    /// `path` below the server's URL, unless it is a URL of its own
+    fn url(server: &TestServer, path: &str) -> String {
+        if path.contains("://") {
+            path.to_string()
+        } else {
+            format!("{}/{}", base(server), path.trim_start_matches('/'))
+        }
+    }
+}
+
+impl TestProvider for Custom {
+    fn download_url(&self, server: &TestServer, bytes: u64) -> String {
+        match &server.endpoints {
+            Some(endpoints) => Self::url(
+                server,
+                &endpoints.download.replace("{bytes}", &bytes.to_string()),
+            ),
+            None => Cloudflare.download_url(server, bytes),
+        }
+    }
+
+    fn upload_request(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        match &server.endpoints {
+            Some(endpoints) => client.post(Self::url(server, &endpoints.upload)),
+            None => Cloudflare.upload_request(client, server),
+        }
+    }
+
+    fn latency_probe(&self, client: &Client, server: &TestServer) -> RequestBuilder {
+        match server.endpoints.as_ref().and_then(|e| e.latency.as_deref()) {
+            Some(path) => client.head(Self::url(server, path)),
+            None => client.head(&server.url),
+        }
+    }
+
+    fn websocket_url(&self, server: &TestServer) -> Option<String> {
+        let path = server.endpoints.as_ref()?.websocket.as_deref()?;
+        websocket_scheme(&Self::url(server, path))
+    }
+}
+
+/// `url` with `https` turned into `wss`, None for plain HTTP. Sockets are only
+/// opened over TLS.
+fn websocket_scheme(url: &str) -> Option<String> {
+    if url.starts_with("wss://") {
+        return Some(url.to_string());
+    }
+    url.strip_prefix("https://")
+        .map(|rest| format!("wss://{}", rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::{CustomEndpoints, ServerCapabilities, ServerProvider, SpeedPhase};
+
+    fn server(protocol: ServerProtocol) -> TestServer {
+        TestServer {
+            name: "HQ".to_string(),
+            url: "https://speed.corp.example/".to_string(),
+            location: "Frankfurt".to_string(),
+            distance_km: None,
+            latency_ms: None,
+            provider: ServerProvider::SelfHosted,
+            protocol,
+            capabilities: ServerCapabilities {
+                supports_download: true,
+                supports_upload: false,
+                supports_latency: true,
+                max_test_size_mb: 2000,
+                geographic_weight: 1.0,
+            },
+            quality_score: None,
+            country_code: None,
+            city: None,
+            is_backup: false,
+            endpoints: None,
+        }
+    }
+
+    fn client() -> Client {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        Client::new()
+    }
+
+    fn upload_url(server: &TestServer) -> String {
+        let request = server
+            .backend()
+            .upload_request(&client(), server)
+            .build()
+            .unwrap();
+        assert_eq!(request.method(), reqwest::Method::POST);
+        request.url().to_string()
+    }
+
+    fn latency_request(server: &TestServer) -> (reqwest::Method, String) {
+        let request = server
+            .backend()
+            .latency_probe(&client(), server)
+            .build()
+            .unwrap();
+        (request.method().clone(), request.url().to_string())
+    }
+
+    #[test]
+    fn test_builtin_backends() {
+        let server = server(ServerProtocol::Cloudflare);
+        assert_eq!(
+            server.backend().download_url(&server, 100_000),
+            "https://speed.corp.example/__down?bytes=100000"
+        );
+        assert_eq!(upload_url(&server), "https://speed.corp.example/__up");
+        assert_eq!(
+            latency_request(&server),
+            (
+                reqwest::Method::GET,
+                "https://speed.corp.example/__down?bytes=0".to_string()
+            )
+        );
+        assert!(!server.capabilities.supports(SpeedPhase::Upload));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(server.backend().server_clock(&headers), None);
+        headers.insert("cf-meta-request-time", "1700000000123".parse().unwrap());
+        assert_eq!(
+            server.backend().server_clock(&headers),
+            Some(1_700_000_000_123.0)
+        );
+
+        let librespeed = TestServer {
+            protocol: ServerProtocol::LibreSpeed,
+            ..server.clone()
+        };
+        let backend = librespeed.backend();
+        assert_eq!(
+            backend.download_url(&librespeed, 100_000),
+            "https://speed.corp.example/garbage.php?ckSize=1"
+        );
+        assert_eq!(
+            backend.download_url(&librespeed, 100 * 1024 * 1024 + 1),
+            "https://speed.corp.example/garbage.php?ckSize=101"
+        );
+        assert_eq!(
+            upload_url(&librespeed),
+            "https://speed.corp.example/empty.php"
+        );
+
+        let ookla = TestServer {
+            url: "https://speedtest.isp.example:8080".to_string(),
+            protocol: ServerProtocol::Ookla,
+            ..server.clone()
+        };
+        assert_eq!(
+            ookla.backend().download_url(&ookla, 25_000_000),
+            "https://speedtest.isp.example:8080/download?size=25000000"
+        );
+        assert_eq!(
+            upload_url(&ookla),
+            "https://speedtest.isp.example:8080/upload"
+        );
+        assert_eq!(
+            latency_request(&ookla),
+            (
+                reqwest::Method::GET,
+                "https://speedtest.isp.example:8080/hello".to_string()
+            )
+        );
+        assert_eq!(
+            ookla.backend().websocket_url(&ookla).as_deref(),
+            Some("wss://speedtest.isp.example:8080/ws")
+        );
+        assert_eq!(server.backend().websocket_url(&server), None);
+
+        let fast = TestServer {
+            url: "https://oca.example/speedtest?c=de".to_string(),
+            protocol: ServerProtocol::Fast,
+            ..server
+        };
+        assert_eq!(
+            fast.backend()
+                .download_url(&fast, 2 * fast::MAX_RANGE_BYTES),
+            "https://oca.example/speedtest/range/0-26214399?c=de"
+        );
+        assert_eq!(
+            upload_url(&fast),
+            "https://oca.example/speedtest/range/0-0?c=de"
+        );
+    }
+
+    #[test]
+    fn test_custom_endpoints() {
+        let mut server = server(ServerProtocol::Custom);
+        server.endpoints = Some(CustomEndpoints {
+            download: "/files/blob?size={bytes}".to_string(),
+            upload: "https://sink.corp.example/discard".to_string(),
+            latency: Some("ping".to_string()),
+            websocket: Some("ws/echo".to_string()),
+        });
+        assert_eq!(
+            server.backend().download_url(&server, 4096),
+            "https://speed.corp.example/files/blob?size=4096"
+        );
+        assert_eq!(upload_url(&server), "https://sink.corp.example/discard");
+        assert_eq!(
+            latency_request(&server),
+            (
+                reqwest::Method::HEAD,
+                "https://speed.corp.example/ping".to_string()
+            )
+        );
+        assert_eq!(
+            server.backend().websocket_url(&server).as_deref(),
+            Some("wss://speed.corp.example/ws/echo")
+        );
+
+        // A fixed file ignores the requested size
+        server.endpoints.as_mut().unwrap().download = "100MB.bin".to_string();
+        assert_eq!(
+            server.backend().download_url(&server, 4096),
+            "https://speed.corp.example/100MB.bin"
+        );
+
+        // Plain HTTP servers get no socket
+        server.url = "http://10.20.0.5:8080".to_string();
+        assert_eq!(server.backend().websocket_url(&server), None);
+    }
+}
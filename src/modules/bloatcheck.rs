@@ -0,0 +1,407 @@
+//! Bufferbloat Location Check
+//!
+//! Works out where latency builds up while the internet connection is busy,
+//! without logging into the router. TCP handshakes are timed to the router, to a
+//! second host on the LAN and to an internet host, first on an idle line and
+//! then while a download saturates the WAN. A reset answers a handshake as well
+//! as an accept, so the LAN host needs no open port.
+//!
+//! The router is this machine's first hop, so latency that grows on the way to
+//! it is queued on the link in between, typically Wi-Fi. The LAN host is reached
+//! through the router without touching the WAN, ideally over a cable, so growth
+//! beyond the router's own points at the router being overwhelmed. Growth that
+//! only shows on the way to the internet is queued in the router's WAN queue or
+//! the modem, which SQM on the router fixes.
+
+use colored::*;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use strum_macros::Display;
+use tokio::net::TcpStream;
+
+use crate::modules::localnet;
+use crate::modules::speed_test::SpeedTest;
+use crate::modules::terminal::print_table;
+use crate::modules::tls;
+use crate::modules::types::{LinkLatency, TestConfig};
+use crate::modules::ui::UI;
+
+/// Probed for the internet path, Cloudflare's resolver answers from a nearby edge
+const INTERNET_TARGET: (&str, u16) = ("1.1.1.1", 443);
+
+/// Port the router's handshakes are timed on, its web UI or a reset
+const ROUTER_PORT: u16 = 80;
+
+/// Time between rounds of probes, each round probes every path at once
+const PROBE_INTERVAL: Duration = Duration::from_millis(200);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Rounds on the idle line
+const IDLE_ROUNDS: usize = 20;
+
+/// Probes in the download's first seconds are dropped, while it ramps up
+const LOAD_SETTLE: Duration = Duration::from_secs(3);
+
+/// Median latency must grow this much under load to count as bufferbloat
+const BLOAT_MIN_MS: f64 = 20.0;
+
+/// A path whose latency was measured idle and under load
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum BloatPath {
+    #[strum(to_string = "Router")]
+    Router,
+    #[strum(to_string = "LAN host")]
+    LanHost,
+    #[strum(to_string = "Internet")]
+    Internet,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathLatency {
+    pub path: BloatPath,
+    pub idle: LinkLatency,
+    pub loaded: LinkLatency,
+}
+
+impl PathLatency {
+    /// Growth of the median latency under load, None when either phase had no answers
+    pub fn increase_ms(&self) -> Option<f64> {
+        Some(self.loaded.median_ms? - self.idle.median_ms?)
+    }
+}
+
+/// Where latency built up under load
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BloatVerdict {
+    /// No path grew by more than a few milliseconds
+    NoBloat,
+    /// Even the router grew: the queue is on this machine's link to it
+    LocalLink,
+    /// The LAN host grew past the router: the router itself is overwhelmed
+    Router,
+    /// Only the internet grew: the queue is at the WAN, in the router or the modem
+    AccessLink,
+    /// Paths that tell the others apart didn't answer under load
+    Inconclusive,
+}
+
+impl BloatVerdict {
+    /// Judge from each path's latency increase under load
+    pub fn locate(router: Option<f64>, lan_host: Option<f64>, internet: Option<f64>) -> Self {
+        let grew = |increase: Option<f64>| increase.is_some_and(|ms| ms >= BLOAT_MIN_MS);
+        if grew(router) {
+            BloatVerdict::LocalLink
+        } else if grew(lan_host) {
+            // Without the router's own figure the first hop can't be ruled out
+            if router.is_some() {
+                BloatVerdict::Router
+            } else {
+                BloatVerdict::Inconclusive
+            }
+        } else if lan_host.is_none() || internet.is_none() {
+            BloatVerdict::Inconclusive
+        } else if grew(internet) {
+            BloatVerdict::AccessLink
+        } else {
+            BloatVerdict::NoBloat
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BloatCheckReport {
+    /// Router first when it answered, then the LAN host and the internet
+    pub paths: Vec<PathLatency>,
+    /// Download speed the load reached
+    pub load_mbps: f64,
+    pub verdict: BloatVerdict,
+}
+
+impl BloatCheckReport {
+    fn increase_ms(&self, path: BloatPath) -> Option<f64> {
+        self.paths
+            .iter()
+            .find(|latency| latency.path == path)
+            .and_then(PathLatency::increase_ms)
+    }
+}
+
+pub struct BloatCheckTool {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl BloatCheckTool {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    /// Compare latency to the router, to `lan_host` (HOST or HOST:PORT) and to the
+    /// internet on an idle line and while a download saturates it
+    pub async fn run(
+        &self,
+        lan_host: &str,
+    ) -> Result<BloatCheckReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("Bufferbloat Location Check")?;
+        }
+
+        let (host, port) = tls::parse_target(lan_host)?;
+        let lan_address = tokio::net::lookup_host((host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", host))?;
+        let router = localnet::detect()
+            .await
+            .and_then(|network| network.gateway)
+            .map(|gateway| SocketAddr::new(IpAddr::V4(gateway), ROUTER_PORT));
+        let internet = tokio::net::lookup_host(INTERNET_TARGET)
+            .await?
+            .next()
+            .ok_or("Could not resolve the internet probe target")?;
+
+        let mut targets = Vec::new();
+        match router {
+            Some(router) if router.ip() != lan_address.ip() => {
+                targets.push((BloatPath::Router, router))
+            }
+            _ if !self.config.json_output => self.ui.show_info(
+                "No router to probe apart from the LAN host, the Wi-Fi link can't be told apart from the router",
+            )?,
+            _ => {}
+        }
+        targets.push((BloatPath::LanHost, lan_address));
+        targets.push((BloatPath::Internet, internet));
+        let addresses: Vec<SocketAddr> = targets.iter().map(|(_, address)| *address).collect();
+
+        // Idle baseline
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_cyberpunk_spinner("MEASURING IDLE LATENCY"))
+        } else {
+            None
+        };
+        let mut idle = vec![Vec::new(); addresses.len()];
+        let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+        for _ in 0..IDLE_ROUNDS {
+            ticker.tick().await;
+            for (probes, latency) in idle.iter_mut().zip(probe_round(&addresses).await) {
+                probes.push(latency);
+            }
+        }
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ IDLE LATENCY MEASURED ⟩⟩⟩");
+        }
+        for ((path, address), probes) in targets.iter().zip(&idle) {
+            if *path != BloatPath::Router && probes.iter().all(Option::is_none) {
+                return Err(match path {
+                    BloatPath::LanHost => format!(
+                        "{} did not answer, pick another host on the LAN (lanscan mode lists them)",
+                        address
+                    ),
+                    _ => format!("{} did not answer, is the internet reachable?", address),
+                }
+                .into());
+            }
+        }
+
+        // The download runs quietly, this mode shows its own progress
+        let speed_test = SpeedTest::new(TestConfig {
+            json_output: true,
+            animation_enabled: false,
+            ..self.config.clone()
+        })?;
+        let servers = speed_test.choose_load_servers().await?;
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("MEASURING LATENCY UNDER LOAD"),
+            )
+        } else {
+            None
+        };
+        let done = AtomicBool::new(false);
+        let load = async {
+            let outcome = speed_test.run_download_load(&servers).await;
+            done.store(true, Ordering::Relaxed);
+            outcome
+        };
+        let probing = async {
+            let mut loaded = vec![Vec::new(); addresses.len()];
+            let started = Instant::now();
+            let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+            while !done.load(Ordering::Relaxed) {
+                ticker.tick().await;
+                let round = probe_round(&addresses).await;
+                // A round that ended after the download did wasn't under load
+                if started.elapsed() >= LOAD_SETTLE && !done.load(Ordering::Relaxed) {
+                    for (probes, latency) in loaded.iter_mut().zip(round) {
+                        probes.push(latency);
+                    }
+                }
+            }
+            loaded
+        };
+        let (outcome, loaded) = tokio::join!(load, probing);
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ LOADED LATENCY MEASURED ⟩⟩⟩");
+        }
+        let load_mbps = outcome?
+            .mbps()
+            .ok_or("The download meant to load the line failed")?;
+
+        let paths: Vec<PathLatency> = targets
+            .iter()
+            .zip(idle.iter().zip(&loaded))
+            .map(|((path, address), (idle, loaded))| PathLatency {
+                path: *path,
+                idle: LinkLatency::from_probes(address.ip(), address.port(), idle),
+                loaded: LinkLatency::from_probes(address.ip(), address.port(), loaded),
+            })
+            .collect();
+        let mut report = BloatCheckReport {
+            paths,
+            load_mbps,
+            verdict: BloatVerdict::Inconclusive,
+        };
+        report.verdict = BloatVerdict::locate(
+            report.increase_ms(BloatPath::Router),
+            report.increase_ms(BloatPath::LanHost),
+            report.increase_ms(BloatPath::Internet),
+        );
+
+        if !self.config.json_output {
+            self.display_results(&report)?;
+        }
+
+        Ok(report)
+    }
+
+    fn display_results(&self, report: &BloatCheckReport) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(Row::new(vec![
+            Cell::new("Path").style_spec("Fb"),
+            Cell::new("Address").style_spec("Fb"),
+            Cell::new("Idle").style_spec("Fb"),
+            Cell::new("Loaded").style_spec("Fb"),
+            Cell::new("Increase").style_spec("Fb"),
+            Cell::new("Loss loaded").style_spec("Fb"),
+        ]));
+        let ms = |latency: &LinkLatency| match latency.median_ms {
+            Some(ms) => format!("{:.1} ms", ms),
+            None => "no answer".to_string(),
+        };
+        for latency in &report.paths {
+            let increase = match latency.increase_ms() {
+                Some(increase) => Cell::new(&format!("{:+.1} ms", increase))
+                    .style_spec(if increase >= BLOAT_MIN_MS { "Fr" } else { "Fg" }),
+                None => Cell::new("-"),
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&latency.path.to_string()),
+                Cell::new(&SocketAddr::new(latency.idle.address, latency.idle.port).to_string()),
+                Cell::new(&ms(&latency.idle)),
+                Cell::new(&ms(&latency.loaded)),
+                increase,
+                Cell::new(&format!("{:.0}%", latency.loaded.loss_percent())),
+            ]));
+        }
+        print_table(&table)?;
+        println!();
+        println!("Measured under a {:.1} Mbps download.", report.load_mbps);
+
+        // The part of the LAN host's latency the router and cable add, past the first hop
+        if let (Some(router), Some(lan_host)) = (
+            report.increase_ms(BloatPath::Router),
+            report.increase_ms(BloatPath::LanHost),
+        ) {
+            println!(
+                "The wired path past the router grew {:+.1} ms, the link to the router {:+.1} ms.",
+                lan_host - router,
+                router
+            );
+        }
+
+        let verdict = match report.verdict {
+            BloatVerdict::NoBloat => "✓ Latency stays flat under load on every path".bright_green(),
+            BloatVerdict::LocalLink => "⚠ Latency to the router itself grows under load: packets queue on this machine's link to it, typically Wi-Fi. Try a cable, a less crowded channel or moving closer to the access point".bright_yellow(),
+            BloatVerdict::Router => "⚠ Latency through the router to the LAN host grows under load while the link to the router stays flat: the router is overwhelmed by the traffic".bright_yellow(),
+            BloatVerdict::AccessLink => "⚠ Only latency to the internet grows under load: packets queue at the WAN, in the router's WAN queue or the modem. Enable SQM or smart queue management on the router, or cap its speed slightly below the plan".bright_yellow(),
+            BloatVerdict::Inconclusive => "? Too few probes were answered under load to tell where latency builds up".bright_yellow(),
+        };
+        println!("{}", verdict);
+
+        Ok(())
+    }
+}
+
+/// Time a handshake with every address at once, in order
+async fn probe_round(addresses: &[SocketAddr]) -> Vec<Option<f64>> {
+    futures::future::join_all(addresses.iter().map(|address| probe(*address))).await
+}
+
+/// Handshake time in milliseconds, a refused connection answers too
+async fn probe(address: SocketAddr) -> Option<f64> {
+    let start = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+            Some(start.elapsed().as_secs_f64() * 1000.0)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_bloat() {
+        assert_eq!(
+            BloatVerdict::locate(Some(2.0), Some(3.0), Some(8.0)),
+            BloatVerdict::NoBloat
+        );
+        assert_eq!(
+            BloatVerdict::locate(Some(45.0), Some(50.0), Some(60.0)),
+            BloatVerdict::LocalLink
+        );
+        assert_eq!(
+            BloatVerdict::locate(Some(3.0), Some(40.0), Some(45.0)),
+            BloatVerdict::Router
+        );
+        assert_eq!(
+            BloatVerdict::locate(None, Some(40.0), Some(45.0)),
+            BloatVerdict::Inconclusive
+        );
+        assert_eq!(
+            BloatVerdict::locate(Some(1.0), Some(2.0), Some(120.0)),
+            BloatVerdict::AccessLink
+        );
+        assert_eq!(
+            BloatVerdict::locate(None, Some(2.0), Some(120.0)),
+            BloatVerdict::AccessLink
+        );
+        assert_eq!(
+            BloatVerdict::locate(Some(1.0), Some(2.0), None),
+            BloatVerdict::Inconclusive
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_counts_refused_connections() {
+        // Bind and drop a listener to find a port nothing listens on
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(probe(closed).await.is_some());
+    }
+}
@@ -0,0 +1,581 @@
+//! Cloud Provider Reachability
+//!
+//! Times HTTP round trips to public endpoints in AWS, Google Cloud and Azure
+//! regions plus the nearest Cloudflare edge, and ranks them. Requests go over a
+//! kept-alive connection after a warm-up request, so the figures reflect the
+//! network path rather than DNS and TLS setup. Google fronts its regions with
+//! anycast, so its region list comes from gcping.com, whose per-region services
+//! answer from inside the region.
+
+use colored::*;
+use futures::stream::{self, StreamExt};
+use prettytable::{format, Cell, Row, Table};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use strum_macros::Display;
+
+use crate::modules::terminal::print_table;
+use crate::modules::types::{LinkLatency, TestConfig};
+use crate::modules::ui::UI;
+
+/// Timed round trips per region, after the warm-up request
+const PROBES: usize = 5;
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Regions probed at once, low enough not to queue requests behind each other
+const PARALLEL_REGIONS: usize = 8;
+
+const GCPING_ENDPOINTS_URL: &str = "https://global.gcping.com/api/endpoints";
+const CLOUDFLARE_TRACE_URL: &str = "https://speed.cloudflare.com/cdn-cgi/trace";
+
+/// A provider whose fastest region is this much slower than the fastest cloud
+/// region, and at least twice as slow, is flagged as a possible peering problem
+const PEERING_GAP_MS: f64 = 30.0;
+
+/// AWS regions open to every account, timed against DynamoDB's health check
+const AWS_REGIONS: &[(&str, &str)] = &[
+    ("us-east-1", "N. Virginia"),
+    ("us-east-2", "Ohio"),
+    ("us-west-1", "N. California"),
+    ("us-west-2", "Oregon"),
+    ("ca-central-1", "Montreal"),
+    ("sa-east-1", "São Paulo"),
+    ("eu-west-1", "Ireland"),
+    ("eu-west-2", "London"),
+    ("eu-west-3", "Paris"),
+    ("eu-central-1", "Frankfurt"),
+    ("eu-north-1", "Stockholm"),
+    ("ap-south-1", "Mumbai"),
+    ("ap-southeast-1", "Singapore"),
+    ("ap-southeast-2", "Sydney"),
+    ("ap-northeast-1", "Tokyo"),
+    ("ap-northeast-2", "Seoul"),
+];
+
+/// Azure regions, timed against their regional Cognitive Services endpoint
+const AZURE_REGIONS: &[(&str, &str)] = &[
+    ("eastus", "Virginia"),
+    ("centralus", "Iowa"),
+    ("westus2", "Washington"),
+    ("canadacentral", "Toronto"),
+    ("brazilsouth", "São Paulo"),
+    ("northeurope", "Ireland"),
+    ("westeurope", "Netherlands"),
+    ("uksouth", "London"),
+    ("francecentral", "Paris"),
+    ("germanywestcentral", "Frankfurt"),
+    ("swedencentral", "Gävle"),
+    ("centralindia", "Pune"),
+    ("southeastasia", "Singapore"),
+    ("eastasia", "Hong Kong"),
+    ("japaneast", "Tokyo"),
+    ("koreacentral", "Seoul"),
+    ("australiaeast", "Sydney"),
+    ("southafricanorth", "Johannesburg"),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudProvider {
+    #[strum(to_string = "AWS")]
+    Aws,
+    #[strum(to_string = "Google Cloud")]
+    Gcp,
+    #[strum(to_string = "Azure")]
+    Azure,
+    #[strum(to_string = "Cloudflare")]
+    Cloudflare,
+}
+
+impl CloudProvider {
+    /// Cloudflare answers from its nearest edge rather than a region you deploy to
+    pub fn has_regions(&self) -> bool {
+        !matches!(self, CloudProvider::Cloudflare)
+    }
+}
+
+/// One endpoint to time
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CloudRegion {
+    pub provider: CloudProvider,
+    pub region: String,
+    pub location: String,
+    pub url: String,
+}
+
+/// Time to fetch a region's endpoint over a new connection, DNS and TLS included,
+/// roughly what the first request of a freshly started client costs
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct ObjectFetch {
+    pub bytes: usize,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionReachability {
+    #[serde(flatten)]
+    pub region: CloudRegion,
+    /// HTTP round trips on a kept-alive connection, `None` if the warm-up failed
+    pub latency: Option<LinkLatency>,
+    pub fetch: Option<ObjectFetch>,
+    pub error: Option<String>,
+}
+
+impl RegionReachability {
+    pub fn median_ms(&self) -> Option<f64> {
+        self.latency.as_ref().and_then(|l| l.median_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CloudCheckReport {
+    /// Fastest first, unreachable regions last
+    pub regions: Vec<RegionReachability>,
+    /// Set when the Google Cloud region list could not be fetched
+    pub gcp_error: Option<String>,
+}
+
+impl CloudCheckReport {
+    /// Fastest reachable region of each provider, in ranking order
+    pub fn fastest_per_provider(&self) -> Vec<&RegionReachability> {
+        let mut fastest: Vec<&RegionReachability> = Vec::new();
+        for region in self.regions.iter().filter(|r| r.median_ms().is_some()) {
+            if !fastest
+                .iter()
+                .any(|f| f.region.provider == region.region.provider)
+            {
+                fastest.push(region);
+            }
+        }
+        fastest
+    }
+
+    /// Providers much slower from here than the fastest cloud, which tends to
+    /// mean the ISP reaches them over a poor peering or transit link
+    pub fn slow_providers(&self) -> Vec<CloudProvider> {
+        let fastest: Vec<(CloudProvider, f64)> = self
+            .fastest_per_provider()
+            .into_iter()
+            .filter(|r| r.region.provider.has_regions())
+            .filter_map(|r| Some((r.region.provider, r.median_ms()?)))
+            .collect();
+        let Some(&(_, best)) = fastest.first() else {
+            return Vec::new();
+        };
+        fastest
+            .into_iter()
+            .filter(|&(_, ms)| ms - best > PEERING_GAP_MS && ms > best * 2.0)
+            .map(|(provider, _)| provider)
+            .collect()
+    }
+}
+
+pub struct CloudCheckTool {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl CloudCheckTool {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    /// Time every region, with `fetch` also timing a cold fetch of its endpoint
+    pub async fn run(&self, fetch: bool) -> Result<CloudCheckReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("Cloud Region Reachability")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_cyberpunk_spinner("PINGING CLOUD REGIONS"))
+        } else {
+            None
+        };
+
+        let client = Client::builder().timeout(PROBE_TIMEOUT).build()?;
+        let mut regions = fixed_regions();
+        let gcp_error = match gcp_regions(&client).await {
+            Ok(gcp) => {
+                regions.extend(gcp);
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+
+        let mut results: Vec<RegionReachability> = stream::iter(regions)
+            .map(|region| probe_region(&client, region, fetch))
+            .buffer_unordered(PARALLEL_REGIONS)
+            .collect()
+            .await;
+        rank(&mut results);
+
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ REGIONS PINGED ⟩⟩⟩");
+        }
+
+        let report = CloudCheckReport {
+            regions: results,
+            gcp_error,
+        };
+        if !self.config.json_output {
+            self.display_results(&report, fetch)?;
+        }
+
+        Ok(report)
+    }
+
+    fn display_results(
+        &self,
+        report: &CloudCheckReport,
+        fetch: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+        if let Some(error) = &report.gcp_error {
+            println!(
+                "{} {}",
+                "⚠ Google Cloud regions unavailable:".bright_yellow(),
+                error
+            );
+            println!();
+        }
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        let mut titles = vec![
+            Cell::new("#").style_spec("Fb"),
+            Cell::new("Provider").style_spec("Fb"),
+            Cell::new("Region").style_spec("Fb"),
+            Cell::new("Location").style_spec("Fb"),
+            Cell::new("Latency").style_spec("Fb"),
+            Cell::new("Jitter").style_spec("Fb"),
+        ];
+        if fetch {
+            titles.push(Cell::new("Cold fetch").style_spec("Fb"));
+        }
+        table.set_titles(Row::new(titles));
+
+        for (rank, result) in report.regions.iter().enumerate() {
+            let (latency, jitter) = match (&result.latency, result.median_ms()) {
+                (Some(latency), Some(ms)) => (
+                    Cell::new(&format!("{:.1} ms", ms)).style_spec(latency_style(ms)),
+                    Cell::new(&format!("{:.1} ms", latency.jitter_ms)),
+                ),
+                _ => (Cell::new("unreachable").style_spec("Fr"), Cell::new("-")),
+            };
+            let mut row = vec![
+                Cell::new(&(rank + 1).to_string()),
+                Cell::new(&result.region.provider.to_string()),
+                Cell::new(&result.region.region),
+                Cell::new(&result.region.location),
+                latency,
+                jitter,
+            ];
+            if fetch {
+                row.push(Cell::new(&match result.fetch {
+                    Some(fetch) => format!("{:.0} ms", fetch.total_ms),
+                    None => "-".to_string(),
+                }));
+            }
+            table.add_row(Row::new(row));
+        }
+
+        print_table(&table)?;
+        println!();
+
+        let fastest = report.fastest_per_provider();
+        if fastest.is_empty() {
+            println!("{}", "✗ No cloud region answered".bright_red());
+            return Ok(());
+        }
+        println!("{}", "Fastest per provider:".bold());
+        for result in fastest {
+            println!(
+                "  {:<12} {} ({}) {:.1} ms",
+                result.region.provider.to_string(),
+                result.region.region.bright_cyan(),
+                result.region.location,
+                result.median_ms().unwrap_or_default()
+            );
+        }
+
+        for provider in report.slow_providers() {
+            println!(
+                "{} {} is much slower from here than other clouds, which can point to poor peering between your ISP and {}",
+                "⚠".bright_yellow(),
+                provider,
+                provider
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn latency_style(ms: f64) -> &'static str {
+    if ms < 50.0 {
+        "Fg"
+    } else if ms < 150.0 {
+        "Fy"
+    } else {
+        "Fr"
+    }
+}
+
+/// AWS, Azure and Cloudflare endpoints, which follow fixed naming schemes
+fn fixed_regions() -> Vec<CloudRegion> {
+    let aws = AWS_REGIONS.iter().map(|(region, location)| CloudRegion {
+        provider: CloudProvider::Aws,
+        region: region.to_string(),
+        location: location.to_string(),
+        url: format!("https://dynamodb.{}.amazonaws.com/ping", region),
+    });
+    let azure = AZURE_REGIONS.iter().map(|(region, location)| CloudRegion {
+        provider: CloudProvider::Azure,
+        region: region.to_string(),
+        location: location.to_string(),
+        url: format!("https://{}.api.cognitive.microsoft.com/", region),
+    });
+    let cloudflare = CloudRegion {
+        provider: CloudProvider::Cloudflare,
+        // Replaced by the answering data center once probed
+        region: "edge".to_string(),
+        location: "Nearest edge".to_string(),
+        url: CLOUDFLARE_TRACE_URL.to_string(),
+    };
+
+    aws.chain(azure)
+        .chain(std::iter::once(cloudflare))
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct GcpingEndpoint {
+    #[serde(rename = "URL")]
+    url: String,
+    #[serde(rename = "RegionName", default)]
+    region_name: String,
+}
+
+async fn gcp_regions(client: &Client) -> Result<Vec<CloudRegion>, Box<dyn std::error::Error>> {
+    let body = client
+        .get(GCPING_ENDPOINTS_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(parse_gcping_endpoints(&body)?)
+}
+
+/// Regions from gcping's endpoint list, skipping its anycast `global` entry
+fn parse_gcping_endpoints(body: &str) -> Result<Vec<CloudRegion>, serde_json::Error> {
+    let endpoints: BTreeMap<String, GcpingEndpoint> = serde_json::from_str(body)?;
+    Ok(endpoints
+        .into_iter()
+        .filter(|(region, _)| region != "global")
+        .map(|(region, endpoint)| CloudRegion {
+            provider: CloudProvider::Gcp,
+            location: if endpoint.region_name.is_empty() {
+                region.clone()
+            } else {
+                endpoint.region_name
+            },
+            url: format!("{}/api/ping", endpoint.url.trim_end_matches('/')),
+            region,
+        })
+        .collect())
+}
+
+/// The data center code in a Cloudflare trace, e.g. `FRA`
+fn trace_colo(body: &str) -> Option<&str> {
+    body.lines()
+        .find_map(|line| line.strip_prefix("colo="))
+        .map(str::trim)
+        .filter(|colo| !colo.is_empty())
+}
+
+async fn probe_region(client: &Client, mut region: CloudRegion, fetch: bool) -> RegionReachability {
+    let fetch = if fetch {
+        cold_fetch(&region.url).await.ok()
+    } else {
+        None
+    };
+
+    // The warm-up opens the connection the timed requests reuse
+    let (address, body) = match get(client, &region.url).await {
+        Ok(answer) => answer,
+        Err(e) => {
+            return RegionReachability {
+                region,
+                latency: None,
+                fetch,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    if region.provider == CloudProvider::Cloudflare {
+        if let Some(colo) = trace_colo(&body) {
+            region.region = colo.to_string();
+        }
+    }
+
+    let mut probes = Vec::with_capacity(PROBES);
+    let mut error = None;
+    for _ in 0..PROBES {
+        tokio::time::sleep(PROBE_INTERVAL).await;
+        let start = Instant::now();
+        match get(client, &region.url).await {
+            Ok(_) => probes.push(Some(start.elapsed().as_secs_f64() * 1000.0)),
+            Err(e) => {
+                probes.push(None);
+                error = Some(e.to_string());
+            }
+        }
+    }
+
+    RegionReachability {
+        // Behind a proxy the peer address is unknown
+        latency: Some(LinkLatency::from_probes(
+            address.map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |a| a.ip()),
+            address.map_or(443, |a| a.port()),
+            &probes,
+        )),
+        region,
+        fetch,
+        error,
+    }
+}
+
+/// Any answer counts, error statuses included, since only the round trip matters
+async fn get(client: &Client, url: &str) -> Result<(Option<SocketAddr>, String), reqwest::Error> {
+    let response = client.get(url).send().await?;
+    let address = response.remote_addr();
+    Ok((address, response.text().await?))
+}
+
+async fn cold_fetch(url: &str) -> Result<ObjectFetch, reqwest::Error> {
+    let client = Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .pool_max_idle_per_host(0)
+        .build()?;
+    let start = Instant::now();
+    let body = client.get(url).send().await?.bytes().await?;
+    Ok(ObjectFetch {
+        bytes: body.len(),
+        total_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+/// Fastest first, unreachable last, ties broken by provider and region
+fn rank(results: &mut [RegionReachability]) {
+    results.sort_by(|a, b| {
+        let a_ms = a.median_ms().unwrap_or(f64::INFINITY);
+        let b_ms = b.median_ms().unwrap_or(f64::INFINITY);
+        a_ms.total_cmp(&b_ms)
+            .then(a.region.provider.cmp(&b.region.provider))
+            .then_with(|| a.region.region.cmp(&b.region.region))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reachability(provider: CloudProvider, region: &str, ms: Option<f64>) -> RegionReachability {
+        let address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        RegionReachability {
+            region: CloudRegion {
+                provider,
+                region: region.to_string(),
+                location: String::new(),
+                url: String::new(),
+            },
+            latency: Some(LinkLatency::from_probes(address, 443, &[ms])),
+            fetch: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_rank_and_fastest_per_provider() {
+        let mut regions = vec![
+            reachability(CloudProvider::Aws, "us-east-1", Some(90.0)),
+            reachability(CloudProvider::Azure, "westeurope", None),
+            reachability(CloudProvider::Aws, "eu-central-1", Some(12.0)),
+            reachability(CloudProvider::Azure, "northeurope", Some(30.0)),
+        ];
+        rank(&mut regions);
+        let order: Vec<&str> = regions.iter().map(|r| r.region.region.as_str()).collect();
+        assert_eq!(
+            order,
+            ["eu-central-1", "northeurope", "us-east-1", "westeurope"]
+        );
+
+        let report = CloudCheckReport {
+            regions,
+            gcp_error: None,
+        };
+        let fastest: Vec<&str> = report
+            .fastest_per_provider()
+            .iter()
+            .map(|r| r.region.region.as_str())
+            .collect();
+        assert_eq!(fastest, ["eu-central-1", "northeurope"]);
+    }
+
+    #[test]
+    fn test_slow_providers() {
+        let mut regions = vec![
+            // The nearby edge is no yardstick for cloud regions
+            reachability(CloudProvider::Cloudflare, "FRA", Some(3.0)),
+            reachability(CloudProvider::Aws, "eu-central-1", Some(12.0)),
+            reachability(CloudProvider::Gcp, "europe-west3", Some(20.0)),
+            reachability(CloudProvider::Azure, "germanywestcentral", Some(95.0)),
+        ];
+        rank(&mut regions);
+        let report = CloudCheckReport {
+            regions,
+            gcp_error: None,
+        };
+        assert_eq!(report.slow_providers(), [CloudProvider::Azure]);
+    }
+
+    #[test]
+    fn test_parse_gcping_endpoints() {
+        let body = r#"{
+            "global": {"URL": "https://global.gcping.com", "Region": "global", "RegionName": "Global"},
+            "europe-west3": {"URL": "https://europe-west3-abc.a.run.app/", "Region": "europe-west3", "RegionName": "Frankfurt"},
+            "us-central1": {"URL": "https://us-central1-abc.a.run.app", "Region": "us-central1"}
+        }"#;
+        let regions = parse_gcping_endpoints(body).unwrap();
+        assert_eq!(
+            regions,
+            [
+                CloudRegion {
+                    provider: CloudProvider::Gcp,
+                    region: "europe-west3".to_string(),
+                    location: "Frankfurt".to_string(),
+                    url: "https://europe-west3-abc.a.run.app/api/ping".to_string(),
+                },
+                CloudRegion {
+                    provider: CloudProvider::Gcp,
+                    region: "us-central1".to_string(),
+                    location: "us-central1".to_string(),
+                    url: "https://us-central1-abc.a.run.app/api/ping".to_string(),
+                },
+            ]
+        );
+        assert!(parse_gcping_endpoints("not json").is_err());
+    }
+
+    #[test]
+    fn test_trace_colo() {
+        let trace = "fl=123\nh=speed.cloudflare.com\nip=203.0.113.7\ncolo=FRA\nhttp=http/2\n";
+        assert_eq!(trace_colo(trace), Some("FRA"));
+        assert_eq!(trace_colo("fl=123\ncolo=\n"), None);
+    }
+}
@@ -0,0 +1,600 @@
+//! User Configuration
+//!
+//! Optional settings loaded from `config.toml` in the netrunner config directory
+//! (next to the history database). Every key has a default, so a missing file or
+//! a file that only sets a few keys is fine.
+//!
+//! `NETRUNNER_*` environment variables override the file, and command line flags
+//! override both. Keys with a flag get their variable through clap, the rest are
+//! read in [`AppConfig::apply_env`].
+//!
+//! The interactive menu's settings are written back with [`MenuSettings::save_to`],
+//! which leaves the rest of the file as it was.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::modules::types::{
+    AdvertisedPlan, DataSize, DetailLevel, EmailSettings, GeoSettings, ImportantEndpoint,
+    QuietWindow, ReportTarget, ServerFilter, SnmpSettings, TestServer, ThemeName,
+};
+
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Maximum number of speed tests per rolling hour against public servers (0 = unlimited)
+    pub max_tests_per_hour: u32,
+    /// Color theme used when `--theme` is not given
+    pub theme: ThemeName,
+    /// Advertised plan speeds, set as a `[plan]` table with `download_mbps`, `upload_mbps`
+    /// and optionally `alert_below_percent`
+    pub plan: Option<AdvertisedPlan>,
+    /// Aggregation server, set as a `[report]` table with `url` and optionally
+    /// `token` and `client_id`
+    pub report: Option<ReportTarget>,
+    /// Monitor mode alerts, an `[email]` table with `server`, `from`, `to` and
+    /// optionally `port`, `username` and `digest`
+    pub email: Option<EmailSettings>,
+    /// Router polled over SNMP during speed tests, an `[snmp]` table with
+    /// optionally `host`, `port`, `community`, `username`, `auth_protocol` and
+    /// `interface`
+    pub snmp: Option<SnmpSettings>,
+    /// ntfy topic monitor alerts are pushed to: a name on ntfy.sh or the URL of a
+    /// topic on another server
+    pub ntfy: Option<String>,
+    /// Pushover keys monitor alerts are pushed with, as `APP_TOKEN:USER_KEY`
+    pub pushover: Option<String>,
+    /// Times monitor mode skips its tests, each a `[[quiet_hours]]` table with
+    /// `start`, `end` and optionally `days` and `action` (`skip` or `latency`)
+    pub quiet_hours: Vec<QuietWindow>,
+    /// Name recorded with every result, to tell machines apart in aggregated data
+    pub host_label: Option<String>,
+    /// Location recorded with every result, e.g. an office or branch name
+    pub site: Option<String>,
+    /// Labels recorded with every result, e.g. `["wired", "office"]`, to filter
+    /// history exports by
+    pub tags: Vec<String>,
+    /// Data speed tests may use per calendar month before a warning, e.g. `"20GB"`
+    pub monthly_data_budget: Option<DataSize>,
+    /// Hosts timed after every speed test, each an `[[endpoints]]` table with
+    /// `name`, `host` and optionally `port` (443)
+    pub endpoints: Vec<ImportantEndpoint>,
+    /// Geolocation services, a `[geolocation]` table with `builtin`, `disabled`
+    /// and `[[geolocation.custom]]` providers
+    pub geolocation: GeoSettings,
+    /// Server list file used when `--server-list` is not given
+    pub server_list: Option<PathBuf>,
+    /// Servers kept out of the pool, a `[servers]` table with `only_providers`,
+    /// `exclude_providers` and `exclude_hosts`
+    pub servers: ServerFilter,
+    /// Directory of the history database when `--data-dir` is not given
+    pub data_dir: Option<PathBuf>,
+    /// Names diagnostics resolves to time DNS when `--dns-domains` is not given,
+    /// empty for the built-in list
+    pub dns_domains: Vec<String>,
+    /// JSON list of country and ISP medians results are compared with, replacing
+    /// the built-in country table
+    pub medians_url: Option<String>,
+    /// Sign evidence bundles, history backups and share cards without `--sign`
+    pub sign_exports: bool,
+    /// Collector anonymous results go to once `telemetry on` was run, replacing the
+    /// community endpoint
+    pub telemetry_url: Option<String>,
+    /// Animations when `--no-animation` is not given
+    pub animation: Option<bool>,
+    /// Test file size in MB when `--size` is not given
+    pub size_mb: Option<u64>,
+    /// Detail level when `--detail` is not given: `basic`, `standard`, `detailed`
+    /// or `debug`
+    pub detail: Option<String>,
+    /// Server every test uses instead of selecting the best one, a `[pinned_server]`
+    /// table written by the interactive menu's settings
+    pub pinned_server: Option<TestServer>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            max_tests_per_hour: 12,
+            theme: ThemeName::default(),
+            plan: None,
+            report: None,
+            email: None,
+            snmp: None,
+            ntfy: None,
+            pushover: None,
+            quiet_hours: Vec::new(),
+            host_label: None,
+            site: None,
+            tags: Vec::new(),
+            monthly_data_budget: None,
+            endpoints: Vec::new(),
+            geolocation: GeoSettings::default(),
+            server_list: None,
+            servers: ServerFilter::default(),
+            data_dir: None,
+            dns_domains: Vec::new(),
+            medians_url: None,
+            sign_exports: false,
+            telemetry_url: None,
+            animation: None,
+            size_mb: None,
+            detail: None,
+            pinned_server: None,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl AppConfig {
+    /// Load the user configuration, falling back to defaults when no file exists,
+    /// with environment overrides applied
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Self::load_from(&Self::config_path()?)?;
+        config.apply_env(|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    /// Override keys that have no command line flag from `NETRUNNER_*` variables
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        let set = |key: &str| var(key).filter(|value| !value.trim().is_empty());
+
+        if let Some(label) = set("NETRUNNER_HOST_LABEL") {
+            self.host_label = Some(label);
+        }
+        if let Some(site) = set("NETRUNNER_SITE") {
+            self.site = Some(site);
+        }
+        if let Some(email) = &mut self.email {
+            if let Some(username) = set("NETRUNNER_SMTP_USERNAME") {
+                email.username = Some(username);
+            }
+            email.password = set("NETRUNNER_SMTP_PASSWORD");
+        }
+        if let Some(snmp) = &mut self.snmp {
+            if let Some(community) = set("NETRUNNER_SNMP_COMMUNITY") {
+                snmp.community = Some(community);
+            }
+            snmp.password = set("NETRUNNER_SNMP_PASSWORD");
+        }
+        if let Some(topic) = set("NETRUNNER_NTFY") {
+            self.ntfy = Some(topic);
+        }
+        if let Some(keys) = set("NETRUNNER_PUSHOVER") {
+            self.pushover = Some(keys);
+        }
+        if let Some(budget) = set("NETRUNNER_MONTHLY_DATA_BUDGET") {
+            self.monthly_data_budget = Some(
+                budget
+                    .parse()
+                    .map_err(|e| format!("Invalid NETRUNNER_MONTHLY_DATA_BUDGET: {}", e))?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Load configuration from a specific file
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e).into())
+    }
+
+    /// Path of the configuration file, `NETRUNNER_CONFIG` when set
+    pub fn config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(path) = std::env::var_os("NETRUNNER_CONFIG").filter(|path| !path.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let config_dir = dirs::config_dir()
+            .ok_or("Failed to find config directory")?
+            .join("netrunner");
+
+        Ok(config_dir.join(CONFIG_FILE))
+    }
+}
+
+/// Settings the interactive menu changes mid-session
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuSettings {
+    pub animation: bool,
+    pub size_mb: u64,
+    pub detail: DetailLevel,
+    pub pinned_server: Option<TestServer>,
+}
+
+impl MenuSettings {
+    /// Write the settings into the config file at `path`, creating it when missing.
+    /// Only their keys change, other keys and comments stay as written.
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut document: toml_edit::DocumentMut = contents
+            .parse()
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+        document["animation"] = toml_edit::value(self.animation);
+        document["size_mb"] = toml_edit::value(self.size_mb as i64);
+        document["detail"] = toml_edit::value(self.detail.to_string().to_lowercase());
+        match &self.pinned_server {
+            Some(server) => {
+                let table: toml_edit::DocumentMut = toml::to_string(server)?.parse()?;
+                document["pinned_server"] = toml_edit::Item::Table(table.as_table().clone());
+            }
+            None => {
+                document.remove("pinned_server");
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, document.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::ServerProvider;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let dir = tempdir().unwrap();
+        let config = AppConfig::load_from(&dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "max_tests_per_hour = 4\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.max_tests_per_hour, 4);
+        assert_eq!(config.theme, ThemeName::Cyberpunk);
+    }
+
+    #[test]
+    fn test_theme_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "theme = \"colorblind-safe\"\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.theme, ThemeName::ColorblindSafe);
+    }
+
+    #[test]
+    fn test_plan_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "[plan]\ndownload_mbps = 500\nupload_mbps = 50\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(
+            config.plan,
+            Some(AdvertisedPlan {
+                download_mbps: 500.0,
+                upload_mbps: 50.0,
+                alert_below_percent: AdvertisedPlan::DEFAULT_ALERT_BELOW_PERCENT,
+            })
+        );
+    }
+
+    #[test]
+    fn test_plan_alert_threshold_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[plan]\ndownload_mbps = 100\nupload_mbps = 20\nalert_below_percent = 60\n",
+        )
+        .unwrap();
+
+        let plan = AppConfig::load_from(&path).unwrap().plan.unwrap();
+        assert_eq!(plan.alert_below_percent, 60.0);
+    }
+
+    #[test]
+    fn test_plan_without_speed_is_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "[plan]\ndownload_mbps = 0\nupload_mbps = 20\n").unwrap();
+
+        let error = AppConfig::load_from(&path).unwrap_err().to_string();
+        assert!(error.contains("above 0 Mbps"), "{}", error);
+    }
+
+    #[test]
+    fn test_data_budget_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "monthly_data_budget = \"20GB\"\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.monthly_data_budget, Some(DataSize(20_000_000_000)));
+
+        std::fs::write(&path, "monthly_data_budget = \"a lot\"\n").unwrap();
+        assert!(AppConfig::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_endpoints_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[[endpoints]]\nname = \"Work VPN\"\nhost = \"vpn.example.com\"\n\n\
+             [[endpoints]]\nname = \"EU game server\"\nhost = \"203.0.113.7\"\nport = 27015\n",
+        )
+        .unwrap();
+
+        let endpoints = AppConfig::load_from(&path).unwrap().endpoints;
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[0].name, "Work VPN");
+        assert_eq!(endpoints[0].port, 443);
+        assert_eq!(endpoints[1].port, 27015);
+    }
+
+    #[test]
+    fn test_geolocation_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[geolocation]\nbuiltin = false\n\n\
+             [[geolocation.custom]]\nname = \"office\"\nurl = \"https://geo.example.internal/\"\n",
+        )
+        .unwrap();
+
+        let geolocation = AppConfig::load_from(&path).unwrap().geolocation;
+        assert!(!geolocation.builtin);
+        assert_eq!(geolocation.custom.len(), 1);
+        assert_eq!(geolocation.custom[0].city, "/city");
+        assert!(AppConfig::default().geolocation.builtin);
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "host_label = \"from-file\"\nsite = \"Berlin office\"\n",
+        )
+        .unwrap();
+
+        let mut config = AppConfig::load_from(&path).unwrap();
+        let env = |key: &str| match key {
+            "NETRUNNER_HOST_LABEL" => Some("from-env".to_string()),
+            "NETRUNNER_SITE" => Some(String::new()),
+            "NETRUNNER_MONTHLY_DATA_BUDGET" => Some("20GB".to_string()),
+            "NETRUNNER_NTFY" => Some("netrunner-alerts".to_string()),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+        assert_eq!(config.host_label.as_deref(), Some("from-env"));
+        assert_eq!(config.ntfy.as_deref(), Some("netrunner-alerts"));
+        assert_eq!(config.pushover, None);
+        // Empty variables leave the file's value alone
+        assert_eq!(config.site.as_deref(), Some("Berlin office"));
+        assert_eq!(
+            config.monthly_data_budget.map(|budget| budget.0),
+            Some(20_000_000_000)
+        );
+
+        let bad = |key: &str| (key == "NETRUNNER_MONTHLY_DATA_BUDGET").then(|| "lots".to_string());
+        assert!(AppConfig::default().apply_env(bad).is_err());
+    }
+
+    #[test]
+    fn test_email_from_file_and_environment() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[email]\nserver = \"smtp.example.com\"\nfrom = \"netrunner@example.com\"\nto = [\"ops@example.com\"]\ndigest = true\npassword = \"ignored\"\n",
+        )
+        .unwrap();
+
+        let mut config = AppConfig::load_from(&path).unwrap();
+        let email = config.email.as_ref().unwrap();
+        assert_eq!(email.server, "smtp.example.com");
+        assert_eq!(email.port, None);
+        assert_eq!(email.to, vec!["ops@example.com"]);
+        assert!(email.digest);
+        assert_eq!(
+            email.password, None,
+            "passwords only come from the environment"
+        );
+
+        let env = |key: &str| match key {
+            "NETRUNNER_SMTP_USERNAME" => Some("alerts".to_string()),
+            "NETRUNNER_SMTP_PASSWORD" => Some("hunter2".to_string()),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+        let email = config.email.as_ref().unwrap();
+        assert_eq!(email.username.as_deref(), Some("alerts"));
+        assert_eq!(email.password.as_deref(), Some("hunter2"));
+        assert!(!format!("{:?}", email).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_server_list_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "server_list = \"/etc/netrunner/servers.toml\"\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(
+            config.server_list,
+            Some(PathBuf::from("/etc/netrunner/servers.toml"))
+        );
+    }
+
+    #[test]
+    fn test_data_dir_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "data_dir = \"/mnt/nas/netrunner\"\n").unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.data_dir, Some(PathBuf::from("/mnt/nas/netrunner")));
+    }
+
+    #[test]
+    fn test_dns_domains_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "dns_domains = [\"intranet.example.com\", \"github.com\"]\n",
+        )
+        .unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(
+            config.dns_domains,
+            vec!["intranet.example.com", "github.com"]
+        );
+        assert!(AppConfig::default().dns_domains.is_empty());
+    }
+
+    #[test]
+    fn test_medians_url_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "medians_url = \"https://stats.example.com/medians.json\"\n",
+        )
+        .unwrap();
+
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(
+            config.medians_url.as_deref(),
+            Some("https://stats.example.com/medians.json")
+        );
+        assert_eq!(AppConfig::default().medians_url, None);
+    }
+
+    #[test]
+    fn test_server_filter_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[servers]\nonly_providers = [\"Cloudflare\", \"self-hosted\"]\nexclude_hosts = [\"speedtest.example.net\"]\n",
+        )
+        .unwrap();
+
+        let servers = AppConfig::load_from(&path).unwrap().servers;
+        assert_eq!(
+            servers.only_providers,
+            vec![ServerProvider::Cloudflare, ServerProvider::SelfHosted]
+        );
+        assert!(servers.exclude_providers.is_empty());
+        assert_eq!(servers.exclude_hosts, vec!["speedtest.example.net"]);
+        assert!(AppConfig::default().servers.is_empty());
+    }
+
+    #[test]
+    fn test_report_target_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "[report]\nurl = \"https://stats.example.com/api\"\ntoken = \"abc123\"\n",
+        )
+        .unwrap();
+
+        let report = AppConfig::load_from(&path).unwrap().report.unwrap();
+        assert_eq!(report.url, "https://stats.example.com/api");
+        assert_eq!(report.token.as_deref(), Some("abc123"));
+        assert_eq!(report.client_id, None);
+    }
+
+    #[test]
+    fn test_invalid_file_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "max_tests_per_hour = \"lots\"\n").unwrap();
+
+        assert!(AppConfig::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_menu_settings_keep_the_rest_of_the_file() {
+        use crate::modules::types::{ServerCapabilities, ServerProtocol, ServerProvider};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "# Tests per hour\nmax_tests_per_hour = 4\nsize_mb = 5\n\n[plan]\ndownload_mbps = 500\nupload_mbps = 50\n",
+        )
+        .unwrap();
+
+        let mut settings = MenuSettings {
+            animation: false,
+            size_mb: 25,
+            detail: DetailLevel::Detailed,
+            pinned_server: Some(TestServer {
+                name: "Cloudflare".to_string(),
+                url: "https://speed.cloudflare.com".to_string(),
+                location: "Global".to_string(),
+                distance_km: None,
+                latency_ms: Some(12.5),
+                provider: ServerProvider::Cloudflare,
+                protocol: ServerProtocol::Cloudflare,
+                capabilities: ServerCapabilities {
+                    supports_download: true,
+                    supports_upload: true,
+                    supports_latency: true,
+                    max_test_size_mb: 1000,
+                    geographic_weight: 1.0,
+                },
+                quality_score: None,
+                country_code: None,
+                city: None,
+                is_backup: false,
+                endpoints: None,
+            }),
+        };
+        settings.save_to(&path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# Tests per hour\n"));
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.max_tests_per_hour, 4);
+        assert!(config.plan.is_some());
+        assert_eq!(config.animation, Some(false));
+        assert_eq!(config.size_mb, Some(25));
+        assert_eq!(config.detail.as_deref(), Some("detailed"));
+        assert_eq!(config.pinned_server, settings.pinned_server);
+
+        // Going back to automatic selection drops the table
+        settings.pinned_server = None;
+        settings.save_to(&path).unwrap();
+        assert_eq!(AppConfig::load_from(&path).unwrap().pinned_server, None);
+
+        // A missing file is created
+        let path = dir.path().join("new").join(CONFIG_FILE);
+        settings.save_to(&path).unwrap();
+        assert_eq!(AppConfig::load_from(&path).unwrap().size_mb, Some(25));
+    }
+}
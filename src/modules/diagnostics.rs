@@ -1,22 +1,64 @@
 use colored::*;
-use dns_lookup::lookup_host;
+use hickory_resolver::TokioResolver;
 use rand::RngExt as _;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
-use crate::modules::types::{NetworkDiagnostics, RouteHop, TestConfig};
+/// Checked when neither test servers nor `--tls-host` targets are known
+const DEFAULT_TLS_HOSTS: &[&str] = &["speed.cloudflare.com"];
+
+/// Trace target standing for the hosts of the speed test servers
+const SERVER_TRACE_TARGET: &str = "server";
+
+/// Ports tried on the gateway, routers usually serve a web UI or DNS
+const GATEWAY_PORTS: [u16; 3] = [80, 443, 53];
+/// Internet side of the latency baseline
+const INTERNET_BASELINE_TARGET: ([u8; 4], u16) = ([1, 1, 1, 1], 443);
+const BASELINE_PROBES: usize = 10;
+const BASELINE_INTERVAL: Duration = Duration::from_millis(200);
+const BASELINE_TIMEOUT: Duration = Duration::from_secs(1);
+
+use crate::modules::analysis;
+use crate::modules::dns;
+use crate::modules::happy_eyeballs;
+use crate::modules::history::HistoryStorage;
+use crate::modules::ipinfo;
+use crate::modules::localnet;
+use crate::modules::ntp;
+use crate::modules::terminal::print_table;
+use crate::modules::tls;
+use crate::modules::traceroute;
+use crate::modules::types::{
+    AdapterSettings, AsnInfo, ClockCheck, DiagnosticsRecord, DnsIssue, DnsLookup, DnsRecordTiming,
+    DnsSecurityCheck, HappyEyeballsCheck, HappyEyeballsIssue, HostNetwork, LatencyBaseline,
+    LinkLatency, NetworkDiagnostics, ResolverBenchmark, RouteChange, RouteHop, RouteSnapshot,
+    TestConfig, TlsCheck, TlsIssue, TraceProtocol, TracedRoute, WindowsStackCheck,
+    WindowsStackIssue,
+};
 use crate::modules::ui::UI;
+use crate::modules::windows_stack;
 
 pub struct NetworkDiagnosticsTool {
     config: TestConfig,
     ui: UI,
+    rank_causes: bool,
 }
 
 impl NetworkDiagnosticsTool {
     pub fn new(config: TestConfig) -> Self {
         let ui = UI::new(config.clone());
-        Self { config, ui }
+        Self {
+            config,
+            ui,
+            rank_causes: true,
+        }
+    }
+
+    /// Leave ranking probable causes to the caller, which knows more than the diagnostics
+    pub fn without_causes(mut self) -> Self {
+        self.rank_causes = false;
+        self
     }
 
     pub async fn run_diagnostics(&self) -> Result<NetworkDiagnostics, Box<dyn std::error::Error>> {
@@ -37,34 +79,97 @@ impl NetworkDiagnosticsTool {
         // Determine gateway
         let gateway_ip = self.detect_gateway().await?;
 
+        // Compare round trips inside the home network with those beyond it
+        let latency_baseline = self.measure_latency_baseline(gateway_ip).await?;
+
         // Get DNS servers
         let dns_servers = self.detect_dns_servers().await?;
 
+        // Time each record type first, so its first queries are the least likely to be cached
+        let dns_records = self.analyze_dns_records().await?;
+
         // Measure DNS response time
-        let dns_response_time = self.measure_dns_response_time().await?;
+        let (dns_response_time, dns_lookups) = self.measure_dns_response_time().await?;
+
+        // Compare plain and encrypted DNS on public resolvers
+        let resolver_benchmarks = self.benchmark_dns_resolvers().await?;
+
+        // Check DNSSEC validation and look for DNS interception
+        let dns_security = self.check_dns_security().await?;
+
+        // Trace each route and compare it with the previous run
+        let mut routes = Vec::new();
+        for target in self.trace_targets() {
+            let hops = self.trace_route(&target).await?;
+            // Probes on other protocols or ports can hash onto other paths, keep their routes apart
+            let route_key = match self.config.trace.protocol {
+                TraceProtocol::Icmp => target.clone(),
+                protocol => format!("{} {}", target, protocol),
+            };
+            let change = self.detect_route_change(&route_key, &hops);
+            routes.push(TracedRoute {
+                target,
+                hops,
+                change,
+            });
+        }
 
-        // Trace route
-        let route_hops = self.trace_route("8.8.8.8").await?;
+        // Name the networks along the routes and hosting the test servers
+        let server_networks = self.identify_networks(&mut routes).await?;
+        let route_hops = routes
+            .first()
+            .map(|route| route.hops.clone())
+            .unwrap_or_default();
 
         // Check IPv6 availability
         let is_ipv6_available = self.check_ipv6().await?;
 
+        // Race IPv6 against IPv4 the way browsers do, looking for stalled fallbacks
+        let happy_eyeballs = self.check_happy_eyeballs().await?;
+
         // Determine connection type (wired/wireless)
         let connection_type = self.detect_connection_type().await?;
 
         // Get network interface
         let network_interface = self.detect_network_interface().await?;
 
+        // Windows TCP, offload and power settings that commonly hold connections back
+        let windows_stack = self.check_windows_stack().await?;
+
+        // Check TLS against test servers and user-provided hosts
+        let tls_checks = self.check_tls().await?;
+
+        // Compare the system clock with NTP, skew breaks TLS and history timestamps
+        let clock_check = self.check_clock().await?;
+
         let diagnostics = NetworkDiagnostics {
             gateway_ip,
             dns_servers,
             dns_response_time_ms: dns_response_time,
+            dns_lookups,
+            dns_records,
             route_hops,
             is_ipv6_available,
             connection_type: Some(connection_type),
             network_interface: Some(network_interface),
+            tls_checks,
+            resolver_benchmarks,
+            dns_security,
+            routes,
+            server_networks,
+            latency_baseline,
+            clock_check,
+            happy_eyeballs,
+            windows_stack,
         };
 
+        // Kept so DNS latency, route length and IPv6 can be trended with `history diag`
+        if let Err(e) = HistoryStorage::new().and_then(|storage| {
+            storage.save_diagnostics(&DiagnosticsRecord::new(diagnostics.clone()))
+        }) {
+            eprintln!("Failed to save diagnostics: {}", e);
+        }
+
         // Display results with enhanced visuals
         if !self.config.json_output {
             // Show completion animation
@@ -94,14 +199,10 @@ impl NetworkDiagnosticsTool {
             None
         };
 
-        // This is a simplified approach. In a real implementation, you'd:
-        // 1. On Windows: Use "ipconfig" and parse the "Default Gateway" line
-        // 2. On Linux/macOS: Use "ip route | grep default" or "netstat -nr | grep default"
-
-        // For demonstration, we'll simulate it
-        sleep(Duration::from_millis(800)).await;
-
-        let gateway = Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)));
+        let gateway = localnet::detect()
+            .await
+            .and_then(|network| network.gateway)
+            .map(IpAddr::V4);
 
         if let Some(pb) = pb {
             if let Some(gw) = gateway {
@@ -114,6 +215,57 @@ impl NetworkDiagnosticsTool {
         Ok(gateway)
     }
 
+    async fn measure_latency_baseline(
+        &self,
+        gateway: Option<IpAddr>,
+    ) -> Result<LatencyBaseline, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui
+                .show_info("🏠 Comparing home and internet latency...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("TIMING LOCAL AND REMOTE NODES"),
+            )
+        } else {
+            None
+        };
+
+        let gateway_target = match gateway {
+            Some(address) => gateway_port(address)
+                .await
+                .map(|port| SocketAddr::new(address, port)),
+            None => None,
+        };
+        let (gateway, internet) = tokio::join!(
+            async {
+                match gateway_target {
+                    Some(target) => Some(probe_link(target).await),
+                    None => None,
+                }
+            },
+            probe_link(SocketAddr::from(INTERNET_BASELINE_TARGET))
+        );
+        let baseline = LatencyBaseline {
+            gateway,
+            internet: Some(internet),
+        };
+
+        if let Some(pb) = pb {
+            match baseline.bottleneck() {
+                Some(bottleneck) => pb.finish_with_message(format!(
+                    "⟨⟨⟨ LATENCY SOURCE: {} ⟩⟩⟩",
+                    bottleneck.to_string().to_uppercase()
+                )),
+                None => pb.finish_with_message("⟨⟨⟨ LATENCY BASELINE: NOMINAL ⟩⟩⟩"),
+            }
+        }
+
+        Ok(baseline)
+    }
+
     async fn detect_dns_servers(&self) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_info("🔍 Probing DNS infrastructure...")?;
@@ -151,7 +303,11 @@ impl NetworkDiagnosticsTool {
         Ok(dns_servers)
     }
 
-    async fn measure_dns_response_time(&self) -> Result<f64, Box<dyn std::error::Error>> {
+    /// Resolve every configured name at once with the system's resolvers, returning
+    /// the average over those that resolved and each name's timing
+    async fn measure_dns_response_time(
+        &self,
+    ) -> Result<(f64, Vec<DnsLookup>), Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_info("Measuring DNS response time...")?;
         }
@@ -165,47 +321,50 @@ impl NetworkDiagnosticsTool {
             None
         };
 
-        let domains = vec![
-            "google.com",
-            "amazon.com",
-            "facebook.com",
-            "microsoft.com",
-            "apple.com",
-        ];
-
-        let mut total_time = 0.0;
-        let mut successful_lookups = 0;
-
-        for domain in domains {
-            let start = Instant::now();
-            match lookup_host(domain) {
-                Ok(_) => {
-                    let duration = start.elapsed().as_millis() as f64;
-                    total_time += duration;
-                    successful_lookups += 1;
-
-                    if let Some(ref pb) = pb {
-                        pb.set_message(format!(
-                            "⟨⟨⟨ NEURAL LINK TO {} ESTABLISHED: {:.2}ms ⟩⟩⟩",
-                            domain, duration
-                        ));
+        let lookups = match TokioResolver::builder_tokio().and_then(|builder| builder.build()) {
+            Ok(resolver) => {
+                futures::future::join_all(self.config.dns_domains.iter().map(|domain| {
+                    let resolver = &resolver;
+                    async move {
+                        let start = Instant::now();
+                        let answer = resolver.lookup_ip(domain.as_str()).await;
+                        let time_ms = start.elapsed().as_secs_f64() * 1000.0;
+                        DnsLookup {
+                            domain: domain.clone(),
+                            time_ms: answer.is_ok().then_some(time_ms),
+                            error: answer.err().map(|e| e.to_string()),
+                        }
                     }
-                }
-                Err(e) => {
-                    if !self.config.json_output {
-                        self.ui
-                            .show_error(&format!("Failed to resolve {}: {}", domain, e))?;
-                    }
-                }
+                }))
+                .await
             }
+            // Without a usable resolver configuration every name counts as failed
+            Err(e) => self
+                .config
+                .dns_domains
+                .iter()
+                .map(|domain| DnsLookup {
+                    domain: domain.clone(),
+                    time_ms: None,
+                    error: Some(format!("no system resolver: {}", e)),
+                })
+                .collect(),
+        };
 
-            sleep(Duration::from_millis(100)).await;
+        if !self.config.json_output {
+            for lookup in &lookups {
+                if let Some(error) = &lookup.error {
+                    self.ui
+                        .show_error(&format!("Failed to resolve {}: {}", lookup.domain, error))?;
+                }
+            }
         }
 
-        let avg_time = if successful_lookups > 0 {
-            total_time / successful_lookups as f64
-        } else {
+        let times: Vec<f64> = lookups.iter().filter_map(|lookup| lookup.time_ms).collect();
+        let avg_time = if times.is_empty() {
             0.0
+        } else {
+            times.iter().sum::<f64>() / times.len() as f64
         };
 
         if let Some(pb) = pb {
@@ -215,25 +374,44 @@ impl NetworkDiagnosticsTool {
             ));
         }
 
-        Ok(avg_time)
+        Ok((avg_time, lookups))
+    }
+
+    /// `--trace-targets` with `server` expanded to the speed test server hosts
+    fn trace_targets(&self) -> Vec<String> {
+        let mut targets: Vec<String> = Vec::new();
+        for target in &self.config.trace_targets {
+            let expanded = if target == SERVER_TRACE_TARGET {
+                self.config.server_hosts.clone()
+            } else {
+                vec![target.clone()]
+            };
+            for target in expanded {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        targets
     }
 
     async fn trace_route(&self, target: &str) -> Result<Vec<RouteHop>, Box<dyn std::error::Error>> {
         if !self.config.json_output {
-            self.ui
-                .show_info(&format!("Tracing route to {}...", target))?;
+            self.ui.show_info(&format!(
+                "Tracing route to {} over {}...",
+                target, self.config.trace.protocol
+            ))?;
         }
 
-        let max_hops = 15;
-        let pb =
-            if !self.config.json_output && self.config.animation_enabled {
-                Some(self.ui.create_progress_bar(
-                    max_hops,
-                    &format!("🌐 Neural pathfinding to {}...", target),
-                ))
-            } else {
-                None
-            };
+        let options = self.config.trace;
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_progress_bar(
+                u64::from(options.max_hops),
+                &format!("🌐 Neural pathfinding to {}...", target),
+            ))
+        } else {
+            None
+        };
 
         // Show neural network mapping animation
         if !self.config.json_output && self.config.animation_enabled {
@@ -241,101 +419,45 @@ impl NetworkDiagnosticsTool {
             println!();
         }
 
-        let mut hops = Vec::new();
-
-        // This is a simplified approach. In a real implementation, you'd:
-        // 1. Use a proper traceroute implementation or library
-        // 2. On Windows: Use "tracert" command
-        // 3. On Linux/macOS: Use "traceroute" command
-
-        // For demonstration, we'll simulate traceroute
-        for hop_number in 1..=max_hops {
-            // Simulate network delay
-            let mut rng = rand::rng();
-            let delay = if hop_number < 3 {
-                // Local network hops are faster
-                rng.random_range(1..10)
-            } else if hop_number < 8 {
-                // ISP network
-                rng.random_range(10..50)
-            } else {
-                // Internet
-                rng.random_range(50..150)
-            };
-
-            sleep(Duration::from_millis(delay)).await;
-
-            // Simulate sometimes missing hops
-            let address = if hop_number != 6 && hop_number != 9 {
-                let fake_ip = format!("192.168.{}.{}", hop_number, hop_number * 10);
-                Some(fake_ip.parse::<IpAddr>()?)
-            } else {
-                None
-            };
-
-            let hostname = None;
-
-            let response_time = if address.is_some() {
-                Some(delay as f64)
-            } else {
-                None
-            };
-
-            let hop = RouteHop {
-                hop_number: hop_number as u32,
-                address,
-                hostname,
-                response_time_ms: response_time,
-            };
-
-            // Store address and response time before moving hop
-            let hop_addr = hop.address;
-            let hop_resp_time = hop.response_time_ms;
-
-            hops.push(hop);
-
-            if let Some(ref pb) = pb {
-                if let Some(addr) = &hop_addr {
-                    pb.set_message(format!(
+        let address = tokio::net::lookup_host((target, 0))
+            .await
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .map(|addr| addr.ip());
+
+        let hop_pb = pb.clone();
+        let on_hop = move |hop: &RouteHop| {
+            if let Some(pb) = &hop_pb {
+                match (hop.address, hop.response_time_ms) {
+                    (Some(addr), time) => pb.set_message(format!(
                         "⟨⟨⟨ NEURAL NODE {}: {} ({:.2}ms) - SIGNAL ACQUIRED ⟩⟩⟩",
-                        hop_number,
+                        hop.hop_number,
                         addr,
-                        hop_resp_time.unwrap_or(0.0)
-                    ));
-                } else {
-                    pb.set_message(format!(
+                        time.unwrap_or(0.0)
+                    )),
+                    (None, _) => pb.set_message(format!(
                         "⟨⟨⟨ NEURAL NODE {}: ░░░ ENCRYPTED ░░░ ⟩⟩⟩",
-                        hop_number
-                    ));
+                        hop.hop_number
+                    )),
                 }
                 pb.inc(1);
             }
+        };
 
-            // Show packet flow for each hop
-            if !self.config.json_output && self.config.animation_enabled {
-                tokio::time::sleep(Duration::from_millis(50)).await;
-            }
-
-            // Last hop should be the target
-            if hop_number == max_hops {
-                // Simulate target destination
-                let target_ip = IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8));
-                hops.pop(); // Remove the last simulated hop
-                hops.push(RouteHop {
-                    hop_number: hop_number as u32,
-                    address: Some(target_ip),
-                    hostname: Some(target.to_string()),
-                    response_time_ms: Some(delay as f64),
-                });
-
-                if let Some(ref pb) = pb {
-                    pb.set_message(format!(
-                        "⟨⟨⟨ NEURAL NODE {}: {} ({:.2}ms) - DESTINATION REACHED ⟩⟩⟩",
-                        hop_number, target_ip, delay as f64
-                    ));
+        let traced = match address {
+            Some(address) => traceroute::trace(address, options, on_hop).await,
+            None => Err(format!("Could not resolve {}", target).into()),
+        };
+        let hops = match traced {
+            Ok(hops) => hops,
+            Err(e) => {
+                if !self.config.json_output {
+                    self.ui
+                        .show_error(&format!("Route trace unavailable: {}", e))?;
                 }
+                Vec::new()
             }
-        }
+        };
 
         if let Some(pb) = pb {
             pb.finish_with_message(format!(
@@ -348,6 +470,71 @@ impl NetworkDiagnosticsTool {
         Ok(hops)
     }
 
+    /// Diff the route against the last one traced to `target` and save it for the next run
+    fn detect_route_change(&self, target: &str, hops: &[RouteHop]) -> Option<RouteChange> {
+        if hops.iter().all(|hop| hop.address.is_none()) {
+            return None;
+        }
+
+        let storage = HistoryStorage::new().ok()?;
+        let route = RouteSnapshot::new(target, hops);
+        let change = match storage.get_latest_route(target) {
+            Ok(Some(previous)) => route.diff(&previous),
+            _ => None,
+        };
+        if let Err(e) = storage.save_route(&route) {
+            eprintln!("Failed to save route: {}", e);
+        }
+
+        change
+    }
+
+    async fn identify_networks(
+        &self,
+        routes: &mut [TracedRoute],
+    ) -> Result<Vec<HostNetwork>, Box<dyn std::error::Error>> {
+        if routes.iter().all(|route| route.hops.is_empty()) && self.config.server_hosts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if !self.config.json_output {
+            self.ui
+                .show_info("🏢 Identifying network operators and locations...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("DECRYPTING NETWORK OWNERSHIP RECORDS"),
+            )
+        } else {
+            None
+        };
+
+        let enrich_routes = futures::future::join_all(
+            routes
+                .iter_mut()
+                .map(|route| ipinfo::enrich_hops(&mut route.hops)),
+        );
+        let (_, server_networks) = tokio::join!(
+            enrich_routes,
+            ipinfo::lookup_hosts(&self.config.server_hosts)
+        );
+
+        if let Some(pb) = pb {
+            let mut networks: Vec<u32> = routes
+                .iter()
+                .flat_map(|route| &route.hops)
+                .filter_map(|hop| hop.network.as_ref().map(|network| network.asn))
+                .collect();
+            networks.sort_unstable();
+            networks.dedup();
+            pb.finish_with_message(format!("⟨⟨⟨ NETWORKS TRAVERSED: {} ⟩⟩⟩", networks.len()));
+        }
+
+        Ok(server_networks)
+    }
+
     async fn check_ipv6(&self) -> Result<bool, Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_info("Checking IPv6 connectivity...")?;
@@ -379,6 +566,221 @@ impl NetworkDiagnosticsTool {
         Ok(ipv6_available)
     }
 
+    async fn analyze_dns_records(
+        &self,
+    ) -> Result<Vec<DnsRecordTiming>, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui
+                .show_info("🧪 Timing A, AAAA and CNAME lookups cold and cached...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_wave_spinner("PROBING RESOLVER CACHE LAYERS"))
+        } else {
+            None
+        };
+
+        let timings = dns::record_breakdown(&self.config.dns_domains, Duration::from_secs(3)).await;
+
+        if let Some(pb) = pb {
+            let failed = timings
+                .iter()
+                .filter(|timing| timing.error.is_some())
+                .count();
+            if failed == 0 {
+                pb.finish_with_message("⟨⟨⟨ RESOLVER CACHE: MAPPED ⟩⟩⟩");
+            } else {
+                pb.finish_with_message(format!(
+                    "⟨⟨⟨ RESOLVER CACHE: {}/{} QUERIES FAILED ⟩⟩⟩",
+                    failed,
+                    timings.len()
+                ));
+            }
+        }
+
+        Ok(timings)
+    }
+
+    async fn check_happy_eyeballs(
+        &self,
+    ) -> Result<Vec<HappyEyeballsCheck>, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui
+                .show_info("👁️ Racing IPv6 against IPv4 on dual-stack sites...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_pacman_spinner("RACING DUAL-STACK EYEBALLS"))
+        } else {
+            None
+        };
+
+        let checks = futures::future::join_all(
+            happy_eyeballs::SITES
+                .iter()
+                .map(|site| happy_eyeballs::check_site(site, 443, Duration::from_secs(5))),
+        )
+        .await;
+
+        if let Some(pb) = pb {
+            let flagged = checks.iter().filter(|c| !c.issues.is_empty()).count();
+            if flagged == 0 {
+                pb.finish_with_message("⟨⟨⟨ DUAL-STACK FALLBACK: NOMINAL ⟩⟩⟩");
+            } else {
+                pb.finish_with_message(format!(
+                    "⟨⟨⟨ DUAL-STACK FALLBACK: {} OF {} SITES FLAGGED ⟩⟩⟩",
+                    flagged,
+                    checks.len()
+                ));
+            }
+        }
+
+        Ok(checks)
+    }
+
+    async fn benchmark_dns_resolvers(
+        &self,
+    ) -> Result<Vec<ResolverBenchmark>, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui
+                .show_info("🧬 Benchmarking plain and encrypted DNS resolvers...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_dna_helix_spinner("RACING ENCRYPTED DNS MATRICES"),
+            )
+        } else {
+            None
+        };
+
+        let benchmarks = dns::benchmark_resolvers(Duration::from_secs(3)).await;
+
+        if let Some(pb) = pb {
+            match fastest_encrypted_resolver(&benchmarks) {
+                Some(fastest) => pb.finish_with_message(format!(
+                    "⟨⟨⟨ FASTEST ENCRYPTED DNS: {} {} ⟩⟩⟩",
+                    fastest.provider.to_uppercase(),
+                    fastest.transport
+                )),
+                None => pb.finish_with_message("⟨⟨⟨ ENCRYPTED DNS: UNREACHABLE ⟩⟩⟩"),
+            }
+        }
+
+        Ok(benchmarks)
+    }
+
+    async fn check_dns_security(&self) -> Result<DnsSecurityCheck, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_info("🛡️ Verifying DNS integrity...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("SCANNING FOR DNS INTERCEPTORS"),
+            )
+        } else {
+            None
+        };
+
+        let check = dns::check_security(Duration::from_secs(3)).await;
+
+        if let Some(pb) = pb {
+            if check.issues.is_empty() {
+                pb.finish_with_message("⟨⟨⟨ DNS INTEGRITY: VERIFIED ⟩⟩⟩");
+            } else {
+                pb.finish_with_message(format!(
+                    "⟨⟨⟨ DNS INTEGRITY: {} ANOMALIES ⟩⟩⟩",
+                    check.issues.len()
+                ));
+            }
+        }
+
+        Ok(check)
+    }
+
+    async fn check_tls(&self) -> Result<Vec<TlsCheck>, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_info("🔐 Verifying TLS handshakes...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("NEGOTIATING ENCRYPTED CHANNELS"),
+            )
+        } else {
+            None
+        };
+
+        let mut targets = Vec::new();
+        for target in &self.config.tls_hosts {
+            match tls::parse_target(target) {
+                Ok(target) if !targets.contains(&target) => targets.push(target),
+                Ok(_) => {}
+                Err(e) if !self.config.json_output => self.ui.show_error(&e)?,
+                Err(_) => {}
+            }
+        }
+        if targets.is_empty() {
+            targets = DEFAULT_TLS_HOSTS
+                .iter()
+                .map(|host| (host.to_string(), 443))
+                .collect();
+        }
+
+        let checks = futures::future::join_all(
+            targets
+                .iter()
+                .map(|(host, port)| tls::check_host(host, *port, Duration::from_secs(10))),
+        )
+        .await;
+
+        if let Some(pb) = pb {
+            let flagged = checks.iter().filter(|c| !c.issues.is_empty()).count();
+            pb.finish_with_message(format!(
+                "⟨⟨⟨ ENCRYPTED CHANNELS: {} CHECKED, {} FLAGGED ⟩⟩⟩",
+                checks.len(),
+                flagged
+            ));
+        }
+
+        Ok(checks)
+    }
+
+    async fn check_clock(&self) -> Result<Option<ClockCheck>, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui
+                .show_info("🕰️ Checking system clock against NTP...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("SYNCHRONIZING CHRONO MATRIX"),
+            )
+        } else {
+            None
+        };
+
+        let check = ntp::check_clock(Duration::from_secs(3)).await;
+
+        if let Some(pb) = pb {
+            match &check {
+                Some(check) if check.is_skewed() => pb.finish_with_message(format!(
+                    "⟨⟨⟨ CHRONO DRIFT: {} ⟩⟩⟩",
+                    format_offset(check.offset_ms)
+                )),
+                Some(_) => pb.finish_with_message("⟨⟨⟨ CHRONO MATRIX IN SYNC ⟩⟩⟩"),
+                None => pb.finish_with_message("⟨⟨⟨ TIME SERVERS: UNREACHABLE ⟩⟩⟩"),
+            }
+        }
+
+        Ok(check)
+    }
+
     async fn detect_connection_type(&self) -> Result<String, Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_info("Detecting connection type...")?;
@@ -440,6 +842,44 @@ impl NetworkDiagnosticsTool {
         Ok(interface)
     }
 
+    async fn check_windows_stack(
+        &self,
+    ) -> Result<Option<WindowsStackCheck>, Box<dyn std::error::Error>> {
+        if !cfg!(windows) {
+            return Ok(None);
+        }
+        if !self.config.json_output {
+            self.ui
+                .show_info("Inspecting Windows TCP, offload and power settings...")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner("PROBING WINDOWS NETWORK STACK"),
+            )
+        } else {
+            None
+        };
+
+        let check = windows_stack::check().await;
+
+        if let Some(pb) = pb {
+            match &check {
+                Some(check) if check.issues.is_empty() => {
+                    pb.finish_with_message("⟨⟨⟨ NETWORK STACK: NOMINAL ⟩⟩⟩")
+                }
+                Some(check) => pb.finish_with_message(format!(
+                    "⟨⟨⟨ NETWORK STACK: {} SETTINGS FLAGGED ⟩⟩⟩",
+                    check.issues.len()
+                )),
+                None => pb.finish_with_message("⟨⟨⟨ NETWORK STACK: UNREADABLE ⟩⟩⟩"),
+            }
+        }
+
+        Ok(check)
+    }
+
     fn display_diagnostics_results(
         &self,
         diagnostics: &NetworkDiagnostics,
@@ -524,6 +964,17 @@ impl NetworkDiagnosticsTool {
             )),
         ]));
 
+        // Each name resolved, they ran at the same time
+        for lookup in &diagnostics.dns_lookups {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!("   ↳ {}", lookup.domain)),
+                Cell::new(&match lookup.time_ms {
+                    Some(ms) => format!("{:.2}ms", ms),
+                    None => format!("{}", "failed".red()),
+                }),
+            ]));
+        }
+
         // IPv6 Availability with enhanced display
         table.add_row(Row::new(vec![
             Cell::new("🛰️ IPv6 Protocol").style_spec("Fb"),
@@ -555,11 +1006,80 @@ impl NetworkDiagnosticsTool {
             ]));
         }
 
+        // Clock offset against NTP
+        let clock = match &diagnostics.clock_check {
+            Some(check) if check.is_skewed() => {
+                format!("⚠️ {} ({})", format_offset(check.offset_ms), check.server)
+            }
+            Some(check) => format!("✅ in sync, {:+.0}ms ({})", check.offset_ms, check.server),
+            None => "❓ no NTP server answered".to_string(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new("🕰️ Chrono Sync").style_spec("Fb"),
+            Cell::new(&clock),
+        ]));
+
         // Print the table
-        table.printstd();
+        print_table(&table)?;
+
+        if diagnostics.latency_baseline.gateway.is_some()
+            || diagnostics.latency_baseline.internet.is_some()
+        {
+            println!(
+                "\n{}",
+                " 🏠 HOME VS INTERNET 🌍 "
+                    .on_bright_magenta()
+                    .white()
+                    .bold()
+            );
+
+            let mut latency_table = Table::new();
+            latency_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            latency_table.add_row(Row::new(vec![
+                Cell::new("🔗 Link").style_spec("Fb"),
+                Cell::new("📍 Target").style_spec("Fb"),
+                Cell::new("⏱️ Median").style_spec("Fb"),
+                Cell::new("📈 Jitter").style_spec("Fb"),
+                Cell::new("📉 Loss").style_spec("Fb"),
+            ]));
+
+            let links = [
+                ("🏠 Gateway", &diagnostics.latency_baseline.gateway),
+                ("🌍 Internet", &diagnostics.latency_baseline.internet),
+            ];
+            for (label, link) in links {
+                let Some(link) = link else {
+                    latency_table.add_row(Row::new(vec![
+                        Cell::new(label),
+                        Cell::new("not reachable"),
+                        Cell::new("-"),
+                        Cell::new("-"),
+                        Cell::new("-"),
+                    ]));
+                    continue;
+                };
+                latency_table.add_row(Row::new(vec![
+                    Cell::new(label),
+                    Cell::new(&SocketAddr::new(link.address, link.port).to_string()),
+                    Cell::new(
+                        &link
+                            .median_ms
+                            .map_or("-".to_string(), |ms| format!("{:.1}ms", ms)),
+                    ),
+                    Cell::new(&format!("{:.1}ms", link.jitter_ms)),
+                    Cell::new(&format!("{:.0}%", link.loss_percent())),
+                ]));
+            }
+
+            print_table(&latency_table)?;
+        }
 
         // Display route trace if we have hops
-        if !diagnostics.route_hops.is_empty() {
+        if diagnostics
+            .routes
+            .iter()
+            .any(|route| !route.hops.is_empty())
+        {
             println!(
                 "\n{}",
                 " 🌐 NEURAL PATHWAY MAPPING 🌐 "
@@ -580,44 +1100,360 @@ impl NetworkDiagnosticsTool {
                 "╚═══════════════════════════════════════════╝".bright_cyan()
             );
 
-            let mut trace_table = Table::new();
-            trace_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            for route in diagnostics
+                .routes
+                .iter()
+                .filter(|route| !route.hops.is_empty())
+            {
+                self.display_route(route)?;
+            }
+        }
 
-            // Add cyberpunk header
-            trace_table.add_row(Row::new(vec![
-                Cell::new("🔗 Node").style_spec("Fb"),
-                Cell::new("📍 Neural Address").style_spec("Fb"),
+        if !diagnostics.server_networks.is_empty() {
+            println!(
+                "\n{}",
+                " 🛰️ TEST SERVER NETWORKS 🛰️ "
+                    .on_bright_magenta()
+                    .white()
+                    .bold()
+            );
+
+            let mut server_table = Table::new();
+            server_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            server_table.add_row(Row::new(vec![
+                Cell::new("🖥️ Server").style_spec("Fb"),
+                Cell::new("📍 Address").style_spec("Fb"),
                 Cell::new("🏷️ Identity").style_spec("Fb"),
-                Cell::new("⚡ Signal Delay").style_spec("Fb"),
+                Cell::new("🏢 Network").style_spec("Fb"),
             ]));
 
-            for hop in &diagnostics.route_hops {
-                let addr = hop.address.map_or("⟨⟨⟨ ENCRYPTED ⟩⟩⟩".to_string(), |a| {
-                    format!("{} 🔗", a)
-                });
-                let hostname = hop
-                    .hostname
-                    .clone()
-                    .unwrap_or_else(|| "⟨ANONYMOUS⟩".to_string());
-                let time = hop.response_time_ms.map_or("🔒 STEALTH".to_string(), |t| {
-                    if t < 50.0 {
-                        format!("{:.2}ms ⚡", t)
-                    } else if t < 100.0 {
-                        format!("{:.2}ms ⚠️", t)
-                    } else {
-                        format!("{:.2}ms 🐌", t)
+            for server in &diagnostics.server_networks {
+                server_table.add_row(Row::new(vec![
+                    Cell::new(&server.host),
+                    Cell::new(
+                        &server
+                            .address
+                            .map_or("-".to_string(), |addr| addr.to_string()),
+                    ),
+                    Cell::new(server.hostname.as_deref().unwrap_or("-")),
+                    Cell::new(&format_network(server.network.as_ref())),
+                ]));
+            }
+
+            print_table(&server_table)?;
+        }
+
+        if !diagnostics.resolver_benchmarks.is_empty() {
+            println!(
+                "\n{}",
+                " 🧬 DNS RESOLVER BENCHMARK 🧬 "
+                    .on_bright_magenta()
+                    .white()
+                    .bold()
+            );
+
+            let mut dns_table = Table::new();
+            dns_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            dns_table.add_row(Row::new(vec![
+                Cell::new("🏢 Resolver").style_spec("Fb"),
+                Cell::new("🔒 Transport").style_spec("Fb"),
+                Cell::new("⚡ Median").style_spec("Fb"),
+                Cell::new("✅ Answered").style_spec("Fb"),
+            ]));
+
+            for benchmark in &diagnostics.resolver_benchmarks {
+                let median = match (benchmark.median_ms, &benchmark.error) {
+                    (Some(ms), _) => format!("{:.1}ms", ms),
+                    (None, Some(error)) => format!("❌ {}", error),
+                    (None, None) => "-".to_string(),
+                };
+                dns_table.add_row(Row::new(vec![
+                    Cell::new(&benchmark.provider),
+                    Cell::new(&benchmark.transport.to_string()),
+                    Cell::new(&median),
+                    Cell::new(&format!(
+                        "{}/{}",
+                        benchmark.successful_queries, benchmark.queries
+                    )),
+                ]));
+            }
+
+            print_table(&dns_table)?;
+        }
+
+        if !diagnostics.dns_records.is_empty() {
+            println!(
+                "\n{}",
+                " 🧪 DNS RECORD BREAKDOWN 🧪 "
+                    .on_bright_blue()
+                    .white()
+                    .bold()
+            );
+
+            let mut records_table = Table::new();
+            records_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            records_table.add_row(Row::new(vec![
+                Cell::new("🌐 Name").style_spec("Fb"),
+                Cell::new("📇 Record").style_spec("Fb"),
+                Cell::new("🧊 Cold").style_spec("Fb"),
+                Cell::new("🔥 Cached").style_spec("Fb"),
+                Cell::new("💾 Saved").style_spec("Fb"),
+                Cell::new("📋 Answers").style_spec("Fb"),
+            ]));
+
+            for timing in &diagnostics.dns_records {
+                let ms = |time: Option<f64>| match time {
+                    Some(ms) => format!("{:.1}ms", ms),
+                    None => "-".to_string(),
+                };
+                let answers = match (&timing.error, timing.answers.is_empty()) {
+                    (Some(error), _) => format!("❌ {}", error),
+                    (None, true) => "none".dimmed().to_string(),
+                    (None, false) => timing.answers.join(" → "),
+                };
+                records_table.add_row(Row::new(vec![
+                    Cell::new(&timing.domain),
+                    Cell::new(&timing.kind.to_string()),
+                    Cell::new(&ms(timing.cold_ms)),
+                    Cell::new(&ms(timing.warm_ms)),
+                    Cell::new(&ms(timing.cache_saving_ms())),
+                    Cell::new(&answers),
+                ]));
+            }
+
+            print_table(&records_table)?;
+        }
+
+        let security = &diagnostics.dns_security;
+        println!(
+            "\n{}",
+            " 🛡️ DNS INTEGRITY 🛡️ ".on_bright_magenta().white().bold()
+        );
+
+        let mut security_table = Table::new();
+        security_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        let flag = |ok: bool| if ok { "✅" } else { "⚠️" };
+        let resolver = security
+            .system_resolver
+            .map_or("unknown".to_string(), |ip| ip.to_string());
+        let dnssec = match security.dnssec_validating {
+            Some(true) => "✅ validated".to_string(),
+            Some(false) => "⚠️ not validated".to_string(),
+            None => "❔ could not be checked".to_string(),
+        };
+        let nxdomain = !security.issues.contains(&DnsIssue::NxdomainRewritten);
+        let intercepted = security.issues.contains(&DnsIssue::QueriesIntercepted);
+        let answers = if security.mismatched_domains.is_empty() {
+            "✅ match trusted resolver".to_string()
+        } else {
+            format!("⚠️ differ for {}", security.mismatched_domains.join(", "))
+        };
+
+        security_table.add_row(Row::new(vec![
+            Cell::new("🖥️ System resolver").style_spec("Fb"),
+            Cell::new(&resolver),
+        ]));
+        security_table.add_row(Row::new(vec![
+            Cell::new("🔏 DNSSEC").style_spec("Fb"),
+            Cell::new(&dnssec),
+        ]));
+        security_table.add_row(Row::new(vec![
+            Cell::new("👻 Missing domains").style_spec("Fb"),
+            Cell::new(&format!(
+                "{} {}",
+                flag(nxdomain),
+                if nxdomain { "NXDOMAIN" } else { "rewritten" }
+            )),
+        ]));
+        security_table.add_row(Row::new(vec![
+            Cell::new("🎯 Answers").style_spec("Fb"),
+            Cell::new(&answers),
+        ]));
+        security_table.add_row(Row::new(vec![
+            Cell::new("🕵️ Interception").style_spec("Fb"),
+            Cell::new(&format!(
+                "{} {}",
+                flag(!intercepted),
+                if intercepted {
+                    "detected"
+                } else {
+                    "none detected"
+                }
+            )),
+        ]));
+
+        print_table(&security_table)?;
+
+        if !diagnostics.tls_checks.is_empty() {
+            println!(
+                "\n{}",
+                " 🔐 ENCRYPTED CHANNEL ANALYSIS 🔐 "
+                    .on_bright_magenta()
+                    .white()
+                    .bold()
+            );
+
+            let mut tls_table = Table::new();
+            tls_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            tls_table.add_row(Row::new(vec![
+                Cell::new("🖥️ Host").style_spec("Fb"),
+                Cell::new("🔒 Protocol").style_spec("Fb"),
+                Cell::new("🧬 Cipher").style_spec("Fb"),
+                Cell::new("⚡ Handshake").style_spec("Fb"),
+                Cell::new("📜 Certificate").style_spec("Fb"),
+            ]));
+
+            for check in &diagnostics.tls_checks {
+                let host = if check.port == 443 {
+                    check.host.clone()
+                } else {
+                    format!("{}:{}", check.host, check.port)
+                };
+                let certificate = match (&check.error, check.certificate_expires) {
+                    (Some(error), _) => format!("❌ {}", error),
+                    (None, Some(expires)) => {
+                        let days = (expires - chrono::Utc::now()).num_days();
+                        let flag = if check.issues.is_empty() {
+                            "✅"
+                        } else {
+                            "⚠️"
+                        };
+                        format!("{} expires in {} days", flag, days)
                     }
-                });
+                    (None, None) => "✅ valid".to_string(),
+                };
+
+                tls_table.add_row(Row::new(vec![
+                    Cell::new(&host),
+                    Cell::new(check.protocol.as_deref().unwrap_or("-")),
+                    Cell::new(check.cipher_suite.as_deref().unwrap_or("-")),
+                    Cell::new(
+                        &check
+                            .handshake_ms
+                            .map_or("-".to_string(), |ms| format!("{:.1}ms", ms)),
+                    ),
+                    Cell::new(&certificate),
+                ]));
+            }
+
+            print_table(&tls_table)?;
+        }
+
+        if !diagnostics.happy_eyeballs.is_empty() {
+            println!(
+                "\n{}",
+                " 👁️ HAPPY EYEBALLS 👁️ ".on_bright_magenta().white().bold()
+            );
+
+            let mut eyeballs_table = Table::new();
+            eyeballs_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            eyeballs_table.add_row(Row::new(vec![
+                Cell::new("🌐 Site").style_spec("Fb"),
+                Cell::new("⭐ Prefers").style_spec("Fb"),
+                Cell::new("6️⃣ IPv6").style_spec("Fb"),
+                Cell::new("4️⃣ IPv4").style_spec("Fb"),
+                Cell::new("🏁 Race").style_spec("Fb"),
+                Cell::new("↩️ Fallback").style_spec("Fb"),
+            ]));
 
-                trace_table.add_row(Row::new(vec![
-                    Cell::new(&format!("{:02}", hop.hop_number)),
-                    Cell::new(&addr),
-                    Cell::new(&hostname),
-                    Cell::new(&time),
+            for check in &diagnostics.happy_eyeballs {
+                let connect = |ms: Option<f64>, error: &Option<String>| match (ms, error) {
+                    (Some(ms), _) => format!("{:.1}ms", ms),
+                    (None, Some(error)) => format!("❌ {}", error),
+                    (None, None) => "-".to_string(),
+                };
+                let race = match (check.winner, check.race_ms) {
+                    (Some(winner), Some(ms)) => format!("{} in {:.1}ms", winner, ms),
+                    _ => "❌ no connection".to_string(),
+                };
+                let fallback = match check.fallback_ms {
+                    Some(ms) if check.issues.contains(&HappyEyeballsIssue::SlowFallback) => {
+                        format!("⚠️ {:.1}s", ms / 1000.0)
+                    }
+                    Some(ms) => format!("{:.1}ms", ms),
+                    None => "-".to_string(),
+                };
+
+                eyeballs_table.add_row(Row::new(vec![
+                    Cell::new(&check.host),
+                    Cell::new(&check.preferred.map_or("-".to_string(), |f| f.to_string())),
+                    Cell::new(&connect(check.ipv6_connect_ms, &check.ipv6_error)),
+                    Cell::new(&connect(check.ipv4_connect_ms, &check.ipv4_error)),
+                    Cell::new(&race),
+                    Cell::new(&fallback),
                 ]));
             }
 
-            trace_table.printstd();
+            print_table(&eyeballs_table)?;
+        }
+
+        if let Some(stack) = &diagnostics.windows_stack {
+            println!(
+                "\n{}",
+                " 🪟 WINDOWS NETWORK STACK 🪟 "
+                    .on_bright_magenta()
+                    .white()
+                    .bold()
+            );
+
+            let state = |enabled: Option<bool>| match enabled {
+                Some(true) => "on".to_string(),
+                Some(false) => "off".to_string(),
+                None => "-".to_string(),
+            };
+            let mut stack_table = Table::new();
+            stack_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            stack_table.add_row(Row::new(vec![
+                Cell::new("⚙️ Setting").style_spec("Fb"),
+                Cell::new("📋 Value").style_spec("Fb"),
+            ]));
+            stack_table.add_row(Row::new(vec![
+                Cell::new("Receive window auto-tuning"),
+                Cell::new(stack.autotuning_level.as_deref().unwrap_or("-")),
+            ]));
+            stack_table.add_row(Row::new(vec![
+                Cell::new("Receive-side scaling"),
+                Cell::new(&state(stack.rss_enabled)),
+            ]));
+            for (label, mode) in [
+                (
+                    "Wi-Fi power saving (plugged in)",
+                    stack.wifi_power_saving_ac,
+                ),
+                ("Wi-Fi power saving (battery)", stack.wifi_power_saving_dc),
+            ] {
+                stack_table.add_row(Row::new(vec![
+                    Cell::new(label),
+                    Cell::new(&mode.map_or("-".to_string(), |mode| mode.to_string())),
+                ]));
+            }
+            print_table(&stack_table)?;
+
+            if !stack.adapters.is_empty() {
+                let mut adapter_table = Table::new();
+                adapter_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+                adapter_table.add_row(Row::new(vec![
+                    Cell::new("🔌 Adapter").style_spec("Fb"),
+                    Cell::new("📡 Type").style_spec("Fb"),
+                    Cell::new("🔀 RSS").style_spec("Fb"),
+                    Cell::new("📦 LSO").style_spec("Fb"),
+                    Cell::new("💤 May power off").style_spec("Fb"),
+                ]));
+                for adapter in &stack.adapters {
+                    adapter_table.add_row(Row::new(vec![
+                        Cell::new(&format!("{} ({})", adapter.name, adapter.description)),
+                        Cell::new(if adapter.wireless { "Wi-Fi" } else { "Wired" }),
+                        Cell::new(&state(adapter.rss_enabled)),
+                        Cell::new(&state(adapter.lso_enabled)),
+                        Cell::new(&match adapter.power_saving {
+                            Some(true) => "⚠️ yes".to_string(),
+                            Some(false) => "no".to_string(),
+                            None => "-".to_string(),
+                        }),
+                    ]));
+                }
+                print_table(&adapter_table)?;
+            }
         }
 
         // Provide some recommendations based on the diagnostics
@@ -626,6 +1462,114 @@ impl NetworkDiagnosticsTool {
         Ok(())
     }
 
+    /// Hop table for one traced route, marking hops that changed since the last run
+    fn display_route(&self, route: &TracedRoute) -> Result<(), Box<dyn std::error::Error>> {
+        println!(
+            "\n{} {}",
+            "📡 Route to".bold(),
+            route.target.bright_cyan().bold()
+        );
+
+        let mut trace_table = Table::new();
+        trace_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+
+        // Add cyberpunk header
+        trace_table.add_row(Row::new(vec![
+            Cell::new("🔗 Node").style_spec("Fb"),
+            Cell::new("📍 Neural Address").style_spec("Fb"),
+            Cell::new("🏷️ Identity").style_spec("Fb"),
+            Cell::new("🏢 Network").style_spec("Fb"),
+            Cell::new("🗺️ Location").style_spec("Fb"),
+            Cell::new("⚡ Signal Delay").style_spec("Fb"),
+        ]));
+
+        let changed_hops = route
+            .change
+            .as_ref()
+            .map_or(&[][..], |change| change.changed_hops.as_slice());
+
+        for hop in &route.hops {
+            let addr = hop.address.map_or("⟨⟨⟨ ENCRYPTED ⟩⟩⟩".to_string(), |a| {
+                if changed_hops.contains(&hop.hop_number) {
+                    format!("{} 🔀", a)
+                } else {
+                    format!("{} 🔗", a)
+                }
+            });
+            let hostname = hop
+                .hostname
+                .clone()
+                .unwrap_or_else(|| "⟨ANONYMOUS⟩".to_string());
+            let time = hop.response_time_ms.map_or("🔒 STEALTH".to_string(), |t| {
+                if t < 50.0 {
+                    format!("{:.2}ms ⚡", t)
+                } else if t < 100.0 {
+                    format!("{:.2}ms ⚠️", t)
+                } else {
+                    format!("{:.2}ms 🐌", t)
+                }
+            });
+
+            trace_table.add_row(Row::new(vec![
+                Cell::new(&format!("{:02}", hop.hop_number)),
+                Cell::new(&addr),
+                Cell::new(&hostname),
+                Cell::new(&format_network(hop.network.as_ref())),
+                Cell::new(&hop.location.as_ref().map_or("-".to_string(), |location| {
+                    if location.city.is_empty() {
+                        location.country_code.clone()
+                    } else {
+                        format!("{}, {}", location.city, location.country_code)
+                    }
+                })),
+                Cell::new(&time),
+            ]));
+        }
+
+        print_table(&trace_table)?;
+
+        if let Some(path) = ipinfo::path_summary(&route.hops) {
+            println!("{} {}", "🗺️ Physical path:".bold(), path.bright_cyan());
+        }
+
+        if let Some(change) = &route.change {
+            println!(
+                "{}",
+                format!(
+                    "🔀 Route changed since {} at hops {}",
+                    change
+                        .previous_timestamp
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    change
+                        .changed_hops
+                        .iter()
+                        .map(|hop| hop.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+                .bright_yellow()
+            );
+            for &hop_number in &change.changed_hops {
+                let before = change
+                    .previous_hops
+                    .get(hop_number as usize - 1)
+                    .copied()
+                    .flatten()
+                    .map_or("-".to_string(), |addr| addr.to_string());
+                let now = route
+                    .hops
+                    .iter()
+                    .find(|hop| hop.hop_number == hop_number)
+                    .and_then(|hop| hop.address)
+                    .map_or("-".to_string(), |addr| addr.to_string());
+                println!("   {:02}  {} → {}", hop_number, before.dimmed(), now);
+            }
+        }
+
+        Ok(())
+    }
+
     fn show_diagnostics_recommendations(
         &self,
         diagnostics: &NetworkDiagnostics,
@@ -650,11 +1594,6 @@ impl NetworkDiagnosticsTool {
             "╚═══════════════════════════════════════════╝".bright_cyan()
         );
 
-        // Check DNS performance with cyberpunk styling
-        if diagnostics.dns_response_time_ms > 100.0 {
-            println!("⚡ {}", "DNS QUANTUM TUNNELING DEGRADED: Consider upgrading to enhanced DNS matrices like Google (8.8.8.8) or Cloudflare (1.1.1.1) for optimal neural response.".bright_yellow());
-        }
-
         // Check IPv6 availability with enhanced message
         if !diagnostics.is_ipv6_available {
             println!("🛰️ {}", "IPv6 QUANTUM PROTOCOLS OFFLINE: Your network lacks next-gen connectivity. Activating IPv6 will unlock advanced neural pathways to modern digital realms.".bright_blue());
@@ -662,24 +1601,199 @@ impl NetworkDiagnosticsTool {
 
         // Check for missing hops in traceroute
         let missing_hops = diagnostics
-            .route_hops
+            .routes
             .iter()
-            .filter(|hop| hop.address.is_none())
-            .count();
+            .map(|route| {
+                route
+                    .hops
+                    .iter()
+                    .filter(|hop| hop.address.is_none())
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
 
         if missing_hops > 2 {
             println!("🔍 {}", "NEURAL PATHWAY FRAGMENTATION DETECTED: Multiple nodes in stealth mode. This suggests encrypted routing or network infrastructure anomalies.".bright_yellow());
         }
 
-        // Check connection type for wireless optimization with cyberpunk flair
-        if let Some(conn_type) = &diagnostics.connection_type {
-            if conn_type.contains("Wi-Fi") || conn_type.contains("Wireless") {
-                println!("📶 {}", "WIRELESS NEURAL INTERFACE ACTIVE: For maximum bandwidth and signal stability, consider hardwiring your neural port via Ethernet for critical cyberspace operations.".bright_blue());
+        // Recommend the fastest encrypted resolver, compared with plain DNS
+        if let Some(fastest) = fastest_encrypted_resolver(&diagnostics.resolver_benchmarks) {
+            let plain = diagnostics
+                .resolver_benchmarks
+                .iter()
+                .filter(|b| !b.transport.is_encrypted())
+                .filter_map(|b| b.median_ms)
+                .min_by(|a, b| a.total_cmp(b));
+            let comparison = plain.map_or(String::new(), |plain| {
+                format!(" (fastest plain DNS: {:.1}ms)", plain)
+            });
+            println!(
+                "🔒 {}",
+                format!(
+                    "ENCRYPTED DNS RECOMMENDATION: {} over {} answered in {:.1}ms{}. Encrypted DNS keeps your ISP from reading or rewriting lookups.",
+                    fastest.provider,
+                    fastest.transport,
+                    fastest.median_ms.unwrap_or_default(),
+                    comparison
+                )
+                .bright_blue()
+            );
+        }
+
+        // Flag signs of DNS tampering
+        for issue in &diagnostics.dns_security.issues {
+            let alert = match issue {
+                DnsIssue::DnssecNotValidated => "DNSSEC VALIDATION OFFLINE: Your resolver accepts forged signatures. Switch to a validating resolver such as Cloudflare (1.1.1.1) or Quad9 (9.9.9.9).".bright_blue(),
+                DnsIssue::NxdomainRewritten => "NXDOMAIN HIJACK DETECTED: Lookups of missing domains return addresses, so typos land on your ISP's or router's search page. Use a different resolver or disable the ISP's \"DNS assist\" feature.".bright_yellow(),
+                DnsIssue::AnswersDiffer => format!(
+                    "DNS ANSWER MISMATCH: Your resolver returned different addresses than a trusted resolver for {}. Your ISP or router may be redirecting traffic.",
+                    diagnostics.dns_security.mismatched_domains.join(", ")
+                )
+                .bright_yellow(),
+                DnsIssue::QueriesIntercepted => "DNS INTERCEPTION DETECTED: Plain DNS to public resolvers is answered by something on your network path. Use encrypted DNS (DoH or DoT) to bypass it.".bright_yellow(),
+            };
+            println!("🛡️ {}", alert);
+        }
+
+        // Flag a skewed clock, which breaks TLS and misdates history entries
+        if let Some(check) = diagnostics.clock_check.as_ref().filter(|c| c.is_skewed()) {
+            println!(
+                "🕰️ {}",
+                format!(
+                    "CHRONO DESYNC DETECTED: Your system clock is {} compared with {}. Certificates may be rejected and test history timestamps will be off; enable automatic time sync.",
+                    format_offset(check.offset_ms),
+                    check.server
+                )
+                .bright_yellow()
+            );
+        }
+
+        // Flag TLS problems per host
+        for check in &diagnostics.tls_checks {
+            for issue in &check.issues {
+                let advice = match issue {
+                    TlsIssue::OutdatedProtocol => {
+                        "only offers TLS 1.1 or older, which is no longer considered secure"
+                            .to_string()
+                    }
+                    TlsIssue::CertificateExpired => "presents an expired certificate".to_string(),
+                    TlsIssue::CertificateExpiringSoon => format!(
+                        "has a certificate that expires within {} days",
+                        tls::CERT_EXPIRY_WARNING_DAYS
+                    ),
+                    TlsIssue::HandshakeFailed => {
+                        "could not complete a TLS handshake; a proxy or firewall may be interfering"
+                            .to_string()
+                    }
+                };
+                println!(
+                    "🔐 {}",
+                    format!("ENCRYPTED CHANNEL ALERT: {} {}.", check.host, advice).bright_yellow()
+                );
             }
         }
 
+        // Flag dual-stack setups that stall page loads, once per kind of problem
+        let mut eyeball_issues: Vec<HappyEyeballsIssue> = Vec::new();
+        for check in &diagnostics.happy_eyeballs {
+            for issue in &check.issues {
+                if !eyeball_issues.contains(issue) {
+                    eyeball_issues.push(*issue);
+                }
+            }
+        }
+        for issue in eyeball_issues {
+            let sites: Vec<&str> = diagnostics
+                .happy_eyeballs
+                .iter()
+                .filter(|check| check.issues.contains(&issue))
+                .map(|check| check.host.as_str())
+                .collect();
+            let alert = match issue {
+                HappyEyeballsIssue::BrokenIpv6 => format!(
+                    "IPv6 PATHWAY SEVERED: {} resolve to IPv6 addresses that never connect. Fix IPv6 on your router or disable it, so clients stop trying a dead route first.",
+                    sites.join(", ")
+                )
+                .bright_yellow(),
+                HappyEyeballsIssue::SlowFallback => format!(
+                    "FALLBACK STALL DETECTED: IPv6 to {} hangs for over {:.0}s before failing. Apps without Happy Eyeballs freeze that long on every new connection.",
+                    sites.join(", "),
+                    happy_eyeballs::SLOW_FALLBACK_MS / 1000.0
+                )
+                .bright_red(),
+                HappyEyeballsIssue::SlowIpv6 => format!(
+                    "IPv6 LAGGING: IPv4 beat IPv6 to {} despite IPv6's head start. Your ISP's IPv6 route may be congested or tunneled.",
+                    sites.join(", ")
+                )
+                .bright_blue(),
+            };
+            println!("👁️ {}", alert);
+        }
+
+        // Windows settings, each with the command or setting that fixes it
+        if let Some(stack) = &diagnostics.windows_stack {
+            let adapters = |matches: fn(&AdapterSettings) -> bool| {
+                stack
+                    .adapters
+                    .iter()
+                    .filter(|adapter| matches(adapter))
+                    .map(|adapter| adapter.name.as_str())
+                    .collect::<Vec<_>>()
+            };
+            for issue in &stack.issues {
+                let alert = match issue {
+                    WindowsStackIssue::AutoTuningRestricted => format!(
+                        "RECEIVE WINDOW CLAMPED: TCP auto-tuning is {}, which caps downloads on fast or distant links. Run `netsh int tcp set global autotuninglevel=normal` as administrator.",
+                        stack.autotuning_level.as_deref().unwrap_or("restricted")
+                    )
+                    .bright_yellow(),
+                    WindowsStackIssue::RssDisabled => {
+                        let names = adapters(|adapter| adapter.rss_enabled == Some(false));
+                        let fix = if stack.rss_enabled == Some(false) || names.is_empty() {
+                            "`netsh int tcp set global rss=enabled`".to_string()
+                        } else {
+                            names
+                                .iter()
+                                .map(|name| format!("`Enable-NetAdapterRss -Name \"{}\"`", name))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        };
+                        format!(
+                            "SINGLE-CORE BOTTLENECK: Receive-side scaling is off, so one CPU core handles all incoming traffic. Run {} as administrator.",
+                            fix
+                        )
+                        .bright_yellow()
+                    }
+                    WindowsStackIssue::AdapterPowerSaving => format!(
+                        "ADAPTER HIBERNATION RISK: Windows may switch off {} to save power, dropping the connection after idle periods. Untick \"Allow the computer to turn off this device to save power\" under Power Management in the adapter's Device Manager properties.",
+                        adapters(|adapter| adapter.power_saving == Some(true)).join(", ")
+                    )
+                    .bright_yellow(),
+                    WindowsStackIssue::WifiPowerSaving => format!(
+                        "WI-FI DOZING: Wireless power saving adds latency and jitter between packets. Set Power Options > Wireless Adapter Settings > Power Saving Mode to Maximum Performance, or run `powercfg /setacvalueindex SCHEME_CURRENT {} {} 0` (and /setdcvalueindex for battery), then `powercfg /setactive SCHEME_CURRENT`.",
+                        windows_stack::WIRELESS_SUBGROUP,
+                        windows_stack::WIRELESS_POWER_SETTING
+                    )
+                    .bright_blue(),
+                };
+                println!("🪟 {}", alert);
+            }
+        }
+
+        // Rank what slow or unsteady connections come down to, weighing past results
+        let causes = if self.rank_causes {
+            let history = HistoryStorage::new()
+                .and_then(|storage| storage.get_recent_results(analysis::HISTORY_RESULTS))
+                .unwrap_or_default();
+            analysis::diagnose_network(diagnostics, &history)
+        } else {
+            Vec::new()
+        };
+        analysis::show_causes(&causes);
+
         // If everything looks good with cyberpunk celebration
-        if diagnostics.dns_response_time_ms < 50.0 && missing_hops <= 2 {
+        if causes.is_empty() && diagnostics.dns_response_time_ms < 50.0 && missing_hops <= 2 {
             println!("🚀 {}", "NEURAL NETWORK STATUS: ⟨⟨⟨ OPTIMAL ⟩⟩⟩ - All cybernetic systems operating at peak efficiency! You're ready to jack into the matrix.".bright_green());
 
             // Show success animation if enabled
@@ -695,3 +1809,73 @@ impl NetworkDiagnosticsTool {
 }
 
 use prettytable::{format, Cell, Row, Table};
+
+/// `2.4s behind` style description of a clock offset, positive offsets mean our clock is behind
+fn format_offset(offset_ms: f64) -> String {
+    let direction = if offset_ms > 0.0 { "behind" } else { "ahead" };
+    let magnitude = offset_ms.abs();
+    if magnitude >= 1000.0 {
+        format!("{:.1}s {}", magnitude / 1000.0, direction)
+    } else {
+        format!("{:.0}ms {}", magnitude, direction)
+    }
+}
+
+/// `AS15169 GOOGLE` style label for a hop's network
+fn format_network(network: Option<&AsnInfo>) -> String {
+    match network {
+        Some(network) if network.organization.is_empty() => format!("AS{}", network.asn),
+        Some(network) => format!("AS{} {}", network.asn, network.organization),
+        None => "-".to_string(),
+    }
+}
+
+/// First of [`GATEWAY_PORTS`] the gateway answers on, a refusal still times a round trip
+async fn gateway_port(address: IpAddr) -> Option<u16> {
+    let answers = futures::future::join_all(
+        GATEWAY_PORTS
+            .iter()
+            .map(|&port| round_trip(SocketAddr::new(address, port))),
+    )
+    .await;
+    GATEWAY_PORTS
+        .iter()
+        .zip(answers)
+        .find_map(|(&port, answer)| answer.map(|_| port))
+}
+
+/// Time [`BASELINE_PROBES`] handshakes with `target`, spaced out to expose jitter
+pub(crate) async fn probe_link(target: SocketAddr) -> LinkLatency {
+    let mut probes = Vec::with_capacity(BASELINE_PROBES);
+    for i in 0..BASELINE_PROBES {
+        if i > 0 {
+            sleep(BASELINE_INTERVAL).await;
+        }
+        probes.push(round_trip(target).await);
+    }
+    LinkLatency::from_probes(target.ip(), target.port(), &probes)
+}
+
+/// Milliseconds until `target` accepts or refuses a TCP connection
+async fn round_trip(target: SocketAddr) -> Option<f64> {
+    let start = Instant::now();
+    match tokio::time::timeout(BASELINE_TIMEOUT, tokio::net::TcpStream::connect(target)).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+            Some(start.elapsed().as_secs_f64() * 1000.0)
+        }
+        _ => None,
+    }
+}
+
+/// Encrypted resolver with the lowest median query time
+fn fastest_encrypted_resolver(benchmarks: &[ResolverBenchmark]) -> Option<&ResolverBenchmark> {
+    benchmarks
+        .iter()
+        .filter(|b| b.transport.is_encrypted() && b.median_ms.is_some())
+        .min_by(|a, b| {
+            a.median_ms
+                .unwrap_or(f64::MAX)
+                .total_cmp(&b.median_ms.unwrap_or(f64::MAX))
+        })
+}
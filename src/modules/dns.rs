@@ -0,0 +1,646 @@
+//! DNS Resolver Benchmarks and Integrity Checks
+//!
+//! Times the same queries against public resolvers over plain UDP, DNS-over-HTTPS
+//! (RFC 8484) and DNS-over-TLS (RFC 7858), so encrypted DNS can be compared with
+//! what the system uses by default. Also checks whether the system resolver
+//! validates DNSSEC and whether anything on the path rewrites or intercepts DNS.
+//! Messages are built by hand: only A queries and A answers are needed, which
+//! doesn't warrant a full DNS library. The per-record breakdown goes through the
+//! system resolver with hickory, which also handles AAAA and CNAME answers.
+
+use hickory_resolver::net::NetError;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::TokioResolver;
+use reqwest::Client;
+use rustls::pki_types::ServerName;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_rustls::TlsConnector;
+
+use crate::modules::tls;
+use crate::modules::types::{
+    median, DnsIssue, DnsRecordKind, DnsRecordTiming, DnsSecurityCheck, DnsTransport,
+    ResolverBenchmark,
+};
+
+/// Names queried against every resolver after one warm-up query
+const BENCHMARK_DOMAINS: &[&str] = &["example.com", "wikipedia.org", "github.com"];
+
+/// Correctly signed zone, confirming the resolver works before judging its validation
+const DNSSEC_SIGNED_DOMAIN: &str = "isc.org";
+
+/// Zone with deliberately broken signatures that validating resolvers refuse to answer
+const DNSSEC_BROKEN_DOMAIN: &str = "dnssec-failed.org";
+
+/// Names with stable, well-known addresses for comparing the system resolver with a trusted one
+const TRUSTED_ANSWER_DOMAINS: &[&str] = &["one.one.one.one", "dns.google"];
+
+const RCODE_SERVFAIL: u8 = 2;
+
+/// Longest CNAME chain followed, real ones rarely have more than three hops
+const MAX_CNAME_HOPS: usize = 8;
+
+/// A public resolver reachable over every transport
+pub struct DnsProvider {
+    pub name: &'static str,
+    pub address: IpAddr,
+    pub doh_url: &'static str,
+    /// Name on the resolver's certificate, used as SNI for DNS-over-TLS
+    pub tls_name: &'static str,
+}
+
+pub const PROVIDERS: [DnsProvider; 3] = [
+    DnsProvider {
+        name: "Cloudflare",
+        address: IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+        doh_url: "https://cloudflare-dns.com/dns-query",
+        tls_name: "one.one.one.one",
+    },
+    DnsProvider {
+        name: "Google",
+        address: IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+        doh_url: "https://dns.google/dns-query",
+        tls_name: "dns.google",
+    },
+    DnsProvider {
+        name: "Quad9",
+        address: IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+        doh_url: "https://dns.quad9.net/dns-query",
+        tls_name: "dns.quad9.net",
+    },
+];
+
+/// Benchmark every provider over every transport
+pub async fn benchmark_resolvers(timeout: Duration) -> Vec<ResolverBenchmark> {
+    let runs = PROVIDERS.iter().flat_map(|provider| {
+        [DnsTransport::Udp, DnsTransport::Doh, DnsTransport::Dot]
+            .map(|transport| benchmark(provider, transport, timeout))
+    });
+
+    futures::future::join_all(runs).await
+}
+
+async fn benchmark(
+    provider: &DnsProvider,
+    transport: DnsTransport,
+    timeout: Duration,
+) -> ResolverBenchmark {
+    let mut resolver = Resolver::new(provider, transport);
+    let mut times = Vec::new();
+    let mut error = None;
+
+    // The first query sets up the connection and warms the resolver cache, so it is not timed
+    let domains = std::iter::once(BENCHMARK_DOMAINS[0]).chain(BENCHMARK_DOMAINS.iter().copied());
+    for (i, domain) in domains.enumerate() {
+        let start = Instant::now();
+        match tokio::time::timeout(timeout, resolver.query(domain)).await {
+            Ok(Ok(_)) if i > 0 => times.push(start.elapsed().as_secs_f64() * 1000.0),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error = Some(e.to_string()),
+            Err(_) => error = Some(format!("No answer within {}s", timeout.as_secs())),
+        }
+    }
+
+    ResolverBenchmark {
+        provider: provider.name.to_string(),
+        transport,
+        median_ms: (!times.is_empty()).then(|| median(&times)),
+        successful_queries: times.len(),
+        queries: BENCHMARK_DOMAINS.len(),
+        error: if times.is_empty() { error } else { None },
+    }
+}
+
+/// Time the A, AAAA and CNAME chain of every name against the system's resolvers.
+/// hickory's own cache is off, so the repeated query reaches the resolver and shows
+/// what its cache saves.
+pub async fn record_breakdown(domains: &[String], timeout: Duration) -> Vec<DnsRecordTiming> {
+    const KINDS: [DnsRecordKind; 3] = [
+        DnsRecordKind::A,
+        DnsRecordKind::Aaaa,
+        DnsRecordKind::CnameChain,
+    ];
+
+    let resolver = TokioResolver::builder_tokio().and_then(|mut builder| {
+        let options = builder.options_mut();
+        options.cache_size = 0;
+        options.timeout = timeout;
+        builder.build()
+    });
+
+    match resolver {
+        Ok(resolver) => {
+            let timings = domains.iter().flat_map(|domain| {
+                let resolver = &resolver;
+                KINDS.map(move |kind| time_record(resolver, domain, kind))
+            });
+            futures::future::join_all(timings).await
+        }
+        // Without a usable resolver configuration every query counts as failed
+        Err(e) => domains
+            .iter()
+            .flat_map(|domain| {
+                KINDS.map(|kind| DnsRecordTiming {
+                    domain: domain.clone(),
+                    kind,
+                    cold_ms: None,
+                    warm_ms: None,
+                    answers: Vec::new(),
+                    error: Some(format!("no system resolver: {}", e)),
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Resolve one record type of `domain` twice in a row
+async fn time_record(
+    resolver: &TokioResolver,
+    domain: &str,
+    kind: DnsRecordKind,
+) -> DnsRecordTiming {
+    let mut timing = DnsRecordTiming {
+        domain: domain.to_string(),
+        kind,
+        cold_ms: None,
+        warm_ms: None,
+        answers: Vec::new(),
+        error: None,
+    };
+
+    for warm in [false, true] {
+        let start = Instant::now();
+        let answers = match kind {
+            DnsRecordKind::A => lookup_records(resolver, domain, RecordType::A).await,
+            DnsRecordKind::Aaaa => lookup_records(resolver, domain, RecordType::AAAA).await,
+            DnsRecordKind::CnameChain => cname_chain(resolver, domain).await,
+        };
+        let time_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        match answers {
+            Ok(_) if warm => timing.warm_ms = Some(time_ms),
+            Ok(answers) => {
+                timing.cold_ms = Some(time_ms);
+                timing.answers = answers;
+            }
+            Err(e) => {
+                timing.error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    timing
+}
+
+/// Answers of one type for `name`; a name without records of that type is an empty answer
+async fn lookup_records(
+    resolver: &TokioResolver,
+    name: &str,
+    record_type: RecordType,
+) -> Result<Vec<String>, NetError> {
+    match resolver.lookup(name, record_type).await {
+        Ok(lookup) => Ok(lookup
+            .answers()
+            .iter()
+            .filter(|record| record.record_type() == record_type)
+            .map(|record| record.data.to_string())
+            .collect()),
+        Err(e) if e.is_no_records_found() && !e.is_nx_domain() => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Follow CNAME records from `domain` one query at a time, returning the names passed through
+async fn cname_chain(resolver: &TokioResolver, domain: &str) -> Result<Vec<String>, NetError> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut name = domain.to_string();
+
+    for _ in 0..MAX_CNAME_HOPS {
+        let lookup = match resolver.lookup(name.as_str(), RecordType::CNAME).await {
+            Ok(lookup) => lookup,
+            Err(e) if e.is_no_records_found() && !e.is_nx_domain() => break,
+            Err(e) => return Err(e),
+        };
+        let target = lookup
+            .answers()
+            .iter()
+            .find_map(|record| match &record.data {
+                RData::CNAME(cname) => Some(cname.to_string().trim_end_matches('.').to_string()),
+                _ => None,
+            });
+        match target {
+            // A loop would otherwise be followed until the hop limit
+            Some(target) if !chain.contains(&target) => {
+                chain.push(target.clone());
+                name = target;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(chain)
+}
+
+/// Check DNSSEC validation by the system resolver and look for signs of DNS tampering
+pub async fn check_security(timeout: Duration) -> DnsSecurityCheck {
+    let system_resolver = system_resolver();
+    let (dnssec_validating, intercepted, nxdomain_rewritten, mismatched_domains) = tokio::join!(
+        check_dnssec(system_resolver, timeout),
+        check_interception(timeout),
+        check_nxdomain(timeout),
+        compare_with_trusted(timeout),
+    );
+
+    let mut issues = Vec::new();
+    if dnssec_validating == Some(false) {
+        issues.push(DnsIssue::DnssecNotValidated);
+    }
+    if nxdomain_rewritten {
+        issues.push(DnsIssue::NxdomainRewritten);
+    }
+    if !mismatched_domains.is_empty() {
+        issues.push(DnsIssue::AnswersDiffer);
+    }
+    if intercepted {
+        issues.push(DnsIssue::QueriesIntercepted);
+    }
+
+    DnsSecurityCheck {
+        system_resolver,
+        dnssec_validating,
+        mismatched_domains,
+        issues,
+    }
+}
+
+/// A validating resolver answers the signed zone and fails the broken one
+async fn check_dnssec(resolver: Option<IpAddr>, timeout: Duration) -> Option<bool> {
+    let resolver = resolver?;
+    let signed = udp_lookup(resolver, DNSSEC_SIGNED_DOMAIN, timeout)
+        .await
+        .ok()?;
+    if signed.rcode != 0 {
+        return None;
+    }
+    let broken = udp_lookup(resolver, DNSSEC_BROKEN_DOMAIN, timeout)
+        .await
+        .ok()?;
+    Some(broken.rcode == RCODE_SERVFAIL)
+}
+
+/// Every public provider validates DNSSEC, so a successful answer for the broken
+/// zone came from a middlebox answering port 53 in their place
+async fn check_interception(timeout: Duration) -> bool {
+    let answers = futures::future::join_all(
+        PROVIDERS
+            .iter()
+            .map(|provider| udp_lookup(provider.address, DNSSEC_BROKEN_DOMAIN, timeout)),
+    )
+    .await;
+
+    answers
+        .into_iter()
+        .any(|answer| matches!(answer, Ok(answer) if answer.rcode == 0))
+}
+
+/// Resolve a random name that can't exist; getting addresses back means NXDOMAIN is rewritten
+async fn check_nxdomain(timeout: Duration) -> bool {
+    let domain = format!("netrunner-{:08x}.example.com", rand::random::<u32>());
+    !system_lookup(&domain, timeout).await.is_empty()
+}
+
+/// Domains the system resolves to addresses a trusted DoH resolver never returns
+async fn compare_with_trusted(timeout: Duration) -> Vec<String> {
+    let mut trusted = Resolver::new(&PROVIDERS[0], DnsTransport::Doh);
+    let mut mismatched = Vec::new();
+
+    for &domain in TRUSTED_ANSWER_DOMAINS {
+        let system = system_lookup(domain, timeout).await;
+        let expected = match tokio::time::timeout(timeout, trusted.query(domain)).await {
+            Ok(Ok(answer)) => answer.addresses,
+            _ => continue,
+        };
+
+        if !system.is_empty()
+            && !expected.is_empty()
+            && expected.iter().all(|a| !system.contains(a))
+        {
+            mismatched.push(domain.to_string());
+        }
+    }
+
+    mismatched
+}
+
+/// IPv4 addresses for `domain` from the operating system's resolver
+async fn system_lookup(domain: &str, timeout: Duration) -> HashSet<Ipv4Addr> {
+    match tokio::time::timeout(timeout, tokio::net::lookup_host((domain, 0))).await {
+        Ok(Ok(addrs)) => addrs
+            .filter_map(|addr| match addr.ip() {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// One plain UDP query, returning the answer whatever its result code
+async fn udp_lookup(
+    server: IpAddr,
+    domain: &str,
+    timeout: Duration,
+) -> Result<DnsAnswer, Box<dyn std::error::Error>> {
+    let id = rand::random();
+    let query = build_query(id, domain)?;
+    let response = tokio::time::timeout(timeout, query_udp(server, &query))
+        .await
+        .map_err(|_| format!("No answer within {}s", timeout.as_secs()))??;
+    parse_response(&response, id)
+}
+
+async fn query_udp(server: IpAddr, query: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let local: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(SocketAddr::new(server, 53)).await?;
+    socket.send(query).await?;
+
+    let mut buf = vec![0u8; 1232];
+    let n = socket.recv(&mut buf).await?;
+    buf.truncate(n);
+    Ok(buf)
+}
+
+/// First nameserver from /etc/resolv.conf, where the system resolver is configured on Unix
+fn system_resolver() -> Option<IpAddr> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    parse_resolv_conf(&conf)
+}
+
+fn parse_resolv_conf(conf: &str) -> Option<IpAddr> {
+    conf.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some("nameserver"), Some(address)) => address.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+type TlsStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+/// One resolver over one transport, keeping its connection open between queries
+struct Resolver<'a> {
+    provider: &'a DnsProvider,
+    transport: DnsTransport,
+    http: Option<Client>,
+    tls: Option<TlsStream>,
+    next_id: u16,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(provider: &'a DnsProvider, transport: DnsTransport) -> Self {
+        Self {
+            provider,
+            transport,
+            http: None,
+            tls: None,
+            next_id: rand::random(),
+        }
+    }
+
+    async fn query(&mut self, domain: &str) -> Result<DnsAnswer, Box<dyn std::error::Error>> {
+        // DoH requires ID 0 so responses stay cacheable
+        let id = match self.transport {
+            DnsTransport::Doh => 0,
+            _ => {
+                self.next_id = self.next_id.wrapping_add(1);
+                self.next_id
+            }
+        };
+        let query = build_query(id, domain)?;
+
+        let response = match self.transport {
+            DnsTransport::Udp => query_udp(self.provider.address, &query).await?,
+            DnsTransport::Doh => self.query_doh(&query).await?,
+            DnsTransport::Dot => self.query_dot(&query).await?,
+        };
+
+        check_response(&response, id)
+    }
+
+    async fn query_doh(&mut self, query: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.http.is_none() {
+            self.http = Some(Client::builder().build()?);
+        }
+        let client = self.http.as_ref().unwrap();
+
+        let response = client
+            .post(self.provider.doh_url)
+            .header("content-type", "application/dns-message")
+            .header("accept", "application/dns-message")
+            .body(query.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn query_dot(&mut self, query: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.tls.is_none() {
+            let tcp = TcpStream::connect(SocketAddr::new(self.provider.address, 853)).await?;
+            let connector = TlsConnector::from(Arc::new(tls::client_config()?));
+            let server_name = ServerName::try_from(self.provider.tls_name)?;
+            self.tls = Some(connector.connect(server_name, tcp).await?);
+        }
+        let stream = self.tls.as_mut().unwrap();
+
+        // DNS over a stream prefixes each message with its length
+        stream
+            .write_all(&(query.len() as u16).to_be_bytes())
+            .await?;
+        stream.write_all(query).await?;
+        stream.flush().await?;
+
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).await?;
+        let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut response).await?;
+        Ok(response)
+    }
+}
+
+/// Build a recursive query for the A records of `domain`
+fn build_query(id: u16, domain: &str) -> Result<Vec<u8>, String> {
+    let mut message = Vec::with_capacity(32 + domain.len());
+    message.extend_from_slice(&id.to_be_bytes());
+    // Flags: recursion desired
+    message.extend_from_slice(&[0x01, 0x00]);
+    // One question, no answer, authority or additional records
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in domain.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid domain name '{}'", domain));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+
+    // QTYPE A, QCLASS IN
+    message.extend_from_slice(&[0, 1, 0, 1]);
+    Ok(message)
+}
+
+/// Result code and IPv4 addresses of a DNS response
+#[derive(Debug)]
+struct DnsAnswer {
+    rcode: u8,
+    addresses: Vec<Ipv4Addr>,
+}
+
+/// Accept a response to query `id` that reports success
+fn check_response(response: &[u8], id: u16) -> Result<DnsAnswer, Box<dyn std::error::Error>> {
+    let answer = parse_response(response, id)?;
+    match answer.rcode {
+        0 => Ok(answer),
+        rcode => Err(format!("DNS error code {}", rcode).into()),
+    }
+}
+
+/// Read the result code and the A records of a response to query `id`
+fn parse_response(response: &[u8], id: u16) -> Result<DnsAnswer, Box<dyn std::error::Error>> {
+    if response.len() < 12 {
+        return Err("Truncated DNS response".into());
+    }
+    if u16::from_be_bytes([response[0], response[1]]) != id {
+        return Err("DNS response for a different query".into());
+    }
+    if response[2] & 0x80 == 0 {
+        return Err("DNS message is not a response".into());
+    }
+
+    let questions = u16::from_be_bytes([response[4], response[5]]);
+    let answers = u16::from_be_bytes([response[6], response[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        // Name, then QTYPE and QCLASS
+        pos = skip_name(response, pos)? + 4;
+    }
+
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(response, pos)?;
+        // TYPE, CLASS, TTL and RDLENGTH
+        let header = response
+            .get(pos..pos + 10)
+            .ok_or("Truncated DNS response")?;
+        let record_type = u16::from_be_bytes([header[0], header[1]]);
+        let length = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let data = response
+            .get(pos + 10..pos + 10 + length)
+            .ok_or("Truncated DNS response")?;
+
+        if record_type == 1 && length == 4 {
+            addresses.push(Ipv4Addr::new(data[0], data[1], data[2], data[3]));
+        }
+        pos += 10 + length;
+    }
+
+    Ok(DnsAnswer {
+        rcode: response[3] & 0x0f,
+        addresses,
+    })
+}
+
+/// Position after the name starting at `pos`, which may end in a compression pointer
+fn skip_name(message: &[u8], mut pos: usize) -> Result<usize, &'static str> {
+    loop {
+        let length = *message.get(pos).ok_or("Truncated DNS response")? as usize;
+        if length == 0 {
+            return Ok(pos + 1);
+        }
+        if length & 0xc0 == 0xc0 {
+            return Ok(pos + 2);
+        }
+        pos += 1 + length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_query() {
+        let query = build_query(0xabcd, "example.com.").unwrap();
+
+        assert_eq!(&query[..4], &[0xab, 0xcd, 0x01, 0x00]);
+        assert_eq!(&query[4..6], &[0, 1]);
+        assert_eq!(&query[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&query[25..], &[0, 1, 0, 1]);
+        assert!(build_query(1, "bad..name").is_err());
+    }
+
+    #[test]
+    fn test_check_response() {
+        let mut response = build_query(7, "example.com").unwrap();
+        assert!(
+            check_response(&response, 7).is_err(),
+            "queries are not responses"
+        );
+
+        response[2] |= 0x80;
+        assert!(check_response(&response, 7).is_ok());
+        assert!(check_response(&response, 8).is_err());
+
+        // NXDOMAIN
+        response[3] |= 0x03;
+        assert!(check_response(&response, 7).is_err());
+        assert!(check_response(&response[..5], 7).is_err());
+    }
+
+    #[test]
+    fn test_parse_response_answers() {
+        let mut response = build_query(9, "one.one.one.one").unwrap();
+        response[2] |= 0x80;
+        // Two answers
+        response[7] = 2;
+        for address in [[1, 1, 1, 1], [1, 0, 0, 1]] {
+            // Pointer to the question name, A, IN, TTL 300, 4 bytes of data
+            response.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 1, 0x2c, 0, 4]);
+            response.extend_from_slice(&address);
+        }
+
+        let answer = parse_response(&response, 9).unwrap();
+        assert_eq!(answer.rcode, 0);
+        assert_eq!(
+            answer.addresses,
+            [Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(1, 0, 0, 1)]
+        );
+
+        response.truncate(response.len() - 2);
+        assert!(parse_response(&response, 9).is_err());
+    }
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let conf = "# Generated by NetworkManager\nsearch lan\nnameserver 192.168.1.1\nnameserver 1.1.1.1\n";
+        assert_eq!(
+            parse_resolv_conf(conf),
+            Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)))
+        );
+        assert_eq!(parse_resolv_conf("search lan\n"), None);
+    }
+}
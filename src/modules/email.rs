@@ -0,0 +1,211 @@
+//! Email Alerts
+//!
+//! Monitor mode mails outages and results below the advertised plan through an
+//! SMTP server, configured as an `[email]` table in `config.toml`:
+//!
+//! ```toml
+//! [email]
+//! server = "smtp.example.com"
+//! port = 587                  # 465 for implicit TLS, anything else uses STARTTLS
+//! from = "netrunner@example.com"
+//! to = ["ops@example.com"]
+//! digest = true               # one mail an hour instead of one per alert
+//! ```
+//!
+//! Credentials come from `NETRUNNER_SMTP_USERNAME` (or `username` in the file) and
+//! `NETRUNNER_SMTP_PASSWORD`, so the password never sits in the config file.
+//! Digest alerts wait in the history database, so they also batch up across the
+//! separate runs of a scheduled `--once` service. With `--no-history` nothing is
+//! kept and every alert is mailed right away.
+
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+use crate::modules::history::HistoryStorage;
+use crate::modules::types::{AlertKind, EmailSettings, MonitorAlert};
+
+/// How long the oldest alert waits before a digest goes out
+pub const DIGEST_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+const SUBMISSION_PORT: u16 = 587;
+const SMTPS_PORT: u16 = 465;
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Mail `alert`, or queue it and mail the digest once it is due. Called every
+/// monitor cycle, with or without an alert, so a due digest goes out on time.
+pub async fn notify(
+    settings: &EmailSettings,
+    host_label: Option<&str>,
+    alert: Option<MonitorAlert>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if settings.to.is_empty() {
+        return Err("No recipients, set `to` in the [email] table".into());
+    }
+
+    if !settings.digest || HistoryStorage::is_disabled() {
+        if let Some(alert) = alert {
+            let (subject, body) = alert_message(host_label, &alert);
+            send(settings, &subject, body).await?;
+        }
+        return Ok(());
+    }
+
+    // Closed again before sending, so a slow server doesn't hold the database lock
+    let pending = {
+        let storage = HistoryStorage::new()?;
+        if let Some(alert) = &alert {
+            storage.queue_alert(alert)?;
+        }
+        storage.get_pending_alerts()?
+    };
+    if !digest_due(&pending, Utc::now()) {
+        return Ok(());
+    }
+
+    let (subject, body) = digest_message(host_label, &pending);
+    send(settings, &subject, body).await?;
+    if let Some(newest) = pending.last() {
+        HistoryStorage::new()?.clear_pending_alerts(newest.timestamp)?;
+    }
+    Ok(())
+}
+
+/// Whether the oldest of the pending alerts, oldest first, has waited long enough
+pub fn digest_due(pending: &[MonitorAlert], now: DateTime<Utc>) -> bool {
+    pending
+        .first()
+        .is_some_and(|oldest| now - oldest.timestamp >= DIGEST_INTERVAL)
+}
+
+/// Subject and body of a mail about a single alert
+pub fn alert_message(host_label: Option<&str>, alert: &MonitorAlert) -> (String, String) {
+    (
+        subject(host_label, &alert.kind.to_string()),
+        format!("{}\n", alert_line(alert)),
+    )
+}
+
+/// Subject and body of a mail listing every pending alert
+pub fn digest_message(host_label: Option<&str>, alerts: &[MonitorAlert]) -> (String, String) {
+    let count = |kind: AlertKind| alerts.iter().filter(|alert| alert.kind == kind).count();
+    let mut summary = Vec::new();
+    for (kind, label) in [
+        (AlertKind::Outage, "outage"),
+        (AlertKind::BelowPlan, "below plan"),
+    ] {
+        match count(kind) {
+            0 => {}
+            1 => summary.push(format!("1 {}", label)),
+            n if kind == AlertKind::Outage => summary.push(format!("{} {}s", n, label)),
+            n => summary.push(format!("{} {}", n, label)),
+        }
+    }
+
+    let body: String = alerts
+        .iter()
+        .map(|alert| format!("{}\n", alert_line(alert)))
+        .collect();
+    (subject(host_label, &summary.join(", ")), body)
+}
+
+fn subject(host_label: Option<&str>, text: &str) -> String {
+    match host_label {
+        Some(label) => format!("[netrunner {}] {}", label, text),
+        None => format!("[netrunner] {}", text),
+    }
+}
+
+fn alert_line(alert: &MonitorAlert) -> String {
+    format!(
+        "{}  {}: {}",
+        alert
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        alert.kind,
+        alert.message
+    )
+}
+
+async fn send(
+    settings: &EmailSettings,
+    subject: &str,
+    body: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut builder = Message::builder()
+        .from(settings.from.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in &settings.to {
+        builder = builder.to(recipient.parse()?);
+    }
+
+    transport(settings)?.send(builder.body(body)?).await?;
+    Ok(())
+}
+
+fn transport(
+    settings: &EmailSettings,
+) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+    let port = settings.port.unwrap_or(SUBMISSION_PORT);
+    let builder = if port == SMTPS_PORT {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.server)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.server)?
+    };
+    let builder = builder.port(port).timeout(Some(SEND_TIMEOUT));
+
+    Ok(match (&settings.username, &settings.password) {
+        (Some(username), Some(password)) => builder
+            .credentials(Credentials::new(username.clone(), password.clone()))
+            .build(),
+        _ => builder.build(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(minutes_ago: i64, kind: AlertKind, now: DateTime<Utc>) -> MonitorAlert {
+        MonitorAlert {
+            timestamp: now - TimeDelta::minutes(minutes_ago),
+            kind,
+            message: "download 12.0 Mbps".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_digest_due() {
+        let now = Utc::now();
+        assert!(!digest_due(&[], now));
+        assert!(!digest_due(&[alert(59, AlertKind::Outage, now)], now));
+        assert!(digest_due(
+            &[
+                alert(61, AlertKind::Outage, now),
+                alert(1, AlertKind::BelowPlan, now)
+            ],
+            now
+        ));
+    }
+
+    #[test]
+    fn test_messages() {
+        let now = Utc::now();
+        let (subject, body) = alert_message(Some("nas"), &alert(0, AlertKind::BelowPlan, now));
+        assert_eq!(subject, "[netrunner nas] Below plan");
+        assert!(body.ends_with("Below plan: download 12.0 Mbps\n"));
+
+        let alerts = [
+            alert(50, AlertKind::Outage, now),
+            alert(30, AlertKind::BelowPlan, now),
+            alert(10, AlertKind::Outage, now),
+        ];
+        let (subject, body) = digest_message(None, &alerts);
+        assert_eq!(subject, "[netrunner] 2 outages, 1 below plan");
+        assert_eq!(body.lines().count(), 3);
+    }
+}
@@ -0,0 +1,451 @@
+//! ISP Complaint Evidence
+//!
+//! Collects a series of spaced speed tests plus network diagnostics into a
+//! single bundle meant to be attached to a dispute with an ISP. The bundle
+//! records when and where every test ran, summarises the measurements against
+//! the advertised plan and carries a SHA-256 checksum. The checksum catches
+//! corruption in transit, not deliberate edits, as anyone can recompute it:
+//! `--sign` adds a signature for that (see `signing`).
+//!
+//! Output formats are picked from the file extension:
+//! - `.zip`: `evidence.json`, a human readable `report.txt` and `SHA256SUMS`
+//! - `.json`: the bundle itself, with the digest in a `.sha256` file next to it
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::modules::types::{median, AdvertisedPlan, NetworkDiagnostics, SpeedTestResult};
+
+/// Version of the bundle layout, bumped when fields change meaning
+pub const EVIDENCE_FORMAT_VERSION: u32 = 1;
+
+/// Where the tests were run from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnvironmentInfo {
+    pub netrunner_version: String,
+    pub os: String,
+    pub arch: String,
+    pub client_ip: Option<IpAddr>,
+    pub isp: Option<String>,
+}
+
+impl EnvironmentInfo {
+    /// Describe the current machine, taking network details from the first result that has them
+    pub fn collect(results: &[SpeedTestResult]) -> Self {
+        Self {
+            netrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            client_ip: results.iter().find_map(|r| r.client_ip),
+            isp: results.iter().find_map(|r| r.isp.clone()),
+        }
+    }
+}
+
+/// Aggregate figures over all tests in a bundle
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EvidenceSummary {
+    pub test_count: usize,
+    pub first_test_at: Option<DateTime<Utc>>,
+    pub last_test_at: Option<DateTime<Utc>>,
+    pub median_download_mbps: f64,
+    pub min_download_mbps: f64,
+    pub max_download_mbps: f64,
+    pub median_upload_mbps: f64,
+    pub min_upload_mbps: f64,
+    pub max_upload_mbps: f64,
+    pub median_ping_ms: f64,
+    pub avg_packet_loss_percent: f64,
+    /// Median download as a percentage of the advertised plan
+    pub download_percent_of_plan: Option<f64>,
+    /// Median upload as a percentage of the advertised plan
+    pub upload_percent_of_plan: Option<f64>,
+}
+
+impl EvidenceSummary {
+    pub fn from_results(results: &[SpeedTestResult], plan: Option<&AdvertisedPlan>) -> Self {
+        let download: Vec<f64> = results.iter().map(|r| r.download_mbps).collect();
+        let upload: Vec<f64> = results.iter().map(|r| r.upload_mbps).collect();
+        let ping: Vec<f64> = results.iter().map(|r| r.ping_ms).collect();
+
+        let median_download_mbps = median(&download);
+        let median_upload_mbps = median(&upload);
+
+        Self {
+            test_count: results.len(),
+            first_test_at: results.iter().map(|r| r.timestamp).min(),
+            last_test_at: results.iter().map(|r| r.timestamp).max(),
+            median_download_mbps,
+            min_download_mbps: download.iter().copied().reduce(f64::min).unwrap_or(0.0),
+            max_download_mbps: download.iter().copied().reduce(f64::max).unwrap_or(0.0),
+            median_upload_mbps,
+            min_upload_mbps: upload.iter().copied().reduce(f64::min).unwrap_or(0.0),
+            max_upload_mbps: upload.iter().copied().reduce(f64::max).unwrap_or(0.0),
+            median_ping_ms: median(&ping),
+            avg_packet_loss_percent: if results.is_empty() {
+                0.0
+            } else {
+                results.iter().map(|r| r.packet_loss_percent).sum::<f64>() / results.len() as f64
+            },
+            download_percent_of_plan: plan.map(|p| p.download_percent(median_download_mbps)),
+            upload_percent_of_plan: plan.map(|p| p.upload_percent(median_upload_mbps)),
+        }
+    }
+}
+
+/// Everything needed to back up a complaint about connection speed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceBundle {
+    pub format_version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub environment: EnvironmentInfo,
+    pub plan: Option<AdvertisedPlan>,
+    pub summary: EvidenceSummary,
+    pub results: Vec<SpeedTestResult>,
+    pub diagnostics: Option<NetworkDiagnostics>,
+}
+
+impl EvidenceBundle {
+    pub fn new(
+        results: Vec<SpeedTestResult>,
+        diagnostics: Option<NetworkDiagnostics>,
+        plan: Option<AdvertisedPlan>,
+    ) -> Self {
+        Self {
+            format_version: EVIDENCE_FORMAT_VERSION,
+            generated_at: Utc::now(),
+            environment: EnvironmentInfo::collect(&results),
+            summary: EvidenceSummary::from_results(&results, plan.as_ref()),
+            plan,
+            results,
+            diagnostics,
+        }
+    }
+
+    /// Write the bundle to `path` as ZIP or JSON, depending on the extension.
+    /// Returns the SHA-256 digest of the JSON document.
+    pub fn write(&self, path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        let digest = sha256_hex(json.as_bytes());
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("zip") => self.write_zip(path, &json, &digest)?,
+            Some("json") => {
+                std::fs::write(path, &json)?;
+                std::fs::write(
+                    digest_path(path),
+                    format!("{}  {}\n", digest, file_name(path)),
+                )?;
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported evidence format for {}, use a .zip or .json file name",
+                    path.display()
+                )
+                .into())
+            }
+        }
+
+        Ok(digest)
+    }
+
+    fn write_zip(
+        &self,
+        path: &Path,
+        json: &str,
+        json_digest: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let report = self.render_report(json_digest);
+        let checksums = format!(
+            "{}  evidence.json\n{}  report.txt\n",
+            json_digest,
+            sha256_hex(report.as_bytes())
+        );
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in [
+            ("evidence.json", json),
+            ("report.txt", report.as_str()),
+            ("SHA256SUMS", checksums.as_str()),
+        ] {
+            zip.start_file(name, options)?;
+            zip.write_all(contents.as_bytes())?;
+        }
+        zip.finish()?;
+
+        Ok(())
+    }
+
+    /// Plain-text report laid out for attaching to a support ticket
+    pub fn render_report(&self, json_digest: &str) -> String {
+        let summary = &self.summary;
+        let time = |t: Option<DateTime<Utc>>| {
+            t.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "n/a".to_string())
+        };
+        let mut out = String::new();
+
+        // Writing to a String cannot fail
+        let _ = writeln!(out, "INTERNET SPEED EVIDENCE REPORT");
+        let _ = writeln!(out, "==============================");
+        let _ = writeln!(
+            out,
+            "Generated:        {}",
+            self.generated_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        let _ = writeln!(
+            out,
+            "Collection period: {} - {}",
+            time(summary.first_test_at),
+            time(summary.last_test_at)
+        );
+        let _ = writeln!(
+            out,
+            "ISP:              {}",
+            self.environment.isp.as_deref().unwrap_or("Unknown")
+        );
+        let _ = writeln!(
+            out,
+            "Public IP:        {}",
+            self.environment
+                .client_ip
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|| "Unknown".to_string())
+        );
+        let _ = writeln!(
+            out,
+            "Measured with:    netrunner_cli {} ({}/{})",
+            self.environment.netrunner_version, self.environment.os, self.environment.arch
+        );
+        let _ = writeln!(
+            out,
+            "Advertised plan:  {}",
+            self.plan
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "not configured".to_string())
+        );
+
+        let _ = writeln!(out, "\nINDIVIDUAL TESTS");
+        let _ = writeln!(out, "----------------");
+        let _ = writeln!(
+            out,
+            "{:<24} {:>10} {:>10} {:>9} {:>7}  Server",
+            "Time (UTC)", "Down Mbps", "Up Mbps", "Ping ms", "Loss %"
+        );
+        for result in &self.results {
+            let _ = writeln!(
+                out,
+                "{:<24} {:>10.2} {:>10.2} {:>9.1} {:>7.1}  {}",
+                result.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                result.download_mbps,
+                result.upload_mbps,
+                result.ping_ms,
+                result.packet_loss_percent,
+                result.server_location
+            );
+        }
+
+        let _ = writeln!(out, "\nSUMMARY");
+        let _ = writeln!(out, "-------");
+        let _ = writeln!(out, "Tests:            {}", summary.test_count);
+        let _ = writeln!(
+            out,
+            "Download:         median {:.2} Mbps (min {:.2}, max {:.2})",
+            summary.median_download_mbps, summary.min_download_mbps, summary.max_download_mbps
+        );
+        let _ = writeln!(
+            out,
+            "Upload:           median {:.2} Mbps (min {:.2}, max {:.2})",
+            summary.median_upload_mbps, summary.min_upload_mbps, summary.max_upload_mbps
+        );
+        let _ = writeln!(
+            out,
+            "Ping:             median {:.1} ms",
+            summary.median_ping_ms
+        );
+        let _ = writeln!(
+            out,
+            "Packet loss:      average {:.2}%",
+            summary.avg_packet_loss_percent
+        );
+        if let (Some(down), Some(up)) = (
+            summary.download_percent_of_plan,
+            summary.upload_percent_of_plan,
+        ) {
+            let _ = writeln!(
+                out,
+                "Plan attainment:  download {:.0}%, upload {:.0}% of advertised speed",
+                down, up
+            );
+        }
+
+        if let Some(diagnostics) = &self.diagnostics {
+            let _ = writeln!(out, "\nNETWORK DIAGNOSTICS");
+            let _ = writeln!(out, "-------------------");
+            let _ = writeln!(
+                out,
+                "Gateway:          {}",
+                diagnostics
+                    .gateway_ip
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| "Unknown".to_string())
+            );
+            let _ = writeln!(
+                out,
+                "DNS response:     {:.1} ms",
+                diagnostics.dns_response_time_ms
+            );
+            let _ = writeln!(out, "Route hops:       {}", diagnostics.route_hops.len());
+            let _ = writeln!(
+                out,
+                "IPv6 available:   {}",
+                if diagnostics.is_ipv6_available {
+                    "yes"
+                } else {
+                    "no"
+                }
+            );
+        }
+
+        let _ = writeln!(out, "\nCHECKSUM");
+        let _ = writeln!(out, "--------");
+        let _ = writeln!(out, "SHA-256 of evidence.json: {}", json_digest);
+
+        out
+    }
+}
+
+/// Default output file name for a bundle generated at `at`
+pub fn default_output_path(at: DateTime<Utc>) -> PathBuf {
+    PathBuf::from(format!(
+        "netrunner-evidence-{}.zip",
+        at.format("%Y%m%d-%H%M%S")
+    ))
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn digest_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    fn sample_results() -> Vec<SpeedTestResult> {
+        [(180.0, 20.0), (240.0, 35.0), (200.0, 30.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, (down, up))| SpeedTestResult {
+                timestamp: Utc::now() - chrono::Duration::minutes(30 - 10 * i as i64),
+                download_mbps: *down,
+                upload_mbps: *up,
+                ping_ms: 12.0,
+                packet_loss_percent: i as f64,
+                isp: Some("Example Broadband".to_string()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_summary_against_plan() {
+        let plan: AdvertisedPlan = "500/50".parse().unwrap();
+        let summary = EvidenceSummary::from_results(&sample_results(), Some(&plan));
+
+        assert_eq!(summary.test_count, 3);
+        assert_eq!(summary.median_download_mbps, 200.0);
+        assert_eq!(summary.min_download_mbps, 180.0);
+        assert_eq!(summary.max_upload_mbps, 35.0);
+        assert_eq!(summary.avg_packet_loss_percent, 1.0);
+        assert_eq!(summary.download_percent_of_plan, Some(40.0));
+        assert_eq!(summary.upload_percent_of_plan, Some(60.0));
+        assert!(summary.first_test_at < summary.last_test_at);
+
+        let without_plan = EvidenceSummary::from_results(&[], None);
+        assert_eq!(without_plan.test_count, 0);
+        assert_eq!(without_plan.download_percent_of_plan, None);
+    }
+
+    #[test]
+    fn test_write_zip_bundle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("evidence.zip");
+        let bundle = EvidenceBundle::new(sample_results(), None, "500/50".parse().ok());
+
+        let digest = bundle.write(&path).unwrap();
+        assert_eq!(digest.len(), 64);
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut json = String::new();
+        archive
+            .by_name("evidence.json")
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(sha256_hex(json.as_bytes()), digest);
+
+        let mut report = String::new();
+        archive
+            .by_name("report.txt")
+            .unwrap()
+            .read_to_string(&mut report)
+            .unwrap();
+        assert!(report.contains("Example Broadband"));
+        assert!(report.contains("download 40%, upload 60%"));
+        assert!(report.contains(&digest));
+
+        let mut checksums = String::new();
+        archive
+            .by_name("SHA256SUMS")
+            .unwrap()
+            .read_to_string(&mut checksums)
+            .unwrap();
+        assert!(checksums.starts_with(&format!("{}  evidence.json", digest)));
+    }
+
+    #[test]
+    fn test_write_json_bundle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("evidence.json");
+        let bundle = EvidenceBundle::new(sample_results(), None, None);
+
+        let digest = bundle.write(&path).unwrap();
+        let restored: EvidenceBundle =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(restored.results.len(), 3);
+        assert_eq!(restored.format_version, EVIDENCE_FORMAT_VERSION);
+
+        let sidecar = std::fs::read_to_string(dir.path().join("evidence.json.sha256")).unwrap();
+        assert_eq!(sidecar, format!("{}  evidence.json\n", digest));
+
+        assert!(bundle.write(&dir.path().join("evidence.txt")).is_err());
+    }
+}
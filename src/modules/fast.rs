@@ -0,0 +1,204 @@
+//! Netflix fast.com Servers
+//!
+//! fast.com measures against Netflix Open Connect appliances, the caches that
+//! serve Netflix video from inside or next to the ISP's network. Its page script
+//! carries an API token, and api.fast.com hands out signed URLs of the caches
+//! nearest the caller for it. Tests with `--provider fast` run the usual transfer
+//! engine against those URLs, so the result matches the number Netflix shows:
+//! downloads request byte ranges, uploads POST to an empty range.
+
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::modules::types::{ServerCapabilities, ServerProtocol, ServerProvider, TestServer};
+
+pub const FAST_URL: &str = "https://fast.com";
+
+/// Hands out the nearest caches for a token from the fast.com script
+pub const API_URL: &str = "https://api.fast.com/netflix/speedtest/v2";
+
+/// Caches asked for, as many as fast.com itself uses
+const URL_COUNT: &str = "5";
+
+/// Largest range a cache serves in one response
+pub const MAX_RANGE_BYTES: u64 = 25 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    #[serde(default)]
+    targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    url: String,
+    #[serde(default)]
+    location: Option<TargetLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetLocation {
+    city: Option<String>,
+    country: Option<String>,
+}
+
+impl Target {
+    fn into_server(self) -> TestServer {
+        let city = self.location.as_ref().and_then(|l| l.city.clone());
+        let country = self.location.as_ref().and_then(|l| l.country.clone());
+        let location = [city.as_deref(), country.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+        TestServer {
+            name: match &city {
+                Some(city) => format!("Netflix {}", city),
+                None => "Netflix".to_string(),
+            },
+            url: self.url,
+            location: if location.is_empty() {
+                "Netflix Open Connect".to_string()
+            } else {
+                location
+            },
+            // fast.com already picked the nearest, they are ranked on latency alone
+            distance_km: Some(0.0),
+            latency_ms: None,
+            provider: ServerProvider::Netflix,
+            protocol: ServerProtocol::Fast,
+            capabilities: ServerCapabilities {
+                supports_download: true,
+                supports_upload: true,
+                supports_latency: true,
+                max_test_size_mb: MAX_RANGE_BYTES / (1024 * 1024),
+                geographic_weight: 1.0,
+            },
+            quality_score: None,
+            country_code: country,
+            city,
+            is_backup: false,
+            endpoints: None,
+        }
+    }
+}
+
+/// The caches fast.com would test this machine against, nearest first
+pub async fn servers(
+    client: &reqwest::Client,
+) -> Result<Vec<TestServer>, Box<dyn std::error::Error>> {
+    let get_text = |url: String| async move {
+        client
+            .get(url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    };
+
+    let page = get_text(FAST_URL.to_string()).await?;
+    let script = script_path(&page).ok_or("the fast.com page has no app script")?;
+    let script = get_text(format!("{}{}", FAST_URL, script)).await?;
+    let token = find_token(&script).ok_or("the fast.com script has no API token")?;
+
+    let url = url::Url::parse_with_params(
+        API_URL,
+        [("https", "true"), ("token", token), ("urlCount", URL_COUNT)],
+    )?;
+    let response: ApiResponse = client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if response.targets.is_empty() {
+        return Err("api.fast.com handed out no servers".into());
+    }
+    Ok(response
+        .targets
+        .into_iter()
+        .map(Target::into_server)
+        .collect())
+}
+
+/// Path of the page's `app-<hash>.js` script, which holds the token
+fn script_path(page: &str) -> Option<&str> {
+    let start = page.find("src=\"/app-")? + "src=\"".len();
+    let end = start + page[start..].find('"')?;
+    Some(&page[start..end])
+}
+
+fn find_token(script: &str) -> Option<&str> {
+    let start = script.find("token:\"")? + "token:\"".len();
+    let end = start + script[start..].find('"')?;
+    Some(&script[start..end]).filter(|token| !token.is_empty())
+}
+
+/// `url` asking for bytes `0..=last` of the cache's test file, as a path segment
+/// in front of the signature
+pub fn range_url(url: &str, last: u64) -> String {
+    match url.split_once('?') {
+        Some((path, query)) => format!("{}/range/0-{}?{}", path.trim_end_matches('/'), last, query),
+        None => format!("{}/range/0-{}", url.trim_end_matches('/'), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_and_token() {
+        let page = r#"<html><head><script src="/app-ed402d.js"></script></head></html>"#;
+        assert_eq!(script_path(page), Some("/app-ed402d.js"));
+        assert_eq!(script_path("<html></html>"), None);
+
+        let script = r#"var a={https:!0,token:"YXNkZmFzZGxmbnNkYWZoYXNkZmhrYWxm",urlCount:5}"#;
+        assert_eq!(find_token(script), Some("YXNkZmFzZGxmbnNkYWZoYXNkZmhrYWxm"));
+        assert_eq!(find_token(r#"token:"""#), None);
+    }
+
+    #[test]
+    fn test_range_url() {
+        assert_eq!(
+            range_url(
+                "https://ipv4-c001-fra002-ix.1.oca.nflxvideo.net/speedtest?c=de&e=1&t=x",
+                26_214_399
+            ),
+            "https://ipv4-c001-fra002-ix.1.oca.nflxvideo.net/speedtest/range/0-26214399?c=de&e=1&t=x"
+        );
+        assert_eq!(
+            range_url("https://oca.example/speedtest", 0),
+            "https://oca.example/speedtest/range/0-0"
+        );
+    }
+
+    #[test]
+    fn test_targets_become_servers() {
+        let response: ApiResponse = serde_json::from_str(
+            r#"{"client":{"ip":"192.0.2.1"},"targets":[
+                {"name":"https://ipv4-c001-fra002-ix.1.oca.nflxvideo.net/speedtest",
+                 "url":"https://ipv4-c001-fra002-ix.1.oca.nflxvideo.net/speedtest?c=de",
+                 "location":{"city":"Frankfurt","country":"DE"}},
+                {"url":"https://ipv4-c002.example/speedtest?c=de"}]}"#,
+        )
+        .unwrap();
+        let servers: Vec<TestServer> = response
+            .targets
+            .into_iter()
+            .map(Target::into_server)
+            .collect();
+
+        assert_eq!(servers[0].name, "Netflix Frankfurt");
+        assert_eq!(servers[0].location, "Frankfurt, DE");
+        assert_eq!(servers[0].country_code.as_deref(), Some("DE"));
+        assert_eq!(servers[0].provider, ServerProvider::Netflix);
+        assert_eq!(servers[0].protocol, ServerProtocol::Fast);
+        assert_eq!(servers[1].name, "Netflix");
+        assert_eq!(servers[1].location, "Netflix Open Connect");
+    }
+}
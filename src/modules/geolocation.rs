@@ -0,0 +1,474 @@
+//! Geolocation Providers
+//!
+//! The speed test picks servers near the tester, so it first asks an IP
+//! geolocation service where it is. Each service sits behind [`GeoProvider`];
+//! the built-in ones can be switched off in `config.toml` and replaced by a
+//! self-hosted service whose JSON answer is mapped with JSON pointers, for
+//! networks that must not talk to third-party IP APIs.
+
+use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::modules::types::{CustomGeoProvider, GeoSettings};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoLocation {
+    pub country: String,
+    pub city: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub isp: Option<String>,
+}
+
+/// A service answering where the caller's public address is
+#[async_trait]
+pub trait GeoProvider: Send + Sync {
+    /// Name shown in output and used to disable the provider, e.g. `ipapi.co`
+    fn name(&self) -> &str;
+
+    fn url(&self) -> &str;
+
+    /// Read a location out of the service's JSON answer
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>>;
+
+    async fn locate(
+        &self,
+        client: &Client,
+        timeout: Duration,
+    ) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        let response = client.get(self.url()).timeout(timeout).send().await?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP error: {}", response.status()).into());
+        }
+
+        let json: Value = response.json().await?;
+        self.parse(&json)
+    }
+}
+
+/// Providers enabled by `settings` in the order they are listed, built-in ones first
+pub fn providers(settings: &GeoSettings) -> Vec<Box<dyn GeoProvider>> {
+    let builtin: Vec<Box<dyn GeoProvider>> = vec![
+        Box::new(IpapiCo),
+        Box::new(IpApiCom),
+        Box::new(IpinfoIo),
+        Box::new(FreegeoipApp),
+        Box::new(IpwhoisApp),
+    ];
+
+    builtin
+        .into_iter()
+        .filter(|_| settings.builtin)
+        .filter(|provider| {
+            !settings
+                .disabled
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(provider.name()))
+        })
+        .chain(
+            settings
+                .custom
+                .iter()
+                .cloned()
+                .map(|provider| Box::new(provider) as Box<dyn GeoProvider>),
+        )
+        .collect()
+}
+
+/// Ask every provider at once and return the first valid location with the name of
+/// the provider that gave it. Returning drops, and so cancels, the requests still in
+/// flight. `None` when every provider failed or none answered within `overall`.
+pub async fn first_location<'a>(
+    providers: &'a [Box<dyn GeoProvider>],
+    client: &Client,
+    per_provider: Duration,
+    overall: Duration,
+) -> Option<(&'a str, GeoLocation)> {
+    let mut pending: FuturesUnordered<_> = providers
+        .iter()
+        .map(|provider| async move {
+            let result = provider
+                .locate(client, per_provider)
+                .await
+                .map_err(|e| e.to_string());
+            (provider.name(), result)
+        })
+        .collect();
+    let race = async {
+        while let Some((name, result)) = pending.next().await {
+            match result {
+                Ok(geo) => return Some((name, geo)),
+                Err(e) => {
+                    if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                        eprintln!("[TRACE] {} geolocation failed: {}", name, e);
+                    }
+                }
+            }
+        }
+        None
+    };
+
+    match tokio::time::timeout(overall, race).await {
+        Ok(found) => found,
+        Err(_) => {
+            if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                eprintln!(
+                    "[TRACE] no geolocation service answered within {}s",
+                    overall.as_secs()
+                );
+            }
+            None
+        }
+    }
+}
+
+/// A non-empty string field, "Unknown" counting as empty
+fn text(value: Option<&Value>, field: &str) -> Result<String, String> {
+    value
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty() && *s != "Unknown")
+        .map(String::from)
+        .ok_or_else(|| format!("Invalid {}", field))
+}
+
+/// A coordinate given as a number or a numeric string
+fn coordinate(value: Option<&Value>, field: &str) -> Result<f64, String> {
+    value
+        .and_then(|v| v.as_f64().or_else(|| v.as_str()?.trim().parse().ok()))
+        .ok_or_else(|| format!("Invalid {}", field))
+}
+
+/// Services answer 0,0 when they cannot place an address
+fn located(
+    country: String,
+    city: String,
+    latitude: f64,
+    longitude: f64,
+    isp: Option<&Value>,
+) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+    if latitude == 0.0 && longitude == 0.0 {
+        return Err("Invalid coordinates".into());
+    }
+
+    Ok(GeoLocation {
+        country,
+        city,
+        latitude,
+        longitude,
+        isp: isp.and_then(Value::as_str).map(String::from),
+    })
+}
+
+struct IpapiCo;
+
+impl GeoProvider for IpapiCo {
+    fn name(&self) -> &str {
+        "ipapi.co"
+    }
+
+    fn url(&self) -> &str {
+        "https://ipapi.co/json/"
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        if json.get("error").is_some() {
+            return Err(format!(
+                "API error: {}",
+                json["reason"].as_str().unwrap_or("Unknown")
+            )
+            .into());
+        }
+
+        located(
+            text(json.get("country_name"), "country")?,
+            text(json.get("city"), "city")?,
+            coordinate(json.get("latitude"), "latitude")?,
+            coordinate(json.get("longitude"), "longitude")?,
+            json.get("org"),
+        )
+    }
+}
+
+struct IpApiCom;
+
+impl GeoProvider for IpApiCom {
+    fn name(&self) -> &str {
+        "ip-api.com"
+    }
+
+    fn url(&self) -> &str {
+        "http://ip-api.com/json/?fields=status,message,country,city,lat,lon,isp"
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        if json["status"].as_str() != Some("success") {
+            return Err(format!(
+                "API error: {}",
+                json["message"].as_str().unwrap_or("Unknown")
+            )
+            .into());
+        }
+
+        located(
+            text(json.get("country"), "country")?,
+            text(json.get("city"), "city")?,
+            coordinate(json.get("lat"), "latitude")?,
+            coordinate(json.get("lon"), "longitude")?,
+            json.get("isp"),
+        )
+    }
+}
+
+struct IpinfoIo;
+
+impl GeoProvider for IpinfoIo {
+    fn name(&self) -> &str {
+        "ipinfo.io"
+    }
+
+    fn url(&self) -> &str {
+        "https://ipinfo.io/json"
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        // ipinfo.io returns "lat,lon" in the "loc" field
+        let loc = json["loc"].as_str().ok_or("Invalid location")?;
+        let (latitude, longitude) = loc.split_once(',').ok_or("Invalid coordinates format")?;
+
+        located(
+            text(json.get("country"), "country")?,
+            text(json.get("city"), "city")?,
+            latitude.parse().map_err(|_| "Invalid latitude")?,
+            longitude.parse().map_err(|_| "Invalid longitude")?,
+            json.get("org"),
+        )
+    }
+}
+
+struct FreegeoipApp;
+
+impl GeoProvider for FreegeoipApp {
+    fn name(&self) -> &str {
+        "freegeoip.app"
+    }
+
+    fn url(&self) -> &str {
+        "https://freegeoip.app/json/"
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        located(
+            text(json.get("country_name"), "country")?,
+            text(json.get("city"), "city")?,
+            coordinate(json.get("latitude"), "latitude")?,
+            coordinate(json.get("longitude"), "longitude")?,
+            None,
+        )
+    }
+}
+
+struct IpwhoisApp;
+
+impl GeoProvider for IpwhoisApp {
+    fn name(&self) -> &str {
+        "ipwhois.app"
+    }
+
+    fn url(&self) -> &str {
+        "https://ipwho.is/"
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        if !json["success"].as_bool().unwrap_or(false) {
+            return Err(format!(
+                "API error: {}",
+                json["message"].as_str().unwrap_or("Unknown")
+            )
+            .into());
+        }
+
+        located(
+            text(json.get("country"), "country")?,
+            text(json.get("city"), "city")?,
+            coordinate(json.get("latitude"), "latitude")?,
+            coordinate(json.get("longitude"), "longitude")?,
+            json.pointer("/connection/isp"),
+        )
+    }
+}
+
+impl GeoProvider for CustomGeoProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn parse(&self, json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        located(
+            text(json.pointer(&self.country), "country")?,
+            text(json.pointer(&self.city), "city")?,
+            coordinate(json.pointer(&self.latitude), "latitude")?,
+            coordinate(json.pointer(&self.longitude), "longitude")?,
+            self.isp.as_deref().and_then(|isp| json.pointer(isp)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Answers after `delay` without touching the network
+    struct Scripted {
+        name: &'static str,
+        delay: Duration,
+        city: Option<&'static str>,
+    }
+
+    #[async_trait]
+    impl GeoProvider for Scripted {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn url(&self) -> &str {
+            "http://localhost/"
+        }
+
+        fn parse(&self, _json: &Value) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+            unreachable!("scripted providers never fetch")
+        }
+
+        async fn locate(
+            &self,
+            _client: &Client,
+            _timeout: Duration,
+        ) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+            tokio::time::sleep(self.delay).await;
+            let city = self.city.ok_or("HTTP error: 429 Too Many Requests")?;
+            located("Germany".to_string(), city.to_string(), 52.52, 13.405, None)
+        }
+    }
+
+    fn scripted(
+        name: &'static str,
+        delay_ms: u64,
+        city: Option<&'static str>,
+    ) -> Box<dyn GeoProvider> {
+        Box::new(Scripted {
+            name,
+            delay: Duration::from_millis(delay_ms),
+            city,
+        })
+    }
+
+    fn client() -> Client {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        Client::new()
+    }
+
+    #[tokio::test]
+    async fn test_first_location_wins() {
+        let providers = vec![
+            scripted("slow", 5_000, Some("Munich")),
+            scripted("broken", 10, None),
+            scripted("fast", 50, Some("Berlin")),
+        ];
+        let start = std::time::Instant::now();
+        let found = first_location(
+            &providers,
+            &client(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        )
+        .await;
+
+        let (name, geo) = found.unwrap();
+        assert_eq!(name, "fast");
+        assert_eq!(geo.city, "Berlin");
+        // The slow lookup was cancelled rather than awaited
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_first_location_gives_up() {
+        let failing = vec![scripted("a", 10, None), scripted("b", 20, None)];
+        let client = client();
+        let timeout = Duration::from_secs(10);
+        assert!(first_location(&failing, &client, timeout, timeout)
+            .await
+            .is_none());
+
+        let slow = vec![scripted("slow", 5_000, Some("Munich"))];
+        let start = std::time::Instant::now();
+        let timed_out = first_location(&slow, &client, timeout, Duration::from_millis(100)).await;
+        assert!(timed_out.is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_builtin_parsers() {
+        let ipapi = json!({
+            "country_name": "Germany", "city": "Berlin",
+            "latitude": 52.52, "longitude": 13.405, "org": "Deutsche Telekom AG"
+        });
+        let geo = IpapiCo.parse(&ipapi).unwrap();
+        assert_eq!(geo.city, "Berlin");
+        assert_eq!(geo.isp.as_deref(), Some("Deutsche Telekom AG"));
+        assert!(IpapiCo
+            .parse(&json!({"error": true, "reason": "RateLimited"}))
+            .is_err());
+
+        let ipinfo = json!({"country": "DE", "city": "Berlin", "loc": "52.5200,13.4050"});
+        let geo = IpinfoIo.parse(&ipinfo).unwrap();
+        assert_eq!((geo.latitude, geo.longitude), (52.52, 13.405));
+
+        let unplaced = json!({"status": "success", "country": "Germany", "city": "Berlin", "lat": 0.0, "lon": 0.0});
+        assert!(IpApiCom.parse(&unplaced).is_err());
+
+        let ipwhois = json!({
+            "success": true, "country": "Germany", "city": "Berlin",
+            "latitude": 52.52, "longitude": 13.405, "connection": {"isp": "Vodafone"}
+        });
+        assert_eq!(
+            IpwhoisApp.parse(&ipwhois).unwrap().isp.as_deref(),
+            Some("Vodafone")
+        );
+    }
+
+    #[test]
+    fn test_custom_provider() {
+        let provider: CustomGeoProvider = toml::from_str(
+            r#"
+                name = "office"
+                url = "https://geo.example.internal/whoami"
+                city = "/location/city"
+                latitude = "/location/lat"
+                longitude = "/location/lon"
+                isp = "/asn/name"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(provider.country, "/country");
+
+        let answer = json!({
+            "country": "Germany",
+            "location": {"city": "Hamburg", "lat": "53.55", "lon": 9.99},
+            "asn": {"name": "Office Uplink"}
+        });
+        let geo = provider.parse(&answer).unwrap();
+        assert_eq!(geo.city, "Hamburg");
+        assert_eq!((geo.latitude, geo.longitude), (53.55, 9.99));
+        assert_eq!(geo.isp.as_deref(), Some("Office Uplink"));
+
+        assert!(provider.parse(&json!({"country": "Germany"})).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Happy Eyeballs Checks
+//!
+//! Dual-stack clients race IPv6 against IPv4 (RFC 8305): IPv6 goes first and IPv4
+//! joins after a short head start unless IPv6 has connected. When a network hands
+//! out IPv6 addresses but drops IPv6 traffic, clients that don't race wait for the
+//! IPv6 connect to time out before falling back, and every page load stalls for
+//! seconds. Each site is connected to over both families on its own, then raced.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use crate::modules::types::{HappyEyeballsCheck, HappyEyeballsIssue, IpFamily};
+
+/// Popular sites serving both A and AAAA records
+pub const SITES: [&str; 3] = ["www.google.com", "www.facebook.com", "www.wikipedia.org"];
+
+/// Head start IPv6 gets before IPv4 joins the race, RFC 8305's recommended default
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Falling back to IPv4 after this long is a noticeable stall
+pub const SLOW_FALLBACK_MS: f64 = 2000.0;
+
+/// Connect to `host` over IPv6 and IPv4 separately, then race the two
+pub async fn check_site(host: &str, port: u16, timeout: Duration) -> HappyEyeballsCheck {
+    let mut check = HappyEyeballsCheck {
+        host: host.to_string(),
+        preferred: None,
+        ipv6_connect_ms: None,
+        ipv4_connect_ms: None,
+        ipv6_error: None,
+        ipv4_error: None,
+        winner: None,
+        race_ms: None,
+        fallback_ms: None,
+        issues: Vec::new(),
+    };
+
+    let addresses: Vec<SocketAddr> =
+        match tokio::time::timeout(timeout, tokio::net::lookup_host((host, port))).await {
+            Ok(Ok(addresses)) => addresses.collect(),
+            Ok(Err(e)) => {
+                check.ipv6_error = Some(e.to_string());
+                check.ipv4_error = Some(e.to_string());
+                return check;
+            }
+            Err(_) => {
+                let error = format!("No address within {}s", timeout.as_secs());
+                check.ipv6_error = Some(error.clone());
+                check.ipv4_error = Some(error);
+                return check;
+            }
+        };
+    check.preferred = addresses.first().map(|address| IpFamily::of(address.ip()));
+    let ipv6 = addresses.iter().find(|address| address.is_ipv6()).copied();
+    let ipv4 = addresses.iter().find(|address| address.is_ipv4()).copied();
+
+    let (ipv6_attempt, ipv4_attempt) =
+        tokio::join!(timed_connect(ipv6, timeout), timed_connect(ipv4, timeout));
+    // Without an IPv6 route the attempt fails at once, which no client waits on
+    let ipv6_routable = ipv6.is_some()
+        && !matches!(&ipv6_attempt.1, Err(e) if matches!(
+            e.kind(),
+            io::ErrorKind::NetworkUnreachable | io::ErrorKind::AddrNotAvailable
+        ));
+    match ipv6_attempt {
+        (ms, Ok(())) => check.ipv6_connect_ms = Some(ms),
+        (_, Err(e)) => check.ipv6_error = Some(e.to_string()),
+    }
+    match ipv4_attempt {
+        (ms, Ok(())) => check.ipv4_connect_ms = Some(ms),
+        (_, Err(e)) => check.ipv4_error = Some(e.to_string()),
+    }
+    // A client trying IPv6 first waits for it to fail before connecting over IPv4
+    if ipv6_routable && check.ipv6_connect_ms.is_none() {
+        check.fallback_ms = check.ipv4_connect_ms.map(|ms| ipv6_attempt.0 + ms);
+    }
+
+    // Raced after the separate attempts, so the two don't compete with the race
+    let start = Instant::now();
+    check.winner = race(ipv6, ipv4, timeout).await;
+    check.race_ms = check.winner.map(|_| start.elapsed().as_secs_f64() * 1000.0);
+
+    check.issues = issues(ipv6_routable, &check);
+    check
+}
+
+/// Connect to `address`, returning the time taken until success or failure
+async fn timed_connect(address: Option<SocketAddr>, timeout: Duration) -> (f64, io::Result<()>) {
+    let start = Instant::now();
+    let result = match address {
+        Some(address) => match tokio::time::timeout(timeout, TcpStream::connect(address)).await {
+            Ok(result) => result.map(|_| ()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("No connection within {}s", timeout.as_secs()),
+            )),
+        },
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No address for this family",
+        )),
+    };
+    (start.elapsed().as_secs_f64() * 1000.0, result)
+}
+
+async fn connects(address: Option<SocketAddr>, timeout: Duration) -> bool {
+    timed_connect(address, timeout).await.1.is_ok()
+}
+
+/// Start IPv6, give it its head start, then let IPv4 compete; a failed attempt
+/// leaves the other to finish on its own
+async fn race(
+    ipv6: Option<SocketAddr>,
+    ipv4: Option<SocketAddr>,
+    timeout: Duration,
+) -> Option<IpFamily> {
+    let mut ipv6_attempt = Box::pin(connects(ipv6, timeout));
+
+    let head_start = tokio::select! {
+        connected = &mut ipv6_attempt => Some(connected),
+        _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY) => None,
+    };
+
+    match head_start {
+        Some(true) => Some(IpFamily::Ipv6),
+        // IPv6 failed early, IPv4 starts right away
+        Some(false) => connects(ipv4, timeout).await.then_some(IpFamily::Ipv4),
+        None => {
+            let mut ipv4_attempt = Box::pin(connects(ipv4, timeout));
+            tokio::select! {
+                connected = &mut ipv6_attempt => if connected {
+                    Some(IpFamily::Ipv6)
+                } else {
+                    ipv4_attempt.await.then_some(IpFamily::Ipv4)
+                },
+                connected = &mut ipv4_attempt => if connected {
+                    Some(IpFamily::Ipv4)
+                } else {
+                    ipv6_attempt.await.then_some(IpFamily::Ipv6)
+                },
+            }
+        }
+    }
+}
+
+/// Problems shown by a check; `ipv6_routable` is set when the site has IPv6
+/// addresses and this host has a route to them
+fn issues(ipv6_routable: bool, check: &HappyEyeballsCheck) -> Vec<HappyEyeballsIssue> {
+    let mut issues = Vec::new();
+    if !ipv6_routable || check.ipv4_connect_ms.is_none() {
+        return issues;
+    }
+
+    if check.ipv6_connect_ms.is_none() {
+        issues.push(HappyEyeballsIssue::BrokenIpv6);
+        if check.fallback_ms.is_some_and(|ms| ms >= SLOW_FALLBACK_MS) {
+            issues.push(HappyEyeballsIssue::SlowFallback);
+        }
+    } else if check.winner == Some(IpFamily::Ipv4) {
+        issues.push(HappyEyeballsIssue::SlowIpv6);
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(
+        ipv6_connect_ms: Option<f64>,
+        fallback_ms: Option<f64>,
+        winner: IpFamily,
+    ) -> HappyEyeballsCheck {
+        HappyEyeballsCheck {
+            host: "www.example.com".to_string(),
+            preferred: Some(IpFamily::Ipv6),
+            ipv6_connect_ms,
+            ipv4_connect_ms: Some(20.0),
+            ipv6_error: None,
+            ipv4_error: None,
+            winner: Some(winner),
+            race_ms: Some(20.0),
+            fallback_ms,
+            issues: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_issues() {
+        assert!(issues(true, &check(Some(15.0), None, IpFamily::Ipv6)).is_empty());
+        assert!(
+            issues(false, &check(None, None, IpFamily::Ipv4)).is_empty(),
+            "without IPv6 addresses or a route to them nothing falls back"
+        );
+        assert_eq!(
+            issues(true, &check(Some(400.0), None, IpFamily::Ipv4)),
+            vec![HappyEyeballsIssue::SlowIpv6]
+        );
+        assert_eq!(
+            issues(true, &check(None, Some(30.0), IpFamily::Ipv4)),
+            vec![HappyEyeballsIssue::BrokenIpv6]
+        );
+        assert_eq!(
+            issues(true, &check(None, Some(5020.0), IpFamily::Ipv4)),
+            vec![
+                HappyEyeballsIssue::BrokenIpv6,
+                HappyEyeballsIssue::SlowFallback
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_race_falls_back_to_ipv4() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ipv4 = listener.local_addr().unwrap();
+
+        let winner = race(None, Some(ipv4), Duration::from_secs(1)).await;
+        assert_eq!(winner, Some(IpFamily::Ipv4));
+        assert_eq!(race(None, None, Duration::from_secs(1)).await, None);
+    }
+}
@@ -6,19 +6,97 @@
 //! - Type-safe table definitions
 //! - Crash recovery
 //! - Compact storage
+//! - Versioned records, migrated when a newer version opens an older database
+//! - Backup archives (`history.json` plus `SHA256SUMS` in a ZIP) to move history
+//!   between machines
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
 use redb::{ReadableDatabase, ReadableTable, ReadableTableMetadata, TableDefinition};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use crate::modules::types::SpeedTestResult;
+use crate::modules::evidence::sha256_hex;
+use crate::modules::types::{
+    AdvertisedPlan, AggregateResult, Baseline, CachedServer, ConnectionQuality, DataUsage,
+    DiagnosticsRecord, EndpointLatency, EngineInfo, FullAnalysisResult, JitterMethod, LatencyStats,
+    LinkStats, MonitorAlert, OneWayDelay, OtherTraffic, PhaseFailure, PingSample, RouteSnapshot,
+    ServerFailover, SpeedTestResult, TransportInfo, WebSocketLatency,
+};
 
 const DB_NAME: &str = "netrunner_history.db";
+
+/// Directory chosen with `--data-dir` or `data_dir` in config.toml, replacing the
+/// platform data directory for the rest of the process
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set by `--no-history`: every storage is an empty database in memory
+static HISTORY_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// How long `new` waits for another netrunner_cli process to close the database
+const LOCK_WAIT: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
 const RETENTION_DAYS: i64 = 30;
 
 const RESULTS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("test_results");
+/// Running totals kept by schema 2 and earlier, which drifted as results were
+/// deleted. Dropped by the migration, statistics come from the summaries now.
 const STATS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("statistics");
+/// Start times of tests against public servers, kept for the courtesy cap
+const TEST_RUNS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("test_runs");
+/// Medians of multi-run tests (`--runs`), kept apart so they don't count twice in statistics
+const AGGREGATES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("aggregate_results");
+/// Latency probes from ping monitor mode, one per second or so, kept for the retention period
+const PING_SAMPLES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("ping_samples");
+/// Routes traced by diagnostics, compared run to run to spot routing changes
+const ROUTES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("routes");
+/// Data used by speed tests per calendar month, kept beyond the retention period
+/// so a month's total stays complete for the data budget
+const DATA_USAGE_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("data_usage");
+/// Results summarised per UTC day, keyed by the date (`2024-03-05`) and updated on
+/// every save. Kept beyond the retention period like the data usage, so statistics
+/// over long ranges read a few summaries instead of decoding every result.
+const DAILY_SUMMARIES_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("daily_summaries");
+/// Results summarised per week, keyed by the date of its Monday
+const WEEKLY_SUMMARIES_TABLE: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("weekly_summaries");
+/// Diagnostics runs, kept for the retention period. Stored as JSON rather than
+/// postcard: the report grows often and fields added since default when missing.
+const DIAGNOSTICS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("diagnostics");
+/// Full analyses (`--mode full`), kept for the retention period as JSON like diagnostics
+const FULL_ANALYSES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("full_analyses");
+/// Best server of the last full selection, so the next test can skip it
+const BEST_SERVER_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("best_server");
+const BEST_SERVER_KEY: &str = "last";
+/// Named baselines from `netrunner baseline`, as JSON and never pruned
+const BASELINES_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("baselines");
+/// Name of the baseline normal tests are compared with
+const ACTIVE_BASELINE_TABLE: TableDefinition<&str, &str> = TableDefinition::new("active_baseline");
+const ACTIVE_BASELINE_KEY: &str = "active";
+/// Monitor alerts waiting for the next email digest, as JSON
+const PENDING_ALERTS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("pending_alerts");
+/// Database wide settings, currently only the schema version
+const METADATA_TABLE: TableDefinition<&str, u32> = TableDefinition::new("metadata");
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Version of the backup archive layout, bumped when fields change meaning
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+/// Version of the database layout. 0 is a database from before versioning, whose
+/// results and aggregates are bare postcard with no layout number in front. 1 has
+/// no daily and weekly summaries, 2 still keeps running totals in `statistics`.
+const SCHEMA_VERSION: u32 = 3;
+
+/// Layout of `SpeedTestResult` new records are written with, stored in front of every
+/// result and aggregate. Adding a field to `SpeedTestResult` means keeping the current
+/// layout as a struct below, decoding it under its old number and bumping this.
+const RESULT_LAYOUT: u32 = 15;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestStatistics {
@@ -44,12 +122,12 @@ impl Default for TestStatistics {
             test_count: 0,
             avg_download_mbps: 0.0,
             max_download_mbps: 0.0,
-            min_download_mbps: f64::MAX,
+            min_download_mbps: 0.0,
             avg_upload_mbps: 0.0,
             max_upload_mbps: 0.0,
-            min_upload_mbps: f64::MAX,
+            min_upload_mbps: 0.0,
             avg_ping_ms: 0.0,
-            min_ping_ms: f64::MAX,
+            min_ping_ms: 0.0,
             max_ping_ms: 0.0,
             total_data_downloaded_gb: 0.0,
             total_data_uploaded_gb: 0.0,
@@ -67,58 +145,368 @@ pub struct HistoryStorage {
 impl HistoryStorage {
     /// Create a new history storage instance
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let db_path = Self::get_db_path()?;
-        let db = redb::Database::create(db_path)?;
+        Self::open(LOCK_WAIT)
+    }
 
-        Ok(Self { db })
+    /// Open the history database, waiting up to `wait` while another netrunner_cli
+    /// process has it open. redb locks the whole file, so no reads are possible
+    /// meanwhile either.
+    pub fn open(wait: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        let db = if HISTORY_DISABLED.load(Ordering::Relaxed) {
+            redb::Database::builder().create_with_backend(redb::backends::InMemoryBackend::new())?
+        } else {
+            create_when_unlocked(&Self::get_db_path()?, wait)?
+        };
+        let storage = Self { db };
+        storage.migrate()?;
+
+        Ok(storage)
+    }
+
+    /// Keep nothing for the rest of the process: storages opened from now on are
+    /// empty and forget what is saved to them
+    pub fn disable() {
+        HISTORY_DISABLED.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `--no-history` made storages forget what is saved to them
+    pub fn is_disabled() -> bool {
+        HISTORY_DISABLED.load(Ordering::Relaxed)
     }
 
     /// Create a new history storage instance with custom path (for testing)
     #[cfg(test)]
     fn new_with_path(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let db = redb::Database::create(path)?;
-        Ok(Self { db })
+        let storage = Self {
+            db: redb::Database::create(path)?,
+        };
+        storage.migrate()?;
+        Ok(storage)
     }
 
-    /// Get the database path
-    fn get_db_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
-        let config_dir = dirs::config_dir()
-            .ok_or("Failed to find config directory")?
-            .join("netrunner");
+    /// Bring a database written by an older version up to `SCHEMA_VERSION`. A
+    /// database from a newer version is left alone, records in layouts this version
+    /// doesn't know are skipped when reading.
+    fn migrate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let txn = self.db.begin_write()?;
+        let version = txn
+            .open_table(METADATA_TABLE)?
+            .get(SCHEMA_VERSION_KEY)?
+            .map(|version| version.value())
+            .unwrap_or(0);
+        if version >= SCHEMA_VERSION {
+            txn.abort()?;
+            return Ok(());
+        }
+
+        if version < 1 {
+            Self::add_layout_versions(&txn)?;
+        }
+        if version < 2 {
+            Self::add_summaries(&txn)?;
+        }
+        if version < 3 {
+            txn.delete_table(STATS_TABLE)?;
+        }
 
-        std::fs::create_dir_all(&config_dir)?;
-        Ok(config_dir.join(DB_NAME))
+        txn.open_table(METADATA_TABLE)?
+            .insert(SCHEMA_VERSION_KEY, SCHEMA_VERSION)?;
+        txn.commit()?;
+
+        Ok(())
     }
 
-    /// Save a test result
-    pub fn save_result(&self, result: &SpeedTestResult) -> Result<(), Box<dyn std::error::Error>> {
-        // Use timestamp as key (nanoseconds since epoch for uniqueness)
-        let key = result
-            .timestamp
-            .timestamp_nanos_opt()
-            .unwrap_or_default()
-            .to_be_bytes();
+    /// Schema 0 to 1: put the layout number in front of every result and aggregate.
+    /// Bare records are recognised by trying the layouts newest first, records no
+    /// layout decodes could never be read and are dropped.
+    fn add_layout_versions(txn: &redb::WriteTransaction) -> Result<(), Box<dyn std::error::Error>> {
+        let mut results = txn.open_table(RESULTS_TABLE)?;
+        let records = results
+            .iter()?
+            .map(|item| item.map(|(key, value)| (key.value().to_vec(), value.value().to_vec())))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, value) in records {
+            match decode_unversioned_result(&value) {
+                Some(result) => {
+                    results.insert(key.as_slice(), encode_result(&result)?.as_slice())?;
+                }
+                None => {
+                    if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                        eprintln!("[TRACE] Dropping unreadable history record during migration");
+                    }
+                    results.remove(key.as_slice())?;
+                }
+            }
+        }
+
+        let mut aggregates = txn.open_table(AGGREGATES_TABLE)?;
+        let records = aggregates
+            .iter()?
+            .map(|item| item.map(|(key, value)| (key.value().to_vec(), value.value().to_vec())))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, value) in records {
+            match decode_unversioned_aggregate(&value) {
+                Some(aggregate) => {
+                    aggregates.insert(key.as_slice(), encode_aggregate(&aggregate)?.as_slice())?;
+                }
+                None => {
+                    aggregates.remove(key.as_slice())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Schema 1 to 2: summarise the stored results per day and week
+    fn add_summaries(txn: &redb::WriteTransaction) -> Result<(), Box<dyn std::error::Error>> {
+        let results = txn.open_table(RESULTS_TABLE)?;
+        let mut days: BTreeMap<NaiveDate, PeriodSummary> = BTreeMap::new();
+        for item in results.iter()? {
+            let (_, value) = item?;
+            if let Ok(result) = decode_result(value.value()) {
+                days.entry(result.timestamp.date_naive())
+                    .or_default()
+                    .add(&result);
+            }
+        }
+
+        let mut daily = txn.open_table(DAILY_SUMMARIES_TABLE)?;
+        let mut weekly = txn.open_table(WEEKLY_SUMMARIES_TABLE)?;
+        let mut weeks: BTreeMap<NaiveDate, PeriodSummary> = BTreeMap::new();
+        for (day, summary) in &days {
+            daily.insert(
+                period_key(*day).as_str(),
+                postcard::to_stdvec(summary)?.as_slice(),
+            )?;
+            weeks.entry(week_of(*day)).or_default().merge(summary);
+        }
+        for (week, summary) in &weeks {
+            weekly.insert(
+                period_key(*week).as_str(),
+                postcard::to_stdvec(summary)?.as_slice(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Schema version the database is at
+    pub fn schema_version(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(METADATA_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(table
+            .get(SCHEMA_VERSION_KEY)?
+            .map(|version| version.value())
+            .unwrap_or(0))
+    }
+
+    /// Keep the database in `dir` from now on. Only the first call counts, so the
+    /// command line is applied before the config file.
+    pub fn set_data_dir(dir: PathBuf) {
+        let _ = DATA_DIR.set(dir);
+    }
 
+    /// Directory the database is kept in: the one set with `set_data_dir`, otherwise
+    /// `netrunner` in the platform data directory (`$XDG_DATA_HOME` on Linux)
+    pub fn data_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(dir) = DATA_DIR.get() {
+            return Ok(dir.clone());
+        }
+
+        Ok(dirs::data_dir()
+            .ok_or("Failed to find data directory")?
+            .join("netrunner"))
+    }
+
+    /// Get the database path
+    fn get_db_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+        // Earlier versions kept the database next to the config file
+        let legacy = dirs::config_dir().map(|dir| dir.join("netrunner").join(DB_NAME));
+        prepare_db_path(&Self::data_dir()?, legacy.as_deref())
+    }
+
+    /// Save a test result. Returns false when the same result is already stored,
+    /// different results from the same nanosecond are both kept.
+    pub fn save_result(
+        &self,
+        result: &SpeedTestResult,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
         // Serialize result
-        let value = postcard::to_stdvec(result)?;
+        let value = encode_result(result)?;
 
         // Store in database
         let txn = self.db.begin_write()?;
-        {
+        let inserted = {
             let mut table = txn.open_table(RESULTS_TABLE)?;
-            table.insert(key.as_slice(), value.as_slice())?;
+            insert_result(&mut table, result.timestamp, &value)?
+        };
+        if !inserted {
+            txn.abort()?;
+            return Ok(false);
         }
         txn.commit()?;
 
-        // Update statistics
-        self.update_statistics(result)?;
+        self.record_summaries(result)?;
+        self.record_data_usage(result)?;
 
         // Clean up old records (older than 30 days)
         self.cleanup_old_records()?;
 
+        Ok(true)
+    }
+
+    /// Add a result to the summaries of its day and week
+    fn record_summaries(&self, result: &SpeedTestResult) -> Result<(), Box<dyn std::error::Error>> {
+        let day = result.timestamp.date_naive();
+        let txn = self.db.begin_write()?;
+        for (definition, start) in [
+            (DAILY_SUMMARIES_TABLE, day),
+            (WEEKLY_SUMMARIES_TABLE, week_of(day)),
+        ] {
+            let mut table = txn.open_table(definition)?;
+            let key = period_key(start);
+            let mut summary = table
+                .get(key.as_str())?
+                .and_then(|value| postcard::from_bytes::<PeriodSummary>(value.value()).ok())
+                .unwrap_or_default();
+            summary.add(result);
+            table.insert(key.as_str(), postcard::to_stdvec(&summary)?.as_slice())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Summarise the given days again from the results stored for them, and their
+    /// weeks from the days, after results were removed or restored
+    fn rebuild_summaries(
+        &self,
+        days: impl IntoIterator<Item = NaiveDate>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let days: BTreeSet<NaiveDate> = days.into_iter().collect();
+        let weeks: BTreeSet<NaiveDate> = days.iter().map(|&day| week_of(day)).collect();
+
+        let txn = self.db.begin_write()?;
+        {
+            let results = txn.open_table(RESULTS_TABLE)?;
+            let mut daily = txn.open_table(DAILY_SUMMARIES_TABLE)?;
+            for &day in &days {
+                let (start, end) = (start_of_day(day), start_of_day(day + chrono::Days::new(1)));
+                let mut summary = PeriodSummary::default();
+                for item in
+                    results.range(timestamp_key(start).as_slice()..timestamp_key(end).as_slice())?
+                {
+                    let (_, value) = item?;
+                    if let Ok(result) = decode_result(value.value()) {
+                        summary.add(&result);
+                    }
+                }
+                let key = period_key(day);
+                if summary == PeriodSummary::default() {
+                    daily.remove(key.as_str())?;
+                } else {
+                    daily.insert(key.as_str(), postcard::to_stdvec(&summary)?.as_slice())?;
+                }
+            }
+
+            let mut weekly = txn.open_table(WEEKLY_SUMMARIES_TABLE)?;
+            for &week in &weeks {
+                let mut summary = PeriodSummary::default();
+                let (first, last) = (period_key(week), period_key(week + chrono::Days::new(6)));
+                for item in daily.range(first.as_str()..=last.as_str())? {
+                    let (_, value) = item?;
+                    if let Ok(day) = postcard::from_bytes::<PeriodSummary>(value.value()) {
+                        summary.merge(&day);
+                    }
+                }
+                let key = period_key(week);
+                if summary == PeriodSummary::default() {
+                    weekly.remove(key.as_str())?;
+                } else {
+                    weekly.insert(key.as_str(), postcard::to_stdvec(&summary)?.as_slice())?;
+                }
+            }
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Add a result's data used to the total of its month
+    fn record_data_usage(
+        &self,
+        result: &SpeedTestResult,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(data_used) = result.data_used else {
+            return Ok(());
+        };
+        let month = MonthlyDataUsage::month_of(result.timestamp);
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(DATA_USAGE_TABLE)?;
+            let mut usage = table
+                .get(month.as_bytes())?
+                .and_then(|value| postcard::from_bytes::<MonthlyDataUsage>(value.value()).ok())
+                .unwrap_or_else(|| MonthlyDataUsage {
+                    month: month.clone(),
+                    ..Default::default()
+                });
+            usage.test_count += 1;
+            usage.data_used = usage.data_used + data_used;
+            let value = postcard::to_stdvec(&usage)?;
+            table.insert(month.as_bytes(), value.as_slice())?;
+        }
+        txn.commit()?;
+
         Ok(())
     }
 
+    /// Data used by speed tests in each month, newest first
+    pub fn get_monthly_data_usage(
+        &self,
+    ) -> Result<Vec<MonthlyDataUsage>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(DATA_USAGE_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut months = Vec::new();
+        // `YYYY-MM` keys sort chronologically
+        for item in table.iter()?.rev() {
+            let (_, value) = item?;
+            if let Ok(usage) = postcard::from_bytes(value.value()) {
+                months.push(usage);
+            }
+        }
+
+        Ok(months)
+    }
+
+    /// Data used by speed tests in the month containing `at`
+    pub fn get_data_usage_for_month(
+        &self,
+        at: DateTime<Utc>,
+    ) -> Result<MonthlyDataUsage, Box<dyn std::error::Error>> {
+        let month = MonthlyDataUsage::month_of(at);
+        Ok(self
+            .get_monthly_data_usage()?
+            .into_iter()
+            .find(|usage| usage.month == month)
+            .unwrap_or(MonthlyDataUsage {
+                month,
+                ..Default::default()
+            }))
+    }
+
     /// Get recent test results
     pub fn get_recent_results(
         &self,
@@ -136,7 +524,7 @@ impl HistoryStorage {
                 break;
             }
             let (_, value) = item?;
-            if let Ok(result) = postcard::from_bytes::<SpeedTestResult>(value.value()) {
+            if let Ok(result) = decode_result(value.value()) {
                 results.push(result);
             }
         }
@@ -144,51 +532,92 @@ impl HistoryStorage {
         Ok(results)
     }
 
-    /// Get all test results
+    /// Get the most recent test result, if any
+    pub fn get_latest_result(&self) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
+        Ok(self.get_recent_results(1)?.into_iter().next())
+    }
+
+    /// Get all test results, newest first. Loads the whole history, prefer
+    /// `iter_results` where the results are only looked at once.
     pub fn get_all_results(&self) -> Result<Vec<SpeedTestResult>, Box<dyn std::error::Error>> {
+        Ok(self.iter_results()?.rev().collect::<Result<_, _>>()?)
+    }
+
+    /// Every stored result, oldest first or newest first with `rev()`, decoded
+    /// only as the iterator reaches it
+    pub fn iter_results(&self) -> Result<ResultIter, Box<dyn std::error::Error>> {
+        self.iter_results_in(..)
+    }
+
+    /// Stored results with timestamps in `range`, decoded lazily like `iter_results`
+    pub fn iter_results_in(
+        &self,
+        range: impl RangeBounds<DateTime<Utc>>,
+    ) -> Result<ResultIter, Box<dyn std::error::Error>> {
+        // Keys continue past the timestamp, so an included end takes in everything
+        // before the next nanosecond
+        let nanosecond = chrono::Duration::nanoseconds(1);
+        let start = match range.start_bound() {
+            Bound::Included(at) => Bound::Included(timestamp_key(*at)),
+            Bound::Excluded(at) => Bound::Included(timestamp_key(*at + nanosecond)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(at) => Bound::Excluded(timestamp_key(*at + nanosecond)),
+            Bound::Excluded(at) => Bound::Excluded(timestamp_key(*at)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
         let txn = self.db.begin_read()?;
         let table = txn.open_table(RESULTS_TABLE)?;
+        // The range keeps the transaction alive until the iterator is dropped
+        let range = table.range::<&[u8]>((
+            start.as_ref().map(|key| key.as_slice()),
+            end.as_ref().map(|key| key.as_slice()),
+        ))?;
+        Ok(ResultIter { range })
+    }
 
+    /// Stored results `keep` accepts, newest first
+    fn filter_results(
+        &self,
+        keep: impl Fn(&SpeedTestResult) -> bool,
+    ) -> Result<Vec<SpeedTestResult>, Box<dyn std::error::Error>> {
         let mut results = Vec::new();
-
-        for item in table.iter()?.rev() {
-            let (_, value) = item?;
-            if let Ok(result) = postcard::from_bytes::<SpeedTestResult>(value.value()) {
+        for result in self.iter_results()?.rev() {
+            let result = result?;
+            if keep(&result) {
                 results.push(result);
             }
         }
-
         Ok(results)
     }
 
+    /// The stored result no other beats, where `beats(a, b)` says whether `a`
+    /// replaces `b`. Results are visited oldest first.
+    fn best_result(
+        &self,
+        beats: impl Fn(&SpeedTestResult, &SpeedTestResult) -> bool,
+    ) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
+        let mut best: Option<SpeedTestResult> = None;
+        for result in self.iter_results()? {
+            let result = result?;
+            if best.as_ref().is_none_or(|best| beats(&result, best)) {
+                best = Some(result);
+            }
+        }
+        Ok(best)
+    }
+
     /// Get results within a date range
     pub fn get_results_by_date_range(
         &self,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let txn = self.db.begin_read()?;
-        let table = txn.open_table(RESULTS_TABLE)?;
-
-        let start_key = start
-            .timestamp_nanos_opt()
-            .unwrap_or_default()
-            .to_be_bytes();
-        let end_key = end.timestamp_nanos_opt().unwrap_or_default().to_be_bytes();
-
-        let mut results = Vec::new();
-
-        let start_slice: &[u8] = start_key.as_slice();
-        let end_slice: &[u8] = end_key.as_slice();
-
-        for item in table.range(start_slice..=end_slice)? {
-            let (_, value) = item?;
-            if let Ok(result) = postcard::from_bytes::<SpeedTestResult>(value.value()) {
-                results.push(result);
-            }
-        }
-
-        Ok(results)
+        Ok(self
+            .iter_results_in(start..=end)?
+            .collect::<Result<_, _>>()?)
     }
 
     /// Get results filtered by quality
@@ -196,12 +625,7 @@ impl HistoryStorage {
         &self,
         quality: crate::modules::types::ConnectionQuality,
     ) -> Result<Vec<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let all_results = self.get_all_results()?;
-
-        Ok(all_results
-            .into_iter()
-            .filter(|r| r.quality == quality)
-            .collect())
+        self.filter_results(|r| r.quality == quality)
     }
 
     /// Get results by server location
@@ -209,147 +633,71 @@ impl HistoryStorage {
         &self,
         server_location: &str,
     ) -> Result<Vec<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let all_results = self.get_all_results()?;
-
-        Ok(all_results
-            .into_iter()
-            .filter(|r| r.server_location.contains(server_location))
-            .collect())
+        self.filter_results(|r| r.server_location.contains(server_location))
     }
 
-    /// Update statistics
-    fn update_statistics(
-        &self,
-        result: &SpeedTestResult,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut stats = self.get_statistics_internal()?;
-
-        // Update counts
-        stats.test_count += 1;
-
-        // Update download stats
-        stats.avg_download_mbps = (stats.avg_download_mbps * (stats.test_count - 1) as f64
-            + result.download_mbps)
-            / stats.test_count as f64;
-        stats.max_download_mbps = stats.max_download_mbps.max(result.download_mbps);
-        stats.min_download_mbps = stats.min_download_mbps.min(result.download_mbps);
-
-        // Update upload stats
-        stats.avg_upload_mbps = (stats.avg_upload_mbps * (stats.test_count - 1) as f64
-            + result.upload_mbps)
-            / stats.test_count as f64;
-        stats.max_upload_mbps = stats.max_upload_mbps.max(result.upload_mbps);
-        stats.min_upload_mbps = stats.min_upload_mbps.min(result.upload_mbps);
-
-        // Update ping stats
-        stats.avg_ping_ms = (stats.avg_ping_ms * (stats.test_count - 1) as f64 + result.ping_ms)
-            / stats.test_count as f64;
-        stats.min_ping_ms = stats.min_ping_ms.min(result.ping_ms);
-        stats.max_ping_ms = stats.max_ping_ms.max(result.ping_ms);
-
-        // Estimate data transferred (rough calculation based on test duration and speed)
-        let test_duration_hours = result.test_duration_seconds / 3600.0;
-        stats.total_data_downloaded_gb += result.download_mbps * test_duration_hours / 8.0 / 1000.0;
-        stats.total_data_uploaded_gb += result.upload_mbps * test_duration_hours / 8.0 / 1000.0;
-
-        // Update timestamps
-        stats.last_test = result.timestamp;
-        if stats.test_count == 1 {
-            stats.first_test = result.timestamp;
-        }
-
-        // Save updated statistics
-        let value = postcard::to_stdvec(&stats)?;
-        let txn = self.db.begin_write()?;
-        {
-            let mut table = txn.open_table(STATS_TABLE)?;
-            table.insert(b"global".as_slice(), value.as_slice())?;
-        }
-        txn.commit()?;
-
-        Ok(())
-    }
-
-    /// Get statistics
+    /// Statistics over every stored result, put together from the summaries between
+    /// the first and the last of them. Deleted results leave the summaries as well
+    /// and pruned ones fall outside that span, so nothing is kept up to date on the
+    /// side. Without results every field is zero.
     pub fn get_statistics(&self) -> Result<TestStatistics, Box<dyn std::error::Error>> {
-        self.get_statistics_internal()
-    }
-
-    fn get_statistics_internal(&self) -> Result<TestStatistics, Box<dyn std::error::Error>> {
-        let txn = self.db.begin_read()?;
-        let table = match txn.open_table(STATS_TABLE) {
-            Ok(t) => t,
-            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(TestStatistics::default()),
-            Err(e) => return Err(e.into()),
+        let mut results = self.iter_results()?;
+        let Some(first) = results.next().transpose()? else {
+            return Ok(TestStatistics::default());
+        };
+        let last = match results.next_back().transpose()? {
+            Some(last) => last.timestamp,
+            None => first.timestamp,
         };
+        drop(results);
 
-        match table.get(b"global".as_slice())? {
-            // Fall back to default if the stored bytes cannot be decoded
-            // (e.g. stale bytes written by an older version).
-            Some(value) => Ok(postcard::from_bytes(value.value()).unwrap_or_default()),
-            None => Ok(TestStatistics::default()),
-        }
+        self.get_statistics_by_date_range(first.timestamp, last)
     }
 
-    /// Get statistics for a specific date range
+    /// Get statistics for a specific date range, both ends included. Whole weeks
+    /// and days inside the range come from their summaries, only the partial days
+    /// at either end are read result by result.
     pub fn get_statistics_by_date_range(
         &self,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<TestStatistics, Box<dyn std::error::Error>> {
-        let results = self.get_results_by_date_range(start, end)?;
-
-        if results.is_empty() {
-            return Ok(TestStatistics::default());
-        }
+        let txn = self.db.begin_read()?;
+        let results = txn.open_table(RESULTS_TABLE)?;
+        let daily = txn.open_table(DAILY_SUMMARIES_TABLE)?;
+        let weekly = txn.open_table(WEEKLY_SUMMARIES_TABLE)?;
 
-        let mut stats = TestStatistics {
-            test_count: results.len(),
-            max_download_mbps: 0.0,
-            min_download_mbps: f64::MAX,
-            max_upload_mbps: 0.0,
-            min_upload_mbps: f64::MAX,
-            ..Default::default()
+        let end = end + chrono::Duration::nanoseconds(1);
+        let covered = |from: NaiveDate, until: NaiveDate| {
+            start_of_day(from) >= start && start_of_day(until) <= end
         };
+        let mut summary = PeriodSummary::default();
+        let mut day = start.date_naive();
+        while start_of_day(day) < end {
+            let next_week = day + chrono::Days::new(7);
+            if day.weekday() == Weekday::Mon && covered(day, next_week) {
+                summary.merge(&read_summary(&weekly, day)?.unwrap_or_default());
+                day = next_week;
+                continue;
+            }
 
-        // Calculate statistics
-        let mut total_download = 0.0;
-        let mut total_upload = 0.0;
-        let mut total_ping = 0.0;
-        stats.max_ping_ms = 0.0;
-        stats.min_ping_ms = f64::MAX;
-
-        for result in &results {
-            total_download += result.download_mbps;
-            total_upload += result.upload_mbps;
-            total_ping += result.ping_ms;
-
-            stats.max_download_mbps = stats.max_download_mbps.max(result.download_mbps);
-            stats.min_download_mbps = stats.min_download_mbps.min(result.download_mbps);
-            stats.max_upload_mbps = stats.max_upload_mbps.max(result.upload_mbps);
-            stats.min_upload_mbps = stats.min_upload_mbps.min(result.upload_mbps);
-            stats.max_ping_ms = stats.max_ping_ms.max(result.ping_ms);
-            stats.min_ping_ms = stats.min_ping_ms.min(result.ping_ms);
-
-            // Estimate data transferred
-            let test_duration_hours = result.test_duration_seconds / 3600.0;
-            stats.total_data_downloaded_gb +=
-                result.download_mbps * test_duration_hours / 8.0 / 1000.0;
-            stats.total_data_uploaded_gb += result.upload_mbps * test_duration_hours / 8.0 / 1000.0;
-        }
-
-        stats.avg_download_mbps = total_download / results.len() as f64;
-        stats.avg_upload_mbps = total_upload / results.len() as f64;
-        stats.avg_ping_ms = total_ping / results.len() as f64;
-
-        if let Some(first) = results.last() {
-            stats.first_test = first.timestamp;
-        }
-        if let Some(last) = results.first() {
-            stats.last_test = last.timestamp;
+            let next_day = day + chrono::Days::new(1);
+            if covered(day, next_day) {
+                summary.merge(&read_summary(&daily, day)?.unwrap_or_default());
+            } else {
+                let from = timestamp_key(start_of_day(day).max(start));
+                let until = timestamp_key(start_of_day(next_day).min(end));
+                for item in results.range(from.as_slice()..until.as_slice())? {
+                    let (_, value) = item?;
+                    if let Ok(result) = decode_result(value.value()) {
+                        summary.add(&result);
+                    }
+                }
+            }
+            day = next_day;
         }
 
-        Ok(stats)
+        Ok(summary.statistics())
     }
 
     /// Get the number of stored results
@@ -363,362 +711,3552 @@ impl HistoryStorage {
         Ok(table.len()? as usize)
     }
 
-    /// Delete a specific result
+    /// Delete the results taken at `timestamp`
     pub fn delete_result(
         &self,
         timestamp: DateTime<Utc>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let key = timestamp
-            .timestamp_nanos_opt()
-            .unwrap_or_default()
-            .to_be_bytes();
+        let (start, end) = result_keys_at(timestamp);
 
         let txn = self.db.begin_write()?;
         {
             let mut table = txn.open_table(RESULTS_TABLE)?;
-            table.remove(key.as_slice())?;
+            table.retain_in(start.as_slice()..end.as_slice(), |_, _| false)?;
         }
         txn.commit()?;
 
-        // Recalculate statistics
-        self.recalculate_statistics()?;
+        self.rebuild_summaries([timestamp.date_naive()])?;
 
         Ok(())
     }
 
-    /// Clear all history
-    pub fn clear_history(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Replace the tags of a stored result, found by its time and contents.
+    /// Returns false when the result is no longer stored.
+    pub fn set_result_tags(
+        &self,
+        result: &SpeedTestResult,
+        tags: Vec<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let value = encode_result(result)?;
+
         let txn = self.db.begin_write()?;
-        txn.delete_table(RESULTS_TABLE)?;
-        txn.delete_table(STATS_TABLE)?;
+        {
+            let mut table = txn.open_table(RESULTS_TABLE)?;
+            let Some(key) = find_result(&table, result.timestamp, &value)? else {
+                drop(table);
+                txn.abort()?;
+                return Ok(false);
+            };
+
+            let tagged = encode_result(&SpeedTestResult {
+                tags,
+                ..result.clone()
+            })?;
+            table.insert(key.as_slice(), tagged.as_slice())?;
+        }
         txn.commit()?;
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Recalculate all statistics from scratch
-    fn recalculate_statistics(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Clear stats table
+    /// Clear all history
+    pub fn clear_history(&self) -> Result<(), Box<dyn std::error::Error>> {
         let txn = self.db.begin_write()?;
-        txn.delete_table(STATS_TABLE)?;
+        txn.delete_table(RESULTS_TABLE)?;
+        txn.delete_table(DAILY_SUMMARIES_TABLE)?;
+        txn.delete_table(WEEKLY_SUMMARIES_TABLE)?;
         txn.commit()?;
 
-        let results = self.get_all_results()?;
-
-        for result in results {
-            self.update_statistics(&result)?;
-        }
-
         Ok(())
     }
 
     /// Clean up records older than the retention period (30 days)
     fn cleanup_old_records(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Calculate cutoff timestamp (30 days ago)
-        let cutoff = Utc::now() - chrono::Duration::days(RETENTION_DAYS);
-        let cutoff_nanos = cutoff.timestamp_nanos_opt().unwrap_or_default();
+        self.cleanup_old_records_manual()?;
+        Ok(())
+    }
 
-        // Collect keys to delete
-        let mut keys_to_delete = Vec::new();
+    /// Record the start of a test against public servers, dropping entries older than a day
+    pub fn record_test_run(&self, at: DateTime<Utc>) -> Result<(), Box<dyn std::error::Error>> {
+        let key = at.timestamp_nanos_opt().unwrap_or_default().to_be_bytes();
+        let cutoff = (at - chrono::Duration::days(1))
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
 
+        let txn = self.db.begin_write()?;
         {
-            let txn = self.db.begin_read()?;
-            let table = match txn.open_table(RESULTS_TABLE) {
-                Ok(t) => t,
-                Err(redb::TableError::TableDoesNotExist(_)) => return Ok(()),
-                Err(e) => return Err(e.into()),
-            };
-
-            for item in table.iter()? {
-                let (_, value) = item?;
-
-                // Deserialize to check timestamp
-                if let Ok(result) = postcard::from_bytes::<SpeedTestResult>(value.value()) {
-                    let result_nanos = result.timestamp.timestamp_nanos_opt().unwrap_or_default();
-
-                    if result_nanos < cutoff_nanos {
-                        keys_to_delete.push(
-                            result
-                                .timestamp
-                                .timestamp_nanos_opt()
-                                .unwrap_or_default()
-                                .to_be_bytes(),
-                        );
-                    }
-                }
-            }
+            let mut table = txn.open_table(TEST_RUNS_TABLE)?;
+            table.insert(key.as_slice(), [].as_slice())?;
+            table.retain_in(..cutoff.as_slice(), |_, _| false)?;
         }
+        txn.commit()?;
 
-        let deleted_count = keys_to_delete.len();
+        Ok(())
+    }
 
-        // Delete old records
-        if deleted_count > 0 {
-            let txn = self.db.begin_write()?;
-            {
-                let mut table = txn.open_table(RESULTS_TABLE)?;
-                for key in &keys_to_delete {
-                    table.remove(key.as_slice())?;
-                }
-            }
-            txn.commit()?;
+    /// Start times of recorded test runs since `since`, oldest first
+    pub fn get_test_runs_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(TEST_RUNS_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let since_key = since
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let mut runs = Vec::new();
+        for item in table.range(since_key.as_slice()..)? {
+            let (key, _) = item?;
+            if let Ok(bytes) = <[u8; 8]>::try_from(key.value()) {
+                runs.push(DateTime::from_timestamp_nanos(i64::from_be_bytes(bytes)));
+            }
+        }
+
+        Ok(runs)
+    }
+
+    /// Save the median of a multi-run test
+    pub fn save_aggregate(
+        &self,
+        aggregate: &AggregateResult,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = aggregate
+            .median
+            .timestamp
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let value = encode_aggregate(aggregate)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(AGGREGATES_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Get recent multi-run medians, newest first
+    pub fn get_recent_aggregates(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<AggregateResult>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(AGGREGATES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut aggregates = Vec::new();
+        for item in table.iter()?.rev() {
+            if aggregates.len() >= limit {
+                break;
+            }
+            let (_, value) = item?;
+            if let Ok(aggregate) = decode_aggregate(value.value()) {
+                aggregates.push(aggregate);
+            }
+        }
+
+        Ok(aggregates)
+    }
+
+    /// Save a ping monitor probe, dropping probes older than the retention period
+    pub fn save_ping_sample(&self, sample: &PingSample) -> Result<(), Box<dyn std::error::Error>> {
+        let key = sample
+            .timestamp
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let cutoff = (sample.timestamp - chrono::Duration::days(RETENTION_DAYS))
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let value = postcard::to_stdvec(sample)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(PING_SAMPLES_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+            table.retain_in(..cutoff.as_slice(), |_, _| false)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Ping monitor probes since `since`, oldest first
+    pub fn get_ping_samples_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PingSample>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(PING_SAMPLES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let since_key = since
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let mut samples = Vec::new();
+        for item in table.range(since_key.as_slice()..)? {
+            let (_, value) = item?;
+            if let Ok(sample) = postcard::from_bytes(value.value()) {
+                samples.push(sample);
+            }
+        }
+
+        Ok(samples)
+    }
+
+    /// Save a traced route, dropping routes older than the retention period
+    pub fn save_route(&self, route: &RouteSnapshot) -> Result<(), Box<dyn std::error::Error>> {
+        let key = route
+            .timestamp
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let cutoff = (route.timestamp - chrono::Duration::days(RETENTION_DAYS))
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_be_bytes();
+        let value = postcard::to_stdvec(route)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(ROUTES_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+            table.retain_in(..cutoff.as_slice(), |_, _| false)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Most recent route traced to `target`
+    pub fn get_latest_route(
+        &self,
+        target: &str,
+    ) -> Result<Option<RouteSnapshot>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(ROUTES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        for item in table.iter()?.rev() {
+            let (_, value) = item?;
+            if let Ok(route) = postcard::from_bytes::<RouteSnapshot>(value.value()) {
+                if route.target == target {
+                    return Ok(Some(route));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Save a diagnostics run, dropping runs older than the retention period
+    pub fn save_diagnostics(
+        &self,
+        record: &DiagnosticsRecord,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = timestamp_key(record.timestamp);
+        let cutoff = timestamp_key(record.timestamp - chrono::Duration::days(RETENTION_DAYS));
+        let value = serde_json::to_vec(record)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(DIAGNOSTICS_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+            table.retain_in(..cutoff.as_slice(), |_, _| false)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Recent diagnostics runs, newest first
+    pub fn get_recent_diagnostics(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<DiagnosticsRecord>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(DIAGNOSTICS_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut records = Vec::new();
+        for item in table.iter()?.rev() {
+            if records.len() >= limit {
+                break;
+            }
+            let (_, value) = item?;
+            if let Ok(record) = serde_json::from_slice(value.value()) {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Save a full analysis, dropping analyses older than the retention period. Its
+    /// speed test is saved with `save_result` like any other.
+    pub fn save_full_analysis(
+        &self,
+        analysis: &FullAnalysisResult,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = timestamp_key(analysis.timestamp);
+        let cutoff = timestamp_key(analysis.timestamp - chrono::Duration::days(RETENTION_DAYS));
+        let value = serde_json::to_vec(analysis)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(FULL_ANALYSES_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+            table.retain_in(..cutoff.as_slice(), |_, _| false)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Recent full analyses, newest first
+    pub fn get_recent_full_analyses(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<FullAnalysisResult>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(FULL_ANALYSES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut analyses = Vec::new();
+        for item in table.iter()?.rev() {
+            if analyses.len() >= limit {
+                break;
+            }
+            let (_, value) = item?;
+            if let Ok(analysis) = serde_json::from_slice(value.value()) {
+                analyses.push(analysis);
+            }
+        }
+
+        Ok(analyses)
+    }
+
+    /// Remember the server the last full selection picked
+    pub fn save_best_server(
+        &self,
+        cached: &CachedServer,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let value = postcard::to_stdvec(cached)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(BEST_SERVER_TABLE)?;
+            table.insert(BEST_SERVER_KEY, value.as_slice())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Server picked by the last full selection. An entry written by an older
+    /// version that no longer decodes counts as none.
+    pub fn get_best_server(&self) -> Result<Option<CachedServer>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(BEST_SERVER_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(table
+            .get(BEST_SERVER_KEY)?
+            .and_then(|value| postcard::from_bytes(value.value()).ok()))
+    }
+
+    /// Save a baseline under its name, replacing one of the same name, and make it
+    /// the one normal tests are compared with. Its two runs count towards the data
+    /// used that month, but not as results.
+    pub fn save_baseline(&self, baseline: &Baseline) -> Result<(), Box<dyn std::error::Error>> {
+        let value = serde_json::to_vec(baseline)?;
+        self.record_data_usage(&baseline.multi_stream)?;
+        self.record_data_usage(&baseline.single_stream)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(BASELINES_TABLE)?;
+            table.insert(baseline.name.as_str(), value.as_slice())?;
+            let mut table = txn.open_table(ACTIVE_BASELINE_TABLE)?;
+            table.insert(ACTIVE_BASELINE_KEY, baseline.name.as_str())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Every saved baseline, by name
+    pub fn get_baselines(&self) -> Result<Vec<Baseline>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(BASELINES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut baselines = Vec::new();
+        for item in table.iter()? {
+            let (_, value) = item?;
+            if let Ok(baseline) = serde_json::from_slice(value.value()) {
+                baselines.push(baseline);
+            }
+        }
+
+        Ok(baselines)
+    }
+
+    /// Compare normal tests with the baseline called `name`
+    pub fn set_active_baseline(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.get_baselines()?.iter().any(|b| b.name == name) {
+            return Err(format!(
+                "No baseline named '{}', run `netrunner_cli baseline --name {}` first",
+                name, name
+            )
+            .into());
+        }
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(ACTIVE_BASELINE_TABLE)?;
+            table.insert(ACTIVE_BASELINE_KEY, name)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Baseline normal tests are compared with, if any
+    pub fn get_active_baseline(&self) -> Result<Option<Baseline>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let name = match txn.open_table(ACTIVE_BASELINE_TABLE) {
+            Ok(table) => table
+                .get(ACTIVE_BASELINE_KEY)?
+                .map(|name| name.value().to_string()),
+            Err(redb::TableError::TableDoesNotExist(_)) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let Some(name) = name else {
+            return Ok(None);
+        };
+
+        let table = txn.open_table(BASELINES_TABLE)?;
+        Ok(table
+            .get(name.as_str())?
+            .and_then(|value| serde_json::from_slice(value.value()).ok()))
+    }
+
+    /// Hold a monitor alert back for the next email digest
+    pub fn queue_alert(&self, alert: &MonitorAlert) -> Result<(), Box<dyn std::error::Error>> {
+        let key = timestamp_key(alert.timestamp);
+        let value = serde_json::to_vec(alert)?;
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(PENDING_ALERTS_TABLE)?;
+            table.insert(key.as_slice(), value.as_slice())?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Alerts waiting for the next digest, oldest first
+    pub fn get_pending_alerts(&self) -> Result<Vec<MonitorAlert>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(PENDING_ALERTS_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut alerts = Vec::new();
+        for item in table.iter()? {
+            let (_, value) = item?;
+            if let Ok(alert) = serde_json::from_slice(value.value()) {
+                alerts.push(alert);
+            }
+        }
+
+        Ok(alerts)
+    }
+
+    /// Forget the alerts up to `until` once their digest is sent, keeping any
+    /// queued since
+    pub fn clear_pending_alerts(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let until = timestamp_key(until);
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(PENDING_ALERTS_TABLE)?;
+            table.retain_in(..=until.as_slice(), |_, _| false)?;
+        }
+        txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Every route traced, oldest first
+    fn get_all_routes(&self) -> Result<Vec<RouteSnapshot>, Box<dyn std::error::Error>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(ROUTES_TABLE) {
+            Ok(t) => t,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut routes = Vec::new();
+        for item in table.iter()? {
+            let (_, value) = item?;
+            if let Ok(route) = postcard::from_bytes(value.value()) {
+                routes.push(route);
+            }
+        }
+
+        Ok(routes)
+    }
+
+    /// Copy everything worth keeping out of the database
+    pub fn to_backup(&self) -> Result<HistoryBackup, Box<dyn std::error::Error>> {
+        Ok(HistoryBackup {
+            format_version: BACKUP_FORMAT_VERSION,
+            created_at: Utc::now(),
+            netrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            results: self.get_all_results()?,
+            aggregates: self.get_recent_aggregates(usize::MAX)?,
+            ping_samples: self.get_ping_samples_since(DateTime::UNIX_EPOCH)?,
+            routes: self.get_all_routes()?,
+            data_usage: self.get_monthly_data_usage()?,
+            diagnostics: self.get_recent_diagnostics(usize::MAX)?,
+            full_analyses: self.get_recent_full_analyses(usize::MAX)?,
+            baselines: self.get_baselines()?,
+        })
+    }
+
+    /// Write a backup archive to `path`. Returns the backup and the SHA-256 digest
+    /// of its `history.json`.
+    pub fn backup(
+        &self,
+        path: &Path,
+    ) -> Result<(HistoryBackup, String), Box<dyn std::error::Error>> {
+        let backup = self.to_backup()?;
+        let digest = backup.write(path)?;
+        Ok((backup, digest))
+    }
+
+    /// Merge a backup archive into the database. Results already present are
+    /// skipped and other records at the same time replaced, so restoring the same
+    /// archive twice changes nothing. Returns the backup and how many of its
+    /// results were already present.
+    pub fn restore(
+        &self,
+        path: &Path,
+    ) -> Result<(HistoryBackup, usize), Box<dyn std::error::Error>> {
+        let backup = HistoryBackup::read(path)?;
+        let duplicates = self.merge_backup(&backup)?;
+        Ok((backup, duplicates))
+    }
+
+    fn merge_backup(&self, backup: &HistoryBackup) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut duplicates = 0;
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(RESULTS_TABLE)?;
+            for result in &backup.results {
+                let value = encode_result(result)?;
+                if !insert_result(&mut table, result.timestamp, &value)? {
+                    duplicates += 1;
+                }
+            }
+
+            let mut table = txn.open_table(AGGREGATES_TABLE)?;
+            for aggregate in &backup.aggregates {
+                let value = encode_aggregate(aggregate)?;
+                let key = timestamp_key(aggregate.median.timestamp);
+                table.insert(key.as_slice(), value.as_slice())?;
+            }
+
+            let mut table = txn.open_table(PING_SAMPLES_TABLE)?;
+            for sample in &backup.ping_samples {
+                let value = postcard::to_stdvec(sample)?;
+                table.insert(timestamp_key(sample.timestamp).as_slice(), value.as_slice())?;
+            }
+
+            let mut table = txn.open_table(ROUTES_TABLE)?;
+            for route in &backup.routes {
+                let value = postcard::to_stdvec(route)?;
+                table.insert(timestamp_key(route.timestamp).as_slice(), value.as_slice())?;
+            }
+
+            let mut table = txn.open_table(DIAGNOSTICS_TABLE)?;
+            for record in &backup.diagnostics {
+                let value = serde_json::to_vec(record)?;
+                table.insert(timestamp_key(record.timestamp).as_slice(), value.as_slice())?;
+            }
+
+            let mut table = txn.open_table(FULL_ANALYSES_TABLE)?;
+            for analysis in &backup.full_analyses {
+                let value = serde_json::to_vec(analysis)?;
+                table.insert(
+                    timestamp_key(analysis.timestamp).as_slice(),
+                    value.as_slice(),
+                )?;
+            }
+
+            // Baselines of the same name are replaced, the active one stays as it is
+            let mut table = txn.open_table(BASELINES_TABLE)?;
+            for baseline in &backup.baselines {
+                let value = serde_json::to_vec(baseline)?;
+                table.insert(baseline.name.as_str(), value.as_slice())?;
+            }
+
+            // A month counted on both machines keeps whichever total saw more tests,
+            // adding them up would count a month restored twice double
+            let mut table = txn.open_table(DATA_USAGE_TABLE)?;
+            for usage in &backup.data_usage {
+                let existing = table
+                    .get(usage.month.as_bytes())?
+                    .and_then(|value| postcard::from_bytes::<MonthlyDataUsage>(value.value()).ok());
+                if existing.is_none_or(|existing| existing.test_count < usage.test_count) {
+                    let value = postcard::to_stdvec(usage)?;
+                    table.insert(usage.month.as_bytes(), value.as_slice())?;
+                }
+            }
+        }
+        txn.commit()?;
+
+        self.rebuild_summaries(
+            backup
+                .results
+                .iter()
+                .map(|result| result.timestamp.date_naive()),
+        )?;
+
+        Ok(duplicates)
+    }
+
+    /// Export history to JSON
+    pub fn export_to_json(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(path)?;
+        self.export(file, ExportFormat::Json, &ExportFilter::default())?;
+        Ok(())
+    }
+
+    /// Write the results `filter` lets through to `out`, oldest first. Records are
+    /// decoded and written one at a time, so years of history never sit in memory
+    /// at once. Returns how many were written.
+    pub fn export(
+        &self,
+        out: impl Write,
+        format: ExportFormat,
+        filter: &ExportFilter,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let range = (
+            filter.since.map_or(Bound::Unbounded, Bound::Included),
+            filter.until.map_or(Bound::Unbounded, Bound::Excluded),
+        );
+        let mut out = std::io::BufWriter::new(out);
+        match format {
+            ExportFormat::Json => out.write_all(b"[")?,
+            ExportFormat::Csv => writeln!(out, "{}", CSV_HEADER)?,
+        }
+
+        let mut count = 0;
+        for result in self.iter_results_in(range)? {
+            let result = result?;
+            if !filter.matches(&result) {
+                continue;
+            }
+            match format {
+                ExportFormat::Json => {
+                    out.write_all(if count == 0 { b"\n  " } else { b",\n  " })?;
+                    serde_json::to_writer(&mut out, &result)?;
+                }
+                ExportFormat::Csv => writeln!(out, "{}", csv_row(&result))?,
+            }
+            count += 1;
+        }
+
+        if format == ExportFormat::Json {
+            out.write_all(if count == 0 { b"]\n" } else { b"\n]\n" })?;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Import history from JSON. Returns how many results were new, ones already
+    /// stored are skipped so importing a file twice adds nothing.
+    pub fn import_from_json(&self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        let results: Vec<SpeedTestResult> = serde_json::from_str(&json)?;
+
+        let mut count = 0;
+        for result in results {
+            if self.save_result(&result)? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Get database statistics
+    pub fn get_db_stats(&self) -> Result<DbStats, Box<dyn std::error::Error>> {
+        let db_path = Self::get_db_path()?;
+        let size_on_disk = std::fs::metadata(&db_path).map(|m| m.len()).unwrap_or(0);
+        let results_count = self.count()?;
+
+        Ok(DbStats {
+            size_on_disk,
+            results_count,
+            schema_version: self.schema_version()?,
+            db_path: db_path.to_string_lossy().to_string(),
+        })
+    }
+
+    /// Optimize database
+    /// Note: redb's compact() requires &mut self which is not available through &self.
+    /// The database already manages its storage efficiently with ACID transactions.
+    pub fn optimize(&self) -> Result<(), Box<dyn std::error::Error>> {
+        // redb handles storage management internally; no explicit optimization needed.
+        Ok(())
+    }
+
+    /// Get fastest recorded download speed
+    pub fn get_fastest_download(
+        &self,
+    ) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
+        self.best_result(|a, b| a.download_mbps > b.download_mbps)
+    }
+
+    /// Get fastest recorded upload speed
+    pub fn get_fastest_upload(
+        &self,
+    ) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
+        self.best_result(|a, b| a.upload_mbps > b.upload_mbps)
+    }
+
+    /// Get lowest recorded ping
+    pub fn get_lowest_ping(&self) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
+        // Of equal pings the newest counts
+        self.best_result(|a, b| a.ping_ms <= b.ping_ms)
+    }
+
+    /// Manually cleanup old records (older than retention period)
+    /// Returns the number of records deleted
+    pub fn cleanup_old_records_manual(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        // Keys sort by time, so everything before the cutoff's key is older
+        let cutoff = timestamp_key(Utc::now() - chrono::Duration::days(RETENTION_DAYS));
+
+        let mut deleted_count = 0;
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(RESULTS_TABLE)?;
+            table.retain_in(..cutoff.as_slice(), |_, _| {
+                deleted_count += 1;
+                false
+            })?;
+        }
+        txn.commit()?;
+
+        Ok(deleted_count)
+    }
+
+    /// Remove results stored more than once, keeping the first copy. Saves and
+    /// restores skip copies already, this clears out any that got in before.
+    /// Returns how many were removed.
+    pub fn dedupe_results(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut duplicates = Vec::new();
+        let mut days = BTreeSet::new();
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(RESULTS_TABLE)?;
+            // Results at the same time sit next to each other, compared once
+            // brought to the current layout
+            let mut at = None;
+            let mut seen: Vec<Vec<u8>> = Vec::new();
+            for item in table.iter()? {
+                let (key, value) = item?;
+                let Ok(result) = decode_result(value.value()) else {
+                    continue;
+                };
+                if at != Some(result.timestamp) {
+                    at = Some(result.timestamp);
+                    seen.clear();
+                }
+                let value = encode_result(&result)?;
+                if seen.contains(&value) {
+                    duplicates.push(key.value().to_vec());
+                    days.insert(result.timestamp.date_naive());
+                } else {
+                    seen.push(value);
+                }
+            }
+
+            for key in &duplicates {
+                table.remove(key.as_slice())?;
+            }
+        }
+        txn.commit()?;
+
+        self.rebuild_summaries(days)?;
+
+        Ok(duplicates.len())
+    }
+
+    /// Get the retention period in days
+    pub const fn get_retention_days() -> i64 {
+        RETENTION_DAYS
+    }
+
+    /// Get speed trends (compares recent results to historical average)
+    pub fn get_speed_trends(&self) -> Result<SpeedTrends, Box<dyn std::error::Error>> {
+        let all_stats = self.get_statistics()?;
+        let recent_results: Vec<SpeedTestResult> = self
+            .get_recent_results(10)?
+            .into_iter()
+            .filter(|result| !result.has_failures())
+            .collect();
+
+        if recent_results.is_empty() {
+            return Ok(SpeedTrends::default());
+        }
+
+        let recent_avg_download = recent_results.iter().map(|r| r.download_mbps).sum::<f64>()
+            / recent_results.len() as f64;
+        let recent_avg_upload =
+            recent_results.iter().map(|r| r.upload_mbps).sum::<f64>() / recent_results.len() as f64;
+        let recent_avg_ping =
+            recent_results.iter().map(|r| r.ping_ms).sum::<f64>() / recent_results.len() as f64;
+
+        let download_trend = if all_stats.avg_download_mbps > 0.0 {
+            ((recent_avg_download - all_stats.avg_download_mbps) / all_stats.avg_download_mbps)
+                * 100.0
+        } else {
+            0.0
+        };
+
+        let upload_trend = if all_stats.avg_upload_mbps > 0.0 {
+            ((recent_avg_upload - all_stats.avg_upload_mbps) / all_stats.avg_upload_mbps) * 100.0
+        } else {
+            0.0
+        };
+
+        let ping_trend = if all_stats.avg_ping_ms > 0.0 {
+            ((recent_avg_ping - all_stats.avg_ping_ms) / all_stats.avg_ping_ms) * 100.0
+        } else {
+            0.0
+        };
+
+        Ok(SpeedTrends {
+            download_trend_percent: download_trend,
+            upload_trend_percent: upload_trend,
+            ping_trend_percent: ping_trend,
+            improving: download_trend > 0.0 && upload_trend > 0.0 && ping_trend < 0.0,
+        })
+    }
+
+    /// Get how stored results compare to the advertised plan, overall and per day
+    pub fn get_plan_attainment(
+        &self,
+        plan: &AdvertisedPlan,
+    ) -> Result<PlanAttainmentStats, Box<dyn std::error::Error>> {
+        Ok(PlanAttainmentStats::from_results(
+            &self.get_all_results()?,
+            plan,
+        ))
+    }
+
+    /// Get average speeds per hour of day and weekday, over results since `since`
+    /// or all of them
+    pub fn get_congestion_stats(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<CongestionStats, Box<dyn std::error::Error>> {
+        let results = match since {
+            Some(start) => self.get_results_by_date_range(start, Utc::now())?,
+            None => self.get_all_results()?,
+        };
+        Ok(CongestionStats::from_results(&results))
+    }
+}
+
+/// Results of `HistoryStorage::iter_results`, records no layout decodes are skipped
+pub struct ResultIter {
+    range: redb::Range<'static, &'static [u8], &'static [u8]>,
+}
+
+type ResultEntry = (
+    redb::AccessGuard<'static, &'static [u8]>,
+    redb::AccessGuard<'static, &'static [u8]>,
+);
+
+impl ResultIter {
+    /// Decode a record, None when it is skipped
+    fn decode(
+        item: Result<ResultEntry, redb::StorageError>,
+    ) -> Option<Result<SpeedTestResult, redb::StorageError>> {
+        match item {
+            Ok((_, value)) => decode_result(value.value()).ok().map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl Iterator for ResultIter {
+    type Item = Result<SpeedTestResult, redb::StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.by_ref().find_map(Self::decode)
+    }
+}
+
+impl DoubleEndedIterator for ResultIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.by_ref().rev().find_map(Self::decode)
+    }
+}
+
+/// File format of `HistoryStorage::export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// An array with one result object per line
+    Json,
+    /// One row per result with the headline figures, for spreadsheets
+    Csv,
+}
+
+/// Which results an export includes, every result when left at the default
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub until: Option<DateTime<Utc>>,
+    /// Tags a result must all carry
+    pub tags: Vec<String>,
+    pub quality: Option<ConnectionQuality>,
+    /// Text the server location must contain
+    pub server: Option<String>,
+}
+
+impl ExportFilter {
+    pub fn matches(&self, result: &SpeedTestResult) -> bool {
+        self.since.is_none_or(|since| result.timestamp >= since)
+            && self.until.is_none_or(|until| result.timestamp < until)
+            && self.tags.iter().all(|tag| result.tags.contains(tag))
+            && self.quality.is_none_or(|quality| result.quality == quality)
+            && self
+                .server
+                .as_ref()
+                .is_none_or(|server| result.server_location.contains(server.as_str()))
+    }
+}
+
+const CSV_HEADER: &str = "timestamp,download_mbps,upload_mbps,ping_ms,jitter_ms,packet_loss_percent,quality,server_location,server_ip,isp,host_label,site,tags,jitter_method";
+
+/// A result as a CSV row in the columns of `CSV_HEADER`, tags separated by `;`
+fn csv_row(result: &SpeedTestResult) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    [
+        result.timestamp.to_rfc3339(),
+        format!("{:.2}", result.download_mbps),
+        format!("{:.2}", result.upload_mbps),
+        format!("{:.2}", result.ping_ms),
+        format!("{:.2}", result.jitter_ms),
+        format!("{:.2}", result.packet_loss_percent),
+        result.quality.to_string(),
+        result.server_location.clone(),
+        optional(result.server_ip.map(|ip| ip.to_string())),
+        optional(result.isp.clone()),
+        optional(result.host_label.clone()),
+        optional(result.site.clone()),
+        result.tags.join(";"),
+        result.jitter_method.to_string(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Quote a field holding a separator, quote or line break, doubling its quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub struct DbStats {
+    pub size_on_disk: u64,
+    pub results_count: usize,
+    pub schema_version: u32,
+    pub db_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(dead_code)]
+pub struct SpeedTrends {
+    pub download_trend_percent: f64,
+    pub upload_trend_percent: f64,
+    pub ping_trend_percent: f64,
+    pub improving: bool,
+}
+
+/// Portable copy of the history, stored as `history.json` in a backup archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryBackup {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub netrunner_version: String,
+    /// Newest first
+    pub results: Vec<SpeedTestResult>,
+    /// Newest first
+    pub aggregates: Vec<AggregateResult>,
+    /// Oldest first
+    pub ping_samples: Vec<PingSample>,
+    /// Oldest first
+    pub routes: Vec<RouteSnapshot>,
+    /// Newest first
+    pub data_usage: Vec<MonthlyDataUsage>,
+    /// Newest first, missing from backups made before diagnostics were kept
+    #[serde(default)]
+    pub diagnostics: Vec<DiagnosticsRecord>,
+    /// Newest first, missing from backups made before full analyses were kept
+    #[serde(default)]
+    pub full_analyses: Vec<FullAnalysisResult>,
+    /// By name, missing from backups made before baselines existed
+    #[serde(default)]
+    pub baselines: Vec<Baseline>,
+}
+
+impl HistoryBackup {
+    /// Write the backup as a ZIP with `history.json` and its `SHA256SUMS`.
+    /// Returns the SHA-256 digest of `history.json`.
+    pub fn write(&self, path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        let digest = sha256_hex(json.as_bytes());
+        let checksums = format!("{}  history.json\n", digest);
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in [("history.json", &json), ("SHA256SUMS", &checksums)] {
+            zip.start_file(name, options)?;
+            zip.write_all(contents.as_bytes())?;
+        }
+        zip.finish()?;
+
+        Ok(digest)
+    }
+
+    /// Read a backup archive, refusing one whose `history.json` doesn't match its
+    /// checksum or that was written in a newer format
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)
+            .map_err(|e| format!("{} is not a history backup: {}", path.display(), e))?;
+        let mut read_entry = |name: &str| -> Result<String, Box<dyn std::error::Error>> {
+            let mut contents = String::new();
+            archive
+                .by_name(name)
+                .map_err(|_| format!("{} has no {}", path.display(), name))?
+                .read_to_string(&mut contents)?;
+            Ok(contents)
+        };
+        let json = read_entry("history.json")?;
+        let checksums = read_entry("SHA256SUMS")?;
+
+        let expected = checksums
+            .lines()
+            .find_map(|line| line.strip_suffix("  history.json"))
+            .ok_or_else(|| format!("{} has no checksum for history.json", path.display()))?;
+        if sha256_hex(json.as_bytes()) != expected {
+            return Err(format!(
+                "{} is damaged: history.json does not match its SHA-256 checksum",
+                path.display()
+            )
+            .into());
+        }
+
+        let backup: Self = serde_json::from_str(&json)?;
+        if backup.format_version > BACKUP_FORMAT_VERSION {
+            return Err(format!(
+                "{} was written by netrunner_cli {} in backup format {}, this version reads up to {}",
+                path.display(),
+                backup.netrunner_version,
+                backup.format_version,
+                BACKUP_FORMAT_VERSION
+            )
+            .into());
+        }
+
+        Ok(backup)
+    }
+}
+
+/// Data speed tests used during one calendar month of local time
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct MonthlyDataUsage {
+    /// `YYYY-MM`
+    pub month: String,
+    pub test_count: usize,
+    pub data_used: DataUsage,
+}
+
+impl MonthlyDataUsage {
+    fn month_of(at: DateTime<Utc>) -> String {
+        at.with_timezone(&chrono::Local).format("%Y-%m").to_string()
+    }
+}
+
+/// Plan attainment across the stored history
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PlanAttainmentStats {
+    pub test_count: usize,
+    pub avg_download_percent: f64,
+    pub avg_upload_percent: f64,
+    /// Results where either direction fell below the plan's alert threshold
+    pub below_alert_count: usize,
+    /// Daily averages, oldest first
+    pub daily: Vec<DailyPlanAttainment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyPlanAttainment {
+    pub date: chrono::NaiveDate,
+    pub test_count: usize,
+    pub avg_download_percent: f64,
+    pub avg_upload_percent: f64,
+}
+
+impl PlanAttainmentStats {
+    /// Attainment of `results`, leaving out those with a failed phase
+    pub fn from_results(results: &[SpeedTestResult], plan: &AdvertisedPlan) -> Self {
+        let results: Vec<&SpeedTestResult> = results
+            .iter()
+            .filter(|result| !result.has_failures())
+            .collect();
+        if results.is_empty() {
+            return Self::default();
+        }
+
+        let mut stats = Self {
+            test_count: results.len(),
+            ..Self::default()
+        };
+        let mut by_day: std::collections::BTreeMap<chrono::NaiveDate, (usize, f64, f64)> =
+            std::collections::BTreeMap::new();
+
+        for result in &results {
+            let attainment = plan.attainment(result);
+            stats.avg_download_percent += attainment.download_percent;
+            stats.avg_upload_percent += attainment.upload_percent;
+            if plan.is_below_alert(&attainment) {
+                stats.below_alert_count += 1;
+            }
+
+            let day = by_day.entry(result.timestamp.date_naive()).or_default();
+            day.0 += 1;
+            day.1 += attainment.download_percent;
+            day.2 += attainment.upload_percent;
+        }
+
+        stats.avg_download_percent /= results.len() as f64;
+        stats.avg_upload_percent /= results.len() as f64;
+        stats.daily = by_day
+            .into_iter()
+            .map(|(date, (count, download, upload))| DailyPlanAttainment {
+                date,
+                test_count: count,
+                avg_download_percent: download / count as f64,
+                avg_upload_percent: upload / count as f64,
+            })
+            .collect();
+
+        stats
+    }
+}
+
+/// Average speeds per local hour of day and weekday, to show when the line slows down
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CongestionStats {
+    pub test_count: usize,
+    pub avg_download_mbps: f64,
+    /// Per hour over all days, hours without tests are left out
+    pub hourly: Vec<CongestionSlot>,
+    /// Per weekday and hour, Monday first, slots without tests are left out
+    pub weekly: Vec<CongestionSlot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CongestionSlot {
+    /// `None` in the hourly averages
+    pub weekday: Option<chrono::Weekday>,
+    pub hour: u32,
+    pub test_count: usize,
+    pub avg_download_mbps: f64,
+    pub avg_upload_mbps: f64,
+    pub avg_ping_ms: f64,
+}
+
+impl CongestionStats {
+    /// Averages over `results` by local time, failed tests are left out
+    pub fn from_results(results: &[SpeedTestResult]) -> Self {
+        use chrono::{Datelike, Timelike};
+
+        let results: Vec<&SpeedTestResult> = results
+            .iter()
+            .filter(|result| result.quality != ConnectionQuality::Failed && !result.has_failures())
+            .collect();
+        if results.is_empty() {
+            return Self::default();
+        }
+
+        let mut hourly: std::collections::BTreeMap<u32, Vec<&SpeedTestResult>> =
+            std::collections::BTreeMap::new();
+        let mut weekly: std::collections::BTreeMap<(u32, u32), Vec<&SpeedTestResult>> =
+            std::collections::BTreeMap::new();
+        for result in &results {
+            let local = result.timestamp.with_timezone(&chrono::Local);
+            hourly.entry(local.hour()).or_default().push(result);
+            weekly
+                .entry((local.weekday().num_days_from_monday(), local.hour()))
+                .or_default()
+                .push(result);
+        }
+
+        Self {
+            test_count: results.len(),
+            avg_download_mbps: results.iter().map(|r| r.download_mbps).sum::<f64>()
+                / results.len() as f64,
+            hourly: hourly
+                .into_iter()
+                .map(|(hour, slot)| CongestionSlot::new(None, hour, &slot))
+                .collect(),
+            weekly: weekly
+                .into_iter()
+                .map(|((day, hour), slot)| {
+                    let weekday = chrono::Weekday::try_from(day as u8).ok();
+                    CongestionSlot::new(weekday, hour, &slot)
+                })
+                .collect(),
+        }
+    }
+
+    /// Averages of one weekday and hour, `None` when no test ran then
+    pub fn slot(&self, weekday: chrono::Weekday, hour: u32) -> Option<&CongestionSlot> {
+        self.weekly
+            .iter()
+            .find(|slot| slot.weekday == Some(weekday) && slot.hour == hour)
+    }
+
+    /// Hour of day with the lowest average download
+    pub fn slowest_hour(&self) -> Option<&CongestionSlot> {
+        self.hourly
+            .iter()
+            .min_by(|a, b| a.avg_download_mbps.total_cmp(&b.avg_download_mbps))
+    }
+
+    /// Hour of day with the highest average download
+    pub fn fastest_hour(&self) -> Option<&CongestionSlot> {
+        self.hourly
+            .iter()
+            .max_by(|a, b| a.avg_download_mbps.total_cmp(&b.avg_download_mbps))
+    }
+}
+
+impl CongestionSlot {
+    fn new(weekday: Option<chrono::Weekday>, hour: u32, results: &[&SpeedTestResult]) -> Self {
+        let count = results.len() as f64;
+        Self {
+            weekday,
+            hour,
+            test_count: results.len(),
+            avg_download_mbps: results.iter().map(|r| r.download_mbps).sum::<f64>() / count,
+            avg_upload_mbps: results.iter().map(|r| r.upload_mbps).sum::<f64>() / count,
+            avg_ping_ms: results.iter().map(|r| r.ping_ms).sum::<f64>() / count,
+        }
+    }
+}
+
+/// Results of a day or week folded into sums and extremes, so statistics over a
+/// range can be put together without reading the results themselves. Results with
+/// a failed phase only add the data they used, their speeds are not measurements.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PeriodSummary {
+    pub test_count: usize,
+    pub download_sum_mbps: f64,
+    pub upload_sum_mbps: f64,
+    pub ping_sum_ms: f64,
+    pub min_download_mbps: f64,
+    pub max_download_mbps: f64,
+    pub min_upload_mbps: f64,
+    pub max_upload_mbps: f64,
+    pub min_ping_ms: f64,
+    pub max_ping_ms: f64,
+    pub downloaded_gb: f64,
+    pub uploaded_gb: f64,
+    pub first_test: Option<DateTime<Utc>>,
+    pub last_test: Option<DateTime<Utc>>,
+}
+
+impl PeriodSummary {
+    pub fn add(&mut self, result: &SpeedTestResult) {
+        let (downloaded_gb, uploaded_gb) = data_used_gb(result);
+        if result.has_failures() {
+            self.downloaded_gb += downloaded_gb;
+            self.uploaded_gb += uploaded_gb;
+            return;
+        }
+        self.merge(&Self {
+            test_count: 1,
+            download_sum_mbps: result.download_mbps,
+            upload_sum_mbps: result.upload_mbps,
+            ping_sum_ms: result.ping_ms,
+            min_download_mbps: result.download_mbps,
+            max_download_mbps: result.download_mbps,
+            min_upload_mbps: result.upload_mbps,
+            max_upload_mbps: result.upload_mbps,
+            min_ping_ms: result.ping_ms,
+            max_ping_ms: result.ping_ms,
+            downloaded_gb,
+            uploaded_gb,
+            first_test: Some(result.timestamp),
+            last_test: Some(result.timestamp),
+        });
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.downloaded_gb += other.downloaded_gb;
+        self.uploaded_gb += other.uploaded_gb;
+        if other.test_count == 0 {
+            return;
+        }
+        if self.test_count == 0 {
+            *self = Self {
+                downloaded_gb: self.downloaded_gb,
+                uploaded_gb: self.uploaded_gb,
+                ..other.clone()
+            };
+            return;
+        }
+        self.test_count += other.test_count;
+        self.download_sum_mbps += other.download_sum_mbps;
+        self.upload_sum_mbps += other.upload_sum_mbps;
+        self.ping_sum_ms += other.ping_sum_ms;
+        self.min_download_mbps = self.min_download_mbps.min(other.min_download_mbps);
+        self.max_download_mbps = self.max_download_mbps.max(other.max_download_mbps);
+        self.min_upload_mbps = self.min_upload_mbps.min(other.min_upload_mbps);
+        self.max_upload_mbps = self.max_upload_mbps.max(other.max_upload_mbps);
+        self.min_ping_ms = self.min_ping_ms.min(other.min_ping_ms);
+        self.max_ping_ms = self.max_ping_ms.max(other.max_ping_ms);
+        // Both are set once a summary counts a test
+        self.first_test = self.first_test.min(other.first_test);
+        self.last_test = self.last_test.max(other.last_test);
+    }
+
+    pub fn statistics(&self) -> TestStatistics {
+        if self.test_count == 0 {
+            return TestStatistics {
+                total_data_downloaded_gb: self.downloaded_gb,
+                total_data_uploaded_gb: self.uploaded_gb,
+                ..TestStatistics::default()
+            };
+        }
+        let count = self.test_count as f64;
+        TestStatistics {
+            test_count: self.test_count,
+            avg_download_mbps: self.download_sum_mbps / count,
+            max_download_mbps: self.max_download_mbps,
+            min_download_mbps: self.min_download_mbps,
+            avg_upload_mbps: self.upload_sum_mbps / count,
+            max_upload_mbps: self.max_upload_mbps,
+            min_upload_mbps: self.min_upload_mbps,
+            avg_ping_ms: self.ping_sum_ms / count,
+            min_ping_ms: self.min_ping_ms,
+            max_ping_ms: self.max_ping_ms,
+            total_data_downloaded_gb: self.downloaded_gb,
+            total_data_uploaded_gb: self.uploaded_gb,
+            first_test: self.first_test.unwrap_or_else(Utc::now),
+            last_test: self.last_test.unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// Key of the summary of a day, or of the week starting that Monday
+fn period_key(start: NaiveDate) -> String {
+    start.format("%Y-%m-%d").to_string()
+}
+
+/// Monday of the week a day is in
+fn week_of(day: NaiveDate) -> NaiveDate {
+    day.week(Weekday::Mon).first_day()
+}
+
+fn start_of_day(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+fn read_summary(
+    table: &impl ReadableTable<&'static str, &'static [u8]>,
+    start: NaiveDate,
+) -> Result<Option<PeriodSummary>, redb::StorageError> {
+    Ok(table
+        .get(period_key(start).as_str())?
+        .and_then(|value| postcard::from_bytes(value.value()).ok()))
+}
+
+/// Gigabytes a test downloaded and uploaded. Results from before the byte counters
+/// only have an estimate from their speeds and the whole test duration.
+fn data_used_gb(result: &SpeedTestResult) -> (f64, f64) {
+    match result.data_used {
+        Some(data_used) => (
+            data_used.downloaded_bytes as f64 / 1e9,
+            data_used.uploaded_bytes as f64 / 1e9,
+        ),
+        None => {
+            // Megabits per second over the seconds, divided into gigabytes
+            let seconds = result.test_duration_seconds;
+            (
+                result.download_mbps * seconds / 8.0 / 1000.0,
+                result.upload_mbps * seconds / 8.0 / 1000.0,
+            )
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `latency_stats` was added, as written by
+/// releases from before layout numbers. Postcard records carry no field names, so
+/// records written by older versions are decoded with this and converted rather
+/// than dropped.
+#[derive(Deserialize)]
+struct BaselineSpeedTestResult {
+    timestamp: DateTime<Utc>,
+    download_mbps: f64,
+    upload_mbps: f64,
+    ping_ms: f64,
+    jitter_ms: f64,
+    packet_loss_percent: f64,
+    server_location: String,
+    server_ip: Option<IpAddr>,
+    client_ip: Option<IpAddr>,
+    quality: ConnectionQuality,
+    test_duration_seconds: f64,
+    isp: Option<String>,
+}
+
+impl From<BaselineSpeedTestResult> for SpeedTestResult {
+    fn from(baseline: BaselineSpeedTestResult) -> Self {
+        let jitter_method =
+            legacy_jitter_method(&baseline.server_location, JitterMethod::StandardDeviation);
+        Self {
+            timestamp: baseline.timestamp,
+            download_mbps: baseline.download_mbps,
+            upload_mbps: baseline.upload_mbps,
+            ping_ms: baseline.ping_ms,
+            jitter_ms: baseline.jitter_ms,
+            packet_loss_percent: baseline.packet_loss_percent,
+            server_location: baseline.server_location,
+            server_ip: baseline.server_ip,
+            client_ip: baseline.client_ip,
+            quality: baseline.quality,
+            test_duration_seconds: baseline.test_duration_seconds,
+            isp: baseline.isp,
+            latency_stats: None,
+            host_label: None,
+            site: None,
+            failovers: Vec::new(),
+            failures: Vec::new(),
+            data_used: None,
+            endpoints: Vec::new(),
+            engine: None,
+            country: None,
+            link_stats: None,
+            other_traffic: None,
+            tags: Vec::new(),
+            transport: None,
+            websocket_latency: None,
+            one_way_delay: None,
+            jitter_method,
+        }
+    }
+}
+
+/// How a result saved before the method was recorded computed its jitter. ndt7
+/// results, told apart by their location, always held the server's RTT variance.
+fn legacy_jitter_method(server_location: &str, probes: JitterMethod) -> JitterMethod {
+    if server_location.ends_with("(M-Lab)") {
+        JitterMethod::RttVariance
+    } else {
+        probes
+    }
+}
+
+/// Layout of `SpeedTestResult` before `host_label` and `site` were added. Postcard
+/// encodes a nested struct exactly like its fields inlined, so the older layout is
+/// reused.
+#[derive(Deserialize)]
+struct LegacySpeedTestResult {
+    result: BaselineSpeedTestResult,
+    latency_stats: Option<LatencyStats>,
+}
+
+impl From<LegacySpeedTestResult> for SpeedTestResult {
+    fn from(legacy: LegacySpeedTestResult) -> Self {
+        Self {
+            latency_stats: legacy.latency_stats,
+            ..legacy.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `failovers` was added
+#[derive(Deserialize)]
+struct LabelledSpeedTestResult {
+    result: LegacySpeedTestResult,
+    host_label: Option<String>,
+    site: Option<String>,
+}
+
+impl From<LabelledSpeedTestResult> for SpeedTestResult {
+    fn from(labelled: LabelledSpeedTestResult) -> Self {
+        Self {
+            host_label: labelled.host_label,
+            site: labelled.site,
+            ..labelled.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `failures` was added
+#[derive(Deserialize)]
+struct FailoverSpeedTestResult {
+    result: LabelledSpeedTestResult,
+    failovers: Vec<ServerFailover>,
+}
+
+impl From<FailoverSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: FailoverSpeedTestResult) -> Self {
+        Self {
+            failovers: versioned.failovers,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `data_used` was added
+#[derive(Deserialize)]
+struct PhaseFailureSpeedTestResult {
+    result: FailoverSpeedTestResult,
+    failures: Vec<PhaseFailure>,
+}
+
+impl From<PhaseFailureSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: PhaseFailureSpeedTestResult) -> Self {
+        Self {
+            failures: versioned.failures,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `endpoints` was added
+#[derive(Deserialize)]
+struct DataUsageSpeedTestResult {
+    result: PhaseFailureSpeedTestResult,
+    data_used: Option<DataUsage>,
+}
+
+impl From<DataUsageSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: DataUsageSpeedTestResult) -> Self {
+        Self {
+            data_used: versioned.data_used,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `engine` was added
+#[derive(Deserialize)]
+struct EndpointsSpeedTestResult {
+    result: DataUsageSpeedTestResult,
+    endpoints: Vec<EndpointLatency>,
+}
+
+impl From<EndpointsSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: EndpointsSpeedTestResult) -> Self {
+        Self {
+            endpoints: versioned.endpoints,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `country` was added
+#[derive(Deserialize)]
+struct EngineSpeedTestResult {
+    result: EndpointsSpeedTestResult,
+    engine: Option<EngineInfo>,
+}
+
+impl From<EngineSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: EngineSpeedTestResult) -> Self {
+        Self {
+            engine: versioned.engine,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `link_stats` was added
+#[derive(Deserialize)]
+struct CountrySpeedTestResult {
+    result: EngineSpeedTestResult,
+    country: Option<String>,
+}
+
+impl From<CountrySpeedTestResult> for SpeedTestResult {
+    fn from(versioned: CountrySpeedTestResult) -> Self {
+        Self {
+            country: versioned.country,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `other_traffic` was added
+#[derive(Deserialize)]
+struct LinkStatsSpeedTestResult {
+    result: CountrySpeedTestResult,
+    link_stats: Option<LinkStats>,
+}
+
+impl From<LinkStatsSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: LinkStatsSpeedTestResult) -> Self {
+        Self {
+            link_stats: versioned.link_stats,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `tags` was added
+#[derive(Deserialize)]
+struct OtherTrafficSpeedTestResult {
+    result: LinkStatsSpeedTestResult,
+    other_traffic: Option<OtherTraffic>,
+}
+
+impl From<OtherTrafficSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: OtherTrafficSpeedTestResult) -> Self {
+        Self {
+            other_traffic: versioned.other_traffic,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `transport` was added
+#[derive(Deserialize)]
+struct TagsSpeedTestResult {
+    result: OtherTrafficSpeedTestResult,
+    tags: Vec<String>,
+}
+
+impl From<TagsSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: TagsSpeedTestResult) -> Self {
+        Self {
+            tags: versioned.tags,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `websocket_latency` was added
+#[derive(Deserialize)]
+struct TransportSpeedTestResult {
+    result: TagsSpeedTestResult,
+    transport: Option<TransportInfo>,
+}
+
+impl From<TransportSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: TransportSpeedTestResult) -> Self {
+        Self {
+            transport: versioned.transport,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `one_way_delay` was added
+#[derive(Deserialize)]
+struct WebSocketSpeedTestResult {
+    result: TransportSpeedTestResult,
+    websocket_latency: Option<WebSocketLatency>,
+}
+
+impl From<WebSocketSpeedTestResult> for SpeedTestResult {
+    fn from(versioned: WebSocketSpeedTestResult) -> Self {
+        Self {
+            websocket_latency: versioned.websocket_latency,
+            ..versioned.result.into()
+        }
+    }
+}
+
+/// Layout of `SpeedTestResult` before `jitter_method` was added
+#[derive(Deserialize)]
+struct OneWayDelaySpeedTestResult {
+    result: WebSocketSpeedTestResult,
+    one_way_delay: Option<OneWayDelay>,
+}
+
+impl From<OneWayDelaySpeedTestResult> for SpeedTestResult {
+    fn from(versioned: OneWayDelaySpeedTestResult) -> Self {
+        let result: SpeedTestResult = versioned.result.into();
+        Self {
+            one_way_delay: versioned.one_way_delay,
+            // The version that wrote this layout computed jitter per RFC 3550
+            jitter_method: legacy_jitter_method(&result.server_location, JitterMethod::Rfc3550),
+            ..result
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LegacyAggregateResult<T> {
+    run_count: usize,
+    run_timestamps: Vec<DateTime<Utc>>,
+    median: T,
+}
+
+impl<T: Into<SpeedTestResult>> From<LegacyAggregateResult<T>> for AggregateResult {
+    fn from(legacy: LegacyAggregateResult<T>) -> Self {
+        Self {
+            run_count: legacy.run_count,
+            run_timestamps: legacy.run_timestamps,
+            median: legacy.median.into(),
+        }
+    }
+}
+
+/// Open the database at `path`, retrying while another process holds its lock
+fn create_when_unlocked(
+    path: &Path,
+    wait: Duration,
+) -> Result<redb::Database, Box<dyn std::error::Error>> {
+    let deadline = Instant::now() + wait;
+    loop {
+        match redb::Database::create(path) {
+            Err(redb::DatabaseError::DatabaseAlreadyOpen) if Instant::now() < deadline => {
+                std::thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err(redb::DatabaseError::DatabaseAlreadyOpen) => {
+                return Err(format!(
+                    "History database {} is in use by another netrunner_cli process. \
+                     Try again when it finishes, or pass --no-history to run without history",
+                    path.display()
+                )
+                .into());
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Create `data_dir` and return the database path in it. A database only found at
+/// the `legacy` path is moved over first, so history survives the move.
+fn prepare_db_path(
+    data_dir: &Path,
+    legacy: Option<&Path>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(data_dir)?;
+    let path = data_dir.join(DB_NAME);
+
+    if let Some(legacy) = legacy.filter(|legacy| *legacy != path && legacy.exists()) {
+        if !path.exists() {
+            // A rename can't cross file systems, e.g. onto a NAS mount
+            if std::fs::rename(legacy, &path).is_err() {
+                std::fs::copy(legacy, &path)?;
+                std::fs::remove_file(legacy)?;
+            }
+            if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                eprintln!(
+                    "[TRACE] Moved history database from {} to {}",
+                    legacy.display(),
+                    path.display()
+                );
+            }
+        }
+    }
+
+    Ok(path)
+}
+
+/// Table key of a record taken at `at`, nanoseconds since the epoch so keys sort by time
+fn timestamp_key(at: DateTime<Utc>) -> [u8; 8] {
+    at.timestamp_nanos_opt().unwrap_or_default().to_be_bytes()
+}
+
+/// Key of a new result: its timestamp key followed by a random UUID, so results
+/// from the same nanosecond don't overwrite each other. Results stored before
+/// the suffix keep their bare timestamp key, which sorts among the others.
+fn result_key(at: DateTime<Utc>) -> Vec<u8> {
+    let mut key = timestamp_key(at).to_vec();
+    key.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    key
+}
+
+/// Key range holding every result taken at `at`
+fn result_keys_at(at: DateTime<Utc>) -> ([u8; 8], [u8; 8]) {
+    (
+        timestamp_key(at),
+        timestamp_key(at + chrono::Duration::nanoseconds(1)),
+    )
+}
+
+/// Key of the stored copy of an encoded result taken at `at`, if there is one
+fn find_result(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    at: DateTime<Utc>,
+    value: &[u8],
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let (start, end) = result_keys_at(at);
+    for item in table.range(start.as_slice()..end.as_slice())? {
+        let (key, stored) = item?;
+        // Older layouts are compared once brought to the current one
+        let stored = decode_result(stored.value()).and_then(|result| encode_result(&result));
+        if stored.is_ok_and(|stored| stored == value) {
+            return Ok(Some(key.value().to_vec()));
+        }
+    }
+    Ok(None)
+}
+
+/// Store an encoded result under a new key, unless the same result is already
+/// stored at its time. Returns whether it was stored.
+fn insert_result(
+    table: &mut redb::Table<&'static [u8], &'static [u8]>,
+    at: DateTime<Utc>,
+    value: &[u8],
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if find_result(table, at, value)?.is_some() {
+        return Ok(false);
+    }
+
+    table.insert(result_key(at).as_slice(), value)?;
+    Ok(true)
+}
+
+/// Encode a result behind its layout number
+fn encode_result(result: &SpeedTestResult) -> Result<Vec<u8>, postcard::Error> {
+    postcard::to_stdvec(&(RESULT_LAYOUT, result))
+}
+
+/// Encode an aggregate behind the layout number of its median
+fn encode_aggregate(aggregate: &AggregateResult) -> Result<Vec<u8>, postcard::Error> {
+    postcard::to_stdvec(&(RESULT_LAYOUT, aggregate))
+}
+
+/// Decode a stored result in whichever layout it was written with
+fn decode_result(bytes: &[u8]) -> Result<SpeedTestResult, postcard::Error> {
+    let (layout, payload) = postcard::take_from_bytes::<u32>(bytes)?;
+    decode_result_layout(layout, payload)
+}
+
+/// Decode a stored aggregate in whichever layout it was written with
+fn decode_aggregate(bytes: &[u8]) -> Result<AggregateResult, postcard::Error> {
+    let (layout, payload) = postcard::take_from_bytes::<u32>(bytes)?;
+    decode_aggregate_layout(layout, payload)
+}
+
+/// Decode a result without its layout number, as written by a given layout
+fn decode_result_layout(layout: u32, bytes: &[u8]) -> Result<SpeedTestResult, postcard::Error> {
+    match layout {
+        RESULT_LAYOUT => postcard::from_bytes(bytes),
+        14 => postcard::from_bytes::<OneWayDelaySpeedTestResult>(bytes).map(Into::into),
+        13 => postcard::from_bytes::<WebSocketSpeedTestResult>(bytes).map(Into::into),
+        12 => postcard::from_bytes::<TransportSpeedTestResult>(bytes).map(Into::into),
+        11 => postcard::from_bytes::<TagsSpeedTestResult>(bytes).map(Into::into),
+        10 => postcard::from_bytes::<OtherTrafficSpeedTestResult>(bytes).map(Into::into),
+        9 => postcard::from_bytes::<LinkStatsSpeedTestResult>(bytes).map(Into::into),
+        8 => postcard::from_bytes::<CountrySpeedTestResult>(bytes).map(Into::into),
+        7 => postcard::from_bytes::<EngineSpeedTestResult>(bytes).map(Into::into),
+        6 => postcard::from_bytes::<EndpointsSpeedTestResult>(bytes).map(Into::into),
+        5 => postcard::from_bytes::<DataUsageSpeedTestResult>(bytes).map(Into::into),
+        4 => postcard::from_bytes::<PhaseFailureSpeedTestResult>(bytes).map(Into::into),
+        3 => postcard::from_bytes::<FailoverSpeedTestResult>(bytes).map(Into::into),
+        2 => postcard::from_bytes::<LabelledSpeedTestResult>(bytes).map(Into::into),
+        1 => postcard::from_bytes::<LegacySpeedTestResult>(bytes).map(Into::into),
+        // Never written behind a number, only recognised in bare records
+        0 => postcard::from_bytes::<BaselineSpeedTestResult>(bytes).map(Into::into),
+        // Written by a newer version
+        _ => Err(postcard::Error::DeserializeBadEncoding),
+    }
+}
+
+/// Decode an aggregate without its layout number, as written by a given layout
+fn decode_aggregate_layout(layout: u32, bytes: &[u8]) -> Result<AggregateResult, postcard::Error> {
+    match layout {
+        RESULT_LAYOUT => postcard::from_bytes(bytes),
+        14 => postcard::from_bytes::<LegacyAggregateResult<OneWayDelaySpeedTestResult>>(bytes)
+            .map(Into::into),
+        13 => postcard::from_bytes::<LegacyAggregateResult<WebSocketSpeedTestResult>>(bytes)
+            .map(Into::into),
+        12 => postcard::from_bytes::<LegacyAggregateResult<TransportSpeedTestResult>>(bytes)
+            .map(Into::into),
+        11 => postcard::from_bytes::<LegacyAggregateResult<TagsSpeedTestResult>>(bytes)
+            .map(Into::into),
+        10 => postcard::from_bytes::<LegacyAggregateResult<OtherTrafficSpeedTestResult>>(bytes)
+            .map(Into::into),
+        9 => postcard::from_bytes::<LegacyAggregateResult<LinkStatsSpeedTestResult>>(bytes)
+            .map(Into::into),
+        8 => postcard::from_bytes::<LegacyAggregateResult<CountrySpeedTestResult>>(bytes)
+            .map(Into::into),
+        7 => postcard::from_bytes::<LegacyAggregateResult<EngineSpeedTestResult>>(bytes)
+            .map(Into::into),
+        6 => postcard::from_bytes::<LegacyAggregateResult<EndpointsSpeedTestResult>>(bytes)
+            .map(Into::into),
+        5 => postcard::from_bytes::<LegacyAggregateResult<DataUsageSpeedTestResult>>(bytes)
+            .map(Into::into),
+        4 => postcard::from_bytes::<LegacyAggregateResult<PhaseFailureSpeedTestResult>>(bytes)
+            .map(Into::into),
+        3 => postcard::from_bytes::<LegacyAggregateResult<FailoverSpeedTestResult>>(bytes)
+            .map(Into::into),
+        2 => postcard::from_bytes::<LegacyAggregateResult<LabelledSpeedTestResult>>(bytes)
+            .map(Into::into),
+        1 => postcard::from_bytes::<LegacyAggregateResult<LegacySpeedTestResult>>(bytes)
+            .map(Into::into),
+        0 => postcard::from_bytes::<LegacyAggregateResult<BaselineSpeedTestResult>>(bytes)
+            .map(Into::into),
+        _ => Err(postcard::Error::DeserializeBadEncoding),
+    }
+}
+
+/// Decode a bare result from before schema 1. Layouts are tried newest first, since
+/// an older layout also decodes a prefix of a newer record, down to the one
+/// released versions wrote before layouts were numbered.
+fn decode_unversioned_result(bytes: &[u8]) -> Option<SpeedTestResult> {
+    (0..=RESULT_LAYOUT)
+        .rev()
+        .find_map(|layout| decode_result_layout(layout, bytes).ok())
+}
+
+/// Decode a bare aggregate from before schema 1
+fn decode_unversioned_aggregate(bytes: &[u8]) -> Option<AggregateResult> {
+    (0..=RESULT_LAYOUT)
+        .rev()
+        .find_map(|layout| decode_aggregate_layout(layout, bytes).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::{AlertKind, ConnectionQuality, NetworkDiagnostics, SpeedPhase};
+    use tempfile::tempdir;
+
+    /// A record as stored, `bytes` behind the layout number
+    fn with_layout(layout: u32, bytes: Vec<u8>) -> Vec<u8> {
+        let mut record = postcard::to_stdvec(&layout).unwrap();
+        record.extend(bytes);
+        record
+    }
+
+    // Results as each layout wrote them, captured with the version that introduced
+    // it. They stand for records already on disk, so they are never regenerated.
+
+    /// A result as released versions before `latency_stats` stored it: 80/20 Mbps
+    /// from Frankfurt on 1 March 2025, rated good, by "Example ISP"
+    const BASELINE_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 49, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 84, 64, 0, 0, 0, 0, 0, 0, 52, 64, 0, 0, 0, 0, 0, 0, 46, 64, 0, 0, 0, 0, 0, 0,
+        0, 64, 0, 0, 0, 0, 0, 0, 0, 0, 9, 70, 114, 97, 110, 107, 102, 117, 114, 116, 1, 0, 1, 1, 1,
+        1, 0, 1, 0, 0, 0, 0, 0, 0, 36, 64, 1, 11, 69, 120, 97, 109, 112, 108, 101, 32, 73, 83, 80,
+    ];
+
+    /// Layout 1: 120 Mbps on 2 March 2025 with latency statistics over 10, 11 and 12 ms
+    const LAYOUT_1_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 50, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 94, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 1, 3, 0, 0, 0, 0, 0, 0, 36, 64, 0, 0, 0, 0, 0, 0, 38, 64, 0, 0, 0, 0, 0, 0, 40, 64,
+        0, 0, 0, 0, 0, 0, 40, 64, 0, 0, 0, 0, 0, 0, 40, 64, 3, 0, 0, 0, 0, 0, 0, 36, 64, 0, 0, 0,
+        0, 0, 0, 38, 64, 1, 0, 0, 0, 0, 0, 0, 38, 64, 0, 0, 0, 0, 0, 0, 40, 64, 1, 0, 0, 0, 0, 0,
+        0, 40, 64, 0, 0, 0, 0, 0, 0, 42, 64, 1,
+    ];
+
+    /// Layout 2: 95 Mbps on 3 March 2025 from host "nas" at site "Berlin"
+    const LAYOUT_2_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 51, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 192, 87, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 1, 3, 110, 97, 115, 1, 6, 66, 101, 114, 108, 105, 110,
+    ];
+
+    /// Layout 3: 90 Mbps on 4 March 2025, the upload having failed over from server a to b
+    const LAYOUT_3_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 52, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 128, 86, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 1, 1, 1, 97, 1, 98,
+    ];
+
+    /// Layout 4: 80 Mbps over 100 s on 5 March 2025, the upload failed with "transfers stalled"
+    const LAYOUT_4_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 53, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 84, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 89,
+        64, 0, 0, 0, 0, 0, 1, 1, 17, 116, 114, 97, 110, 115, 102, 101, 114, 115, 32, 115, 116, 97,
+        108, 108, 101, 100,
+    ];
+
+    /// Layout 5: 70 Mbps on 6 March 2025, using 1000 bytes down and 100 up
+    const LAYOUT_5_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 54, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 128, 81, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 1, 232, 7, 100,
+    ];
+
+    /// Layout 6: 65 Mbps on 7 March 2025 with an unanswered "gateway" endpoint at 192.168.1.1
+    const LAYOUT_6_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 55, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 64, 80, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 7, 103, 97, 116, 101, 119, 97, 121, 11, 49, 57, 50, 46, 49,
+        54, 56, 46, 49, 46, 49, 0,
+    ];
+
+    /// Layout 7: 60 Mbps on 8 March 2025 with 8 download and 4 upload connections
+    const LAYOUT_7_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 56, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 78, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 5, 48, 46, 55, 46, 51, 1, 12, 51, 52, 98, 102, 55, 51, 48,
+        50, 52, 57, 102, 49, 0, 0, 8, 4, 30, 0, 0, 0, 0, 0, 0,
+    ];
+
+    /// Layout 8: 55 Mbps on 9 March 2025 in Germany
+    const LAYOUT_8_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 48, 57, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 128, 75, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 7, 71, 101, 114, 109, 97, 110, 121,
+    ];
+
+    /// Layout 9: 50 Mbps on 10 March 2025 over eth0
+    const LAYOUT_9_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 48, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 73, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 4, 101, 116, 104, 48, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0,
+    ];
+
+    /// Layout 10: 45 Mbps on 11 March 2025 while other devices downloaded 12 Mbps, 2 of them on the LAN
+    const LAYOUT_10_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 49, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 128, 70, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 40, 64, 0, 1, 2,
+    ];
+
+    /// Layout 11: 40 Mbps on 12 March 2025 tagged "wired"
+    const LAYOUT_11_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 50, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 68, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 5, 119, 105, 114, 101, 100,
+    ];
+
+    /// Layout 12: 35 Mbps on 13 March 2025 over h2 and TLS 1.3
+    const LAYOUT_12_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 51, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 128, 65, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 104, 50, 1, 7, 84, 76, 83, 32, 49,
+        46, 51,
+    ];
+
+    /// Layout 13: 30 Mbps on 14 March 2025 with a WebSocket set up in 40 ms and pinged in 10, 11 and 12 ms
+    const LAYOUT_13_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 52, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 62, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 68, 64, 0, 0, 0, 0, 0, 0,
+        38, 64, 3, 0, 0, 0, 0, 0, 0, 36, 64, 0, 0, 0, 0, 0, 0, 38, 64, 0, 0, 0, 0, 0, 0, 40, 64, 0,
+        0, 0, 0, 0, 0, 40, 64, 0, 0, 0, 0, 0, 0, 40, 64, 3, 0, 0, 0, 0, 0, 0, 36, 64, 0, 0, 0, 0,
+        0, 0, 38, 64, 1, 0, 0, 0, 0, 0, 0, 38, 64, 0, 0, 0, 0, 0, 0, 40, 64, 1, 0, 0, 0, 0, 0, 0,
+        40, 64, 0, 0, 0, 0, 0, 0, 42, 64, 1, 0,
+    ];
+
+    /// Layout 14: 25 Mbps on 15 March 2025 with 1.5 ms of jitter and the round
+    /// trip split into 7 ms up and 5 ms down
+    const LAYOUT_14_RECORD: &[u8] = &[
+        20, 50, 48, 50, 53, 45, 48, 51, 45, 49, 53, 84, 49, 50, 58, 48, 48, 58, 48, 48, 90, 0, 0,
+        0, 0, 0, 0, 57, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 248,
+        63, 0, 0, 0, 0, 0, 0, 0, 0, 7, 85, 110, 107, 110, 111, 119, 110, 0, 0, 5, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 28, 64, 0, 0, 0, 0,
+        0, 0, 20, 64, 0, 0, 0, 0, 0, 0, 248, 63, 20,
+    ];
+
+    /// Store `bytes` as `layout` wrote them, under the timestamp they carry
+    fn insert_record(storage: &HistoryStorage, layout: u32, bytes: &[u8]) {
+        let timestamp = decode_result_layout(layout, bytes).unwrap().timestamp;
+        let txn = storage.db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(RESULTS_TABLE).unwrap();
+            table
+                .insert(
+                    result_key(timestamp).as_slice(),
+                    with_layout(layout, bytes.to_vec()).as_slice(),
+                )
+                .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn test_storage_creation() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path);
+        assert!(storage.is_ok());
+    }
+
+    #[test]
+    fn test_save_and_retrieve() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let result = SpeedTestResult {
+            timestamp: Utc::now(),
+            download_mbps: 100.0,
+            upload_mbps: 50.0,
+            ping_ms: 10.0,
+            jitter_ms: 1.0,
+            packet_loss_percent: 0.0,
+            server_location: "Test Server".to_string(),
+            server_ip: None,
+            client_ip: None,
+            quality: ConnectionQuality::Excellent,
+            test_duration_seconds: 10.0,
+            isp: None,
+            ..Default::default()
+        };
+
+        assert!(storage.save_result(&result).is_ok());
+
+        let results = storage.get_recent_results(1).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].download_mbps, 100.0);
+    }
+
+    #[test]
+    fn test_latest_result() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let older = SpeedTestResult {
+            timestamp: Utc::now() - chrono::Duration::minutes(10),
+            download_mbps: 40.0,
+            ..Default::default()
+        };
+        let newer = SpeedTestResult {
+            download_mbps: 80.0,
+            ..Default::default()
+        };
+        storage.save_result(&newer).unwrap();
+        storage.save_result(&older).unwrap();
+
+        let latest = storage.get_latest_result().unwrap().unwrap();
+        assert_eq!(latest.download_mbps, 80.0);
+    }
+
+    #[test]
+    fn test_export_with_filters() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let now = Utc::now();
+        let result = |days: i64, tags: &[&str], quality, server: &str| SpeedTestResult {
+            timestamp: now - chrono::Duration::days(days),
+            download_mbps: 100.0 + days as f64,
+            quality,
+            server_location: server.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        for stored in [
+            result(5, &["wired"], ConnectionQuality::Good, "Berlin, DE"),
+            result(
+                3,
+                &["wired", "office"],
+                ConnectionQuality::Good,
+                "Frankfurt, DE",
+            ),
+            result(2, &["wifi"], ConnectionQuality::Poor, "Berlin, DE"),
+            result(
+                1,
+                &["wired"],
+                ConnectionQuality::Excellent,
+                "Paris \"Nord\", FR",
+            ),
+        ] {
+            storage.save_result(&stored).unwrap();
+        }
+
+        let filter = ExportFilter {
+            since: Some(now - chrono::Duration::days(4)),
+            tags: vec!["wired".to_string()],
+            ..Default::default()
+        };
+        let mut csv = Vec::new();
+        let count = storage
+            .export(&mut csv, ExportFormat::Csv, &filter)
+            .unwrap();
+        assert_eq!(count, 2);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        // Oldest first, fields with separators or quotes quoted
+        assert!(lines[1].contains(",103.00,") && lines[1].ends_with(",wired;office,RFC 3550"));
+        assert!(lines[2].contains(",\"Paris \"\"Nord\"\", FR\","));
+
+        let filter = ExportFilter {
+            until: Some(now - chrono::Duration::days(1) - chrono::Duration::hours(1)),
+            quality: Some(ConnectionQuality::Good),
+            server: Some("Berlin".to_string()),
+            ..Default::default()
+        };
+        let mut json = Vec::new();
+        storage
+            .export(&mut json, ExportFormat::Json, &filter)
+            .unwrap();
+        let exported: Vec<SpeedTestResult> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].download_mbps, 105.0);
+
+        let mut empty = Vec::new();
+        let filter = ExportFilter {
+            tags: vec!["vpn".to_string()],
+            ..Default::default()
+        };
+        storage
+            .export(&mut empty, ExportFormat::Json, &filter)
+            .unwrap();
+        assert!(serde_json::from_slice::<Vec<SpeedTestResult>>(&empty)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_statistics_from_summaries() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path.clone()).unwrap();
+
+        // Two tests a day over three weeks
+        let now = Utc::now();
+        let results: Vec<SpeedTestResult> = (0..42)
+            .map(|i| SpeedTestResult {
+                timestamp: now - chrono::Duration::hours(12 * i + 1),
+                download_mbps: 50.0 + (i * 7 % 13) as f64,
+                upload_mbps: 10.0 + (i % 5) as f64,
+                ping_ms: 10.0 + (i % 3) as f64,
+                ..Default::default()
+            })
+            .collect();
+        for result in &results {
+            storage.save_result(result).unwrap();
+        }
+        // Saving one again is skipped rather than counted twice
+        assert!(!storage.save_result(&results[3]).unwrap());
+
+        let expected = |start: DateTime<Utc>, end: DateTime<Utc>| {
+            let mut summary = PeriodSummary::default();
+            for result in results
+                .iter()
+                .filter(|r| r.timestamp >= start && r.timestamp <= end)
+            {
+                summary.add(result);
+            }
+            summary.statistics()
+        };
+        let check = |storage: &HistoryStorage, start, end| {
+            let stats = storage.get_statistics_by_date_range(start, end).unwrap();
+            let wanted = expected(start, end);
+            assert_eq!(stats.test_count, wanted.test_count);
+            assert!((stats.avg_download_mbps - wanted.avg_download_mbps).abs() < 1e-9);
+            assert!((stats.avg_ping_ms - wanted.avg_ping_ms).abs() < 1e-9);
+            assert_eq!(stats.min_download_mbps, wanted.min_download_mbps);
+            assert_eq!(stats.max_upload_mbps, wanted.max_upload_mbps);
+            assert_eq!(stats.first_test, wanted.first_test);
+            assert_eq!(stats.last_test, wanted.last_test);
+        };
+        // Partial days at both ends around whole days and weeks
+        let start = now - chrono::Duration::days(19) - chrono::Duration::hours(5);
+        check(&storage, start, now - chrono::Duration::hours(3));
+        check(&storage, start, now);
+        check(&storage, now - chrono::Duration::hours(30), now);
+
+        storage.delete_result(results[10].timestamp).unwrap();
+        let stats = storage
+            .get_statistics_by_date_range(now - chrono::Duration::days(30), now)
+            .unwrap();
+        assert_eq!(stats.test_count, results.len() - 1);
+
+        // A database from before summaries gets them built when opened
+        {
+            let txn = storage.db.begin_write().unwrap();
+            txn.delete_table(DAILY_SUMMARIES_TABLE).unwrap();
+            txn.delete_table(WEEKLY_SUMMARIES_TABLE).unwrap();
+            txn.open_table(METADATA_TABLE)
+                .unwrap()
+                .insert(SCHEMA_VERSION_KEY, 1)
+                .unwrap();
+            txn.commit().unwrap();
+        }
+        drop(storage);
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        let stats = storage
+            .get_statistics_by_date_range(now - chrono::Duration::days(30), now)
+            .unwrap();
+        assert_eq!(stats.test_count, results.len() - 1);
+    }
+
+    #[test]
+    fn test_iter_results() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let now = Utc::now();
+        for (minutes, download_mbps, ping_ms) in
+            [(30, 80.0, 20.0), (20, 120.0, 9.0), (10, 95.0, 9.0)]
+        {
+            storage
+                .save_result(&SpeedTestResult {
+                    timestamp: now - chrono::Duration::minutes(minutes),
+                    download_mbps,
+                    ping_ms,
+                    quality: ConnectionQuality::Good,
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        // A record from a newer version is skipped, not an error
+        let txn = storage.db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(RESULTS_TABLE).unwrap();
+            let key = timestamp_key(now - chrono::Duration::minutes(15));
+            table
+                .insert(key.as_slice(), with_layout(999, vec![1, 2, 3]).as_slice())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let downloads =
+            |iter: &mut dyn Iterator<Item = Result<SpeedTestResult, redb::StorageError>>| {
+                iter.map(|result| result.unwrap().download_mbps)
+                    .collect::<Vec<_>>()
+            };
+        assert_eq!(
+            downloads(&mut storage.iter_results().unwrap()),
+            [80.0, 120.0, 95.0]
+        );
+        assert_eq!(
+            downloads(&mut storage.iter_results().unwrap().rev()),
+            [95.0, 120.0, 80.0]
+        );
+        let range = now - chrono::Duration::minutes(25)..now - chrono::Duration::minutes(10);
+        assert_eq!(
+            downloads(&mut storage.iter_results_in(range).unwrap()),
+            [120.0]
+        );
+
+        let fastest = storage.get_fastest_download().unwrap().unwrap();
+        assert_eq!(fastest.download_mbps, 120.0);
+        // Of equal pings the newest
+        let lowest = storage.get_lowest_ping().unwrap().unwrap();
+        assert_eq!(lowest.download_mbps, 95.0);
+        assert_eq!(
+            storage
+                .get_results_by_quality(ConnectionQuality::Good)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_results_at_same_time() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let timestamp = Utc::now();
+        let first = SpeedTestResult {
+            timestamp,
+            download_mbps: 100.0,
+            ..Default::default()
+        };
+        let second = SpeedTestResult {
+            download_mbps: 200.0,
+            ..first.clone()
+        };
+        assert!(storage.save_result(&first).unwrap());
+        assert!(storage.save_result(&second).unwrap());
+        assert!(!storage.save_result(&first).unwrap());
+        assert_eq!(storage.count().unwrap(), 2);
+        assert_eq!(
+            storage
+                .get_results_by_date_range(timestamp, timestamp)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // Importing the same file twice adds nothing the second time
+        let path = temp_dir.path().join("import.json");
+        let imported = SpeedTestResult {
+            timestamp: timestamp - chrono::Duration::hours(1),
+            ..Default::default()
+        };
+        std::fs::write(&path, serde_json::to_string(&vec![imported]).unwrap()).unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(storage.import_from_json(path).unwrap(), 1);
+        assert_eq!(storage.import_from_json(path).unwrap(), 0);
+        assert_eq!(storage.count().unwrap(), 3);
+
+        // A copy under the bare timestamp key of older versions is found by dedupe
+        let txn = storage.db.begin_write().unwrap();
+        {
+            let mut table = txn.open_table(RESULTS_TABLE).unwrap();
+            let value = encode_result(&first).unwrap();
+            table
+                .insert(timestamp_key(timestamp).as_slice(), value.as_slice())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        assert_eq!(storage.count().unwrap(), 4);
+        assert_eq!(storage.dedupe_results().unwrap(), 1);
+        assert_eq!(storage.dedupe_results().unwrap(), 0);
+        assert_eq!(storage.get_statistics().unwrap().test_count, 3);
+
+        // Tags go on the matching result only
+        let tags = vec!["vpn".to_string()];
+        assert!(storage.set_result_tags(&second, tags.clone()).unwrap());
+        assert!(!storage.set_result_tags(&second, tags.clone()).unwrap());
+        let at_once = storage
+            .get_results_by_date_range(timestamp, timestamp)
+            .unwrap();
+        assert_eq!(at_once.iter().filter(|r| r.tags == tags).count(), 1);
+        assert_eq!(at_once.len(), 2);
+
+        storage.delete_result(timestamp).unwrap();
+        assert_eq!(storage.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_test_run_log() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let now = Utc::now();
+        assert!(storage.get_test_runs_since(now).unwrap().is_empty());
+
+        storage
+            .record_test_run(now - chrono::Duration::days(2))
+            .unwrap();
+        storage
+            .record_test_run(now - chrono::Duration::minutes(90))
+            .unwrap();
+        storage
+            .record_test_run(now - chrono::Duration::minutes(10))
+            .unwrap();
+        storage.record_test_run(now).unwrap();
+
+        let last_hour = storage
+            .get_test_runs_since(now - chrono::Duration::hours(1))
+            .unwrap();
+        assert_eq!(last_hour.len(), 2);
+        assert!(last_hour[0] < last_hour[1]);
+
+        // Entries older than a day are pruned on insert
+        let all = storage
+            .get_test_runs_since(now - chrono::Duration::days(7))
+            .unwrap();
+        assert_eq!(all.len(), 3);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        // Without results the minimums are zero rather than a sentinel
+        let stats = storage.get_statistics().unwrap();
+        assert_eq!(stats.test_count, 0);
+        assert_eq!(stats.min_download_mbps, 0.0);
+        assert_eq!(stats.min_ping_ms, 0.0);
+    }
+
+    #[test]
+    fn test_failed_phases_stay_out_of_statistics() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let now = Utc::now();
+        storage
+            .save_result(&SpeedTestResult {
+                timestamp: now - chrono::Duration::seconds(20),
+                download_mbps: 100.0,
+                upload_mbps: 20.0,
+                ping_ms: 10.0,
+                ..Default::default()
+            })
+            .unwrap();
+        // The upload stalled: stored as 0 Mbps, which is no measurement
+        storage
+            .save_result(&SpeedTestResult {
+                timestamp: now - chrono::Duration::seconds(10),
+                download_mbps: 80.0,
+                upload_mbps: 0.0,
+                ping_ms: 30.0,
+                failures: vec![PhaseFailure {
+                    phase: SpeedPhase::Upload,
+                    reason: "transfers stalled".to_string(),
+                }],
+                data_used: Some(DataUsage {
+                    downloaded_bytes: 1_000_000_000,
+                    uploaded_bytes: 0,
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let stats = storage.get_statistics().unwrap();
+        assert_eq!(stats.test_count, 1);
+        assert_eq!(stats.avg_upload_mbps, 20.0);
+        assert_eq!(stats.min_upload_mbps, 20.0);
+        assert_eq!(stats.avg_ping_ms, 10.0);
+        // The data it used still counts
+        assert!((stats.total_data_downloaded_gb - 1.0).abs() < 1e-9);
+
+        let plan: AdvertisedPlan = "100/20".parse().unwrap();
+        let attainment = storage.get_plan_attainment(&plan).unwrap();
+        assert_eq!(attainment.test_count, 1);
+        assert_eq!(attainment.avg_upload_percent, 100.0);
+    }
+
+    #[test]
+    fn test_statistics_match_recount() {
+        use rand::{RngExt, SeedableRng};
+
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let now = Utc::now();
+        let recount = |results: &[SpeedTestResult]| {
+            let mut summary = PeriodSummary::default();
+            for result in results {
+                summary.add(result);
+            }
+            summary.statistics()
+        };
+        let same = |stats: &TestStatistics, wanted: &TestStatistics| {
+            assert_eq!(stats.test_count, wanted.test_count);
+            assert!((stats.avg_download_mbps - wanted.avg_download_mbps).abs() < 1e-6);
+            assert!((stats.avg_upload_mbps - wanted.avg_upload_mbps).abs() < 1e-6);
+            assert!((stats.avg_ping_ms - wanted.avg_ping_ms).abs() < 1e-6);
+            assert_eq!(stats.min_download_mbps, wanted.min_download_mbps);
+            assert_eq!(stats.max_download_mbps, wanted.max_download_mbps);
+            assert_eq!(stats.min_upload_mbps, wanted.min_upload_mbps);
+            assert_eq!(stats.max_upload_mbps, wanted.max_upload_mbps);
+            assert_eq!(stats.min_ping_ms, wanted.min_ping_ms);
+            assert_eq!(stats.max_ping_ms, wanted.max_ping_ms);
+            if wanted.test_count > 0 {
+                assert_eq!(stats.first_test, wanted.first_test);
+                assert_eq!(stats.last_test, wanted.last_test);
+            }
+        };
+
+        // Random saves, saves at taken times and deletes, some of them old enough to be
+        // pruned, checked against statistics counted from the stored results
+        let mut rng = rand::rngs::StdRng::seed_from_u64(4661);
+        let mut saved: Vec<DateTime<Utc>> = Vec::new();
+        for step in 0..200 {
+            match rng.random_range(0..10) {
+                0..=5 => {
+                    let timestamp =
+                        now - chrono::Duration::minutes(rng.random_range(0..60 * 24 * 35));
+                    storage
+                        .save_result(&SpeedTestResult {
+                            timestamp,
+                            download_mbps: rng.random_range(1.0..900.0),
+                            upload_mbps: rng.random_range(1.0..100.0),
+                            ping_ms: rng.random_range(1.0..200.0),
+                            ..Default::default()
+                        })
+                        .unwrap();
+                    saved.push(timestamp);
+                }
+                6 | 7 if !saved.is_empty() => {
+                    let timestamp = saved[rng.random_range(0..saved.len())];
+                    storage
+                        .save_result(&SpeedTestResult {
+                            timestamp,
+                            download_mbps: rng.random_range(1.0..900.0),
+                            upload_mbps: rng.random_range(1.0..100.0),
+                            ping_ms: rng.random_range(1.0..200.0),
+                            ..Default::default()
+                        })
+                        .unwrap();
+                }
+                _ if !saved.is_empty() => {
+                    let timestamp = saved.swap_remove(rng.random_range(0..saved.len()));
+                    storage.delete_result(timestamp).unwrap();
+                }
+                _ => {}
+            }
+
+            if step % 10 == 0 {
+                let stored = storage.get_all_results().unwrap();
+                same(&storage.get_statistics().unwrap(), &recount(&stored));
+
+                // Ranges within the retention period, away from what pruning removed
+                let start = now - chrono::Duration::minutes(rng.random_range(0..60 * 24 * 28));
+                let end = start + chrono::Duration::minutes(rng.random_range(0..60 * 24 * 28));
+                let inside: Vec<SpeedTestResult> = stored
+                    .into_iter()
+                    .filter(|r| r.timestamp >= start && r.timestamp <= end)
+                    .collect();
+                same(
+                    &storage.get_statistics_by_date_range(start, end).unwrap(),
+                    &recount(&inside),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_attainment() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        let plan: AdvertisedPlan = "100/20".parse().unwrap();
+
+        // Noon today, so all offsets below stay on their calendar day
+        let now = Utc::now()
+            .date_naive()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_utc();
+        // Two results today (a second apart) and one yesterday
+        for (offset, download, upload) in [
+            (chrono::Duration::days(1), 90.0, 20.0),
+            (chrono::Duration::seconds(1), 50.0, 10.0),
+            (chrono::Duration::zero(), 100.0, 18.0),
+        ] {
+            storage
+                .save_result(&SpeedTestResult {
+                    timestamp: now - offset,
+                    download_mbps: download,
+                    upload_mbps: upload,
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let stats = storage.get_plan_attainment(&plan).unwrap();
+        assert_eq!(stats.test_count, 3);
+        assert_eq!(stats.avg_download_percent, 80.0);
+        assert_eq!(stats.below_alert_count, 1);
+        assert_eq!(stats.daily.len(), 2);
+        assert!(stats.daily[0].date < stats.daily[1].date);
+        assert_eq!(stats.daily[1].test_count, 2);
+        assert_eq!(stats.daily[1].avg_download_percent, 75.0);
+    }
+
+    #[test]
+    fn test_congestion_stats() {
+        use chrono::TimeZone;
+
+        let at = |day: u32, hour: u32| {
+            chrono::Local
+                .with_ymd_and_hms(2026, 10, day, hour, 30, 0)
+                .single()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        // Fast mornings, slow Monday evenings, a failed test that must not count
+        let results: Vec<SpeedTestResult> = [
+            (12, 9, 300.0, ConnectionQuality::Excellent),
+            (13, 9, 280.0, ConnectionQuality::Excellent),
+            (12, 21, 60.0, ConnectionQuality::Average),
+            (12, 21, 40.0, ConnectionQuality::Poor),
+            (13, 21, 0.0, ConnectionQuality::Failed),
+        ]
+        .into_iter()
+        .map(|(day, hour, download_mbps, quality)| SpeedTestResult {
+            timestamp: at(day, hour),
+            download_mbps,
+            ping_ms: 10.0,
+            quality,
+            ..Default::default()
+        })
+        .collect();
+
+        let stats = CongestionStats::from_results(&results);
+        assert_eq!(stats.test_count, 4);
+        assert_eq!(stats.avg_download_mbps, 170.0);
+        assert_eq!(stats.hourly.len(), 2);
+        assert_eq!(stats.weekly.len(), 3);
+
+        let evening = stats.slot(chrono::Weekday::Mon, 21).unwrap();
+        assert_eq!(evening.test_count, 2);
+        assert_eq!(evening.avg_download_mbps, 50.0);
+        assert!(stats.slot(chrono::Weekday::Tue, 21).is_none());
+        assert_eq!(stats.slowest_hour().unwrap().hour, 21);
+        assert_eq!(stats.fastest_hour().unwrap().avg_download_mbps, 290.0);
+        assert!(stats.hourly.iter().all(|slot| slot.weekday.is_none()));
+
+        assert_eq!(CongestionStats::from_results(&[]).test_count, 0);
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert!(storage.get_recent_aggregates(5).unwrap().is_empty());
+
+        let runs = vec![
+            SpeedTestResult {
+                timestamp: Utc::now() - chrono::Duration::seconds(30),
+                download_mbps: 90.0,
+                ..Default::default()
+            },
+            SpeedTestResult {
+                download_mbps: 110.0,
+                ..Default::default()
+            },
+        ];
+        for run in &runs {
+            storage.save_result(run).unwrap();
+        }
+        storage
+            .save_aggregate(&AggregateResult::from_runs(&runs).unwrap())
+            .unwrap();
 
-            // Recalculate statistics
-            self.recalculate_statistics()?;
+        let aggregates = storage.get_recent_aggregates(5).unwrap();
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].run_count, 2);
+        assert_eq!(aggregates[0].median.download_mbps, 100.0);
+        // The aggregate is not counted as another test
+        assert_eq!(storage.get_statistics().unwrap().test_count, 2);
+    }
+
+    #[test]
+    fn test_ping_samples() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        let now = Utc::now();
+
+        for (seconds_ago, latency_ms) in [(3, Some(12.5)), (2, None), (1, Some(14.0))] {
+            storage
+                .save_ping_sample(&PingSample {
+                    timestamp: now - chrono::Duration::seconds(seconds_ago),
+                    target: "1.1.1.1:443".to_string(),
+                    latency_ms,
+                })
+                .unwrap();
         }
 
-        Ok(())
+        let samples = storage
+            .get_ping_samples_since(now - chrono::Duration::seconds(2))
+            .unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].latency_ms, None);
+        assert_eq!(samples[1].latency_ms, Some(14.0));
+        // Ping samples are not speed tests
+        assert_eq!(storage.count().unwrap(), 0);
     }
 
-    /// Export history to JSON
-    pub fn export_to_json(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let results = self.get_all_results()?;
-        let json = serde_json::to_string_pretty(&results)?;
-        std::fs::write(path, json)?;
-        Ok(())
+    #[test]
+    fn test_routes() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert_eq!(storage.get_latest_route("8.8.8.8").unwrap(), None);
+
+        let route = |target: &str, seconds_ago: i64, last_hop: &str| RouteSnapshot {
+            timestamp: Utc::now() - chrono::Duration::seconds(seconds_ago),
+            target: target.to_string(),
+            hops: vec![Some("192.168.1.1".parse().unwrap()), last_hop.parse().ok()],
+        };
+        storage
+            .save_route(&route("8.8.8.8", 30, "10.0.0.1"))
+            .unwrap();
+        storage
+            .save_route(&route("8.8.8.8", 20, "10.0.0.2"))
+            .unwrap();
+        storage
+            .save_route(&route("1.1.1.1", 10, "10.0.0.3"))
+            .unwrap();
+
+        let latest = storage.get_latest_route("8.8.8.8").unwrap().unwrap();
+        assert_eq!(latest.hops[1], Some("10.0.0.2".parse().unwrap()));
     }
 
-    /// Import history from JSON
-    pub fn import_from_json(&self, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
-        let json = std::fs::read_to_string(path)?;
-        let results: Vec<SpeedTestResult> = serde_json::from_str(&json)?;
+    #[test]
+    fn test_best_server() {
+        use crate::modules::types::{
+            ServerCapabilities, ServerProtocol, ServerProvider, TestServer,
+        };
 
-        let count = results.len();
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert!(storage.get_best_server().unwrap().is_none());
 
-        for result in results {
-            self.save_result(&result)?;
-        }
+        let cached = |name: &str, latency_ms: f64| CachedServer {
+            server: TestServer {
+                name: name.to_string(),
+                url: "https://speed.cloudflare.com".to_string(),
+                location: "Global".to_string(),
+                distance_km: Some(0.0),
+                latency_ms: Some(latency_ms),
+                provider: ServerProvider::Cloudflare,
+                protocol: ServerProtocol::Cloudflare,
+                capabilities: ServerCapabilities {
+                    supports_download: true,
+                    supports_upload: true,
+                    supports_latency: true,
+                    max_test_size_mb: 1000,
+                    geographic_weight: 1.0,
+                },
+                quality_score: Some(100.0),
+                country_code: None,
+                city: None,
+                is_backup: false,
+                endpoints: None,
+            },
+            latency_ms,
+            selected_at: Utc::now(),
+        };
+        storage.save_best_server(&cached("First", 20.0)).unwrap();
+        storage.save_best_server(&cached("Second", 12.0)).unwrap();
 
-        Ok(count)
+        // Only the latest selection is kept
+        let best = storage.get_best_server().unwrap().unwrap();
+        assert_eq!(best.server.name, "Second");
+        assert_eq!(best.latency_ms, 12.0);
     }
 
-    /// Get database statistics
-    pub fn get_db_stats(&self) -> Result<DbStats, Box<dyn std::error::Error>> {
-        let db_path = Self::get_db_path()?;
-        let size_on_disk = std::fs::metadata(&db_path).map(|m| m.len()).unwrap_or(0);
-        let results_count = self.count()?;
+    #[test]
+    fn test_diagnostics() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert!(storage.get_recent_diagnostics(5).unwrap().is_empty());
 
-        Ok(DbStats {
-            size_on_disk,
-            results_count,
-            db_path: db_path.to_string_lossy().to_string(),
-        })
-    }
+        let record = |days_ago: i64, dns_response_time_ms: f64| DiagnosticsRecord {
+            timestamp: Utc::now() - chrono::Duration::days(days_ago),
+            diagnostics: NetworkDiagnostics {
+                gateway_ip: None,
+                dns_servers: vec![],
+                dns_response_time_ms,
+                dns_lookups: vec![],
+                dns_records: vec![],
+                route_hops: vec![],
+                is_ipv6_available: days_ago == 0,
+                connection_type: Some("Ethernet".to_string()),
+                network_interface: None,
+                tls_checks: vec![],
+                resolver_benchmarks: vec![],
+                dns_security: Default::default(),
+                routes: vec![],
+                server_networks: vec![],
+                latency_baseline: Default::default(),
+                clock_check: None,
+                happy_eyeballs: vec![],
+                windows_stack: None,
+            },
+        };
+        storage.save_diagnostics(&record(40, 50.0)).unwrap();
+        storage.save_diagnostics(&record(1, 20.0)).unwrap();
+        storage.save_diagnostics(&record(0, 15.0)).unwrap();
 
-    /// Optimize database
-    /// Note: redb's compact() requires &mut self which is not available through &self.
-    /// The database already manages its storage efficiently with ACID transactions.
-    pub fn optimize(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // redb handles storage management internally; no explicit optimization needed.
-        Ok(())
+        // The run from before the retention period is gone
+        let records = storage.get_recent_diagnostics(5).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].diagnostics.dns_response_time_ms, 15.0);
+        assert!(records[0].diagnostics.is_ipv6_available);
+        assert_eq!(records[1].diagnostics.dns_response_time_ms, 20.0);
+        assert_eq!(storage.get_recent_diagnostics(1).unwrap().len(), 1);
+        // Diagnostics are not speed tests
+        assert_eq!(storage.count().unwrap(), 0);
+
+        let analysis = FullAnalysisResult::new(
+            SpeedTestResult {
+                download_mbps: 250.0,
+                ..Default::default()
+            },
+            records[0].diagnostics.clone(),
+        );
+        storage.save_full_analysis(&analysis).unwrap();
+        let analyses = storage.get_recent_full_analyses(5).unwrap();
+        assert_eq!(analyses.len(), 1);
+        assert_eq!(analyses[0].speed.download_mbps, 250.0);
+        assert_eq!(analyses[0].correlations, analysis.correlations);
     }
 
-    /// Get fastest recorded download speed
-    pub fn get_fastest_download(
-        &self,
-    ) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let results = self.get_all_results()?;
-        Ok(results.into_iter().max_by(|a, b| {
-            a.download_mbps
-                .partial_cmp(&b.download_mbps)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        }))
+    #[test]
+    fn test_baselines() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert!(storage.get_active_baseline().unwrap().is_none());
+
+        let baseline = |name: &str, download_mbps: f64| Baseline {
+            name: name.to_string(),
+            created_at: Utc::now(),
+            multi_stream: SpeedTestResult {
+                download_mbps,
+                ..Default::default()
+            },
+            single_stream: SpeedTestResult::default(),
+        };
+        storage.save_baseline(&baseline("office", 500.0)).unwrap();
+        storage.save_baseline(&baseline("home", 100.0)).unwrap();
+        // Saving again under the same name replaces it
+        storage.save_baseline(&baseline("home", 120.0)).unwrap();
+
+        let names: Vec<String> = storage
+            .get_baselines()
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["home", "office"]);
+        let active = storage.get_active_baseline().unwrap().unwrap();
+        assert_eq!(active.name, "home");
+        assert_eq!(active.multi_stream.download_mbps, 120.0);
+
+        storage.set_active_baseline("office").unwrap();
+        assert_eq!(
+            storage.get_active_baseline().unwrap().unwrap().name,
+            "office"
+        );
+        assert!(storage.set_active_baseline("cabin").is_err());
+        // Baselines are not speed tests
+        assert_eq!(storage.count().unwrap(), 0);
     }
 
-    /// Get fastest recorded upload speed
-    pub fn get_fastest_upload(
-        &self,
-    ) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let results = self.get_all_results()?;
-        Ok(results.into_iter().max_by(|a, b| {
-            a.upload_mbps
-                .partial_cmp(&b.upload_mbps)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        }))
+    #[test]
+    fn test_pending_alerts() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert!(storage.get_pending_alerts().unwrap().is_empty());
+
+        let now = Utc::now();
+        let alert = |minutes_ago: i64, kind: AlertKind| MonitorAlert {
+            timestamp: now - chrono::Duration::minutes(minutes_ago),
+            kind,
+            message: format!("{} minutes ago", minutes_ago),
+        };
+        storage
+            .queue_alert(&alert(5, AlertKind::BelowPlan))
+            .unwrap();
+        storage.queue_alert(&alert(50, AlertKind::Outage)).unwrap();
+
+        let pending = storage.get_pending_alerts().unwrap();
+        assert_eq!(
+            pending,
+            vec![alert(50, AlertKind::Outage), alert(5, AlertKind::BelowPlan)]
+        );
+
+        storage
+            .clear_pending_alerts(now - chrono::Duration::minutes(50))
+            .unwrap();
+        assert_eq!(
+            storage.get_pending_alerts().unwrap(),
+            vec![alert(5, AlertKind::BelowPlan)]
+        );
+        storage.clear_pending_alerts(now).unwrap();
+        assert!(storage.get_pending_alerts().unwrap().is_empty());
     }
 
-    /// Get lowest recorded ping
-    pub fn get_lowest_ping(&self) -> Result<Option<SpeedTestResult>, Box<dyn std::error::Error>> {
-        let results = self.get_all_results()?;
-        Ok(results.into_iter().min_by(|a, b| {
-            a.ping_ms
-                .partial_cmp(&b.ping_ms)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        }))
+    #[test]
+    fn test_host_labels_and_legacy_records() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        storage
+            .save_result(&SpeedTestResult {
+                timestamp: Utc::now() - chrono::Duration::seconds(30),
+                download_mbps: 80.0,
+                host_label: Some("nas".to_string()),
+                site: Some("Berlin".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        // A record written before host labels existed
+        insert_record(&storage, 1, LAYOUT_1_RECORD);
+
+        let results = storage.get_all_results().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].host_label.as_deref(), Some("nas"));
+        assert_eq!(results[0].site.as_deref(), Some("Berlin"));
+        assert_eq!(results[1].download_mbps, 120.0);
+        assert_eq!(results[1].latency_stats.as_ref().unwrap().sample_count, 3);
+        assert_eq!(results[1].host_label, None);
     }
 
-    /// Manually cleanup old records (older than retention period)
-    /// Returns the number of records deleted
-    pub fn cleanup_old_records_manual(&self) -> Result<usize, Box<dyn std::error::Error>> {
-        // Calculate cutoff timestamp (30 days ago)
-        let cutoff = Utc::now() - chrono::Duration::days(RETENTION_DAYS);
-        let cutoff_nanos = cutoff.timestamp_nanos_opt().unwrap_or_default();
+    #[test]
+    fn test_records_from_before_failovers_and_failures() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
 
-        // Collect keys to delete
-        let mut keys_to_delete = Vec::new();
+        // Written after host labels but before failovers
+        insert_record(&storage, 2, LAYOUT_2_RECORD);
+        // Written after failovers but before failures
+        insert_record(&storage, 3, LAYOUT_3_RECORD);
 
-        {
-            let txn = self.db.begin_read()?;
-            let table = match txn.open_table(RESULTS_TABLE) {
-                Ok(t) => t,
-                Err(redb::TableError::TableDoesNotExist(_)) => return Ok(0),
-                Err(e) => return Err(e.into()),
-            };
+        let results = storage.get_all_results().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].download_mbps, 95.0);
+        assert_eq!(results[1].host_label.as_deref(), Some("nas"));
+        assert_eq!(results[1].site.as_deref(), Some("Berlin"));
+        assert!(results[1].failovers.is_empty());
+        assert_eq!(results[0].download_mbps, 90.0);
+        assert_eq!(
+            results[0].failovers,
+            vec![ServerFailover {
+                phase: SpeedPhase::Upload,
+                failed_server: "a".to_string(),
+                fallback_server: "b".to_string(),
+            }]
+        );
+        assert!(results[0].failures.is_empty());
+    }
 
-            for item in table.iter()? {
-                let (_, value) = item?;
-
-                // Deserialize to check timestamp
-                if let Ok(result) = postcard::from_bytes::<SpeedTestResult>(value.value()) {
-                    let result_nanos = result.timestamp.timestamp_nanos_opt().unwrap_or_default();
-
-                    if result_nanos < cutoff_nanos {
-                        keys_to_delete.push(
-                            result
-                                .timestamp
-                                .timestamp_nanos_opt()
-                                .unwrap_or_default()
-                                .to_be_bytes(),
-                        );
-                    }
-                }
-            }
+    #[test]
+    fn test_monthly_data_usage() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+
+        let used = |downloaded_bytes, uploaded_bytes| {
+            Some(DataUsage {
+                downloaded_bytes,
+                uploaded_bytes,
+            })
+        };
+        let now = Utc::now();
+        for (timestamp, data_used) in [
+            (now - chrono::Duration::seconds(20), used(300, 30)),
+            (now - chrono::Duration::seconds(10), used(200, 20)),
+            // Results from before the byte counters add nothing
+            (now, None),
+            (now - chrono::Duration::days(62), used(1_000, 100)),
+        ] {
+            storage
+                .save_result(&SpeedTestResult {
+                    timestamp,
+                    data_used,
+                    ..Default::default()
+                })
+                .unwrap();
         }
 
-        let deleted_count = keys_to_delete.len();
+        let this_month = storage.get_data_usage_for_month(now).unwrap();
+        assert_eq!(this_month.test_count, 2);
+        assert_eq!(this_month.data_used.total(), 550);
 
-        // Delete old records
-        if deleted_count > 0 {
-            let txn = self.db.begin_write()?;
-            {
-                let mut table = txn.open_table(RESULTS_TABLE)?;
-                for key in &keys_to_delete {
-                    table.remove(key.as_slice())?;
-                }
-            }
-            txn.commit()?;
+        // The old month outlives its results, which retention has removed
+        let months = storage.get_monthly_data_usage().unwrap();
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0], this_month);
+        assert_eq!(months[1].data_used.total(), 1_100);
+        assert_eq!(storage.count().unwrap(), 3);
 
-            // Recalculate statistics
-            self.recalculate_statistics()?;
-        }
+        let empty = storage
+            .get_data_usage_for_month(now + chrono::Duration::days(62))
+            .unwrap();
+        assert_eq!(empty.test_count, 0);
+    }
 
-        Ok(deleted_count)
+    #[test]
+    fn test_decode_record_from_before_endpoints() {
+        let decoded = decode_result_layout(5, LAYOUT_5_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 70.0);
+        assert_eq!(
+            decoded.data_used,
+            Some(DataUsage {
+                downloaded_bytes: 1_000,
+                uploaded_bytes: 100,
+            })
+        );
+        assert!(decoded.endpoints.is_empty());
     }
 
-    /// Get the retention period in days
-    pub const fn get_retention_days() -> i64 {
-        RETENTION_DAYS
+    #[test]
+    fn test_decode_record_from_before_engine() {
+        let decoded = decode_result_layout(6, LAYOUT_6_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 65.0);
+        assert_eq!(decoded.endpoints.len(), 1);
+        assert_eq!(decoded.endpoints[0].host, "192.168.1.1");
+        assert_eq!(decoded.endpoints[0].latency, None);
+        assert_eq!(decoded.engine, None);
     }
 
-    /// Get speed trends (compares recent results to historical average)
-    pub fn get_speed_trends(&self) -> Result<SpeedTrends, Box<dyn std::error::Error>> {
-        let all_stats = self.get_statistics()?;
-        let recent_results = self.get_recent_results(10)?;
+    #[test]
+    fn test_decode_record_from_before_country() {
+        let decoded = decode_result_layout(7, LAYOUT_7_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 60.0);
+        let engine = decoded.engine.unwrap();
+        assert_eq!(engine.download_connections, 8);
+        assert_eq!(engine.upload_connections, 4);
+        assert_eq!(decoded.country, None);
+    }
 
-        if recent_results.is_empty() {
-            return Ok(SpeedTrends::default());
-        }
+    #[test]
+    fn test_decode_record_from_before_link_stats() {
+        let decoded = decode_result_layout(8, LAYOUT_8_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 55.0);
+        assert_eq!(decoded.country.as_deref(), Some("Germany"));
+        assert_eq!(decoded.link_stats, None);
+    }
 
-        let recent_avg_download = recent_results.iter().map(|r| r.download_mbps).sum::<f64>()
-            / recent_results.len() as f64;
-        let recent_avg_upload =
-            recent_results.iter().map(|r| r.upload_mbps).sum::<f64>() / recent_results.len() as f64;
-        let recent_avg_ping =
-            recent_results.iter().map(|r| r.ping_ms).sum::<f64>() / recent_results.len() as f64;
+    #[test]
+    fn test_decode_record_from_before_other_traffic() {
+        let decoded = decode_result_layout(9, LAYOUT_9_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 50.0);
+        assert_eq!(
+            decoded.link_stats,
+            Some(LinkStats {
+                interface: "eth0".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(decoded.other_traffic, None);
+    }
 
-        let download_trend = if all_stats.avg_download_mbps > 0.0 {
-            ((recent_avg_download - all_stats.avg_download_mbps) / all_stats.avg_download_mbps)
-                * 100.0
-        } else {
-            0.0
-        };
+    #[test]
+    fn test_decode_record_from_before_tags() {
+        let decoded = decode_result_layout(10, LAYOUT_10_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 45.0);
+        assert_eq!(
+            decoded.other_traffic,
+            Some(OtherTraffic {
+                download_mbps: Some(12.0),
+                upload_mbps: None,
+                lan_devices: Some(2),
+            })
+        );
+        assert!(decoded.tags.is_empty());
+    }
 
-        let upload_trend = if all_stats.avg_upload_mbps > 0.0 {
-            ((recent_avg_upload - all_stats.avg_upload_mbps) / all_stats.avg_upload_mbps) * 100.0
-        } else {
-            0.0
-        };
+    #[test]
+    fn test_decode_record_from_before_transport() {
+        let decoded = decode_result_layout(11, LAYOUT_11_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 40.0);
+        assert_eq!(decoded.tags, vec!["wired".to_string()]);
+        assert_eq!(decoded.transport, None);
+    }
 
-        let ping_trend = if all_stats.avg_ping_ms > 0.0 {
-            ((recent_avg_ping - all_stats.avg_ping_ms) / all_stats.avg_ping_ms) * 100.0
-        } else {
-            0.0
-        };
+    #[test]
+    fn test_decode_record_from_before_websocket_latency() {
+        let decoded = decode_result_layout(12, LAYOUT_12_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 35.0);
+        assert_eq!(
+            decoded.transport,
+            Some(TransportInfo {
+                alpn: Some("h2".to_string()),
+                tls_version: Some("TLS 1.3".to_string()),
+            })
+        );
+        assert_eq!(decoded.websocket_latency, None);
+    }
 
-        Ok(SpeedTrends {
-            download_trend_percent: download_trend,
-            upload_trend_percent: upload_trend,
-            ping_trend_percent: ping_trend,
-            improving: download_trend > 0.0 && upload_trend > 0.0 && ping_trend < 0.0,
-        })
+    #[test]
+    fn test_decode_record_from_before_one_way_delay() {
+        let decoded = decode_result_layout(13, LAYOUT_13_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 30.0);
+        assert_eq!(
+            decoded.websocket_latency,
+            Some(WebSocketLatency {
+                setup_ms: 40.0,
+                rtt_ms: 11.0,
+                rtt_stats: LatencyStats::from_samples(&[10.0, 11.0, 12.0]).unwrap(),
+                unanswered: 0,
+            })
+        );
+        assert_eq!(decoded.one_way_delay, None);
+        assert_eq!(decoded.jitter_method, JitterMethod::StandardDeviation);
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
-pub struct DbStats {
-    pub size_on_disk: u64,
-    pub results_count: usize,
-    pub db_path: String,
-}
+    #[test]
+    fn test_decode_record_from_before_jitter_method() {
+        let decoded = decode_result_layout(14, LAYOUT_14_RECORD).unwrap();
+        assert_eq!(decoded.download_mbps, 25.0);
+        assert_eq!(decoded.jitter_ms, 1.5);
+        assert_eq!(
+            decoded.one_way_delay,
+            Some(OneWayDelay {
+                upstream_ms: 7.0,
+                downstream_ms: 5.0,
+                uncertainty_ms: 1.5,
+                samples: 20,
+            })
+        );
+        assert_eq!(decoded.jitter_method, JitterMethod::Rfc3550);
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-#[allow(dead_code)]
-pub struct SpeedTrends {
-    pub download_trend_percent: f64,
-    pub upload_trend_percent: f64,
-    pub ping_trend_percent: f64,
-    pub improving: bool,
-}
+        // ndt7 results held the server's RTT variance in every layout
+        assert_eq!(
+            legacy_jitter_method("Frankfurt (M-Lab)", JitterMethod::Rfc3550),
+            JitterMethod::RttVariance
+        );
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::modules::types::ConnectionQuality;
-    use tempfile::tempdir;
+    #[test]
+    fn test_decode_baseline_record() {
+        let decoded = decode_result_layout(0, BASELINE_RECORD).unwrap();
+        assert_eq!(decoded.timestamp.to_rfc3339(), "2025-03-01T12:00:00+00:00");
+        assert_eq!(decoded.download_mbps, 80.0);
+        assert_eq!(decoded.upload_mbps, 20.0);
+        assert_eq!(decoded.server_location, "Frankfurt");
+        assert_eq!(decoded.quality, ConnectionQuality::Good);
+        assert_eq!(decoded.isp.as_deref(), Some("Example ISP"));
+        assert_eq!(decoded.latency_stats, None);
+
+        // Every later layout needs the latency statistics that baseline records lack
+        assert!(decode_result_layout(1, BASELINE_RECORD).is_err());
+    }
 
     #[test]
-    fn test_storage_creation() {
+    fn test_migrate_unversioned_database() {
         let temp_dir = tempdir().unwrap();
         let db_path = temp_dir.path().join("test_db");
-        let storage = HistoryStorage::new_with_path(db_path);
-        assert!(storage.is_ok());
+
+        // A database from before schema versions: bare records in older layouts,
+        // including what released versions wrote
+        let baseline = decode_result_layout(0, BASELINE_RECORD).unwrap();
+        let labelled = decode_result_layout(2, LAYOUT_2_RECORD).unwrap();
+        let current = SpeedTestResult {
+            timestamp: Utc::now() - chrono::Duration::seconds(30),
+            download_mbps: 90.0,
+            ..Default::default()
+        };
+        let aggregate = AggregateResult::from_runs(std::slice::from_ref(&current)).unwrap();
+        {
+            let db = redb::Database::create(&db_path).unwrap();
+            let txn = db.begin_write().unwrap();
+            {
+                let mut table = txn.open_table(RESULTS_TABLE).unwrap();
+                for (result, bytes) in [
+                    (&baseline, BASELINE_RECORD.to_vec()),
+                    (&labelled, LAYOUT_2_RECORD.to_vec()),
+                    (&current, postcard::to_stdvec(&current).unwrap()),
+                ] {
+                    let key = result.timestamp.timestamp_nanos_opt().unwrap();
+                    table
+                        .insert(key.to_be_bytes().as_slice(), bytes.as_slice())
+                        .unwrap();
+                }
+                table
+                    .insert([0u8; 8].as_slice(), [0xff].as_slice())
+                    .unwrap();
+
+                let mut table = txn.open_table(AGGREGATES_TABLE).unwrap();
+                table
+                    .insert(
+                        [1u8; 8].as_slice(),
+                        postcard::to_stdvec(&aggregate).unwrap().as_slice(),
+                    )
+                    .unwrap();
+            }
+            txn.commit().unwrap();
+        }
+
+        let storage = HistoryStorage::new_with_path(db_path.clone()).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), SCHEMA_VERSION);
+        let results = storage.get_all_results().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].download_mbps, 90.0);
+        assert_eq!(results[1].host_label.as_deref(), Some("nas"));
+        assert_eq!(results[2].download_mbps, 80.0);
+        assert_eq!(results[2].isp.as_deref(), Some("Example ISP"));
+        assert_eq!(results[2].latency_stats, None);
+        // The unreadable record is gone rather than skipped on every read
+        assert_eq!(storage.count().unwrap(), 3);
+        let aggregates = storage.get_recent_aggregates(5).unwrap();
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].median.download_mbps, 90.0);
+
+        // Opening it again doesn't migrate twice
+        drop(storage);
+        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        assert_eq!(storage.get_all_results().unwrap().len(), 3);
     }
 
     #[test]
-    fn test_save_and_retrieve() {
+    fn test_backup_and_restore() {
         let temp_dir = tempdir().unwrap();
-        let db_path = temp_dir.path().join("test_db");
-        let storage = HistoryStorage::new_with_path(db_path).unwrap();
+        let source = HistoryStorage::new_with_path(temp_dir.path().join("source_db")).unwrap();
+        let now = Utc::now();
+        let runs: Vec<SpeedTestResult> = [100.0, 120.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, download_mbps)| SpeedTestResult {
+                timestamp: now - chrono::Duration::seconds(10 * i as i64),
+                download_mbps,
+                data_used: Some(DataUsage {
+                    downloaded_bytes: 1_000,
+                    uploaded_bytes: 100,
+                }),
+                ..Default::default()
+            })
+            .collect();
+        for result in &runs {
+            source.save_result(result).unwrap();
+        }
+        source
+            .save_aggregate(&AggregateResult::from_runs(&runs).unwrap())
+            .unwrap();
+        source
+            .save_ping_sample(&PingSample {
+                timestamp: now,
+                target: "1.1.1.1:443".to_string(),
+                latency_ms: Some(12.5),
+            })
+            .unwrap();
+        source
+            .save_route(&RouteSnapshot {
+                timestamp: now,
+                target: "8.8.8.8".to_string(),
+                hops: vec![Some("192.168.1.1".parse().unwrap())],
+            })
+            .unwrap();
+        source
+            .save_baseline(&Baseline {
+                name: "home".to_string(),
+                created_at: now,
+                multi_stream: runs[0].clone(),
+                single_stream: runs[1].clone(),
+            })
+            .unwrap();
 
-        let result = SpeedTestResult {
-            timestamp: Utc::now(),
-            download_mbps: 100.0,
-            upload_mbps: 50.0,
-            ping_ms: 10.0,
-            jitter_ms: 1.0,
-            packet_loss_percent: 0.0,
-            server_location: "Test Server".to_string(),
-            server_ip: None,
-            client_ip: None,
-            quality: ConnectionQuality::Excellent,
-            test_duration_seconds: 10.0,
-            isp: None,
-        };
+        let archive = temp_dir.path().join("history.zip");
+        let (backup, digest) = source.backup(&archive).unwrap();
+        assert_eq!(backup.results.len(), 2);
+        assert_eq!(digest.len(), 64);
 
-        assert!(storage.save_result(&result).is_ok());
+        let target = HistoryStorage::new_with_path(temp_dir.path().join("target_db")).unwrap();
+        let (restored, duplicates) = target.restore(&archive).unwrap();
+        assert_eq!(restored.format_version, BACKUP_FORMAT_VERSION);
+        assert_eq!(duplicates, 0);
+        // Restoring twice doesn't duplicate anything
+        assert_eq!(target.restore(&archive).unwrap().1, 2);
 
-        let results = storage.get_recent_results(1).unwrap();
-        assert_eq!(results.len(), 1);
+        let results = target.get_all_results().unwrap();
+        assert_eq!(results.len(), 2);
         assert_eq!(results[0].download_mbps, 100.0);
+        assert_eq!(target.get_recent_aggregates(5).unwrap().len(), 1);
+        assert_eq!(
+            target.get_ping_samples_since(DateTime::UNIX_EPOCH).unwrap(),
+            backup.ping_samples
+        );
+        assert!(target.get_latest_route("8.8.8.8").unwrap().is_some());
+        assert_eq!(
+            target.get_monthly_data_usage().unwrap(),
+            source.get_monthly_data_usage().unwrap()
+        );
+        assert_eq!(target.get_statistics().unwrap().test_count, 2);
+        // Baselines come along, which one is active is up to this machine
+        assert_eq!(target.get_baselines().unwrap().len(), 1);
+        assert!(target.get_active_baseline().unwrap().is_none());
     }
 
     #[test]
-    fn test_statistics() {
+    fn test_restore_rejects_damaged_backup() {
+        let temp_dir = tempdir().unwrap();
+        let storage = HistoryStorage::new_with_path(temp_dir.path().join("test_db")).unwrap();
+        storage.save_result(&SpeedTestResult::default()).unwrap();
+        let archive = temp_dir.path().join("history.zip");
+        let (backup, digest) = storage.backup(&archive).unwrap();
+
+        // Same checksum, edited figures
+        let mut edited = backup.clone();
+        edited.results[0].download_mbps = 1_000.0;
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("history.json", options).unwrap();
+        zip.write_all(serde_json::to_string_pretty(&edited).unwrap().as_bytes())
+            .unwrap();
+        zip.start_file("SHA256SUMS", options).unwrap();
+        zip.write_all(format!("{}  history.json\n", digest).as_bytes())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let error = storage.restore(&archive).unwrap_err().to_string();
+        assert!(
+            error.contains("does not match its SHA-256 checksum"),
+            "{}",
+            error
+        );
+
+        std::fs::write(&archive, b"not a zip").unwrap();
+        assert!(storage.restore(&archive).is_err());
+        assert_eq!(storage.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_database_moves_from_legacy_location() {
+        let temp_dir = tempdir().unwrap();
+        let legacy_dir = temp_dir.path().join("config");
+        std::fs::create_dir_all(&legacy_dir).unwrap();
+        let legacy = legacy_dir.join(DB_NAME);
+        {
+            let storage = HistoryStorage::new_with_path(legacy.clone()).unwrap();
+            storage.save_result(&SpeedTestResult::default()).unwrap();
+        }
+
+        let data_dir = temp_dir.path().join("data").join("netrunner");
+        let path = prepare_db_path(&data_dir, Some(&legacy)).unwrap();
+        assert_eq!(path, data_dir.join(DB_NAME));
+        assert!(!legacy.exists());
+        let storage = HistoryStorage::new_with_path(path.clone()).unwrap();
+        assert_eq!(storage.count().unwrap(), 1);
+        drop(storage);
+
+        // A database already in the data directory is never replaced
+        std::fs::write(&legacy, b"stale").unwrap();
+        prepare_db_path(&data_dir, Some(&legacy)).unwrap();
+        assert!(legacy.exists());
+        assert_eq!(
+            HistoryStorage::new_with_path(path)
+                .unwrap()
+                .count()
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_waits_for_another_process() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test_db");
+        let holder = HistoryStorage::new_with_path(db_path.clone()).unwrap();
+
+        let error = create_when_unlocked(&db_path, Duration::from_millis(200))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.contains("in use by another netrunner_cli process"),
+            "{}",
+            error
+        );
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(holder);
+        });
+        assert!(create_when_unlocked(&db_path, Duration::from_secs(5)).is_ok());
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn test_data_used_from_byte_counters() {
         let temp_dir = tempdir().unwrap();
         let db_path = temp_dir.path().join("test_db");
         let storage = HistoryStorage::new_with_path(db_path).unwrap();
 
-        let stats = storage.get_statistics();
-        assert!(stats.is_ok());
+        let counted = SpeedTestResult {
+            download_mbps: 900.0,
+            upload_mbps: 100.0,
+            test_duration_seconds: 40.0,
+            data_used: Some(DataUsage {
+                downloaded_bytes: 1_500_000_000,
+                uploaded_bytes: 250_000_000,
+            }),
+            ..Default::default()
+        };
+        storage.save_result(&counted).unwrap();
+
+        // Written after failures but before data used, so its share can only be
+        // estimated from 80 Mbps over 100 s
+        insert_record(&storage, 4, LAYOUT_4_RECORD);
+        let failed = decode_result_layout(4, LAYOUT_4_RECORD).unwrap();
+
+        let results = storage.get_all_results().unwrap();
+        assert_eq!(results[0].data_used, counted.data_used);
+        assert_eq!(results[1].failures, failed.failures);
+        assert_eq!(results[1].data_used, None);
+
+        // The estimated 1 GB counts as well, statistics are read from the results
+        let stats = storage.get_statistics().unwrap();
+        assert!((stats.total_data_downloaded_gb - 2.5).abs() < 1e-9);
+        assert!((stats.total_data_uploaded_gb - 0.25).abs() < 1e-9);
+
+        // Starting within the old record's day, so that day is read result by result
+        let range = storage
+            .get_statistics_by_date_range(
+                failed.timestamp - chrono::Duration::hours(1),
+                Utc::now() + chrono::Duration::hours(1),
+            )
+            .unwrap();
+        assert!((range.total_data_downloaded_gb - 2.5).abs() < 1e-9);
     }
 }
@@ -0,0 +1,816 @@
+//! History Browser TUI - Browses stored speed test results full-screen
+//!
+//! `netrunner_cli history --tui` renders:
+//! - A scrollable list of results, newest first
+//! - Details of the selected result
+//! - Download and upload over time for the listed results
+//! - A filter box matching dates, servers, ISPs, qualities and tags
+//! - Keys to delete the selected result or edit its tags
+
+use chrono::Local;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Dataset, GraphType, Padding, Paragraph, Row, Table,
+        TableState, Wrap,
+    },
+    Terminal,
+};
+use std::io;
+use std::time::Duration;
+
+use crate::modules::{
+    history::HistoryStorage,
+    stats_ui::{
+        quality_color, COLOR_BLUE, COLOR_CYAN, COLOR_DIM, COLOR_GREEN, COLOR_MAGENTA,
+        COLOR_PANEL_BG, COLOR_RED, COLOR_YELLOW,
+    },
+    terminal::run_fullscreen,
+    types::SpeedTestResult,
+};
+
+/// Rows moved by PageUp and PageDown
+const PAGE_SIZE: usize = 10;
+
+/// What keys currently do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Typing into the filter box
+    Filter,
+    /// Editing the tags of the selected result
+    Tags,
+    /// Waiting for y/n before deleting the selected result
+    ConfirmDelete,
+}
+
+/// Application state for the history browser
+struct BrowserApp {
+    /// Every stored result, newest first
+    results: Vec<SpeedTestResult>,
+    /// Indices into `results` the filter lets through
+    visible: Vec<usize>,
+    table: TableState,
+    mode: Mode,
+    filter: String,
+    /// Tags being edited, comma separated
+    input: String,
+    /// Outcome of the last action, shown until the next key
+    status: Option<String>,
+}
+
+impl BrowserApp {
+    fn new(results: Vec<SpeedTestResult>) -> Self {
+        let mut app = Self {
+            results,
+            visible: Vec::new(),
+            table: TableState::default(),
+            mode: Mode::Browse,
+            filter: String::new(),
+            input: String::new(),
+            status: None,
+        };
+        app.apply_filter();
+        app
+    }
+
+    /// Index into `results` of the selected row
+    fn selected_index(&self) -> Option<usize> {
+        self.table
+            .selected()
+            .and_then(|row| self.visible.get(row).copied())
+    }
+
+    fn selected(&self) -> Option<&SpeedTestResult> {
+        self.selected_index().map(|index| &self.results[index])
+    }
+
+    /// Recompute the listed results, keeping the selection on the same result
+    /// while it stays listed
+    fn apply_filter(&mut self) {
+        let selected = self.selected_index();
+        self.visible = (0..self.results.len())
+            .filter(|&index| matches_filter(&self.results[index], &self.filter))
+            .collect();
+        let row = selected
+            .and_then(|index| self.visible.iter().position(|&i| i == index))
+            .unwrap_or(0);
+        self.table.select((!self.visible.is_empty()).then_some(row));
+    }
+
+    /// Move the selection by `rows`, stopping at either end
+    fn move_selection(&mut self, rows: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let row = self.table.selected().unwrap_or(0);
+        let last = self.visible.len() - 1;
+        self.table
+            .select(Some(row.saturating_add_signed(rows).min(last)));
+    }
+
+    /// Handle a key press, returns true when the browser should close
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        self.status = None;
+        match self.mode {
+            Mode::Browse => match code {
+                KeyCode::Char('q') => return true,
+                KeyCode::Esc if self.filter.is_empty() => return true,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.apply_filter();
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+                KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+                KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN),
+                KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX),
+                KeyCode::Char('/') => self.mode = Mode::Filter,
+                KeyCode::Char('t') => {
+                    if let Some(result) = self.selected() {
+                        self.input = result.tags.join(", ");
+                        self.mode = Mode::Tags;
+                    }
+                }
+                KeyCode::Char('d') | KeyCode::Delete if self.selected().is_some() => {
+                    self.mode = Mode::ConfirmDelete;
+                }
+                _ => {}
+            },
+            Mode::Filter => match code {
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.apply_filter();
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Down => self.move_selection(1),
+                KeyCode::Up => self.move_selection(-1),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.apply_filter();
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.apply_filter();
+                }
+                _ => {}
+            },
+            Mode::Tags => match code {
+                KeyCode::Enter => {
+                    self.save_tags();
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                _ => {}
+            },
+            Mode::ConfirmDelete => {
+                if matches!(code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    self.delete_selected();
+                }
+                self.mode = Mode::Browse;
+            }
+        }
+        false
+    }
+
+    /// Store the edited tags on the selected result
+    fn save_tags(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+        let tags = parse_tags(&self.input);
+        let saved = HistoryStorage::new()
+            .and_then(|storage| storage.set_result_tags(&self.results[index], tags.clone()));
+        self.status = Some(match saved {
+            Ok(true) => {
+                self.results[index].tags = tags;
+                self.apply_filter();
+                "✓ Tags saved".to_string()
+            }
+            Ok(false) => "⚠ The result is no longer stored".to_string(),
+            Err(e) => format!("✗ Could not save tags: {e}"),
+        });
+    }
+
+    /// Delete the selected result from the history and the list
+    fn delete_selected(&mut self) {
+        let Some(timestamp) = self.selected().map(|result| result.timestamp) else {
+            return;
+        };
+        let deleted = HistoryStorage::new().and_then(|storage| storage.delete_result(timestamp));
+        self.status = Some(match deleted {
+            Ok(()) => {
+                // Results from the same moment go with it, like in the history
+                let row = self.table.selected().unwrap_or(0);
+                self.results.retain(|result| result.timestamp != timestamp);
+                self.table.select(None);
+                self.apply_filter();
+                self.table
+                    .select((!self.visible.is_empty()).then(|| row.min(self.visible.len() - 1)));
+                format!(
+                    "✓ Deleted the result from {}",
+                    timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                )
+            }
+            Err(e) => format!("✗ Could not delete: {e}"),
+        });
+    }
+}
+
+/// Whether a result matches the filter box: every word has to appear in its
+/// local date and time, server, ISP, quality or tags, ignoring case
+fn matches_filter(result: &SpeedTestResult, filter: &str) -> bool {
+    let text = format!(
+        "{} {} {} {} {}",
+        result
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M"),
+        result.server_location,
+        result.isp.as_deref().unwrap_or_default(),
+        result.quality,
+        result.tags.join(" "),
+    )
+    .to_lowercase();
+    filter
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| text.contains(word))
+}
+
+/// Tags typed as a comma separated list, blanks and repeats dropped
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+    {
+        if !tags.iter().any(|known| known == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+// ── Public entry-point ────────────────────────────────────────────────────────
+
+/// Launch the interactive history browser.
+///
+/// Loads every stored result, then enters an alternate-screen TUI loop. The
+/// history is opened again for each delete or tag edit, so a monitor service
+/// can keep saving results while the browser is open.
+pub fn show_history_browser() -> io::Result<()> {
+    let results = match HistoryStorage::new().and_then(|storage| storage.get_all_results()) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Could not load history: {e}");
+            return Ok(());
+        }
+    };
+
+    if results.is_empty() {
+        println!("No test history found. Run a speed test first.");
+        return Ok(());
+    }
+
+    let mut app = BrowserApp::new(results);
+    run_fullscreen(|terminal| run_browser_loop(terminal, &mut app))
+}
+
+// ── Event loop ────────────────────────────────────────────────────────────────
+
+fn run_browser_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut BrowserApp,
+) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| render_browser(frame, app))?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                // Typed text would repeat on terminals that report releases too
+                if key.kind == KeyEventKind::Press && app.handle_key(key.code) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// ── Rendering ─────────────────────────────────────────────────────────────────
+
+fn render_browser(frame: &mut ratatui::Frame, app: &mut BrowserApp) {
+    let area = frame.area();
+
+    frame.render_widget(
+        Block::default().style(Style::default().bg(COLOR_PANEL_BG)),
+        area,
+    );
+
+    let outer = Layout::vertical([
+        Constraint::Length(3), // header
+        Constraint::Min(0),    // body
+        Constraint::Length(3), // filter / prompt
+        Constraint::Length(3), // footer
+    ])
+    .split(area);
+
+    render_header(frame, outer[0]);
+
+    let columns = Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(outer[1]);
+    render_results_list(frame, columns[0], app);
+
+    let right =
+        Layout::vertical([Constraint::Min(12), Constraint::Percentage(45)]).split(columns[1]);
+    render_details(frame, right[0], app);
+    render_chart(frame, right[1], app);
+
+    render_input_bar(frame, outer[2], app);
+    render_footer(frame, outer[3], app.mode);
+}
+
+fn render_header(frame: &mut ratatui::Frame, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(COLOR_CYAN))
+        .style(Style::default().bg(COLOR_PANEL_BG));
+
+    let text = Paragraph::new(Line::from(vec![
+        Span::styled("⟨⟨⟨ ", Style::default().fg(COLOR_CYAN)),
+        Span::styled(
+            "NETRUNNER",
+            Style::default()
+                .fg(COLOR_MAGENTA)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(" // ", Style::default().fg(COLOR_DIM)),
+        Span::styled(
+            "HISTORY BROWSER",
+            Style::default().fg(COLOR_CYAN).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(" ⟩⟩⟩", Style::default().fg(COLOR_CYAN)),
+    ]))
+    .alignment(Alignment::Center)
+    .block(block);
+
+    frame.render_widget(text, area);
+}
+
+fn panel<'a>(title: String, color: Color) -> Block<'a> {
+    Block::default()
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color))
+        .title_style(Style::default().fg(color).add_modifier(Modifier::BOLD))
+        .style(Style::default().bg(COLOR_PANEL_BG))
+}
+
+fn render_results_list(frame: &mut ratatui::Frame, area: Rect, app: &mut BrowserApp) {
+    let block = panel(
+        format!(" 🗂  Results [{}/{}] ", app.visible.len(), app.results.len()),
+        COLOR_BLUE,
+    );
+
+    if app.visible.is_empty() {
+        let paragraph = Paragraph::new("No results match the filter.")
+            .alignment(Alignment::Center)
+            .block(block);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let header = Row::new(
+        ["Date/Time", "↓ Mbps", "↑ Mbps", "Ping ms", "Quality"].map(|h| {
+            Cell::from(h).style(
+                Style::default()
+                    .fg(COLOR_YELLOW)
+                    .add_modifier(Modifier::BOLD),
+            )
+        }),
+    );
+
+    let rows: Vec<Row<'_>> = app
+        .visible
+        .iter()
+        .map(|&index| {
+            let r = &app.results[index];
+            let tagged = if r.tags.is_empty() { "" } else { " •" };
+            Row::new(vec![
+                Cell::from(format!(
+                    "{}{}",
+                    r.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    tagged
+                )),
+                Cell::from(format!("{:.1}", r.download_mbps)),
+                Cell::from(format!("{:.1}", r.upload_mbps)),
+                Cell::from(format!("{:.0}", r.ping_ms)),
+                Cell::from(format!("{}", r.quality))
+                    .style(Style::default().fg(quality_color(&r.quality))),
+            ])
+            .style(Style::default().fg(Color::White))
+        })
+        .collect();
+
+    let widths = [
+        Constraint::Length(18),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Min(9),
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .column_spacing(1)
+        .row_highlight_style(
+            Style::default()
+                .bg(Color::Rgb(30, 30, 60))
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+
+    frame.render_stateful_widget(table, area, &mut app.table);
+}
+
+fn render_details(frame: &mut ratatui::Frame, area: Rect, app: &BrowserApp) {
+    let block = panel(" 🔍  Details ".to_string(), COLOR_MAGENTA).padding(Padding::horizontal(1));
+
+    let Some(r) = app.selected() else {
+        frame.render_widget(block, area);
+        return;
+    };
+
+    let label = |text: &str| -> Span<'static> {
+        Span::styled(
+            format!("{text:<11}"),
+            Style::default().fg(COLOR_CYAN).add_modifier(Modifier::BOLD),
+        )
+    };
+    let value =
+        |text: String| -> Span<'static> { Span::styled(text, Style::default().fg(Color::White)) };
+
+    let mut lines: Vec<Line<'static>> = vec![
+        Line::from(vec![
+            label("Time"),
+            value(
+                r.timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ),
+        ]),
+        Line::from(vec![label("Server"), value(r.server_location.clone())]),
+    ];
+    if let Some(isp) = &r.isp {
+        lines.push(Line::from(vec![label("ISP"), value(isp.clone())]));
+    }
+    lines.extend([
+        Line::from(vec![
+            label("Download"),
+            value(format!("{:.2} Mbps", r.download_mbps)),
+        ]),
+        Line::from(vec![
+            label("Upload"),
+            value(format!("{:.2} Mbps", r.upload_mbps)),
+        ]),
+        Line::from(vec![
+            label("Ping"),
+            value(format!(
+                "{:.1} ms (jitter {:.1} ms, loss {:.1}%)",
+                r.ping_ms, r.jitter_ms, r.packet_loss_percent
+            )),
+        ]),
+        Line::from(vec![
+            label("Quality"),
+            Span::styled(
+                r.quality.to_string(),
+                Style::default().fg(quality_color(&r.quality)),
+            ),
+        ]),
+        Line::from(vec![
+            label("Duration"),
+            value(format!("{:.1} s", r.test_duration_seconds)),
+        ]),
+    ]);
+    if let Some(data) = &r.data_used {
+        lines.push(Line::from(vec![
+            label("Data used"),
+            value(format!(
+                "↓ {:.1} MB  ↑ {:.1} MB",
+                data.downloaded_bytes as f64 / 1_000_000.0,
+                data.uploaded_bytes as f64 / 1_000_000.0
+            )),
+        ]));
+    }
+    if let Some(other) = &r.other_traffic {
+        let rate = |mbps: Option<f64>| mbps.map_or("-".to_string(), |mbps| format!("{mbps:.1}"));
+        lines.push(Line::from(vec![
+            label("Other LAN"),
+            value(format!(
+                "↓ {} Mbps  ↑ {} Mbps",
+                rate(other.download_mbps),
+                rate(other.upload_mbps)
+            )),
+        ]));
+    }
+    lines.push(Line::from(vec![
+        label("Tags"),
+        if r.tags.is_empty() {
+            Span::styled("none", Style::default().fg(COLOR_DIM))
+        } else {
+            Span::styled(r.tags.join(", "), Style::default().fg(COLOR_GREEN))
+        },
+    ]));
+    for failure in &r.failures {
+        lines.push(Line::from(vec![
+            label("Failed"),
+            Span::styled(
+                format!("{}: {}", failure.phase, failure.reason),
+                Style::default().fg(COLOR_RED),
+            ),
+        ]));
+    }
+
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block);
+    frame.render_widget(paragraph, area);
+}
+
+fn render_chart(frame: &mut ratatui::Frame, area: Rect, app: &BrowserApp) {
+    let block = panel(" 📈  Speed Over Time ".to_string(), COLOR_CYAN);
+
+    // Listed results oldest first, placed by their time in seconds
+    let point = |index: usize, mbps: f64| (app.results[index].timestamp.timestamp() as f64, mbps);
+    let download: Vec<(f64, f64)> = app
+        .visible
+        .iter()
+        .rev()
+        .map(|&i| point(i, app.results[i].download_mbps))
+        .collect();
+    let upload: Vec<(f64, f64)> = app
+        .visible
+        .iter()
+        .rev()
+        .map(|&i| point(i, app.results[i].upload_mbps))
+        .collect();
+    let selected: Vec<(f64, f64)> = app
+        .selected_index()
+        .map(|i| {
+            vec![
+                point(i, app.results[i].download_mbps),
+                point(i, app.results[i].upload_mbps),
+            ]
+        })
+        .unwrap_or_default();
+
+    let (Some(first), Some(last)) = (download.first(), download.last()) else {
+        frame.render_widget(block, area);
+        return;
+    };
+    // A single result still gets an axis to sit on
+    let (start, end) = (first.0, last.0.max(first.0 + 1.0));
+    let top = download
+        .iter()
+        .chain(&upload)
+        .map(|&(_, mbps)| mbps)
+        .fold(1.0, f64::max)
+        * 1.1;
+    let date = |secs: f64| {
+        chrono::DateTime::from_timestamp(secs as i64, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local)
+            .format("%m-%d")
+            .to_string()
+    };
+
+    let datasets = vec![
+        Dataset::default()
+            .name("Download")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(COLOR_CYAN))
+            .data(&download),
+        Dataset::default()
+            .name("Upload")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(COLOR_MAGENTA))
+            .data(&upload),
+        Dataset::default()
+            .marker(Marker::Block)
+            .graph_type(GraphType::Scatter)
+            .style(Style::default().fg(COLOR_YELLOW))
+            .data(&selected),
+    ];
+
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(COLOR_DIM))
+                .bounds([start, end])
+                .labels([date(start), date(end)]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(COLOR_DIM))
+                .bounds([0.0, top])
+                .labels(["0".to_string(), format!("{top:.0}")]),
+        );
+
+    frame.render_widget(chart, area);
+}
+
+fn render_input_bar(frame: &mut ratatui::Frame, area: Rect, app: &BrowserApp) {
+    let cursor = Span::styled("█", Style::default().fg(COLOR_YELLOW));
+    let key = |text: &'static str| Span::styled(text, Style::default().fg(COLOR_YELLOW).bold());
+    let text = |text: String| Span::styled(text, Style::default().fg(Color::White));
+
+    let (title, line) = match app.mode {
+        Mode::Filter => (
+            " Filter ",
+            Line::from(vec![text(app.filter.clone()), cursor]),
+        ),
+        Mode::Tags => (
+            " Tags, comma separated ",
+            Line::from(vec![text(app.input.clone()), cursor]),
+        ),
+        Mode::ConfirmDelete => (
+            " Delete ",
+            Line::from(vec![
+                Span::styled(
+                    format!(
+                        "Delete the result from {}? ",
+                        app.selected()
+                            .map(|r| r.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M"))
+                            .map(|at| at.to_string())
+                            .unwrap_or_default()
+                    ),
+                    Style::default().fg(COLOR_RED).bold(),
+                ),
+                key("y"),
+                Span::raw(" / "),
+                key("n"),
+            ]),
+        ),
+        Mode::Browse => match &app.status {
+            Some(status) => (" Status ", Line::from(text(status.clone()))),
+            None if app.filter.is_empty() => (
+                " Filter ",
+                Line::from(Span::styled(
+                    "Press / to filter by date, server, ISP, quality or tag",
+                    Style::default().fg(COLOR_DIM),
+                )),
+            ),
+            None => (" Filter ", Line::from(text(app.filter.clone()))),
+        },
+    };
+
+    let border = if app.mode == Mode::Browse {
+        COLOR_DIM
+    } else {
+        COLOR_YELLOW
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border))
+        .style(Style::default().bg(COLOR_PANEL_BG));
+
+    frame.render_widget(Paragraph::new(line).block(block), area);
+}
+
+fn render_footer(frame: &mut ratatui::Frame, area: Rect, mode: Mode) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(COLOR_DIM))
+        .style(Style::default().bg(COLOR_PANEL_BG));
+
+    let key = |text: &'static str| Span::styled(text, Style::default().fg(COLOR_YELLOW).bold());
+    let text = match mode {
+        Mode::Browse => Line::from(vec![
+            key("↑/↓"),
+            Span::raw("  Select   "),
+            key("/"),
+            Span::raw("  Filter   "),
+            key("t"),
+            Span::raw("  Tags   "),
+            key("d"),
+            Span::raw("  Delete   "),
+            key("q / Esc"),
+            Span::raw("  Quit"),
+        ]),
+        Mode::Filter => Line::from(vec![
+            key("Enter"),
+            Span::raw("  Keep filter   "),
+            key("Esc"),
+            Span::raw("  Clear filter"),
+        ]),
+        Mode::Tags => Line::from(vec![
+            key("Enter"),
+            Span::raw("  Save tags   "),
+            key("Esc"),
+            Span::raw("  Cancel"),
+        ]),
+        Mode::ConfirmDelete => Line::from(vec![
+            key("y"),
+            Span::raw("  Delete   "),
+            key("any other key"),
+            Span::raw("  Keep"),
+        ]),
+    };
+
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .block(block);
+
+    frame.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::ConnectionQuality;
+
+    fn result(server: &str, tags: &[&str]) -> SpeedTestResult {
+        SpeedTestResult {
+            server_location: server.to_string(),
+            quality: ConnectionQuality::Good,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        let r = result("Frankfurt, DE", &["wired"]);
+        assert!(matches_filter(&r, ""));
+        assert!(matches_filter(&r, "frankfurt WIRED"));
+        assert!(matches_filter(&r, "good"));
+        assert!(!matches_filter(&r, "frankfurt wifi"));
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_tags(" wired, vpn,,wired , "), vec!["wired", "vpn"]);
+        assert!(parse_tags("  ").is_empty());
+    }
+
+    #[test]
+    fn test_filter_keeps_selection() {
+        let mut app = BrowserApp::new(vec![
+            result("Berlin", &[]),
+            result("Paris", &["vpn"]),
+            result("Berlin", &["vpn"]),
+        ]);
+        app.move_selection(2);
+        assert_eq!(app.selected_index(), Some(2));
+
+        app.handle_key(KeyCode::Char('/'));
+        for c in "vpn".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(app.visible, vec![1, 2]);
+        assert_eq!(app.selected_index(), Some(2));
+
+        // Clearing the filter lists everything again
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.visible.len(), 3);
+        assert_eq!(app.mode, Mode::Browse);
+        assert!(!app.handle_key(KeyCode::Up));
+        assert_eq!(app.selected_index(), Some(1));
+        assert!(app.handle_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_render_modes() {
+        let mut app = BrowserApp::new(vec![result("Berlin", &["wired"]), result("Paris", &[])]);
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 40)).unwrap();
+        for key in [KeyCode::Char('t'), KeyCode::Esc, KeyCode::Char('d')] {
+            app.handle_key(key);
+            terminal
+                .draw(|frame| render_browser(frame, &mut app))
+                .unwrap();
+        }
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("HISTORY BROWSER"));
+        assert!(screen.contains("Delete the result"));
+    }
+}
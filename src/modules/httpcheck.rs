@@ -0,0 +1,457 @@
+//! HTTP Endpoint Health Check
+//!
+//! Fetches one URL and breaks the request down into DNS lookup, TCP connect,
+//! TLS handshake, time to first byte and total time, like `curl -w` does. Each
+//! phase is timed on its own connection step instead of going through reqwest,
+//! which does not expose them. Phases slower than their threshold are flagged,
+//! since "the internet is slow" often turns out to be one slow site.
+
+use colored::*;
+use prettytable::{format, Cell, Row, Table};
+use reqwest::Url;
+use rustls::pki_types::ServerName;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use strum_macros::Display;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::modules::terminal::print_table;
+use crate::modules::tls;
+use crate::modules::types::TestConfig;
+use crate::modules::ui::UI;
+
+/// One step of an HTTP request
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpPhase {
+    #[strum(serialize = "DNS lookup")]
+    Dns,
+    #[strum(serialize = "TCP connect")]
+    Connect,
+    #[strum(serialize = "TLS handshake")]
+    Tls,
+    #[strum(serialize = "Time to first byte")]
+    Ttfb,
+    #[strum(serialize = "Total")]
+    Total,
+}
+
+impl HttpPhase {
+    pub const ALL: [HttpPhase; 5] = [
+        HttpPhase::Dns,
+        HttpPhase::Connect,
+        HttpPhase::Tls,
+        HttpPhase::Ttfb,
+        HttpPhase::Total,
+    ];
+}
+
+/// Duration of each phase in milliseconds. Phases are measured separately, except
+/// `total_ms` which covers the whole request from DNS lookup to the last byte.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Default)]
+pub struct HttpTimings {
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    /// Absent for plain `http://` URLs
+    pub tls_ms: Option<f64>,
+    /// From sending the request to the first response byte, mostly server think time
+    pub ttfb_ms: f64,
+    pub total_ms: f64,
+}
+
+impl HttpTimings {
+    pub fn phase_ms(&self, phase: HttpPhase) -> Option<f64> {
+        match phase {
+            HttpPhase::Dns => Some(self.dns_ms),
+            HttpPhase::Connect => Some(self.connect_ms),
+            HttpPhase::Tls => self.tls_ms,
+            HttpPhase::Ttfb => Some(self.ttfb_ms),
+            HttpPhase::Total => Some(self.total_ms),
+        }
+    }
+}
+
+/// Slowest acceptable duration of each phase in milliseconds
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct HttpThresholds {
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    pub tls_ms: f64,
+    pub ttfb_ms: f64,
+    pub total_ms: f64,
+}
+
+impl Default for HttpThresholds {
+    fn default() -> Self {
+        Self {
+            dns_ms: 100.0,
+            connect_ms: 150.0,
+            tls_ms: 300.0,
+            ttfb_ms: 800.0,
+            total_ms: 2000.0,
+        }
+    }
+}
+
+impl HttpThresholds {
+    pub fn limit_ms(&self, phase: HttpPhase) -> f64 {
+        match phase {
+            HttpPhase::Dns => self.dns_ms,
+            HttpPhase::Connect => self.connect_ms,
+            HttpPhase::Tls => self.tls_ms,
+            HttpPhase::Ttfb => self.ttfb_ms,
+            HttpPhase::Total => self.total_ms,
+        }
+    }
+
+    /// Phases of `timings` that took longer than their threshold
+    pub fn slow_phases(&self, timings: &HttpTimings) -> Vec<HttpPhase> {
+        HttpPhase::ALL
+            .into_iter()
+            .filter(|&phase| {
+                timings
+                    .phase_ms(phase)
+                    .is_some_and(|ms| ms > self.limit_ms(phase))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpCheckReport {
+    pub url: String,
+    pub address: IpAddr,
+    pub status: u16,
+    pub response_bytes: usize,
+    pub timings: HttpTimings,
+    pub thresholds: HttpThresholds,
+    pub slow_phases: Vec<HttpPhase>,
+}
+
+impl HttpCheckReport {
+    /// A successful status with every phase within its threshold
+    pub fn is_healthy(&self) -> bool {
+        self.status < 400 && self.slow_phases.is_empty()
+    }
+}
+
+pub struct HttpCheckTool {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl HttpCheckTool {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    pub async fn run(
+        &self,
+        url: &Url,
+        thresholds: HttpThresholds,
+    ) -> Result<HttpCheckReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("HTTP Endpoint Check")?;
+        }
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(
+                self.ui
+                    .create_cyberpunk_spinner(&format!("PROBING {}", url.as_str().to_uppercase())),
+            )
+        } else {
+            None
+        };
+
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let fetched = tokio::time::timeout(timeout, fetch(url))
+            .await
+            .map_err(|_| format!("{} did not respond within {}s", url, timeout.as_secs()))?;
+
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ ENDPOINT PROBED ⟩⟩⟩");
+        }
+        let fetched = fetched?;
+
+        let report = HttpCheckReport {
+            url: url.to_string(),
+            address: fetched.address,
+            status: fetched.status,
+            response_bytes: fetched.response_bytes,
+            slow_phases: thresholds.slow_phases(&fetched.timings),
+            timings: fetched.timings,
+            thresholds,
+        };
+
+        if !self.config.json_output {
+            self.display_results(&report)?;
+        }
+
+        Ok(report)
+    }
+
+    fn display_results(&self, report: &HttpCheckReport) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+        println!(
+            "{} {} ({})",
+            "🎯 URL:".bold(),
+            report.url.bright_cyan(),
+            report.address
+        );
+        let status = format!("HTTP {}", report.status);
+        println!(
+            "{} {}  {} bytes",
+            "📨 Response:".bold(),
+            if report.status < 400 {
+                status.bright_green()
+            } else {
+                status.bright_red()
+            },
+            report.response_bytes
+        );
+        println!();
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(Row::new(vec![
+            Cell::new("Phase").style_spec("Fb"),
+            Cell::new("Time").style_spec("Fb"),
+            Cell::new("Threshold").style_spec("Fb"),
+        ]));
+
+        for phase in HttpPhase::ALL {
+            let Some(ms) = report.timings.phase_ms(phase) else {
+                continue;
+            };
+            let slow = report.slow_phases.contains(&phase);
+            table.add_row(Row::new(vec![
+                Cell::new(&phase.to_string()),
+                Cell::new(&format!("{:.1} ms", ms)).style_spec(if slow { "Fr" } else { "Fg" }),
+                Cell::new(&format!("{:.0} ms", report.thresholds.limit_ms(phase))),
+            ]));
+        }
+
+        print_table(&table)?;
+        println!();
+
+        if report.is_healthy() {
+            println!("{}", "✓ Endpoint healthy".bright_green());
+        } else if report.slow_phases.is_empty() {
+            println!(
+                "{}",
+                "✗ Endpoint answered with an error status".bright_red()
+            );
+        } else {
+            let slow: Vec<String> = report.slow_phases.iter().map(|p| p.to_string()).collect();
+            println!(
+                "{} {}",
+                "✗ Slower than expected:".bright_red(),
+                slow.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+struct Fetched {
+    address: IpAddr,
+    status: u16,
+    response_bytes: usize,
+    timings: HttpTimings,
+}
+
+/// Fetch `url` with a single HTTP/1.1 GET, timing each step
+async fn fetch(url: &Url) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let https = match url.scheme() {
+        "https" => true,
+        "http" => false,
+        other => return Err(format!("Unsupported URL scheme '{}'", other).into()),
+    };
+    let host = url.host().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+
+    let start = Instant::now();
+
+    let address = match &host {
+        url::Host::Domain(domain) => tokio::net::lookup_host((*domain, port))
+            .await?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", domain))?
+            .ip(),
+        url::Host::Ipv4(ip) => IpAddr::V4(*ip),
+        url::Host::Ipv6(ip) => IpAddr::V6(*ip),
+    };
+    let dns_ms = elapsed_ms(start);
+
+    let step = Instant::now();
+    let tcp = TcpStream::connect(SocketAddr::new(address, port)).await?;
+    tcp.set_nodelay(true)?;
+    let connect_ms = elapsed_ms(step);
+
+    let request = format!(
+        "GET {path} HTTP/1.1\r\n\
+         Host: {host}\r\n\
+         User-Agent: netrunner_cli/{version}\r\n\
+         Accept: */*\r\n\
+         Connection: close\r\n\r\n",
+        path = &url[url::Position::BeforePath..url::Position::AfterQuery],
+        host = &url[url::Position::BeforeHost..url::Position::AfterPort],
+        version = env!("CARGO_PKG_VERSION"),
+    );
+
+    let (tls_ms, exchange) = if https {
+        let mut tls_config = tls::client_config()?;
+        tls_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let server_name = match host {
+            url::Host::Domain(domain) => ServerName::try_from(domain.to_string())?,
+            url::Host::Ipv4(ip) => ServerName::from(IpAddr::V4(ip)),
+            url::Host::Ipv6(ip) => ServerName::from(IpAddr::V6(ip)),
+        };
+
+        let step = Instant::now();
+        let mut stream = TlsConnector::from(Arc::new(tls_config))
+            .connect(server_name, tcp)
+            .await?;
+        let tls_ms = elapsed_ms(step);
+        (
+            Some(tls_ms),
+            exchange(&mut stream, request.as_bytes()).await?,
+        )
+    } else {
+        let mut tcp = tcp;
+        (None, exchange(&mut tcp, request.as_bytes()).await?)
+    };
+
+    Ok(Fetched {
+        address,
+        status: exchange.status,
+        response_bytes: exchange.response_bytes,
+        timings: HttpTimings {
+            dns_ms,
+            connect_ms,
+            tls_ms,
+            ttfb_ms: exchange.ttfb_ms,
+            total_ms: elapsed_ms(start),
+        },
+    })
+}
+
+struct Exchange {
+    status: u16,
+    response_bytes: usize,
+    ttfb_ms: f64,
+}
+
+/// Send the request and read the response until the server closes the connection
+async fn exchange<S>(stream: &mut S, request: &[u8]) -> Result<Exchange, Box<dyn std::error::Error>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let sent = Instant::now();
+    stream.write_all(request).await?;
+    stream.flush().await?;
+
+    let mut head = Vec::new();
+    let mut ttfb_ms = None;
+    let mut response_bytes = 0;
+    let mut buf = [0u8; 16 * 1024];
+    loop {
+        let n = match stream.read(&mut buf).await {
+            Ok(n) => n,
+            // Many servers close TLS connections without a close_notify
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            break;
+        }
+        ttfb_ms.get_or_insert_with(|| elapsed_ms(sent));
+        response_bytes += n;
+        if head.len() < 256 {
+            head.extend_from_slice(&buf[..n.min(256)]);
+        }
+    }
+
+    Ok(Exchange {
+        status: parse_status(&head).ok_or("Invalid HTTP response")?,
+        response_bytes,
+        ttfb_ms: ttfb_ms.ok_or("Server closed the connection without a response")?,
+    })
+}
+
+/// Status code from a response starting with e.g. `HTTP/1.1 200 OK`
+fn parse_status(head: &[u8]) -> Option<u16> {
+    let line = head.split(|&b| b == b'\r' || b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    parts.next()?.starts_with("HTTP/").then_some(())?;
+    parts.next()?.parse().ok()
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_status(b"HTTP/1.1 200 OK\r\nServer: x\r\n"), Some(200));
+        assert_eq!(
+            parse_status(b"HTTP/1.0 503 Service Unavailable\r\n"),
+            Some(503)
+        );
+        assert_eq!(parse_status(b"SSH-2.0-OpenSSH\r\n"), None);
+        assert_eq!(parse_status(b""), None);
+    }
+
+    #[test]
+    fn test_slow_phases() {
+        let thresholds = HttpThresholds::default();
+        let timings = HttpTimings {
+            dns_ms: 20.0,
+            connect_ms: 400.0,
+            tls_ms: None,
+            ttfb_ms: 100.0,
+            total_ms: 2500.0,
+        };
+
+        assert_eq!(
+            thresholds.slow_phases(&timings),
+            [HttpPhase::Connect, HttpPhase::Total]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_plain_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"GET /health?full=1 HTTP/1.1\r\n"));
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let url = Url::parse(&format!("http://{}/health?full=1", addr)).unwrap();
+        let fetched = fetch(&url).await.unwrap();
+
+        assert_eq!(fetched.status, 204);
+        assert_eq!(fetched.address, addr.ip());
+        assert_eq!(fetched.timings.tls_ms, None);
+        assert!(fetched.timings.total_ms >= fetched.timings.ttfb_ms);
+    }
+}
@@ -2,11 +2,7 @@
 //!
 //! Displays the Netrunner logo with cyberpunk glow animations when the app starts.
 
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Layout, Rect},
@@ -18,6 +14,7 @@ use ratatui::{
 use std::io;
 use std::time::{Duration, Instant};
 
+use crate::modules::terminal::{run_fullscreen, TerminalCapabilities};
 use crate::modules::{NetrunnerLogo, NetrunnerLogoSize};
 
 // Cyberpunk color palette for border effects
@@ -32,21 +29,7 @@ const BORDER_COLORS: [Color; 6] = [
 
 /// Display the animated intro screen with glowing logo
 pub fn show_intro() -> io::Result<()> {
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    // Run the intro animation
-    let result = run_intro_animation(&mut terminal);
-
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-
-    result
+    run_fullscreen(run_intro_animation)
 }
 
 fn run_intro_animation(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
@@ -288,7 +271,10 @@ pub fn show_simple_intro() -> io::Result<()> {
     Cyberpunk Network Diagnostics
 "
     );
-    std::thread::sleep(Duration::from_millis(1500));
+    // Only pause for a human watching the terminal, not for logs or pipes
+    if TerminalCapabilities::get().is_tty {
+        std::thread::sleep(Duration::from_millis(1500));
+    }
     Ok(())
 }
 
@@ -0,0 +1,334 @@
+//! IP Address Enrichment
+//!
+//! Looks up who operates an address and where it is: the announcing autonomous
+//! system via RIPEstat, the reverse DNS (PTR) name via the system resolver and
+//! the city via ip-api.com's batch endpoint. Private and other non-routable
+//! addresses get no AS or location lookup, no registry knows them.
+
+use reqwest::Client;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::modules::types::{AsnInfo, HopLocation, HostNetwork, RouteHop};
+
+const RIPESTAT_PREFIX_OVERVIEW: &str = "https://stat.ripe.net/data/prefix-overview/data.json";
+
+/// Geolocates up to 100 addresses per request; the free tier is HTTP only
+const IP_API_BATCH: &str =
+    "http://ip-api.com/batch?fields=status,query,country,countryCode,city,lat,lon";
+
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fill in PTR names, AS networks and locations of every answering hop
+pub async fn enrich_hops(hops: &mut [RouteHop]) {
+    let Ok(client) = Client::builder().timeout(LOOKUP_TIMEOUT).build() else {
+        return;
+    };
+
+    let lookups = hops.iter().map(|hop| async {
+        match hop.address {
+            Some(address) => tokio::join!(reverse_dns(address), lookup_network(&client, address)),
+            None => (None, None),
+        }
+    });
+    let public: Vec<IpAddr> = hops
+        .iter()
+        .filter_map(|hop| hop.address)
+        .filter(|&address| is_public(address))
+        .collect();
+    let (results, locations) =
+        tokio::join!(futures::future::join_all(lookups), locate(&client, &public));
+
+    for (hop, (hostname, network)) in hops.iter_mut().zip(results) {
+        hop.hostname = hop.hostname.take().or(hostname);
+        hop.network = network;
+        hop.location = hop.address.and_then(|address| {
+            locations
+                .iter()
+                .find(|(located, _)| *located == address)
+                .map(|(_, location)| location.clone())
+        });
+    }
+}
+
+/// Geolocate public addresses in one batch request, skipping any the service can't place
+async fn locate(client: &Client, addresses: &[IpAddr]) -> Vec<(IpAddr, HopLocation)> {
+    if addresses.is_empty() {
+        return Vec::new();
+    }
+
+    let response = match client.post(IP_API_BATCH).json(addresses).send().await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return Vec::new(),
+    };
+    match response.json::<serde_json::Value>().await {
+        Ok(json) => parse_batch_locations(&json),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn parse_batch_locations(json: &serde_json::Value) -> Vec<(IpAddr, HopLocation)> {
+    let Some(entries) = json.as_array() else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter(|entry| entry["status"] == "success")
+        .filter_map(|entry| {
+            let address = entry["query"].as_str()?.parse().ok()?;
+            Some((
+                address,
+                HopLocation {
+                    country: entry["country"].as_str()?.to_string(),
+                    country_code: entry["countryCode"].as_str()?.to_string(),
+                    city: entry["city"].as_str().unwrap_or_default().to_string(),
+                    latitude: entry["lat"].as_f64()?,
+                    longitude: entry["lon"].as_f64()?,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Countries the route passes through in order, each with the cities visited
+/// there, e.g. `DE (Berlin → Frankfurt) → NL (Amsterdam)`
+pub fn path_summary(hops: &[RouteHop]) -> Option<String> {
+    let mut countries: Vec<(&str, Vec<&str>)> = Vec::new();
+    for location in hops.iter().filter_map(|hop| hop.location.as_ref()) {
+        match countries.last_mut() {
+            Some((code, cities)) if *code == location.country_code => {
+                if !location.city.is_empty() && cities.last() != Some(&location.city.as_str()) {
+                    cities.push(&location.city);
+                }
+            }
+            _ => countries.push((
+                &location.country_code,
+                [location.city.as_str()]
+                    .into_iter()
+                    .filter(|city| !city.is_empty())
+                    .collect(),
+            )),
+        }
+    }
+
+    if countries.is_empty() {
+        return None;
+    }
+    Some(
+        countries
+            .iter()
+            .map(|(code, cities)| {
+                if cities.is_empty() {
+                    code.to_string()
+                } else {
+                    format!("{} ({})", code, cities.join(" → "))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" → "),
+    )
+}
+
+/// Resolve each host and look up its PTR name and network
+pub async fn lookup_hosts(hosts: &[String]) -> Vec<HostNetwork> {
+    let Ok(client) = Client::builder().timeout(LOOKUP_TIMEOUT).build() else {
+        return Vec::new();
+    };
+
+    let lookups = hosts.iter().map(|host| {
+        let client = &client;
+        async move {
+            let address = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .map(|addr| addr.ip());
+            let (hostname, network) = match address {
+                Some(address) => {
+                    tokio::join!(reverse_dns(address), lookup_network(client, address))
+                }
+                None => (None, None),
+            };
+
+            HostNetwork {
+                host: host.clone(),
+                address,
+                hostname,
+                network,
+            }
+        }
+    });
+
+    futures::future::join_all(lookups).await
+}
+
+/// PTR name of `address`, if it has one
+pub async fn reverse_dns(address: IpAddr) -> Option<String> {
+    let lookup = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&address));
+    match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+        // Without a PTR record the address comes back as text
+        Ok(Ok(Ok(name))) if name != address.to_string() => Some(name),
+        _ => None,
+    }
+}
+
+/// Autonomous system announcing `address`, from RIPEstat's prefix overview
+pub async fn lookup_network(client: &Client, address: IpAddr) -> Option<AsnInfo> {
+    if !is_public(address) {
+        return None;
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        RIPESTAT_PREFIX_OVERVIEW,
+        [
+            ("resource", address.to_string().as_str()),
+            ("sourceapp", "netrunner_cli"),
+        ],
+    )
+    .ok()?;
+    let response = client.get(url).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let json: serde_json::Value = response.json().await.ok()?;
+    parse_prefix_overview(&json)
+}
+
+fn parse_prefix_overview(json: &serde_json::Value) -> Option<AsnInfo> {
+    let asn = json["data"]["asns"].as_array()?.first()?;
+    Some(AsnInfo {
+        asn: u32::try_from(asn["asn"].as_u64()?).ok()?,
+        organization: asn["holder"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Whether `address` is routed on the public internet
+pub fn is_public(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (first == 100 && (64..128).contains(&second))
+                || first == 0
+                || first >= 240)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local fc00::/7, link-local fe80::/10, documentation 2001:db8::/32
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        for public in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+        for private in [
+            "192.168.1.1",
+            "10.0.0.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "169.254.1.1",
+            "192.0.2.1",
+            "fd00::1",
+            "fe80::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(private.parse().unwrap()), "{}", private);
+        }
+    }
+
+    #[test]
+    fn test_parse_prefix_overview() {
+        let json = serde_json::json!({
+            "status": "ok",
+            "data": {
+                "resource": "8.8.8.0/24",
+                "asns": [{ "asn": 15169, "holder": "GOOGLE - Google LLC" }]
+            }
+        });
+        assert_eq!(
+            parse_prefix_overview(&json),
+            Some(AsnInfo {
+                asn: 15169,
+                organization: "GOOGLE - Google LLC".to_string(),
+            })
+        );
+
+        let unannounced = serde_json::json!({ "data": { "asns": [] } });
+        assert_eq!(parse_prefix_overview(&unannounced), None);
+    }
+
+    #[test]
+    fn test_parse_batch_locations() {
+        let json = serde_json::json!([
+            {
+                "status": "success",
+                "query": "8.8.8.8",
+                "country": "United States",
+                "countryCode": "US",
+                "city": "Ashburn",
+                "lat": 39.03,
+                "lon": -77.5
+            },
+            { "status": "fail", "query": "203.0.113.9", "message": "reserved range" }
+        ]);
+        let locations = parse_batch_locations(&json);
+
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].0, "8.8.8.8".parse::<IpAddr>().unwrap());
+        assert_eq!(locations[0].1.city, "Ashburn");
+        assert_eq!(locations[0].1.longitude, -77.5);
+    }
+
+    #[test]
+    fn test_path_summary() {
+        let hop = |code: &str, city: &str| RouteHop {
+            hop_number: 0,
+            address: None,
+            hostname: None,
+            response_time_ms: None,
+            network: None,
+            location: (!code.is_empty()).then(|| HopLocation {
+                country: String::new(),
+                country_code: code.to_string(),
+                city: city.to_string(),
+                latitude: 0.0,
+                longitude: 0.0,
+            }),
+        };
+        let hops = [
+            hop("", ""),
+            hop("DE", "Berlin"),
+            hop("DE", "Berlin"),
+            hop("", ""),
+            hop("DE", "Frankfurt"),
+            hop("NL", ""),
+            hop("US", "Ashburn"),
+        ];
+
+        assert_eq!(
+            path_summary(&hops).unwrap(),
+            "DE (Berlin → Frankfurt) → NL → US (Ashburn)"
+        );
+        assert_eq!(path_summary(&hops[..1]), None);
+    }
+}
@@ -0,0 +1,422 @@
+//! Local Network Scan
+//!
+//! Sweeps the local subnet for devices by attempting TCP connections to a few
+//! common ports on every address. A device answers either the connection,
+//! accepted or refused, or at least the ARP lookup the system makes before
+//! sending it, so firewalled devices still turn up in the ARP table afterwards.
+//! Each device is listed with its MAC address, the vendor registered for that
+//! MAC and its DNS name.
+
+use colored::*;
+use futures::StreamExt;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+use crate::modules::ipinfo;
+use crate::modules::localnet::{self, Ipv4Subnet, LocalNetwork};
+use crate::modules::terminal::print_table;
+use crate::modules::types::TestConfig;
+use crate::modules::ui::UI;
+
+/// Web, SSH, SMB and the iOS sync service, between them most devices answer one
+const PROBE_PORTS: &[u16] = &[80, 443, 22, 445, 62078];
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Hosts probed at once
+const CONCURRENCY: usize = 128;
+
+/// Largest subnet swept, 1022 hosts
+pub const MIN_PREFIX_LEN: u8 = 22;
+
+/// OUI registries shipped by the hwdata, ieee-data, arp-scan and nmap packages
+const OUI_DATABASES: &[&str] = &[
+    "/usr/share/hwdata/oui.txt",
+    "/usr/share/ieee-data/oui.txt",
+    "/usr/share/misc/oui.txt",
+    "/usr/share/arp-scan/ieee-oui.txt",
+    "/usr/share/nmap/nmap-mac-prefixes",
+];
+
+/// Vendors recognised without a registry on disk
+const KNOWN_OUIS: &[(&str, &str)] = &[
+    ("b827eb", "Raspberry Pi Foundation"),
+    ("dca632", "Raspberry Pi Trading"),
+    ("e45f01", "Raspberry Pi Trading"),
+    ("d83add", "Raspberry Pi Trading"),
+    ("000c29", "VMware"),
+    ("005056", "VMware"),
+    ("080027", "VirtualBox"),
+    ("525400", "QEMU virtual NIC"),
+    ("00155d", "Microsoft Hyper-V"),
+];
+
+/// A device found on the local network
+#[derive(Debug, Clone, Serialize)]
+pub struct LanDevice {
+    pub address: Ipv4Addr,
+    pub mac: Option<String>,
+    pub vendor: Option<String>,
+    pub hostname: Option<String>,
+    /// Probe ports that accepted a connection
+    pub open_ports: Vec<u16>,
+    pub is_gateway: bool,
+    /// The machine running the scan
+    pub is_self: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanScanReport {
+    pub interface: Option<String>,
+    pub subnet: Ipv4Subnet,
+    pub gateway: Option<Ipv4Addr>,
+    pub devices: Vec<LanDevice>,
+}
+
+pub struct LanScanner {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl LanScanner {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    /// Sweep `subnet`, or the subnet of the interface that reaches the internet
+    pub async fn run(
+        &self,
+        subnet: Option<Ipv4Subnet>,
+    ) -> Result<LanScanReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("Local Network Scan")?;
+        }
+
+        let local = localnet::detect().await;
+        let subnet = match (subnet, &local) {
+            (Some(subnet), _) if subnet.prefix_len < MIN_PREFIX_LEN => {
+                return Err(format!(
+                    "Subnet {} is too large to sweep, use /{} or smaller",
+                    subnet, MIN_PREFIX_LEN
+                )
+                .into());
+            }
+            (Some(subnet), _) => subnet,
+            (None, Some(local)) if local.subnet.prefix_len < MIN_PREFIX_LEN => {
+                let narrowed = Ipv4Subnet::new(local.address, MIN_PREFIX_LEN);
+                if !self.config.json_output {
+                    self.ui.show_info(&format!(
+                        "{} is too large to sweep, scanning {} around this machine",
+                        local.subnet, narrowed
+                    ))?;
+                }
+                narrowed
+            }
+            (None, Some(local)) => local.subnet,
+            (None, None) => {
+                return Err("Could not determine the local network, pass --subnet CIDR".into())
+            }
+        };
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_progress_bar(
+                subnet.host_count(),
+                &format!("📡 Sweeping {} for devices...", subnet),
+            ))
+        } else {
+            None
+        };
+
+        let mut answered: HashMap<Ipv4Addr, Vec<u16>> = futures::stream::iter(subnet.hosts())
+            .map(|address| {
+                let pb = pb.clone();
+                async move {
+                    let answer = probe_host(address).await;
+                    if let Some(pb) = pb {
+                        pb.inc(1);
+                    }
+                    answer.map(|open_ports| (address, open_ports))
+                }
+            })
+            .buffer_unordered(CONCURRENCY)
+            .filter_map(|answer| async move { answer })
+            .collect()
+            .await;
+
+        // Devices that ignored every probe still answered ARP
+        let macs: HashMap<Ipv4Addr, String> = localnet::neighbours()
+            .await
+            .into_iter()
+            .filter(|neighbour| subnet.contains(neighbour.address))
+            .map(|neighbour| (neighbour.address, neighbour.mac))
+            .collect();
+        for address in macs.keys() {
+            answered.entry(*address).or_default();
+        }
+        if let Some(local) = local
+            .as_ref()
+            .filter(|local| subnet.contains(local.address))
+        {
+            answered.entry(local.address).or_default();
+        }
+
+        if let Some(pb) = &pb {
+            pb.finish_with_message(format!("⟨⟨⟨ {} DEVICES FOUND ⟩⟩⟩", answered.len()));
+        }
+
+        let report = self
+            .build_report(subnet, local.as_ref(), answered, macs)
+            .await;
+        if !self.config.json_output {
+            self.display_results(&report)?;
+        }
+
+        Ok(report)
+    }
+
+    async fn build_report(
+        &self,
+        subnet: Ipv4Subnet,
+        local: Option<&LocalNetwork>,
+        answered: HashMap<Ipv4Addr, Vec<u16>>,
+        macs: HashMap<Ipv4Addr, String>,
+    ) -> LanScanReport {
+        let gateway = local.and_then(|local| local.gateway);
+        let prefixes: HashSet<String> = macs.values().map(|mac| oui(mac)).collect();
+        let vendors = load_vendors(&prefixes).await;
+
+        let mut devices: Vec<LanDevice> =
+            futures::future::join_all(answered.into_iter().map(|(address, open_ports)| {
+                let mac = macs.get(&address).cloned();
+                let vendor = mac.as_deref().and_then(|mac| vendor(mac, &vendors));
+                async move {
+                    LanDevice {
+                        address,
+                        hostname: ipinfo::reverse_dns(IpAddr::V4(address)).await,
+                        mac,
+                        vendor,
+                        open_ports,
+                        is_gateway: gateway == Some(address),
+                        is_self: local.is_some_and(|local| local.address == address),
+                    }
+                }
+            }))
+            .await;
+        devices.sort_by_key(|device| device.address);
+
+        LanScanReport {
+            interface: local.and_then(|local| local.interface.clone()),
+            subnet,
+            gateway,
+            devices,
+        }
+    }
+
+    fn display_results(&self, report: &LanScanReport) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+        println!(
+            "{} {}{}",
+            "🏠 Network:".bold(),
+            report.subnet.to_string().bright_cyan(),
+            report
+                .interface
+                .as_ref()
+                .map_or(String::new(), |interface| format!(" on {}", interface))
+        );
+        println!();
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(Row::new(vec![
+            Cell::new("Address").style_spec("Fb"),
+            Cell::new("MAC").style_spec("Fb"),
+            Cell::new("Vendor").style_spec("Fb"),
+            Cell::new("Hostname").style_spec("Fb"),
+            Cell::new("Open ports").style_spec("Fb"),
+        ]));
+
+        for device in &report.devices {
+            let address = if device.is_gateway {
+                format!("{} (gateway)", device.address)
+            } else if device.is_self {
+                format!("{} (this device)", device.address)
+            } else {
+                device.address.to_string()
+            };
+            let dash = || "-".to_string();
+            let open_ports = if device.open_ports.is_empty() {
+                dash()
+            } else {
+                device
+                    .open_ports
+                    .iter()
+                    .map(|port| port.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+
+            table.add_row(Row::new(vec![
+                Cell::new(&address),
+                Cell::new(&device.mac.clone().unwrap_or_else(dash)),
+                Cell::new(&device.vendor.clone().unwrap_or_else(dash)),
+                Cell::new(&device.hostname.clone().unwrap_or_else(dash)),
+                Cell::new(&open_ports),
+            ]));
+        }
+
+        print_table(&table)?;
+
+        let unidentified = report
+            .devices
+            .iter()
+            .filter(|device| {
+                !device.is_self && device.vendor.is_none() && device.hostname.is_none()
+            })
+            .count();
+        println!();
+        println!(
+            "{} {} devices on {}, {} without a known vendor or name",
+            "📊 Summary:".bold(),
+            report.devices.len(),
+            report.subnet,
+            unidentified
+        );
+        if unidentified > 0 {
+            println!(
+                "{}",
+                "💡 Unrecognised devices can be checked against your router's DHCP client list before blaming the ISP for slow speeds".bright_yellow()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Try the probe ports on `address`, returning those that accepted when the host answered at all
+async fn probe_host(address: Ipv4Addr) -> Option<Vec<u16>> {
+    let attempts = PROBE_PORTS.iter().map(|&port| async move {
+        let addr = SocketAddr::new(IpAddr::V4(address), port);
+        match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => Some(true),
+            // Refused means something at the address sent a reset
+            Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => Some(false),
+            _ => None,
+        }
+    });
+    let results = futures::future::join_all(attempts).await;
+
+    results.iter().any(Option::is_some).then(|| {
+        PROBE_PORTS
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| **result == Some(true))
+            .map(|(port, _)| *port)
+            .collect()
+    })
+}
+
+/// First three octets of a MAC as lowercase hex, the key OUI registries use
+fn oui(mac: &str) -> String {
+    mac.split(':').take(3).collect()
+}
+
+/// Vendor of `mac`, or a note that it's randomised
+fn vendor(mac: &str, vendors: &HashMap<String, String>) -> Option<String> {
+    let prefix = oui(mac);
+    if let Some(vendor) = vendors.get(&prefix) {
+        return Some(vendor.clone());
+    }
+    if let Some((_, vendor)) = KNOWN_OUIS.iter().find(|(known, _)| *known == prefix) {
+        return Some(vendor.to_string());
+    }
+
+    // Phones and laptops pick a locally administered address per network for privacy
+    let first = u8::from_str_radix(mac.get(..2)?, 16).ok()?;
+    (first & 0x02 != 0).then(|| "Private (randomised MAC)".to_string())
+}
+
+/// Vendors of the wanted `prefixes` from the first OUI registry found on disk
+async fn load_vendors(prefixes: &HashSet<String>) -> HashMap<String, String> {
+    if prefixes.is_empty() {
+        return HashMap::new();
+    }
+    for path in OUI_DATABASES {
+        if let Ok(registry) = tokio::fs::read_to_string(path).await {
+            return parse_oui_registry(&registry, prefixes);
+        }
+    }
+    HashMap::new()
+}
+
+/// Read `AA-BB-CC   (hex)   Vendor` (IEEE), `AABBCC<tab>Vendor` (arp-scan) and
+/// `AABBCC Vendor` (nmap) lines, keeping only `prefixes`
+fn parse_oui_registry(registry: &str, prefixes: &HashSet<String>) -> HashMap<String, String> {
+    registry
+        .lines()
+        .filter_map(|line| {
+            let (prefix, rest) = line.trim().split_once(char::is_whitespace)?;
+            let prefix = prefix.replace('-', "").to_lowercase();
+            if prefix.len() != 6 || !prefixes.contains(&prefix) {
+                return None;
+            }
+            let rest = rest.trim();
+            let vendor = rest
+                .strip_prefix("(hex)")
+                .or_else(|| rest.strip_prefix("(base 16)"))
+                .unwrap_or(rest)
+                .trim();
+            (!vendor.is_empty()).then(|| (prefix, vendor.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendor() {
+        let vendors = HashMap::from([("001a2b".to_string(), "Example Corp".to_string())]);
+
+        assert_eq!(
+            vendor("00:1a:2b:33:44:55", &vendors).as_deref(),
+            Some("Example Corp")
+        );
+        assert_eq!(
+            vendor("b8:27:eb:01:02:03", &vendors).as_deref(),
+            Some("Raspberry Pi Foundation")
+        );
+        assert_eq!(
+            vendor("3a:1f:00:01:02:03", &vendors).as_deref(),
+            Some("Private (randomised MAC)")
+        );
+        assert_eq!(vendor("00:11:22:33:44:55", &vendors), None);
+    }
+
+    #[test]
+    fn test_parse_oui_registry() {
+        let registry = "OUI/MA-L                                                    Organization\n\
+                        00-1A-2B   (hex)\t\tExample Corp\n\
+                        001A2B     (base 16)\t\tExample Corp\n\
+                        00-11-22   (hex)\t\tOther Inc\n\
+                        AABBCC\tArp Scan Vendor\n\
+                        DDEEFF Nmap Vendor\n";
+        let prefixes = HashSet::from([
+            "001a2b".to_string(),
+            "aabbcc".to_string(),
+            "ddeeff".to_string(),
+        ]);
+        let vendors = parse_oui_registry(registry, &prefixes);
+
+        assert_eq!(vendors.len(), 3);
+        assert_eq!(vendors["001a2b"], "Example Corp");
+        assert_eq!(vendors["aabbcc"], "Arp Scan Vendor");
+        assert_eq!(vendors["ddeeff"], "Nmap Vendor");
+    }
+}
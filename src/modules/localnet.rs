@@ -0,0 +1,1727 @@
+//! Local Network
+//!
+//! Finds this machine's IPv4 subnet and default gateway, and the neighbours the
+//! system has resolved link-layer addresses for. Linux is read from `/proc/net`,
+//! other systems from `route`, `ifconfig`, `ipconfig` and `arp` output.
+//!
+//! On Linux it also reads the error counters of the interface carrying the
+//! default route, from `/sys/class/net/<iface>/statistics`, and the TCP
+//! retransmissions from `/proc/net/snmp`. A snapshot is taken before and after
+//! the measured phases of a speed test and the difference stored with the result:
+//! a noisy cable or a duplex mismatch shows up in these counters long before it
+//! shows up in Mbps. Retransmissions are counted for the whole machine, which is
+//! close enough while the test saturates the link. The byte counters, read for a
+//! few seconds before a test starts, reveal a backup or a download already using
+//! the line.
+//!
+//! With an `[snmp]` table in `config.toml` the router's own WAN counters are
+//! polled over SNMPv2c, or SNMPv3 with authentication (SHA or SHA-256, no
+//! privacy), while the throughput phases run. Whether the WAN reached the plan
+//! tells an ISP limit apart from a Wi-Fi or LAN one.
+
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::modules::types::{LinkStats, SnmpAuthProtocol, SnmpSettings, WanRate, WanUsage};
+
+/// Traffic before a test above this, in either direction, counts as background traffic
+const BACKGROUND_TRAFFIC_MBPS: f64 = 1.0;
+
+/// Prefix assumed when the system doesn't say, by far the most common home subnet
+const DEFAULT_PREFIX_LEN: u8 = 24;
+
+/// An IPv4 network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Subnet {
+    pub network: Ipv4Addr,
+    pub prefix_len: u8,
+}
+
+impl Ipv4Subnet {
+    /// The subnet of `address`, with host bits cleared
+    pub fn new(address: Ipv4Addr, prefix_len: u8) -> Self {
+        let prefix_len = prefix_len.min(32);
+        Self {
+            network: Ipv4Addr::from(u32::from(address) & Self::mask(prefix_len)),
+            prefix_len,
+        }
+    }
+
+    fn mask(prefix_len: u8) -> u32 {
+        u32::MAX
+            .checked_shl(32 - u32::from(prefix_len))
+            .unwrap_or(0)
+    }
+
+    pub fn contains(&self, address: Ipv4Addr) -> bool {
+        u32::from(address) & Self::mask(self.prefix_len) == u32::from(self.network)
+    }
+
+    /// Usable host addresses, without the network and broadcast addresses
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let network = u32::from(self.network);
+        let size = 1u64 << (32 - u32::from(self.prefix_len));
+        let (first, last) = if size <= 2 {
+            (network, network + (size - 1) as u32)
+        } else {
+            (network + 1, network + (size - 2) as u32)
+        };
+        (first..=last).map(Ipv4Addr::from)
+    }
+
+    pub fn host_count(&self) -> u64 {
+        match 1u64 << (32 - u32::from(self.prefix_len)) {
+            size if size <= 2 => size,
+            size => size - 2,
+        }
+    }
+}
+
+impl std::fmt::Display for Ipv4Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+impl std::str::FromStr for Ipv4Subnet {
+    type Err = String;
+
+    /// Parse `ADDRESS/PREFIX`, e.g. `192.168.1.0/24`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid subnet '{}', expected ADDRESS/PREFIX (e.g. 192.168.1.0/24)",
+                s
+            )
+        };
+        let (address, prefix_len) = s.split_once('/').ok_or_else(invalid)?;
+        let address: Ipv4Addr = address.trim().parse().map_err(|_| invalid())?;
+        let prefix_len: u8 = prefix_len.trim().parse().map_err(|_| invalid())?;
+        if prefix_len > 32 {
+            return Err(invalid());
+        }
+        Ok(Self::new(address, prefix_len))
+    }
+}
+
+impl Serialize for Ipv4Subnet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// This machine's place on the local network
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalNetwork {
+    pub interface: Option<String>,
+    pub address: Ipv4Addr,
+    pub subnet: Ipv4Subnet,
+    pub gateway: Option<Ipv4Addr>,
+}
+
+/// A neighbour from the system's ARP table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbour {
+    pub address: Ipv4Addr,
+    /// Lowercase and colon separated, e.g. `aa:bb:cc:00:11:22`
+    pub mac: String,
+}
+
+/// The IPv4 address, subnet and gateway used to reach the internet
+pub async fn detect() -> Option<LocalNetwork> {
+    let address = outbound_address()?;
+    let (interface, gateway, prefix_len) = if cfg!(target_os = "linux") {
+        let routes = tokio::fs::read_to_string("/proc/net/route").await.ok()?;
+        parse_proc_route(&routes, address)
+    } else if cfg!(windows) {
+        let routes = run("route", &["print", "-4", "0.0.0.0"]).await;
+        let ipconfig = run("ipconfig", &[]).await;
+        (
+            None,
+            routes.as_deref().and_then(parse_route_print_gateway),
+            ipconfig
+                .as_deref()
+                .and_then(|output| parse_ipconfig_prefix(output, address)),
+        )
+    } else {
+        let route = run("route", &["-n", "get", "default"]).await;
+        let (interface, gateway) = route
+            .as_deref()
+            .map(parse_route_get)
+            .unwrap_or((None, None));
+        let prefix_len = match &interface {
+            Some(interface) => run("ifconfig", &[interface])
+                .await
+                .and_then(|output| parse_ifconfig_prefix(&output, address)),
+            None => None,
+        };
+        (interface, gateway, prefix_len)
+    };
+
+    Some(LocalNetwork {
+        interface,
+        address,
+        subnet: Ipv4Subnet::new(address, prefix_len.unwrap_or(DEFAULT_PREFIX_LEN)),
+        gateway,
+    })
+}
+
+/// Neighbours with a resolved MAC address in the ARP table
+pub async fn neighbours() -> Vec<Neighbour> {
+    if cfg!(target_os = "linux") {
+        tokio::fs::read_to_string("/proc/net/arp")
+            .await
+            .map(|table| parse_proc_arp(&table))
+            .unwrap_or_default()
+    } else {
+        let args: &[&str] = if cfg!(windows) { &["-a"] } else { &["-an"] };
+        run("arp", args)
+            .await
+            .map(|output| parse_arp_output(&output))
+            .unwrap_or_default()
+    }
+}
+
+/// Other devices on this machine's subnet that the ARP table saw recently, not
+/// counting the router. None when the local network can't be detected.
+pub async fn lan_device_count() -> Option<usize> {
+    let network = detect().await?;
+    let count = neighbours()
+        .await
+        .iter()
+        .filter(|neighbour| {
+            network.subnet.contains(neighbour.address)
+                && neighbour.address != network.address
+                && Some(neighbour.address) != network.gateway
+        })
+        .count();
+    Some(count)
+}
+
+/// Source address the system picks for internet traffic; connecting a UDP socket
+/// sends nothing
+fn outbound_address() -> Option<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(8, 8, 8, 8), 53)).ok()?;
+    match socket.local_addr().ok()? {
+        SocketAddr::V4(addr) if !addr.ip().is_unspecified() => Some(*addr.ip()),
+        _ => None,
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `/proc/net/route` holds addresses as little-endian hex
+fn proc_hex_address(hex: &str) -> Option<Ipv4Addr> {
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Ipv4Addr::from(value.to_le_bytes()))
+}
+
+/// Interface and gateway of the default route, and the prefix of the route covering `address`
+fn parse_proc_route(
+    routes: &str,
+    address: Ipv4Addr,
+) -> (Option<String>, Option<Ipv4Addr>, Option<u8>) {
+    let mut interface = None;
+    let mut gateway = None;
+    let mut prefix_len = None;
+
+    for line in routes.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (Some(destination), Some(via), Some(mask)) = (
+            fields.get(1).and_then(|hex| proc_hex_address(hex)),
+            fields.get(2).and_then(|hex| proc_hex_address(hex)),
+            fields.get(7).and_then(|hex| proc_hex_address(hex)),
+        ) else {
+            continue;
+        };
+
+        if destination.is_unspecified() && mask.is_unspecified() {
+            interface.get_or_insert_with(|| fields[0].to_string());
+            gateway.get_or_insert(via);
+        } else if !mask.is_unspecified() {
+            let len = u32::from(mask).leading_ones() as u8;
+            // The most specific on-link route containing our address is our subnet,
+            // host routes aside
+            if via.is_unspecified()
+                && len < 32
+                && Ipv4Subnet::new(destination, len).contains(address)
+                && prefix_len.is_none_or(|current| len > current)
+            {
+                prefix_len = Some(len);
+            }
+        }
+    }
+
+    (interface, gateway, prefix_len)
+}
+
+/// `route -n get default` on macOS and the BSDs
+fn parse_route_get(output: &str) -> (Option<String>, Option<Ipv4Addr>) {
+    let field = |name: &str| {
+        output.lines().find_map(|line| {
+            let (key, value) = line.trim().split_once(':')?;
+            (key.trim() == name).then(|| value.trim().to_string())
+        })
+    };
+    (
+        field("interface"),
+        field("gateway").and_then(|gateway| gateway.parse().ok()),
+    )
+}
+
+/// `ifconfig IFACE` prints `inet 192.168.1.23 netmask 0xffffff00 ...`
+fn parse_ifconfig_prefix(output: &str, address: Ipv4Addr) -> Option<u8> {
+    output.lines().find_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let inet = tokens.iter().position(|&token| token == "inet")?;
+        if tokens.get(inet + 1)?.parse::<Ipv4Addr>().ok()? != address {
+            return None;
+        }
+        let netmask = tokens.iter().position(|&token| token == "netmask")?;
+        let mask = tokens.get(netmask + 1)?;
+        let mask = match mask.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => u32::from(mask.parse::<Ipv4Addr>().ok()?),
+        };
+        Some(mask.leading_ones() as u8)
+    })
+}
+
+/// Gateway of the `0.0.0.0 0.0.0.0 GATEWAY INTERFACE METRIC` row of `route print`
+fn parse_route_print_gateway(output: &str) -> Option<Ipv4Addr> {
+    output.lines().find_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["0.0.0.0", "0.0.0.0", gateway, ..] => gateway.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// The `Subnet Mask` line following the adapter's `IPv4 Address` line in `ipconfig`
+fn parse_ipconfig_prefix(output: &str, address: Ipv4Addr) -> Option<u8> {
+    let address = address.to_string();
+    let mut lines = output.lines().skip_while(|line| {
+        !(line.trim_start().starts_with("IPv4") && line.contains(address.as_str()))
+    });
+    lines.next()?;
+    lines.take(3).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.contains("Subnet Mask")
+            .then(|| value.trim().parse::<Ipv4Addr>().ok())
+            .flatten()
+            .map(|mask| u32::from(mask).leading_ones() as u8)
+    })
+}
+
+fn parse_proc_arp(table: &str) -> Vec<Neighbour> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // Flags 0x0 marks an entry still waiting for an answer
+            if fields.get(2) == Some(&"0x0") {
+                return None;
+            }
+            neighbour(fields.first()?, fields.get(3)?)
+        })
+        .collect()
+}
+
+/// `arp -an` (`? (192.168.1.1) at a:b:c:d:e:f on en0`) and `arp -a` on Windows
+/// (`192.168.1.1   aa-bb-cc-dd-ee-ff   dynamic`)
+fn parse_arp_output(output: &str) -> Vec<Neighbour> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let address = tokens
+                .iter()
+                .find(|token| token.trim_matches(['(', ')']).parse::<Ipv4Addr>().is_ok())?;
+            let mac = tokens
+                .iter()
+                .find(|token| token.split([':', '-']).count() == 6)?;
+            neighbour(address.trim_matches(['(', ')']), mac)
+        })
+        .collect()
+}
+
+/// A neighbour with its MAC normalised, skipping incomplete and broadcast entries
+fn neighbour(address: &str, mac: &str) -> Option<Neighbour> {
+    let address: Ipv4Addr = address.parse().ok()?;
+    let octets = mac
+        .split([':', '-'])
+        .map(|octet| u8::from_str_radix(octet, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if octets.len() != 6 || octets.iter().all(|&o| o == 0) || octets.iter().all(|&o| o == 0xff) {
+        return None;
+    }
+
+    Some(Neighbour {
+        address,
+        mac: octets
+            .iter()
+            .map(|octet| format!("{:02x}", octet))
+            .collect::<Vec<_>>()
+            .join(":"),
+    })
+}
+
+/// Counters of the default route's interface at one moment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkSnapshot {
+    pub interface: String,
+    pub link_speed_mbps: Option<u32>,
+    pub duplex: Option<String>,
+    pub counters: LinkCounters,
+}
+
+/// Raw kernel counters, only ever compared with an earlier snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub rx_crc_errors: u64,
+    pub collisions: u64,
+    pub tcp_out_segs: u64,
+    pub tcp_retrans_segs: u64,
+}
+
+impl LinkSnapshot {
+    /// Read the counters, None on other platforms or without a default route
+    pub fn capture() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        let interface = default_interface(&std::fs::read_to_string("/proc/net/route").ok()?)?;
+        let dir = Path::new("/sys/class/net").join(&interface);
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+        let counter = |name: &str| {
+            read(&format!("statistics/{}", name))
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0)
+        };
+        let (tcp_out_segs, tcp_retrans_segs) = std::fs::read_to_string("/proc/net/snmp")
+            .ok()
+            .and_then(|snmp| tcp_segments(&snmp))
+            .unwrap_or_default();
+
+        Some(Self {
+            // Reading these fails with EINVAL on Wi-Fi and virtual interfaces
+            link_speed_mbps: read("speed")
+                .and_then(|speed| speed.trim().parse::<i64>().ok())
+                .filter(|speed| *speed > 0)
+                .and_then(|speed| u32::try_from(speed).ok()),
+            duplex: read("duplex")
+                .map(|duplex| duplex.trim().to_string())
+                .filter(|duplex| duplex == "full" || duplex == "half"),
+            counters: LinkCounters {
+                rx_bytes: counter("rx_bytes"),
+                tx_bytes: counter("tx_bytes"),
+                rx_packets: counter("rx_packets"),
+                tx_packets: counter("tx_packets"),
+                rx_errors: counter("rx_errors"),
+                tx_errors: counter("tx_errors"),
+                rx_dropped: counter("rx_dropped"),
+                tx_dropped: counter("tx_dropped"),
+                rx_crc_errors: counter("rx_crc_errors"),
+                collisions: counter("collisions"),
+                tcp_out_segs,
+                tcp_retrans_segs,
+            },
+            interface,
+        })
+    }
+
+    /// What changed between `self` and the later snapshot `after`, None when the
+    /// default route moved to another interface in between
+    pub fn delta(&self, after: &LinkSnapshot) -> Option<LinkStats> {
+        if self.interface != after.interface {
+            return None;
+        }
+
+        // Counters restart when a driver is reloaded, which reads as no change
+        let (before, after_counters) = (self.counters, after.counters);
+        let diff =
+            |field: fn(&LinkCounters) -> u64| field(&after_counters).saturating_sub(field(&before));
+        Some(LinkStats {
+            interface: after.interface.clone(),
+            link_speed_mbps: after.link_speed_mbps,
+            duplex: after.duplex.clone(),
+            rx_packets: diff(|c| c.rx_packets),
+            tx_packets: diff(|c| c.tx_packets),
+            rx_errors: diff(|c| c.rx_errors),
+            tx_errors: diff(|c| c.tx_errors),
+            rx_dropped: diff(|c| c.rx_dropped),
+            tx_dropped: diff(|c| c.tx_dropped),
+            rx_crc_errors: diff(|c| c.rx_crc_errors),
+            collisions: diff(|c| c.collisions),
+            tcp_segments_sent: diff(|c| c.tcp_out_segs),
+            tcp_retransmits: diff(|c| c.tcp_retrans_segs),
+        })
+    }
+}
+
+/// Traffic the default route's interface carried over a short window
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceTraffic {
+    pub interface: String,
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+}
+
+impl InterfaceTraffic {
+    /// Rates between two snapshots `elapsed` apart, None when the default route
+    /// moved to another interface in between
+    pub fn between(before: &LinkSnapshot, after: &LinkSnapshot, elapsed: Duration) -> Option<Self> {
+        let seconds = elapsed.as_secs_f64();
+        if before.interface != after.interface || seconds <= 0.0 {
+            return None;
+        }
+        let mbps = |field: fn(&LinkCounters) -> u64| {
+            field(&after.counters).saturating_sub(field(&before.counters)) as f64 * 8.0
+                / seconds
+                / 1_000_000.0
+        };
+        Some(Self {
+            interface: after.interface.clone(),
+            download_mbps: mbps(|c| c.rx_bytes),
+            upload_mbps: mbps(|c| c.tx_bytes),
+        })
+    }
+
+    /// Whether it is enough to skew a speed test, a backup or a video call rather
+    /// than the trickle of an idle machine
+    pub fn is_significant(&self) -> bool {
+        self.download_mbps.max(self.upload_mbps) >= BACKGROUND_TRAFFIC_MBPS
+    }
+}
+
+/// Traffic on the default route's interface over `window`, None on other
+/// platforms than Linux
+pub async fn sample_traffic(window: Duration) -> Option<InterfaceTraffic> {
+    let before = LinkSnapshot::capture()?;
+    let started = Instant::now();
+    tokio::time::sleep(window).await;
+    let after = LinkSnapshot::capture()?;
+    InterfaceTraffic::between(&before, &after, started.elapsed())
+}
+
+/// Interface of the IPv4 default route in `/proc/net/route`, the one with the
+/// lowest metric when there are several
+fn default_interface(route_table: &str) -> Option<String> {
+    route_table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (interface, destination, mask) = (fields.first()?, fields.get(1)?, fields.get(7)?);
+            let metric: u32 = fields.get(6)?.parse().ok()?;
+            (*destination == "00000000" && *mask == "00000000").then_some((metric, *interface))
+        })
+        .min_by_key(|(metric, _)| *metric)
+        .map(|(_, interface)| interface.to_string())
+}
+
+/// `OutSegs` and `RetransSegs` from the `Tcp:` lines of `/proc/net/snmp`
+fn tcp_segments(snmp: &str) -> Option<(u64, u64)> {
+    let mut tcp = snmp.lines().filter(|line| line.starts_with("Tcp:"));
+    let names: Vec<&str> = tcp.next()?.split_whitespace().collect();
+    let values: Vec<&str> = tcp.next()?.split_whitespace().collect();
+    let value = |name: &str| {
+        let index = names.iter().position(|field| *field == name)?;
+        values.get(index)?.parse().ok()
+    };
+    Some((value("OutSegs")?, value("RetransSegs")?))
+}
+
+/// Default SNMP agent port
+const SNMP_PORT: u16 = 161;
+const SNMP_TIMEOUT: Duration = Duration::from_secs(2);
+const SNMP_ATTEMPTS: u32 = 3;
+/// How often the router's WAN counters are read during a test
+const WAN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const IF_DESCR: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 2];
+const IF_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
+const IF_HC_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 6];
+const IF_HC_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 10];
+const IF_HIGH_SPEED: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 15];
+/// USM statistics a v3 agent reports errors with, under usmStats
+const USM_STATS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1];
+
+/// Parts of interface names routers commonly give their internet uplink
+const WAN_HINTS: [&str; 8] = [
+    "wan", "ppp", "dsl", "wwan", "lte", "internet", "dialer", "cable",
+];
+
+const GET_REQUEST: u8 = 0xa0;
+const GET_NEXT_REQUEST: u8 = 0xa1;
+const RESPONSE: u8 = 0xa2;
+const REPORT: u8 = 0xa8;
+
+/// A variable from an SNMP response
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnmpValue {
+    Integer(i64),
+    /// Counter32, Gauge32, TimeTicks or Counter64
+    Unsigned(u64),
+    Octets(Vec<u8>),
+    /// noSuchObject, noSuchInstance, endOfMibView or NULL
+    Missing,
+    Other,
+}
+
+impl SnmpValue {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            SnmpValue::Unsigned(value) => Some(*value),
+            SnmpValue::Integer(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// SNMPv3 engine of the agent and the key localized to it
+struct Engine {
+    id: Vec<u8>,
+    boots: i64,
+    time: i64,
+    discovered: Instant,
+    key: Vec<u8>,
+}
+
+enum Security {
+    Community(String),
+    User {
+        name: String,
+        protocol: SnmpAuthProtocol,
+        password: String,
+        engine: Option<Engine>,
+    },
+}
+
+/// Minimal SNMP manager: GET and GETNEXT over v2c, or v3 with authentication
+pub struct SnmpClient {
+    socket: tokio::net::UdpSocket,
+    security: Security,
+    request_id: i32,
+}
+
+impl SnmpClient {
+    /// Client for the agent at `host` as `settings` describe, v3 when a user is set
+    pub async fn connect(
+        host: &str,
+        settings: &SnmpSettings,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let port = settings.port.unwrap_or(SNMP_PORT);
+        let address = tokio::net::lookup_host((host, port))
+            .await?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", host))?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = tokio::net::UdpSocket::bind(local).await?;
+        socket.connect(address).await?;
+
+        let security = match &settings.username {
+            Some(name) => {
+                let password = settings
+                    .password
+                    .clone()
+                    .ok_or("SNMPv3 needs a password, set NETRUNNER_SNMP_PASSWORD")?;
+                // RFC 3414 keeps shorter passwords out of key localization
+                if password.len() < 8 {
+                    return Err("SNMPv3 passwords are at least 8 characters".into());
+                }
+                Security::User {
+                    name: name.clone(),
+                    protocol: settings.auth_protocol,
+                    password,
+                    engine: None,
+                }
+            }
+            None => Security::Community(
+                settings
+                    .community
+                    .clone()
+                    .unwrap_or_else(|| "public".to_string()),
+            ),
+        };
+
+        Ok(Self {
+            socket,
+            security,
+            request_id: rand::random::<i32>() & 0x3fff_ffff,
+        })
+    }
+
+    /// Values of `oids`, in order
+    pub async fn get(
+        &mut self,
+        oids: &[&[u32]],
+    ) -> Result<Vec<(Vec<u32>, SnmpValue)>, Box<dyn std::error::Error>> {
+        self.request(GET_REQUEST, oids).await
+    }
+
+    /// Index and value of every instance below `prefix`, e.g. a table column
+    pub async fn walk(
+        &mut self,
+        prefix: &[u32],
+    ) -> Result<Vec<(u32, SnmpValue)>, Box<dyn std::error::Error>> {
+        let mut values = Vec::new();
+        let mut oid = prefix.to_vec();
+        // Bounded in case an agent answers with OIDs that don't increase
+        for _ in 0..1024 {
+            let Some((next, value)) = self
+                .request(GET_NEXT_REQUEST, &[&oid])
+                .await?
+                .into_iter()
+                .next()
+            else {
+                break;
+            };
+            if !next.starts_with(prefix) || next <= oid || value == SnmpValue::Missing {
+                break;
+            }
+            if let Some(index) = next.last().filter(|_| next.len() == prefix.len() + 1) {
+                values.push((*index, value));
+            }
+            oid = next;
+        }
+        Ok(values)
+    }
+
+    async fn request(
+        &mut self,
+        pdu_type: u8,
+        oids: &[&[u32]],
+    ) -> Result<Vec<(Vec<u32>, SnmpValue)>, Box<dyn std::error::Error>> {
+        if matches!(&self.security, Security::User { engine: None, .. }) {
+            self.discover_engine().await?;
+        }
+
+        // One retry after the agent reports our clock for its engine as stale
+        for resync in [true, false] {
+            self.request_id = self.request_id.wrapping_add(1) & 0x3fff_ffff;
+            let id = self.request_id;
+            let message = self.encode(pdu_type, id, oids);
+            let (kind, bindings) = self.exchange(&message, id).await?;
+            if kind == RESPONSE {
+                return Ok(bindings);
+            }
+
+            let Some((oid, value)) = bindings.into_iter().next() else {
+                return Err("The router sent an empty report".into());
+            };
+            match oid.strip_prefix(USM_STATS).and_then(|rest| rest.first()) {
+                Some(2) if resync => {
+                    // notInTimeWindows: the report carried the current time
+                    self.discover_engine().await?;
+                }
+                Some(3) => return Err("The router doesn't know the SNMPv3 user".into()),
+                Some(5) => return Err("Wrong SNMPv3 password or auth_protocol".into()),
+                Some(1) => {
+                    return Err("The router requires SNMPv3 privacy, which isn't supported".into())
+                }
+                _ => {
+                    return Err(
+                        format!("The router refused the request ({:?} = {:?})", oid, value).into(),
+                    )
+                }
+            }
+        }
+        Err("The router kept rejecting the SNMPv3 time".into())
+    }
+
+    /// Learn the agent's engine ID, boots and time from the report an
+    /// unauthenticated request provokes, then localize the key to it
+    async fn discover_engine(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.request_id = self.request_id.wrapping_add(1) & 0x3fff_ffff;
+        let id = self.request_id;
+        let scoped = sequence(&[octets(b""), octets(b""), pdu(GET_REQUEST, id, &[])]);
+        let (message, _) = v3_message(id, 0x04, b"", 0, 0, "", 0, scoped);
+        let reply = self.receive_matching(&message, id).await?;
+        let parsed = parse_v3(&reply).ok_or("Malformed SNMPv3 report")?;
+
+        let Security::User {
+            protocol,
+            password,
+            engine,
+            ..
+        } = &mut self.security
+        else {
+            return Ok(());
+        };
+        *engine = Some(Engine {
+            key: localized_key(*protocol, password.as_bytes(), &parsed.engine_id),
+            id: parsed.engine_id,
+            boots: parsed.engine_boots,
+            time: parsed.engine_time,
+            discovered: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn encode(&self, pdu_type: u8, id: i32, oids: &[&[u32]]) -> Vec<u8> {
+        match &self.security {
+            Security::Community(community) => sequence(&[
+                integer(1),
+                octets(community.as_bytes()),
+                pdu(pdu_type, id, oids),
+            ]),
+            Security::User {
+                name,
+                protocol,
+                engine: Some(engine),
+                ..
+            } => {
+                let scoped = sequence(&[octets(&engine.id), octets(b""), pdu(pdu_type, id, oids)]);
+                let time = engine.time + engine.discovered.elapsed().as_secs() as i64;
+                let (mut message, auth_offset) = v3_message(
+                    id,
+                    0x05,
+                    &engine.id,
+                    engine.boots,
+                    time,
+                    name,
+                    protocol.mac_len(),
+                    scoped,
+                );
+                let mac = authenticate(*protocol, &engine.key, &message);
+                message[auth_offset..auth_offset + mac.len()].copy_from_slice(&mac);
+                message
+            }
+            Security::User { engine: None, .. } => Vec::new(),
+        }
+    }
+
+    /// Send `message` and decode the answer to request `id`
+    async fn exchange(
+        &self,
+        message: &[u8],
+        id: i32,
+    ) -> Result<(u8, Vec<(Vec<u32>, SnmpValue)>), Box<dyn std::error::Error>> {
+        let reply = self.receive_matching(message, id).await?;
+        match &self.security {
+            Security::Community(_) => {
+                let (_, message, _) = read_tlv(&reply)
+                    .filter(|(tag, ..)| *tag == 0x30)
+                    .ok_or("Malformed SNMP response")?;
+                let (_, rest) = expect(message, 0x02).ok_or("Malformed SNMP response")?;
+                let (_, rest) = expect(rest, 0x04).ok_or("Malformed SNMP response")?;
+                let (kind, _, bindings) = parse_pdu(rest)?;
+                Ok((kind, bindings))
+            }
+            Security::User {
+                protocol,
+                engine: Some(engine),
+                ..
+            } => {
+                let parsed = parse_v3(&reply).ok_or("Malformed SNMPv3 response")?;
+                if parsed.authenticated {
+                    let mut unsigned = reply.clone();
+                    let range = parsed.auth_range.clone();
+                    unsigned[range.clone()].fill(0);
+                    if authenticate(*protocol, &engine.key, &unsigned) != reply[range] {
+                        return Err("SNMPv3 response failed authentication".into());
+                    }
+                } else if parsed.kind != REPORT {
+                    return Err("Unauthenticated SNMPv3 response".into());
+                }
+                Ok((parsed.kind, parsed.bindings))
+            }
+            Security::User { engine: None, .. } => Err("SNMPv3 engine not discovered".into()),
+        }
+    }
+
+    /// Send `message` until a datagram answering request `id` arrives
+    async fn receive_matching(
+        &self,
+        message: &[u8],
+        id: i32,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer = vec![0u8; 65536];
+        for _ in 0..SNMP_ATTEMPTS {
+            self.socket.send(message).await?;
+            let deadline = tokio::time::Instant::now() + SNMP_TIMEOUT;
+            while let Ok(received) =
+                tokio::time::timeout_at(deadline, self.socket.recv(&mut buffer)).await
+            {
+                let reply = &buffer[..received?];
+                if response_id(reply) == Some(id as i64) {
+                    return Ok(reply.to_vec());
+                }
+            }
+        }
+        Err("The router did not answer SNMP requests".into())
+    }
+}
+
+impl SnmpAuthProtocol {
+    /// Bytes of the HMAC sent in a message
+    fn mac_len(self) -> usize {
+        match self {
+            SnmpAuthProtocol::Sha => 12,
+            SnmpAuthProtocol::Sha256 => 24,
+        }
+    }
+
+    fn digest(self) -> &'static ring::digest::Algorithm {
+        match self {
+            SnmpAuthProtocol::Sha => &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+            SnmpAuthProtocol::Sha256 => &ring::digest::SHA256,
+        }
+    }
+
+    fn hmac(self) -> ring::hmac::Algorithm {
+        match self {
+            SnmpAuthProtocol::Sha => ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            SnmpAuthProtocol::Sha256 => ring::hmac::HMAC_SHA256,
+        }
+    }
+}
+
+/// RFC 3414 password to key: hash a megabyte of the repeated password, then
+/// bind the result to the agent's engine ID
+fn localized_key(protocol: SnmpAuthProtocol, password: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    let mut context = ring::digest::Context::new(protocol.digest());
+    let mut repeated = password.iter().cycle();
+    for _ in 0..(1024 * 1024 / 64) {
+        let chunk: Vec<u8> = repeated.by_ref().take(64).copied().collect();
+        context.update(&chunk);
+    }
+    let user_key = context.finish();
+
+    let mut context = ring::digest::Context::new(protocol.digest());
+    context.update(user_key.as_ref());
+    context.update(engine_id);
+    context.update(user_key.as_ref());
+    context.finish().as_ref().to_vec()
+}
+
+fn authenticate(protocol: SnmpAuthProtocol, key: &[u8], message: &[u8]) -> Vec<u8> {
+    let key = ring::hmac::Key::new(protocol.hmac(), key);
+    ring::hmac::sign(&key, message).as_ref()[..protocol.mac_len()].to_vec()
+}
+
+/// A v3 message with the MAC zeroed, and where the MAC goes
+#[allow(clippy::too_many_arguments)]
+fn v3_message(
+    id: i32,
+    flags: u8,
+    engine_id: &[u8],
+    boots: i64,
+    time: i64,
+    user: &str,
+    mac_len: usize,
+    scoped_pdu: Vec<u8>,
+) -> (Vec<u8>, usize) {
+    let header = sequence(&[
+        integer(id as i64),
+        integer(65507),
+        octets(&[flags]),
+        integer(3),
+    ]);
+    let before_mac = [
+        octets(engine_id),
+        integer(boots),
+        integer(time),
+        octets(user.as_bytes()),
+    ]
+    .concat();
+    let parameters = sequence(&[before_mac.clone(), octets(&vec![0; mac_len]), octets(b"")]);
+    // The MAC's own tag and length are two bytes, as is the empty privacy parameter
+    let parameters_header = parameters.len() - (before_mac.len() + 2 + mac_len + 2);
+    let security = octets(&parameters);
+    let security_header = security.len() - parameters.len();
+    let prefix = [integer(3), header].concat();
+
+    let mac_in_body = prefix.len() + security_header + parameters_header + before_mac.len() + 2;
+    let body_len = prefix.len() + security.len() + scoped_pdu.len();
+    let message = sequence(&[prefix, security, scoped_pdu]);
+    let mac_offset = message.len() - body_len + mac_in_body;
+    (message, mac_offset)
+}
+
+struct V3Reply {
+    kind: u8,
+    bindings: Vec<(Vec<u32>, SnmpValue)>,
+    engine_id: Vec<u8>,
+    engine_boots: i64,
+    engine_time: i64,
+    authenticated: bool,
+    auth_range: std::ops::Range<usize>,
+}
+
+fn parse_v3(reply: &[u8]) -> Option<V3Reply> {
+    let (_, message, _) = read_tlv(reply).filter(|(tag, ..)| *tag == 0x30)?;
+    let (version, rest) = expect(message, 0x02)?;
+    if decode_integer(version)? != 3 {
+        return None;
+    }
+    let (header, rest) = expect(rest, 0x30)?;
+    let (_, header) = expect(header, 0x02)?;
+    let (_, header) = expect(header, 0x02)?;
+    let (flags, _) = expect(header, 0x04)?;
+    let (security, rest) = expect(rest, 0x04)?;
+    let (security, _) = expect(security, 0x30)?;
+    let (engine_id, security) = expect(security, 0x04)?;
+    let (engine_boots, security) = expect(security, 0x02)?;
+    let (engine_time, security) = expect(security, 0x02)?;
+    let (_, security) = expect(security, 0x04)?;
+    let (mac, _) = expect(security, 0x04)?;
+    let (scoped, _) = expect(rest, 0x30)?;
+    let (_, scoped) = expect(scoped, 0x04)?;
+    let (_, scoped) = expect(scoped, 0x04)?;
+    let (kind, _, bindings) = parse_pdu(scoped).ok()?;
+
+    let mac_start = mac.as_ptr() as usize - reply.as_ptr() as usize;
+    Some(V3Reply {
+        kind,
+        bindings,
+        engine_id: engine_id.to_vec(),
+        engine_boots: decode_integer(engine_boots)?,
+        engine_time: decode_integer(engine_time)?,
+        authenticated: flags.first().is_some_and(|flags| flags & 0x01 != 0),
+        auth_range: mac_start..mac_start + mac.len(),
+    })
+}
+
+/// Request ID of a v2c or v3 reply; for v3 the message ID, which is sent equal
+fn response_id(reply: &[u8]) -> Option<i64> {
+    let (_, message, _) = read_tlv(reply).filter(|(tag, ..)| *tag == 0x30)?;
+    let (version, rest) = expect(message, 0x02)?;
+    if decode_integer(version)? == 3 {
+        let (header, _) = expect(rest, 0x30)?;
+        let (id, _) = expect(header, 0x02)?;
+        return decode_integer(id);
+    }
+    let (_, rest) = expect(rest, 0x04)?;
+    let (_, pdu, _) = read_tlv(rest)?;
+    let (id, _) = expect(pdu, 0x02)?;
+    decode_integer(id)
+}
+
+fn pdu(pdu_type: u8, id: i32, oids: &[&[u32]]) -> Vec<u8> {
+    let bindings: Vec<Vec<u8>> = oids
+        .iter()
+        .map(|oid| sequence(&[object_id(oid), vec![0x05, 0x00]]))
+        .collect();
+    tlv(
+        pdu_type,
+        &[
+            integer(id as i64),
+            integer(0),
+            integer(0),
+            sequence(&bindings),
+        ]
+        .concat(),
+    )
+}
+
+/// Type, request ID and variable bindings of a PDU
+#[allow(clippy::type_complexity)]
+fn parse_pdu(bytes: &[u8]) -> Result<(u8, i64, Vec<(Vec<u32>, SnmpValue)>), String> {
+    let malformed = || "Malformed SNMP PDU".to_string();
+    let (kind, pdu, _) = read_tlv(bytes).ok_or_else(malformed)?;
+    let (id, rest) = expect(pdu, 0x02).ok_or_else(malformed)?;
+    let (status, rest) = expect(rest, 0x02).ok_or_else(malformed)?;
+    let (_, rest) = expect(rest, 0x02).ok_or_else(malformed)?;
+    let (mut list, _) = expect(rest, 0x30).ok_or_else(malformed)?;
+
+    let status = decode_integer(status).ok_or_else(malformed)?;
+    if status != 0 {
+        return Err(format!("The router answered with SNMP error {}", status));
+    }
+    let mut bindings = Vec::new();
+    while !list.is_empty() {
+        let (binding, rest) = expect(list, 0x30).ok_or_else(malformed)?;
+        let (oid, value) = expect(binding, 0x06).ok_or_else(malformed)?;
+        let (tag, value, _) = read_tlv(value).ok_or_else(malformed)?;
+        let value = match tag {
+            0x02 => decode_integer(value).map_or(SnmpValue::Other, SnmpValue::Integer),
+            0x04 => SnmpValue::Octets(value.to_vec()),
+            // Counter32, Gauge32, TimeTicks, Counter64
+            0x41 | 0x42 | 0x43 | 0x46 => {
+                decode_unsigned(value).map_or(SnmpValue::Other, SnmpValue::Unsigned)
+            }
+            0x05 | 0x80 | 0x81 | 0x82 => SnmpValue::Missing,
+            _ => SnmpValue::Other,
+        };
+        bindings.push((decode_oid(oid).ok_or_else(malformed)?, value));
+        list = rest;
+    }
+    Ok((kind, decode_integer(id).ok_or_else(malformed)?, bindings))
+}
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if value.len() < 0x80 {
+        out.push(value.len() as u8);
+    } else {
+        let len = (value.len() as u32).to_be_bytes();
+        let skip = len.iter().take_while(|byte| **byte == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&len[skip..]);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+fn sequence(parts: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x30, &parts.concat())
+}
+
+fn octets(value: &[u8]) -> Vec<u8> {
+    tlv(0x04, value)
+}
+
+/// Shortest two's complement form
+fn integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    tlv(0x02, &bytes[start..])
+}
+
+fn object_id(arcs: &[u32]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    if let [first, second, rest @ ..] = arcs {
+        encoded.push((first * 40 + second) as u8);
+        for arc in rest {
+            let mut groups = vec![(arc & 0x7f) as u8];
+            let mut value = arc >> 7;
+            while value > 0 {
+                groups.push((value & 0x7f) as u8 | 0x80);
+                value >>= 7;
+            }
+            encoded.extend(groups.iter().rev());
+        }
+    }
+    tlv(0x06, &encoded)
+}
+
+/// Tag, value and what follows of the first TLV in `bytes`
+fn read_tlv(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = bytes.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, byte| len << 8 | *byte as usize);
+        (len, &rest[count..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+fn expect(bytes: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    read_tlv(bytes)
+        .filter(|(found, ..)| *found == tag)
+        .map(|(_, value, rest)| (value, rest))
+}
+
+fn decode_integer(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+    let fill = if value[0] & 0x80 != 0 { 0xff } else { 0 };
+    let mut bytes = [fill; 8];
+    bytes[8 - value.len()..].copy_from_slice(value);
+    Some(i64::from_be_bytes(bytes))
+}
+
+/// Counters may carry a leading zero byte to stay positive
+fn decode_unsigned(value: &[u8]) -> Option<u64> {
+    let value = match value {
+        [0, rest @ ..] if rest.len() == 8 => rest,
+        _ => value,
+    };
+    (value.len() <= 8).then(|| {
+        value
+            .iter()
+            .fold(0, |total, byte| total << 8 | *byte as u64)
+    })
+}
+
+fn decode_oid(value: &[u8]) -> Option<Vec<u32>> {
+    let (&first, rest) = value.split_first()?;
+    let top = (first / 40).min(2);
+    let mut arcs = vec![top as u32, (first - top * 40) as u32];
+    let mut arc: u32 = 0;
+    for byte in rest {
+        arc = arc.checked_mul(128)? | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    Some(arcs)
+}
+
+/// The router interface carrying internet traffic
+#[derive(Debug, Clone, PartialEq)]
+pub struct WanInterface {
+    pub index: u32,
+    pub name: String,
+    pub speed_mbps: Option<u64>,
+}
+
+/// Find the WAN interface: the configured name, or else the first whose name
+/// looks like an uplink
+pub async fn find_wan_interface(
+    client: &mut SnmpClient,
+    configured: Option<&str>,
+) -> Result<WanInterface, Box<dyn std::error::Error>> {
+    let mut names = client.walk(IF_NAME).await?;
+    if names.is_empty() {
+        names = client.walk(IF_DESCR).await?;
+    }
+    let names: Vec<(u32, String)> = names
+        .into_iter()
+        .filter_map(|(index, value)| match value {
+            SnmpValue::Octets(name) => Some((index, String::from_utf8_lossy(&name).into_owned())),
+            _ => None,
+        })
+        .collect();
+
+    let (index, name) = pick_wan_interface(&names, configured)?;
+    let speed = IF_HIGH_SPEED
+        .iter()
+        .copied()
+        .chain([index])
+        .collect::<Vec<_>>();
+    let speed_mbps = client
+        .get(&[&speed])
+        .await?
+        .first()
+        .and_then(|(_, value)| value.as_u64())
+        .filter(|speed| *speed > 0);
+    Ok(WanInterface {
+        index,
+        name,
+        speed_mbps,
+    })
+}
+
+fn pick_wan_interface(
+    names: &[(u32, String)],
+    configured: Option<&str>,
+) -> Result<(u32, String), String> {
+    let found = match configured {
+        Some(wanted) => names
+            .iter()
+            .find(|(index, name)| name.eq_ignore_ascii_case(wanted) || index.to_string() == wanted),
+        None => names.iter().find(|(_, name)| {
+            let name = name.to_ascii_lowercase();
+            WAN_HINTS.iter().any(|hint| name.contains(hint))
+        }),
+    };
+    found.cloned().ok_or_else(|| {
+        let listed = names
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        match configured {
+            Some(wanted) => format!("The router has no interface '{}', it has: {}", wanted, listed),
+            None => format!(
+                "Could not tell the router's WAN interface, set `interface` in the [snmp] table to one of: {}",
+                listed
+            ),
+        }
+    })
+}
+
+/// Octet counters of the WAN interface at one poll
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WanSample {
+    pub at: Instant,
+    pub in_octets: u64,
+    pub out_octets: u64,
+}
+
+/// Polls the router's WAN counters in the background until dropped
+pub struct WanPoller {
+    pub interface: WanInterface,
+    samples: Arc<Mutex<Vec<WanSample>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl WanPoller {
+    /// Find the WAN interface of the router in `settings`, or of the default
+    /// gateway, and start polling it
+    pub async fn start(settings: &SnmpSettings) -> Result<Self, Box<dyn std::error::Error>> {
+        let host = match &settings.host {
+            Some(host) => host.clone(),
+            None => detect()
+                .await
+                .and_then(|network| network.gateway)
+                .ok_or("No default gateway found, set `host` in the [snmp] table")?
+                .to_string(),
+        };
+        let mut client = SnmpClient::connect(&host, settings).await?;
+        let interface = find_wan_interface(&mut client, settings.interface.as_deref()).await?;
+
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let in_octets: Vec<u32> = IF_HC_IN_OCTETS
+            .iter()
+            .copied()
+            .chain([interface.index])
+            .collect();
+        let out_octets: Vec<u32> = IF_HC_OUT_OCTETS
+            .iter()
+            .copied()
+            .chain([interface.index])
+            .collect();
+        let recorded = samples.clone();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(WAN_POLL_INTERVAL);
+            loop {
+                ticks.tick().await;
+                let Ok(values) = client.get(&[&in_octets, &out_octets]).await else {
+                    continue;
+                };
+                if let [(_, received), (_, sent)] = values.as_slice() {
+                    if let (Some(in_octets), Some(out_octets)) = (received.as_u64(), sent.as_u64())
+                    {
+                        recorded
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .push(WanSample {
+                                at: Instant::now(),
+                                in_octets,
+                                out_octets,
+                            });
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            interface,
+            samples,
+            task,
+        })
+    }
+
+    /// WAN traffic inbound during `download` and outbound during `upload`
+    pub fn usage(&self, download: (Instant, Instant), upload: (Instant, Instant)) -> WanUsage {
+        let samples = self
+            .samples
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        WanUsage {
+            interface: self.interface.name.clone(),
+            port_speed_mbps: self.interface.speed_mbps,
+            download: wan_rate(&samples, download, |sample| sample.in_octets),
+            upload: wan_rate(&samples, upload, |sample| sample.out_octets),
+        }
+    }
+}
+
+impl Drop for WanPoller {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Average and peak rate of a counter over the polls inside `window`
+fn wan_rate(
+    samples: &[WanSample],
+    (from, to): (Instant, Instant),
+    counter: fn(&WanSample) -> u64,
+) -> Option<WanRate> {
+    let inside: Vec<&WanSample> = samples
+        .iter()
+        .filter(|sample| sample.at >= from && sample.at <= to)
+        .collect();
+    let mbps = |first: &WanSample, last: &WanSample| {
+        let seconds = last.at.duration_since(first.at).as_secs_f64();
+        // A counter reset reads as no traffic rather than a huge jump
+        let octets = counter(last).saturating_sub(counter(first));
+        (seconds > 0.0).then(|| octets as f64 * 8.0 / seconds / 1_000_000.0)
+    };
+
+    let (first, last) = (inside.first()?, inside.last()?);
+    let average_mbps = mbps(first, last)?;
+    let peak_mbps = inside
+        .windows(2)
+        .filter_map(|pair| mbps(pair[0], pair[1]))
+        .fold(0.0, f64::max);
+    Some(WanRate {
+        average_mbps,
+        peak_mbps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet() {
+        let subnet: Ipv4Subnet = "192.168.1.77/24".parse().unwrap();
+        assert_eq!(subnet.to_string(), "192.168.1.0/24");
+        assert!(subnet.contains(Ipv4Addr::new(192, 168, 1, 200)));
+        assert!(!subnet.contains(Ipv4Addr::new(192, 168, 2, 1)));
+        assert_eq!(subnet.host_count(), 254);
+
+        let hosts: Vec<Ipv4Addr> = "10.0.0.0/30"
+            .parse::<Ipv4Subnet>()
+            .unwrap()
+            .hosts()
+            .collect();
+        assert_eq!(
+            hosts,
+            [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]
+        );
+
+        assert!("192.168.1.0".parse::<Ipv4Subnet>().is_err());
+        assert!("192.168.1.0/33".parse::<Ipv4Subnet>().is_err());
+    }
+
+    #[test]
+    fn test_parse_proc_route() {
+        let routes =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                      eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+                      eth0\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n\
+                      docker0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0\n";
+
+        assert_eq!(
+            parse_proc_route(routes, Ipv4Addr::new(192, 168, 1, 23)),
+            (
+                Some("eth0".to_string()),
+                Some(Ipv4Addr::new(192, 168, 1, 1)),
+                Some(24)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_bsd_and_windows_output() {
+        let route = "   route to: default\ndestination: default\n       mask: default\n    gateway: 192.168.1.1\n  interface: en0\n";
+        assert_eq!(
+            parse_route_get(route),
+            (Some("en0".to_string()), Some(Ipv4Addr::new(192, 168, 1, 1)))
+        );
+
+        let ifconfig = "en0: flags=8863<UP,BROADCAST> mtu 1500\n\tinet6 fe80::1%en0 prefixlen 64\n\tinet 192.168.1.23 netmask 0xffffff00 broadcast 192.168.1.255\n";
+        assert_eq!(
+            parse_ifconfig_prefix(ifconfig, Ipv4Addr::new(192, 168, 1, 23)),
+            Some(24)
+        );
+
+        let route_print = "Active Routes:\nNetwork Destination        Netmask          Gateway       Interface  Metric\n          0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.23     25\n";
+        assert_eq!(
+            parse_route_print_gateway(route_print),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+
+        let ipconfig = "Ethernet adapter Ethernet:\r\n\r\n   IPv4 Address. . . . . . . . . . . : 192.168.1.23\r\n   Subnet Mask . . . . . . . . . . . : 255.255.252.0\r\n   Default Gateway . . . . . . . . . : 192.168.1.1\r\n";
+        assert_eq!(
+            parse_ipconfig_prefix(ipconfig, Ipv4Addr::new(192, 168, 1, 23)),
+            Some(22)
+        );
+    }
+
+    #[test]
+    fn test_parse_arp_tables() {
+        let proc_arp = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                        192.168.1.1      0x1         0x2         AA:BB:CC:00:11:22     *        eth0\n\
+                        192.168.1.9      0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert_eq!(
+            parse_proc_arp(proc_arp),
+            [Neighbour {
+                address: Ipv4Addr::new(192, 168, 1, 1),
+                mac: "aa:bb:cc:00:11:22".to_string(),
+            }]
+        );
+
+        let bsd = "? (192.168.1.1) at a:bb:c:0:11:22 on en0 ifscope [ethernet]\n\
+                   ? (192.168.1.9) at (incomplete) on en0 ifscope [ethernet]\n\
+                   ? (192.168.1.255) at ff:ff:ff:ff:ff:ff on en0 ifscope [ethernet]\n";
+        assert_eq!(
+            parse_arp_output(bsd),
+            [Neighbour {
+                address: Ipv4Addr::new(192, 168, 1, 1),
+                mac: "0a:bb:0c:00:11:22".to_string(),
+            }]
+        );
+
+        let windows = "Interface: 192.168.1.23 --- 0x4\r\n  Internet Address      Physical Address      Type\r\n  192.168.1.1           aa-bb-cc-00-11-22     dynamic\r\n";
+        assert_eq!(parse_arp_output(windows).len(), 1);
+    }
+
+    #[test]
+    fn test_default_interface() {
+        let table =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+        assert_eq!(default_interface(table).as_deref(), Some("eth0"));
+        assert_eq!(default_interface(table.lines().next().unwrap()), None);
+    }
+
+    #[test]
+    fn test_tcp_segments() {
+        let snmp = "Ip: Forwarding DefaultTTL InReceives
+Ip: 1 64 123456
+Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens PassiveOpens AttemptFails EstabResets CurrEstab InSegs OutSegs RetransSegs InErrs OutRsts InCsumErrors
+Tcp: 1 200 120000 -1 4521 12 33 41 9 982311 874520 1312 0 210 0
+Udp: InDatagrams NoPorts
+Udp: 5120 3
+";
+        assert_eq!(tcp_segments(snmp), Some((874520, 1312)));
+        assert_eq!(tcp_segments("Ip: Forwarding\nIp: 1\n"), None);
+    }
+
+    #[test]
+    fn test_delta() {
+        let snapshot = |rx_errors, tcp_out_segs, tcp_retrans_segs| LinkSnapshot {
+            interface: "eth0".to_string(),
+            link_speed_mbps: Some(1000),
+            duplex: Some("half".to_string()),
+            counters: LinkCounters {
+                rx_packets: 1_000 + tcp_out_segs,
+                rx_errors,
+                tcp_out_segs,
+                tcp_retrans_segs,
+                ..Default::default()
+            },
+        };
+
+        let stats = snapshot(4, 10_000, 100)
+            .delta(&snapshot(16, 30_000, 500))
+            .unwrap();
+        assert_eq!(stats.rx_packets, 20_000);
+        assert_eq!(stats.rx_errors, 12);
+        assert_eq!(stats.error_count(), 12);
+        assert_eq!(stats.retransmit_percent(), 2.0);
+        assert!(stats.is_half_duplex());
+
+        // A reset counter doesn't wrap around
+        let reset = snapshot(16, 30_000, 500).delta(&snapshot(0, 5, 0)).unwrap();
+        assert_eq!(reset.rx_errors, 0);
+        assert_eq!(reset.retransmit_percent(), 0.0);
+
+        let moved = LinkSnapshot {
+            interface: "wlan0".to_string(),
+            ..snapshot(0, 0, 0)
+        };
+        assert_eq!(snapshot(0, 0, 0).delta(&moved), None);
+    }
+
+    #[test]
+    fn test_interface_traffic() {
+        let snapshot = |interface: &str, rx_bytes, tx_bytes| LinkSnapshot {
+            interface: interface.to_string(),
+            counters: LinkCounters {
+                rx_bytes,
+                tx_bytes,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let elapsed = Duration::from_secs(2);
+
+        let traffic = InterfaceTraffic::between(
+            &snapshot("eth0", 1_000, 0),
+            &snapshot("eth0", 2_501_000, 50_000),
+            elapsed,
+        )
+        .unwrap();
+        assert_eq!(traffic.download_mbps, 10.0);
+        assert_eq!(traffic.upload_mbps, 0.2);
+        assert!(traffic.is_significant());
+
+        let idle = InterfaceTraffic::between(
+            &snapshot("eth0", 0, 0),
+            &snapshot("eth0", 20_000, 10_000),
+            elapsed,
+        )
+        .unwrap();
+        assert!(!idle.is_significant());
+        assert_eq!(
+            InterfaceTraffic::between(&snapshot("eth0", 0, 0), &snapshot("wlan0", 0, 0), elapsed),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ber() {
+        assert_eq!(integer(0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer(128), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(integer(-1), vec![0x02, 0x01, 0xff]);
+        for value in [0, 127, 128, 65507, -129, i32::MAX as i64] {
+            let encoded = integer(value);
+            assert_eq!(decode_integer(&encoded[2..]), Some(value));
+        }
+
+        let oid = object_id(IF_HC_IN_OCTETS);
+        assert_eq!(&oid[..4], &[0x06, 0x0a, 0x2b, 0x06]);
+        assert_eq!(decode_oid(&oid[2..]).unwrap(), IF_HC_IN_OCTETS);
+        let large = [1, 3, 6, 1, 4, 1, 2021, 200_000];
+        assert_eq!(decode_oid(&object_id(&large)[2..]).unwrap(), large);
+
+        let long = octets(&[7; 300]);
+        assert_eq!(&long[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        let (tag, value, rest) = read_tlv(&long).unwrap();
+        assert_eq!((tag, value.len(), rest.len()), (0x04, 300, 0));
+        assert_eq!(read_tlv(&long[..100]), None);
+
+        assert_eq!(
+            decode_unsigned(&[0x00, 0xff, 0, 0, 0, 0, 0, 0, 1]),
+            Some(0xff00_0000_0000_0001)
+        );
+    }
+
+    #[test]
+    fn test_localized_key() {
+        // RFC 3414 appendix A.3.2
+        let key = localized_key(
+            SnmpAuthProtocol::Sha,
+            b"maplesyrup",
+            &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+        );
+        let hex: String = key.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(hex, "6695febc9288e36282235fc7151f128497b38f3f");
+    }
+
+    #[test]
+    fn test_v3_message_mac_offset() {
+        let scoped = sequence(&[
+            octets(b"engine"),
+            octets(b""),
+            pdu(GET_REQUEST, 7, &[IF_NAME]),
+        ]);
+        let (message, offset) = v3_message(7, 0x05, b"engine", 3, 1234, "netrunner", 12, scoped);
+        let parsed = parse_v3(&message).unwrap();
+        assert_eq!(parsed.auth_range, offset..offset + 12);
+        assert_eq!(parsed.engine_id, b"engine");
+        assert_eq!(parsed.engine_time, 1234);
+        assert!(parsed.authenticated);
+        assert_eq!(response_id(&message), Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_snmp_get_v2c() {
+        let agent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = agent.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1500];
+            let (len, from) = agent.recv_from(&mut buffer).await.unwrap();
+            let (_, message, _) = read_tlv(&buffer[..len]).unwrap();
+            let (_, rest) = expect(message, 0x02).unwrap();
+            let (community, rest) = expect(rest, 0x04).unwrap();
+            assert_eq!(community, b"s3cret");
+            let (kind, id, bindings) = parse_pdu(rest).unwrap();
+            assert_eq!(kind, GET_REQUEST);
+
+            let counter = tlv(0x46, &[0x00, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+            let reply = sequence(&[
+                integer(1),
+                octets(b"s3cret"),
+                tlv(
+                    RESPONSE,
+                    &[
+                        integer(id),
+                        integer(0),
+                        integer(0),
+                        sequence(&[sequence(&[object_id(&bindings[0].0), counter])]),
+                    ]
+                    .concat(),
+                ),
+            ]);
+            agent.send_to(&reply, from).await.unwrap();
+        });
+
+        let settings = SnmpSettings {
+            port: Some(port),
+            community: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let mut client = SnmpClient::connect("127.0.0.1", &settings).await.unwrap();
+        let values = client.get(&[IF_HC_IN_OCTETS]).await.unwrap();
+        assert_eq!(values[0].0, IF_HC_IN_OCTETS);
+        assert_eq!(values[0].1, SnmpValue::Unsigned(1 << 63));
+    }
+
+    #[test]
+    fn test_pick_wan_interface() {
+        let names = vec![
+            (1, "lo".to_string()),
+            (2, "br-lan".to_string()),
+            (5, "pppoe-wan".to_string()),
+        ];
+        assert_eq!(pick_wan_interface(&names, None).unwrap().0, 5);
+        assert_eq!(pick_wan_interface(&names, Some("BR-LAN")).unwrap().0, 2);
+        assert_eq!(pick_wan_interface(&names, Some("1")).unwrap().1, "lo");
+        let err = pick_wan_interface(&names[..2], None).unwrap_err();
+        assert!(err.contains("lo, br-lan"), "{}", err);
+    }
+
+    #[test]
+    fn test_wan_rate() {
+        let start = Instant::now();
+        let sample = |seconds: u64, megabits: u64| WanSample {
+            at: start + Duration::from_secs(seconds),
+            in_octets: megabits * 1_000_000 / 8,
+            out_octets: 0,
+        };
+        let samples = [
+            sample(0, 0),
+            sample(1, 100),
+            sample(2, 300),
+            sample(3, 400),
+            sample(9, 900),
+        ];
+        let window = (start, start + Duration::from_secs(3));
+
+        let rate = wan_rate(&samples, window, |sample| sample.in_octets).unwrap();
+        assert!((rate.average_mbps - 133.3).abs() < 0.1);
+        assert!((rate.peak_mbps - 200.0).abs() < 0.001);
+        assert_eq!(
+            wan_rate(&samples[..1], window, |sample| sample.in_octets),
+            None
+        );
+    }
+}
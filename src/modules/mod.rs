@@ -1,11 +1,49 @@
+pub mod analysis;
+pub mod backend;
+pub mod bloatcheck;
+pub mod bug_report;
+pub mod cloudcheck;
+pub mod config;
 pub mod diagnostics;
+pub mod dns;
+pub mod email;
+pub mod evidence;
+pub mod fast;
+pub mod geolocation;
+pub mod happy_eyeballs;
 pub mod history;
+pub mod history_browser;
+pub mod httpcheck;
 pub mod intro;
+pub mod ipinfo;
+pub mod lanscan;
+pub mod localnet;
 pub mod logo;
+pub mod ndt7;
+pub mod ntp;
+pub mod pingmon;
+pub mod portcheck;
+pub mod portmap;
+pub mod push;
+pub mod qos;
+pub mod rate_limit;
+pub mod regional;
+pub mod reporting;
+pub mod resolver;
+pub mod service;
+pub mod share_card;
+pub mod signing;
 pub mod speed_test;
 pub mod stats_ui;
+pub mod telemetry;
+pub mod terminal;
+pub mod theme;
+pub mod tls;
+pub mod traceroute;
 pub mod types;
 pub mod ui;
+pub mod websocket;
+pub mod windows_stack;
 
 // Re-export common types for easier access
 // These are public API exports used by external consumers
@@ -13,12 +51,15 @@ pub mod ui;
 pub use intro::{show_intro, show_simple_intro};
 pub use logo::{NetrunnerLogo, NetrunnerLogoSize};
 #[allow(unused_imports)]
-pub use types::{ConnectionQuality, DetailLevel, SpeedTestResult, TestConfig};
+pub use types::{
+    AdvertisedPlan, ConnectionQuality, DetailLevel, SpeedTestResult, TestConfig, ThemeName,
+    VisualStyle,
+};
 
 // Re-export storage and speed test as primary
 #[allow(unused_imports)]
 pub use history::{HistoryStorage, SpeedTrends, TestStatistics};
 #[allow(unused_imports)]
-pub use speed_test::{GeoLocation, SpeedTest};
+pub use speed_test::{GeoLocation, ProviderCheck, SpeedTest};
 #[allow(unused_imports)]
 pub use stats_ui::show_statistics_tui;
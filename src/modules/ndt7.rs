@@ -0,0 +1,420 @@
+//! Measurement Lab ndt7 Tests
+//!
+//! M-Lab runs ndt7 servers in hundreds of locations and publishes every
+//! measurement, so results taken with `--provider mlab` can be compared with
+//! research data sets. ndt7 measures each direction over a single WebSocket for
+//! about ten seconds:
+//! - The locate service hands out the nearest server with signed test URLs
+//! - In a download the server sends binary messages and, between them, JSON
+//!   measurements with its kernel's TCP_INFO for the connection
+//! - In an upload the client sends binary messages growing with the bytes sent,
+//!   and the server reports what it received the same way
+//!
+//! Latency, jitter and retransmissions come from the server's TCP_INFO, not from
+//! separate probes.
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::modules::resolver::ResolverCache;
+use crate::modules::types::TransportInfo;
+use crate::modules::websocket::{self, Socket};
+
+/// Nearest ndt7 servers for the caller, with access tokens in their URLs
+pub const LOCATE_URL: &str = "https://locate.measurementlab.net/v2/nearest/ndt/ndt7";
+
+/// WebSocket subprotocol both sides have to agree on
+pub const SUBPROTOCOL: &str = "net.measurementlab.ndt.v7";
+
+/// Servers end a test after about ten seconds, clients give up after fifteen
+pub const MAX_TEST_DURATION: Duration = Duration::from_secs(15);
+
+/// How long the client sends in an upload
+pub const UPLOAD_DURATION: Duration = Duration::from_secs(10);
+
+/// First upload message size, doubled as the upload goes on
+const INITIAL_MESSAGE_BYTES: usize = 1 << 13;
+
+/// Largest message ndt7 servers accept
+const MAX_MESSAGE_BYTES: usize = 1 << 24;
+
+#[derive(Debug, Deserialize)]
+struct LocateResponse {
+    #[serde(default)]
+    results: Vec<LocatedServer>,
+}
+
+/// An ndt7 server the locate service picked
+#[derive(Debug, Clone, Deserialize)]
+pub struct LocatedServer {
+    /// Machine name, e.g. `mlab1-fra05.mlab-oti.measurement-lab.org`
+    pub machine: String,
+    #[serde(default)]
+    pub location: Option<ServerLocation>,
+    /// Test URLs keyed by scheme and path, e.g. `wss:///ndt/v7/download`
+    #[serde(default)]
+    urls: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerLocation {
+    pub city: Option<String>,
+    pub country: Option<String>,
+}
+
+impl LocatedServer {
+    pub fn download_url(&self) -> Option<&str> {
+        self.urls.get("wss:///ndt/v7/download").map(String::as_str)
+    }
+
+    pub fn upload_url(&self) -> Option<&str> {
+        self.urls.get("wss:///ndt/v7/upload").map(String::as_str)
+    }
+
+    /// City and country, the machine name when the locate service left them out
+    pub fn location_name(&self) -> String {
+        let parts: Vec<&str> = self
+            .location
+            .iter()
+            .flat_map(|location| [&location.city, &location.country])
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        if parts.is_empty() {
+            self.machine.clone()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Ask the locate service for the nearest servers, best first
+pub async fn locate(
+    client: &reqwest::Client,
+) -> Result<Vec<LocatedServer>, Box<dyn std::error::Error>> {
+    let url = url::Url::parse_with_params(
+        LOCATE_URL,
+        [
+            ("client_name", env!("CARGO_PKG_NAME")),
+            ("client_version", env!("CARGO_PKG_VERSION")),
+        ],
+    )?;
+    let response: LocateResponse = client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let servers: Vec<LocatedServer> = response
+        .results
+        .into_iter()
+        .filter(|server| server.download_url().is_some() && server.upload_url().is_some())
+        .collect();
+    if servers.is_empty() {
+        return Err("M-Lab's locate service has no ndt7 server available".into());
+    }
+    Ok(servers)
+}
+
+/// A measurement message, as the server sends it during both tests. Its
+/// `AppInfo` counts what the server's side wrote, the client counts that itself.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Measurement {
+    #[serde(rename = "TCPInfo")]
+    pub tcp_info: Option<TcpInfo>,
+}
+
+/// The fields of the server's TCP_INFO used here, times in microseconds
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TcpInfo {
+    pub elapsed_time: Option<i64>,
+    #[serde(rename = "RTT")]
+    pub rtt: Option<i64>,
+    #[serde(rename = "RTTVar")]
+    pub rtt_var: Option<i64>,
+    #[serde(rename = "MinRTT")]
+    pub min_rtt: Option<i64>,
+    pub bytes_sent: Option<i64>,
+    pub bytes_retrans: Option<i64>,
+    pub bytes_received: Option<i64>,
+}
+
+impl TcpInfo {
+    /// Share of the bytes the server sent that it had to send again
+    pub fn retransmit_percent(&self) -> Option<f64> {
+        let sent = self.bytes_sent.filter(|&sent| sent > 0)?;
+        Some(self.bytes_retrans.unwrap_or(0) as f64 * 100.0 / sent as f64)
+    }
+
+    /// Receive rate the server saw during an upload
+    pub fn received_mbps(&self) -> Option<f64> {
+        let elapsed = self.elapsed_time.filter(|&elapsed| elapsed > 0)?;
+        Some(self.bytes_received? as f64 * 8.0 / elapsed as f64)
+    }
+}
+
+/// What one test direction measured
+#[derive(Debug, Clone, Default)]
+pub struct Transfer {
+    /// Bytes the client received or sent
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// The last measurement the server sent
+    pub last: Option<Measurement>,
+    /// Smoothed round-trip times the server reported, in milliseconds
+    pub rtt_samples_ms: Vec<f64>,
+    /// What the WebSocket's TLS connection negotiated
+    pub transport: Option<TransportInfo>,
+}
+
+impl Transfer {
+    /// Rate the client saw
+    pub fn mbps(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes as f64 * 8.0 / (seconds * 1_000_000.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn tcp_info(&self) -> Option<&TcpInfo> {
+        self.last.as_ref()?.tcp_info.as_ref()
+    }
+
+    fn record(&mut self, text: &str) {
+        let Ok(measurement) = serde_json::from_str::<Measurement>(text) else {
+            return;
+        };
+        if let Some(rtt) = measurement.tcp_info.and_then(|info| info.rtt) {
+            self.rtt_samples_ms.push(rtt as f64 / 1000.0);
+        }
+        self.last = Some(measurement);
+    }
+}
+
+/// Open the test's WebSocket to a `wss://` URL from the locate service
+async fn connect(
+    url: &str,
+    resolver: &ResolverCache,
+) -> Result<Socket, Box<dyn std::error::Error>> {
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_MESSAGE_BYTES));
+    websocket::connect(url, resolver, Some(SUBPROTOCOL), Some(config)).await
+}
+
+fn transport(socket: &Socket) -> TransportInfo {
+    let connection = socket.get_ref().get_ref().1;
+    TransportInfo {
+        alpn: connection
+            .alpn_protocol()
+            .map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+        tls_version: connection.protocol_version().map(|version| match version {
+            rustls::ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+            rustls::ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+            other => format!("{:?}", other),
+        }),
+    }
+}
+
+/// Run a download test, adding every byte received to `counter` as it arrives.
+/// Ends early once `max_bytes` were received.
+pub async fn download(
+    url: &str,
+    resolver: &ResolverCache,
+    counter: Arc<AtomicU64>,
+    max_bytes: Option<u64>,
+) -> Result<Transfer, Box<dyn std::error::Error>> {
+    let mut socket = connect(url, resolver).await?;
+    let mut transfer = Transfer {
+        transport: Some(transport(&socket)),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    while let Ok(Some(message)) = tokio::time::timeout(
+        MAX_TEST_DURATION.saturating_sub(start.elapsed()),
+        socket.next(),
+    )
+    .await
+    {
+        match message? {
+            Message::Binary(data) => transfer.bytes += data.len() as u64,
+            Message::Text(text) => {
+                transfer.bytes += text.len() as u64;
+                transfer.record(&text);
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        }
+        counter.store(transfer.bytes, Ordering::Relaxed);
+        if max_bytes.is_some_and(|max| transfer.bytes >= max) {
+            break;
+        }
+    }
+    transfer.elapsed = start.elapsed();
+    let _ = socket.close(None).await;
+
+    if transfer.bytes == 0 {
+        return Err("the ndt7 server sent no data".into());
+    }
+    Ok(transfer)
+}
+
+/// Run an upload test for `UPLOAD_DURATION`, adding every byte sent to `counter`.
+/// Ends early once `max_bytes` were sent.
+pub async fn upload(
+    url: &str,
+    resolver: &ResolverCache,
+    counter: Arc<AtomicU64>,
+    max_bytes: Option<u64>,
+) -> Result<Transfer, Box<dyn std::error::Error>> {
+    let socket = connect(url, resolver).await?;
+    let transport = transport(&socket);
+    let (mut sink, mut stream) = socket.split();
+
+    // The server's measurements arrive while the client is busy sending
+    let received = Arc::new(Mutex::new(Transfer::default()));
+    let reader = {
+        let received = Arc::clone(&received);
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = stream.next().await {
+                match message {
+                    Message::Text(text) => received.lock().unwrap().record(&text),
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+        })
+    };
+
+    // Random, so nothing on the path can compress it
+    let mut payload = vec![0u8; MAX_MESSAGE_BYTES];
+    rand::fill(payload.as_mut_slice());
+    let payload = Bytes::from(payload);
+    let mut message_bytes = INITIAL_MESSAGE_BYTES;
+    let mut sent = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < UPLOAD_DURATION && max_bytes.is_none_or(|max| sent < max) {
+        let message = Message::Binary(payload.slice(..message_bytes));
+        let send = tokio::time::timeout(
+            UPLOAD_DURATION.saturating_sub(start.elapsed()),
+            sink.send(message),
+        );
+        match send.await {
+            Ok(result) => result?,
+            Err(_) => break,
+        }
+        sent += message_bytes as u64;
+        counter.store(sent, Ordering::Relaxed);
+        message_bytes = next_message_bytes(message_bytes, sent);
+    }
+    let elapsed = start.elapsed();
+    let _ = sink.close().await;
+    let _ = tokio::time::timeout(Duration::from_secs(2), reader).await;
+
+    let received = std::mem::take(&mut *received.lock().unwrap());
+    Ok(Transfer {
+        bytes: sent,
+        elapsed,
+        transport: Some(transport),
+        ..received
+    })
+}
+
+/// Size of the next upload message: doubled once the bytes sent are 16 times the
+/// current size, so slow links keep sending small messages
+fn next_message_bytes(current: usize, sent: u64) -> usize {
+    if current < MAX_MESSAGE_BYTES && current as u64 <= sent / 16 {
+        current * 2
+    } else {
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_response() {
+        let response: LocateResponse = serde_json::from_str(
+            r#"{"results":[{
+                "machine": "mlab1-fra05.mlab-oti.measurement-lab.org",
+                "location": {"city": "Frankfurt", "country": "DE"},
+                "urls": {
+                    "wss:///ndt/v7/download": "wss://ndt.fra05/ndt/v7/download?access_token=a",
+                    "wss:///ndt/v7/upload": "wss://ndt.fra05/ndt/v7/upload?access_token=b",
+                    "ws:///ndt/v7/download": "ws://ndt.fra05/ndt/v7/download?access_token=c"
+                }
+            }, {
+                "machine": "mlab2-ams08.mlab-oti.measurement-lab.org",
+                "urls": {}
+            }]}"#,
+        )
+        .unwrap();
+
+        let server = &response.results[0];
+        assert_eq!(server.location_name(), "Frankfurt, DE");
+        assert_eq!(
+            server.download_url(),
+            Some("wss://ndt.fra05/ndt/v7/download?access_token=a")
+        );
+        assert_eq!(
+            server.upload_url(),
+            Some("wss://ndt.fra05/ndt/v7/upload?access_token=b")
+        );
+
+        let bare = &response.results[1];
+        assert_eq!(bare.location_name(), bare.machine);
+        assert_eq!(bare.download_url(), None);
+    }
+
+    #[test]
+    fn test_measurement() {
+        let mut transfer = Transfer::default();
+        transfer.record(
+            r#"{"AppInfo":{"ElapsedTime":2000000,"NumBytes":25000000},
+                "Origin":"server","Test":"download",
+                "TCPInfo":{"ElapsedTime":2000000,"RTT":12500,"RTTVar":1500,"MinRTT":9800,
+                           "BytesSent":25000000,"BytesRetrans":250000,"BytesReceived":4000}}"#,
+        );
+        transfer.record("not a measurement");
+
+        assert_eq!(transfer.rtt_samples_ms, vec![12.5]);
+        let info = transfer.tcp_info().unwrap();
+        assert_eq!(info.min_rtt, Some(9800));
+        assert_eq!(info.retransmit_percent(), Some(1.0));
+        assert_eq!(info.received_mbps(), Some(0.016));
+    }
+
+    #[test]
+    fn test_next_message_bytes() {
+        assert_eq!(next_message_bytes(INITIAL_MESSAGE_BYTES, 8192), 8192);
+        assert_eq!(next_message_bytes(INITIAL_MESSAGE_BYTES, 16 * 8192), 16384);
+        assert_eq!(
+            next_message_bytes(MAX_MESSAGE_BYTES, u64::MAX),
+            MAX_MESSAGE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_transfer_mbps() {
+        let transfer = Transfer {
+            bytes: 12_500_000,
+            elapsed: Duration::from_secs(10),
+            ..Default::default()
+        };
+        assert_eq!(transfer.mbps(), 10.0);
+        assert_eq!(Transfer::default().mbps(), 0.0);
+    }
+}
@@ -0,0 +1,141 @@
+//! Clock Check
+//!
+//! Asks public NTP servers for the time with a single SNTP exchange and works
+//! out how far the system clock is off. A skewed clock makes certificates look
+//! expired or not yet valid and puts history entries at the wrong time.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+use crate::modules::types::ClockCheck;
+
+/// Tried in order until one answers
+const NTP_SERVERS: &[&str] = &["pool.ntp.org", "time.cloudflare.com", "time.google.com"];
+
+const NTP_PORT: u16 = 123;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Compare the system clock with the first NTP server that answers
+pub async fn check_clock(timeout: Duration) -> Option<ClockCheck> {
+    for server in NTP_SERVERS {
+        if let Ok(Ok(check)) = tokio::time::timeout(timeout, query(server)).await {
+            return Some(check);
+        }
+    }
+    None
+}
+
+async fn query(server: &str) -> std::io::Result<ClockCheck> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((server, NTP_PORT)).await?;
+
+    let request = sntp_request(SystemTime::now());
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 48];
+    loop {
+        let len = socket.recv(&mut response).await?;
+        let received = SystemTime::now();
+        // Stray or spoofed answers do not echo our transmit time, keep waiting
+        if let Some((offset, delay)) = parse_sntp_response(&response[..len], &request, received) {
+            return Ok(ClockCheck {
+                server: server.to_string(),
+                offset_ms: offset * 1000.0,
+                round_trip_ms: delay * 1000.0,
+            });
+        }
+    }
+}
+
+/// Client request (version 4, mode 3) carrying `now` as its transmit time
+fn sntp_request(now: SystemTime) -> [u8; 48] {
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+    request[40..48].copy_from_slice(&to_ntp_timestamp(now).to_be_bytes());
+    request
+}
+
+/// Clock offset and round trip delay in seconds from a server answer to `request`
+fn parse_sntp_response(
+    response: &[u8],
+    request: &[u8; 48],
+    received: SystemTime,
+) -> Option<(f64, f64)> {
+    if response.len() < 48 {
+        return None;
+    }
+    let mode = response[0] & 0x07;
+    let stratum = response[1];
+    // Stratum 0 is a kiss-o'-death telling the client to back off
+    if mode != 4 || stratum == 0 || response[24..32] != request[40..48] {
+        return None;
+    }
+
+    let seconds = |bytes: &[u8]| ntp_seconds(u64::from_be_bytes(bytes.try_into().unwrap()));
+    let sent = seconds(&request[40..48]);
+    let server_received = seconds(&response[32..40]);
+    let server_sent = seconds(&response[40..48]);
+    let received = ntp_seconds(to_ntp_timestamp(received));
+
+    let offset = ((server_received - sent) + (server_sent - received)) / 2.0;
+    let delay = (received - sent) - (server_sent - server_received);
+    Some((offset, delay.max(0.0)))
+}
+
+/// 32.32 fixed point seconds since 1900
+fn to_ntp_timestamp(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_unix.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (u64::from(since_unix.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Seconds since 1900 as a float
+fn ntp_seconds(timestamp: u64) -> f64 {
+    (timestamp >> 32) as f64 + (timestamp & 0xffff_ffff) as f64 / 4_294_967_296.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntp_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let timestamp = to_ntp_timestamp(time);
+        assert_eq!(timestamp >> 32, 1_700_000_000 + NTP_UNIX_OFFSET);
+        assert!((ntp_seconds(timestamp) - (1_700_000_000.5 + NTP_UNIX_OFFSET as f64)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_sntp_response() {
+        let sent = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let request = sntp_request(sent);
+
+        // The server clock runs 2 s ahead and the network adds 50 ms each way
+        let mut response = [0u8; 48];
+        response[0] = 0x24;
+        response[1] = 2;
+        response[24..32].copy_from_slice(&request[40..48]);
+        response[32..40]
+            .copy_from_slice(&to_ntp_timestamp(sent + Duration::from_millis(2050)).to_be_bytes());
+        response[40..48]
+            .copy_from_slice(&to_ntp_timestamp(sent + Duration::from_millis(2060)).to_be_bytes());
+        let received = sent + Duration::from_millis(110);
+
+        let (offset, delay) = parse_sntp_response(&response, &request, received).unwrap();
+        assert!((offset - 2.0).abs() < 1e-3);
+        assert!((delay - 0.1).abs() < 1e-3);
+
+        // A kiss-o'-death answer carries no usable time
+        let mut kiss = response;
+        kiss[1] = 0;
+        assert!(parse_sntp_response(&kiss, &request, received).is_none());
+
+        // Neither does an answer to some other request
+        let other = sntp_request(sent + Duration::from_secs(1));
+        assert!(parse_sntp_response(&response, &other, received).is_none());
+    }
+}
@@ -0,0 +1,433 @@
+//! Continuous Latency Monitor
+//!
+//! Smokeping-style monitor: times a TCP handshake with the target on a fixed
+//! interval and keeps a rolling latency plot with loss and statistics on screen.
+//! TCP is used instead of ICMP, which needs raw socket privileges. Every probe is
+//! saved to history so outages and spikes can be reviewed afterwards.
+
+use chrono::{DateTime, Utc};
+use colored::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use crate::modules::history::HistoryStorage;
+use crate::modules::terminal::TerminalCapabilities;
+use crate::modules::tls;
+use crate::modules::types::{
+    consecutive_jitter, median, percent_lost, LatencyStats, PingSample, TestConfig,
+};
+use crate::modules::ui::UI;
+
+/// Time between probes when `--interval` isn't given
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Probes shown in the plot and used for the rolling statistics
+const WINDOW: usize = 60;
+
+/// Probed by monitor mode during `latency` quiet hours and after an anomaly
+pub const MONITOR_PROBE_TARGET: &str = "1.1.1.1:443";
+
+/// Longest wait for a handshake, shorter intervals wait at most one interval
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Consecutive lost probes that count as an outage rather than loss
+const OUTAGE_MIN_LOST: usize = 3;
+
+/// A probe is a spike when it is this many times the median latency...
+const SPIKE_FACTOR: f64 = 3.0;
+/// ...and at least this much slower, so 1 ms to 3 ms on a LAN doesn't count
+const SPIKE_MIN_EXTRA_MS: f64 = 50.0;
+
+/// Probes kept while another process has the history open, an hour at the default interval
+const MAX_UNSAVED_SAMPLES: usize = 3600;
+
+const PLOT_GLYPHS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+const PLOT_GLYPHS_ASCII: [&str; 8] = ["_", ".", "-", ":", "=", "+", "*", "#"];
+
+/// Outages and spikes found in stored ping samples
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PingEvent {
+    /// A run of consecutive lost probes
+    Outage {
+        target: String,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        lost: usize,
+    },
+    /// A probe far above the target's median latency
+    Spike {
+        target: String,
+        at: DateTime<Utc>,
+        latency_ms: f64,
+        median_ms: f64,
+    },
+}
+
+/// Statistics over the probes currently in the plot
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollingStats {
+    pub sent: usize,
+    pub lost: usize,
+    pub latency: Option<LatencyStats>,
+    /// Mean difference between consecutive answered probes
+    pub jitter_ms: f64,
+}
+
+impl RollingStats {
+    pub fn from_window(window: &VecDeque<Option<f64>>) -> Self {
+        let answered: Vec<f64> = window.iter().flatten().copied().collect();
+
+        Self {
+            sent: window.len(),
+            lost: window.len() - answered.len(),
+            latency: LatencyStats::from_samples(&answered),
+            jitter_ms: consecutive_jitter(&answered),
+        }
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        percent_lost(self.lost, self.sent)
+    }
+}
+
+pub struct PingMonitor {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl PingMonitor {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    /// Probe `target` (HOST or HOST:PORT, port 443 by default) every `interval` until interrupted
+    pub async fn run(
+        &self,
+        target: &str,
+        interval: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (host, port) = tls::parse_target(target)?;
+        let address = tokio::net::lookup_host((host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", host))?;
+        let timeout = interval.min(PROBE_TIMEOUT);
+
+        // Redraw the plot in place on a terminal, otherwise log one line per probe
+        let live = !self.config.json_output && TerminalCapabilities::get().is_tty;
+        if !self.config.json_output {
+            self.ui.show_section_header("Latency Monitor")?;
+            println!(
+                "Probing {} ({}) every {}, press Ctrl+C to stop.",
+                target.bright_cyan(),
+                address,
+                humantime::format_duration(interval)
+            );
+            println!();
+        }
+
+        let term = console::Term::stdout();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut window = VecDeque::with_capacity(WINDOW);
+        let (mut total_sent, mut total_lost) = (0usize, 0usize);
+        let mut drawn_lines = 0;
+        // The history is opened per probe instead of held for the whole session, so
+        // speed tests running meanwhile can save theirs. Probes that find it busy
+        // wait here for the next one that gets in.
+        let mut unsaved = VecDeque::new();
+        let mut reported_busy = false;
+
+        loop {
+            ticker.tick().await;
+            let sample = PingSample {
+                timestamp: Utc::now(),
+                target: target.to_string(),
+                latency_ms: probe(address, timeout).await,
+            };
+
+            if unsaved.len() == MAX_UNSAVED_SAMPLES {
+                unsaved.pop_front();
+            }
+            unsaved.push_back(sample.clone());
+            match HistoryStorage::open(Duration::ZERO) {
+                Ok(storage) => {
+                    reported_busy = false;
+                    for sample in unsaved.drain(..) {
+                        if let Err(e) = storage.save_ping_sample(&sample) {
+                            eprintln!("Failed to save ping sample: {}", e);
+                        }
+                    }
+                }
+                Err(e) if !reported_busy => {
+                    eprintln!("Keeping probes until history storage is free: {}", e);
+                    reported_busy = true;
+                }
+                Err(_) => {}
+            }
+
+            if window.len() == WINDOW {
+                window.pop_front();
+            }
+            window.push_back(sample.latency_ms);
+            total_sent += 1;
+            total_lost += usize::from(sample.latency_ms.is_none());
+            let stats = RollingStats::from_window(&window);
+
+            if self.config.json_output {
+                println!("{}", serde_json::to_string(&sample)?);
+            } else if live {
+                if drawn_lines > 0 {
+                    term.clear_last_lines(drawn_lines)?;
+                }
+                let lines = [
+                    render_plot(&window),
+                    format_stats(&sample, &stats),
+                    format!(
+                        "{} {}/{} probes lost since start",
+                        "📊 Total:".bold(),
+                        total_lost,
+                        total_sent
+                    ),
+                ];
+                for line in &lines {
+                    println!("{}", line);
+                }
+                drawn_lines = lines.len();
+            } else {
+                println!(
+                    "{}  {}",
+                    sample
+                        .timestamp
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M:%S"),
+                    format_stats(&sample, &stats)
+                );
+            }
+        }
+    }
+}
+
+/// Time a single handshake with `target` (HOST or HOST:PORT), a lost probe
+/// leaving the latency empty
+pub async fn probe_target(target: &str) -> Result<PingSample, Box<dyn std::error::Error>> {
+    let (host, port) = tls::parse_target(target)?;
+    let address = tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {}", host))?;
+    Ok(PingSample {
+        timestamp: Utc::now(),
+        target: target.to_string(),
+        latency_ms: probe(address, PROBE_TIMEOUT).await,
+    })
+}
+
+/// Time a TCP handshake, `None` when unanswered within `timeout`. A refused
+/// connection comes back from the host, so it answers the probe too.
+async fn probe(address: SocketAddr, timeout: Duration) -> Option<f64> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+            Some(start.elapsed().as_secs_f64() * 1000.0)
+        }
+        _ => None,
+    }
+}
+
+/// One glyph per probe scaled to the slowest in the window, lost probes marked in red
+fn render_plot(window: &VecDeque<Option<f64>>) -> String {
+    let symbols = TerminalCapabilities::get().symbols();
+    let glyphs = if TerminalCapabilities::get().unicode {
+        PLOT_GLYPHS
+    } else {
+        PLOT_GLYPHS_ASCII
+    };
+    let scale = window
+        .iter()
+        .flatten()
+        .fold(1.0_f64, |max, &ms| max.max(ms));
+
+    window
+        .iter()
+        .map(|latency| match latency {
+            Some(ms) => {
+                let level = ((ms / scale) * (glyphs.len() - 1) as f64).round() as usize;
+                let glyph = glyphs[level.min(glyphs.len() - 1)];
+                if *ms <= 50.0 {
+                    glyph.bright_green().to_string()
+                } else if *ms <= 100.0 {
+                    glyph.bright_yellow().to_string()
+                } else {
+                    glyph.bright_red().to_string()
+                }
+            }
+            None => symbols.cross.bright_red().bold().to_string(),
+        })
+        .collect()
+}
+
+fn format_stats(sample: &PingSample, stats: &RollingStats) -> String {
+    let last = match sample.latency_ms {
+        Some(ms) => format!("{:>7.1} ms", ms),
+        None => format!("{:>10}", "lost").bright_red().to_string(),
+    };
+    let latency = match &stats.latency {
+        Some(latency) => format!(
+            "min {:.1}  avg {:.1}  p95 {:.1}  max {:.1}  jitter {:.1} ms",
+            latency.min_ms, latency.avg_ms, latency.p95_ms, latency.max_ms, stats.jitter_ms
+        ),
+        None => "no answers".to_string(),
+    };
+    let loss = format!("loss {:.1}%", stats.loss_percent());
+    let loss = if stats.lost > 0 {
+        loss.bright_red().to_string()
+    } else {
+        loss
+    };
+
+    format!("{}  {}  {}", last, latency, loss)
+}
+
+/// Find outages and spikes in samples ordered by time, per target
+pub fn find_events(samples: &[PingSample]) -> Vec<PingEvent> {
+    let mut targets: Vec<&str> = Vec::new();
+    for sample in samples {
+        if !targets.contains(&sample.target.as_str()) {
+            targets.push(&sample.target);
+        }
+    }
+
+    let mut events = Vec::new();
+    for target in targets {
+        let samples: Vec<&PingSample> = samples.iter().filter(|s| s.target == target).collect();
+        let answered: Vec<f64> = samples.iter().filter_map(|s| s.latency_ms).collect();
+        let median_ms = median(&answered);
+        let mut lost_run: Vec<&PingSample> = Vec::new();
+
+        let end_outage = |lost_run: &mut Vec<&PingSample>, events: &mut Vec<PingEvent>| {
+            if lost_run.len() >= OUTAGE_MIN_LOST {
+                events.push(PingEvent::Outage {
+                    target: target.to_string(),
+                    start: lost_run[0].timestamp,
+                    end: lost_run[lost_run.len() - 1].timestamp,
+                    lost: lost_run.len(),
+                });
+            }
+            lost_run.clear();
+        };
+
+        for &sample in &samples {
+            match sample.latency_ms {
+                None => lost_run.push(sample),
+                Some(latency_ms) => {
+                    end_outage(&mut lost_run, &mut events);
+                    if latency_ms >= median_ms * SPIKE_FACTOR
+                        && latency_ms - median_ms >= SPIKE_MIN_EXTRA_MS
+                    {
+                        events.push(PingEvent::Spike {
+                            target: target.to_string(),
+                            at: sample.timestamp,
+                            latency_ms,
+                            median_ms,
+                        });
+                    }
+                }
+            }
+        }
+        end_outage(&mut lost_run, &mut events);
+    }
+
+    events.sort_by_key(|event| match event {
+        PingEvent::Outage { start, .. } => *start,
+        PingEvent::Spike { at, .. } => *at,
+    });
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(latencies: &[Option<f64>]) -> Vec<PingSample> {
+        let start = Utc::now();
+        latencies
+            .iter()
+            .enumerate()
+            .map(|(i, &latency_ms)| PingSample {
+                timestamp: start + chrono::Duration::seconds(i as i64),
+                target: "1.1.1.1".to_string(),
+                latency_ms,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rolling_stats() {
+        let window: VecDeque<Option<f64>> = [Some(10.0), None, Some(14.0), Some(12.0)].into();
+        let stats = RollingStats::from_window(&window);
+
+        assert_eq!(stats.sent, 4);
+        assert_eq!(stats.lost, 1);
+        assert_eq!(stats.loss_percent(), 25.0);
+        assert_eq!(stats.jitter_ms, 3.0);
+        assert_eq!(stats.latency.unwrap().max_ms, 14.0);
+
+        let empty = RollingStats::from_window(&VecDeque::new());
+        assert_eq!(empty.loss_percent(), 0.0);
+        assert!(empty.latency.is_none());
+    }
+
+    #[test]
+    fn test_find_events() {
+        let samples = samples(&[
+            Some(10.0),
+            None,
+            Some(11.0),
+            None,
+            None,
+            None,
+            Some(12.0),
+            Some(180.0),
+            Some(10.0),
+            None,
+            None,
+            None,
+        ]);
+        let events = find_events(&samples);
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[0],
+            PingEvent::Outage { start, lost: 3, .. } if *start == samples[3].timestamp
+        ));
+        assert!(matches!(
+            &events[1],
+            PingEvent::Spike { latency_ms, .. } if *latency_ms == 180.0
+        ));
+        // An outage still running at the end of the samples is reported too
+        assert!(matches!(&events[2], PingEvent::Outage { lost: 3, .. }));
+    }
+
+    #[test]
+    fn test_small_spikes_are_ignored() {
+        // Tripling on a LAN is still only a few milliseconds
+        let events = find_events(&samples(&[Some(1.0), Some(1.0), Some(4.0), Some(1.0)]));
+        assert!(events.is_empty());
+    }
+    #[tokio::test]
+    async fn test_probe_counts_refused_connections() {
+        // Bind and drop a listener to find a port nothing listens on
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(probe(closed, PROBE_TIMEOUT).await.is_some());
+    }
+}
@@ -0,0 +1,372 @@
+//! Port Connectivity Check
+//!
+//! Attempts TCP connections (and optionally UDP probes) to a list of ports on one
+//! host, timing each attempt. Useful for telling a firewall apart from a slow line.
+
+use colored::*;
+use prettytable::{format, Cell, Row, Table};
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use strum_macros::Display;
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::modules::terminal::print_table;
+use crate::modules::types::TestConfig;
+use crate::modules::ui::UI;
+
+/// Most ports accepted in one check, so a typo cannot start a full port scan
+pub const MAX_PORTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum PortState {
+    /// Accepted a connection or answered a probe
+    #[strum(serialize = "open")]
+    Open,
+    /// Actively refused, the host is reachable but nothing listens
+    #[strum(serialize = "closed")]
+    Closed,
+    /// No answer or an unreachable error, typically a firewall dropping packets
+    #[strum(serialize = "filtered")]
+    Filtered,
+    /// UDP probe without any answer; silence is normal for many UDP services
+    #[strum(serialize = "open|filtered")]
+    OpenOrFiltered,
+}
+
+/// Outcome of probing a single port
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PortCheckResult {
+    pub port: u16,
+    pub protocol: Protocol,
+    pub state: PortState,
+    /// Time until the connection was accepted or refused
+    pub time_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortCheckReport {
+    pub target: String,
+    pub address: IpAddr,
+    pub results: Vec<PortCheckResult>,
+}
+
+/// What to check
+#[derive(Debug, Clone)]
+pub struct PortCheckRequest {
+    pub target: String,
+    pub ports: Vec<u16>,
+    pub udp: bool,
+    pub timeout: Duration,
+}
+
+pub struct PortCheckTool {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl PortCheckTool {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    pub async fn run(
+        &self,
+        request: &PortCheckRequest,
+    ) -> Result<PortCheckReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("Port Connectivity Check")?;
+        }
+
+        let address = tokio::net::lookup_host((request.target.as_str(), 0))
+            .await?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", request.target))?
+            .ip();
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_cyberpunk_spinner(&format!(
+                "PROBING {} PORTS ON {}",
+                request.ports.len(),
+                request.target.to_uppercase()
+            )))
+        } else {
+            None
+        };
+
+        let tcp = futures::future::join_all(
+            request
+                .ports
+                .iter()
+                .map(|&port| check_tcp(SocketAddr::new(address, port), request.timeout)),
+        );
+        let udp = async {
+            if request.udp {
+                futures::future::join_all(
+                    request
+                        .ports
+                        .iter()
+                        .map(|&port| check_udp(SocketAddr::new(address, port), request.timeout)),
+                )
+                .await
+            } else {
+                Vec::new()
+            }
+        };
+        let (tcp, udp) = tokio::join!(tcp, udp);
+
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ PORT SCAN COMPLETE ⟩⟩⟩");
+        }
+
+        let report = PortCheckReport {
+            target: request.target.clone(),
+            address,
+            results: tcp.into_iter().chain(udp).collect(),
+        };
+
+        if !self.config.json_output {
+            self.display_results(&report)?;
+        }
+
+        Ok(report)
+    }
+
+    fn display_results(&self, report: &PortCheckReport) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+        println!(
+            "{} {} ({})",
+            "🎯 Target:".bold(),
+            report.target.bright_cyan(),
+            report.address
+        );
+        println!();
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(Row::new(vec![
+            Cell::new("Port").style_spec("Fb"),
+            Cell::new("Protocol").style_spec("Fb"),
+            Cell::new("State").style_spec("Fb"),
+            Cell::new("Time").style_spec("Fb"),
+        ]));
+
+        for result in &report.results {
+            let style = match result.state {
+                PortState::Open => "Fg",
+                PortState::Closed => "Fr",
+                PortState::Filtered | PortState::OpenOrFiltered => "Fy",
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&result.port.to_string()),
+                Cell::new(&result.protocol.to_string()),
+                Cell::new(&result.state.to_string()).style_spec(style),
+                Cell::new(
+                    &result
+                        .time_ms
+                        .map(|ms| format!("{:.1} ms", ms))
+                        .unwrap_or_else(|| "-".to_string()),
+                ),
+            ]));
+        }
+
+        print_table(&table)?;
+
+        let open = report
+            .results
+            .iter()
+            .filter(|r| r.state == PortState::Open)
+            .count();
+        println!();
+        println!(
+            "{} {} of {} probes answered",
+            "📊 Summary:".bold(),
+            open,
+            report.results.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// Parse a port list such as `22,80,443` or `8000-8010,22`, keeping the given order
+pub fn parse_ports(s: &str) -> Result<Vec<u16>, String> {
+    let invalid = |part: &str| {
+        format!(
+            "Invalid port '{}', expected a comma-separated list like 22,80,443 or 8000-8010",
+            part
+        )
+    };
+    let port = |text: &str| match text.trim().parse::<u16>() {
+        Ok(0) | Err(_) => Err(invalid(text.trim())),
+        Ok(port) => Ok(port),
+    };
+
+    let mut ports = Vec::new();
+    for part in s.split(',').filter(|part| !part.trim().is_empty()) {
+        let range = match part.split_once('-') {
+            Some((start, end)) => port(start)?..=port(end)?,
+            None => {
+                let single = port(part)?;
+                single..=single
+            }
+        };
+        if range.is_empty() {
+            return Err(invalid(part.trim()));
+        }
+        for port in range {
+            if !ports.contains(&port) {
+                ports.push(port);
+            }
+            if ports.len() > MAX_PORTS {
+                return Err(format!(
+                    "At most {} ports can be checked at once",
+                    MAX_PORTS
+                ));
+            }
+        }
+    }
+
+    if ports.is_empty() {
+        return Err("No ports given".to_string());
+    }
+    Ok(ports)
+}
+
+/// Try a TCP handshake; a refusal means closed, silence or an unreachable error filtered
+async fn check_tcp(addr: SocketAddr, timeout: Duration) -> PortCheckResult {
+    let start = Instant::now();
+    let (state, time_ms) = match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => (PortState::Open, Some(elapsed_ms(start))),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+            (PortState::Closed, Some(elapsed_ms(start)))
+        }
+        Ok(Err(_)) | Err(_) => (PortState::Filtered, None),
+    };
+
+    PortCheckResult {
+        port: addr.port(),
+        protocol: Protocol::Tcp,
+        state,
+        time_ms,
+    }
+}
+
+/// Send an empty datagram. Any reply means open and an ICMP port unreachable
+/// (reported as a refused or reset connection) means closed. Most UDP services
+/// ignore unexpected datagrams, so silence is only open|filtered.
+async fn check_udp(addr: SocketAddr, timeout: Duration) -> PortCheckResult {
+    let start = Instant::now();
+    let (state, time_ms) = match tokio::time::timeout(timeout, udp_probe(addr)).await {
+        Ok(Ok(())) => (PortState::Open, Some(elapsed_ms(start))),
+        Ok(Err(e))
+            if matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
+            ) =>
+        {
+            (PortState::Closed, Some(elapsed_ms(start)))
+        }
+        Ok(Err(_)) => (PortState::Filtered, None),
+        Err(_) => (PortState::OpenOrFiltered, None),
+    };
+
+    PortCheckResult {
+        port: addr.port(),
+        protocol: Protocol::Udp,
+        state,
+        time_ms,
+    }
+}
+
+async fn udp_probe(addr: SocketAddr) -> std::io::Result<()> {
+    let local: SocketAddr = if addr.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+    socket.send(&[]).await?;
+
+    let mut buf = [0u8; 512];
+    socket.recv(&mut buf).await?;
+    Ok(())
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("22,80,443,3389").unwrap(), [22, 80, 443, 3389]);
+        assert_eq!(
+            parse_ports("443, 8000-8002,443").unwrap(),
+            [443, 8000, 8001, 8002]
+        );
+        assert!(parse_ports("").is_err());
+        assert!(parse_ports("0").is_err());
+        assert!(parse_ports("http").is_err());
+        assert!(parse_ports("90-80").is_err());
+        assert!(parse_ports("1-65535").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_open_and_closed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+
+        let result = check_tcp(open, Duration::from_secs(2)).await;
+        assert_eq!(result.state, PortState::Open);
+        assert!(result.time_ms.is_some());
+
+        // A port that was just released has nothing listening on it
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let result = check_tcp(closed, Duration::from_secs(2)).await;
+        assert_eq!(result.state, PortState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_udp_answer_is_open() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 16];
+            let (_, peer) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(b"pong", peer).await.unwrap();
+        });
+
+        let result = check_udp(addr, Duration::from_secs(2)).await;
+        assert_eq!(result.state, PortState::Open);
+        assert_eq!(result.protocol, Protocol::Udp);
+    }
+
+    #[test]
+    fn test_state_display() {
+        assert_eq!(PortState::OpenOrFiltered.to_string(), "open|filtered");
+        assert_eq!(
+            serde_json::to_value(PortState::OpenOrFiltered).unwrap(),
+            "open_or_filtered"
+        );
+    }
+}
@@ -0,0 +1,718 @@
+//! Router Port Mapping Check
+//!
+//! Asks the gateway whether it offers NAT-PMP or UPnP IGD, the protocols games
+//! and self-hosted services use to open ports on their own. On request it also
+//! opens a short-lived TCP mapping, has an outside service connect to it and
+//! removes the mapping again, showing whether inbound connections get through.
+
+use colored::*;
+use prettytable::{format, Cell, Row, Table};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use strum_macros::Display;
+use tokio::net::{TcpListener, UdpSocket};
+
+use crate::modules::localnet;
+use crate::modules::terminal::print_table;
+use crate::modules::types::TestConfig;
+use crate::modules::ui::UI;
+
+const NAT_PMP_PORT: u16 = 5351;
+/// RFC 6886 starts retransmitting after 250 ms and doubles the wait each time
+const NAT_PMP_FIRST_WAIT: Duration = Duration::from_millis(250);
+const NAT_PMP_ATTEMPTS: u32 = 4;
+
+const SSDP_ADDRESS: SocketAddr = SocketAddr::V4(std::net::SocketAddrV4::new(
+    Ipv4Addr::new(239, 255, 255, 250),
+    1900,
+));
+const SSDP_WAIT: Duration = Duration::from_secs(3);
+const IGD_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+/// Services that can add port mappings, newest first
+const WAN_SERVICES: &[&str] = &[
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+const UPNP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Mappings expire on their own should the check be interrupted before removing them
+const MAPPING_LIFETIME_SECS: u32 = 120;
+const MAPPING_DESCRIPTION: &str = "netrunner_cli reachability check";
+
+/// Connects back to the caller's address on the given port
+const REACHABILITY_URL: &str = "https://ifconfig.co/port";
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingMethod {
+    #[strum(to_string = "NAT-PMP")]
+    NatPmp,
+    #[strum(to_string = "UPnP")]
+    Upnp,
+}
+
+/// A router answering NAT-PMP
+#[derive(Debug, Clone, Serialize)]
+pub struct NatPmpGateway {
+    pub external_address: Ipv4Addr,
+}
+
+/// A router announcing itself as a UPnP Internet Gateway Device
+#[derive(Debug, Clone, Serialize)]
+pub struct UpnpGateway {
+    /// Device description URL from the SSDP answer
+    pub location: String,
+    /// Router firmware as given in the SSDP `SERVER` header
+    pub server: Option<String>,
+    /// WAN service type used for port mappings
+    pub service: String,
+    pub control_url: String,
+    pub external_address: Option<IpAddr>,
+}
+
+/// Outcome of opening a temporary mapping and probing it from outside
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingAttempt {
+    pub method: Option<MappingMethod>,
+    pub internal_port: u16,
+    /// Port the router opened, which may differ from the one asked for
+    pub external_port: Option<u16>,
+    /// `None` when no probe could be made
+    pub reachable: Option<bool>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PortMapReport {
+    pub gateway: Ipv4Addr,
+    pub local_address: Ipv4Addr,
+    pub nat_pmp: Option<NatPmpGateway>,
+    pub upnp: Option<UpnpGateway>,
+    pub mapping: Option<MappingAttempt>,
+}
+
+impl PortMapReport {
+    /// Address the router itself reports on its WAN side
+    pub fn external_address(&self) -> Option<IpAddr> {
+        self.nat_pmp
+            .as_ref()
+            .map(|nat_pmp| IpAddr::V4(nat_pmp.external_address))
+            .or_else(|| self.upnp.as_ref().and_then(|upnp| upnp.external_address))
+    }
+
+    /// Router's WAN address is itself private, so there is a second NAT (often the ISP's) upstream
+    pub fn is_double_nat(&self) -> bool {
+        match self.external_address() {
+            Some(IpAddr::V4(address)) => is_non_public(address),
+            _ => false,
+        }
+    }
+}
+
+pub struct PortMapTool {
+    config: TestConfig,
+    ui: UI,
+}
+
+impl PortMapTool {
+    pub fn new(config: TestConfig) -> Self {
+        let ui = UI::new(config.clone());
+        Self { config, ui }
+    }
+
+    /// Look for NAT-PMP and UPnP on the gateway, then map and probe `map_port` when given
+    pub async fn run(
+        &self,
+        map_port: Option<u16>,
+    ) -> Result<PortMapReport, Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            self.ui.show_section_header("Router Port Mapping")?;
+        }
+
+        let local = localnet::detect()
+            .await
+            .ok_or("Could not determine the local network")?;
+        let gateway = local.gateway.ok_or("No default gateway found")?;
+
+        let pb = if !self.config.json_output && self.config.animation_enabled {
+            Some(self.ui.create_cyberpunk_spinner(&format!(
+                "QUERYING GATEWAY {} FOR NAT-PMP AND UPNP",
+                gateway
+            )))
+        } else {
+            None
+        };
+
+        let (nat_pmp, upnp) = tokio::join!(query_nat_pmp(gateway), discover_upnp(gateway));
+
+        if let Some(pb) = pb {
+            pb.finish_with_message("⟨⟨⟨ GATEWAY INTERROGATION COMPLETE ⟩⟩⟩");
+        }
+
+        let mut report = PortMapReport {
+            gateway,
+            local_address: local.address,
+            nat_pmp,
+            upnp,
+            mapping: None,
+        };
+
+        if let Some(port) = map_port {
+            let pb = if !self.config.json_output && self.config.animation_enabled {
+                Some(self.ui.create_cyberpunk_spinner(&format!(
+                    "MAPPING PORT {} AND PROBING FROM OUTSIDE",
+                    port
+                )))
+            } else {
+                None
+            };
+
+            report.mapping = Some(self.test_mapping(&report, port).await);
+
+            if let Some(pb) = pb {
+                pb.finish_with_message("⟨⟨⟨ INBOUND PROBE COMPLETE ⟩⟩⟩");
+            }
+        }
+
+        if !self.config.json_output {
+            self.display_results(&report)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Map `port` to this machine, probe it from outside and remove the mapping again
+    async fn test_mapping(&self, report: &PortMapReport, port: u16) -> MappingAttempt {
+        let mut attempt = MappingAttempt {
+            method: None,
+            internal_port: port,
+            external_port: None,
+            reachable: None,
+            error: None,
+        };
+
+        // The outside probe only sees the port open when something listens behind it
+        let _listener = match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                attempt.error = Some(format!("Could not listen on port {}: {}", port, e));
+                return attempt;
+            }
+        };
+
+        let mapped = if report.nat_pmp.is_some() {
+            attempt.method = Some(MappingMethod::NatPmp);
+            nat_pmp_map(report.gateway, port, port, MAPPING_LIFETIME_SECS).await
+        } else if let Some(upnp) = &report.upnp {
+            attempt.method = Some(MappingMethod::Upnp);
+            upnp_add_mapping(upnp, report.local_address, port)
+                .await
+                .map(|()| port)
+        } else {
+            Err("The router offers neither NAT-PMP nor UPnP".to_string())
+        };
+
+        let external_port = match mapped {
+            Ok(external_port) => external_port,
+            Err(e) => {
+                attempt.error = Some(e);
+                return attempt;
+            }
+        };
+        attempt.external_port = Some(external_port);
+
+        match check_reachable(external_port).await {
+            Ok(reachable) => attempt.reachable = Some(reachable),
+            Err(e) => attempt.error = Some(format!("Reachability probe failed: {}", e)),
+        }
+
+        // Best effort, the lifetime removes the mapping anyway
+        match attempt.method {
+            Some(MappingMethod::NatPmp) => {
+                let _ = nat_pmp_map(report.gateway, port, 0, 0).await;
+            }
+            Some(MappingMethod::Upnp) => {
+                if let Some(upnp) = &report.upnp {
+                    let _ = upnp_delete_mapping(upnp, external_port).await;
+                }
+            }
+            None => {}
+        }
+
+        attempt
+    }
+
+    fn display_results(&self, report: &PortMapReport) -> Result<(), Box<dyn std::error::Error>> {
+        println!();
+
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+
+        table.add_row(Row::new(vec![
+            Cell::new("🌐 Gateway").style_spec("Fb"),
+            Cell::new(&report.gateway.to_string()),
+        ]));
+
+        let nat_pmp = match &report.nat_pmp {
+            Some(nat_pmp) => format!("✅ available (WAN {})", nat_pmp.external_address),
+            None => "❌ no answer".to_string(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new("🔌 NAT-PMP").style_spec("Fb"),
+            Cell::new(&nat_pmp),
+        ]));
+
+        let upnp = match &report.upnp {
+            Some(upnp) => format!(
+                "✅ available ({}{})",
+                upnp.service.rsplit(':').nth(1).unwrap_or(&upnp.service),
+                upnp.server
+                    .as_ref()
+                    .map_or(String::new(), |server| format!(", {}", server))
+            ),
+            None => "❌ no answer".to_string(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new("📡 UPnP IGD").style_spec("Fb"),
+            Cell::new(&upnp),
+        ]));
+
+        if let Some(address) = report.external_address() {
+            table.add_row(Row::new(vec![
+                Cell::new("🛰️ Router WAN address").style_spec("Fb"),
+                Cell::new(&address.to_string()),
+            ]));
+        }
+
+        if let Some(mapping) = &report.mapping {
+            let mapped = match (mapping.method, mapping.external_port) {
+                (Some(method), Some(external_port)) => format!(
+                    "{} {} → {}:{}",
+                    method, external_port, report.local_address, mapping.internal_port
+                ),
+                (Some(method), None) => format!("{} mapping refused", method),
+                (None, _) => "not attempted".to_string(),
+            };
+            table.add_row(Row::new(vec![
+                Cell::new("🔀 Mapping").style_spec("Fb"),
+                Cell::new(&mapped),
+            ]));
+
+            let inbound = match mapping.reachable {
+                Some(true) => "✅ REACHABLE".green(),
+                Some(false) => "❌ BLOCKED".red(),
+                None => "⚠️ UNKNOWN".yellow(),
+            };
+            table.add_row(Row::new(vec![
+                Cell::new("📥 Inbound").style_spec("Fb"),
+                Cell::new(&inbound.to_string()),
+            ]));
+        }
+
+        print_table(&table)?;
+        println!();
+
+        if let Some(error) = report.mapping.as_ref().and_then(|m| m.error.as_ref()) {
+            println!("{} {}", "⚠️".yellow(), error.bright_red());
+        }
+
+        if report.nat_pmp.is_none() && report.upnp.is_none() {
+            println!(
+                "{}",
+                "💡 Automatic port mapping is off or unsupported. Games and servers behind this router need ports forwarded by hand.".bright_yellow()
+            );
+        }
+        if report.is_double_nat() {
+            println!(
+                "{}",
+                "💡 The router's WAN address is private, so another NAT sits upstream (often the ISP's CGNAT). Mappings on this router will not make you reachable from the internet.".bright_yellow()
+            );
+        } else if report.mapping.as_ref().and_then(|m| m.reachable) == Some(false) {
+            println!(
+                "{}",
+                "💡 The mapping was made but nothing got through. A firewall on this machine, the router or the ISP is blocking inbound connections.".bright_yellow()
+            );
+        }
+        if report.mapping.is_none() && (report.nat_pmp.is_some() || report.upnp.is_some()) {
+            println!(
+                "{}",
+                "💡 Pass --map-port PORT to open a temporary mapping and test inbound connections"
+                    .bright_yellow()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Send a NAT-PMP request with the RFC 6886 retransmission schedule and return the answer
+async fn nat_pmp_request(gateway: Ipv4Addr, request: &[u8]) -> std::io::Result<Vec<u8>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect((gateway, NAT_PMP_PORT)).await?;
+
+    let mut wait = NAT_PMP_FIRST_WAIT;
+    let mut buf = [0u8; 16];
+    for _ in 0..NAT_PMP_ATTEMPTS {
+        socket.send(request).await?;
+        if let Ok(received) = tokio::time::timeout(wait, socket.recv(&mut buf)).await {
+            return Ok(buf[..received?].to_vec());
+        }
+        wait *= 2;
+    }
+    Err(std::io::ErrorKind::TimedOut.into())
+}
+
+async fn query_nat_pmp(gateway: Ipv4Addr) -> Option<NatPmpGateway> {
+    let response = nat_pmp_request(gateway, &[0, 0]).await.ok()?;
+    let external_address = parse_nat_pmp_address(&response).ok()?;
+    Some(NatPmpGateway { external_address })
+}
+
+/// Ask for a TCP mapping, returning the external port granted; lifetime 0 removes it
+async fn nat_pmp_map(
+    gateway: Ipv4Addr,
+    internal_port: u16,
+    external_port: u16,
+    lifetime_secs: u32,
+) -> Result<u16, String> {
+    let request = nat_pmp_mapping_request(internal_port, external_port, lifetime_secs);
+    let response = nat_pmp_request(gateway, &request)
+        .await
+        .map_err(|e| format!("NAT-PMP request failed: {}", e))?;
+    parse_nat_pmp_mapping(&response)
+}
+
+/// TCP mapping request (opcode 2)
+fn nat_pmp_mapping_request(internal_port: u16, external_port: u16, lifetime_secs: u32) -> [u8; 12] {
+    let mut request = [0u8; 12];
+    request[1] = 2;
+    request[4..6].copy_from_slice(&internal_port.to_be_bytes());
+    request[6..8].copy_from_slice(&external_port.to_be_bytes());
+    request[8..12].copy_from_slice(&lifetime_secs.to_be_bytes());
+    request
+}
+
+/// Check the version, opcode and result code of a NAT-PMP answer
+fn check_nat_pmp_response(response: &[u8], opcode: u8, len: usize) -> Result<(), String> {
+    if response.len() < len || response[0] != 0 || response[1] != 128 + opcode {
+        return Err("Malformed NAT-PMP response".to_string());
+    }
+    match u16::from_be_bytes([response[2], response[3]]) {
+        0 => Ok(()),
+        1 => Err("NAT-PMP version not supported by the router".to_string()),
+        2 => Err("The router refused the mapping".to_string()),
+        3 => Err("The router has no internet connection".to_string()),
+        4 => Err("The router is out of mappings".to_string()),
+        code => Err(format!("NAT-PMP error {}", code)),
+    }
+}
+
+fn parse_nat_pmp_address(response: &[u8]) -> Result<Ipv4Addr, String> {
+    check_nat_pmp_response(response, 0, 12)?;
+    Ok(Ipv4Addr::new(
+        response[8],
+        response[9],
+        response[10],
+        response[11],
+    ))
+}
+
+fn parse_nat_pmp_mapping(response: &[u8]) -> Result<u16, String> {
+    check_nat_pmp_response(response, 2, 16)?;
+    Ok(u16::from_be_bytes([response[10], response[11]]))
+}
+
+/// Find the gateway's Internet Gateway Device over SSDP and read its WAN service
+async fn discover_upnp(gateway: Ipv4Addr) -> Option<UpnpGateway> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP_ADDRESS, IGD_DEVICE
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDRESS).await.ok()?;
+
+    // Other devices may answer too, only the gateway can map ports for us
+    let deadline = tokio::time::Instant::now() + SSDP_WAIT;
+    let mut buf = [0u8; 2048];
+    let (location, server) = loop {
+        let (len, from) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        if from.ip() != IpAddr::V4(gateway) {
+            continue;
+        }
+        if let Some(answer) = parse_ssdp_response(&String::from_utf8_lossy(&buf[..len])) {
+            break answer;
+        }
+    };
+
+    let client = Client::builder().timeout(UPNP_TIMEOUT).build().ok()?;
+    let description = client.get(&location).send().await.ok()?.text().await.ok()?;
+    let (service, control_path) = find_wan_service(&description)?;
+    let control_url = reqwest::Url::parse(&location)
+        .ok()?
+        .join(&control_path)
+        .ok()?
+        .to_string();
+
+    let mut upnp = UpnpGateway {
+        location,
+        server,
+        service,
+        control_url,
+        external_address: None,
+    };
+    upnp.external_address = soap_call(&upnp, "GetExternalIPAddress", &[])
+        .await
+        .ok()
+        .and_then(|body| xml_text(&body, "NewExternalIPAddress"))
+        .and_then(|address| address.parse().ok());
+    Some(upnp)
+}
+
+/// `LOCATION` and `SERVER` headers of an SSDP answer for an IGD
+fn parse_ssdp_response(response: &str) -> Option<(String, Option<String>)> {
+    let mut location = None;
+    let mut server = None;
+    for line in response.lines().skip(1) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "location" => location = Some(value),
+            "server" if !value.is_empty() => server = Some(value),
+            _ => {}
+        }
+    }
+    Some((location?, server))
+}
+
+/// Service type and control URL of the first WAN connection service in a device description
+fn find_wan_service(description: &str) -> Option<(String, String)> {
+    let services: Vec<(String, String)> = description
+        .split("<service>")
+        .skip(1)
+        .filter_map(|block| {
+            let block = block.split("</service>").next()?;
+            Some((
+                xml_text(block, "serviceType")?,
+                xml_text(block, "controlURL")?,
+            ))
+        })
+        .collect();
+
+    WAN_SERVICES.iter().find_map(|wanted| {
+        services
+            .iter()
+            .find(|(service, _)| service == wanted)
+            .cloned()
+    })
+}
+
+/// Text of the first `<tag>` element, ignoring namespace prefixes on the answer
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = xml.find(&format!("{}>", tag))? + tag.len() + 1;
+    let len = xml[open..].find("</")?;
+    Some(xml[open..open + len].trim().to_string())
+}
+
+async fn upnp_add_mapping(
+    upnp: &UpnpGateway,
+    internal_client: Ipv4Addr,
+    port: u16,
+) -> Result<(), String> {
+    let port = port.to_string();
+    soap_call(
+        upnp,
+        "AddPortMapping",
+        &[
+            ("NewRemoteHost", ""),
+            ("NewExternalPort", &port),
+            ("NewProtocol", "TCP"),
+            ("NewInternalPort", &port),
+            ("NewInternalClient", &internal_client.to_string()),
+            ("NewEnabled", "1"),
+            ("NewPortMappingDescription", MAPPING_DESCRIPTION),
+            ("NewLeaseDuration", &MAPPING_LIFETIME_SECS.to_string()),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+async fn upnp_delete_mapping(upnp: &UpnpGateway, external_port: u16) -> Result<(), String> {
+    soap_call(
+        upnp,
+        "DeletePortMapping",
+        &[
+            ("NewRemoteHost", ""),
+            ("NewExternalPort", &external_port.to_string()),
+            ("NewProtocol", "TCP"),
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Invoke `action` on the WAN service, returning the response body
+async fn soap_call(
+    upnp: &UpnpGateway,
+    action: &str,
+    arguments: &[(&str, &str)],
+) -> Result<String, String> {
+    let arguments: String = arguments
+        .iter()
+        .map(|(name, value)| format!("<{0}>{1}</{0}>", name, value))
+        .collect();
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:{0} xmlns:u=\"{1}\">{2}</u:{0}></s:Body></s:Envelope>",
+        action, upnp.service, arguments
+    );
+
+    let client = Client::builder()
+        .timeout(UPNP_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .post(&upnp.control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header("SOAPAction", format!("\"{}#{}\"", upnp.service, action))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("UPnP {} failed: {}", action, e))?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+
+    if status.is_success() {
+        Ok(text)
+    } else {
+        Err(format!(
+            "UPnP {} failed: {}",
+            action,
+            xml_text(&text, "errorDescription").unwrap_or_else(|| status.to_string())
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct PortProbe {
+    reachable: bool,
+}
+
+/// Have an outside service connect to our public IPv4 address on `port`
+async fn check_reachable(port: u16) -> Result<bool, Box<dyn std::error::Error>> {
+    // The service probes the address the request came from, which must be the IPv4 one we mapped
+    let client = Client::builder()
+        .timeout(REACHABILITY_TIMEOUT)
+        .local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+        .build()?;
+    let probe: PortProbe = client
+        .get(format!("{}/{}", REACHABILITY_URL, port))
+        .header("Accept", "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(probe.reachable)
+}
+
+/// Private, shared (CGNAT) or otherwise not routable on the internet
+fn is_non_public(address: Ipv4Addr) -> bool {
+    let [a, b, ..] = address.octets();
+    address.is_private()
+        || address.is_loopback()
+        || address.is_link_local()
+        || address.is_unspecified()
+        || (a == 100 && (64..128).contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nat_pmp_messages() {
+        assert_eq!(
+            nat_pmp_mapping_request(8080, 8080, 120),
+            [0, 2, 0, 0, 0x1f, 0x90, 0x1f, 0x90, 0, 0, 0, 120]
+        );
+
+        let address = [0, 128, 0, 0, 0, 0, 1, 0, 203, 0, 113, 7];
+        assert_eq!(
+            parse_nat_pmp_address(&address),
+            Ok(Ipv4Addr::new(203, 0, 113, 7))
+        );
+
+        let mapping = [
+            0, 130, 0, 0, 0, 0, 1, 0, 0x1f, 0x90, 0x1f, 0x91, 0, 0, 0, 120,
+        ];
+        assert_eq!(parse_nat_pmp_mapping(&mapping), Ok(8081));
+
+        let refused = [0, 130, 0, 2, 0, 0, 1, 0, 0x1f, 0x90, 0, 0, 0, 0, 0, 0];
+        assert!(parse_nat_pmp_mapping(&refused).is_err());
+        // An address answer is not a mapping answer
+        assert!(parse_nat_pmp_mapping(&address).is_err());
+    }
+
+    #[test]
+    fn test_parse_ssdp_response() {
+        let response = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nLocation: http://192.168.1.1:5000/rootDesc.xml\r\nSERVER: OpenWRT/23.05 UPnP/1.1 MiniUPnPd/2.3.3\r\n\r\n";
+        assert_eq!(
+            parse_ssdp_response(response),
+            Some((
+                "http://192.168.1.1:5000/rootDesc.xml".to_string(),
+                Some("OpenWRT/23.05 UPnP/1.1 MiniUPnPd/2.3.3".to_string())
+            ))
+        );
+        assert_eq!(parse_ssdp_response("HTTP/1.1 200 OK\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_find_wan_service() {
+        let description = r#"<root><device><serviceList>
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType><controlURL>/ctl/L3F</controlURL></service>
+            </serviceList><deviceList><device><serviceList>
+            <service>
+              <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+              <controlURL>/ctl/IPConn</controlURL>
+            </service>
+            </serviceList></device></deviceList></device></root>"#;
+        assert_eq!(
+            find_wan_service(description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1".to_string(),
+                "/ctl/IPConn".to_string()
+            ))
+        );
+
+        let answer = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse><NewExternalIPAddress>198.51.100.4</NewExternalIPAddress></u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(
+            xml_text(answer, "NewExternalIPAddress"),
+            Some("198.51.100.4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_double_nat() {
+        let report = |external_address| PortMapReport {
+            gateway: Ipv4Addr::new(192, 168, 1, 1),
+            local_address: Ipv4Addr::new(192, 168, 1, 20),
+            nat_pmp: Some(NatPmpGateway { external_address }),
+            upnp: None,
+            mapping: None,
+        };
+        assert!(report(Ipv4Addr::new(100, 72, 1, 9)).is_double_nat());
+        assert!(report(Ipv4Addr::new(10, 0, 0, 2)).is_double_nat());
+        assert!(!report(Ipv4Addr::new(203, 0, 113, 7)).is_double_nat());
+    }
+}
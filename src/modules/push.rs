@@ -0,0 +1,163 @@
+//! Push Notifications
+//!
+//! Sends monitor alerts to a phone through ntfy or Pushover, each set up with one
+//! line in `config.toml` (or `NETRUNNER_NTFY` / `NETRUNNER_PUSHOVER`):
+//!
+//! ```toml
+//! ntfy = "netrunner-home-7f3a"            # topic on ntfy.sh, or a full topic URL
+//! pushover = "APP_TOKEN:USER_KEY"
+//! ```
+//!
+//! Unlike email there is no digest: a push is only useful while it's news, so
+//! every alert is sent right away. Outages go out with high priority.
+
+use reqwest::{Client, Request};
+use serde_json::json;
+use std::time::Duration;
+
+use crate::modules::types::{AlertKind, MonitorAlert, PushTarget};
+
+const NTFY_SERVER: &str = "https://ntfy.sh";
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Targets from the `ntfy` and `pushover` config values
+pub fn targets(ntfy: Option<&str>, pushover: Option<&str>) -> Result<Vec<PushTarget>, String> {
+    let mut targets = Vec::new();
+
+    if let Some(topic) = ntfy.map(str::trim) {
+        let url = if topic.starts_with("https://") || topic.starts_with("http://") {
+            topic.trim_end_matches('/').to_string()
+        } else if !topic.is_empty()
+            && topic
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            format!("{}/{}", NTFY_SERVER, topic)
+        } else {
+            return Err(format!(
+                "Invalid ntfy topic '{}', expected a name of letters, digits, - and _ or a URL",
+                topic
+            ));
+        };
+        targets.push(PushTarget::Ntfy(url));
+    }
+
+    if let Some(keys) = pushover {
+        match keys.trim().split_once(':') {
+            Some((token, user)) if !token.is_empty() && !user.is_empty() => {
+                targets.push(PushTarget::Pushover {
+                    token: token.to_string(),
+                    user: user.to_string(),
+                });
+            }
+            _ => return Err("Invalid pushover keys, expected APP_TOKEN:USER_KEY".to_string()),
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Push `alert` to `target`
+pub async fn notify(
+    client: &Client,
+    target: &PushTarget,
+    host_label: Option<&str>,
+    alert: &MonitorAlert,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = request(client, target, host_label, alert)?;
+    client.execute(request).await?.error_for_status()?;
+    Ok(())
+}
+
+fn request(
+    client: &Client,
+    target: &PushTarget,
+    host_label: Option<&str>,
+    alert: &MonitorAlert,
+) -> reqwest::Result<Request> {
+    let title = match host_label {
+        Some(label) => format!("netrunner {}: {}", label, alert.kind),
+        None => format!("netrunner: {}", alert.kind),
+    };
+    let urgent = alert.kind == AlertKind::Outage;
+
+    match target {
+        PushTarget::Ntfy(url) => client
+            .post(url)
+            .header("Title", title)
+            .header("Priority", if urgent { "high" } else { "default" })
+            .header("Tags", if urgent { "rotating_light" } else { "turtle" })
+            .body(alert.message.clone())
+            .timeout(SEND_TIMEOUT)
+            .build(),
+        PushTarget::Pushover { token, user } => client
+            .post(PUSHOVER_URL)
+            .json(&json!({
+                "token": token,
+                "user": user,
+                "title": title,
+                "message": alert.message,
+                "priority": if urgent { 1 } else { 0 },
+            }))
+            .timeout(SEND_TIMEOUT)
+            .build(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_targets() {
+        assert_eq!(targets(None, None).unwrap(), vec![]);
+        assert_eq!(
+            targets(Some("netrunner-home_1"), Some("tok:usr")).unwrap(),
+            vec![
+                PushTarget::Ntfy("https://ntfy.sh/netrunner-home_1".to_string()),
+                PushTarget::Pushover {
+                    token: "tok".to_string(),
+                    user: "usr".to_string()
+                }
+            ]
+        );
+        assert_eq!(
+            targets(Some("https://ntfy.example.com/alerts/"), None).unwrap(),
+            vec![PushTarget::Ntfy(
+                "https://ntfy.example.com/alerts".to_string()
+            )]
+        );
+        assert!(targets(Some("my topic"), None).is_err());
+        assert!(targets(None, Some("tok")).is_err());
+        assert!(targets(None, Some(":usr")).is_err());
+    }
+
+    #[test]
+    fn test_requests() {
+        let client = Client::new();
+        let alert = MonitorAlert {
+            timestamp: Utc::now(),
+            kind: AlertKind::Outage,
+            message: "Download failed: connection reset".to_string(),
+        };
+
+        let ntfy = PushTarget::Ntfy("https://ntfy.sh/netrunner".to_string());
+        let ntfy = request(&client, &ntfy, Some("nas"), &alert).unwrap();
+        assert_eq!(ntfy.url().as_str(), "https://ntfy.sh/netrunner");
+        assert_eq!(ntfy.headers()["Title"], "netrunner nas: Outage");
+        assert_eq!(ntfy.headers()["Priority"], "high");
+
+        let pushover = PushTarget::Pushover {
+            token: "tok".to_string(),
+            user: "usr".to_string(),
+        };
+        let pushover = request(&client, &pushover, None, &alert).unwrap();
+        let body: serde_json::Value =
+            serde_json::from_slice(pushover.body().unwrap().as_bytes().unwrap()).unwrap();
+        assert_eq!(body["title"], "netrunner: Outage");
+        assert_eq!(body["priority"], 1);
+        assert_eq!(body["user"], "usr");
+    }
+}
@@ -0,0 +1,744 @@
+//! QoS Marking and TCP Inspection
+//!
+//! reqwest does not expose the sockets it connects, so a speed test run with
+//! `--dscp` or at the debug detail level sends its traffic through a relay on the
+//! loopback interface. The relay opens every upstream connection itself, with the
+//! code point set when marking. HTTPS arrives as CONNECT and the still encrypted
+//! stream is passed through in both directions. Plain HTTP arrives as requests in
+//! absolute form, each sent on over a connection of its own.
+//!
+//! Upstream hosts are looked up in the test's `ResolverCache`, so the relay
+//! connects to the same addresses as the rest of the test.
+//!
+//! Owning the sockets also lets the relay sample their kernel state (`TCP_INFO`
+//! on Linux, `TCP_CONNECTION_INFO` on macOS): round trip time, congestion window
+//! and retransmissions, summed up as the test's `TcpHealth`.
+
+use reqwest::header::HeaderValue;
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::modules::resolver::ResolverCache;
+use crate::modules::types::{Dscp, TcpHealth};
+
+/// Longest request head accepted from the client
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// How often the state of every relayed connection is read
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Loopback proxy owning the connections it opens, stopped on drop
+pub struct SocketRelay {
+    address: SocketAddr,
+    /// Other local users cannot borrow the relay without this header
+    authorization: String,
+    /// Plain HTTP is only relayed when marking, see `proxy`
+    marking: bool,
+    task: JoinHandle<()>,
+}
+
+impl SocketRelay {
+    /// Listen on a free loopback port, must be called inside the tokio runtime.
+    /// Connections are marked with `dscp` and sampled into `inspector` if given.
+    pub fn start(
+        dscp: Option<Dscp>,
+        inspector: Option<Arc<TcpInspector>>,
+        resolver: ResolverCache,
+    ) -> io::Result<Self> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|e| io::Error::other(format!("QoS relay needs a runtime: {}", e)))?;
+        let _guard = runtime.enter();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let listener = TcpListener::from_std(listener)?;
+        let authorization = format!(
+            "Bearer {:016x}{:016x}",
+            rand::random::<u64>(),
+            rand::random::<u64>()
+        );
+
+        let expected = authorization.clone();
+        let task = runtime.spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let expected = expected.clone();
+                let inspector = inspector.clone();
+                let resolver = resolver.clone();
+                tokio::spawn(async move {
+                    let _ = relay(client, dscp, inspector, &resolver, &expected).await;
+                });
+            }
+        });
+
+        Ok(Self {
+            address,
+            authorization,
+            marking: dscp.is_some(),
+            task,
+        })
+    }
+
+    /// Proxy routing the client's requests through the relay. Only HTTPS goes
+    /// through when just inspecting, so plain HTTP servers keep working as before.
+    pub fn proxy(&self) -> reqwest::Result<reqwest::Proxy> {
+        let authorization = HeaderValue::from_str(&self.authorization)
+            .expect("relay token is a valid header value");
+        let url = format!("http://{}", self.address);
+        let proxy = if self.marking {
+            reqwest::Proxy::all(url)?
+        } else {
+            reqwest::Proxy::https(url)?
+        };
+        Ok(proxy.custom_http_auth(authorization))
+    }
+}
+
+impl Drop for SocketRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serve one CONNECT or plain HTTP request, then copy bytes until either side closes
+async fn relay(
+    mut client: TcpStream,
+    dscp: Option<Dscp>,
+    inspector: Option<Arc<TcpInspector>>,
+    resolver: &ResolverCache,
+    authorization: &str,
+) -> io::Result<()> {
+    let head = read_request_head(&mut client).await?;
+    let request = match parse_request(&head, authorization) {
+        Ok(request) => request,
+        Err(status) => {
+            client
+                .write_all(format!("HTTP/1.1 {}\r\n\r\n", status).as_bytes())
+                .await?;
+            return Ok(());
+        }
+    };
+    let target = match &request {
+        ProxyRequest::Tunnel(target) | ProxyRequest::Forward { target, .. } => target,
+    };
+
+    let mut upstream = match connect(target, dscp, resolver).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            client
+                .write_all(format!("HTTP/1.1 502 {}\r\n\r\n", e).as_bytes())
+                .await?;
+            return Err(e);
+        }
+    };
+    match &request {
+        ProxyRequest::Tunnel(_) => {
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?;
+        }
+        // The body, if any, follows with the copy
+        ProxyRequest::Forward { head, .. } => upstream.write_all(head.as_bytes()).await?,
+    }
+
+    let Some(inspector) = inspector else {
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        return Ok(());
+    };
+
+    // The stream lives until the copy ends, so its socket can be read meanwhile
+    let socket = socket_handle(&upstream);
+    let id = inspector.register();
+    let copy = tokio::io::copy_bidirectional(&mut client, &mut upstream);
+    tokio::pin!(copy);
+    let mut ticks = tokio::time::interval(SAMPLE_INTERVAL);
+    let copied = loop {
+        tokio::select! {
+            copied = &mut copy => break copied,
+            _ = ticks.tick() => {
+                if let Some(sample) = read_tcp_info(socket) {
+                    inspector.record(id, sample);
+                }
+            }
+        }
+    };
+    inspector.close(id);
+    copied?;
+    Ok(())
+}
+
+/// Everything up to the blank line ending the request head, read a byte at a
+/// time so none of the tunnelled stream is consumed with it
+async fn read_request_head(client: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_HEAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too long",
+            ));
+        }
+        head.push(client.read_u8().await?);
+    }
+    String::from_utf8(head).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// What a client asked the relay for
+#[derive(Debug, PartialEq)]
+enum ProxyRequest {
+    /// CONNECT to `host:port`
+    Tunnel(String),
+    /// A plain HTTP request for `host:port`, `head` rewritten to send it there
+    Forward { target: String, head: String },
+}
+
+/// The request in `head`, or the status line to refuse it with. Forwarded requests
+/// lose the proxy headers and ask the server to close the connection after its
+/// response, so the client cannot send the next request, maybe for another host,
+/// over the same upstream connection.
+fn parse_request(head: &str, authorization: &str) -> Result<ProxyRequest, &'static str> {
+    let mut lines = head.trim_end().split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let headers: Vec<&str> = lines.collect();
+
+    let authorized = headers.iter().any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("proxy-authorization") && value.trim() == authorization
+        })
+    });
+    if !authorized {
+        return Err("407 Proxy Authentication Required");
+    }
+
+    let mut parts = request_line.split(' ');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("CONNECT"), Some(target), _) if target.contains(':') => {
+            Ok(ProxyRequest::Tunnel(target.to_string()))
+        }
+        (Some(method), Some(target), Some(version)) if target.starts_with("http://") => {
+            let url = url::Url::parse(target).map_err(|_| "400 Bad Request")?;
+            let host = url.host_str().ok_or("400 Bad Request")?;
+            let port = url.port_or_known_default().unwrap_or(80);
+
+            let mut head = format!(
+                "{} {} {}\r\n",
+                method,
+                &url[url::Position::BeforePath..],
+                version
+            );
+            for line in headers {
+                let name = line.split_once(':').map_or(line, |(name, _)| name.trim());
+                if !["proxy-authorization", "proxy-connection", "connection"]
+                    .iter()
+                    .any(|skipped| name.eq_ignore_ascii_case(skipped))
+                {
+                    head.push_str(line);
+                    head.push_str("\r\n");
+                }
+            }
+            head.push_str("Connection: close\r\n\r\n");
+
+            Ok(ProxyRequest::Forward {
+                target: format!("{}:{}", host, port),
+                head,
+            })
+        }
+        _ => Err("405 Method Not Allowed"),
+    }
+}
+
+/// Connect to the first address of `target` that accepts, marked with `dscp`
+async fn connect(
+    target: &str,
+    dscp: Option<Dscp>,
+    resolver: &ResolverCache,
+) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host has no addresses");
+    for address in resolver.lookup_target(target).await? {
+        let socket = match address {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if let Some(dscp) = dscp {
+            mark(&socket, address, dscp)?;
+        }
+        match socket.connect(address).await {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn mark(socket: &TcpSocket, address: SocketAddr, dscp: Dscp) -> io::Result<()> {
+    match address {
+        SocketAddr::V4(_) => socket.set_tos_v4(dscp.tos()),
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        SocketAddr::V6(_) => socket.set_tclass_v6(dscp.tos()),
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd"
+        )))]
+        SocketAddr::V6(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "IPv6 traffic class cannot be set on this platform",
+        )),
+    }
+}
+
+/// One reading of a connection's kernel state
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TcpSample {
+    /// Smoothed round trip time
+    pub rtt_ms: f64,
+    pub cwnd_bytes: u64,
+    pub segments_sent: Option<u64>,
+    pub segments_received: u64,
+    /// Segments sent again since the connection opened
+    pub retransmits: u64,
+}
+
+#[derive(Debug, Default)]
+struct ConnectionSamples {
+    /// Last reading before the current measurement began
+    baseline: Option<TcpSample>,
+    latest: Option<TcpSample>,
+    /// Readings taken while the connection moved data
+    busy: Vec<TcpSample>,
+    closed: bool,
+}
+
+/// Collects the samples of every relayed connection
+#[derive(Debug, Default)]
+pub struct TcpInspector {
+    next_id: AtomicU64,
+    connections: Mutex<HashMap<u64, ConnectionSamples>>,
+}
+
+impl TcpInspector {
+    /// Whether this platform lets a socket's TCP state be read
+    pub fn is_supported() -> bool {
+        cfg!(any(target_os = "linux", target_os = "macos"))
+    }
+
+    fn register(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, ConnectionSamples::default());
+        id
+    }
+
+    fn record(&self, id: u64, sample: TcpSample) {
+        let mut connections = self.lock();
+        let Some(connection) = connections.get_mut(&id) else {
+            return;
+        };
+        // Idle pooled connections would only pull the round trip times down
+        let moved = connection.latest.is_none_or(|latest| {
+            latest.segments_received != sample.segments_received
+                || latest.segments_sent != sample.segments_sent
+                || latest.retransmits != sample.retransmits
+        });
+        if moved {
+            connection.busy.push(sample);
+        }
+        connection.latest = Some(sample);
+    }
+
+    fn close(&self, id: u64) {
+        if let Some(connection) = self.lock().get_mut(&id) {
+            connection.closed = true;
+        }
+    }
+
+    /// Start a new measurement, earlier traffic no longer counts
+    pub fn reset(&self) {
+        let mut connections = self.lock();
+        connections.retain(|_, connection| !connection.closed);
+        for connection in connections.values_mut() {
+            connection.baseline = connection.latest;
+            connection.busy.clear();
+        }
+    }
+
+    /// Summary of the connections since the last `reset`, None when no
+    /// connection moved data
+    pub fn health(&self) -> Option<TcpHealth> {
+        summarize(self.lock().values())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, ConnectionSamples>> {
+        self.connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn summarize<'a>(connections: impl Iterator<Item = &'a ConnectionSamples>) -> Option<TcpHealth> {
+    let busy: Vec<&ConnectionSamples> = connections
+        .filter(|connection| !connection.busy.is_empty())
+        .collect();
+    if busy.is_empty() {
+        return None;
+    }
+
+    let mut segments_sent = Some(0);
+    let mut retransmits = 0;
+    for connection in &busy {
+        let (Some(latest), baseline) = (connection.latest, connection.baseline.unwrap_or_default())
+        else {
+            continue;
+        };
+        segments_sent = segments_sent
+            .zip(latest.segments_sent)
+            .map(|(total, sent)| total + sent.saturating_sub(baseline.segments_sent.unwrap_or(0)));
+        retransmits += latest.retransmits.saturating_sub(baseline.retransmits);
+    }
+
+    let samples: Vec<TcpSample> = busy
+        .iter()
+        .flat_map(|connection| connection.busy.iter().copied())
+        .collect();
+    let mut rtts: Vec<f64> = samples.iter().map(|sample| sample.rtt_ms).collect();
+    rtts.sort_by(f64::total_cmp);
+    let mut cwnds: Vec<u64> = samples.iter().map(|sample| sample.cwnd_bytes).collect();
+    cwnds.sort_unstable();
+
+    Some(TcpHealth {
+        connections: busy.len(),
+        samples: samples.len(),
+        segments_sent,
+        retransmits,
+        rtt_min_ms: rtts[0],
+        rtt_median_ms: rtts[rtts.len() / 2],
+        rtt_max_ms: rtts[rtts.len() - 1],
+        cwnd_median_bytes: cwnds[cwnds.len() / 2],
+    })
+}
+
+#[cfg(unix)]
+type SocketHandle = std::os::fd::RawFd;
+#[cfg(not(unix))]
+type SocketHandle = ();
+
+#[cfg(unix)]
+fn socket_handle(stream: &TcpStream) -> SocketHandle {
+    use std::os::fd::AsRawFd;
+    stream.as_raw_fd()
+}
+
+#[cfg(not(unix))]
+fn socket_handle(_stream: &TcpStream) -> SocketHandle {}
+
+/// Read `TCP_INFO`. The kernel struct is declared here up to `tcpi_segs_in`
+/// (Linux 4.2), as libc's copy stops before the segment counters.
+#[cfg(target_os = "linux")]
+fn read_tcp_info(socket: SocketHandle) -> Option<TcpSample> {
+    #[repr(C)]
+    #[derive(Default)]
+    struct TcpInfo {
+        state_and_flags: [u8; 8],
+        rto: u32,
+        ato: u32,
+        snd_mss: u32,
+        rcv_mss: u32,
+        unacked: u32,
+        sacked: u32,
+        lost: u32,
+        retrans: u32,
+        fackets: u32,
+        last_data_sent: u32,
+        last_ack_sent: u32,
+        last_data_recv: u32,
+        last_ack_recv: u32,
+        pmtu: u32,
+        rcv_ssthresh: u32,
+        rtt: u32,
+        rttvar: u32,
+        snd_ssthresh: u32,
+        snd_cwnd: u32,
+        advmss: u32,
+        reordering: u32,
+        rcv_rtt: u32,
+        rcv_space: u32,
+        total_retrans: u32,
+        pacing_rate: u64,
+        max_pacing_rate: u64,
+        bytes_acked: u64,
+        bytes_received: u64,
+        segs_out: u32,
+        segs_in: u32,
+    }
+
+    let mut info = TcpInfo::default();
+    let mut len = std::mem::size_of::<TcpInfo>() as libc::socklen_t;
+    // SAFETY: the buffer is a plain C struct of `len` bytes and the socket is
+    // kept open by the caller
+    let status = unsafe {
+        libc::getsockopt(
+            socket,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            (&mut info as *mut TcpInfo).cast(),
+            &mut len,
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+    // Kernels before 4.2 stop short of the segment counters, leaving them zero
+    let counted = len as usize >= std::mem::size_of::<TcpInfo>();
+    Some(TcpSample {
+        rtt_ms: info.rtt as f64 / 1000.0,
+        cwnd_bytes: info.snd_cwnd as u64 * info.snd_mss as u64,
+        segments_sent: counted.then_some(info.segs_out as u64),
+        segments_received: info.segs_in as u64,
+        retransmits: info.total_retrans as u64,
+    })
+}
+
+/// Read `TCP_CONNECTION_INFO`, which counts retransmitted bytes rather than segments
+#[cfg(target_os = "macos")]
+fn read_tcp_info(socket: SocketHandle) -> Option<TcpSample> {
+    // SAFETY: tcp_connection_info is a plain C struct, all zeroes is valid
+    let mut info: libc::tcp_connection_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_connection_info>() as libc::socklen_t;
+    // SAFETY: the buffer is `len` bytes and the socket is kept open by the caller
+    let status = unsafe {
+        libc::getsockopt(
+            socket,
+            libc::IPPROTO_TCP,
+            libc::TCP_CONNECTION_INFO,
+            (&mut info as *mut libc::tcp_connection_info).cast(),
+            &mut len,
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+    let mss = info.tcpi_maxseg.max(1) as u64;
+    Some(TcpSample {
+        rtt_ms: info.tcpi_srtt as f64,
+        cwnd_bytes: info.tcpi_snd_cwnd as u64,
+        segments_sent: Some(info.tcpi_txpackets),
+        segments_received: info.tcpi_rxpackets,
+        retransmits: info.tcpi_txretransmitbytes / mss,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_tcp_info(_socket: SocketHandle) -> Option<TcpSample> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let head = "CONNECT speed.cloudflare.com:443 HTTP/1.1\r\n\
+                    Host: speed.cloudflare.com:443\r\n\
+                    proxy-authorization: Bearer secret\r\n\r\n";
+        assert_eq!(
+            parse_request(head, "Bearer secret"),
+            Ok(ProxyRequest::Tunnel("speed.cloudflare.com:443".to_string()))
+        );
+        assert_eq!(
+            parse_request(head, "Bearer other"),
+            Err("407 Proxy Authentication Required")
+        );
+
+        let get = "GET http://ip-api.com/json/?fields=country HTTP/1.1\r\n\
+                   Host: ip-api.com\r\n\
+                   Proxy-Authorization: Bearer secret\r\n\
+                   Connection: keep-alive\r\n\r\n";
+        assert_eq!(
+            parse_request(get, "Bearer secret"),
+            Ok(ProxyRequest::Forward {
+                target: "ip-api.com:80".to_string(),
+                head: "GET /json/?fields=country HTTP/1.1\r\n\
+                       Host: ip-api.com\r\n\
+                       Connection: close\r\n\r\n"
+                    .to_string(),
+            })
+        );
+
+        let other = "GET /json/ HTTP/1.1\r\nProxy-Authorization: Bearer secret\r\n\r\n";
+        assert_eq!(
+            parse_request(other, "Bearer secret"),
+            Err("405 Method Not Allowed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_marked() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        let stream = connect(&target, Some(Dscp::EF), &ResolverCache::default())
+            .await
+            .unwrap();
+        let tos = socket2::SockRef::from(&stream).tos_v4().unwrap();
+        assert_eq!(tos, Dscp::EF.tos());
+    }
+
+    #[tokio::test]
+    async fn test_relay_tunnels() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_address = upstream.local_addr().unwrap();
+        let echo = tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let relay = SocketRelay::start(Some(Dscp::EF), None, ResolverCache::default()).unwrap();
+        let mut client = TcpStream::connect(relay.address).await.unwrap();
+        let request = format!(
+            "CONNECT {} HTTP/1.1\r\nProxy-Authorization: {}\r\n\r\n",
+            upstream_address, relay.authorization
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let mut reply = Vec::new();
+        while !reply.ends_with(b"\r\n\r\n") {
+            reply.push(client.read_u8().await.unwrap());
+        }
+        assert!(reply.starts_with(b"HTTP/1.1 200"));
+
+        client.write_all(b"ping").await.unwrap();
+        let mut echoed = [0u8; 4];
+        client.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+        echo.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_relay_forwards_plain_http() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_address = upstream.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let head = read_request_head(&mut stream).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            head
+        });
+
+        let relay = SocketRelay::start(Some(Dscp::EF), None, ResolverCache::default()).unwrap();
+        let mut client = TcpStream::connect(relay.address).await.unwrap();
+        let request = format!(
+            "GET http://{}/json/ HTTP/1.1\r\nHost: {}\r\nProxy-Authorization: {}\r\n\r\n",
+            upstream_address, upstream_address, relay.authorization
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert!(reply.starts_with(b"HTTP/1.1 200 OK"));
+        assert!(reply.ends_with(b"ok"));
+
+        let head = server.await.unwrap();
+        assert!(head.starts_with("GET /json/ HTTP/1.1\r\n"));
+        assert!(!head.to_ascii_lowercase().contains("proxy-authorization"));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let sample = |rtt_ms, segments_sent, retransmits| TcpSample {
+            rtt_ms,
+            cwnd_bytes: 64 * 1024,
+            segments_sent: Some(segments_sent),
+            segments_received: segments_sent,
+            retransmits,
+        };
+        let inspector = TcpInspector::default();
+        let first = inspector.register();
+        let second = inspector.register();
+        let idle = inspector.register();
+
+        inspector.record(first, sample(10.0, 1_000, 5));
+        inspector.record(idle, sample(9.0, 10, 0));
+        inspector.reset();
+        assert_eq!(inspector.health(), None, "nothing moved since the reset");
+
+        inspector.record(first, sample(40.0, 3_000, 25));
+        inspector.record(second, sample(12.0, 1_000, 10));
+        inspector.record(second, sample(30.0, 2_000, 30));
+        inspector.record(idle, sample(9.0, 10, 0));
+        inspector.close(second);
+
+        let health = inspector.health().unwrap();
+        assert_eq!(health.connections, 2);
+        assert_eq!(health.samples, 3);
+        assert_eq!(health.segments_sent, Some(4_000));
+        assert_eq!(health.retransmits, 50);
+        assert_eq!(health.retransmit_percent(), Some(1.25));
+        assert!(health.is_loss_limited());
+        assert_eq!(health.rtt_min_ms, 12.0);
+        assert_eq!(health.rtt_median_ms, 30.0);
+        assert_eq!(health.rtt_inflation(), 2.5);
+
+        // Closed connections go with the next reset
+        inspector.reset();
+        assert_eq!(inspector.lock().len(), 2);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[tokio::test]
+    async fn test_relay_samples_connections() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream_address = upstream.local_addr().unwrap();
+        let sink = tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.unwrap();
+            received.len()
+        });
+
+        let inspector = Arc::new(TcpInspector::default());
+        let relay =
+            SocketRelay::start(None, Some(inspector.clone()), ResolverCache::default()).unwrap();
+        let mut client = TcpStream::connect(relay.address).await.unwrap();
+        let request = format!(
+            "CONNECT {} HTTP/1.1\r\nProxy-Authorization: {}\r\n\r\n",
+            upstream_address, relay.authorization
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        while !reply.ends_with(b"\r\n\r\n") {
+            reply.push(client.read_u8().await.unwrap());
+        }
+
+        for _ in 0..3 {
+            client.write_all(&[0u8; 64 * 1024]).await.unwrap();
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+        }
+        drop(client);
+        assert_eq!(sink.await.unwrap(), 3 * 64 * 1024);
+
+        let health = inspector.health().unwrap();
+        assert_eq!(health.connections, 1);
+        assert!(health.samples >= 2);
+        assert!(health.cwnd_median_bytes > 0);
+    }
+}
@@ -0,0 +1,267 @@
+//! Courtesy Controls for Public Endpoints
+//!
+//! The default test servers are free, shared infrastructure. Two guards keep
+//! netrunner a polite client:
+//! - `ProviderRateLimiter` paces outgoing requests per provider so retry loops
+//!   and parallel transfers cannot flood a single operator
+//! - `ProbePacer` holds latency probes to one server under a per-minute limit,
+//!   for proxies that flag bursts of small requests
+//! - `CourtesyCap` limits how many full tests may start per rolling hour, which
+//!   matters for unattended monitor deployments
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::modules::history::HistoryStorage;
+use crate::modules::types::ServerProvider;
+
+/// Request budget for a single provider
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProviderLimit {
+    /// Requests that may be issued back to back before pacing kicks in
+    pub burst: u32,
+    /// Sustained requests per second once the burst is used up
+    pub per_second: f64,
+}
+
+impl ProviderLimit {
+    pub fn for_provider(provider: &ServerProvider) -> Self {
+        match provider {
+            // Anycast CDN built for speed tests, tolerates the full connection fan-out
+            ServerProvider::Cloudflare => Self {
+                burst: 64,
+                per_second: 20.0,
+            },
+            // Only used for latency probes, never meant as a test endpoint
+            ServerProvider::Google => Self {
+                burst: 10,
+                per_second: 5.0,
+            },
+            // The user's own servers, paced only to keep request bursts sane
+            ServerProvider::SelfHosted => Self {
+                burst: 128,
+                per_second: 100.0,
+            },
+            // Volunteer-run servers (speedtest.net hosts, LibreSpeed mirrors)
+            ServerProvider::Netflix | ServerProvider::Ookla | ServerProvider::Custom(_) => Self {
+                burst: 32,
+                per_second: 10.0,
+            },
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.per_second)
+    }
+}
+
+/// Per-provider request pacing (generic cell rate algorithm)
+#[derive(Debug, Default)]
+pub struct ProviderRateLimiter {
+    /// Theoretical arrival time of the next request, per provider
+    next_arrival: Mutex<HashMap<String, Instant>>,
+}
+
+impl ProviderRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait until a request to `provider` fits in its budget
+    pub async fn acquire(&self, provider: &ServerProvider) {
+        let wait = self.reserve(provider, Instant::now()).await;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Book the next slot for `provider` and return how long the caller has to wait for it
+    async fn reserve(&self, provider: &ServerProvider, now: Instant) -> Duration {
+        let limit = ProviderLimit::for_provider(provider);
+        let interval = limit.interval();
+        let tolerance = interval * limit.burst.saturating_sub(1);
+
+        let mut next_arrival = self.next_arrival.lock().await;
+        let arrival = next_arrival
+            .get(&provider.to_string())
+            .copied()
+            .unwrap_or(now)
+            .max(now);
+        let allowed_at = arrival.checked_sub(tolerance).unwrap_or(now).max(now);
+        next_arrival.insert(provider.to_string(), arrival + interval);
+
+        allowed_at - now
+    }
+}
+
+/// Spaces the probes to each server so none starts sooner than the minimum
+/// interval after the last one
+#[derive(Debug, Default)]
+pub struct ProbePacer {
+    /// Earliest start of the next probe, per server host
+    next_probe: Mutex<HashMap<String, Instant>>,
+}
+
+impl ProbePacer {
+    /// Wait until a probe to `host` may start
+    pub async fn acquire(&self, host: &str, min_interval: Duration) {
+        let wait = self.reserve(host, min_interval, Instant::now()).await;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Book the next probe to `host` and return how long the caller has to wait for it
+    async fn reserve(&self, host: &str, min_interval: Duration, now: Instant) -> Duration {
+        let mut next_probe = self.next_probe.lock().await;
+        let start = next_probe.get(host).copied().unwrap_or(now).max(now);
+        next_probe.insert(host.to_string(), start + min_interval);
+        start - now
+    }
+}
+
+/// Returned when starting another test would exceed the hourly courtesy cap
+#[derive(Clone)]
+pub struct CourtesyCapExceeded {
+    pub recent_tests: usize,
+    pub max_tests_per_hour: u32,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for CourtesyCapExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Courtesy cap reached: {} tests in the last hour (limit {}). \
+             Next test allowed in {}. Raise max_tests_per_hour in config.toml \
+             or pass --max-tests-per-hour to change the limit (0 disables it)",
+            self.recent_tests,
+            self.max_tests_per_hour,
+            humantime::format_duration(Duration::from_secs(self.retry_after.as_secs().max(1)))
+        )
+    }
+}
+
+// Errors returned from main are printed with Debug, so keep the message readable there too
+impl fmt::Debug for CourtesyCapExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for CourtesyCapExceeded {}
+
+/// Hourly limit on full tests against public servers
+#[derive(Debug, Clone, Copy)]
+pub struct CourtesyCap {
+    /// 0 disables the cap
+    pub max_tests_per_hour: u32,
+}
+
+impl CourtesyCap {
+    pub fn new(max_tests_per_hour: u32) -> Self {
+        Self { max_tests_per_hour }
+    }
+
+    /// Check the cap against runs recorded in `storage` and record this run if allowed
+    pub fn check_and_record(
+        &self,
+        storage: &HistoryStorage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        if self.max_tests_per_hour > 0 {
+            let recent = storage.get_test_runs_since(now - chrono::Duration::hours(1))?;
+            self.check(&recent, now)?;
+        }
+        storage.record_test_run(now)
+    }
+
+    /// Check the cap against run start times from the last hour, oldest first
+    pub fn check(
+        &self,
+        recent_runs: &[DateTime<Utc>],
+        now: DateTime<Utc>,
+    ) -> Result<(), CourtesyCapExceeded> {
+        let max = self.max_tests_per_hour as usize;
+        if max == 0 || recent_runs.len() < max {
+            return Ok(());
+        }
+
+        // The slot frees up once enough of the oldest runs age out of the window
+        let freeing_run = recent_runs[recent_runs.len() - max];
+        let retry_after = (freeing_run + chrono::Duration::hours(1) - now)
+            .to_std()
+            .unwrap_or_default();
+
+        Err(CourtesyCapExceeded {
+            recent_tests: recent_runs.len(),
+            max_tests_per_hour: self.max_tests_per_hour,
+            retry_after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter_allows_burst_then_paces() {
+        let limiter = ProviderRateLimiter::new();
+        let provider = ServerProvider::Google;
+        let limit = ProviderLimit::for_provider(&provider);
+        let now = Instant::now();
+
+        for _ in 0..limit.burst {
+            assert!(limiter.reserve(&provider, now).await.is_zero());
+        }
+
+        let wait = limiter.reserve(&provider, now).await;
+        assert_eq!(wait, limit.interval());
+
+        // Other providers have their own budget
+        assert!(limiter
+            .reserve(&ServerProvider::Cloudflare, now)
+            .await
+            .is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_probe_pacer_spaces_each_host() {
+        let pacer = ProbePacer::default();
+        let interval = Duration::from_secs(2);
+        let now = Instant::now();
+
+        assert!(pacer.reserve("a.example", interval, now).await.is_zero());
+        assert_eq!(pacer.reserve("a.example", interval, now).await, interval);
+        assert_eq!(
+            pacer
+                .reserve("a.example", interval, now + Duration::from_secs(1))
+                .await,
+            Duration::from_secs(3)
+        );
+        assert!(pacer.reserve("b.example", interval, now).await.is_zero());
+    }
+
+    #[test]
+    fn test_courtesy_cap() {
+        let now = Utc::now();
+        let cap = CourtesyCap::new(3);
+        let runs: Vec<_> = [50, 30, 10]
+            .iter()
+            .map(|mins| now - chrono::Duration::minutes(*mins))
+            .collect();
+
+        assert!(cap.check(&runs[..2], now).is_ok());
+
+        let err = cap.check(&runs, now).unwrap_err();
+        assert_eq!(err.recent_tests, 3);
+        // The run from 50 minutes ago leaves the window in 10 minutes
+        assert_eq!(err.retry_after.as_secs() / 60, 10);
+
+        assert!(CourtesyCap::new(0).check(&runs, now).is_ok());
+    }
+}
@@ -0,0 +1,207 @@
+//! Regional Speed Comparison
+//!
+//! Puts a result next to typical speeds where the tester is, so users can tell
+//! whether their numbers are normal for their country or ISP. A small table of
+//! national fixed-broadband medians ships with the binary; `medians_url` in
+//! `config.toml` points at a JSON list to use instead, which may also break the
+//! medians down by ISP:
+//!
+//! ```json
+//! [
+//!   { "country": "Germany", "download_mbps": 95.0, "upload_mbps": 40.0 },
+//!   { "country": "Germany", "isp": "Deutsche Telekom", "download_mbps": 110.0, "upload_mbps": 45.0 }
+//! ]
+//! ```
+//!
+//! Countries match by name or ISO code, ISPs when either name contains the other.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::modules::types::SpeedTestResult;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Approximate national medians of fixed broadband as (ISO code, name, download,
+/// upload) in Mbps, rounded from public speed indexes of 2025
+const COUNTRY_MEDIANS: &[(&str, &str, f64, f64)] = &[
+    ("AU", "Australia", 100.0, 20.0),
+    ("AT", "Austria", 90.0, 30.0),
+    ("BE", "Belgium", 120.0, 30.0),
+    ("BR", "Brazil", 200.0, 110.0),
+    ("CA", "Canada", 250.0, 100.0),
+    ("CH", "Switzerland", 250.0, 140.0),
+    ("CL", "Chile", 330.0, 240.0),
+    ("CN", "China", 270.0, 60.0),
+    ("DE", "Germany", 95.0, 40.0),
+    ("DK", "Denmark", 250.0, 200.0),
+    ("ES", "Spain", 260.0, 230.0),
+    ("FR", "France", 300.0, 260.0),
+    ("GB", "United Kingdom", 130.0, 30.0),
+    ("HU", "Hungary", 230.0, 110.0),
+    ("IN", "India", 65.0, 60.0),
+    ("IT", "Italy", 100.0, 40.0),
+    ("JP", "Japan", 220.0, 140.0),
+    ("KR", "South Korea", 200.0, 200.0),
+    ("MX", "Mexico", 90.0, 50.0),
+    ("NL", "Netherlands", 200.0, 60.0),
+    ("PL", "Poland", 170.0, 90.0),
+    ("PT", "Portugal", 200.0, 120.0),
+    ("RO", "Romania", 240.0, 170.0),
+    ("SE", "Sweden", 250.0, 200.0),
+    ("SG", "Singapore", 350.0, 300.0),
+    ("US", "United States", 270.0, 50.0),
+];
+
+/// Median speeds of one country, or of one ISP within it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegionalMedian {
+    pub country: String,
+    #[serde(default)]
+    pub isp: Option<String>,
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+}
+
+/// A result measured against the closest matching median
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RegionalComparison {
+    pub median: RegionalMedian,
+    /// How far the result is above (positive) or below the median
+    pub download_percent: f64,
+    pub upload_percent: f64,
+}
+
+/// Medians shipped with netrunner, by country only
+pub fn builtin_medians() -> Vec<RegionalMedian> {
+    COUNTRY_MEDIANS
+        .iter()
+        .map(|&(_, name, download_mbps, upload_mbps)| RegionalMedian {
+            country: name.to_string(),
+            isp: None,
+            download_mbps,
+            upload_mbps,
+        })
+        .collect()
+}
+
+/// Download a list of medians in the format shown in the module docs
+pub async fn fetch_medians(
+    client: &Client,
+    url: &str,
+) -> Result<Vec<RegionalMedian>, Box<dyn std::error::Error>> {
+    let medians = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<RegionalMedian>>()
+        .await?;
+    Ok(medians)
+}
+
+/// Compare `result` with the median of `isp` in `country`, or the country's when
+/// the ISP is not listed
+pub fn compare(
+    medians: &[RegionalMedian],
+    country: &str,
+    isp: Option<&str>,
+    result: &SpeedTestResult,
+) -> Option<RegionalComparison> {
+    let in_country: Vec<&RegionalMedian> = medians
+        .iter()
+        .filter(|median| same_country(&median.country, country))
+        .collect();
+    let median = in_country
+        .iter()
+        .find(|median| match (median.isp.as_deref(), isp) {
+            (Some(listed), Some(isp)) => same_isp(listed, isp),
+            _ => false,
+        })
+        .or_else(|| in_country.iter().find(|median| median.isp.is_none()))?;
+
+    Some(RegionalComparison {
+        median: (*median).clone(),
+        download_percent: percent_difference(result.download_mbps, median.download_mbps),
+        upload_percent: percent_difference(result.upload_mbps, median.upload_mbps),
+    })
+}
+
+fn percent_difference(value: f64, median: f64) -> f64 {
+    if median > 0.0 {
+        (value - median) / median * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Geolocation services report either names or ISO codes
+fn same_country(a: &str, b: &str) -> bool {
+    let name = |country: &str| {
+        COUNTRY_MEDIANS
+            .iter()
+            .find(|(code, _, _, _)| code.eq_ignore_ascii_case(country))
+            .map_or(country.to_string(), |(_, name, _, _)| name.to_string())
+    };
+    name(a.trim()).eq_ignore_ascii_case(&name(b.trim()))
+}
+
+/// ISP names vary in their legal suffixes, e.g. "Deutsche Telekom AG"
+fn same_isp(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim().to_lowercase(), b.trim().to_lowercase());
+    !a.is_empty() && !b.is_empty() && (a.contains(&b) || b.contains(&a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(download_mbps: f64, upload_mbps: f64) -> SpeedTestResult {
+        SpeedTestResult {
+            download_mbps,
+            upload_mbps,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_with_country_median() {
+        let medians = builtin_medians();
+
+        let comparison = compare(&medians, "DE", None, &result(190.0, 20.0)).unwrap();
+        assert_eq!(comparison.median.country, "Germany");
+        assert_eq!(comparison.download_percent, 100.0);
+        assert_eq!(comparison.upload_percent, -50.0);
+
+        assert!(compare(&medians, "germany", Some("Vodafone"), &result(1.0, 1.0)).is_some());
+        assert!(compare(&medians, "Atlantis", None, &result(1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_compare_prefers_isp_median() {
+        let medians: Vec<RegionalMedian> = serde_json::from_str(
+            r#"[
+                {"country": "Germany", "download_mbps": 100.0, "upload_mbps": 40.0},
+                {"country": "Germany", "isp": "Deutsche Telekom", "download_mbps": 200.0, "upload_mbps": 50.0}
+            ]"#,
+        )
+        .unwrap();
+
+        let telekom = compare(
+            &medians,
+            "Germany",
+            Some("Deutsche Telekom AG"),
+            &result(100.0, 50.0),
+        )
+        .unwrap();
+        assert_eq!(telekom.median.isp.as_deref(), Some("Deutsche Telekom"));
+        assert_eq!(telekom.download_percent, -50.0);
+        assert_eq!(telekom.upload_percent, 0.0);
+
+        let other = compare(&medians, "DE", Some("Vodafone"), &result(100.0, 40.0)).unwrap();
+        assert_eq!(other.median.isp, None);
+        assert_eq!(other.download_percent, 0.0);
+    }
+}
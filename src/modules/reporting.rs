@@ -0,0 +1,171 @@
+//! Result Reporting
+//!
+//! Uploads each speed test result to a self-hosted aggregation server so teams
+//! can collect measurements from many machines or sites in one place. Enabled
+//! with `--report-to URL` or a `[report]` table in `config.toml`.
+//!
+//! # Payload
+//!
+//! Every completed test is sent as `POST <url>` with a JSON body, and with
+//! `Authorization: Bearer <token>` when a token is configured:
+//!
+//! ```json
+//! {
+//!   "format_version": 1,
+//!   "client": {
+//!     "id": "office-berlin-01",
+//!     "netrunner_version": "0.7.3",
+//!     "os": "linux",
+//!     "arch": "x86_64"
+//!   },
+//!   "plan": { "download_mbps": 500.0, "upload_mbps": 50.0, "alert_below_percent": 80.0 },
+//!   "result": {
+//!     "timestamp": "...", "download_mbps": 512.3, "...": "...",
+//!     "host_label": "office-berlin-01", "site": "Berlin", "use_cases": { }
+//!   }
+//! }
+//! ```
+//!
+//! - `format_version` is bumped on incompatible changes to this layout
+//! - `client.id` is the configured `client_id`, falling back to `host_label` and
+//!   then the hostname
+//! - `plan` is `null` unless an advertised plan is configured
+//! - `result` has the same shape as one result printed with `--json`
+//!
+//! Any 2xx response counts as accepted; the response body is ignored.
+
+use std::time::Duration;
+
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::modules::types::{AdvertisedPlan, ReportTarget, ResultReport, SpeedTestResult};
+
+/// Version of the upload payload layout
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Body of a result upload
+#[derive(Debug, Serialize)]
+pub struct ReportPayload<'a> {
+    pub format_version: u32,
+    pub client: ClientInfo,
+    pub plan: Option<AdvertisedPlan>,
+    pub result: ResultReport<'a>,
+}
+
+/// Identifies the machine that ran the test
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ClientInfo {
+    pub id: Option<String>,
+    pub netrunner_version: String,
+    pub os: String,
+    pub arch: String,
+}
+
+impl ClientInfo {
+    pub fn collect(client_id: Option<&str>) -> Self {
+        Self {
+            id: client_id.map(str::to_string).or_else(hostname),
+            netrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+impl<'a> ReportPayload<'a> {
+    pub fn new(
+        target: &ReportTarget,
+        plan: Option<AdvertisedPlan>,
+        result: &'a SpeedTestResult,
+    ) -> Self {
+        Self {
+            format_version: REPORT_FORMAT_VERSION,
+            client: ClientInfo::collect(
+                target.client_id.as_deref().or(result.host_label.as_deref()),
+            ),
+            plan,
+            result: result.report(),
+        }
+    }
+}
+
+/// POST a result to the aggregation server
+pub async fn upload(
+    target: &ReportTarget,
+    plan: Option<AdvertisedPlan>,
+    result: &SpeedTestResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = Client::builder().timeout(UPLOAD_TIMEOUT).build()?;
+
+    let mut request = client
+        .post(&target.url)
+        .json(&ReportPayload::new(target, plan, result));
+    if let Some(token) = &target.token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Report server {} answered {}",
+            target.url,
+            response.status()
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Best-effort machine name from the platform's `hostname` command
+fn hostname() -> Option<String> {
+    let output = std::process::Command::new("hostname").output().ok()?;
+    let name = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::ConnectionQuality;
+
+    #[test]
+    fn test_payload_format() {
+        let target = ReportTarget {
+            url: "https://example.com/api".to_string(),
+            token: Some("secret".to_string()),
+            client_id: Some("office-berlin-01".to_string()),
+        };
+        let result = SpeedTestResult {
+            download_mbps: 512.3,
+            upload_mbps: 48.7,
+            quality: ConnectionQuality::Excellent,
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(ReportPayload::new(&target, None, &result)).unwrap();
+
+        assert_eq!(json["format_version"], REPORT_FORMAT_VERSION);
+        assert_eq!(json["client"]["id"], "office-berlin-01");
+        assert_eq!(json["client"]["os"], std::env::consts::OS);
+        assert!(json["plan"].is_null());
+        assert_eq!(json["result"]["download_mbps"], 512.3);
+        assert!(json["result"]["use_cases"].is_object());
+        assert!(!json.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn test_token_not_serialized_or_debug_printed() {
+        let target = ReportTarget {
+            url: "https://example.com/api".to_string(),
+            token: Some("secret".to_string()),
+            client_id: None,
+        };
+
+        assert!(!serde_json::to_string(&target).unwrap().contains("secret"));
+        assert!(!format!("{:?}", target).contains("secret"));
+    }
+}
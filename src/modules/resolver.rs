@@ -0,0 +1,125 @@
+//! Shared Name Lookups
+//!
+//! A speed test opens dozens of connections to the same few hosts and used to
+//! look each of them up again, once more for the server address in the result.
+//! `ResolverCache` resolves every host once per test. The HTTP client, the QoS
+//! relay and `SpeedTest` all ask it, so the recorded server address is the one
+//! the connections went to.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// A host's addresses, filled in by the first lookup that succeeds
+type Entry = Arc<OnceCell<Vec<IpAddr>>>;
+
+/// Addresses of every host looked up so far, kept for the life of one test.
+/// Clones share their entries.
+#[derive(Clone, Default)]
+pub struct ResolverCache {
+    hosts: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl ResolverCache {
+    /// Addresses of `host` in the system resolver's order. Concurrent lookups of
+    /// the same host wait for one query, a failed one is tried again next time.
+    pub async fn lookup(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        // URLs put IPv6 literals in brackets
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+
+        let cell = Arc::clone(
+            self.hosts
+                .lock()
+                .unwrap()
+                .entry(host.to_ascii_lowercase())
+                .or_default(),
+        );
+        cell.get_or_try_init(|| async {
+            let ips: Vec<IpAddr> = tokio::net::lookup_host((host, 0))
+                .await?
+                .map(|address| address.ip())
+                .collect();
+            if ips.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} has no addresses", host),
+                ));
+            }
+            Ok(ips)
+        })
+        .await
+        .cloned()
+    }
+
+    /// Addresses of a `host:port` target, as in a CONNECT request
+    pub async fn lookup_target(&self, target: &str) -> io::Result<Vec<SocketAddr>> {
+        let (host, port) = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not host:port", target),
+                )
+            })?;
+        Ok(self
+            .lookup(host)
+            .await?
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect())
+    }
+}
+
+/// Lets reqwest connect through the cache, the port comes from the URL
+impl reqwest::dns::Resolve for ResolverCache {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        // reqwest wants a 'static future, so it gets a clone
+        let cache = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let ips = cache.lookup(&host).await?;
+            let addresses: reqwest::dns::Addrs =
+                Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addresses)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[tokio::test]
+    async fn test_lookup_target() {
+        let cache = ResolverCache::default();
+        assert_eq!(
+            cache.lookup_target("127.0.0.1:8080").await.unwrap(),
+            vec![SocketAddr::from((Ipv4Addr::LOCALHOST, 8080))]
+        );
+        assert_eq!(
+            cache.lookup_target("[::1]:443").await.unwrap(),
+            vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 443))]
+        );
+        assert!(cache.lookup_target("example.com").await.is_err());
+        // Literals are never stored
+        assert!(cache.hosts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_clones_share_lookups() {
+        let cache = ResolverCache::default();
+        let clone = cache.clone();
+        let (first, second) = tokio::join!(cache.lookup("localhost"), clone.lookup("LOCALHOST"));
+        let first = first.unwrap();
+        assert!(first.iter().all(IpAddr::is_loopback));
+        assert_eq!(first, second.unwrap());
+        assert_eq!(cache.hosts.lock().unwrap().len(), 1);
+    }
+}
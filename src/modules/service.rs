@@ -0,0 +1,434 @@
+//! Background Service Installation
+//!
+//! Schedules `netrunner --mode monitor --once` with the platform's own scheduler
+//! so monitoring keeps running without a terminal:
+//! - Linux: a user-level systemd service and timer in `~/.config/systemd/user`
+//! - macOS: a launchd agent in `~/Library/LaunchAgents`
+//! - Windows: a Scheduled Task created with `schtasks`
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+const UNIT_NAME: &str = "netrunner-monitor";
+const LAUNCHD_LABEL: &str = "io.github.sorinirimies.netrunner-monitor";
+const TASK_NAME: &str = "Netrunner Monitor";
+
+/// Shortest interval accepted, anything faster would be abusive to public servers
+pub const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the scheduled job runs and how often
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceSpec {
+    pub executable: PathBuf,
+    pub interval: Duration,
+}
+
+impl ServiceSpec {
+    pub fn new(interval: Duration) -> Result<Self, Box<dyn std::error::Error>> {
+        if interval < MIN_INTERVAL {
+            return Err(format!(
+                "Service interval must be at least {}",
+                humantime::format_duration(MIN_INTERVAL)
+            )
+            .into());
+        }
+
+        Ok(Self {
+            executable: std::env::current_exe()?,
+            interval,
+        })
+    }
+
+    /// Arguments for one monitoring cycle; retries ride out short outages
+    pub fn arguments(&self) -> Vec<String> {
+        ["--mode", "monitor", "--once", "--retries", "2"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+}
+
+/// Scheduler used on the current platform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServicePlatform {
+    Systemd,
+    Launchd,
+    WindowsTask,
+}
+
+impl ServicePlatform {
+    pub fn current() -> Result<Self, Box<dyn std::error::Error>> {
+        if cfg!(target_os = "macos") {
+            Ok(Self::Launchd)
+        } else if cfg!(windows) {
+            Ok(Self::WindowsTask)
+        } else if cfg!(target_os = "linux") {
+            Ok(Self::Systemd)
+        } else {
+            Err("Service installation is supported on Linux (systemd), macOS and Windows".into())
+        }
+    }
+}
+
+/// Outcome of an install or uninstall
+#[derive(Debug, Default)]
+pub struct ServiceReport {
+    /// Files written or removed
+    pub files: Vec<PathBuf>,
+    /// Scheduler commands that failed; the files are still in place and
+    /// the command can be rerun by hand
+    pub failed_commands: Vec<String>,
+}
+
+/// Install the scheduled job for the current platform
+pub fn install(spec: &ServiceSpec) -> Result<ServiceReport, Box<dyn std::error::Error>> {
+    let mut report = ServiceReport::default();
+
+    match ServicePlatform::current()? {
+        ServicePlatform::Systemd => {
+            let dir = systemd_user_dir()?;
+            std::fs::create_dir_all(&dir)?;
+            let (service, timer) = systemd_units(spec);
+            let timer_unit = format!("{}.timer", UNIT_NAME);
+            write_file(
+                &dir.join(format!("{}.service", UNIT_NAME)),
+                &service,
+                &mut report,
+            )?;
+            write_file(&dir.join(&timer_unit), &timer, &mut report)?;
+
+            run("systemctl", &["--user", "daemon-reload"], &mut report);
+            run(
+                "systemctl",
+                &["--user", "enable", "--now", &timer_unit],
+                &mut report,
+            );
+        }
+        ServicePlatform::Launchd => {
+            let path = launchd_plist_path()?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            write_file(&path, &launchd_plist(spec), &mut report)?;
+            run(
+                "launchctl",
+                &["load", "-w", &path.to_string_lossy()],
+                &mut report,
+            );
+        }
+        ServicePlatform::WindowsTask => {
+            let args = schtasks_create_args(spec);
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            run("schtasks", &args, &mut report);
+            if !report.failed_commands.is_empty() {
+                return Err("Failed to create the scheduled task".into());
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Remove the scheduled job for the current platform
+pub fn uninstall() -> Result<ServiceReport, Box<dyn std::error::Error>> {
+    let mut report = ServiceReport::default();
+
+    match ServicePlatform::current()? {
+        ServicePlatform::Systemd => {
+            let timer_unit = format!("{}.timer", UNIT_NAME);
+            run(
+                "systemctl",
+                &["--user", "disable", "--now", &timer_unit],
+                &mut report,
+            );
+
+            let dir = systemd_user_dir()?;
+            for name in [format!("{}.service", UNIT_NAME), timer_unit] {
+                remove_file(&dir.join(name), &mut report)?;
+            }
+            run("systemctl", &["--user", "daemon-reload"], &mut report);
+        }
+        ServicePlatform::Launchd => {
+            let path = launchd_plist_path()?;
+            if path.exists() {
+                run(
+                    "launchctl",
+                    &["unload", "-w", &path.to_string_lossy()],
+                    &mut report,
+                );
+            }
+            remove_file(&path, &mut report)?;
+        }
+        ServicePlatform::WindowsTask => {
+            run(
+                "schtasks",
+                &["/Delete", "/TN", TASK_NAME, "/F"],
+                &mut report,
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+/// Last `lines` lines the scheduled monitor logged, oldest first. Only systemd
+/// keeps the job's output; launchd and Task Scheduler discard it.
+pub fn recent_log(lines: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    match ServicePlatform::current()? {
+        ServicePlatform::Systemd => {
+            let output = Command::new("journalctl")
+                .args(["--user", "--unit", &format!("{}.service", UNIT_NAME)])
+                .args([
+                    "--lines",
+                    &lines.to_string(),
+                    "--no-pager",
+                    "--output",
+                    "short-iso",
+                ])
+                .output()?;
+            if !output.status.success() {
+                return Err(format!(
+                    "journalctl failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )
+                .into());
+            }
+            Ok(String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|line| !line.starts_with("-- "))
+                .map(String::from)
+                .collect())
+        }
+        ServicePlatform::Launchd | ServicePlatform::WindowsTask => {
+            Err("the scheduler on this platform does not keep the monitor's output".into())
+        }
+    }
+}
+
+/// Render the systemd service and timer units
+pub fn systemd_units(spec: &ServiceSpec) -> (String, String) {
+    let exec_start = std::iter::once(spec.executable.to_string_lossy().into_owned())
+        .chain(spec.arguments())
+        .map(|arg| systemd_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let service = format!(
+        "[Unit]\n\
+         Description=Netrunner internet speed monitor\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        exec_start
+    );
+
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run the netrunner speed monitor every {interval}\n\
+         \n\
+         [Timer]\n\
+         OnBootSec=5min\n\
+         OnUnitActiveSec={seconds}s\n\
+         RandomizedDelaySec=60\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        interval = humantime::format_duration(spec.interval),
+        seconds = spec.interval.as_secs()
+    );
+
+    (service, timer)
+}
+
+/// Render the launchd agent
+pub fn launchd_plist(spec: &ServiceSpec) -> String {
+    let arguments: String = std::iter::once(spec.executable.to_string_lossy().into_owned())
+        .chain(spec.arguments())
+        .map(|arg| format!("        <string>{}</string>\n", escape_xml(&arg)))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>StartInterval</key>
+    <integer>{seconds}</integer>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Background</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        arguments = arguments,
+        seconds = spec.interval.as_secs()
+    )
+}
+
+/// Arguments for `schtasks /Create`; the task scheduler counts in whole minutes
+pub fn schtasks_create_args(spec: &ServiceSpec) -> Vec<String> {
+    let minutes = (spec.interval.as_secs() / 60).max(1);
+    let (schedule, modifier) = if minutes < 24 * 60 {
+        ("MINUTE", minutes)
+    } else {
+        ("DAILY", minutes / (24 * 60))
+    };
+
+    let command = std::iter::once(format!("\"{}\"", spec.executable.display()))
+        .chain(spec.arguments())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    [
+        "/Create",
+        "/TN",
+        TASK_NAME,
+        "/TR",
+        &command,
+        "/SC",
+        schedule,
+        "/MO",
+        &modifier.to_string(),
+        "/F",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+fn systemd_user_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(dirs::config_dir()
+        .ok_or("Failed to find config directory")?
+        .join("systemd")
+        .join("user"))
+}
+
+fn launchd_plist_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(dirs::home_dir()
+        .ok_or("Failed to find home directory")?
+        .join("Library")
+        .join("LaunchAgents")
+        .join(format!("{}.plist", LAUNCHD_LABEL)))
+}
+
+fn write_file(
+    path: &Path,
+    contents: &str,
+    report: &mut ServiceReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, contents)?;
+    report.files.push(path.to_path_buf());
+    Ok(())
+}
+
+fn remove_file(path: &Path, report: &mut ServiceReport) -> Result<(), Box<dyn std::error::Error>> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+        report.files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Run a scheduler command, recording it in the report when it fails
+fn run(program: &str, args: &[&str], report: &mut ServiceReport) {
+    let succeeded = Command::new(program)
+        .args(args)
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+
+    if !succeeded {
+        report
+            .failed_commands
+            .push(format!("{} {}", program, args.join(" ")));
+    }
+}
+
+/// Quote an ExecStart word when it contains characters systemd would split on
+fn systemd_quote(arg: &str) -> String {
+    if arg.contains([' ', '"', '\\', '\'']) {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(executable: &str, interval: Duration) -> ServiceSpec {
+        ServiceSpec {
+            executable: PathBuf::from(executable),
+            interval,
+        }
+    }
+
+    #[test]
+    fn test_rejects_short_interval() {
+        assert!(ServiceSpec::new(Duration::from_secs(30)).is_err());
+        assert!(ServiceSpec::new(Duration::from_secs(30 * 60)).is_ok());
+    }
+
+    #[test]
+    fn test_systemd_units() {
+        let (service, timer) = systemd_units(&spec(
+            "/home/me/my tools/netrunner_cli",
+            Duration::from_secs(30 * 60),
+        ));
+
+        assert!(service.contains("Type=oneshot"));
+        assert!(service.contains(
+            "ExecStart=\"/home/me/my tools/netrunner_cli\" --mode monitor --once --retries 2"
+        ));
+        assert!(timer.contains("OnUnitActiveSec=1800s"));
+        assert!(timer.contains("every 30m"));
+        assert!(timer.contains("WantedBy=timers.target"));
+    }
+
+    #[test]
+    fn test_launchd_plist() {
+        let plist = launchd_plist(&spec(
+            "/Applications/A&B/netrunner_cli",
+            Duration::from_secs(3600),
+        ));
+
+        assert!(plist.contains("<string>/Applications/A&amp;B/netrunner_cli</string>"));
+        assert!(plist.contains("<string>--once</string>"));
+        assert!(plist.contains("<integer>3600</integer>"));
+    }
+
+    #[test]
+    fn test_schtasks_schedule() {
+        let args = schtasks_create_args(&spec(
+            r"C:\Program Files\netrunner.exe",
+            Duration::from_secs(45 * 60),
+        ));
+        assert_eq!(
+            args[4],
+            r#""C:\Program Files\netrunner.exe" --mode monitor --once --retries 2"#
+        );
+        assert_eq!(&args[5..9], ["/SC", "MINUTE", "/MO", "45"]);
+
+        let daily = schtasks_create_args(&spec("netrunner.exe", Duration::from_secs(2 * 86400)));
+        assert_eq!(&daily[5..9], ["/SC", "DAILY", "/MO", "2"]);
+    }
+}
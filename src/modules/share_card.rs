@@ -0,0 +1,180 @@
+//! Shareable Result Card
+//!
+//! Renders a speed test result as a 1200x630 card (the common social preview
+//! size) so results can be posted without screenshotting the terminal. The card
+//! is built as SVG and rasterized with resvg when a PNG is requested.
+
+use std::path::Path;
+
+use resvg::{tiny_skia, usvg};
+
+use crate::modules::types::{ConnectionQuality, SpeedTestResult};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const FONT: &str = "DejaVu Sans Mono, Menlo, Consolas, monospace";
+
+/// Write the card for `result` to `path`, picking SVG or PNG from the file extension
+pub fn export_image(
+    result: &SpeedTestResult,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("svg") => std::fs::write(path, render_svg(result))?,
+        Some("png") => render_png(result)?.save_png(path)?,
+        _ => {
+            return Err(format!(
+                "Unsupported image format for {}, use a .svg or .png file name",
+                path.display()
+            )
+            .into())
+        }
+    }
+
+    Ok(())
+}
+
+/// Rasterize the card, using the system fonts for its text
+fn render_png(result: &SpeedTestResult) -> Result<tiny_skia::Pixmap, Box<dyn std::error::Error>> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+
+    let tree = usvg::Tree::from_str(&render_svg(result), &options)?;
+    let mut pixmap =
+        tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or("Failed to allocate image buffer")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    Ok(pixmap)
+}
+
+/// Build the SVG markup for the card
+pub fn render_svg(result: &SpeedTestResult) -> String {
+    let quality_color = quality_hex(result.quality);
+    let isp = result.isp.as_deref().unwrap_or("Unknown ISP");
+    let details = format!("{} · {}", isp, result.server_location);
+    let timestamp = result.timestamp.format("%Y-%m-%d %H:%M UTC").to_string();
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">
+  <defs>
+    <linearGradient id="bg" x1="0" y1="0" x2="1" y2="1">
+      <stop offset="0" stop-color="#0b0f1a"/>
+      <stop offset="1" stop-color="#1d0b33"/>
+    </linearGradient>
+  </defs>
+  <rect width="{w}" height="{h}" fill="url(#bg)"/>
+  <rect x="24" y="24" width="{fw}" height="{fh}" rx="18" fill="none" stroke="#00ffff" stroke-width="3"/>
+  <g font-family="{font}">
+    <text x="64" y="112" font-size="60" font-weight="bold" fill="#00ffff">NETRUNNER</text>
+    <text x="64" y="152" font-size="24" fill="#ff00ff">&gt;&gt;&gt; SPEED TEST RESULTS &lt;&lt;&lt;</text>
+    <rect x="{badge_x}" y="72" width="300" height="64" rx="12" fill="{quality_color}"/>
+    <text x="{badge_mid}" y="114" font-size="30" font-weight="bold" fill="#0b0f1a" text-anchor="middle">{quality}</text>
+
+    <text x="64" y="240" font-size="26" fill="#5c5cff">DOWNLOAD</text>
+    <text x="64" y="330" font-size="96" font-weight="bold" fill="#00ff96">{download:.1}</text>
+    <text x="64" y="372" font-size="28" fill="#00ff96">Mbps</text>
+
+    <text x="620" y="240" font-size="26" fill="#5c5cff">UPLOAD</text>
+    <text x="620" y="330" font-size="96" font-weight="bold" fill="#00ff96">{upload:.1}</text>
+    <text x="620" y="372" font-size="28" fill="#00ff96">Mbps</text>
+
+    <text x="64" y="450" font-size="30" fill="#00ffff">PING {ping:.1} ms</text>
+    <text x="450" y="450" font-size="30" fill="#00ffff">JITTER {jitter:.1} ms</text>
+    <text x="870" y="450" font-size="30" fill="#00ffff">LOSS {loss:.1}%</text>
+
+    <text x="64" y="530" font-size="24" fill="#e5e5e5">{details}</text>
+    <text x="64" y="574" font-size="20" fill="#7f7f7f">{timestamp}</text>
+    <text x="1136" y="574" font-size="20" fill="#ff00ff" text-anchor="end">netrunner_cli v{version}</text>
+  </g>
+</svg>
+"##,
+        w = WIDTH,
+        h = HEIGHT,
+        fw = WIDTH - 48,
+        fh = HEIGHT - 48,
+        font = FONT,
+        badge_x = WIDTH - 64 - 300,
+        badge_mid = WIDTH - 64 - 150,
+        quality_color = quality_color,
+        quality = escape_xml(&result.quality.to_string().to_uppercase()),
+        download = result.download_mbps,
+        upload = result.upload_mbps,
+        ping = result.ping_ms,
+        jitter = result.jitter_ms,
+        loss = result.packet_loss_percent,
+        details = escape_xml(&details),
+        timestamp = timestamp,
+        version = env!("CARGO_PKG_VERSION"),
+    )
+}
+
+fn quality_hex(quality: ConnectionQuality) -> &'static str {
+    match quality {
+        ConnectionQuality::Excellent => "#00ff96",
+        ConnectionQuality::Good => "#00cd66",
+        ConnectionQuality::Average => "#ffff00",
+        ConnectionQuality::Poor => "#ffa500",
+        ConnectionQuality::VeryPoor | ConnectionQuality::Failed => "#ff3c3c",
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_result() -> SpeedTestResult {
+        SpeedTestResult {
+            download_mbps: 512.3,
+            upload_mbps: 48.7,
+            ping_ms: 9.4,
+            quality: ConnectionQuality::Excellent,
+            isp: Some("Fiber & Co <Metro>".to_string()),
+            server_location: "Frankfurt, Germany".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_svg_contains_result_and_escapes_text() {
+        let svg = render_svg(&sample_result());
+
+        assert!(svg.contains("512.3"));
+        assert!(svg.contains("48.7"));
+        assert!(svg.contains("EXCELLENT"));
+        assert!(svg.contains("Fiber &amp; Co &lt;Metro&gt;"));
+        assert!(!svg.contains("Fiber & Co"));
+    }
+
+    #[test]
+    fn test_export_by_extension() {
+        let dir = tempdir().unwrap();
+        let result = sample_result();
+
+        let svg_path = dir.path().join("card.svg");
+        export_image(&result, &svg_path).unwrap();
+        assert!(std::fs::read_to_string(&svg_path)
+            .unwrap()
+            .starts_with("<svg"));
+
+        let png_path = dir.path().join("card.PNG");
+        export_image(&result, &png_path).unwrap();
+        let png = std::fs::read(&png_path).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+
+        assert!(export_image(&result, &dir.path().join("card.gif")).is_err());
+    }
+}
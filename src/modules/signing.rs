@@ -0,0 +1,255 @@
+//! Result Signing
+//!
+//! Evidence bundles, history backups and share cards can be signed with an
+//! Ed25519 key generated on first use and kept as `signing.key` next to the
+//! history database. The signature is written beside the export as `<file>.sig`,
+//! so the export itself opens exactly as before.
+//!
+//! `netrunner_cli verify <file>` checks the file against its signature. The
+//! public key travels inside the signature, so anyone can tell the file is
+//! unchanged since it was signed; comparing the key's fingerprint with one shared
+//! earlier, e.g. in the first message to an ISP, shows who signed it.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::modules::evidence::sha256_hex;
+use crate::modules::history::HistoryStorage;
+
+const KEY_FILE: &str = "signing.key";
+const ALGORITHM: &str = "ed25519";
+
+/// Detached signature of an exported file, stored as JSON in `<file>.sig`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileSignature {
+    pub algorithm: String,
+    /// Public half of the signing key, hex encoded
+    pub public_key: String,
+    /// SHA-256 of the signed file
+    pub sha256: String,
+    pub signed_at: DateTime<Utc>,
+    /// Signature over the digest and time, hex encoded
+    pub signature: String,
+}
+
+impl FileSignature {
+    /// Short form of the public key for comparing by eye
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+}
+
+/// Where this machine's signing key is kept
+pub fn key_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(HistoryStorage::data_dir()?.join(KEY_FILE))
+}
+
+/// Load the key at `path`, generating and saving one when there is none yet
+pub fn load_or_create_key(path: &Path) -> Result<Ed25519KeyPair, Box<dyn std::error::Error>> {
+    if path.exists() {
+        return load_key(path);
+    }
+
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|_| "Failed to generate a signing key")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    write_private(path, pkcs8.as_ref())?;
+    load_key(path)
+}
+
+fn load_key(path: &Path) -> Result<Ed25519KeyPair, Box<dyn std::error::Error>> {
+    let pkcs8 = std::fs::read(path)?;
+    Ed25519KeyPair::from_pkcs8(&pkcs8)
+        .map_err(|_| format!("{} is not an Ed25519 key", path.display()).into())
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+/// Public key of this machine's signing key, if one has been generated
+pub fn local_public_key() -> Option<String> {
+    let path = key_path().ok()?;
+    if !path.exists() {
+        return None;
+    }
+    load_key(&path)
+        .ok()
+        .map(|key| to_hex(key.public_key().as_ref()))
+}
+
+/// Sign `data`, the contents of an exported file
+pub fn sign(key: &Ed25519KeyPair, data: &[u8], signed_at: DateTime<Utc>) -> FileSignature {
+    let sha256 = sha256_hex(data);
+    let signature = key.sign(&signed_message(&sha256, signed_at));
+    FileSignature {
+        algorithm: ALGORITHM.to_string(),
+        public_key: to_hex(key.public_key().as_ref()),
+        sha256,
+        signed_at,
+        signature: to_hex(signature.as_ref()),
+    }
+}
+
+/// Check that `data` is what `signature` was made over
+pub fn verify(data: &[u8], signature: &FileSignature) -> Result<(), String> {
+    if signature.algorithm != ALGORITHM {
+        return Err(format!(
+            "Unsupported signature algorithm '{}'",
+            signature.algorithm
+        ));
+    }
+    let public_key = from_hex(&signature.public_key).ok_or("Malformed public key")?;
+    let signature_bytes = from_hex(&signature.signature).ok_or("Malformed signature")?;
+
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(
+            &signed_message(&signature.sha256, signature.signed_at),
+            &signature_bytes,
+        )
+        .map_err(|_| "Signature does not match its key, the .sig file was altered")?;
+    if sha256_hex(data) != signature.sha256 {
+        return Err("The file was modified after it was signed".to_string());
+    }
+    Ok(())
+}
+
+/// Sign the file at `path` with this machine's key and write `<path>.sig`
+pub fn sign_file(path: &Path) -> Result<(PathBuf, FileSignature), Box<dyn std::error::Error>> {
+    let key = load_or_create_key(&key_path()?)?;
+    let signature = sign(&key, &std::fs::read(path)?, Utc::now());
+    let signature_path = signature_path(path);
+    std::fs::write(&signature_path, serde_json::to_string_pretty(&signature)?)?;
+    Ok((signature_path, signature))
+}
+
+/// Check the file at `path` against the signature at `signature_path`
+pub fn verify_file(
+    path: &Path,
+    signature_path: &Path,
+) -> Result<FileSignature, Box<dyn std::error::Error>> {
+    let signature: FileSignature =
+        serde_json::from_str(&std::fs::read_to_string(signature_path).map_err(|e| {
+            format!(
+                "Failed to read signature {}: {}",
+                signature_path.display(),
+                e
+            )
+        })?)
+        .map_err(|e| format!("Invalid signature {}: {}", signature_path.display(), e))?;
+    verify(&std::fs::read(path)?, &signature)?;
+    Ok(signature)
+}
+
+/// `<path>.sig`, where a file's signature is written
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// First 16 bytes of the key's SHA-256, in groups of four hex digits
+pub fn fingerprint(public_key: &str) -> String {
+    let digest = from_hex(public_key)
+        .map(|key| sha256_hex(&key))
+        .unwrap_or_default();
+    digest
+        .as_bytes()
+        .chunks(4)
+        .take(8)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn signed_message(sha256: &str, signed_at: DateTime<Utc>) -> Vec<u8> {
+    format!(
+        "netrunner-signature-v1\n{}\n{}\n",
+        sha256,
+        signed_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    )
+    .into_bytes()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = tempdir().unwrap();
+        let key = load_or_create_key(&dir.path().join(KEY_FILE)).unwrap();
+        let data = br#"{"download_mbps": 48.2}"#;
+
+        let signature = sign(&key, data, Utc::now());
+        assert!(verify(data, &signature).is_ok());
+        assert_eq!(signature.fingerprint().len(), 39);
+
+        let err = verify(br#"{"download_mbps": 480.2}"#, &signature).unwrap_err();
+        assert!(err.contains("modified"), "{}", err);
+
+        // A matching digest for the new contents doesn't help without the key
+        let mut forged = signature.clone();
+        forged.sha256 = sha256_hex(b"forged");
+        assert!(verify(b"forged", &forged).is_err());
+
+        let mut backdated = signature;
+        backdated.signed_at -= chrono::TimeDelta::days(30);
+        assert!(verify(data, &backdated).is_err());
+    }
+
+    #[test]
+    fn test_key_is_kept() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keys").join(KEY_FILE);
+
+        let first = load_or_create_key(&path).unwrap();
+        let second = load_or_create_key(&path).unwrap();
+        assert_eq!(first.public_key().as_ref(), second.public_key().as_ref());
+
+        std::fs::write(&path, b"not a key").unwrap();
+        assert!(load_or_create_key(&path).is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(from_hex("00ab7f"), Some(vec![0x00, 0xab, 0x7f]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
@@ -10,31 +10,256 @@
 //! - Support for speeds up to 10 Gbps
 //! - Fault tolerance and automatic fallbacks
 
+use bytes::Bytes;
 use chrono::Utc;
 use colored::*;
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use prettytable::{format, Cell, Row, Table};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::{Mutex, RwLock};
-
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use crate::modules::diagnostics::probe_link;
+use crate::modules::fast;
+use crate::modules::geolocation;
+pub use crate::modules::geolocation::GeoLocation;
+use crate::modules::history::HistoryStorage;
+use crate::modules::localnet::{self, LinkSnapshot, WanPoller};
+use crate::modules::ndt7;
+use crate::modules::ntp;
+use crate::modules::qos::{SocketRelay, TcpInspector};
+use crate::modules::rate_limit::{ProbePacer, ProviderRateLimiter};
+use crate::modules::regional;
+use crate::modules::resolver::ResolverCache;
+use crate::modules::terminal::print_table;
+use crate::modules::tls::{self, VersionLog};
 use crate::modules::types::{
-    ConnectionQuality, ServerCapabilities, ServerProvider, SpeedTestResult, TestConfig, TestServer,
+    interarrival_jitter, BitRate, CachedServer, ConnectionQuality, CustomServer, DataSize,
+    DataUsage, DetailLevel, EndpointLatency, EngineInfo, JitterMethod, LatencyStats, OneWayDelay,
+    OtherTraffic, PhaseCountdown, PhaseFailure, PhaseOutcome, ServerCapabilities, ServerFailover,
+    ServerProtocol, ServerProvider, SpeedPhase, SpeedProvider, SpeedTestResult, TcpHealth,
+    TestConfig, TestServer, TransportInfo, WanUsage, WanVerdict, WebSocketLatency,
 };
 use crate::modules::ui::UI;
+use crate::modules::websocket;
 
 const PARALLEL_CONNECTIONS: usize = 50;
+const UPLOAD_CONNECTIONS: usize = 10;
 const SERVER_SELECTION_COUNT: usize = 3;
+const PHASE_DURATION: Duration = Duration::from_secs(15);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GeoLocation {
-    pub country: String,
-    pub city: String,
-    pub latitude: f64,
-    pub longitude: f64,
-    pub isp: Option<String>,
+/// Interface traffic is sampled this long before a test to find other traffic
+const IDLE_CHECK_WINDOW: Duration = Duration::from_secs(3);
+
+/// Upload bodies are handed to the connection in pieces of this size
+const UPLOAD_PIECE_SIZE: usize = 64 * 1024;
+
+/// Pool health checks give up on a server after this long
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Size asked of a download endpoint during the health check, the body is never read
+const HEALTH_CHECK_BYTES: u64 = 1_000;
+
+/// Rough time the jitter, loss and IP lookups take after the upload, for the ETA
+const WRAP_UP_ESTIMATE: Duration = Duration::from_secs(3);
+
+/// A phase with no bytes moving for this long while its requests fail is abandoned
+const PHASE_STALL_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Bytes asked for by each download request when nothing limits the test
+const DOWNLOAD_REQUEST_BYTES: u64 = 100_000_000;
+
+/// Under `--max-data` or `--max-rate` requests shrink, but never below this
+const MIN_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// A throttled request is sized to finish in about this long, well inside the timeouts
+const THROTTLED_REQUEST_SECONDS: f64 = 5.0;
+
+/// A cached best server older than this is selected again from the full pool
+const CACHED_SERVER_MAX_AGE_HOURS: i64 = 24;
+
+/// How much slower than when it was selected a cached server may answer, as a
+/// factor plus a constant so jitter on very close servers does not count
+const CACHED_SERVER_LATENCY_FACTOR: f64 = 2.0;
+const CACHED_SERVER_LATENCY_SLACK_MS: f64 = 20.0;
+
+/// Servers requested from the speedtest.net list, before the distance filter
+const SPEEDTEST_NET_SERVER_LIMIT: usize = 30;
+
+/// Speedtest.net servers further away than this are not worth probing
+const SPEEDTEST_NET_MAX_DISTANCE_KM: f64 = 2_000.0;
+
+/// Bytes moved by each connection of a phase. Every connection adds to its own counter
+/// and the monitor sums them, so the hot path never waits on a lock shared by 50 tasks.
+#[derive(Debug)]
+pub struct ConnectionCounters {
+    counters: Vec<PaddedCounter>,
+}
+
+/// Kept on its own cache line so neighbouring connections do not contend for it
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct PaddedCounter(AtomicU64);
+
+impl ConnectionCounters {
+    pub fn new(connections: usize) -> Self {
+        Self {
+            counters: (0..connections).map(|_| PaddedCounter::default()).collect(),
+        }
+    }
+
+    pub fn add(&self, connection: usize, bytes: usize) {
+        self.counters[connection]
+            .0
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counters
+            .iter()
+            .map(|counter| counter.0.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+/// Shared by a phase's connections to stay within `--max-data` and `--max-rate`
+struct TransferLimiter {
+    /// Bytes the phase may still move, None when uncapped
+    allowance: Option<AtomicU64>,
+    throttle: Option<Throttle>,
+}
+
+impl TransferLimiter {
+    fn new(allowance: Option<u64>, max_rate: Option<BitRate>) -> Self {
+        Self {
+            allowance: allowance.map(AtomicU64::new),
+            throttle: max_rate.map(Throttle::new),
+        }
+    }
+
+    /// Take up to `bytes` from the allowance, returning how many may be moved
+    fn reserve(&self, bytes: usize) -> usize {
+        let Some(allowance) = &self.allowance else {
+            return bytes;
+        };
+        let previous = allowance
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                Some(left.saturating_sub(bytes as u64))
+            })
+            .unwrap_or_default();
+        previous.min(bytes as u64) as usize
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.allowance
+            .as_ref()
+            .is_some_and(|allowance| allowance.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Size for the next request of one of `connections`, shrunk so that together they
+    /// stay near the allowance and finish in time at the throttled rate
+    fn request_bytes(&self, max: u64, connections: usize) -> u64 {
+        let connections = connections as u64;
+        let mut bytes = max;
+        if let Some(allowance) = &self.allowance {
+            bytes = bytes.min(allowance.load(Ordering::Relaxed) / connections);
+        }
+        if let Some(throttle) = &self.throttle {
+            let per_request = throttle.bytes_per_second * THROTTLED_REQUEST_SECONDS;
+            bytes = bytes.min(per_request as u64 / connections);
+        }
+        bytes.max(MIN_REQUEST_BYTES)
+    }
+
+    /// Wait until moving `bytes` more keeps the phase within the rate limit
+    async fn pace(&self, bytes: usize) {
+        if let Some(throttle) = &self.throttle {
+            throttle.wait(bytes).await;
+        }
+    }
+}
+
+/// Books every chunk of a phase a slot on one shared timeline, so the connections
+/// together move at most `bytes_per_second` without holding a lock
+struct Throttle {
+    start: Instant,
+    bytes_per_second: f64,
+    /// End of the last booked slot in nanoseconds since `start`
+    booked_until: AtomicU64,
+}
+
+impl Throttle {
+    fn new(rate: BitRate) -> Self {
+        Self {
+            start: Instant::now(),
+            bytes_per_second: rate.bytes_per_second(),
+            booked_until: AtomicU64::new(0),
+        }
+    }
+
+    async fn wait(&self, bytes: usize) {
+        let cost = (bytes as f64 / self.bytes_per_second * 1e9) as u64;
+        let now = self.start.elapsed().as_nanos() as u64;
+        // An idle timeline restarts from now instead of allowing a burst
+        let previous = self
+            .booked_until
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |booked| {
+                Some(booked.max(now) + cost)
+            })
+            .unwrap_or_default();
+        let slot_end = previous.max(now) + cost;
+        tokio::time::sleep_until((self.start + Duration::from_nanos(slot_end)).into()).await;
+    }
+}
+
+/// Shared by a phase's connections and its monitor to notice when every connection is failing
+#[derive(Default)]
+struct PhaseHealth {
+    errors_since_progress: AtomicUsize,
+    stalled: AtomicBool,
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl PhaseHealth {
+    /// The byte counters moved since the last monitor tick. Checked per tick rather
+    /// than per chunk, so connections don't all write to this on every read.
+    fn record_progress(&self) {
+        self.errors_since_progress.store(0, Ordering::Relaxed);
+    }
+
+    fn record_error(&self, error: reqwest::Error) {
+        self.errors_since_progress.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(error.to_string());
+        }
+    }
+
+    fn is_failing(&self) -> bool {
+        self.errors_since_progress.load(Ordering::Relaxed) > 0
+    }
+
+    fn stall(&self) {
+        self.stalled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_stalled(&self) -> bool {
+        self.stalled.load(Ordering::Relaxed)
+    }
+
+    /// Why the phase produced no measurement
+    fn failure_reason(&self) -> String {
+        let last_error = self.last_error.lock().ok().and_then(|e| e.clone());
+        match (self.is_stalled(), last_error) {
+            (true, Some(error)) => format!("transfers stalled, last error: {}", error),
+            (true, None) => "transfers stalled".to_string(),
+            (false, Some(error)) => format!("no data transferred, last error: {}", error),
+            (false, None) => "no data transferred".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -49,17 +274,223 @@ pub struct ServerPerformance {
     pub overall_score: f64,
 }
 
+/// Outcome of the conformance checks run against one provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCheck {
+    pub provider: String,
+    pub server_name: String,
+    pub server_url: String,
+    pub latency_ok: bool,
+    pub latency_ms: Option<f64>,
+    pub download_ok: bool,
+    pub download_bytes: Option<usize>,
+    /// None when the provider does not advertise upload support
+    pub upload_ok: Option<bool>,
+}
+
+impl ProviderCheck {
+    /// Size of the download probe, small enough to be polite to every provider
+    pub const PROBE_BYTES: usize = 100_000;
+
+    /// A provider is functional when every check it supports passed
+    pub fn is_functional(&self) -> bool {
+        self.latency_ok && self.download_ok && self.upload_ok.unwrap_or(true)
+    }
+}
+
+/// Whether a pool entry's test endpoints answer, `None` for directions it does not offer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerHealth {
+    pub server_name: String,
+    pub server_url: String,
+    pub provider: String,
+    pub download_ok: Option<bool>,
+    pub upload_ok: Option<bool>,
+}
+
+impl ServerHealth {
+    /// A server stays in the pool while at least one of its test directions works
+    pub fn is_alive(&self) -> bool {
+        self.download_ok == Some(true) || self.upload_ok == Some(true)
+    }
+}
+
+/// Outcome of `servers verify`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerVerification {
+    pub providers: Vec<ProviderCheck>,
+    /// Every candidate for the pool, nearest first
+    pub pool: Vec<ServerHealth>,
+}
+
+/// Servers settled on before the location is known
+enum EarlyServers {
+    /// Pinned in the menu or reused from the last run, with the label shown for it
+    Chosen(Box<TestServer>, &'static str),
+    /// The pool still has to be built, from what was found out meanwhile
+    Pool(EarlyDiscovery),
+}
+
+/// The part of building the pool that does not need the location, done while
+/// it is detected
+#[derive(Default)]
+struct EarlyDiscovery {
+    /// The speedtest.net server list, None when it could not be fetched
+    speedtest_list: Option<String>,
+    /// Health of the global servers, which are candidates wherever the client is
+    health: Vec<ServerHealth>,
+}
+
+/// One answered latency probe
+struct ProbeTiming {
+    /// Unix milliseconds of the system clock when it was sent
+    sent_ms: f64,
+    rtt_ms: f64,
+    /// When the server received it by its own clock, from backends that say
+    server_ms: Option<f64>,
+}
+
+/// One entry of the speedtest.net server list
+#[derive(Debug, Deserialize)]
+struct SpeedtestNetServer {
+    /// `host:port` of the test endpoint
+    host: String,
+    /// City the server stands in
+    name: String,
+    country: String,
+    cc: Option<String>,
+    /// Operator hosting the server
+    sponsor: Option<String>,
+    #[serde(deserialize_with = "number_or_string")]
+    lat: f64,
+    #[serde(deserialize_with = "number_or_string")]
+    lon: f64,
+    /// Kilometres from the caller as seen by speedtest.net
+    #[serde(default, deserialize_with = "optional_number_or_string")]
+    distance: Option<f64>,
+}
+
+/// The API sends coordinates as strings and distances as numbers, accept either
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(f64),
+    String(String),
+}
+
+impl NumberOrString {
+    fn value<E: serde::de::Error>(self) -> Result<f64, E> {
+        match self {
+            NumberOrString::Number(n) => Ok(n),
+            NumberOrString::String(s) => s.trim().parse().map_err(E::custom),
+        }
+    }
+}
+
+fn number_or_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    NumberOrString::deserialize(deserializer)?.value()
+}
+
+fn optional_number_or_string<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    Option::<NumberOrString>::deserialize(deserializer)?
+        .map(NumberOrString::value)
+        .transpose()
+}
+
+/// Wait before retry number `attempt` (1-based): the base backoff, doubled for every
+/// further retry and capped at an hour
+fn retry_delay(backoff_seconds: u64, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    Duration::from_secs(backoff_seconds.saturating_mul(factor).min(3600))
+}
+
+/// How hard a speed test pushes: streams per phase, how long each throughput phase
+/// runs and how many servers the streams spread over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestProfile {
+    pub download_connections: usize,
+    pub upload_connections: usize,
+    pub phase_duration: Duration,
+    pub server_count: usize,
+}
+
+impl Default for TestProfile {
+    fn default() -> Self {
+        Self {
+            download_connections: PARALLEL_CONNECTIONS,
+            upload_connections: UPLOAD_CONNECTIONS,
+            phase_duration: PHASE_DURATION,
+            server_count: SERVER_SELECTION_COUNT,
+        }
+    }
+}
+
+impl TestProfile {
+    /// Twice as long over twice as many servers, for baselines
+    pub fn calibration() -> Self {
+        Self {
+            phase_duration: PHASE_DURATION * 2,
+            server_count: SERVER_SELECTION_COUNT * 2,
+            ..Self::default()
+        }
+    }
+
+    /// One stream each way, which shows what a single connection can get
+    pub fn single_stream(self) -> Self {
+        Self {
+            download_connections: 1,
+            upload_connections: 1,
+            ..self
+        }
+    }
+}
+
 pub struct SpeedTest {
     config: TestConfig,
+    profile: TestProfile,
     client: Client,
     ui: UI,
     geo_location: Arc<RwLock<Option<GeoLocation>>>,
     server_pool: Arc<RwLock<Vec<TestServer>>>,
+    /// Servers chosen by the last test, nearest first
+    selected_servers: Arc<RwLock<Vec<TestServer>>>,
+    rate_limiter: Arc<ProviderRateLimiter>,
+    /// Per-server limit on the latency and jitter probes
+    probe_pacer: ProbePacer,
+    /// HTTP version the first download response came back with
+    negotiated_protocol: Arc<OnceLock<reqwest::Version>>,
+    /// TLS version of the client's latest handshake
+    tls_versions: Arc<VersionLog>,
+    /// Host lookups shared by the client, the QoS relay and the result's server address
+    resolver: ResolverCache,
+    /// Public address of this machine once a lookup succeeded
+    client_ip: Arc<OnceLock<IpAddr>>,
+    /// Samples the TCP state of the test's connections at the debug detail level
+    tcp_inspector: Option<Arc<TcpInspector>>,
+    /// Carries the client's connections when they are DSCP marked or inspected,
+    /// held to keep it running
+    _relay: Option<SocketRelay>,
 }
 
 impl SpeedTest {
     pub fn new(config: TestConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = Client::builder()
+        let tcp_inspector = (config.detail_level >= DetailLevel::Debug
+            && TcpInspector::is_supported())
+        .then(|| Arc::new(TcpInspector::default()));
+        let resolver = ResolverCache::default();
+        let tls_versions = Arc::new(VersionLog::default());
+        let relay = if config.dscp.is_some() || tcp_inspector.is_some() {
+            Some(SocketRelay::start(
+                config.dscp,
+                tcp_inspector.clone(),
+                resolver.clone(),
+            )?)
+        } else {
+            None
+        };
+        let mut builder = Client::builder()
             .timeout(Duration::from_secs(30))
             .pool_max_idle_per_host(100)
             .pool_idle_timeout(Duration::from_secs(120))
@@ -68,60 +499,344 @@ impl SpeedTest {
             .http2_adaptive_window(true)
             .http2_initial_stream_window_size(1024 * 1024) // 1MB
             .http2_initial_connection_window_size(2 * 1024 * 1024) // 2MB
-            .danger_accept_invalid_certs(false)
-            .build()?;
+            .tls_backend_preconfigured(tls::transfer_config(Arc::clone(&tls_versions))?)
+            .dns_resolver(resolver.clone());
+        if let Some(relay) = &relay {
+            builder = builder.proxy(relay.proxy()?);
+        }
+        let client = builder.build()?;
 
         let ui = UI::new(config.clone());
 
         Ok(Self {
             config,
+            profile: TestProfile::default(),
             client,
             ui,
             geo_location: Arc::new(RwLock::new(None)),
             server_pool: Arc::new(RwLock::new(Vec::new())),
+            selected_servers: Arc::new(RwLock::new(Vec::new())),
+            rate_limiter: Arc::new(ProviderRateLimiter::new()),
+            probe_pacer: ProbePacer::default(),
+            negotiated_protocol: Arc::new(OnceLock::new()),
+            tls_versions,
+            resolver,
+            client_ip: Arc::new(OnceLock::new()),
+            tcp_inspector,
+            _relay: relay,
         })
     }
 
-    /// Run the complete speed test with intelligent server selection
+    /// Run with `profile` instead of the standard streams and durations
+    pub fn with_profile(mut self, profile: TestProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Servers the last completed test measured against
+    pub async fn selected_servers(&self) -> Vec<TestServer> {
+        self.selected_servers.read().await.clone()
+    }
+
+    /// Run the complete speed test with intelligent server selection.
+    /// Failed attempts are retried `config.retries` times with exponential backoff,
+    /// so transient outages (geolocation down, a 503 from a server) don't fail unattended runs.
     pub async fn run_full_test(&self) -> Result<SpeedTestResult, Box<dyn std::error::Error>> {
-        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let attempt_result = match self.config.provider {
+                SpeedProvider::Auto | SpeedProvider::Fast => self.run_attempt().await,
+                SpeedProvider::Mlab => self.run_ndt7_attempt().await,
+            };
+            match attempt_result {
+                Ok(result) => return Ok(result),
+                Err(e) if attempt < self.config.retries => {
+                    attempt += 1;
+                    let delay = retry_delay(self.config.retry_backoff_seconds, attempt);
+                    eprintln!(
+                        "{} {} (retry {}/{} in {})",
+                        "⚠ Test failed:".bright_yellow(),
+                        e,
+                        attempt,
+                        self.config.retries,
+                        humantime::format_duration(delay)
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
 
-        // Phase 1: Detect location
-        let geo = self.detect_location().await?;
+    /// Locate and pick servers as a full test would, for `run_download_load`
+    pub async fn choose_load_servers(&self) -> Result<Vec<TestServer>, Box<dyn std::error::Error>> {
+        let (located, early) = tokio::join!(self.locate(), self.early_servers());
+        let geo = located.unwrap_or_else(|| self.default_location());
         *self.geo_location.write().await = Some(geo.clone());
+        let (servers, _) = self.choose_servers(&geo, early).await?;
+        Ok(servers)
+    }
+
+    /// Run only the download phase against `servers`, to keep the line busy while
+    /// something else is measured
+    pub async fn run_download_load(
+        &self,
+        servers: &[TestServer],
+    ) -> Result<PhaseOutcome, Box<dyn std::error::Error>> {
+        let max_data = self.config.max_data.map(|size| size.0);
+        let (outcome, _) = self
+            .run_phase_with_failover(
+                SpeedPhase::Download,
+                servers,
+                max_data,
+                Duration::ZERO,
+                &mut Vec::new(),
+            )
+            .await?;
+        Ok(outcome)
+    }
+
+    /// Warn, or fail with `require_idle`, when the interface already carries
+    /// traffic before the test, e.g. a running backup. Only checked on Linux.
+    async fn check_idle(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(traffic) = localnet::sample_traffic(IDLE_CHECK_WINDOW).await else {
+            return Ok(());
+        };
+        if !traffic.is_significant() {
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} is already carrying {:.1} Mbps down and {:.1} Mbps up, a backup or download running meanwhile will skew the result",
+            traffic.interface, traffic.download_mbps, traffic.upload_mbps
+        );
+        if self.config.require_idle {
+            return Err(format!("{} (stop it or run without --require-idle)", message).into());
+        }
+        if !self.config.json_output {
+            println!("{} {}", "⚠".bright_yellow(), message.bright_yellow());
+        }
+        Ok(())
+    }
+
+    /// Pick the pinned or reusable server, or failing that start the pool with
+    /// the checks that don't need the location
+    async fn early_servers(&self) -> EarlyServers {
+        // fast.com's URLs are signed for one test, its pool is fetched with the location
+        if self.config.provider == SpeedProvider::Fast {
+            return EarlyServers::Pool(EarlyDiscovery::default());
+        }
+        if let Some(server) = &self.config.pinned_server {
+            return EarlyServers::Chosen(Box::new(server.clone()), "📌 Pinned:");
+        }
+        match self.reusable_server().await {
+            Some(server) => EarlyServers::Chosen(Box::new(server), "✓ Reusing:"),
+            None => EarlyServers::Pool(self.early_discovery().await),
+        }
+    }
+
+    async fn early_discovery(&self) -> EarlyDiscovery {
+        if self
+            .config
+            .server_list
+            .as_ref()
+            .is_some_and(|list| list.replace)
+        {
+            return EarlyDiscovery::default();
+        }
+        let global: Vec<TestServer> = self
+            .get_global_cdn_servers()
+            .into_iter()
+            .filter(|server| self.config.server_filter.allows(server))
+            .collect();
+        let (speedtest_list, health) = tokio::join!(
+            self.fetch_speedtest_net_list(),
+            futures::future::join_all(global.iter().map(|server| Self::check_server_health(
+                &self.client,
+                &self.rate_limiter,
+                server
+            )))
+        );
+        EarlyDiscovery {
+            speedtest_list,
+            health,
+        }
+    }
+
+    /// Build the server pool and select the best servers, unless a server was
+    /// pinned from the interactive picker or the last run's best server still
+    /// answers. Returns them with the label they are announced under.
+    async fn choose_servers(
+        &self,
+        geo: &GeoLocation,
+        early: EarlyServers,
+    ) -> Result<(Vec<TestServer>, &'static str), Box<dyn std::error::Error>> {
+        let (best_servers, label) = match early {
+            EarlyServers::Chosen(server, label) => (vec![*server], label),
+            EarlyServers::Pool(discovery) => {
+                self.build_server_pool(geo, discovery).await?;
+                let best_servers = self.select_best_servers().await?;
+                if self.config.provider == SpeedProvider::Auto {
+                    self.remember_best_server(&best_servers[0]);
+                }
+                (best_servers, "✓ Selected:")
+            }
+        };
+        *self.selected_servers.write().await = best_servers.clone();
+        Ok((best_servers, label))
+    }
 
-        // Phase 2: Build server pool
-        self.build_server_pool(&geo).await?;
+    async fn run_attempt(&self) -> Result<SpeedTestResult, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+
+        // Phase 0: Other traffic already using the line skews the result
+        self.check_idle().await?;
+
+        // Phase 1: Detect location, the result only records a country that was found.
+        // The server checks that don't need it and the client's address are looked
+        // up at the same time.
+        let overlap_started = Instant::now();
+        let ((located, locate_time), (early, early_time), (client_ip, client_ip_time)) = tokio::join!(
+            timed(self.locate()),
+            timed(self.early_servers()),
+            timed(self.get_client_ip())
+        );
+        let overlap_time = overlap_started.elapsed();
+        let country = located.as_ref().map(|geo| geo.country.clone());
+        let geo = located.unwrap_or_else(|| self.default_location());
+        *self.geo_location.write().await = Some(geo.clone());
 
-        // Phase 3: Select best servers
-        let best_servers = self.select_best_servers().await?;
+        // Phases 2 and 3: Build the server pool and select the best servers
+        let (best_servers, label) = self.choose_servers(&geo, early).await?;
+        if std::env::var("NETRUNNER_DEBUG").is_ok() {
+            eprintln!(
+                "[TRACE] phase timings: location {:.2}s, early server checks {:.2}s, client IP {:.2}s, side by side {:.2}s; pool and selection {:.2}s; ready to transfer after {:.2}s",
+                locate_time.as_secs_f64(),
+                early_time.as_secs_f64(),
+                client_ip_time.as_secs_f64(),
+                overlap_time.as_secs_f64(),
+                (overlap_started.elapsed() - overlap_time).as_secs_f64(),
+                start.elapsed().as_secs_f64()
+            );
+        }
 
         if !self.config.json_output {
             println!(
                 "{} {} ({}, {:.0} km)",
-                "✓ Selected:".bright_green().bold(),
+                label.bright_green().bold(),
                 best_servers[0].name,
                 best_servers[0].location,
                 best_servers[0].distance_km.unwrap_or(0.0)
             );
         }
 
-        // Phase 4: Measure latency
-        let ping_ms = self.measure_latency(&best_servers[0]).await?;
+        // The router's WAN counters are polled through phases 5 and 6
+        let wan_poller = match &self.config.snmp {
+            Some(settings) => match WanPoller::start(settings).await {
+                Ok(poller) => Some(poller),
+                Err(e) => {
+                    if !self.config.json_output {
+                        self.ui
+                            .show_error(&format!("Router SNMP polling unavailable: {}", e))?;
+                    }
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Phase 4: Measure latency, with the interface counters of phases 4 to 7.
+        // The transfers reuse the connections opened from here on.
+        let link_before = LinkSnapshot::capture();
+        self.tls_versions.clear();
+        if let Some(inspector) = &self.tcp_inspector {
+            inspector.reset();
+        }
+        let (ping_ms, mut latency_samples) = self.measure_latency(&best_servers[0]).await?;
+        let websocket_latency = self.measure_websocket_latency(&best_servers[0]).await;
+
+        // Phase 5: Download test (progressive), allowed half of any data cap
+        let max_data = self.config.max_data.map(|size| size.0);
+        let mut failovers = Vec::new();
+        let download_started = Instant::now();
+        let (download, downloaded_bytes) = self
+            .run_phase_with_failover(
+                SpeedPhase::Download,
+                &best_servers,
+                max_data.map(|max| max / 2),
+                self.profile.phase_duration + WRAP_UP_ESTIMATE,
+                &mut failovers,
+            )
+            .await?;
 
-        // Phase 5: Download test (progressive)
-        let download_mbps = self.progressive_download_test(&best_servers).await?;
+        // Phase 6: Upload test (progressive), allowed what the download left
+        let upload_started = Instant::now();
+        let (upload, uploaded_bytes) = self
+            .run_phase_with_failover(
+                SpeedPhase::Upload,
+                &best_servers,
+                max_data.map(|max| max.saturating_sub(downloaded_bytes)),
+                WRAP_UP_ESTIMATE,
+                &mut failovers,
+            )
+            .await?;
+        let wan_usage = wan_poller.map(|poller| {
+            poller.usage(
+                (download_started, upload_started),
+                (upload_started, Instant::now()),
+            )
+        });
 
-        // Phase 6: Upload test (progressive)
-        let upload_mbps = self.progressive_upload_test(&best_servers).await?;
+        let failures = [
+            (SpeedPhase::Download, &download),
+            (SpeedPhase::Upload, &upload),
+        ]
+        .into_iter()
+        .filter_map(|(phase, outcome)| match outcome {
+            PhaseOutcome::Failed(reason) => Some(PhaseFailure {
+                phase,
+                reason: reason.clone(),
+            }),
+            PhaseOutcome::Measured(_) => None,
+        })
+        .collect();
+        let download_mbps = download.mbps().unwrap_or(0.0);
+        let upload_mbps = upload.mbps().unwrap_or(0.0);
 
         // Phase 7: Calculate statistics
-        let (jitter_ms, packet_loss) = self.measure_jitter_and_loss(&best_servers[0]).await?;
+        let (jitter_ms, packet_loss, jitter_samples, one_way_delay) =
+            self.measure_jitter_and_loss(&best_servers[0]).await?;
+        latency_samples.extend(jitter_samples);
+        let link_stats = link_before
+            .zip(LinkSnapshot::capture())
+            .and_then(|(before, after)| before.delta(&after));
+        let tcp_health = self
+            .tcp_inspector
+            .as_ref()
+            .and_then(|inspector| inspector.health());
+
+        let quality = ConnectionQuality::from_metrics(
+            download_mbps,
+            upload_mbps,
+            ping_ms,
+            jitter_ms,
+            packet_loss,
+        );
 
-        let quality = ConnectionQuality::from_speed_and_ping(download_mbps, upload_mbps, ping_ms);
+        // Phase 8: Latency to the user's own endpoints
+        let endpoints = self.measure_endpoints().await?;
         let test_duration = start.elapsed().as_secs_f64();
 
-        let result = SpeedTestResult {
+        let tls_version = self.tls_versions.version();
+        let transport = self
+            .negotiated_protocol
+            .get()
+            .map(|&version| TransportInfo {
+                // Only a TLS connection negotiates its protocol
+                alpn: tls_version.as_ref().and_then(|_| alpn_name(version)),
+                tls_version,
+            });
+        let mut result = SpeedTestResult {
             timestamp: Utc::now(),
             download_mbps,
             upload_mbps,
@@ -130,419 +845,668 @@ impl SpeedTest {
             packet_loss_percent: packet_loss,
             server_location: best_servers[0].location.clone(),
             server_ip: self.resolve_server_ip(&best_servers[0].url).await,
-            client_ip: self.get_client_ip().await,
+            client_ip,
             quality,
             test_duration_seconds: test_duration,
             isp: geo.isp.clone(),
+            latency_stats: LatencyStats::from_samples(&latency_samples),
+            host_label: self.config.host_label.clone(),
+            site: self.config.site.clone(),
+            failovers,
+            failures,
+            data_used: Some(DataUsage {
+                downloaded_bytes,
+                uploaded_bytes,
+            }),
+            endpoints,
+            engine: Some(EngineInfo::current(
+                &self.config,
+                self.negotiated_protocol
+                    .get()
+                    .map(|version| format!("{:?}", version)),
+                self.profile.download_connections as u32,
+                self.profile.upload_connections as u32,
+            )),
+            country,
+            link_stats,
+            other_traffic: None,
+            tags: self.config.tags.clone(),
+            transport,
+            websocket_latency,
+            one_way_delay,
+            jitter_method: JitterMethod::Rfc3550,
         };
+        if let Some(usage) = &wan_usage {
+            let lan_devices = localnet::lan_device_count().await;
+            result.other_traffic = Some(OtherTraffic::from_wan(usage, &result, lan_devices));
+        }
 
         if !self.config.json_output {
             self.display_results(&result)?;
+            if let Some(health) = &tcp_health {
+                self.display_tcp_health(health);
+            }
+            if let Some(usage) = &wan_usage {
+                self.display_wan_usage(usage, &result);
+            }
+            self.display_regional_comparison(&geo, &result).await?;
         }
 
         Ok(result)
     }
 
-    /// Detect user's geolocation using multiple services
-    async fn detect_location(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+    /// One test against the nearest Measurement Lab server with the ndt7 protocol.
+    /// Latency, jitter and loss come from the server's view of the download
+    /// connection instead of separate probes.
+    async fn run_ndt7_attempt(&self) -> Result<SpeedTestResult, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        self.check_idle().await?;
+
+        // Phase 1: Locate ourselves and the nearest M-Lab server
         if !self.config.json_output {
-            println!("{}", "🌍 Detecting your location...".bright_cyan());
+            println!(
+                "{}",
+                "🔭 Asking Measurement Lab for the nearest ndt7 server...".bright_cyan()
+            );
         }
-
-        // Try multiple geolocation services sequentially (first success wins)
-        // Try ipapi.co
-        match self.try_ipapi_co().await {
-            Ok(geo) => {
-                if !self.config.json_output {
-                    println!(
-                        "{} {}, {} (via ipapi.co)",
-                        "📍 Location:".bright_green(),
-                        geo.city,
-                        geo.country
-                    );
-                    if let Some(isp) = &geo.isp {
-                        println!("{} {}", "🔌 ISP:".bright_blue(), isp);
-                    }
-                }
-                return Ok(geo);
-            }
-            Err(e) => {
-                // Log error at trace level for debugging
-                if std::env::var("NETRUNNER_DEBUG").is_ok() {
-                    eprintln!("[TRACE] ipapi.co geolocation failed: {}", e);
-                }
-            }
+        let (located, servers, client_ip) = tokio::join!(
+            self.locate(),
+            ndt7::locate(&self.client),
+            self.get_client_ip()
+        );
+        let server = servers?.swap_remove(0);
+        let country = located.as_ref().map(|geo| geo.country.clone());
+        let geo = located.unwrap_or_else(|| self.default_location());
+        *self.geo_location.write().await = Some(geo.clone());
+        if !self.config.json_output {
+            println!(
+                "{} {} ({})",
+                "✓ M-Lab:".bright_green().bold(),
+                server.location_name(),
+                server.machine
+            );
         }
 
-        // Try ip-api.com
-        match self.try_ip_api_com().await {
-            Ok(geo) => {
-                if !self.config.json_output {
-                    println!(
-                        "{} {}, {} (via ip-api.com)",
-                        "📍 Location:".bright_green(),
-                        geo.city,
-                        geo.country
-                    );
-                    if let Some(isp) = &geo.isp {
-                        println!("{} {}", "🔌 ISP:".bright_blue(), isp);
-                    }
-                }
-                return Ok(geo);
-            }
-            Err(e) => {
-                // Log error at trace level for debugging
-                if std::env::var("NETRUNNER_DEBUG").is_ok() {
-                    eprintln!("[TRACE] ip-api.com geolocation failed: {}", e);
-                }
-            }
-        }
+        // Phases 2 and 3: Download, then upload, allowed half of any data cap each
+        let link_before = LinkSnapshot::capture();
+        let max_data = self.config.max_data.map(|size| size.0);
+        let download = self
+            .run_ndt7_phase(
+                SpeedPhase::Download,
+                server.download_url().unwrap_or_default(),
+                max_data.map(|max| max / 2),
+                ndt7::UPLOAD_DURATION + WRAP_UP_ESTIMATE,
+            )
+            .await;
+        let downloaded_bytes = download.as_ref().map_or(0, |transfer| transfer.bytes);
+        let upload = self
+            .run_ndt7_phase(
+                SpeedPhase::Upload,
+                server.upload_url().unwrap_or_default(),
+                max_data.map(|max| max.saturating_sub(downloaded_bytes)),
+                WRAP_UP_ESTIMATE,
+            )
+            .await;
+        let uploaded_bytes = upload.as_ref().map_or(0, |transfer| transfer.bytes);
 
-        // Try ipinfo.io
-        match self.try_ipinfo_io().await {
-            Ok(geo) => {
-                if !self.config.json_output {
-                    println!(
-                        "{} {}, {} (via ipinfo.io)",
-                        "📍 Location:".bright_green(),
-                        geo.city,
-                        geo.country
-                    );
-                    if let Some(isp) = &geo.isp {
-                        println!("{} {}", "🔌 ISP:".bright_blue(), isp);
-                    }
-                }
-                return Ok(geo);
-            }
-            Err(e) => {
-                // Log error at trace level for debugging
-                if std::env::var("NETRUNNER_DEBUG").is_ok() {
-                    eprintln!("[TRACE] ipinfo.io geolocation failed: {}", e);
-                }
-            }
-        }
+        let failures: Vec<PhaseFailure> = [
+            (SpeedPhase::Download, &download),
+            (SpeedPhase::Upload, &upload),
+        ]
+        .into_iter()
+        .filter_map(|(phase, transfer)| {
+            transfer.as_ref().err().map(|reason| PhaseFailure {
+                phase,
+                reason: reason.clone(),
+            })
+        })
+        .collect();
+        let download_mbps = download.as_ref().map_or(0.0, ndt7::Transfer::mbps);
+        // The server counts what actually arrived, the client only what it queued
+        let upload_mbps = upload.as_ref().map_or(0.0, |transfer| {
+            transfer
+                .tcp_info()
+                .and_then(ndt7::TcpInfo::received_mbps)
+                .unwrap_or_else(|| transfer.mbps())
+        });
 
-        // Try freegeoip.app
-        match self.try_freegeoip_app().await {
-            Ok(geo) => {
-                if !self.config.json_output {
-                    println!(
-                        "{} {}, {} (via freegeoip.app)",
-                        "📍 Location:".bright_green(),
-                        geo.city,
-                        geo.country
-                    );
-                    if let Some(isp) = &geo.isp {
-                        println!("{} {}", "🔌 ISP:".bright_blue(), isp);
-                    }
-                }
-                return Ok(geo);
-            }
-            Err(e) => {
-                // Log error at trace level for debugging
-                if std::env::var("NETRUNNER_DEBUG").is_ok() {
-                    eprintln!("[TRACE] freegeoip.app geolocation failed: {}", e);
-                }
-            }
-        }
+        // Phase 4: Statistics from the server's TCP_INFO
+        let download_info = download.as_ref().ok().and_then(ndt7::Transfer::tcp_info);
+        let mut latency_samples: Vec<f64> = [&download, &upload]
+            .into_iter()
+            .flatten()
+            .flat_map(|transfer| transfer.rtt_samples_ms.iter().copied())
+            .collect();
+        let ping_ms = download_info
+            .and_then(|info| info.min_rtt)
+            .map(|rtt| rtt as f64 / 1000.0)
+            .or_else(|| latency_samples.iter().copied().reduce(f64::min))
+            .unwrap_or(0.0);
+        let jitter_ms = download_info
+            .and_then(|info| info.rtt_var)
+            .map_or(0.0, |rtt_var| rtt_var as f64 / 1000.0);
+        let packet_loss = download_info
+            .and_then(ndt7::TcpInfo::retransmit_percent)
+            .unwrap_or(0.0);
+        latency_samples.retain(|&rtt| rtt > 0.0);
+        let link_stats = link_before
+            .zip(LinkSnapshot::capture())
+            .and_then(|(before, after)| before.delta(&after));
+
+        let quality = ConnectionQuality::from_metrics(
+            download_mbps,
+            upload_mbps,
+            ping_ms,
+            jitter_ms,
+            packet_loss,
+        );
 
-        // Try ipwhois.app
-        match self.try_ipwhois_app().await {
-            Ok(geo) => {
-                if !self.config.json_output {
-                    println!(
-                        "{} {}, {} (via ipwhois.app)",
-                        "📍 Location:".bright_green(),
-                        geo.city,
-                        geo.country
-                    );
-                    if let Some(isp) = &geo.isp {
-                        println!("{} {}", "🔌 ISP:".bright_blue(), isp);
-                    }
-                }
-                return Ok(geo);
-            }
-            Err(e) => {
-                // Log error at trace level for debugging
-                if std::env::var("NETRUNNER_DEBUG").is_ok() {
-                    eprintln!("[TRACE] ipwhois.app geolocation failed: {}", e);
-                }
-            }
-        }
+        // Phase 5: Latency to the user's own endpoints
+        let endpoints = self.measure_endpoints().await?;
+
+        let result = SpeedTestResult {
+            timestamp: Utc::now(),
+            download_mbps,
+            upload_mbps,
+            ping_ms,
+            jitter_ms,
+            packet_loss_percent: packet_loss,
+            server_location: format!("{} (M-Lab)", server.location_name()),
+            server_ip: self
+                .resolve_server_ip(server.download_url().unwrap_or_default())
+                .await,
+            client_ip,
+            quality,
+            test_duration_seconds: start.elapsed().as_secs_f64(),
+            isp: geo.isp.clone(),
+            latency_stats: LatencyStats::from_samples(&latency_samples),
+            host_label: self.config.host_label.clone(),
+            site: self.config.site.clone(),
+            failovers: Vec::new(),
+            failures,
+            data_used: Some(DataUsage {
+                downloaded_bytes,
+                uploaded_bytes,
+            }),
+            endpoints,
+            engine: Some(EngineInfo::current(
+                &self.config,
+                Some("ndt7".to_string()),
+                1,
+                1,
+            )),
+            country,
+            link_stats,
+            other_traffic: None,
+            tags: self.config.tags.clone(),
+            transport: download
+                .as_ref()
+                .ok()
+                .and_then(|transfer| transfer.transport.clone()),
+            websocket_latency: None,
+            one_way_delay: None,
+            jitter_method: JitterMethod::RttVariance,
+        };
 
-        // Fallback: Use a default location (USA central) if all services fail
         if !self.config.json_output {
-            println!(
-                "{} Using default location (USA Central) - all geolocation services failed",
-                "⚠".bright_yellow()
-            );
+            self.display_results(&result)?;
+            self.display_regional_comparison(&geo, &result).await?;
         }
 
-        Ok(GeoLocation {
-            country: "United States".to_string(),
-            city: "Kansas City".to_string(),
-            latitude: 39.0997,
-            longitude: -94.5786,
-            isp: None,
-        })
+        Ok(result)
     }
 
-    async fn try_ipapi_co(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .get("https://ipapi.co/json/")
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+    /// Run one ndt7 direction with the live display of the HTTP phases
+    async fn run_ndt7_phase(
+        &self,
+        phase: SpeedPhase,
+        url: &str,
+        max_bytes: Option<u64>,
+        after: Duration,
+    ) -> Result<ndt7::Transfer, String> {
+        let (title, label, quality_band): (_, _, fn(f64) -> ConnectionQuality) = match phase {
+            SpeedPhase::Download => (
+                "DOWNLOAD SPEED BANDWIDTH MONITOR",
+                "Download",
+                ConnectionQuality::from_download_mbps,
+            ),
+            SpeedPhase::Upload => (
+                "UPLOAD SPEED BANDWIDTH MONITOR",
+                "Upload",
+                ConnectionQuality::from_upload_mbps,
+            ),
+        };
+        if !self.config.json_output {
+            let _ = self
+                .ui
+                .show_section_header(&format!("Testing {} Speed (M-Lab ndt7)", label));
         }
+        let bw_monitor = (!self.config.json_output && self.config.animation_enabled)
+            .then(|| self.ui.create_bandwidth_monitor(title, label, quality_band));
 
-        let json: serde_json::Value = response.json().await?;
-
-        // Check for API error
-        if json.get("error").is_some() {
-            return Err(format!(
-                "API error: {}",
-                json["reason"].as_str().unwrap_or("Unknown")
-            )
-            .into());
-        }
+        let counter = Arc::new(AtomicU64::new(0));
+        let monitor_counter = Arc::clone(&counter);
+        let monitor_clone = bw_monitor.clone();
+        let progress_events = self.config.progress_events;
+        let monitor_handle = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut last_bytes = 0;
+            let mut last_time = Instant::now();
+            let mut first_render = true;
+            let mut last_event: Option<Instant> = None;
 
-        let country = json["country_name"]
-            .as_str()
-            .filter(|s| !s.is_empty() && *s != "Unknown")
-            .ok_or("Invalid country")?
-            .to_string();
+            loop {
+                tokio::time::sleep(Duration::from_millis(200)).await;
 
-        let city = json["city"]
-            .as_str()
-            .filter(|s| !s.is_empty() && *s != "Unknown")
-            .ok_or("Invalid city")?
-            .to_string();
+                let countdown =
+                    PhaseCountdown::new(phase, ndt7::UPLOAD_DURATION, start.elapsed(), after);
+                if progress_events
+                    && last_event.is_none_or(|at| at.elapsed() >= Duration::from_secs(1))
+                {
+                    emit_progress(&countdown);
+                    last_event = Some(Instant::now());
+                }
 
-        let latitude = json["latitude"].as_f64().ok_or("Invalid latitude")?;
-        let longitude = json["longitude"].as_f64().ok_or("Invalid longitude")?;
+                let bytes = monitor_counter.load(Ordering::Relaxed);
+                let speed = bytes.saturating_sub(last_bytes) as f64 * 8.0
+                    / (last_time.elapsed().as_secs_f64() * 1_000_000.0);
+                last_bytes = bytes;
+                last_time = Instant::now();
+
+                if let Some(ref monitor) = monitor_clone {
+                    monitor.update(speed).await;
+                    monitor.set_countdown(countdown).await;
+                    if first_render {
+                        let _ = monitor.render_live().await;
+                        first_render = false;
+                    } else {
+                        let _ = monitor.render_live_update().await;
+                    }
+                }
+            }
+        });
 
-        if latitude == 0.0 && longitude == 0.0 {
-            return Err("Invalid coordinates".into());
+        let transfer = match phase {
+            SpeedPhase::Download => ndt7::download(url, &self.resolver, counter, max_bytes).await,
+            SpeedPhase::Upload => ndt7::upload(url, &self.resolver, counter, max_bytes).await,
         }
+        .map_err(|e| e.to_string());
+        monitor_handle.abort();
+        let _ = monitor_handle.await;
 
-        Ok(GeoLocation {
-            country,
-            city,
-            latitude,
-            longitude,
-            isp: json["org"].as_str().map(String::from),
-        })
+        if let Some(ref monitor) = bw_monitor {
+            if let Ok(transfer) = &transfer {
+                monitor.update(transfer.mbps()).await;
+            }
+            monitor.mark_final().await;
+            let _ = monitor.render_live_update().await;
+        }
+        transfer
     }
 
-    async fn try_ip_api_com(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .get("http://ip-api.com/json/?fields=status,message,country,city,lat,lon,isp")
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await?;
+    /// Health-check every candidate for the pool and run small conformance checks
+    /// against one server of every provider, without leaving out dead servers first
+    pub async fn verify_servers(&self) -> Result<ServerVerification, Box<dyn std::error::Error>> {
+        let (geo, speedtest_list) =
+            tokio::join!(self.detect_location(), self.fetch_speedtest_net_list());
+        let geo = geo?;
+        *self.geo_location.write().await = Some(geo.clone());
+        let candidates = self.candidate_servers(&geo, speedtest_list).await;
+
+        // The pool is sorted nearest first, so the first server seen per provider is the closest
+        let mut representatives: Vec<TestServer> = Vec::new();
+        for server in &candidates {
+            if !representatives
+                .iter()
+                .any(|s| s.provider == server.provider)
+            {
+                representatives.push(server.clone());
+            }
+        }
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+        if !self.config.json_output {
+            println!(
+                "{} {} providers and {} servers...",
+                "🧪 Verifying".bright_cyan(),
+                representatives.len(),
+                candidates.len()
+            );
         }
 
-        let json: serde_json::Value = response.json().await?;
+        let (providers, pool) = tokio::join!(
+            futures::future::join_all(representatives.iter().map(|server| Self::check_provider(
+                &self.client,
+                &self.rate_limiter,
+                server
+            )),),
+            futures::future::join_all(candidates.iter().map(|server| Self::check_server_health(
+                &self.client,
+                &self.rate_limiter,
+                server
+            )),)
+        );
+
+        Ok(ServerVerification { providers, pool })
+    }
 
-        // Check for API error
-        if json["status"].as_str() != Some("success") {
-            return Err(format!(
-                "API error: {}",
-                json["message"].as_str().unwrap_or("Unknown")
-            )
-            .into());
-        }
+    async fn check_provider(
+        client: &Client,
+        rate_limiter: &ProviderRateLimiter,
+        server: &TestServer,
+    ) -> ProviderCheck {
+        let timeout = Duration::from_secs(10);
 
-        let country = json["country"]
-            .as_str()
-            .filter(|s| !s.is_empty() && *s != "Unknown")
-            .ok_or("Invalid country")?
-            .to_string();
+        // Latency endpoint: a HEAD request must be answered
+        rate_limiter.acquire(&server.provider).await;
+        let start = Instant::now();
+        let latency_ms = match server
+            .backend()
+            .latency_probe(client, server)
+            .timeout(timeout)
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
+                Some(start.elapsed().as_secs_f64() * 1000.0)
+            }
+            _ => None,
+        };
 
-        let city = json["city"]
-            .as_str()
-            .filter(|s| !s.is_empty() && *s != "Unknown")
-            .ok_or("Invalid city")?
-            .to_string();
+        // Download endpoint: the body must hold at least the requested size, LibreSpeed
+        // rounds up to whole megabytes
+        let download_url = server
+            .backend()
+            .download_url(server, ProviderCheck::PROBE_BYTES as u64);
+        rate_limiter.acquire(&server.provider).await;
+        let download_bytes = match client.get(&download_url).timeout(timeout).send().await {
+            Ok(resp) if resp.status().is_success() => resp.bytes().await.ok().map(|b| b.len()),
+            _ => None,
+        };
 
-        let latitude = json["lat"].as_f64().ok_or("Invalid latitude")?;
-        let longitude = json["lon"].as_f64().ok_or("Invalid longitude")?;
+        // Upload endpoint: a small POST must be acknowledged with a success status
+        let upload_ok = if server.capabilities.supports_upload {
+            rate_limiter.acquire(&server.provider).await;
+            Some(matches!(
+                server
+                    .backend()
+                    .upload_request(client, server)
+                    .body(vec![0u8; 64 * 1024])
+                    .timeout(timeout)
+                    .send()
+                    .await,
+                Ok(resp) if resp.status().is_success()
+            ))
+        } else {
+            None
+        };
 
-        if latitude == 0.0 && longitude == 0.0 {
-            return Err("Invalid coordinates".into());
+        ProviderCheck {
+            provider: server.provider.to_string(),
+            server_name: server.name.clone(),
+            server_url: server.url.clone(),
+            latency_ok: latency_ms.is_some(),
+            latency_ms,
+            download_ok: download_bytes.is_some_and(|bytes| bytes >= ProviderCheck::PROBE_BYTES),
+            download_bytes,
+            upload_ok,
         }
+    }
 
-        Ok(GeoLocation {
-            country,
-            city,
-            latitude,
-            longitude,
-            isp: json["isp"].as_str().map(String::from),
+    /// Detect user's geolocation using multiple services
+    async fn detect_location(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
+        Ok(match self.locate().await {
+            Some(geo) => geo,
+            None => self.default_location(),
         })
     }
 
-    async fn try_ipinfo_io(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .get("https://ipinfo.io/json")
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+    /// Ask the geolocation services in turn, None when none of them answers
+    async fn locate(&self) -> Option<GeoLocation> {
+        if !self.config.json_output {
+            println!("{}", "🌍 Detecting your location...".bright_cyan());
         }
 
-        let json: serde_json::Value = response.json().await?;
-
-        let country = json["country"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid country")?
-            .to_string();
-
-        let city = json["city"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid city")?
-            .to_string();
-
-        // ipinfo.io returns "lat,lon" in the "loc" field
-        let loc = json["loc"].as_str().ok_or("Invalid location")?;
-        let coords: Vec<&str> = loc.split(',').collect();
-        if coords.len() != 2 {
-            return Err("Invalid coordinates format".into());
+        let providers = geolocation::providers(&self.config.geolocation);
+        if std::env::var("NETRUNNER_DEBUG").is_ok() {
+            let names: Vec<&str> = providers.iter().map(|p| p.name()).collect();
+            eprintln!("[TRACE] geolocation providers: {}", names.join(", "));
         }
 
-        let latitude: f64 = coords[0].parse().map_err(|_| "Invalid latitude")?;
-        let longitude: f64 = coords[1].parse().map_err(|_| "Invalid longitude")?;
-
-        if latitude == 0.0 && longitude == 0.0 {
-            return Err("Invalid coordinates".into());
+        let per_provider = Duration::from_secs(self.config.geo_service_timeout_seconds);
+        let overall = Duration::from_secs(self.config.geo_timeout_seconds);
+        let (service, geo) =
+            geolocation::first_location(&providers, &self.client, per_provider, overall).await?;
+        if !self.config.json_output {
+            println!(
+                "{} {}, {} (via {})",
+                "📍 Location:".bright_green(),
+                geo.city,
+                geo.country,
+                service
+            );
+            if let Some(isp) = &geo.isp {
+                println!("{} {}", "🔌 ISP:".bright_blue(), isp);
+            }
         }
-
-        Ok(GeoLocation {
-            country,
-            city,
-            latitude,
-            longitude,
-            isp: json["org"].as_str().map(String::from),
-        })
+        Some(geo)
     }
 
-    async fn try_freegeoip_app(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .get("https://freegeoip.app/json/")
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await?;
+    /// Stand-in location (USA central) for when every geolocation service failed
+    fn default_location(&self) -> GeoLocation {
+        if !self.config.json_output {
+            let reason = if geolocation::providers(&self.config.geolocation).is_empty() {
+                "no geolocation services enabled"
+            } else {
+                "all geolocation services failed"
+            };
+            println!(
+                "{} Using default location (USA Central) - {}",
+                "⚠".bright_yellow(),
+                reason
+            );
+        }
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+        GeoLocation {
+            country: "United States".to_string(),
+            city: "Kansas City".to_string(),
+            latitude: 39.0997,
+            longitude: -94.5786,
+            isp: None,
         }
+    }
 
-        let json: serde_json::Value = response.json().await?;
+    /// Build a comprehensive server pool based on location, leaving out servers
+    /// whose test endpoints do not answer
+    async fn build_server_pool(
+        &self,
+        geo: &GeoLocation,
+        discovery: EarlyDiscovery,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.config.json_output {
+            println!("{}", "🔍 Building server pool...".bright_cyan());
+        }
 
-        let country = json["country_name"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid country")?
-            .to_string();
+        let candidates = match self.config.provider {
+            SpeedProvider::Fast => fast::servers(&self.client)
+                .await
+                .map_err(|e| format!("fast.com handed out no servers: {}", e))?,
+            _ => self.candidate_servers(geo, discovery.speedtest_list).await,
+        };
+        if candidates.is_empty() && !self.config.server_filter.is_empty() {
+            return Err("No servers left after the provider and host filters".into());
+        }
+        // Servers checked while the location was detected are not checked again
+        let health = futures::future::join_all(candidates.iter().map(|server| {
+            let checked = discovery
+                .health
+                .iter()
+                .find(|health| health.server_url == server.url)
+                .cloned();
+            async move {
+                match checked {
+                    Some(health) => health,
+                    None => {
+                        Self::check_server_health(&self.client, &self.rate_limiter, server).await
+                    }
+                }
+            }
+        }))
+        .await;
 
-        let city = json["city"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid city")?
-            .to_string();
+        let mut servers = Vec::new();
+        for (mut server, health) in candidates.into_iter().zip(&health) {
+            if !health.is_alive() {
+                if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                    eprintln!(
+                        "[TRACE] dropping {} ({}): test endpoints dead",
+                        server.name, server.url
+                    );
+                }
+                continue;
+            }
+            // Keep the server for whichever direction still works
+            server.capabilities.supports_download = health.download_ok == Some(true);
+            server.capabilities.supports_upload = health.upload_ok == Some(true);
+            servers.push(server);
+        }
 
-        let latitude = json["latitude"].as_f64().ok_or("Invalid latitude")?;
-        let longitude = json["longitude"].as_f64().ok_or("Invalid longitude")?;
+        let server_count = servers.len();
+        let dead = health.len() - server_count;
+        *self.server_pool.write().await = servers;
 
-        if latitude == 0.0 && longitude == 0.0 {
-            return Err("Invalid coordinates".into());
+        if !self.config.json_output {
+            if dead > 0 {
+                println!(
+                    "{} {} servers in pool ({} unresponsive skipped)",
+                    "✓".bright_green(),
+                    server_count,
+                    dead
+                );
+            } else {
+                println!("{} {} servers in pool", "✓".bright_green(), server_count);
+            }
         }
 
-        Ok(GeoLocation {
-            country,
-            city,
-            latitude,
-            longitude,
-            isp: None,
-        })
+        if server_count == 0 {
+            return Err("Every server in the pool failed its health check".into());
+        }
+        Ok(())
     }
 
-    async fn try_ipwhois_app(&self) -> Result<GeoLocation, Box<dyn std::error::Error>> {
-        let response = self
-            .client
-            .get("https://ipwho.is/")
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+    /// The last run's best server, when it may still be used and answers one quick
+    /// probe about as fast as when it was selected
+    async fn reusable_server(&self) -> Option<TestServer> {
+        if self.config.reselect_server {
+            return None;
         }
-
-        let json: serde_json::Value = response.json().await?;
-
-        if !json["success"].as_bool().unwrap_or(false) {
-            return Err(format!(
-                "API error: {}",
-                json["message"].as_str().unwrap_or("Unknown")
-            )
-            .into());
+        let cached = HistoryStorage::new().ok()?.get_best_server().ok()??;
+        if !self.cached_server_usable(&cached, Utc::now()) {
+            return None;
         }
 
-        let country = json["country"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid country")?
-            .to_string();
+        let latency = Self::probe_latency(&self.client, &self.rate_limiter, &cached.server).await;
+        let limit =
+            cached.latency_ms * CACHED_SERVER_LATENCY_FACTOR + CACHED_SERVER_LATENCY_SLACK_MS;
+        if std::env::var("NETRUNNER_DEBUG").is_ok() {
+            eprintln!(
+                "[TRACE] cached server {}: {:.1} ms when selected, now {:?} (limit {:.1} ms)",
+                cached.server.name, cached.latency_ms, latency, limit
+            );
+        }
 
-        let city = json["city"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or("Invalid city")?
-            .to_string();
+        let mut server = cached.server;
+        server.latency_ms = Some(latency.filter(|&ms| ms <= limit)?);
+        Some(server)
+    }
 
-        let latitude = json["latitude"].as_f64().ok_or("Invalid latitude")?;
-        let longitude = json["longitude"].as_f64().ok_or("Invalid longitude")?;
+    /// Whether a cached best server is recent and still allowed by the filters and
+    /// the server list of this run
+    fn cached_server_usable(&self, cached: &CachedServer, now: chrono::DateTime<Utc>) -> bool {
+        let fresh =
+            now - cached.selected_at <= chrono::Duration::hours(CACHED_SERVER_MAX_AGE_HOURS);
+        let listed = match &self.config.server_list {
+            Some(list) if cached.server.provider == ServerProvider::SelfHosted => {
+                list.servers.iter().any(|s| s.url == cached.server.url)
+            }
+            Some(list) => !list.replace,
+            None => cached.server.provider != ServerProvider::SelfHosted,
+        };
+        fresh && listed && self.config.server_filter.allows(&cached.server)
+    }
 
-        if latitude == 0.0 && longitude == 0.0 {
-            return Err("Invalid coordinates".into());
+    fn remember_best_server(&self, server: &TestServer) {
+        let Some(latency_ms) = server.latency_ms else {
+            return;
+        };
+        let cached = CachedServer {
+            server: server.clone(),
+            latency_ms,
+            selected_at: Utc::now(),
+        };
+        if let Err(e) = HistoryStorage::new().and_then(|storage| storage.save_best_server(&cached))
+        {
+            if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                eprintln!("[TRACE] could not cache best server: {}", e);
+            }
         }
+    }
 
-        Ok(GeoLocation {
-            country,
-            city,
-            latitude,
-            longitude,
-            isp: json["connection"]["isp"].as_str().map(String::from),
-        })
+    /// Build the health-checked pool and time every server in it, fastest first,
+    /// for the interactive server picker. Servers that never answered come last.
+    pub async fn discover_servers(&self) -> Result<Vec<TestServer>, Box<dyn std::error::Error>> {
+        let (geo, discovery) = tokio::join!(self.detect_location(), self.early_discovery());
+        let geo = geo?;
+        *self.geo_location.write().await = Some(geo.clone());
+        self.build_server_pool(&geo, discovery).await?;
+
+        let pool = self.server_pool.read().await.clone();
+        let mut servers = futures::future::join_all(pool.iter().map(|server| async move {
+            Self::quick_latency_test(&self.client, &self.rate_limiter, server)
+                .await
+                .unwrap_or_else(|_| server.clone())
+        }))
+        .await;
+
+        servers.sort_by(|a, b| {
+            a.latency_ms
+                .unwrap_or(f64::MAX)
+                .partial_cmp(&b.latency_ms.unwrap_or(f64::MAX))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Ok(servers)
     }
 
-    /// Build a comprehensive server pool based on location
-    async fn build_server_pool(&self, geo: &GeoLocation) -> Result<(), Box<dyn std::error::Error>> {
-        if !self.config.json_output {
-            println!("{}", "🔍 Building server pool...".bright_cyan());
+    /// Servers considered for the pool, nearest first, before any health check
+    async fn candidate_servers(
+        &self,
+        geo: &GeoLocation,
+        speedtest_list: Option<String>,
+    ) -> Vec<TestServer> {
+        let mut servers = Vec::new();
+        let server_list = self.config.server_list.as_ref();
+        if let Some(list) = server_list {
+            servers.extend(list.servers.iter().map(|s| self.self_hosted_server(geo, s)));
         }
 
-        let mut servers = Vec::new();
+        if !server_list.is_some_and(|list| list.replace) {
+            // Try dynamic server discovery first
+            servers.extend(self.discover_nearby_servers(geo, speedtest_list).await);
 
-        // Try dynamic server discovery first
-        servers.extend(self.discover_nearby_servers(geo).await);
+            // Add global CDN endpoints as fallback
+            servers.extend(self.get_global_cdn_servers());
+        }
 
-        // Add global CDN endpoints as fallback
-        servers.extend(self.get_global_cdn_servers());
+        let filter = &self.config.server_filter;
+        servers.retain(|server| {
+            let allowed = filter.allows(server);
+            if !allowed && std::env::var("NETRUNNER_DEBUG").is_ok() {
+                eprintln!(
+                    "[TRACE] filtering out {} ({}, {})",
+                    server.name, server.provider, server.url
+                );
+            }
+            allowed
+        });
 
         // Calculate distances for servers that don't have them
         for server in &mut servers {
@@ -561,15 +1525,81 @@ impl SpeedTest {
 
         // Keep only the best servers
         servers.truncate(20);
+        servers
+    }
 
-        let server_count = servers.len();
-        *self.server_pool.write().await = servers;
+    /// Check that a server's test endpoints answer with 200 OK. Download bodies are
+    /// dropped unread and uploads are empty, so the check costs next to no data.
+    async fn check_server_health(
+        client: &Client,
+        rate_limiter: &ProviderRateLimiter,
+        server: &TestServer,
+    ) -> ServerHealth {
+        let answers_ok = |request: reqwest::RequestBuilder| async move {
+            matches!(
+                request.timeout(HEALTH_CHECK_TIMEOUT).send().await,
+                Ok(response) if response.status() == reqwest::StatusCode::OK
+            )
+        };
 
-        if !self.config.json_output {
-            println!("{} {} servers in pool", "✓".bright_green(), server_count);
+        let download_ok = if server.capabilities.supports_download {
+            rate_limiter.acquire(&server.provider).await;
+            let url = server.backend().download_url(server, HEALTH_CHECK_BYTES);
+            Some(answers_ok(client.get(url)).await)
+        } else {
+            None
+        };
+        let upload_ok = if server.capabilities.supports_upload {
+            rate_limiter.acquire(&server.provider).await;
+            let request = server.backend().upload_request(client, server);
+            Some(answers_ok(request.body(Vec::new())).await)
+        } else {
+            None
+        };
+
+        ServerHealth {
+            server_name: server.name.clone(),
+            server_url: server.url.clone(),
+            provider: server.provider.to_string(),
+            download_ok,
+            upload_ok,
         }
+    }
 
-        Ok(())
+    fn self_hosted_server(&self, geo: &GeoLocation, server: &CustomServer) -> TestServer {
+        let distance_km = match (server.latitude, server.longitude) {
+            (Some(lat), Some(lon)) => {
+                Some(self.calculate_distance(geo.latitude, geo.longitude, lat, lon))
+            }
+            // Ranked on latency alone, and never pushed out of the pool by distance
+            _ => Some(0.0),
+        };
+        let location = server
+            .location
+            .clone()
+            .unwrap_or_else(|| "Self-hosted".to_string());
+
+        TestServer {
+            name: server.name.clone(),
+            url: server.url.clone(),
+            city: Some(location.split(", ").next().unwrap_or(&location).to_string()),
+            location,
+            distance_km,
+            latency_ms: None,
+            provider: ServerProvider::SelfHosted,
+            protocol: server.protocol,
+            capabilities: ServerCapabilities {
+                supports_download: server.download,
+                supports_upload: server.upload,
+                supports_latency: true,
+                max_test_size_mb: server.max_test_size_mb,
+                geographic_weight: 1.0,
+            },
+            quality_score: None,
+            country_code: server.country_code.clone(),
+            is_backup: false,
+            endpoints: server.endpoints.clone(),
+        }
     }
 
     fn get_global_cdn_servers(&self) -> Vec<TestServer> {
@@ -582,6 +1612,7 @@ impl SpeedTest {
                 distance_km: Some(5000.0), // Lower priority than regional servers
                 latency_ms: None,
                 provider: ServerProvider::Cloudflare,
+                protocol: ServerProtocol::Cloudflare,
                 capabilities: ServerCapabilities {
                     supports_download: true,
                     supports_upload: true,
@@ -593,6 +1624,7 @@ impl SpeedTest {
                 country_code: None,
                 city: None,
                 is_backup: true,
+                endpoints: None,
             },
             TestServer {
                 name: "Google Global".to_string(),
@@ -601,6 +1633,7 @@ impl SpeedTest {
                 distance_km: Some(5000.0),
                 latency_ms: None,
                 provider: ServerProvider::Google,
+                protocol: ServerProtocol::Cloudflare,
                 capabilities: ServerCapabilities {
                     supports_download: true,
                     supports_upload: false,
@@ -612,12 +1645,17 @@ impl SpeedTest {
                 country_code: None,
                 city: None,
                 is_backup: true,
+                endpoints: None,
             },
         ]
     }
 
     /// Dynamically discover nearby speed test servers based on user location
-    async fn discover_nearby_servers(&self, geo: &GeoLocation) -> Vec<TestServer> {
+    async fn discover_nearby_servers(
+        &self,
+        geo: &GeoLocation,
+        speedtest_list: Option<String>,
+    ) -> Vec<TestServer> {
         let mut servers = Vec::new();
 
         if !self.config.json_output {
@@ -628,7 +1666,7 @@ impl SpeedTest {
         }
 
         // Try to fetch speedtest.net server list
-        if let Ok(speedtest_servers) = self.fetch_speedtest_net_servers(geo).await {
+        if let Ok(speedtest_servers) = self.fetch_speedtest_net_servers(geo, speedtest_list).await {
             servers.extend(speedtest_servers);
         }
 
@@ -649,20 +1687,26 @@ impl SpeedTest {
         servers
     }
 
+    /// Speedtest.net's JSON list of servers. It is sorted by the caller's address,
+    /// so it can be fetched before the location is known.
+    async fn fetch_speedtest_net_list(&self) -> Option<String> {
+        let url = format!(
+            "https://www.speedtest.net/api/js/servers?engine=js&https_functional=true&limit={}",
+            SPEEDTEST_NET_SERVER_LIMIT
+        );
+        self.client.get(&url).send().await.ok()?.text().await.ok()
+    }
+
     /// Fetch real speedtest.net server list based on location
     async fn fetch_speedtest_net_servers(
         &self,
         geo: &GeoLocation,
+        speedtest_list: Option<String>,
     ) -> Result<Vec<TestServer>, Box<dyn std::error::Error>> {
-        // Speedtest.net uses a JSON API to get nearby servers
-        let url = "https://www.speedtest.net/api/js/servers?engine=js&limit=10";
-
-        if let Ok(response) = self.client.get(url).send().await {
-            if let Ok(text) = response.text().await {
-                // Parse the response and create TestServer objects
-                if let Ok(servers) = self.parse_speedtest_servers(&text, geo) {
-                    return Ok(servers);
-                }
+        // Parse the fetched list and create TestServer objects
+        if let Some(text) = speedtest_list {
+            if let Ok(servers) = self.parse_speedtest_servers(&text, geo) {
+                return Ok(servers);
             }
         }
 
@@ -675,53 +1719,65 @@ impl SpeedTest {
         json: &str,
         geo: &GeoLocation,
     ) -> Result<Vec<TestServer>, Box<dyn std::error::Error>> {
-        // Simple JSON parsing for speedtest.net format
-        // Format: [{"id":123,"host":"server.host.com","lat":40.7,"lon":-74.0,"name":"New York","country":"US","sponsor":"ISP Name"}]
-
-        let mut servers = Vec::new();
+        let entries: Vec<SpeedtestNetServer> = serde_json::from_str(json)?;
 
-        // Use serde_json to parse
-        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json) {
-            if let Some(array) = parsed.as_array() {
-                for server in array.iter().take(10) {
-                    if let (Some(host), Some(name), Some(country), Some(lat), Some(lon)) = (
-                        server.get("host").and_then(|v| v.as_str()),
-                        server.get("name").and_then(|v| v.as_str()),
-                        server.get("country").and_then(|v| v.as_str()),
-                        server.get("lat").and_then(|v| v.as_f64()),
-                        server.get("lon").and_then(|v| v.as_f64()),
-                    ) {
-                        let distance =
-                            self.calculate_distance(geo.latitude, geo.longitude, lat, lon);
-
-                        servers.push(TestServer {
-                            name: format!("{}, {}", name, country),
-                            url: format!("https://{}", host),
-                            location: format!("{}, {}", name, country),
-                            distance_km: Some(distance),
-                            latency_ms: None,
-                            provider: ServerProvider::Custom(
-                                host.split('.').next().unwrap_or("speedtest").to_string(),
-                            ),
-                            capabilities: ServerCapabilities {
-                                supports_download: true,
-                                supports_upload: true,
-                                supports_latency: true,
-                                max_test_size_mb: 1000,
-                                geographic_weight: 1.0,
-                            },
-                            quality_score: None,
-                            country_code: Some(country.to_string()),
-                            city: Some(name.to_string()),
-                            is_backup: false,
-                        });
-                    }
+        let mut servers: Vec<TestServer> = entries
+            .into_iter()
+            .map(|entry| {
+                // The API measures from the caller's address, which beats our own
+                // estimate when geolocation fell back to a default
+                let distance = entry.distance.unwrap_or_else(|| {
+                    self.calculate_distance(geo.latitude, geo.longitude, entry.lat, entry.lon)
+                });
+                let operator = entry
+                    .sponsor
+                    .filter(|sponsor| !sponsor.trim().is_empty())
+                    .unwrap_or_else(|| {
+                        entry
+                            .host
+                            .split('.')
+                            .next()
+                            .unwrap_or("speedtest")
+                            .to_string()
+                    });
+                let location = format!("{}, {}", entry.name, entry.country);
+
+                TestServer {
+                    name: format!("{} ({})", operator, location),
+                    url: format!("https://{}", entry.host),
+                    location,
+                    distance_km: Some(distance),
+                    latency_ms: None,
+                    provider: ServerProvider::Custom(operator),
+                    protocol: ServerProtocol::Ookla,
+                    capabilities: ServerCapabilities {
+                        supports_download: true,
+                        supports_upload: true,
+                        supports_latency: true,
+                        max_test_size_mb: 1000,
+                        geographic_weight: 1.0,
+                    },
+                    quality_score: None,
+                    country_code: Some(entry.cc.unwrap_or(entry.country)),
+                    city: Some(entry.name),
+                    is_backup: false,
+                    endpoints: None,
                 }
-            }
-        }
+            })
+            .filter(|server| {
+                server.distance_km.unwrap_or(f64::MAX) <= SPEEDTEST_NET_MAX_DISTANCE_KM
+            })
+            .collect();
+
+        servers.sort_by(|a, b| {
+            a.distance_km
+                .unwrap_or(f64::MAX)
+                .partial_cmp(&b.distance_km.unwrap_or(f64::MAX))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
         if servers.is_empty() {
-            Err("No servers parsed".into())
+            Err("No speedtest.net servers within range".into())
         } else {
             Ok(servers)
         }
@@ -815,6 +1871,7 @@ impl SpeedTest {
                 distance_km: Some(distance),
                 latency_ms: None,
                 provider: ServerProvider::Custom("LibreSpeed".to_string()),
+                protocol: ServerProtocol::Cloudflare,
                 capabilities: ServerCapabilities {
                     supports_download: true,
                     supports_upload: true,
@@ -826,6 +1883,7 @@ impl SpeedTest {
                 country_code: Some(location.split(", ").last().unwrap_or("").to_string()),
                 city: Some(location.split(", ").next().unwrap_or(location).to_string()),
                 is_backup: false,
+                endpoints: None,
             });
         }
 
@@ -1059,6 +2117,7 @@ impl SpeedTest {
             distance_km: Some(distance),
             latency_ms: None,
             provider: ServerProvider::Custom("LibreSpeed".to_string()),
+            protocol: ServerProtocol::Cloudflare,
             capabilities: ServerCapabilities {
                 supports_download: true,
                 supports_upload: true,
@@ -1070,6 +2129,7 @@ impl SpeedTest {
             country_code,
             city: Some(location.split(", ").next().unwrap_or(location).to_string()),
             is_backup: false,
+            endpoints: None,
         }
     }
 
@@ -1087,6 +2147,7 @@ impl SpeedTest {
             distance_km: None,
             latency_ms: None,
             provider: ServerProvider::Cloudflare,
+            protocol: ServerProtocol::Cloudflare,
             capabilities: ServerCapabilities {
                 supports_download: true,
                 supports_upload: true,
@@ -1098,6 +2159,7 @@ impl SpeedTest {
             country_code,
             city: Some(location.split(',').next().unwrap_or("").trim().to_string()),
             is_backup: false,
+            endpoints: None,
         }
     }
 
@@ -1151,7 +2213,10 @@ impl SpeedTest {
 
         for server in servers.into_iter().take(15) {
             let client = self.client.clone();
-            futures.push(async move { Self::quick_latency_test(&client, &server).await });
+            let rate_limiter = Arc::clone(&self.rate_limiter);
+            futures.push(
+                async move { Self::quick_latency_test(&client, &rate_limiter, &server).await },
+            );
         }
 
         while let Some(result) = futures.next().await {
@@ -1187,7 +2252,7 @@ impl SpeedTest {
 
         let selected = test_results
             .into_iter()
-            .take(SERVER_SELECTION_COUNT)
+            .take(self.profile.server_count)
             .collect::<Vec<_>>();
 
         if !self.config.json_output {
@@ -1212,23 +2277,15 @@ impl SpeedTest {
 
     async fn quick_latency_test(
         client: &Client,
+        rate_limiter: &ProviderRateLimiter,
         server: &TestServer,
     ) -> Result<TestServer, Box<dyn std::error::Error>> {
         let mut latencies = Vec::new();
         let mut server = server.clone();
 
         for _ in 0..3 {
-            let start = Instant::now();
-            match client
-                .head(&server.url)
-                .timeout(Duration::from_secs(2))
-                .send()
-                .await
-            {
-                Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
-                    latencies.push(start.elapsed().as_millis() as f64);
-                }
-                _ => {}
+            if let Some(latency) = Self::probe_latency(client, rate_limiter, &server).await {
+                latencies.push(latency);
             }
         }
 
@@ -1239,58 +2296,184 @@ impl SpeedTest {
         Ok(server)
     }
 
+    /// Time one HEAD request to the server, `None` when it fails or is refused
+    async fn probe_latency(
+        client: &Client,
+        rate_limiter: &ProviderRateLimiter,
+        server: &TestServer,
+    ) -> Option<f64> {
+        rate_limiter.acquire(&server.provider).await;
+        let start = Instant::now();
+        match server
+            .backend()
+            .latency_probe(client, server)
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
+                Some(start.elapsed().as_millis() as f64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Run a throughput phase, dropping the first remaining server each time the phase
+    /// fails on the servers left, and record every switch in `failovers`
+    async fn run_phase_with_failover(
+        &self,
+        phase: SpeedPhase,
+        servers: &[TestServer],
+        allowance: Option<u64>,
+        after: Duration,
+        failovers: &mut Vec<ServerFailover>,
+    ) -> Result<(PhaseOutcome, u64), Box<dyn std::error::Error>> {
+        let servers: Vec<TestServer> = servers
+            .iter()
+            .filter(|server| server.capabilities.supports(phase))
+            .cloned()
+            .collect();
+        let mut reason = format!(
+            "no selected server supports {}",
+            phase.to_string().to_lowercase()
+        );
+        let mut used = 0;
+        for (i, failed) in servers.iter().enumerate() {
+            let remaining = &servers[i..];
+            // Bytes a failed attempt moved still count against the cap
+            let limiter = Arc::new(TransferLimiter::new(
+                allowance.map(|allowance| allowance.saturating_sub(used)),
+                self.config.max_rate,
+            ));
+            let (outcome, bytes) = match phase {
+                SpeedPhase::Download => {
+                    self.progressive_download_test(remaining, limiter, after)
+                        .await?
+                }
+                SpeedPhase::Upload => {
+                    self.progressive_upload_test(remaining, limiter, after)
+                        .await?
+                }
+            };
+            used += bytes;
+            match outcome {
+                PhaseOutcome::Measured(_) => return Ok((outcome, used)),
+                PhaseOutcome::Failed(failure) => reason = failure,
+            }
+
+            let Some(fallback) = servers.get(i + 1) else {
+                break;
+            };
+            if !self.config.json_output {
+                println!(
+                    "{} {} failed on {}, retrying with {}",
+                    "⚠".bright_yellow(),
+                    phase,
+                    failed.name,
+                    fallback.name
+                );
+            }
+            failovers.push(ServerFailover {
+                phase,
+                failed_server: failed.name.clone(),
+                fallback_server: fallback.name.clone(),
+            });
+        }
+
+        if !self.config.json_output {
+            println!(
+                "{} {} failed on every selected server: {}",
+                "✗".bright_red(),
+                phase,
+                reason
+            );
+        }
+        Ok((PhaseOutcome::Failed(reason), used))
+    }
+
     /// Progressive download test - starts with rough estimate, refines over time
     async fn progressive_download_test(
         &self,
         servers: &[TestServer],
-    ) -> Result<f64, Box<dyn std::error::Error>> {
+        limiter: Arc<TransferLimiter>,
+        after: Duration,
+    ) -> Result<(PhaseOutcome, u64), Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_section_header("Testing Download Speed")?;
         }
+        self.show_phase_plan(SpeedPhase::Download, after);
 
         // Create bandwidth monitor (render at end)
         let bw_monitor = if !self.config.json_output && self.config.animation_enabled {
-            let monitor = self
-                .ui
-                .create_bandwidth_monitor("DOWNLOAD SPEED BANDWIDTH MONITOR", "Download");
+            let monitor = self.ui.create_bandwidth_monitor(
+                "DOWNLOAD SPEED BANDWIDTH MONITOR",
+                "Download",
+                ConnectionQuality::from_download_mbps,
+            );
             Some(monitor)
         } else {
             None
         };
 
-        let total_bytes = Arc::new(Mutex::new(0usize));
+        let connections = self.profile.download_connections;
+        let counters = Arc::new(ConnectionCounters::new(connections));
+        let health = Arc::new(PhaseHealth::default());
         let start = Instant::now();
-        let test_duration = Duration::from_secs(15);
+        let test_duration = self.profile.phase_duration;
 
         let mut handles = Vec::new();
 
-        // Start 50 parallel download connections
-        for i in 0..PARALLEL_CONNECTIONS {
+        // Start parallel download connections
+        for i in 0..connections {
             let server = &servers[i % servers.len()];
-            let url = format!("{}/__down?bytes=100000000", server.url); // 100MB chunks
+            let server = server.clone();
+            let provider = server.provider.clone();
             let client = self.client.clone();
-            let total_bytes = Arc::clone(&total_bytes);
+            let rate_limiter = Arc::clone(&self.rate_limiter);
+            let counters = Arc::clone(&counters);
+            let health = Arc::clone(&health);
+            let limiter = Arc::clone(&limiter);
+            let protocol = Arc::clone(&self.negotiated_protocol);
             let test_start = start;
 
             let handle = tokio::spawn(async move {
                 let end_time = test_start + test_duration;
 
-                while Instant::now() < end_time {
-                    match client.get(&url).send().await {
+                while Instant::now() < end_time && !health.is_stalled() && !limiter.is_exhausted() {
+                    rate_limiter.acquire(&provider).await;
+                    let bytes = limiter.request_bytes(DOWNLOAD_REQUEST_BYTES, connections);
+                    let url = server.backend().download_url(&server, bytes);
+                    match client
+                        .get(&url)
+                        .send()
+                        .await
+                        .and_then(|response| response.error_for_status())
+                    {
                         Ok(response) => {
+                            let _ = protocol.set(response.version());
                             let mut stream = response.bytes_stream();
 
                             while let Some(chunk_result) = stream.next().await {
-                                if Instant::now() >= end_time {
+                                if Instant::now() >= end_time || health.is_stalled() {
                                     break;
                                 }
-                                if let Ok(chunk) = chunk_result {
-                                    let mut total = total_bytes.lock().await;
-                                    *total += chunk.len();
+                                match chunk_result {
+                                    Ok(chunk) => {
+                                        counters.add(i, chunk.len());
+                                        limiter.pace(chunk.len()).await;
+                                        if limiter.reserve(chunk.len()) < chunk.len() {
+                                            break;
+                                        }
+                                    }
+                                    Err(e) => {
+                                        health.record_error(e);
+                                        break;
+                                    }
                                 }
                             }
                         }
-                        Err(_) => {
+                        Err(e) => {
+                            health.record_error(e);
                             tokio::time::sleep(Duration::from_millis(100)).await;
                         }
                     }
@@ -1305,19 +2488,45 @@ impl SpeedTest {
         }
 
         // Monitor progress and collect speed samples with live rendering
-        let total_bytes_monitor = Arc::clone(&total_bytes);
+        let monitor_counters = Arc::clone(&counters);
         let monitor_clone = bw_monitor.clone();
+        let monitor_health = Arc::clone(&health);
+        let monitor_limiter = Arc::clone(&limiter);
+        let progress_events = self.config.progress_events;
 
         let monitor_handle = tokio::spawn(async move {
             let mut last_bytes = 0;
             let mut last_time = Instant::now();
+            let mut last_progress = Instant::now();
             let end_time = start + test_duration;
             let mut first_render = true;
+            let mut last_event: Option<Instant> = None;
 
-            while Instant::now() < end_time {
+            while Instant::now() < end_time && !monitor_limiter.is_exhausted() {
                 tokio::time::sleep(Duration::from_millis(200)).await;
 
-                let bytes = *total_bytes_monitor.lock().await;
+                let bytes = monitor_counters.total();
+                if bytes > last_bytes {
+                    last_progress = Instant::now();
+                    monitor_health.record_progress();
+                } else if last_progress.elapsed() >= PHASE_STALL_TIMEOUT
+                    && monitor_health.is_failing()
+                {
+                    monitor_health.stall();
+                    break;
+                }
+                let countdown = PhaseCountdown::new(
+                    SpeedPhase::Download,
+                    test_duration,
+                    start.elapsed(),
+                    after,
+                );
+                if progress_events
+                    && last_event.is_none_or(|at| at.elapsed() >= Duration::from_secs(1))
+                {
+                    emit_progress(&countdown);
+                    last_event = Some(Instant::now());
+                }
                 let time_diff = last_time.elapsed().as_secs_f64();
 
                 if time_diff >= 0.2 {
@@ -1326,6 +2535,7 @@ impl SpeedTest {
 
                     if let Some(ref monitor) = monitor_clone {
                         monitor.update(speed).await;
+                        monitor.set_countdown(countdown).await;
 
                         // Render live update
                         if first_render {
@@ -1342,87 +2552,110 @@ impl SpeedTest {
             }
         });
 
-        // Wait for all tasks to complete
+        // Wait for all tasks to complete, a data cap can end them before the test duration
         for handle in handles {
             let _ = handle.await;
         }
+        let elapsed = start.elapsed().as_secs_f64();
         let _ = monitor_handle.await;
 
         // Calculate final speed
-        let elapsed = start.elapsed().as_secs_f64();
-        let total = *total_bytes.lock().await;
+        let total = counters.total();
 
-        let mbps = if total > 1_000_000 && elapsed > 1.0 {
-            let bits = total as f64 * 8.0;
-            bits / (elapsed * 1_000_000.0)
+        // No data or a stall mid-way means the phase failed on these servers
+        let outcome = if health.is_stalled() || total == 0 {
+            PhaseOutcome::Failed(health.failure_reason())
         } else {
-            1.0 // Minimum 1 Mbps if test failed
+            let mbps = total as f64 * 8.0 / (elapsed * 1_000_000.0);
+            PhaseOutcome::Measured(mbps.min(10_000.0))
         };
 
         // Mark as final and render one last time with checkmark
         if let Some(ref monitor) = bw_monitor {
-            monitor.update(mbps).await;
+            if let Some(mbps) = outcome.mbps() {
+                monitor.update(mbps).await;
+            }
             monitor.mark_final().await;
             let _ = monitor.render_live_update().await;
         }
 
-        Ok(mbps.clamp(1.0, 10_000.0))
+        Ok((outcome, total))
     }
 
     /// Progressive upload test
     async fn progressive_upload_test(
         &self,
         servers: &[TestServer],
-    ) -> Result<f64, Box<dyn std::error::Error>> {
+        limiter: Arc<TransferLimiter>,
+        after: Duration,
+    ) -> Result<(PhaseOutcome, u64), Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_section_header("Testing Upload Speed")?;
         }
+        self.show_phase_plan(SpeedPhase::Upload, after);
 
         // Create bandwidth monitor (render at end)
         let bw_monitor = if !self.config.json_output && self.config.animation_enabled {
-            let monitor = self
-                .ui
-                .create_bandwidth_monitor("UPLOAD SPEED BANDWIDTH MONITOR", "Upload");
+            let monitor = self.ui.create_bandwidth_monitor(
+                "UPLOAD SPEED BANDWIDTH MONITOR",
+                "Upload",
+                ConnectionQuality::from_upload_mbps,
+            );
             Some(monitor)
         } else {
             None
         };
 
-        let total_bytes = Arc::new(Mutex::new(0usize));
+        let connections = self.profile.upload_connections;
+        let counters = Arc::new(ConnectionCounters::new(connections));
+        let health = Arc::new(PhaseHealth::default());
         let start = Instant::now();
-        let test_duration = Duration::from_secs(15);
+        let test_duration = self.profile.phase_duration;
 
         // Use 5MB chunks for upload
         let chunk_size = 5 * 1024 * 1024;
-        let test_data = vec![0u8; chunk_size];
+        let test_data = Bytes::from(vec![0u8; chunk_size]);
 
         let mut handles = Vec::new();
 
-        // Start 10 parallel upload connections
-        for i in 0..10 {
-            let server = &servers[i % servers.len()];
-            let url = format!("{}/__up", server.url);
+        // Start parallel upload connections
+        for i in 0..connections {
+            let server = servers[i % servers.len()].clone();
+            let provider = server.provider.clone();
             let client = self.client.clone();
-            let total_bytes = Arc::clone(&total_bytes);
+            let rate_limiter = Arc::clone(&self.rate_limiter);
+            let counters = Arc::clone(&counters);
             let data = test_data.clone();
+            let health = Arc::clone(&health);
+            let limiter = Arc::clone(&limiter);
             let test_start = start;
 
             let handle = tokio::spawn(async move {
                 let end_time = test_start + test_duration;
 
-                while Instant::now() < end_time {
-                    match client
-                        .post(&url)
-                        .body(data.clone())
+                while Instant::now() < end_time && !health.is_stalled() && !limiter.is_exhausted() {
+                    rate_limiter.acquire(&provider).await;
+                    let size = limiter.request_bytes(data.len() as u64, connections);
+                    let body = reqwest::Body::wrap_stream(counted_pieces(
+                        data.slice(..(size as usize).min(data.len())),
+                        Arc::clone(&counters),
+                        i,
+                        Arc::clone(&limiter),
+                        end_time,
+                    ));
+                    match server
+                        .backend()
+                        .upload_request(&client, &server)
+                        .body(body)
                         .timeout(Duration::from_secs(10))
                         .send()
                         .await
+                        .and_then(|response| response.error_for_status())
                     {
-                        Ok(_) => {
-                            let mut total = total_bytes.lock().await;
-                            *total += data.len();
-                        }
-                        Err(_) => {
+                        // Bytes were counted while the body was sent
+                        Ok(_) => {}
+                        Err(e) => {
+                            health.record_error(e);
                             tokio::time::sleep(Duration::from_millis(100)).await;
                         }
                     }
@@ -1433,19 +2666,41 @@ impl SpeedTest {
         }
 
         // Monitor progress and collect speed samples with live rendering
-        let total_bytes_monitor = Arc::clone(&total_bytes);
+        let monitor_counters = Arc::clone(&counters);
         let monitor_clone = bw_monitor.clone();
+        let monitor_health = Arc::clone(&health);
+        let monitor_limiter = Arc::clone(&limiter);
+        let progress_events = self.config.progress_events;
 
         let monitor_handle = tokio::spawn(async move {
             let mut last_bytes = 0;
             let mut last_time = Instant::now();
+            let mut last_progress = Instant::now();
             let end_time = start + test_duration;
             let mut first_render = true;
+            let mut last_event: Option<Instant> = None;
 
-            while Instant::now() < end_time {
+            while Instant::now() < end_time && !monitor_limiter.is_exhausted() {
                 tokio::time::sleep(Duration::from_millis(200)).await;
 
-                let bytes = *total_bytes_monitor.lock().await;
+                let bytes = monitor_counters.total();
+                if bytes > last_bytes {
+                    last_progress = Instant::now();
+                    monitor_health.record_progress();
+                } else if last_progress.elapsed() >= PHASE_STALL_TIMEOUT
+                    && monitor_health.is_failing()
+                {
+                    monitor_health.stall();
+                    break;
+                }
+                let countdown =
+                    PhaseCountdown::new(SpeedPhase::Upload, test_duration, start.elapsed(), after);
+                if progress_events
+                    && last_event.is_none_or(|at| at.elapsed() >= Duration::from_secs(1))
+                {
+                    emit_progress(&countdown);
+                    last_event = Some(Instant::now());
+                }
                 let time_diff = last_time.elapsed().as_secs_f64();
 
                 if time_diff >= 0.2 {
@@ -1454,6 +2709,7 @@ impl SpeedTest {
 
                     if let Some(ref monitor) = monitor_clone {
                         monitor.update(speed).await;
+                        monitor.set_countdown(countdown).await;
 
                         // Render live update
                         if first_render {
@@ -1470,37 +2726,85 @@ impl SpeedTest {
             }
         });
 
-        // Wait for all tasks to complete
+        // Wait for all tasks to complete, a data cap can end them before the test duration
         for handle in handles {
             let _ = handle.await;
         }
+        let elapsed = start.elapsed().as_secs_f64();
         let _ = monitor_handle.await;
 
         // Calculate final speed
-        let elapsed = start.elapsed().as_secs_f64();
-        let total = *total_bytes.lock().await;
+        let total = counters.total();
 
-        let mbps = if total > 1_000_000 && elapsed > 1.0 {
-            let bits = total as f64 * 8.0;
-            bits / (elapsed * 1_000_000.0)
+        // No data or a stall mid-way means the phase failed on these servers
+        let outcome = if health.is_stalled() || total == 0 {
+            PhaseOutcome::Failed(health.failure_reason())
         } else {
-            1.0 // Minimum 1 Mbps if test failed
+            let mbps = total as f64 * 8.0 / (elapsed * 1_000_000.0);
+            PhaseOutcome::Measured(mbps.min(10_000.0))
         };
 
         // Mark as final and render one last time with checkmark
         if let Some(ref monitor) = bw_monitor {
-            monitor.update(mbps).await;
+            if let Some(mbps) = outcome.mbps() {
+                monitor.update(mbps).await;
+            }
             monitor.mark_final().await;
             let _ = monitor.render_live_update().await;
         }
 
-        Ok(mbps.clamp(1.0, 10_000.0))
+        Ok((outcome, total))
+    }
+
+    /// Without the live monitor, say once how long the phase runs and when the
+    /// result is due
+    fn show_phase_plan(&self, phase: SpeedPhase, after: Duration) {
+        if self.config.json_output || self.config.animation_enabled {
+            return;
+        }
+        let duration = self.profile.phase_duration;
+        let countdown = PhaseCountdown::new(phase, duration, Duration::ZERO, after);
+        println!(
+            "⏱ {} runs for {}s, result in about {:.0}s",
+            phase,
+            duration.as_secs(),
+            countdown.eta_seconds.ceil()
+        );
+    }
+
+    /// Handshake latency to the important endpoints from config.toml, probed side by side
+    async fn measure_endpoints(&self) -> Result<Vec<EndpointLatency>, Box<dyn std::error::Error>> {
+        if self.config.endpoints.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !self.config.json_output {
+            self.ui.show_section_header("Measuring Your Endpoints")?;
+        }
+
+        Ok(
+            futures::future::join_all(self.config.endpoints.iter().map(|endpoint| async move {
+                let address = tokio::net::lookup_host((endpoint.host.as_str(), endpoint.port))
+                    .await
+                    .ok()
+                    .and_then(|mut addresses| addresses.next());
+                EndpointLatency {
+                    name: endpoint.name.clone(),
+                    host: endpoint.host.clone(),
+                    latency: match address {
+                        Some(address) => Some(probe_link(address).await),
+                        None => None,
+                    },
+                }
+            }))
+            .await,
+        )
     }
 
+    /// Measure average latency, also returning the raw samples for the histogram
     async fn measure_latency(
         &self,
         server: &TestServer,
-    ) -> Result<f64, Box<dyn std::error::Error>> {
+    ) -> Result<(f64, Vec<f64>), Box<dyn std::error::Error>> {
         if !self.config.json_output {
             self.ui.show_section_header("Testing Latency")?;
         }
@@ -1511,31 +2815,25 @@ impl SpeedTest {
             None
         };
 
+        let probes = self.config.probes;
+        if std::env::var("NETRUNNER_DEBUG").is_ok() {
+            eprintln!("[TRACE] probe plan: {}", probes.plan(&server.host()));
+        }
+
         let mut latencies = Vec::new();
 
-        for _i in 0..10 {
-            let start = Instant::now();
-            match self
-                .client
-                .head(&server.url)
-                .timeout(Duration::from_secs(2))
-                .send()
-                .await
-            {
-                Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
-                    let latency = start.elapsed().as_millis() as f64;
-                    latencies.push(latency);
-
-                    // Update spinner with current average
-                    if let Some(pb) = &pb {
-                        let current_avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
-                        pb.set_message(format!("Latency: {:.1} ms", current_avg));
-                    }
+        for _i in 0..probes.latency_probes {
+            if let Some(latency) = self.probe(server, Duration::from_secs(2)).await {
+                latencies.push(latency);
+
+                // Update spinner with current average
+                if let Some(pb) = &pb {
+                    let current_avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+                    pb.set_message(format!("Latency: {:.1} ms", current_avg));
                 }
-                _ => {}
             }
 
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            tokio::time::sleep(probes.latency_spacing()).await;
         }
 
         let avg_latency = if !latencies.is_empty() {
@@ -1575,51 +2873,148 @@ impl SpeedTest {
             println!("✓ Latency: {} {}", latency_colored, explanation);
         }
 
-        Ok(avg_latency)
+        Ok((avg_latency, latencies))
     }
 
-    async fn measure_jitter_and_loss(
-        &self,
-        server: &TestServer,
-    ) -> Result<(f64, f64), Box<dyn std::error::Error>> {
-        let mut latencies = Vec::new();
-        let mut lost = 0;
-        let total = 20;
-
-        for _ in 0..total {
-            let start = Instant::now();
-            match self
-                .client
-                .head(&server.url)
-                .timeout(Duration::from_secs(1))
-                .send()
-                .await
-            {
-                Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
-                    latencies.push(start.elapsed().as_millis() as f64);
+    /// Time a WebSocket to the server, when its backend offers one, with as many
+    /// pings as the latency phase sent HEAD requests. None when it has none or the
+    /// socket failed, which leaves the HTTP latency as the only one.
+    async fn measure_websocket_latency(&self, server: &TestServer) -> Option<WebSocketLatency> {
+        let url = server.backend().websocket_url(server)?;
+        let probes = self.config.probes;
+        let spacing = probes
+            .latency_spacing()
+            .max(probes.min_interval().unwrap_or_default());
+
+        match websocket::measure_latency(
+            &url,
+            &self.resolver,
+            probes.latency_probes,
+            spacing,
+            Duration::from_secs(2),
+        )
+        .await
+        {
+            Ok(latency) => {
+                if !self.config.json_output {
+                    println!(
+                        "✓ WebSocket: {} {}",
+                        format!("{:.1} ms", latency.rtt_ms).bright_cyan(),
+                        format!("round trip, {:.1} ms to connect", latency.setup_ms)
+                            .bright_cyan()
+                            .dimmed()
+                    );
                 }
-                _ => {
-                    lost += 1;
+                Some(latency)
+            }
+            Err(e) => {
+                if std::env::var("NETRUNNER_DEBUG").is_ok() {
+                    eprintln!("[TRACE] WebSocket latency to {} failed: {}", url, e);
                 }
+                None
             }
-            tokio::time::sleep(Duration::from_millis(50)).await;
         }
+    }
 
-        let jitter = if latencies.len() > 1 {
-            let mean = latencies.iter().sum::<f64>() / latencies.len() as f64;
-            let variance =
-                latencies.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / latencies.len() as f64;
-            variance.sqrt()
-        } else {
-            0.0
-        };
-
+    /// Measure jitter and packet loss over a steady stream of probes, one sent every
+    /// jitter spacing whether or not the ones before were answered. Also returns the
+    /// raw latency samples and, when the server stamps its answers, the one-way delays.
+    async fn measure_jitter_and_loss(
+        &self,
+        server: &TestServer,
+    ) -> Result<(f64, f64, Vec<f64>, Option<OneWayDelay>), Box<dyn std::error::Error>> {
+        let probes = self.config.probes;
+        let total = probes.jitter_probes.max(1);
+        let start = tokio::time::Instant::now();
+
+        // In the order sent, which RFC 3550 computes jitter in
+        let stream = futures::future::join_all((0..total).map(|i| async move {
+            tokio::time::sleep_until(start + probes.jitter_spacing() * i).await;
+            self.probe_timed(server, Duration::from_secs(1)).await
+        }))
+        .await;
+        let answered: Vec<ProbeTiming> = stream.into_iter().flatten().collect();
+        let latencies: Vec<f64> = answered.iter().map(|probe| probe.rtt_ms).collect();
+
+        // A probe's round trip stands in for its transit time. The jitter only
+        // looks at how transits change, so the way back adds nothing steady.
+        let jitter = interarrival_jitter(&latencies);
+        let lost = total as usize - answered.len();
         let packet_loss = (lost as f64 / total as f64) * 100.0;
+        let one_way_delay = self.one_way_delay(&answered).await;
+
+        Ok((jitter, packet_loss, latencies, one_way_delay))
+    }
+
+    /// Split the round trip of probes the server stamped, after checking the
+    /// system clock against NTP. None below the detailed level, which sends no NTP
+    /// query, or when the server stamps nothing.
+    async fn one_way_delay(&self, probes: &[ProbeTiming]) -> Option<OneWayDelay> {
+        if self.config.detail_level < DetailLevel::Detailed {
+            return None;
+        }
+        let stamped: Vec<(f64, f64, f64)> = probes
+            .iter()
+            .filter_map(|probe| {
+                let stamp = probe.server_ms?;
+                Some((probe.sent_ms, stamp, probe.sent_ms + probe.rtt_ms))
+            })
+            .collect();
+        if stamped.is_empty() {
+            return None;
+        }
+        let clock = ntp::check_clock(Duration::from_secs(2)).await?;
+        let delay = OneWayDelay::from_probes(&stamped, &clock);
+        if std::env::var("NETRUNNER_DEBUG").is_ok() && delay.is_none() {
+            eprintln!(
+                "[TRACE] one-way delay skipped: clock known to ±{:.1} ms via {}",
+                clock.round_trip_ms / 2.0,
+                clock.server
+            );
+        }
+        delay
+    }
+
+    /// Time one latency probe to `server` in milliseconds, None when it failed.
+    /// Waits first if the server's probe limit calls for it.
+    async fn probe(&self, server: &TestServer, timeout: Duration) -> Option<f64> {
+        self.probe_timed(server, timeout)
+            .await
+            .map(|probe| probe.rtt_ms)
+    }
 
-        Ok((jitter, packet_loss))
+    async fn probe_timed(&self, server: &TestServer, timeout: Duration) -> Option<ProbeTiming> {
+        if let Some(interval) = self.config.probes.min_interval() {
+            self.probe_pacer.acquire(&server.host(), interval).await;
+        }
+        let sent_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+        let start = Instant::now();
+        match server
+            .backend()
+            .latency_probe(&self.client, server)
+            .timeout(timeout)
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() || resp.status().is_redirection() => {
+                Some(ProbeTiming {
+                    sent_ms,
+                    rtt_ms: start.elapsed().as_secs_f64() * 1000.0,
+                    server_ms: server.backend().server_clock(resp.headers()),
+                })
+            }
+            _ => None,
+        }
     }
 
     async fn get_client_ip(&self) -> Option<IpAddr> {
+        if let Some(ip) = self.client_ip.get() {
+            return Some(*ip);
+        }
         if let Ok(response) = self
             .client
             .get("https://api.ipify.org?format=json")
@@ -1628,97 +3023,600 @@ impl SpeedTest {
             .await
         {
             if let Ok(json) = response.json::<serde_json::Value>().await {
-                return json["ip"].as_str().and_then(|s| s.parse::<IpAddr>().ok());
+                let ip = json["ip"].as_str().and_then(|s| s.parse::<IpAddr>().ok())?;
+                return Some(*self.client_ip.get_or_init(|| ip));
             }
         }
         None
     }
 
+    /// The server's address as the test's connections looked it up, the first
+    /// one they try
     async fn resolve_server_ip(&self, url: &str) -> Option<IpAddr> {
-        if let Ok(parsed) = url.parse::<reqwest::Url>() {
-            if let Some(host) = parsed.host_str() {
-                if let Ok(addrs) = tokio::net::lookup_host(format!("{}:443", host)).await {
-                    return addrs.into_iter().next().map(|addr| addr.ip());
-                }
-            }
-        }
-        None
+        let parsed = url.parse::<reqwest::Url>().ok()?;
+        let ips = self.resolver.lookup(parsed.host_str()?).await.ok()?;
+        ips.first().copied()
     }
 
-    fn display_results(&self, result: &SpeedTestResult) -> std::io::Result<()> {
+    /// Debug section on how the test's TCP connections fared
+    fn display_tcp_health(&self, health: &TcpHealth) {
+        let theme = self.ui.theme();
         println!();
-        println!("{}", "═".repeat(60).bright_blue());
+        println!("{}", "TCP Health".color(theme.accent).bold());
         println!(
-            "{}",
-            "           SPEED TEST RESULTS           "
-                .bright_yellow()
-                .bold()
+            "{:20} {}",
+            "Connections:".color(theme.label).bold(),
+            format!("{} ({} samples)", health.connections, health.samples).color(theme.primary)
+        );
+        let retransmits = match health.retransmit_percent() {
+            Some(percent) => format!("{} ({:.2}% of segments)", health.retransmits, percent),
+            None => health.retransmits.to_string(),
+        };
+        println!(
+            "{:20} {}",
+            "Retransmits:".color(theme.label).bold(),
+            retransmits.color(if health.is_loss_limited() {
+                theme.error
+            } else {
+                theme.primary
+            })
+        );
+        println!(
+            "{:20} {}",
+            "RTT:".color(theme.label).bold(),
+            format!(
+                "{:.1} min / {:.1} median / {:.1} max ms",
+                health.rtt_min_ms, health.rtt_median_ms, health.rtt_max_ms
+            )
+            .color(theme.primary)
         );
-        println!("{}", "═".repeat(60).bright_blue());
-        println!();
-
         println!(
             "{:20} {}",
-            "Download:".bright_blue().bold(),
-            format!("{:.1} Mbps", result.download_mbps)
-                .bright_green()
+            "Congestion Window:".color(theme.label).bold(),
+            format!("{} median", DataSize(health.cwnd_median_bytes)).color(theme.primary)
+        );
+
+        if health.is_loss_limited() {
+            println!(
+                "{}",
+                "⚠ Loss is limiting throughput: TCP backs off after every lost segment, so the \
+                 line may be faster than measured"
+                    .color(theme.error)
+                    .bold()
+            );
+        }
+        if health.rtt_inflation() >= 3.0 && health.rtt_median_ms - health.rtt_min_ms >= 30.0 {
+            println!(
+                "{}",
+                format!(
+                    "⚠ Round trips grew {:.1}x under load, a sign of bufferbloat in the router or modem",
+                    health.rtt_inflation()
+                )
+                .color(theme.error)
                 .bold()
+            );
+        }
+    }
+
+    /// What the router's WAN carried while the test ran, and what that says
+    /// about where the speed was limited
+    fn display_wan_usage(&self, usage: &WanUsage, result: &SpeedTestResult) {
+        let theme = self.ui.theme();
+        println!();
+        println!("{}", "Router WAN (SNMP)".color(theme.accent).bold());
+        let port = match usage.port_speed_mbps {
+            Some(speed) => format!("{} ({} Mbps port)", usage.interface, speed),
+            None => usage.interface.clone(),
+        };
+        println!(
+            "{:20} {}",
+            "Interface:".color(theme.label).bold(),
+            port.color(theme.primary)
         );
 
+        let plan = self.config.plan.as_ref();
+        for (label, direction, rate, test_mbps, plan_mbps) in [
+            (
+                "WAN Download:",
+                "download",
+                usage.download,
+                result.download_mbps,
+                plan.map(|plan| plan.download_mbps),
+            ),
+            (
+                "WAN Upload:",
+                "upload",
+                usage.upload,
+                result.upload_mbps,
+                plan.map(|plan| plan.upload_mbps),
+            ),
+        ] {
+            let Some(rate) = rate else {
+                println!(
+                    "{:20} {}",
+                    label.color(theme.label).bold(),
+                    "not enough polls during the phase".color(theme.error)
+                );
+                continue;
+            };
+            println!(
+                "{:20} {}",
+                label.color(theme.label).bold(),
+                format!(
+                    "{:.1} Mbps average, {:.1} Mbps peak",
+                    rate.average_mbps, rate.peak_mbps
+                )
+                .color(theme.primary)
+            );
+            let verdict = match rate.verdict(test_mbps, plan_mbps, usage.port_speed_mbps) {
+                WanVerdict::LinkSaturated => format!(
+                    "→ The WAN ran at full rate during the {}: the ISP link is the limit",
+                    direction
+                ),
+                WanVerdict::SharedWithOtherTraffic => format!(
+                    "→ The WAN carried more than the test during the {}: other devices took a share",
+                    direction
+                ),
+                WanVerdict::LocalBottleneck => format!(
+                    "→ The WAN had room to spare during the {}: the limit is between this machine and the router, e.g. Wi-Fi",
+                    direction
+                ),
+                WanVerdict::Inconclusive => continue,
+            };
+            println!("{}", verdict.color(theme.value));
+        }
+    }
+
+    /// Show how the result compares with the median for the tester's ISP or country
+    async fn display_regional_comparison(
+        &self,
+        geo: &GeoLocation,
+        result: &SpeedTestResult,
+    ) -> std::io::Result<()> {
+        let (medians, source) = match &self.config.medians_url {
+            Some(url) => match regional::fetch_medians(&self.client, url).await {
+                Ok(medians) => (medians, url.as_str()),
+                Err(e) => {
+                    self.ui
+                        .show_error(&format!("Could not fetch medians from {}: {}", url, e))?;
+                    return Ok(());
+                }
+            },
+            None => (regional::builtin_medians(), "built-in national medians"),
+        };
+        let Some(comparison) =
+            regional::compare(&medians, &geo.country, geo.isp.as_deref(), result)
+        else {
+            return Ok(());
+        };
+
+        let theme = self.ui.theme();
+        let median = &comparison.median;
+        let region = match &median.isp {
+            Some(isp) => format!("{} in {}", isp, median.country),
+            None => median.country.clone(),
+        };
+        let percent = |value: f64| {
+            format!("{:+.0}%", value).color(if value < -25.0 {
+                theme.error
+            } else {
+                theme.value
+            })
+        };
+        println!();
         println!(
             "{:20} {}",
-            "Upload:".bright_blue().bold(),
-            format!("{:.1} Mbps", result.upload_mbps)
-                .bright_green()
+            "Regional Median:".color(theme.label).bold(),
+            format!(
+                "{:.0} / {:.0} Mbps for {}",
+                median.download_mbps, median.upload_mbps, region
+            )
+            .color(theme.primary)
+        );
+        println!(
+            "{:20} {} down / {} up {}",
+            "You vs Median:".color(theme.label).bold(),
+            percent(comparison.download_percent),
+            percent(comparison.upload_percent),
+            format!("({})", source).dimmed()
+        );
+        Ok(())
+    }
+
+    pub fn display_results(&self, result: &SpeedTestResult) -> std::io::Result<()> {
+        let theme = self.ui.theme();
+        let rule = self.ui.symbols().double_horizontal.repeat(60);
+
+        println!();
+        println!("{}", rule.color(theme.label));
+        println!(
+            "{}",
+            "           SPEED TEST RESULTS           "
+                .color(theme.highlight)
                 .bold()
         );
+        println!("{}", rule.color(theme.label));
+        println!();
+
+        for (label, phase, mbps) in [
+            ("Download:", SpeedPhase::Download, result.download_mbps),
+            ("Upload:", SpeedPhase::Upload, result.upload_mbps),
+        ] {
+            let speed = match result.failures.iter().find(|f| f.phase == phase) {
+                Some(failure) => format!("FAILED ({})", failure.reason).color(theme.error),
+                None => format!("{:.1} Mbps", mbps).color(theme.value),
+            };
+            println!("{:20} {}", label.color(theme.label).bold(), speed.bold());
+        }
 
         println!(
             "{:20} {}",
-            "Ping:".bright_blue().bold(),
-            format!("{:.1} ms", result.ping_ms).bright_cyan().bold()
+            "Ping:".color(theme.label).bold(),
+            format!("{:.1} ms", result.ping_ms)
+                .color(theme.primary)
+                .bold()
         );
 
+        // Methods differ in what they make of the same line, so the detailed view says which
+        let jitter = if self.config.detail_level >= DetailLevel::Detailed {
+            format!("{:.1} ms ({})", result.jitter_ms, result.jitter_method)
+        } else {
+            format!("{:.1} ms", result.jitter_ms)
+        };
         println!(
             "{:20} {}",
-            "Jitter:".bright_blue().bold(),
-            format!("{:.1} ms", result.jitter_ms).bright_cyan()
+            "Jitter:".color(theme.label).bold(),
+            jitter.color(theme.primary)
         );
 
+        if let Some(delay) = result
+            .one_way_delay
+            .as_ref()
+            .filter(|_| self.config.detail_level >= DetailLevel::Detailed)
+        {
+            // Only a difference larger than both errors together says anything
+            let asymmetry = delay.asymmetry_ms();
+            let slower = if asymmetry.abs() <= 2.0 * delay.uncertainty_ms {
+                String::new()
+            } else if asymmetry > 0.0 {
+                format!(", upstream {:.1} ms slower", asymmetry)
+            } else {
+                format!(", downstream {:.1} ms slower", -asymmetry)
+            };
+            println!(
+                "{:20} {} {}",
+                "One-way Delay:".color(theme.label).bold(),
+                format!(
+                    "{:.1} ms up, {:.1} ms down{}",
+                    delay.upstream_ms, delay.downstream_ms, slower
+                )
+                .color(theme.primary),
+                format!("(±{:.1} ms)", delay.uncertainty_ms).color(theme.label)
+            );
+        }
+
+        if let Some(websocket) = &result.websocket_latency {
+            println!(
+                "{:20} {} {}",
+                "WebSocket RTT:".color(theme.label).bold(),
+                format!("{:.1} ms", websocket.rtt_ms).color(theme.primary),
+                format!("(setup {:.1} ms)", websocket.setup_ms).color(theme.label)
+            );
+        }
+
         if result.packet_loss_percent > 0.0 {
             println!(
                 "{:20} {}",
-                "Packet Loss:".bright_blue().bold(),
-                format!("{:.1}%", result.packet_loss_percent).bright_red()
+                "Packet Loss:".color(theme.label).bold(),
+                format!("{:.1}%", result.packet_loss_percent).color(theme.error)
             );
         }
 
         println!(
             "{:20} {}",
-            "Server:".bright_blue().bold(),
-            result.server_location.bright_cyan()
+            "Server:".color(theme.label).bold(),
+            result.server_location.color(theme.primary)
         );
 
+        if let Some(data_used) = result.data_used {
+            println!(
+                "{:20} {}",
+                "Data Used:".color(theme.label).bold(),
+                format!(
+                    "{} ({} down, {} up)",
+                    DataSize(data_used.total()),
+                    DataSize(data_used.downloaded_bytes),
+                    DataSize(data_used.uploaded_bytes)
+                )
+                .color(theme.primary)
+            );
+        }
+
+        if let Some(link) = &result.link_stats {
+            let mut details = vec![link.interface.clone()];
+            if let Some(speed) = link.link_speed_mbps {
+                details.push(format!("{} Mbps", speed));
+            }
+            if let Some(duplex) = &link.duplex {
+                details.push(format!("{} duplex", duplex));
+            }
+            details.push(format!(
+                "{} errors/drops, {:.2}% retransmits",
+                link.error_count(),
+                link.retransmit_percent()
+            ));
+            println!(
+                "{:20} {}",
+                "Link:".color(theme.label).bold(),
+                details.join(", ").color(if link.has_problems() {
+                    theme.error
+                } else {
+                    theme.primary
+                })
+            );
+            if link.has_problems() {
+                println!(
+                    "{}",
+                    "⚠ Link problems, check the cable, switch port and duplex setting"
+                        .color(theme.error)
+                        .bold()
+                );
+            }
+        }
+
+        if let Some(other) = &result.other_traffic {
+            let rate = |mbps: Option<f64>| match mbps {
+                Some(mbps) => format!("{:.1} Mbps", mbps),
+                None => "?".to_string(),
+            };
+            let mut details = format!(
+                "{} down, {} up on the WAN",
+                rate(other.download_mbps),
+                rate(other.upload_mbps)
+            );
+            if let Some(devices) = other.lan_devices {
+                details.push_str(&format!(", {} devices on the LAN", devices));
+            }
+            let affected = other.affected(result);
+            println!(
+                "{:20} {}",
+                "Other Devices:".color(theme.label).bold(),
+                details.color(if affected { theme.error } else { theme.primary })
+            );
+            if affected {
+                println!(
+                    "{}",
+                    "⚠ Other devices used the connection during the test, the speed understates the line"
+                        .color(theme.error)
+                        .bold()
+                );
+            }
+        }
+
+        if let Some(dscp) = self.config.dscp {
+            println!(
+                "{:20} {}",
+                "DSCP:".color(theme.label).bold(),
+                format!("{} ({})", dscp, dscp.value()).color(theme.primary)
+            );
+        }
+
+        if let Some(transport) = result
+            .transport
+            .as_ref()
+            .filter(|_| self.config.detail_level >= DetailLevel::Detailed)
+        {
+            let details = match (&transport.alpn, &transport.tls_version) {
+                (Some(alpn), Some(tls)) => format!("{} over {}", alpn, tls),
+                (None, Some(tls)) => tls.clone(),
+                _ => "plain HTTP".to_string(),
+            };
+            println!(
+                "{:20} {}",
+                "Transport:".color(theme.label).bold(),
+                details.color(theme.primary)
+            );
+        }
+
+        for failover in &result.failovers {
+            println!(
+                "{:20} {}",
+                "Failover:".color(theme.label).bold(),
+                format!(
+                    "{} moved from {} to {}",
+                    failover.phase, failover.failed_server, failover.fallback_server
+                )
+                .color(theme.error)
+            );
+        }
+
         if let Some(isp) = &result.isp {
-            println!("{:20} {}", "ISP:".bright_blue().bold(), isp.bright_cyan());
+            println!(
+                "{:20} {}",
+                "ISP:".color(theme.label).bold(),
+                isp.color(theme.primary)
+            );
         }
 
         println!(
             "{:20} {}",
-            "Quality:".bright_blue().bold(),
-            format!("{}", result.quality).bright_yellow().bold()
+            "Quality:".color(theme.label).bold(),
+            format!("{}", result.quality)
+                .color(theme.quality_color(result.quality))
+                .bold()
         );
 
+        if let Some(plan) = &self.config.plan {
+            let attainment = plan.attainment(result);
+            let below_alert = plan.is_below_alert(&attainment);
+            println!(
+                "{:20} {}",
+                "Plan Attainment:".color(theme.label).bold(),
+                format!(
+                    "{:.0}% down / {:.0}% up of {}",
+                    attainment.download_percent, attainment.upload_percent, plan
+                )
+                .color(if below_alert {
+                    theme.error
+                } else {
+                    theme.value
+                })
+            );
+            if below_alert {
+                println!(
+                    "{}",
+                    format!(
+                        "⚠ Below {:.0}% of your advertised plan",
+                        plan.alert_below_percent
+                    )
+                    .color(theme.error)
+                    .bold()
+                );
+            }
+        }
+
         println!();
-        println!("{}", "═".repeat(60).bright_blue());
+        println!(
+            "{}",
+            "What can I do with this connection?"
+                .color(theme.accent)
+                .bold()
+        );
+        for verdict in result.use_case_scores().iter() {
+            let (mark, color) = if verdict.suitable {
+                (self.ui.symbols().check, theme.value)
+            } else {
+                (self.ui.symbols().cross, theme.error)
+            };
+            println!(
+                "  {} {:26} {}",
+                mark.color(color).bold(),
+                verdict.use_case.to_string().color(theme.label),
+                match &verdict.limited_by {
+                    Some(reason) => format!("{:>3}/100  {}", verdict.score, reason),
+                    None => format!("{:>3}/100", verdict.score),
+                }
+                .color(color)
+            );
+        }
+
+        if !result.endpoints.is_empty() {
+            println!();
+            println!("{}", "Your endpoints".color(theme.accent).bold());
+
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+            table.add_row(Row::new(vec![
+                Cell::new("Endpoint").style_spec("Fb"),
+                Cell::new("Address").style_spec("Fb"),
+                Cell::new("Median").style_spec("Fb"),
+                Cell::new("Jitter").style_spec("Fb"),
+                Cell::new("Loss").style_spec("Fb"),
+            ]));
+            for endpoint in &result.endpoints {
+                let row = match &endpoint.latency {
+                    Some(link) => vec![
+                        Cell::new(&endpoint.name),
+                        Cell::new(&format!(
+                            "{} ({}:{})",
+                            endpoint.host, link.address, link.port
+                        )),
+                        Cell::new(
+                            &link
+                                .median_ms
+                                .map_or("unreachable".to_string(), |ms| format!("{:.1}ms", ms)),
+                        ),
+                        Cell::new(&format!("{:.1}ms", link.jitter_ms)),
+                        Cell::new(&format!("{:.0}%", link.loss_percent())),
+                    ],
+                    None => vec![
+                        Cell::new(&endpoint.name),
+                        Cell::new(&endpoint.host),
+                        Cell::new("not resolved"),
+                        Cell::new("-"),
+                        Cell::new("-"),
+                    ],
+                };
+                table.add_row(Row::new(row));
+            }
+            print_table(&table)?;
+        }
+
+        if let Some(stats) = &result.latency_stats {
+            println!();
+            self.ui.show_latency_histogram(stats)?;
+        }
+
+        println!();
+        println!("{}", rule.color(theme.label));
 
         Ok(())
     }
 }
 
+/// ALPN identifier of an HTTP version
+fn alpn_name(version: reqwest::Version) -> Option<String> {
+    let name = match version {
+        reqwest::Version::HTTP_3 => "h3",
+        reqwest::Version::HTTP_2 => "h2",
+        reqwest::Version::HTTP_11 => "http/1.1",
+        reqwest::Version::HTTP_10 => "http/1.0",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Run `future` and report how long it took, for the phase timings
+async fn timed<T>(future: impl std::future::Future<Output = T>) -> (T, Duration) {
+    let started = Instant::now();
+    let output = future.await;
+    (output, started.elapsed())
+}
+
+/// Write a `progress` event for `--progress-events`, one JSON object per line
+fn emit_progress(countdown: &PhaseCountdown) {
+    if let Ok(line) = serde_json::to_string(countdown) {
+        eprintln!("{}", line);
+    }
+}
+
+/// Upload body split into pieces that are counted as the connection takes them, so samples
+/// rise smoothly and a request still running at `end_time` gets credit for what it sent.
+/// The body ends early at `end_time` or once the data cap is used up rather than sending
+/// bytes that would not be counted, and each piece waits its turn under the rate limit.
+fn counted_pieces(
+    data: Bytes,
+    counters: Arc<ConnectionCounters>,
+    connection: usize,
+    limiter: Arc<TransferLimiter>,
+    end_time: Instant,
+) -> impl Stream<Item = std::io::Result<Bytes>> {
+    let len = data.len();
+    let pieces = (0..len)
+        .step_by(UPLOAD_PIECE_SIZE)
+        .map(move |start| data.slice(start..(start + UPLOAD_PIECE_SIZE).min(len)));
+
+    futures::stream::iter(pieces)
+        .take_while(move |_| futures::future::ready(Instant::now() < end_time))
+        .then(move |piece| {
+            let limiter = Arc::clone(&limiter);
+            async move {
+                let granted = limiter.reserve(piece.len());
+                limiter.pace(granted).await;
+                piece.slice(..granted)
+            }
+        })
+        .take_while(|piece| futures::future::ready(!piece.is_empty()))
+        .map(move |piece| {
+            counters.add(connection, piece.len());
+            Ok(piece)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::modules::types::ServerList;
 
     #[test]
     fn test_region_determination() {
@@ -1734,4 +3632,315 @@ mod tests {
         assert_eq!(speed_test.determine_region("Germany"), "Europe");
         assert_eq!(speed_test.determine_region("Japan"), "Asia Pacific");
     }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(10, 1), Duration::from_secs(10));
+        assert_eq!(retry_delay(10, 2), Duration::from_secs(20));
+        assert_eq!(retry_delay(10, 4), Duration::from_secs(80));
+        assert_eq!(retry_delay(10, 40), Duration::from_secs(3600));
+        assert_eq!(retry_delay(0, 3), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_provider_check_functional() {
+        let mut check = ProviderCheck {
+            provider: "Google".to_string(),
+            server_name: "Google Global".to_string(),
+            server_url: "https://www.google.com".to_string(),
+            latency_ok: true,
+            latency_ms: Some(12.0),
+            download_ok: true,
+            download_bytes: Some(ProviderCheck::PROBE_BYTES),
+            upload_ok: None,
+        };
+        // Providers without upload support are judged on the checks they offer
+        assert!(check.is_functional());
+
+        check.upload_ok = Some(false);
+        assert!(!check.is_functional());
+
+        check.upload_ok = Some(true);
+        check.download_ok = false;
+        assert!(!check.is_functional());
+    }
+
+    #[test]
+    fn test_parse_speedtest_servers() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let speed_test = SpeedTest::new(TestConfig::default()).unwrap();
+        let geo = GeoLocation {
+            country: "Germany".to_string(),
+            city: "Berlin".to_string(),
+            latitude: 52.52,
+            longitude: 13.405,
+            isp: None,
+        };
+        let json = r#"[
+            {"url":"http://speed.example.net:8080/speedtest/upload.php","lat":"52.3676","lon":"4.9041",
+             "distance":577,"name":"Amsterdam","country":"Netherlands","cc":"NL",
+             "sponsor":"Example Fiber","id":"1234","host":"speed.example.net:8080"},
+            {"lat":"48.1351","lon":"11.5820","name":"Munich","country":"Germany",
+             "sponsor":"","host":"muc.speedtest.example.de:8080"},
+            {"lat":"40.7128","lon":"-74.0060","distance":6385,"name":"New York","country":"United States",
+             "cc":"US","sponsor":"Far Away ISP","host":"nyc.example.com:8080"}
+        ]"#;
+
+        let servers = speed_test.parse_speedtest_servers(json, &geo).unwrap();
+
+        // New York is past the distance cut-off, the rest come nearest first
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].city.as_deref(), Some("Munich"));
+        assert!((servers[0].distance_km.unwrap() - 504.0).abs() < 10.0);
+        assert_eq!(
+            servers[0].provider,
+            ServerProvider::Custom("muc".to_string())
+        );
+        assert_eq!(servers[0].country_code.as_deref(), Some("Germany"));
+
+        assert_eq!(servers[1].name, "Example Fiber (Amsterdam, Netherlands)");
+        assert_eq!(servers[1].url, "https://speed.example.net:8080");
+        assert_eq!(servers[1].distance_km, Some(577.0));
+        assert_eq!(servers[1].country_code.as_deref(), Some("NL"));
+
+        assert!(speed_test.parse_speedtest_servers("[]", &geo).is_err());
+        assert!(speed_test
+            .parse_speedtest_servers(r#"[{"host":"x","lat":"north"}]"#, &geo)
+            .is_err());
+    }
+
+    #[test]
+    fn test_cached_server_usable() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let now = Utc::now();
+        let cached = CachedServer {
+            server: TestServer {
+                name: "Cloudflare".to_string(),
+                url: "https://speed.cloudflare.com".to_string(),
+                location: "Global".to_string(),
+                distance_km: Some(0.0),
+                latency_ms: Some(15.0),
+                provider: ServerProvider::Cloudflare,
+                protocol: ServerProtocol::Cloudflare,
+                capabilities: ServerCapabilities {
+                    supports_download: true,
+                    supports_upload: true,
+                    supports_latency: true,
+                    max_test_size_mb: 1000,
+                    geographic_weight: 1.0,
+                },
+                quality_score: None,
+                country_code: None,
+                city: None,
+                is_backup: false,
+                endpoints: None,
+            },
+            latency_ms: 15.0,
+            selected_at: now - chrono::Duration::hours(2),
+        };
+
+        let speed_test = SpeedTest::new(TestConfig::default()).unwrap();
+        assert!(speed_test.cached_server_usable(&cached, now));
+        // A day old selection is made again
+        assert!(!speed_test.cached_server_usable(&cached, now + chrono::Duration::hours(23)));
+
+        let mut config = TestConfig::default();
+        config.server_filter.exclude_providers = vec![ServerProvider::Cloudflare];
+        let speed_test = SpeedTest::new(config).unwrap();
+        assert!(!speed_test.cached_server_usable(&cached, now));
+
+        // A replacing server list only lets its own servers be reused
+        let config = TestConfig {
+            server_list: Some(
+                ServerList::parse(
+                    "replace = true\n[[servers]]\nname = \"HQ\"\nurl = \"https://speed.corp.example\"\n",
+                )
+                .unwrap(),
+            ),
+            ..TestConfig::default()
+        };
+        let speed_test = SpeedTest::new(config).unwrap();
+        assert!(!speed_test.cached_server_usable(&cached, now));
+        let mut self_hosted = cached.clone();
+        self_hosted.server.provider = ServerProvider::SelfHosted;
+        self_hosted.server.url = "https://speed.corp.example".to_string();
+        assert!(speed_test.cached_server_usable(&self_hosted, now));
+        self_hosted.server.url = "https://old.corp.example".to_string();
+        assert!(!speed_test.cached_server_usable(&self_hosted, now));
+    }
+
+    /// Plain HTTP server answering 200 OK on paths starting with one of `ok`, 404 otherwise
+    async fn serve_paths(ok: &'static [&'static str]) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    match stream.read_u8().await {
+                        Ok(byte) => head.push(byte),
+                        Err(_) => break,
+                    }
+                }
+                let head = String::from_utf8_lossy(&head);
+                let path = head.split(' ').nth(1).unwrap_or_default();
+                let status = if ok.iter().any(|prefix| path.starts_with(prefix)) {
+                    "200 OK"
+                } else {
+                    "404 Not Found"
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_server_health_check() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let client = Client::new();
+        let rate_limiter = ProviderRateLimiter::default();
+        let mut server = TestServer {
+            name: "Local".to_string(),
+            url: serve_paths(&["/__down"]).await,
+            location: "Loopback".to_string(),
+            distance_km: None,
+            latency_ms: None,
+            provider: ServerProvider::SelfHosted,
+            protocol: ServerProtocol::Cloudflare,
+            capabilities: ServerCapabilities {
+                supports_download: true,
+                supports_upload: true,
+                supports_latency: true,
+                max_test_size_mb: 100,
+                geographic_weight: 1.0,
+            },
+            quality_score: None,
+            country_code: None,
+            city: None,
+            is_backup: false,
+            endpoints: None,
+        };
+
+        // A broken upload endpoint leaves the server usable for downloads
+        let health = SpeedTest::check_server_health(&client, &rate_limiter, &server).await;
+        assert_eq!(health.download_ok, Some(true));
+        assert_eq!(health.upload_ok, Some(false));
+        assert!(health.is_alive());
+
+        // The same host does not speak LibreSpeed
+        server.protocol = ServerProtocol::LibreSpeed;
+        let health = SpeedTest::check_server_health(&client, &rate_limiter, &server).await;
+        assert!(!health.is_alive());
+
+        server.capabilities.supports_upload = false;
+        server.protocol = ServerProtocol::Cloudflare;
+        let health = SpeedTest::check_server_health(&client, &rate_limiter, &server).await;
+        assert_eq!(health.upload_ok, None);
+        assert!(health.is_alive());
+    }
+
+    #[tokio::test]
+    async fn test_counted_pieces() {
+        let data = Bytes::from(vec![0u8; UPLOAD_PIECE_SIZE * 2 + 100]);
+        let counters = Arc::new(ConnectionCounters::new(2));
+        let unlimited = Arc::new(TransferLimiter::new(None, None));
+
+        let pieces: Vec<Bytes> = counted_pieces(
+            data.clone(),
+            Arc::clone(&counters),
+            1,
+            Arc::clone(&unlimited),
+            Instant::now() + Duration::from_secs(60),
+        )
+        .map(Result::unwrap)
+        .collect()
+        .await;
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[2].len(), 100);
+        assert_eq!(counters.total(), data.len() as u64);
+
+        // Nothing is sent or counted once the phase is over
+        let late = counted_pieces(
+            data.clone(),
+            Arc::clone(&counters),
+            0,
+            unlimited,
+            Instant::now(),
+        )
+        .count()
+        .await;
+        assert_eq!(late, 0);
+        assert_eq!(counters.total(), (UPLOAD_PIECE_SIZE * 2 + 100) as u64);
+
+        // A data cap cuts the body short, mid-piece if need be
+        let capped = Arc::new(TransferLimiter::new(
+            Some(UPLOAD_PIECE_SIZE as u64 + 10),
+            None,
+        ));
+        let sent: usize = counted_pieces(
+            data,
+            Arc::new(ConnectionCounters::new(1)),
+            0,
+            Arc::clone(&capped),
+            Instant::now() + Duration::from_secs(60),
+        )
+        .map(|piece| piece.unwrap().len())
+        .fold(0, |sum, len| async move { sum + len })
+        .await;
+        assert_eq!(sent, UPLOAD_PIECE_SIZE + 10);
+        assert!(capped.is_exhausted());
+    }
+
+    #[test]
+    fn test_transfer_limiter_allowance() {
+        let limiter = TransferLimiter::new(Some(10_000_000), None);
+        assert_eq!(limiter.request_bytes(DOWNLOAD_REQUEST_BYTES, 50), 200_000);
+        assert_eq!(limiter.reserve(9_000_000), 9_000_000);
+        // Requests never shrink below the floor, the allowance still stops the phase
+        assert_eq!(
+            limiter.request_bytes(DOWNLOAD_REQUEST_BYTES, 50),
+            MIN_REQUEST_BYTES
+        );
+        assert_eq!(limiter.reserve(2_000_000), 1_000_000);
+        assert!(limiter.is_exhausted());
+        assert_eq!(limiter.reserve(10), 0);
+
+        let unlimited = TransferLimiter::new(None, None);
+        assert_eq!(unlimited.reserve(usize::MAX), usize::MAX);
+        assert!(!unlimited.is_exhausted());
+        assert_eq!(
+            unlimited.request_bytes(DOWNLOAD_REQUEST_BYTES, 50),
+            DOWNLOAD_REQUEST_BYTES
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_limiter_rate() {
+        // 8 Mbps is 1 MB/s: 5 s worth of data shared by 10 connections
+        let limiter = TransferLimiter::new(None, Some(BitRate(8_000_000)));
+        assert_eq!(limiter.request_bytes(5 * 1024 * 1024, 10), 500_000);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.pace(100_000).await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(290));
+    }
+
+    #[test]
+    fn test_connection_counters() {
+        let counters = ConnectionCounters::new(3);
+        counters.add(0, 100);
+        counters.add(2, 50);
+        counters.add(0, 25);
+        assert_eq!(counters.total(), 175);
+        assert_eq!(std::mem::align_of::<PaddedCounter>(), 64);
+    }
 }
@@ -2,15 +2,11 @@
 //!
 //! Renders an interactive full-screen TUI with:
 //! - Pie charts for download/upload/ping distribution breakdowns
-//! - Summary statistics panel
+//! - Summary statistics panel, with plan attainment when a plan is configured
 //! - Recent results table
 //! - Keyboard navigation
 
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Layout, Rect},
@@ -24,21 +20,22 @@ use std::time::Duration;
 use tui_piechart::{symbols, LegendAlignment, LegendLayout, LegendPosition, PieChart, PieSlice};
 
 use crate::modules::{
-    history::{HistoryStorage, TestStatistics},
-    types::SpeedTestResult,
+    history::{HistoryStorage, PlanAttainmentStats, TestStatistics},
+    terminal::run_fullscreen,
+    types::{AdvertisedPlan, SpeedTestResult},
 };
 
 // ── Cyberpunk colour palette ─────────────────────────────────────────────────
-const COLOR_CYAN: Color = Color::Rgb(0, 255, 255);
-const COLOR_MAGENTA: Color = Color::Rgb(255, 0, 255);
-const COLOR_GREEN: Color = Color::Rgb(0, 255, 128);
-const COLOR_YELLOW: Color = Color::Rgb(255, 220, 0);
-const COLOR_ORANGE: Color = Color::Rgb(255, 140, 0);
-const COLOR_RED: Color = Color::Rgb(255, 60, 60);
-const COLOR_BLUE: Color = Color::Rgb(60, 140, 255);
+pub(crate) const COLOR_CYAN: Color = Color::Rgb(0, 255, 255);
+pub(crate) const COLOR_MAGENTA: Color = Color::Rgb(255, 0, 255);
+pub(crate) const COLOR_GREEN: Color = Color::Rgb(0, 255, 128);
+pub(crate) const COLOR_YELLOW: Color = Color::Rgb(255, 220, 0);
+pub(crate) const COLOR_ORANGE: Color = Color::Rgb(255, 140, 0);
+pub(crate) const COLOR_RED: Color = Color::Rgb(255, 60, 60);
+pub(crate) const COLOR_BLUE: Color = Color::Rgb(60, 140, 255);
 
-const COLOR_DIM: Color = Color::Rgb(80, 80, 100);
-const COLOR_PANEL_BG: Color = Color::Rgb(10, 10, 20);
+pub(crate) const COLOR_DIM: Color = Color::Rgb(80, 80, 100);
+pub(crate) const COLOR_PANEL_BG: Color = Color::Rgb(10, 10, 20);
 
 /// Which chart panel is currently focused
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -73,15 +70,21 @@ impl Focus {
 struct StatsApp {
     stats: TestStatistics,
     recent_results: Vec<SpeedTestResult>,
+    plan_attainment: Option<(AdvertisedPlan, PlanAttainmentStats)>,
     focus: Focus,
     scroll: usize,
 }
 
 impl StatsApp {
-    fn new(stats: TestStatistics, recent_results: Vec<SpeedTestResult>) -> Self {
+    fn new(
+        stats: TestStatistics,
+        recent_results: Vec<SpeedTestResult>,
+        plan_attainment: Option<(AdvertisedPlan, PlanAttainmentStats)>,
+    ) -> Self {
         Self {
             stats,
             recent_results,
+            plan_attainment,
             focus: Focus::Download,
             scroll: 0,
         }
@@ -115,8 +118,9 @@ impl StatsApp {
 ///
 /// Loads data from [`HistoryStorage`], then enters an alternate-screen TUI loop.
 /// Returns immediately with an error message printed if no history is found.
-pub fn show_statistics_tui() -> io::Result<()> {
-    let (stats, recent) = match load_data() {
+/// When `plan` is given the summary also shows how results compare to it.
+pub fn show_statistics_tui(plan: Option<&AdvertisedPlan>) -> io::Result<()> {
+    let (stats, recent, plan_attainment) = match load_data(plan) {
         Ok(data) => data,
         Err(e) => {
             eprintln!("Could not load history: {e}");
@@ -129,26 +133,25 @@ pub fn show_statistics_tui() -> io::Result<()> {
         return Ok(());
     }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let mut app = StatsApp::new(stats, recent);
-    let result = run_stats_loop(&mut terminal, &mut app);
-
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-
-    result
+    let mut app = StatsApp::new(stats, recent, plan_attainment);
+    run_fullscreen(|terminal| run_stats_loop(terminal, &mut app))
 }
 
-fn load_data() -> Result<(TestStatistics, Vec<SpeedTestResult>), Box<dyn std::error::Error>> {
+type StatsData = (
+    TestStatistics,
+    Vec<SpeedTestResult>,
+    Option<(AdvertisedPlan, PlanAttainmentStats)>,
+);
+
+fn load_data(plan: Option<&AdvertisedPlan>) -> Result<StatsData, Box<dyn std::error::Error>> {
     let storage = HistoryStorage::new()?;
     let stats = storage.get_statistics()?;
     let recent = storage.get_recent_results(20)?;
-    Ok((stats, recent))
+    let plan_attainment = match plan {
+        Some(plan) => Some((*plan, storage.get_plan_attainment(plan)?)),
+        None => None,
+    };
+    Ok((stats, recent, plan_attainment))
 }
 
 // ── Event loop ────────────────────────────────────────────────────────────────
@@ -542,7 +545,7 @@ fn render_summary(frame: &mut ratatui::Frame, area: Rect, app: &StatsApp) {
         format!("{} tests", s.test_count)
     };
 
-    let lines: Vec<Line<'static>> = vec![
+    let mut lines: Vec<Line<'static>> = vec![
         Line::from(vec![label("Tests : "), value(test_count_str)]),
         Line::from(vec![
             label("First : "),
@@ -584,6 +587,30 @@ fn render_summary(frame: &mut ratatui::Frame, area: Rect, app: &StatsApp) {
         ]),
     ];
 
+    if let Some((plan, attainment)) = &app.plan_attainment {
+        let below_style = if attainment.below_alert_count > 0 {
+            Style::default().fg(COLOR_RED)
+        } else {
+            Style::default().fg(COLOR_GREEN)
+        };
+        lines.push(Line::from(Span::raw("")));
+        lines.push(Line::from(vec![
+            label("Plan ↓ "),
+            value(format!("{:.0}%", attainment.avg_download_percent)),
+            sep(),
+            label("↑ "),
+            value(format!("{:.0}%", attainment.avg_upload_percent)),
+            sep(),
+            Span::styled(
+                format!(
+                    "{} below {:.0}%",
+                    attainment.below_alert_count, plan.alert_below_percent
+                ),
+                below_style,
+            ),
+        ]));
+    }
+
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, inner);
 }
@@ -731,7 +758,7 @@ fn build_speed_slices(ultra: u32, fast: u32, moderate: u32, slow: u32) -> Vec<Pi
 }
 
 /// Pick a colour that reflects connection quality.
-fn quality_color(quality: &crate::modules::types::ConnectionQuality) -> Color {
+pub(crate) fn quality_color(quality: &crate::modules::types::ConnectionQuality) -> Color {
     use crate::modules::types::ConnectionQuality;
     match quality {
         ConnectionQuality::Excellent => COLOR_GREEN,
@@ -0,0 +1,253 @@
+//! Anonymous Telemetry
+//!
+//! Strictly opt-in: nothing is sent until `netrunner_cli telemetry on`, and
+//! `telemetry off` stops it again. The choice is kept in `telemetry.json` next to
+//! the history database, and `DO_NOT_TRACK=1` overrides it.
+//!
+//! Once on, every completed speed test is sent to a community endpoint for a
+//! public dataset of connection speeds:
+//!
+//! ```json
+//! {
+//!   "format_version": 1,
+//!   "netrunner_version": "0.7.3",
+//!   "os": "linux",
+//!   "hour": "2025-03-01T19:00:00Z",
+//!   "country": "Germany",
+//!   "download_mbps": 512.3,
+//!   "upload_mbps": 48.7,
+//!   "ping_ms": 12.4,
+//!   "jitter_ms": 1.8,
+//!   "packet_loss_percent": 0.0,
+//!   "quality": "Excellent"
+//! }
+//! ```
+//!
+//! No IP addresses, ISP, server, host label, site or identifier of any kind are
+//! sent, and the time is rounded down to the hour, so samples can't be linked to
+//! each other or to a machine. Failed tests are not sent. `telemetry_url` in
+//! `config.toml` points the samples at another collector.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::modules::history::HistoryStorage;
+use crate::modules::types::{ConnectionQuality, SpeedTestResult};
+
+/// Collector samples go to unless `telemetry_url` is set
+pub const COMMUNITY_ENDPOINT: &str = "https://telemetry.netrunner-cli.dev/v1/results";
+
+/// Version of the sample layout
+pub const TELEMETRY_FORMAT_VERSION: u32 = 1;
+
+const CONSENT_FILE: &str = "telemetry.json";
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The user's last answer, absent until telemetry is first switched on or off
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TelemetryConsent {
+    pub enabled: bool,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// What is sent for one speed test, see the module docs
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TelemetrySample {
+    pub format_version: u32,
+    pub netrunner_version: String,
+    pub os: String,
+    pub hour: DateTime<Utc>,
+    pub country: Option<String>,
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+    pub ping_ms: f64,
+    pub jitter_ms: f64,
+    pub packet_loss_percent: f64,
+    pub quality: ConnectionQuality,
+}
+
+impl TelemetrySample {
+    /// The anonymous part of `result`, None for failed tests
+    pub fn from_result(result: &SpeedTestResult) -> Option<Self> {
+        if result.quality == ConnectionQuality::Failed || !result.failures.is_empty() {
+            return None;
+        }
+
+        let round = |value: f64| (value * 10.0).round() / 10.0;
+        Some(Self {
+            format_version: TELEMETRY_FORMAT_VERSION,
+            netrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            hour: result
+                .timestamp
+                .duration_trunc(TimeDelta::hours(1))
+                .unwrap_or(result.timestamp),
+            country: result.country.clone(),
+            download_mbps: round(result.download_mbps),
+            upload_mbps: round(result.upload_mbps),
+            ping_ms: round(result.ping_ms),
+            jitter_ms: round(result.jitter_ms),
+            packet_loss_percent: round(result.packet_loss_percent),
+            quality: result.quality,
+        })
+    }
+}
+
+fn consent_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(HistoryStorage::data_dir()?.join(CONSENT_FILE))
+}
+
+/// The stored answer, None when the user never switched telemetry on or off
+pub fn load_consent() -> Result<Option<TelemetryConsent>, Box<dyn std::error::Error>> {
+    load_consent_from(&consent_path()?)
+}
+
+fn load_consent_from(path: &Path) -> Result<Option<TelemetryConsent>, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(path)?;
+    Ok(Some(serde_json::from_str(&json).map_err(|e| {
+        format!("Invalid telemetry setting {}: {}", path.display(), e)
+    })?))
+}
+
+/// Switch telemetry on or off
+pub fn set_enabled(enabled: bool) -> Result<TelemetryConsent, Box<dyn std::error::Error>> {
+    save_consent_to(&consent_path()?, enabled)
+}
+
+fn save_consent_to(
+    path: &Path,
+    enabled: bool,
+) -> Result<TelemetryConsent, Box<dyn std::error::Error>> {
+    let consent = TelemetryConsent {
+        enabled,
+        changed_at: Utc::now(),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&consent)?)?;
+    Ok(consent)
+}
+
+/// Whether `DO_NOT_TRACK` asks for no telemetry, whatever was chosen before
+pub fn do_not_track(var: impl Fn(&str) -> Option<String>) -> bool {
+    var("DO_NOT_TRACK").is_some_and(|value| {
+        let value = value.trim();
+        !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false")
+    })
+}
+
+/// Where samples go, None unless telemetry is switched on. A setting that can't be
+/// read counts as off.
+pub fn endpoint(configured: Option<&str>) -> Option<String> {
+    if do_not_track(|key| std::env::var(key).ok()) {
+        return None;
+    }
+    let consent = load_consent().ok()??;
+    consent
+        .enabled
+        .then(|| configured.unwrap_or(COMMUNITY_ENDPOINT).to_string())
+}
+
+/// Send the anonymous sample of `result`; failed tests are skipped
+pub async fn submit(url: &str, result: &SpeedTestResult) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(sample) = TelemetrySample::from_result(result) else {
+        return Ok(());
+    };
+
+    Client::builder()
+        .timeout(SUBMIT_TIMEOUT)
+        .build()?
+        .post(url)
+        .json(&sample)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::types::PhaseFailure;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sample_is_anonymous() {
+        let result = SpeedTestResult {
+            timestamp: Utc.with_ymd_and_hms(2025, 3, 1, 19, 42, 17).unwrap(),
+            download_mbps: 512.345,
+            upload_mbps: 48.71,
+            ping_ms: 12.44,
+            quality: ConnectionQuality::Excellent,
+            client_ip: Some("203.0.113.7".parse().unwrap()),
+            server_ip: Some("198.51.100.1".parse().unwrap()),
+            isp: Some("Example Fiber".to_string()),
+            host_label: Some("nas".to_string()),
+            site: Some("Berlin office".to_string()),
+            country: Some("Germany".to_string()),
+            ..Default::default()
+        };
+
+        let sample = TelemetrySample::from_result(&result).unwrap();
+        assert_eq!(
+            sample.hour,
+            Utc.with_ymd_and_hms(2025, 3, 1, 19, 0, 0).unwrap()
+        );
+        assert_eq!(sample.download_mbps, 512.3);
+        assert_eq!(sample.ping_ms, 12.4);
+
+        let json = serde_json::to_string(&sample).unwrap();
+        for private in [
+            "203.0.113.7",
+            "198.51.100.1",
+            "Example Fiber",
+            "nas",
+            "Berlin",
+        ] {
+            assert!(!json.contains(private), "{} leaked into {}", private, json);
+        }
+        assert!(json.contains("\"country\":\"Germany\""));
+
+        let failed = SpeedTestResult {
+            failures: vec![PhaseFailure {
+                phase: crate::modules::types::SpeedPhase::Upload,
+                reason: "transfers stalled".to_string(),
+            }],
+            ..result
+        };
+        assert_eq!(TelemetrySample::from_result(&failed), None);
+    }
+
+    #[test]
+    fn test_consent() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data").join(CONSENT_FILE);
+
+        assert_eq!(load_consent_from(&path).unwrap(), None);
+        let on = save_consent_to(&path, true).unwrap();
+        assert_eq!(load_consent_from(&path).unwrap(), Some(on));
+        save_consent_to(&path, false).unwrap();
+        assert!(!load_consent_from(&path).unwrap().unwrap().enabled);
+
+        std::fs::write(&path, "on").unwrap();
+        assert!(load_consent_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_do_not_track() {
+        let env = |value: &'static str| move |_: &str| Some(value.to_string());
+        assert!(do_not_track(env("1")));
+        assert!(do_not_track(env("true")));
+        assert!(!do_not_track(env("0")));
+        assert!(!do_not_track(env("")));
+        assert!(!do_not_track(|_| None));
+    }
+}
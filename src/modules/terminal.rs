@@ -0,0 +1,238 @@
+//! Terminal Capability Detection
+//!
+//! Decides once per process whether output may use color, truecolor and
+//! Unicode glyphs:
+//! - `NO_COLOR` (any non-empty value) disables color, `CLICOLOR_FORCE` re-enables it
+//! - Color, animations and cursor tricks are off when stdout is not a TTY
+//! - `COLORTERM=truecolor|24bit` enables RGB colors, otherwise themes fall back to ANSI
+//! - A UTF-8 locale is required for braille spinners and box drawing, otherwise ASCII is used
+
+use crossterm::{
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use prettytable::Table;
+use ratatui::{backend::CrosstermBackend, Terminal};
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+/// Glyphs used for progress output, in a Unicode and an ASCII flavour
+#[derive(Debug, PartialEq, Eq)]
+pub struct Symbols {
+    pub spinner: &'static [&'static str],
+    pub check: &'static str,
+    pub cross: &'static str,
+    pub block: &'static str,
+    pub shade: &'static str,
+    pub vertical: &'static str,
+    pub horizontal: &'static str,
+    pub corner: &'static str,
+    pub double_horizontal: &'static str,
+    pub progress_chars: &'static str,
+    pub matrix: &'static [&'static str],
+    pub open: &'static str,
+    pub close: &'static str,
+}
+
+pub const UNICODE_SYMBOLS: Symbols = Symbols {
+    spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+    check: "✓",
+    cross: "✗",
+    block: "█",
+    shade: "░",
+    vertical: "│",
+    horizontal: "─",
+    corner: "└",
+    double_horizontal: "═",
+    progress_chars: "━━╸─",
+    matrix: &["0", "1", "⠁", "⠂", "⠄", "⡀", "⢀", "⠠", "⠐", "⠈"],
+    open: "⟨⟨⟨",
+    close: "⟩⟩⟩",
+};
+
+pub const ASCII_SYMBOLS: Symbols = Symbols {
+    spinner: &["|", "/", "-", "\\"],
+    check: "+",
+    cross: "x",
+    block: "#",
+    shade: ".",
+    vertical: "|",
+    horizontal: "-",
+    corner: "+",
+    double_horizontal: "=",
+    progress_chars: "=>-",
+    matrix: &["0", "1", ".", ":", "'", "`"],
+    open: "<<<",
+    close: ">>>",
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalCapabilities {
+    pub is_tty: bool,
+    pub color: bool,
+    pub truecolor: bool,
+    pub unicode: bool,
+}
+
+impl TerminalCapabilities {
+    /// Capabilities of the current process, detected on first use
+    pub fn get() -> Self {
+        static CAPABILITIES: OnceLock<TerminalCapabilities> = OnceLock::new();
+        *CAPABILITIES.get_or_init(Self::detect)
+    }
+
+    fn detect() -> Self {
+        Self::from_env(|key| std::env::var(key).ok(), io::stdout().is_terminal())
+    }
+
+    /// Derive capabilities from environment variables and whether stdout is a TTY
+    pub fn from_env(var: impl Fn(&str) -> Option<String>, is_tty: bool) -> Self {
+        let set = |key: &str| var(key).is_some_and(|value| !value.is_empty());
+        let term = var("TERM").unwrap_or_default();
+        let dumb = term == "dumb";
+
+        let color = if set("NO_COLOR") {
+            false
+        } else if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+            true
+        } else {
+            is_tty && !dumb
+        };
+
+        let truecolor = color
+            && var("COLORTERM")
+                .is_some_and(|value| matches!(value.as_str(), "truecolor" | "24bit"));
+
+        // The first non-empty locale variable wins, as in setlocale(3)
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|key| var(key).filter(|value| !value.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        let unicode = !dumb
+            && term != "linux"
+            && (cfg!(windows) || locale.contains("utf-8") || locale.contains("utf8"));
+
+        Self {
+            is_tty,
+            color,
+            truecolor,
+            unicode,
+        }
+    }
+
+    /// Make the `colored` crate follow the detected color support
+    pub fn apply(&self) {
+        if !self.color {
+            colored::control::set_override(false);
+        }
+    }
+
+    /// Animations rewrite lines in place, which only works on an interactive terminal
+    pub fn supports_animation(&self) -> bool {
+        self.is_tty
+    }
+
+    pub fn symbols(&self) -> &'static Symbols {
+        if self.unicode {
+            &UNICODE_SYMBOLS
+        } else {
+            &ASCII_SYMBOLS
+        }
+    }
+}
+
+/// Print a table to stdout, dropping its cell styles when color is disabled
+pub fn print_table(table: &Table) -> io::Result<()> {
+    if TerminalCapabilities::get().color {
+        table.printstd();
+        Ok(())
+    } else {
+        table.print(&mut io::stdout()).map(|_| ())
+    }
+}
+
+/// Run `run` on the alternate screen in raw mode and put the terminal back
+/// afterwards, also when it fails. Used by the intro and the full-screen views.
+pub fn run_fullscreen<T>(
+    run: impl FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<T>,
+) -> io::Result<T> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = run(&mut terminal);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn caps(vars: &[(&str, &str)], is_tty: bool) -> TerminalCapabilities {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        TerminalCapabilities::from_env(|key| vars.get(key).cloned(), is_tty)
+    }
+
+    #[test]
+    fn test_no_color_wins() {
+        let caps = caps(
+            &[
+                ("NO_COLOR", "1"),
+                ("COLORTERM", "truecolor"),
+                ("LANG", "en_US.UTF-8"),
+            ],
+            true,
+        );
+        assert!(!caps.color);
+        assert!(!caps.truecolor);
+        assert!(caps.unicode);
+    }
+
+    #[test]
+    fn test_pipe_disables_color_unless_forced() {
+        assert!(!caps(&[("LANG", "C.UTF-8")], false).color);
+        assert!(caps(&[("CLICOLOR_FORCE", "1")], false).color);
+        assert!(!caps(&[], false).supports_animation());
+    }
+
+    #[test]
+    fn test_truecolor_and_locale() {
+        let full = caps(
+            &[
+                ("COLORTERM", "truecolor"),
+                ("LC_ALL", "de_DE.utf8"),
+                ("LANG", "C"),
+            ],
+            true,
+        );
+        assert!(full.truecolor);
+        assert!(full.unicode);
+        assert_eq!(full.symbols(), &UNICODE_SYMBOLS);
+
+        let plain = caps(&[("TERM", "xterm"), ("LANG", "C")], true);
+        assert!(plain.color);
+        assert!(!plain.truecolor);
+        if !cfg!(windows) {
+            assert!(!plain.unicode);
+            assert_eq!(plain.symbols(), &ASCII_SYMBOLS);
+        }
+    }
+
+    #[test]
+    fn test_dumb_terminal() {
+        let caps = caps(&[("TERM", "dumb"), ("LANG", "en_US.UTF-8")], true);
+        assert!(!caps.color);
+        assert!(!caps.unicode);
+    }
+}
@@ -0,0 +1,281 @@
+//! Color Themes
+//!
+//! Maps semantic roles (labels, values, headers, graphs, quality ratings) to
+//! terminal colors so the UI, the bandwidth monitor and the results display
+//! share one palette that can be swapped with `--theme` or `theme` in config.toml.
+
+use colored::Color;
+
+use crate::modules::terminal::TerminalCapabilities;
+use crate::modules::types::{ConnectionQuality, ThemeName};
+
+// Okabe-Ito palette used by the colorblind-safe theme
+const OKABE_BLUE: Color = Color::TrueColor {
+    r: 0,
+    g: 114,
+    b: 178,
+};
+const OKABE_SKY_BLUE: Color = Color::TrueColor {
+    r: 86,
+    g: 180,
+    b: 233,
+};
+const OKABE_REDDISH_PURPLE: Color = Color::TrueColor {
+    r: 204,
+    g: 121,
+    b: 167,
+};
+const OKABE_ORANGE: Color = Color::TrueColor {
+    r: 230,
+    g: 159,
+    b: 0,
+};
+const OKABE_VERMILLION: Color = Color::TrueColor {
+    r: 213,
+    g: 94,
+    b: 0,
+};
+const OKABE_YELLOW: Color = Color::TrueColor {
+    r: 240,
+    g: 228,
+    b: 66,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: ThemeName,
+    /// Banners, secondary values and live readings
+    pub primary: Color,
+    /// Section headers and status titles
+    pub accent: Color,
+    /// Field labels in result listings
+    pub label: Color,
+    /// Headline measurements and success messages
+    pub value: Color,
+    /// Titles and emphasised callouts
+    pub highlight: Color,
+    /// Bars in graphs and histograms
+    pub graph: Color,
+    pub error: Color,
+    /// Spinner color for indicatif templates, None keeps the per-phase colors
+    pub spinner: Option<&'static str>,
+    /// Quality ramp from Excellent to Very Poor/Failed
+    pub quality_ramp: [Color; 5],
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Cyberpunk => Self {
+                name,
+                primary: Color::BrightCyan,
+                accent: Color::BrightMagenta,
+                label: Color::BrightBlue,
+                value: Color::BrightGreen,
+                highlight: Color::BrightYellow,
+                graph: Color::BrightYellow,
+                error: Color::BrightRed,
+                spinner: None,
+                quality_ramp: [
+                    Color::BrightGreen,
+                    Color::Green,
+                    Color::BrightYellow,
+                    Color::Yellow,
+                    Color::BrightRed,
+                ],
+            },
+            ThemeName::Minimal => Self {
+                name,
+                primary: Color::White,
+                accent: Color::BrightWhite,
+                label: Color::White,
+                value: Color::BrightWhite,
+                highlight: Color::BrightWhite,
+                graph: Color::White,
+                error: Color::Red,
+                spinner: Some("white"),
+                quality_ramp: [
+                    Color::Green,
+                    Color::Green,
+                    Color::White,
+                    Color::Yellow,
+                    Color::Red,
+                ],
+            },
+            // Dark, saturated colors that stay readable on white backgrounds
+            ThemeName::LightTerminal => Self {
+                name,
+                primary: Color::Blue,
+                accent: Color::Magenta,
+                label: Color::Black,
+                value: Color::Green,
+                highlight: Color::Magenta,
+                graph: Color::Blue,
+                error: Color::Red,
+                spinner: Some("blue"),
+                quality_ramp: [
+                    Color::Green,
+                    Color::Cyan,
+                    Color::Blue,
+                    Color::Magenta,
+                    Color::Red,
+                ],
+            },
+            // Okabe-Ito palette, distinguishable with the common forms of color blindness
+            ThemeName::ColorblindSafe => Self {
+                name,
+                primary: OKABE_SKY_BLUE,
+                accent: OKABE_REDDISH_PURPLE,
+                label: OKABE_SKY_BLUE,
+                value: OKABE_BLUE,
+                highlight: OKABE_ORANGE,
+                graph: OKABE_BLUE,
+                error: OKABE_VERMILLION,
+                spinner: Some("blue"),
+                quality_ramp: [
+                    OKABE_BLUE,
+                    OKABE_SKY_BLUE,
+                    OKABE_YELLOW,
+                    OKABE_ORANGE,
+                    OKABE_VERMILLION,
+                ],
+            },
+        }
+    }
+
+    /// Color for a quality rating, shared by the gauge and the results display
+    pub fn quality_color(&self, quality: ConnectionQuality) -> Color {
+        match quality {
+            ConnectionQuality::Excellent => self.quality_ramp[0],
+            ConnectionQuality::Good => self.quality_ramp[1],
+            ConnectionQuality::Average => self.quality_ramp[2],
+            ConnectionQuality::Poor => self.quality_ramp[3],
+            ConnectionQuality::VeryPoor | ConnectionQuality::Failed => self.quality_ramp[4],
+        }
+    }
+
+    /// Replace RGB colors with their nearest ANSI color on terminals without truecolor
+    pub fn for_terminal(mut self, capabilities: &TerminalCapabilities) -> Self {
+        if !capabilities.truecolor {
+            for color in [
+                &mut self.primary,
+                &mut self.accent,
+                &mut self.label,
+                &mut self.value,
+                &mut self.highlight,
+                &mut self.graph,
+                &mut self.error,
+            ]
+            .into_iter()
+            .chain(self.quality_ramp.iter_mut())
+            {
+                *color = nearest_ansi(*color);
+            }
+        }
+        self
+    }
+
+    /// Spinner color for an indicatif template, keeping `preferred` when the theme allows it
+    pub fn spinner_color(&self, preferred: &'static str) -> &'static str {
+        self.spinner.unwrap_or(preferred)
+    }
+}
+
+/// Nearest of the 16 ANSI colors (xterm defaults) to an RGB color
+fn nearest_ansi(color: Color) -> Color {
+    const ANSI: [(Color, (i32, i32, i32)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::Red, (205, 0, 0)),
+        (Color::Green, (0, 205, 0)),
+        (Color::Yellow, (205, 205, 0)),
+        (Color::Blue, (0, 0, 238)),
+        (Color::Magenta, (205, 0, 205)),
+        (Color::Cyan, (0, 205, 205)),
+        (Color::White, (229, 229, 229)),
+        (Color::BrightBlack, (127, 127, 127)),
+        (Color::BrightRed, (255, 0, 0)),
+        (Color::BrightGreen, (0, 255, 0)),
+        (Color::BrightYellow, (255, 255, 0)),
+        (Color::BrightBlue, (92, 92, 255)),
+        (Color::BrightMagenta, (255, 0, 255)),
+        (Color::BrightCyan, (0, 255, 255)),
+        (Color::BrightWhite, (255, 255, 255)),
+    ];
+
+    let Color::TrueColor { r, g, b } = color else {
+        return color;
+    };
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+
+    ANSI.iter()
+        .min_by_key(|(_, (ar, ag, ab))| (r - ar).pow(2) + (g - ag).pow(2) + (b - ab).pow(2))
+        .map(|(ansi, _)| *ansi)
+        .unwrap_or(color)
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ThemeName::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_ramp_is_distinct_per_theme() {
+        for name in [
+            ThemeName::Cyberpunk,
+            ThemeName::LightTerminal,
+            ThemeName::ColorblindSafe,
+        ] {
+            let theme = Theme::new(name);
+            let excellent = theme.quality_color(ConnectionQuality::Excellent);
+            let very_poor = theme.quality_color(ConnectionQuality::VeryPoor);
+            assert_ne!(excellent, very_poor, "{} ramp collapses", name);
+        }
+    }
+
+    #[test]
+    fn test_truecolor_fallback() {
+        let ansi_only = TerminalCapabilities {
+            is_tty: true,
+            color: true,
+            truecolor: false,
+            unicode: true,
+        };
+        let theme = Theme::new(ThemeName::ColorblindSafe).for_terminal(&ansi_only);
+        assert!(theme
+            .quality_ramp
+            .iter()
+            .all(|color| !matches!(color, Color::TrueColor { .. })));
+        assert_eq!(theme.error, Color::Red);
+
+        // The ramp stays distinguishable after the fallback
+        let mut ramp = theme.quality_ramp.to_vec();
+        ramp.dedup();
+        assert_eq!(ramp.len(), 5);
+
+        let truecolor = TerminalCapabilities {
+            truecolor: true,
+            ..ansi_only
+        };
+        assert_eq!(
+            Theme::new(ThemeName::ColorblindSafe).for_terminal(&truecolor),
+            Theme::new(ThemeName::ColorblindSafe)
+        );
+    }
+
+    #[test]
+    fn test_spinner_color_override() {
+        assert_eq!(
+            Theme::default().spinner_color("bright_green"),
+            "bright_green"
+        );
+        assert_eq!(
+            Theme::new(ThemeName::Minimal).spinner_color("bright_green"),
+            "white"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! TLS Handshake Checks
+//!
+//! Connects to a host, completes a TLS handshake with the platform's certificate
+//! verifier and reports the negotiated version, cipher suite, handshake time and
+//! certificate validity. rustls only speaks TLS 1.2 and 1.3, so a server that
+//! offers nothing newer shows up as a failed handshake flagged as outdated.
+//!
+//! Speed tests connect through reqwest, which does not say which TLS version a
+//! connection negotiated. Their client is built from [`transfer_config`], whose
+//! [`VersionLog`] tells the versions apart by the secrets rustls derives.
+
+use chrono::{DateTime, Utc};
+use rustls::pki_types::ServerName;
+use rustls::{CertificateError, ClientConfig, KeyLog, ProtocolVersion};
+use rustls_platform_verifier::{BuilderVerifierExt, ConfigVerifierExt};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::modules::types::{TlsCheck, TlsIssue};
+
+/// Certificates expiring within this many days are flagged
+pub const CERT_EXPIRY_WARNING_DAYS: i64 = 14;
+
+/// Client configuration verifying certificates against the platform's trust store
+pub fn client_config() -> Result<ClientConfig, rustls::Error> {
+    ClientConfig::with_platform_verifier()
+}
+
+/// Client configuration for speed test transfers, as reqwest would build it, with
+/// the TLS version of every handshake noted in `versions`
+pub fn transfer_config(versions: Arc<VersionLog>) -> Result<ClientConfig, rustls::Error> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_platform_verifier()?
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    config.key_log = versions;
+    Ok(config)
+}
+
+/// TLS version of the latest handshake, told from the secrets rustls derives: a
+/// TLS 1.2 `CLIENT_RANDOM` master secret or TLS 1.3 traffic secrets. Only their
+/// labels are looked at, the secrets are dropped unread.
+#[derive(Debug, Default)]
+pub struct VersionLog {
+    version: Mutex<Option<&'static str>>,
+}
+
+impl VersionLog {
+    pub fn version(&self) -> Option<String> {
+        self.version.lock().unwrap().map(str::to_string)
+    }
+
+    /// Forget earlier handshakes, so only connections opened from now on count
+    pub fn clear(&self) {
+        *self.version.lock().unwrap() = None;
+    }
+
+    fn note(&self, version: &'static str) {
+        *self.version.lock().unwrap() = Some(version);
+    }
+}
+
+impl KeyLog for VersionLog {
+    // TLS 1.2 hands over its master secret without asking `will_log`
+    fn log(&self, label: &str, _client_random: &[u8], _secret: &[u8]) {
+        if label == "CLIENT_RANDOM" {
+            self.note("TLS 1.2");
+        }
+    }
+
+    // TLS 1.3 asks first, and is told no so its secrets are never passed
+    fn will_log(&self, label: &str) -> bool {
+        if label == "CLIENT_TRAFFIC_SECRET_0" {
+            self.note("TLS 1.3");
+        }
+        false
+    }
+}
+
+/// Split `host`, `host:port` or `[v6]:port` into host and port, defaulting to 443
+pub fn parse_target(target: &str) -> Result<(String, u16), String> {
+    let invalid = || format!("Invalid host '{}', expected HOST or HOST:PORT", target);
+
+    if let Ok(addr) = target.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), addr.port()));
+    }
+    let (host, port) = match target.rsplit_once(':') {
+        // A bare IPv6 address has several colons and no port
+        Some((host, port)) if !host.contains(':') => {
+            (host, port.parse::<u16>().map_err(|_| invalid())?)
+        }
+        _ => (target, 443),
+    };
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() || port == 0 {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+/// Handshake with `host:port` and describe the connection
+pub async fn check_host(host: &str, port: u16, timeout: Duration) -> TlsCheck {
+    let mut check = TlsCheck {
+        host: host.to_string(),
+        port,
+        protocol: None,
+        cipher_suite: None,
+        handshake_ms: None,
+        certificate_subject: None,
+        certificate_issuer: None,
+        certificate_expires: None,
+        error: None,
+        issues: Vec::new(),
+    };
+
+    match tokio::time::timeout(timeout, handshake(host, port, &mut check)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            check.issues.push(classify_error(e.as_ref()));
+            check.error = Some(e.to_string());
+        }
+        Err(_) => {
+            check.issues.push(TlsIssue::HandshakeFailed);
+            check.error = Some(format!("No answer within {}s", timeout.as_secs()));
+        }
+    }
+
+    if let Some(expires) = check.certificate_expires {
+        check.issues.extend(expiry_issue(expires, Utc::now()));
+    }
+
+    check
+}
+
+async fn handshake(
+    host: &str,
+    port: u16,
+    check: &mut TlsCheck,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let server_name = ServerName::try_from(host.to_string())?;
+    let connector = TlsConnector::from(Arc::new(client_config()?));
+
+    let tcp = TcpStream::connect((host, port)).await?;
+    let start = Instant::now();
+    let tls = connector.connect(server_name, tcp).await?;
+    check.handshake_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+
+    let (_, connection) = tls.get_ref();
+    check.protocol = connection.protocol_version().map(protocol_name);
+    check.cipher_suite = connection
+        .negotiated_cipher_suite()
+        .and_then(|suite| suite.suite().as_str())
+        .map(str::to_string);
+
+    if let Some(leaf) = connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+    {
+        if let Ok((_, cert)) = x509_parser::parse_x509_certificate(leaf.as_ref()) {
+            check.certificate_subject = Some(cert.subject().to_string());
+            check.certificate_issuer = Some(cert.issuer().to_string());
+            check.certificate_expires =
+                DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0);
+        }
+    }
+
+    Ok(())
+}
+
+fn protocol_name(version: ProtocolVersion) -> String {
+    match version {
+        ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+        ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Flag expired certificates and those within the warning window
+fn expiry_issue(expires: DateTime<Utc>, now: DateTime<Utc>) -> Option<TlsIssue> {
+    if expires <= now {
+        Some(TlsIssue::CertificateExpired)
+    } else if expires - now < chrono::Duration::days(CERT_EXPIRY_WARNING_DAYS) {
+        Some(TlsIssue::CertificateExpiringSoon)
+    } else {
+        None
+    }
+}
+
+/// Name the reason a handshake failed, looking through the I/O error tokio-rustls wraps it in
+fn classify_error(error: &(dyn std::error::Error + 'static)) -> TlsIssue {
+    let rustls_error = error
+        .downcast_ref::<std::io::Error>()
+        .and_then(|e| e.get_ref())
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        .or_else(|| error.downcast_ref::<rustls::Error>());
+
+    match rustls_error {
+        Some(rustls::Error::InvalidCertificate(
+            CertificateError::Expired | CertificateError::ExpiredContext { .. },
+        )) => TlsIssue::CertificateExpired,
+        Some(rustls::Error::PeerIncompatible(_))
+        | Some(rustls::Error::AlertReceived(rustls::AlertDescription::ProtocolVersion)) => {
+            TlsIssue::OutdatedProtocol
+        }
+        _ => TlsIssue::HandshakeFailed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("example.com").unwrap(),
+            ("example.com".to_string(), 443)
+        );
+        assert_eq!(
+            parse_target("mail.example.com:993").unwrap(),
+            ("mail.example.com".to_string(), 993)
+        );
+        assert_eq!(
+            parse_target("[2001:db8::1]:8443").unwrap(),
+            ("2001:db8::1".to_string(), 8443)
+        );
+        assert_eq!(
+            parse_target("2001:db8::1").unwrap(),
+            ("2001:db8::1".to_string(), 443)
+        );
+        assert!(parse_target("example.com:https").is_err());
+        assert!(parse_target(":443").is_err());
+    }
+
+    #[test]
+    fn test_expiry_issue() {
+        let now = Utc::now();
+        assert_eq!(
+            expiry_issue(now - chrono::Duration::days(1), now),
+            Some(TlsIssue::CertificateExpired)
+        );
+        assert_eq!(
+            expiry_issue(now + chrono::Duration::days(3), now),
+            Some(TlsIssue::CertificateExpiringSoon)
+        );
+        assert_eq!(expiry_issue(now + chrono::Duration::days(90), now), None);
+    }
+
+    #[test]
+    fn test_classify_error() {
+        let expired = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(CertificateError::Expired),
+        );
+        assert_eq!(classify_error(&expired), TlsIssue::CertificateExpired);
+
+        let outdated = rustls::Error::AlertReceived(rustls::AlertDescription::ProtocolVersion);
+        assert_eq!(classify_error(&outdated), TlsIssue::OutdatedProtocol);
+
+        let refused = std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert_eq!(classify_error(&refused), TlsIssue::HandshakeFailed);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_is_flagged() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let check = check_host("127.0.0.1", port, Duration::from_secs(2)).await;
+        assert_eq!(check.issues, [TlsIssue::HandshakeFailed]);
+        assert!(check.error.is_some());
+        assert!(check.protocol.is_none());
+    }
+
+    #[test]
+    fn test_version_log() {
+        let log = VersionLog::default();
+        assert_eq!(log.version(), None);
+
+        assert!(!log.will_log("CLIENT_HANDSHAKE_TRAFFIC_SECRET"));
+        assert_eq!(log.version(), None);
+        assert!(!log.will_log("CLIENT_TRAFFIC_SECRET_0"));
+        assert_eq!(log.version().as_deref(), Some("TLS 1.3"));
+
+        log.log("CLIENT_RANDOM", &[0; 32], &[0; 48]);
+        assert_eq!(log.version().as_deref(), Some("TLS 1.2"));
+
+        log.clear();
+        assert_eq!(log.version(), None);
+    }
+}
@@ -0,0 +1,596 @@
+//! Traceroute
+//!
+//! Sends probes with increasing TTL and records the router answering each one
+//! with an ICMP error. Probes are ICMP echo requests, UDP datagrams or TCP SYNs,
+//! the latter two getting through networks that filter ICMP. Probes for every TTL
+//! go out together, several per hop, so a trace takes about one probe timeout
+//! rather than one per hop. Reading the ICMP answers needs a raw socket and so
+//! root (or CAP_NET_RAW); without one the system `traceroute` (`tracert` on
+//! Windows) is run and its output parsed.
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::modules::types::{median, RouteHop, TraceOptions, TraceProtocol};
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// Pause between rounds of probes, routers rate-limit the time exceeded errors they send
+const ROUND_GAP: Duration = Duration::from_millis(20);
+
+/// How often pending TCP probes are checked for a handshake with the destination
+const TCP_POLL: Duration = Duration::from_millis(5);
+
+/// Trace the route to `target`, calling `on_hop` as each hop first answers
+pub async fn trace(
+    target: IpAddr,
+    options: TraceOptions,
+    on_hop: impl Fn(&RouteHop) + Send + 'static,
+) -> Result<Vec<RouteHop>, Box<dyn std::error::Error>> {
+    let raw = match target {
+        IpAddr::V4(target) => {
+            tokio::task::spawn_blocking(move || trace_raw(target, options, on_hop)).await?
+        }
+        IpAddr::V6(_) => Err(std::io::ErrorKind::Unsupported.into()),
+    };
+
+    match raw {
+        Ok(hops) => Ok(hops),
+        Err(_) => trace_with_command(target, options).await,
+    }
+}
+
+/// How answers are matched back to the probe they answer
+#[derive(Debug, PartialEq)]
+enum ProbeMatch {
+    /// Echo requests carry our id, and the probe number as sequence number
+    Echo(u16),
+    /// UDP and TCP probes each have their own socket, known by its source port
+    Ports { protocol: u8, ports: Vec<u16> },
+}
+
+/// Blocking traceroute reading answers from a raw ICMP socket, probing every TTL at once
+fn trace_raw(
+    target: Ipv4Addr,
+    options: TraceOptions,
+    on_hop: impl Fn(&RouteHop),
+) -> std::io::Result<Vec<RouteHop>> {
+    let icmp = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+    let max_hops = usize::from(options.max_hops.max(1));
+    let probe_count = max_hops * usize::from(options.probes_per_hop.max(1));
+
+    // Probe n goes out in rounds covering TTLs 1..=max_hops
+    let ttl_of = |probe: usize| probe % max_hops + 1;
+    let id: u16 = rand::random();
+    let mut probe_sockets = Vec::with_capacity(probe_count);
+    let mut ports = Vec::with_capacity(probe_count);
+    let mut sent_at = Vec::with_capacity(probe_count);
+    for probe in 0..probe_count {
+        if probe > 0 && ttl_of(probe) == 1 {
+            std::thread::sleep(ROUND_GAP);
+        }
+        let ttl = ttl_of(probe) as u32;
+        match options.protocol {
+            TraceProtocol::Icmp => {
+                icmp.set_ttl_v4(ttl)?;
+                sent_at.push(Instant::now());
+                let destination = SockAddr::from(SocketAddr::new(IpAddr::V4(target), 0));
+                icmp.send_to(&echo_request(id, probe as u16), &destination)?;
+            }
+            TraceProtocol::Udp(port) | TraceProtocol::Tcp(port) => {
+                let tcp = matches!(options.protocol, TraceProtocol::Tcp(_));
+                let socket = if tcp {
+                    Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?
+                } else {
+                    Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?
+                };
+                socket.set_ttl_v4(ttl)?;
+                socket.set_nonblocking(true)?;
+                socket.bind(&SockAddr::from(SocketAddr::from((
+                    Ipv4Addr::UNSPECIFIED,
+                    0,
+                ))))?;
+                let destination = SockAddr::from(SocketAddr::new(IpAddr::V4(target), port));
+
+                sent_at.push(Instant::now());
+                if tcp {
+                    // The SYN goes out here and connect reports the handshake in progress;
+                    // a probe that fails outright just goes unanswered
+                    let _ = socket.connect(&destination);
+                } else {
+                    socket.send_to(b"netrunnr", &destination)?;
+                }
+
+                let local = socket.local_addr()?.as_socket();
+                ports.push(local.map_or(0, |addr| addr.port()));
+                probe_sockets.push(socket);
+            }
+        }
+    }
+    let probe_match = match options.protocol {
+        TraceProtocol::Icmp => ProbeMatch::Echo(id),
+        TraceProtocol::Udp(_) => ProbeMatch::Ports {
+            protocol: IPPROTO_UDP,
+            ports,
+        },
+        TraceProtocol::Tcp(_) => ProbeMatch::Ports {
+            protocol: IPPROTO_TCP,
+            ports,
+        },
+    };
+
+    let mut answers = Answers::new(max_hops, probe_count);
+    let record = |answers: &mut Answers, probe: usize, from: Ipv4Addr, final_hop: bool| {
+        let elapsed = sent_at[probe].elapsed();
+        if answers.answered[probe] || elapsed > options.probe_timeout {
+            return;
+        }
+        let ttl = ttl_of(probe);
+        if answers.record(probe, ttl, from, elapsed, final_hop) {
+            on_hop(&hop_from_replies(ttl, &answers.replies[ttl - 1]));
+        }
+    };
+    let deadline = Instant::now() + options.probe_timeout;
+
+    // The raw socket sees every ICMP packet on the host, skip anything not answering our probes
+    let mut buf = [0u8; 1500];
+    loop {
+        // A TCP probe reaching the destination gets a SYN-ACK or a reset, not an ICMP error
+        if matches!(options.protocol, TraceProtocol::Tcp(_)) {
+            for (probe, socket) in probe_sockets.iter().enumerate() {
+                let connected = socket.peer_addr().is_ok();
+                let refused = matches!(
+                    socket.take_error(),
+                    Ok(Some(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused
+                );
+                if connected || refused {
+                    record(&mut answers, probe, target, true);
+                }
+            }
+        }
+
+        let complete = answers.final_ttl.is_some_and(|last| {
+            (0..probe_count)
+                .filter(|&probe| ttl_of(probe) <= last)
+                .all(|probe| answers.answered[probe])
+        });
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if complete || remaining.is_zero() {
+            break;
+        }
+
+        let wait = match options.protocol {
+            TraceProtocol::Tcp(_) => remaining.min(TCP_POLL),
+            _ => remaining,
+        };
+        icmp.set_read_timeout(Some(wait))?;
+        let n = match (&icmp).read(&mut buf) {
+            Ok(n) => n,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        };
+
+        if let Some((probe, reply)) = parse_reply(&buf[..n], &probe_match) {
+            if probe < probe_count {
+                record(&mut answers, probe, reply.from, reply.final_hop);
+            }
+        }
+    }
+
+    // Probes with a TTL past the destination are answered by the destination too
+    let last = answers.final_ttl.unwrap_or(max_hops);
+    Ok((1..=last)
+        .map(|ttl| hop_from_replies(ttl, &answers.replies[ttl - 1]))
+        .collect())
+}
+
+/// Answers collected so far, per probe and per TTL
+struct Answers {
+    answered: Vec<bool>,
+    replies: Vec<Vec<(Ipv4Addr, f64)>>,
+    /// Lowest TTL that reached the destination
+    final_ttl: Option<usize>,
+}
+
+impl Answers {
+    fn new(max_hops: usize, probe_count: usize) -> Self {
+        Self {
+            answered: vec![false; probe_count],
+            replies: vec![Vec::new(); max_hops],
+            final_ttl: None,
+        }
+    }
+
+    /// Record an answer, true when it's the first for its TTL
+    fn record(
+        &mut self,
+        probe: usize,
+        ttl: usize,
+        from: Ipv4Addr,
+        elapsed: Duration,
+        final_hop: bool,
+    ) -> bool {
+        self.answered[probe] = true;
+        self.replies[ttl - 1].push((from, elapsed.as_secs_f64() * 1000.0));
+        if final_hop {
+            self.final_ttl = Some(self.final_ttl.map_or(ttl, |last| last.min(ttl)));
+        }
+        self.replies[ttl - 1].len() == 1
+    }
+}
+
+/// A hop as the first router to answer, with the median time of all its answered probes
+fn hop_from_replies(ttl: usize, replies: &[(Ipv4Addr, f64)]) -> RouteHop {
+    let times: Vec<f64> = replies.iter().map(|(_, time)| *time).collect();
+    RouteHop {
+        hop_number: ttl as u32,
+        address: replies.first().map(|(from, _)| IpAddr::V4(*from)),
+        hostname: None,
+        response_time_ms: (!times.is_empty()).then(|| median(&times)),
+        network: None,
+        location: None,
+    }
+}
+
+/// An ICMP echo request carrying `id` and `sequence`
+fn echo_request(id: u16, sequence: u16) -> [u8; 16] {
+    let mut packet = [0u8; 16];
+    packet[0] = ICMP_ECHO_REQUEST;
+    packet[4..6].copy_from_slice(&id.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[8..].copy_from_slice(b"netrunnr");
+
+    let checksum = internet_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[derive(Debug, PartialEq)]
+struct Reply {
+    from: Ipv4Addr,
+    /// The destination answered, or a router said it can't be reached
+    final_hop: bool,
+}
+
+/// Match an IPv4 packet from the raw socket against our probes, returning the
+/// number of the probe it answers
+fn parse_reply(packet: &[u8], probes: &ProbeMatch) -> Option<(usize, Reply)> {
+    let header_len = usize::from(packet.first()? & 0x0f) * 4;
+    let from = Ipv4Addr::new(
+        *packet.get(12)?,
+        *packet.get(13)?,
+        *packet.get(14)?,
+        *packet.get(15)?,
+    );
+    let icmp = packet.get(header_len..)?;
+    let echo_probe = |echo: &[u8], id: u16| {
+        (echo.len() >= 8 && echo[4..6] == id.to_be_bytes())
+            .then(|| usize::from(u16::from_be_bytes([echo[6], echo[7]])))
+    };
+
+    match (*icmp.first()?, probes) {
+        (ICMP_ECHO_REPLY, ProbeMatch::Echo(id)) => Some((
+            echo_probe(icmp, *id)?,
+            Reply {
+                from,
+                final_hop: true,
+            },
+        )),
+        (kind @ (ICMP_TIME_EXCEEDED | ICMP_DEST_UNREACHABLE), _) => {
+            // The error quotes our original IP header and the first 8 bytes of its payload
+            let quoted = icmp.get(8..)?;
+            let quoted_header_len = usize::from(quoted.first()? & 0x0f) * 4;
+            let protocol = *quoted.get(9)?;
+            let payload = quoted.get(quoted_header_len..)?;
+
+            let probe = match probes {
+                ProbeMatch::Echo(id) if protocol == IPPROTO_ICMP => {
+                    if payload.first() != Some(&ICMP_ECHO_REQUEST) {
+                        return None;
+                    }
+                    echo_probe(payload, *id)?
+                }
+                ProbeMatch::Ports {
+                    protocol: expected,
+                    ports,
+                } if protocol == *expected => {
+                    let source_port = u16::from_be_bytes([*payload.first()?, *payload.get(1)?]);
+                    ports.iter().position(|&port| port == source_port)?
+                }
+                _ => return None,
+            };
+            Some((
+                probe,
+                Reply {
+                    from,
+                    final_hop: kind == ICMP_DEST_UNREACHABLE,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Run the system traceroute when raw sockets aren't available
+async fn trace_with_command(
+    target: IpAddr,
+    options: TraceOptions,
+) -> Result<Vec<RouteHop>, Box<dyn std::error::Error>> {
+    let max_hops = options.max_hops.to_string();
+    let probes = options.probes_per_hop.to_string();
+    let target = target.to_string();
+    let wait_secs = options.probe_timeout.as_secs().max(1).to_string();
+    let wait_ms = options.probe_timeout.as_millis().max(1).to_string();
+
+    // tracert always sends three ICMP probes per hop
+    let mut command = if cfg!(windows) {
+        if options.protocol != TraceProtocol::Icmp {
+            return Err("tracert only traces with ICMP, use --trace-protocol icmp".into());
+        }
+        let mut command = tokio::process::Command::new("tracert");
+        command.args(["-d", "-h", &max_hops, "-w", &wait_ms, &target]);
+        command
+    } else {
+        let mut command = tokio::process::Command::new("traceroute");
+        command.args(["-n", "-q", &probes, "-w", &wait_secs, "-m", &max_hops]);
+        // Unprivileged ICMP probes aren't allowed everywhere, so for icmp the
+        // command keeps its default UDP probes
+        match options.protocol {
+            TraceProtocol::Icmp => {}
+            TraceProtocol::Udp(port) if cfg!(target_os = "macos") => {
+                command.args(["-P", "UDP", "-p", &port.to_string()]);
+            }
+            TraceProtocol::Tcp(port) if cfg!(target_os = "macos") => {
+                command.args(["-P", "TCP", "-p", &port.to_string()]);
+            }
+            TraceProtocol::Udp(port) => {
+                command.args(["-U", "-p", &port.to_string()]);
+            }
+            TraceProtocol::Tcp(port) => {
+                command.args(["-T", "-p", &port.to_string()]);
+            }
+        }
+        command.arg(&target);
+        command
+    };
+
+    let output = command
+        .output()
+        .await
+        .map_err(|e| format!("Traceroute needs root or the traceroute command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "traceroute failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(parse_command_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse `traceroute -n` and `tracert -d` output: a hop number, then times and an address or `*`
+fn parse_command_output(output: &str) -> Vec<RouteHop> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let hop_number = tokens.next()?.parse::<u32>().ok()?;
+            let tokens: Vec<&str> = tokens.collect();
+
+            let address = tokens.iter().find_map(|token| {
+                token
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .ok()
+            });
+            // Each probe's time is followed by a separate "ms" token; tracert writes "<1"
+            // for sub-millisecond
+            let times: Vec<f64> = tokens
+                .windows(2)
+                .filter(|pair| pair[1] == "ms")
+                .filter_map(|pair| pair[0].trim_start_matches('<').parse::<f64>().ok())
+                .collect();
+            let response_time_ms = (!times.is_empty()).then(|| median(&times));
+
+            Some(RouteHop {
+                hop_number,
+                address,
+                hostname: None,
+                response_time_ms: address.and(response_time_ms),
+                network: None,
+                location: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPv4 packet from `from` carrying `icmp`
+    fn ipv4_packet(from: [u8; 4], icmp: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 1, 0, 0];
+        packet.extend_from_slice(&from);
+        packet.extend_from_slice(&[10, 0, 0, 2]);
+        packet.extend_from_slice(icmp);
+        packet
+    }
+
+    #[test]
+    fn test_echo_request_checksum() {
+        let packet = echo_request(0x1234, 7);
+        assert_eq!(packet[0], ICMP_ECHO_REQUEST);
+        // A packet including its checksum sums to zero
+        assert_eq!(internet_checksum(&packet), 0);
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let request = echo_request(42, 3);
+
+        let mut echo_reply = request;
+        echo_reply[0] = ICMP_ECHO_REPLY;
+        assert_eq!(
+            parse_reply(
+                &ipv4_packet([8, 8, 8, 8], &echo_reply),
+                &ProbeMatch::Echo(42)
+            ),
+            Some((
+                3,
+                Reply {
+                    from: Ipv4Addr::new(8, 8, 8, 8),
+                    final_hop: true,
+                }
+            ))
+        );
+        assert_eq!(
+            parse_reply(
+                &ipv4_packet([8, 8, 8, 8], &echo_reply),
+                &ProbeMatch::Echo(41)
+            ),
+            None
+        );
+
+        // Time exceeded quotes the original packet
+        let mut time_exceeded = vec![ICMP_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0];
+        time_exceeded.extend_from_slice(&ipv4_packet([10, 0, 0, 2], &request[..8]));
+        assert_eq!(
+            parse_reply(
+                &ipv4_packet([192, 168, 1, 1], &time_exceeded),
+                &ProbeMatch::Echo(42)
+            ),
+            Some((
+                3,
+                Reply {
+                    from: Ipv4Addr::new(192, 168, 1, 1),
+                    final_hop: false,
+                }
+            ))
+        );
+        assert_eq!(
+            parse_reply(
+                &ipv4_packet([192, 168, 1, 1], &time_exceeded),
+                &ProbeMatch::Echo(43)
+            ),
+            None
+        );
+        assert_eq!(parse_reply(&[0x45, 0, 0], &ProbeMatch::Echo(42)), None);
+    }
+
+    #[test]
+    fn test_parse_port_reply() {
+        let probes = ProbeMatch::Ports {
+            protocol: IPPROTO_UDP,
+            ports: vec![40000, 40001, 40002],
+        };
+
+        // Port unreachable from the destination quotes the UDP header, source port first
+        let mut datagram = ipv4_packet([10, 0, 0, 2], &[0x9c, 0x41, 0x82, 0x9a, 0, 16, 0, 0]);
+        datagram[9] = IPPROTO_UDP;
+        let mut unreachable = vec![ICMP_DEST_UNREACHABLE, 3, 0, 0, 0, 0, 0, 0];
+        unreachable.extend_from_slice(&datagram);
+        assert_eq!(
+            parse_reply(&ipv4_packet([8, 8, 8, 8], &unreachable), &probes),
+            Some((
+                1,
+                Reply {
+                    from: Ipv4Addr::new(8, 8, 8, 8),
+                    final_hop: true,
+                }
+            ))
+        );
+
+        // The same source port in a TCP segment isn't ours
+        unreachable[8 + 9] = IPPROTO_TCP;
+        assert_eq!(
+            parse_reply(&ipv4_packet([8, 8, 8, 8], &unreachable), &probes),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hop_from_replies() {
+        let router = Ipv4Addr::new(10, 0, 0, 1);
+        let hop = hop_from_replies(2, &[(router, 9.0), (router, 30.0), (router, 10.0)]);
+        assert_eq!(hop.hop_number, 2);
+        assert_eq!(hop.address, Some(IpAddr::V4(router)));
+        assert_eq!(hop.response_time_ms, Some(10.0));
+
+        let silent = hop_from_replies(3, &[]);
+        assert_eq!(silent.address, None);
+        assert_eq!(silent.response_time_ms, None);
+    }
+
+    #[test]
+    fn test_parse_traceroute_output() {
+        let output = "traceroute to 8.8.8.8 (8.8.8.8), 15 hops max, 60 byte packets\n \
+                      1  192.168.1.1  0.512 ms\n \
+                      2  *\n \
+                      3  8.8.8.8  11.204 ms\n";
+        let hops = parse_command_output(output);
+
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].address, Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(hops[0].response_time_ms, Some(0.512));
+        assert_eq!(hops[1].hop_number, 2);
+        assert_eq!(hops[1].address, None);
+        assert_eq!(hops[2].address, Some("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_tracert_output() {
+        let output = "Tracing route to 8.8.8.8 over a maximum of 15 hops\r\n\r\n  \
+                      1    <1 ms    <1 ms    <1 ms  192.168.1.1\r\n  \
+                      2     *        *        *     Request timed out.\r\n  \
+                      3    12 ms    11 ms    12 ms  8.8.8.8\r\n\r\nTrace complete.\r\n";
+        let hops = parse_command_output(output);
+
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].response_time_ms, Some(1.0));
+        assert_eq!(hops[1].address, None);
+        assert_eq!(hops[1].response_time_ms, None);
+        assert_eq!(hops[2].response_time_ms, Some(12.0));
+    }
+
+    #[test]
+    fn test_parse_traceroute_multiple_probes() {
+        let output = " 1  192.168.1.1  0.512 ms  0.498 ms  3.104 ms\n \
+                      2  10.0.0.1  9.1 ms *  8.7 ms\n";
+        let hops = parse_command_output(output);
+
+        assert_eq!(hops[0].response_time_ms, Some(0.512));
+        assert_eq!(hops[1].response_time_ms, Some((9.1 + 8.7) / 2.0));
+    }
+}
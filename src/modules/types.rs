@@ -1,6 +1,10 @@
-use chrono::{DateTime, Utc};
+use crate::modules::analysis::ProbableCause;
+use crate::modules::backend::{self, TestProvider};
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Utc, Weekday};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::net::IpAddr;
+use std::time::Duration;
 use strum::EnumString;
 use strum_macros::Display;
 
@@ -38,6 +42,76 @@ impl ConnectionQuality {
             ConnectionQuality::Failed
         }
     }
+
+    /// Rate a connection from all measured metrics. Starts from `from_speed_and_ping` and
+    /// drops one level for each of 2% packet loss and 40 ms jitter, two levels for 5% loss
+    /// or 100 ms jitter, never below Very Poor.
+    pub fn from_metrics(
+        download_mbps: f64,
+        upload_mbps: f64,
+        ping_ms: f64,
+        jitter_ms: f64,
+        packet_loss_percent: f64,
+    ) -> Self {
+        let base = Self::from_speed_and_ping(download_mbps, upload_mbps, ping_ms);
+        if base == ConnectionQuality::Failed {
+            return base;
+        }
+
+        let loss_penalty = if packet_loss_percent >= 5.0 {
+            2
+        } else if packet_loss_percent >= 2.0 {
+            1
+        } else {
+            0
+        };
+        let jitter_penalty = if jitter_ms >= 100.0 {
+            2
+        } else if jitter_ms >= 40.0 {
+            1
+        } else {
+            0
+        };
+
+        (0..loss_penalty + jitter_penalty).fold(base, |quality, _| quality.downgrade())
+    }
+
+    /// One level worse, stopping at Very Poor
+    fn downgrade(self) -> Self {
+        match self {
+            ConnectionQuality::Excellent => ConnectionQuality::Good,
+            ConnectionQuality::Good => ConnectionQuality::Average,
+            ConnectionQuality::Average => ConnectionQuality::Poor,
+            ConnectionQuality::Poor | ConnectionQuality::VeryPoor => ConnectionQuality::VeryPoor,
+            ConnectionQuality::Failed => ConnectionQuality::Failed,
+        }
+    }
+
+    /// Rate a download throughput alone, using the same thresholds as `from_speed_and_ping`
+    pub fn from_download_mbps(mbps: f64) -> Self {
+        Self::from_throughput(mbps, [100.0, 50.0, 25.0, 10.0])
+    }
+
+    /// Rate an upload throughput alone, using the same thresholds as `from_speed_and_ping`
+    pub fn from_upload_mbps(mbps: f64) -> Self {
+        Self::from_throughput(mbps, [20.0, 10.0, 5.0, 2.0])
+    }
+
+    fn from_throughput(mbps: f64, thresholds: [f64; 4]) -> Self {
+        if mbps >= thresholds[0] {
+            ConnectionQuality::Excellent
+        } else if mbps >= thresholds[1] {
+            ConnectionQuality::Good
+        } else if mbps >= thresholds[2] {
+            ConnectionQuality::Average
+        } else if mbps >= thresholds[3] {
+            ConnectionQuality::Poor
+        } else if mbps > 0.0 {
+            ConnectionQuality::VeryPoor
+        } else {
+            ConnectionQuality::Failed
+        }
+    }
 }
 
 /// Represents a single network speed test result
@@ -47,6 +121,7 @@ pub struct SpeedTestResult {
     pub download_mbps: f64,
     pub upload_mbps: f64,
     pub ping_ms: f64,
+    /// Variation of the round trip, computed as `jitter_method` says
     pub jitter_ms: f64,
     pub packet_loss_percent: f64,
     pub server_location: String,
@@ -55,6 +130,511 @@ pub struct SpeedTestResult {
     pub quality: ConnectionQuality,
     pub test_duration_seconds: f64,
     pub isp: Option<String>,
+    pub latency_stats: Option<LatencyStats>,
+    /// Name of the machine that ran the test, from `host_label` in config.toml
+    #[serde(default)]
+    pub host_label: Option<String>,
+    /// Location the machine belongs to, from `site` in config.toml
+    #[serde(default)]
+    pub site: Option<String>,
+    /// Phases that moved to another server after theirs failed
+    #[serde(default)]
+    pub failovers: Vec<ServerFailover>,
+    /// Phases that failed on every server, their speed is reported as 0
+    #[serde(default)]
+    pub failures: Vec<PhaseFailure>,
+    /// Payload bytes the throughput phases moved, None for results from older versions
+    #[serde(default)]
+    pub data_used: Option<DataUsage>,
+    /// Latency to the important endpoints from config.toml, measured after the test
+    #[serde(default)]
+    pub endpoints: Vec<EndpointLatency>,
+    /// Build and settings that produced the result, None for results from older versions
+    #[serde(default)]
+    pub engine: Option<EngineInfo>,
+    /// Country the test ran from as geolocation reported it, None when no
+    /// service answered or for results from older versions
+    #[serde(default)]
+    pub country: Option<String>,
+    /// Error counters of the network interface while the test ran, Linux only
+    #[serde(default)]
+    pub link_stats: Option<LinkStats>,
+    /// WAN traffic of other devices while the test ran, None without router SNMP
+    #[serde(default)]
+    pub other_traffic: Option<OtherTraffic>,
+    /// Free-form labels from `tags` in config.toml and `--tag`, e.g. `wired`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// HTTP and TLS versions the transfers negotiated, None for results from older versions
+    #[serde(default)]
+    pub transport: Option<TransportInfo>,
+    /// Setup and round trips of a WebSocket to the server, None when it offers none
+    #[serde(default)]
+    pub websocket_latency: Option<WebSocketLatency>,
+    /// Round trip split into directions, None unless the server stamps its answers
+    /// and the clock could be checked closely enough
+    #[serde(default)]
+    pub one_way_delay: Option<OneWayDelay>,
+    /// How `jitter_ms` was computed. Methods give different values for the same
+    /// line, so jitter is only comparable between results using the same one.
+    #[serde(default)]
+    pub jitter_method: JitterMethod,
+}
+
+/// How a result's jitter was computed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterMethod {
+    /// Standard deviation of the probes' round trips, what results saved before
+    /// the method was recorded hold
+    #[default]
+    #[strum(to_string = "standard deviation")]
+    StandardDeviation,
+    /// Interarrival jitter of RFC 3550 over a steady stream of probes
+    #[strum(to_string = "RFC 3550")]
+    Rfc3550,
+    /// The server's smoothed RTT variance of the download connection, for ndt7
+    #[strum(to_string = "TCP RTT variance")]
+    RttVariance,
+}
+
+/// How the transfers reached the server. One HTTP/2 connection multiplexes streams
+/// that take as many HTTP/1.1 connections, which changes what a test measures.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransportInfo {
+    /// ALPN protocol of the download, `h2` or `http/1.1`, None over plain HTTP
+    pub alpn: Option<String>,
+    /// `TLS 1.3` or `TLS 1.2`, None over plain HTTP
+    pub tls_version: Option<String>,
+}
+
+/// Latency to the test server over one WebSocket kept open
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebSocketLatency {
+    /// TCP connect, TLS handshake and WebSocket upgrade together
+    pub setup_ms: f64,
+    /// Median round trip of a ping on the open connection
+    pub rtt_ms: f64,
+    pub rtt_stats: LatencyStats,
+    /// Pings without a pong before the timeout
+    pub unanswered: u32,
+}
+
+/// The round trip to the server split into its two directions. The server stamps
+/// when each probe arrived, the system clock is corrected by an NTP check, and
+/// the server's clock is taken to be right.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OneWayDelay {
+    /// Median time from sending a probe to the server receiving it
+    pub upstream_ms: f64,
+    /// Median time from the server receiving a probe to its answer arriving
+    pub downstream_ms: f64,
+    /// How far either direction may be off, from the NTP round trip and the
+    /// millisecond stamps
+    pub uncertainty_ms: f64,
+    pub samples: usize,
+}
+
+impl OneWayDelay {
+    /// Resolution of the servers' stamps
+    const STAMP_RESOLUTION_MS: f64 = 1.0;
+
+    /// Split probes given as (sent, server received, answer received) in Unix
+    /// milliseconds of the system clock and, for the middle one, the server's.
+    /// None without probes, or when the clock is too uncertain to tell the
+    /// directions apart or the server's stamps are impossible.
+    pub fn from_probes(probes: &[(f64, f64, f64)], clock: &ClockCheck) -> Option<Self> {
+        if probes.is_empty() {
+            return None;
+        }
+        let upstream: Vec<f64> = probes
+            .iter()
+            .map(|&(sent, stamped, _)| stamped - (sent + clock.offset_ms))
+            .collect();
+        let downstream: Vec<f64> = probes
+            .iter()
+            .map(|&(_, stamped, received)| received + clock.offset_ms - stamped)
+            .collect();
+        let delay = Self {
+            upstream_ms: median(&upstream),
+            downstream_ms: median(&downstream),
+            uncertainty_ms: clock.round_trip_ms / 2.0 + Self::STAMP_RESOLUTION_MS,
+            samples: probes.len(),
+        };
+
+        // A direction below zero beyond the error means the server's clock is off,
+        // an error of half the round trip could put all of it on either side
+        let round_trip = delay.upstream_ms + delay.downstream_ms;
+        let plausible = delay.upstream_ms.min(delay.downstream_ms) >= -delay.uncertainty_ms;
+        (plausible && delay.uncertainty_ms < round_trip / 2.0).then_some(delay)
+    }
+
+    /// How much slower upstream is than downstream, negative when downstream is slower
+    pub fn asymmetry_ms(&self) -> f64 {
+        self.upstream_ms - self.downstream_ms
+    }
+}
+
+/// Which build of netrunner measured a result and how, so aggregated results from
+/// different versions or settings can be told apart
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EngineInfo {
+    pub version: String,
+    /// Commit the binary was built from, None for builds outside a git checkout
+    pub git_hash: Option<String>,
+    /// Cargo features the binary was built with
+    pub features: Vec<String>,
+    /// HTTP version the download phase negotiated, e.g. "HTTP/2.0"
+    pub protocol: Option<String>,
+    pub download_connections: u32,
+    pub upload_connections: u32,
+    pub parameters: TestParameters,
+}
+
+impl EngineInfo {
+    /// The running build with the given connection counts and settings
+    pub fn current(
+        config: &TestConfig,
+        protocol: Option<String>,
+        download_connections: u32,
+        upload_connections: u32,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: Some(env!("NETRUNNER_GIT_HASH"))
+                .filter(|hash| !hash.is_empty())
+                .map(str::to_string),
+            features: env!("NETRUNNER_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_string)
+                .collect(),
+            protocol,
+            download_connections,
+            upload_connections,
+            parameters: TestParameters::from_config(config),
+        }
+    }
+}
+
+/// Settings of a speed test that change what it measures
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TestParameters {
+    pub timeout_seconds: u64,
+    pub max_data_bytes: Option<u64>,
+    pub max_rate_bps: Option<u64>,
+    /// DSCP code point the traffic was marked with
+    pub dscp: Option<u8>,
+    pub retries: u32,
+    /// Servers came from a `--server-list` file that replaced the public pool
+    pub self_hosted_only: bool,
+    /// The server was chosen by hand in the interactive menu
+    pub pinned_server: bool,
+}
+
+impl TestParameters {
+    pub fn from_config(config: &TestConfig) -> Self {
+        Self {
+            timeout_seconds: config.timeout_seconds,
+            max_data_bytes: config.max_data.map(|size| size.0),
+            max_rate_bps: config.max_rate.map(|rate| rate.0),
+            dscp: config.dscp.map(Dscp::value),
+            retries: config.retries,
+            self_hosted_only: config.server_list.as_ref().is_some_and(|list| list.replace),
+            pinned_server: config.pinned_server.is_some(),
+        }
+    }
+}
+
+/// Bytes a speed test transferred in each direction
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DataUsage {
+    pub downloaded_bytes: u64,
+    pub uploaded_bytes: u64,
+}
+
+impl DataUsage {
+    pub fn total(&self) -> u64 {
+        self.downloaded_bytes + self.uploaded_bytes
+    }
+}
+
+/// What the interface carrying the default route counted while a test ran. Errors
+/// and drops point at a noisy cable, a failing port or a duplex mismatch long
+/// before the speed shows it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LinkStats {
+    pub interface: String,
+    /// Negotiated link speed, None for Wi-Fi and virtual interfaces
+    pub link_speed_mbps: Option<u32>,
+    /// "full" or "half", None where the driver doesn't say
+    pub duplex: Option<String>,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    /// Frames that arrived damaged, the usual sign of a bad cable
+    pub rx_crc_errors: u64,
+    /// Only happen on half duplex links, so a sign of a duplex mismatch
+    pub collisions: u64,
+    /// TCP segments the whole machine sent and retransmitted meanwhile
+    pub tcp_segments_sent: u64,
+    pub tcp_retransmits: u64,
+}
+
+impl LinkStats {
+    /// Errors and drops on the interface in either direction
+    pub fn error_count(&self) -> u64 {
+        self.rx_errors + self.tx_errors + self.rx_dropped + self.tx_dropped + self.collisions
+    }
+
+    /// Share of sent TCP segments that had to be sent again
+    pub fn retransmit_percent(&self) -> f64 {
+        if self.tcp_segments_sent == 0 {
+            return 0.0;
+        }
+        self.tcp_retransmits as f64 / self.tcp_segments_sent as f64 * 100.0
+    }
+
+    pub fn is_half_duplex(&self) -> bool {
+        self.duplex.as_deref() == Some("half")
+    }
+
+    /// Whether the counters point at a link problem. Some retransmits are normal
+    /// while TCP probes for the link's limit, more than 1% are not.
+    pub fn has_problems(&self) -> bool {
+        self.error_count() > 0 || self.is_half_duplex() || self.retransmit_percent() > 1.0
+    }
+}
+
+/// How the test's TCP connections fared, sampled from the kernel while they ran.
+/// Shown at the debug detail level, it tells loss-limited throughput apart from
+/// a slow line even when the speed looks fine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TcpHealth {
+    pub connections: usize,
+    pub samples: usize,
+    /// Segments the connections sent, None where the platform doesn't count them
+    pub segments_sent: Option<u64>,
+    pub retransmits: u64,
+    pub rtt_min_ms: f64,
+    pub rtt_median_ms: f64,
+    pub rtt_max_ms: f64,
+    /// Median congestion window of the busy connections
+    pub cwnd_median_bytes: u64,
+}
+
+impl TcpHealth {
+    /// Share of the sent segments that had to be sent again
+    pub fn retransmit_percent(&self) -> Option<f64> {
+        self.segments_sent
+            .filter(|sent| *sent > 0)
+            .map(|sent| self.retransmits as f64 / sent as f64 * 100.0)
+    }
+
+    /// More than 1% of segments lost: every loss halves the congestion window, so
+    /// throughput is capped by the loss rather than by the line
+    pub fn is_loss_limited(&self) -> bool {
+        self.retransmit_percent()
+            .is_some_and(|percent| percent > 1.0)
+    }
+
+    /// How much longer round trips took under load than at best, 1.0 when they
+    /// didn't grow at all
+    pub fn rtt_inflation(&self) -> f64 {
+        if self.rtt_min_ms <= 0.0 {
+            return 1.0;
+        }
+        self.rtt_median_ms / self.rtt_min_ms
+    }
+}
+
+/// Traffic on the router's WAN interface while the throughput phases ran, from
+/// its SNMP counters. All devices' traffic counts, not just the test's.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WanUsage {
+    pub interface: String,
+    /// `ifHighSpeed` of the interface, None when the router reports none
+    pub port_speed_mbps: Option<u64>,
+    /// Inbound during the download phase, None without two polls in the phase
+    pub download: Option<WanRate>,
+    /// Outbound during the upload phase
+    pub upload: Option<WanRate>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WanRate {
+    pub average_mbps: f64,
+    /// Fastest interval between two polls
+    pub peak_mbps: f64,
+}
+
+/// Where a throughput phase was limited, judged from the WAN traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WanVerdict {
+    /// The WAN reached the plan or its port speed: the ISP link is the limit
+    LinkSaturated,
+    /// The WAN carried clearly more than the test, other devices took a share
+    SharedWithOtherTraffic,
+    /// The WAN had room to spare below the plan: the limit is between this
+    /// machine and the router, e.g. Wi-Fi
+    LocalBottleneck,
+    /// Without a plan, a WAN below its port speed doesn't tell either way
+    Inconclusive,
+}
+
+impl WanRate {
+    /// What limited a phase that measured `test_mbps`, against the advertised
+    /// `plan_mbps` when there is one and the port speed otherwise
+    pub fn verdict(
+        &self,
+        test_mbps: f64,
+        plan_mbps: Option<f64>,
+        port_speed_mbps: Option<u64>,
+    ) -> WanVerdict {
+        let capacity = plan_mbps.or(port_speed_mbps.map(|speed| speed as f64));
+        if capacity.is_some_and(|capacity| self.peak_mbps >= capacity * 0.9) {
+            WanVerdict::LinkSaturated
+        } else if OtherTraffic::took_share(self.average_mbps - test_mbps, test_mbps) {
+            WanVerdict::SharedWithOtherTraffic
+        } else if plan_mbps.is_some() {
+            WanVerdict::LocalBottleneck
+        } else {
+            WanVerdict::Inconclusive
+        }
+    }
+}
+
+/// Traffic other devices put on the WAN while the test ran: what the router
+/// counted beyond the test's own throughput
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OtherTraffic {
+    /// Inbound beyond the test during the download phase, None without WAN samples
+    pub download_mbps: Option<f64>,
+    /// Outbound beyond the test during the upload phase
+    pub upload_mbps: Option<f64>,
+    /// Other devices in the ARP table after the test, None when it couldn't be read
+    pub lan_devices: Option<usize>,
+}
+
+impl OtherTraffic {
+    /// Split the WAN traffic of each phase into the test's and everyone else's
+    pub fn from_wan(wan: &WanUsage, result: &SpeedTestResult, lan_devices: Option<usize>) -> Self {
+        let beyond = |rate: Option<WanRate>, test_mbps: f64| {
+            rate.map(|rate| (rate.average_mbps - test_mbps).max(0.0))
+        };
+        Self {
+            download_mbps: beyond(wan.download, result.download_mbps),
+            upload_mbps: beyond(wan.upload, result.upload_mbps),
+            lan_devices,
+        }
+    }
+
+    /// Whether other devices took enough of a phase that its speed understates the
+    /// line, judged like `WanVerdict::SharedWithOtherTraffic`
+    pub fn affected(&self, result: &SpeedTestResult) -> bool {
+        self.download_mbps
+            .is_some_and(|other| Self::took_share(other, result.download_mbps))
+            || self
+                .upload_mbps
+                .is_some_and(|other| Self::took_share(other, result.upload_mbps))
+    }
+
+    /// Other traffic of at least 5 Mbps and a fifth of what the test measured
+    pub fn took_share(other_mbps: f64, test_mbps: f64) -> bool {
+        other_mbps >= 5.0 && other_mbps >= test_mbps * 0.2
+    }
+}
+
+impl std::ops::Add for DataUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            downloaded_bytes: self.downloaded_bytes + other.downloaded_bytes,
+            uploaded_bytes: self.uploaded_bytes + other.uploaded_bytes,
+        }
+    }
+}
+
+/// Throughput phase of a speed test
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedPhase {
+    #[strum(to_string = "Download")]
+    Download,
+    #[strum(to_string = "Upload")]
+    Upload,
+}
+
+/// How a throughput phase ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhaseOutcome {
+    Measured(f64),
+    Failed(String),
+}
+
+impl PhaseOutcome {
+    pub fn mbps(&self) -> Option<f64> {
+        match self {
+            PhaseOutcome::Measured(mbps) => Some(*mbps),
+            PhaseOutcome::Failed(_) => None,
+        }
+    }
+}
+
+/// Time left in a running throughput phase and until the whole test has its result,
+/// written as a `progress` event with `--progress-events`
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(tag = "event", rename = "progress")]
+pub struct PhaseCountdown {
+    pub phase: SpeedPhase,
+    pub phase_remaining_seconds: f64,
+    pub eta_seconds: f64,
+}
+
+impl PhaseCountdown {
+    /// `elapsed` into a phase planned to run for `duration`, with `after` of the
+    /// test still to come once it ends
+    pub fn new(
+        phase: SpeedPhase,
+        duration: std::time::Duration,
+        elapsed: std::time::Duration,
+        after: std::time::Duration,
+    ) -> Self {
+        let remaining = duration.saturating_sub(elapsed);
+        Self {
+            phase,
+            phase_remaining_seconds: remaining.as_secs_f64(),
+            eta_seconds: (remaining + after).as_secs_f64(),
+        }
+    }
+
+    /// Short form for the live monitor, e.g. "12s left, result in ~30s"
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.0}s left, result in ~{:.0}s",
+            self.phase_remaining_seconds.ceil(),
+            self.eta_seconds.ceil()
+        )
+    }
+}
+
+/// A throughput phase that produced no measurement on any server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PhaseFailure {
+    pub phase: SpeedPhase,
+    pub reason: String,
+}
+
+/// A phase whose connections all failed, restarted on the next selected server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerFailover {
+    pub phase: SpeedPhase,
+    pub failed_server: String,
+    pub fallback_server: String,
 }
 
 impl Default for SpeedTestResult {
@@ -72,12 +652,525 @@ impl Default for SpeedTestResult {
             quality: ConnectionQuality::Failed,
             test_duration_seconds: 0.0,
             isp: None,
+            latency_stats: None,
+            host_label: None,
+            site: None,
+            failovers: Vec::new(),
+            failures: Vec::new(),
+            data_used: None,
+            endpoints: Vec::new(),
+            engine: None,
+            country: None,
+            link_stats: None,
+            other_traffic: None,
+            tags: Vec::new(),
+            transport: None,
+            websocket_latency: None,
+            one_way_delay: None,
+            jitter_method: JitterMethod::Rfc3550,
         }
     }
 }
 
-/// Represents a test server for speed testing
+impl SpeedTestResult {
+    /// Whether other devices took enough of the WAN during the test that its speed
+    /// understates the line
+    pub fn shared_line(&self) -> bool {
+        self.other_traffic
+            .as_ref()
+            .is_some_and(|other| other.affected(self))
+    }
+
+    /// Suitability of this connection for everyday use cases
+    pub fn use_case_scores(&self) -> UseCaseScores {
+        UseCaseScores::from_result(self)
+    }
+
+    /// Returns true if the result was recorded no longer than `max_age` ago
+    pub fn is_fresher_than(&self, max_age: std::time::Duration) -> bool {
+        let age = Utc::now().signed_duration_since(self.timestamp);
+        match chrono::Duration::from_std(max_age) {
+            Ok(max_age) => age <= max_age,
+            Err(_) => true, // Max age too large to represent - everything is fresh
+        }
+    }
+
+    /// The result together with derived figures that are not stored in history
+    pub fn report(&self) -> ResultReport<'_> {
+        ResultReport {
+            result: self,
+            use_cases: self.use_case_scores(),
+        }
+    }
+
+    /// A phase failed outright, so the result does not describe the connection
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+    }
+}
+
+/// A result as printed with `--json`: the stored fields plus derived use-case scores
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultReport<'a> {
+    #[serde(flatten)]
+    pub result: &'a SpeedTestResult,
+    pub use_cases: UseCaseScores,
+}
+
+/// Median of several complete test runs, stored next to the individual results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateResult {
+    pub run_count: usize,
+    /// When each run finished, oldest first
+    pub run_timestamps: Vec<DateTime<Utc>>,
+    /// Median of every metric, timestamped with the last run
+    pub median: SpeedTestResult,
+}
+
+impl AggregateResult {
+    /// Combine runs into medians, returns None when there are no runs
+    pub fn from_runs(runs: &[SpeedTestResult]) -> Option<Self> {
+        let first = runs.first()?;
+        let last = runs.iter().max_by_key(|r| r.timestamp)?;
+        let metric =
+            |f: fn(&SpeedTestResult) -> f64| median(&runs.iter().map(f).collect::<Vec<_>>());
+
+        let download_mbps = metric(|r| r.download_mbps);
+        let upload_mbps = metric(|r| r.upload_mbps);
+        let ping_ms = metric(|r| r.ping_ms);
+        let jitter_ms = metric(|r| r.jitter_ms);
+        let packet_loss_percent = metric(|r| r.packet_loss_percent);
+        let same_server = runs
+            .iter()
+            .all(|r| r.server_location == first.server_location);
+
+        let mut run_timestamps: Vec<_> = runs.iter().map(|r| r.timestamp).collect();
+        run_timestamps.sort();
+
+        Some(Self {
+            run_count: runs.len(),
+            run_timestamps,
+            median: SpeedTestResult {
+                timestamp: last.timestamp,
+                download_mbps,
+                upload_mbps,
+                ping_ms,
+                jitter_ms,
+                packet_loss_percent,
+                server_location: if same_server {
+                    first.server_location.clone()
+                } else {
+                    "Multiple servers".to_string()
+                },
+                server_ip: if same_server { first.server_ip } else { None },
+                client_ip: first.client_ip,
+                quality: ConnectionQuality::from_metrics(
+                    download_mbps,
+                    upload_mbps,
+                    ping_ms,
+                    jitter_ms,
+                    packet_loss_percent,
+                ),
+                test_duration_seconds: runs.iter().map(|r| r.test_duration_seconds).sum(),
+                isp: first.isp.clone(),
+                latency_stats: None,
+                host_label: first.host_label.clone(),
+                site: first.site.clone(),
+                failovers: runs.iter().flat_map(|r| r.failovers.clone()).collect(),
+                failures: runs.iter().flat_map(|r| r.failures.clone()).collect(),
+                // Every run used the data, so the series total is what it cost
+                data_used: runs.iter().filter_map(|r| r.data_used).reduce(|a, b| a + b),
+                // Endpoint latencies are not summarised, the latest run's stand for the series
+                endpoints: last.endpoints.clone(),
+                engine: last.engine.clone(),
+                country: first.country.clone(),
+                // Like the endpoints, the latest run's counters stand for the series
+                link_stats: last.link_stats.clone(),
+                other_traffic: last.other_traffic.clone(),
+                tags: first.tags.clone(),
+                transport: last.transport.clone(),
+                websocket_latency: last.websocket_latency.clone(),
+                one_way_delay: last.one_way_delay.clone(),
+                // A series runs on one backend, so its runs share the method
+                jitter_method: first.jitter_method,
+            },
+        })
+    }
+}
+
+/// A careful calibration run kept under a name, later tests are compared with it
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Longer test over more servers with the usual number of streams
+    pub multi_stream: SpeedTestResult,
+    /// One stream each way, far below `multi_stream` when a single connection is limited
+    pub single_stream: SpeedTestResult,
+}
+
+impl Baseline {
+    /// How `result` differs from the baseline's multi-stream run
+    pub fn deviation(&self, result: &SpeedTestResult) -> BaselineDeviation {
+        let percent = |value: f64, reference: f64| {
+            (reference > 0.0).then(|| (value - reference) / reference * 100.0)
+        };
+        BaselineDeviation {
+            baseline: self.name.clone(),
+            download_percent: percent(result.download_mbps, self.multi_stream.download_mbps),
+            upload_percent: percent(result.upload_mbps, self.multi_stream.upload_mbps),
+            ping_ms: result.ping_ms - self.multi_stream.ping_ms,
+        }
+    }
+
+    /// Single-stream download as a share of the multi-stream download, in percent
+    pub fn single_stream_percent(&self) -> Option<f64> {
+        (self.multi_stream.download_mbps > 0.0)
+            .then(|| self.single_stream.download_mbps / self.multi_stream.download_mbps * 100.0)
+    }
+}
+
+/// A result compared with a baseline, positive percentages are faster
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BaselineDeviation {
+    pub baseline: String,
+    /// `None` when the baseline measured no download
+    pub download_percent: Option<f64>,
+    pub upload_percent: Option<f64>,
+    /// Milliseconds above the baseline's ping
+    pub ping_ms: f64,
+}
+
+/// Median of `values`, 0.0 when empty
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Mean difference between consecutive latencies, 0.0 with fewer than two
+pub fn consecutive_jitter(latencies: &[f64]) -> f64 {
+    if latencies.len() < 2 {
+        return 0.0;
+    }
+    latencies
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).abs())
+        .sum::<f64>()
+        / (latencies.len() - 1) as f64
+}
+
+/// Share of `sent` probes that were `lost` in percent, 0.0 when none were sent
+pub fn percent_lost(lost: usize, sent: usize) -> f64 {
+    if sent == 0 {
+        0.0
+    } else {
+        lost as f64 / sent as f64 * 100.0
+    }
+}
+
+/// Interarrival jitter of RFC 3550 (section 6.4.1) over transit times in the order
+/// the packets were sent: each change from one transit to the next moves the
+/// estimate by a sixteenth of its difference, so a single outlier barely does.
+/// 0.0 with fewer than two transits.
+pub fn interarrival_jitter(transits: &[f64]) -> f64 {
+    transits.windows(2).fold(0.0, |jitter, pair| {
+        jitter + ((pair[1] - pair[0]).abs() - jitter) / 16.0
+    })
+}
+
+/// Everyday activities a connection is rated for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+pub enum UseCase {
+    #[strum(to_string = "Video calls (Zoom/Teams)")]
+    VideoCalls,
+    #[strum(to_string = "Online gaming")]
+    Gaming,
+    #[strum(to_string = "Cloud gaming")]
+    CloudGaming,
+    #[strum(to_string = "4K streaming")]
+    Streaming4K,
+    #[strum(to_string = "8K streaming")]
+    Streaming8K,
+    #[strum(to_string = "Large uploads")]
+    LargeUploads,
+}
+
+/// Minimum connection needed for a use case
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UseCaseRequirements {
+    pub min_download_mbps: f64,
+    pub min_upload_mbps: f64,
+    pub max_ping_ms: f64,
+    pub max_jitter_ms: f64,
+    pub max_packet_loss_percent: f64,
+}
+
+/// How well a result suits a use case, and what holds it back if it falls short
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UseCaseVerdict {
+    pub use_case: UseCase,
+    /// 0-100: meeting every requirement exactly scores 70, twice the headroom scores 100
+    pub score: u8,
+    pub suitable: bool,
+    /// The metric furthest from its requirement, when one is missed
+    pub limited_by: Option<String>,
+}
+
+impl UseCase {
+    pub const ALL: [UseCase; 6] = [
+        UseCase::VideoCalls,
+        UseCase::Gaming,
+        UseCase::CloudGaming,
+        UseCase::Streaming4K,
+        UseCase::Streaming8K,
+        UseCase::LargeUploads,
+    ];
+
+    /// Score at which every requirement is met exactly
+    pub const PASS_SCORE: u8 = 70;
+
+    pub fn requirements(&self) -> UseCaseRequirements {
+        match self {
+            // HD group calls (Zoom/Teams recommendations)
+            UseCase::VideoCalls => UseCaseRequirements {
+                min_download_mbps: 4.0,
+                min_upload_mbps: 3.0,
+                max_ping_ms: 150.0,
+                max_jitter_ms: 30.0,
+                max_packet_loss_percent: 2.0,
+            },
+            // Competitive play is about responsiveness, not bandwidth
+            UseCase::Gaming => UseCaseRequirements {
+                min_download_mbps: 5.0,
+                min_upload_mbps: 1.0,
+                max_ping_ms: 50.0,
+                max_jitter_ms: 20.0,
+                max_packet_loss_percent: 1.0,
+            },
+            // 4K game streams (GeForce NOW, Xbox Cloud) need both bandwidth and low latency
+            UseCase::CloudGaming => UseCaseRequirements {
+                min_download_mbps: 35.0,
+                min_upload_mbps: 2.0,
+                max_ping_ms: 40.0,
+                max_jitter_ms: 15.0,
+                max_packet_loss_percent: 1.0,
+            },
+            // Players buffer ahead, so latency and jitter barely matter
+            UseCase::Streaming4K => UseCaseRequirements {
+                min_download_mbps: 25.0,
+                min_upload_mbps: 0.0,
+                max_ping_ms: f64::INFINITY,
+                max_jitter_ms: f64::INFINITY,
+                max_packet_loss_percent: 2.0,
+            },
+            UseCase::Streaming8K => UseCaseRequirements {
+                min_download_mbps: 100.0,
+                min_upload_mbps: 0.0,
+                max_ping_ms: f64::INFINITY,
+                max_jitter_ms: f64::INFINITY,
+                max_packet_loss_percent: 2.0,
+            },
+            // Backups and video uploads, 1 GB in under 7 minutes
+            UseCase::LargeUploads => UseCaseRequirements {
+                min_download_mbps: 0.0,
+                min_upload_mbps: 20.0,
+                max_ping_ms: f64::INFINITY,
+                max_jitter_ms: f64::INFINITY,
+                max_packet_loss_percent: 2.0,
+            },
+        }
+    }
+
+    pub fn verdict(&self, result: &SpeedTestResult) -> UseCaseVerdict {
+        let req = self.requirements();
+        let metrics = [
+            (
+                headroom_score(result.download_mbps / req.min_download_mbps),
+                format!("download below {} Mbps", req.min_download_mbps),
+            ),
+            (
+                headroom_score(result.upload_mbps / req.min_upload_mbps),
+                format!("upload below {} Mbps", req.min_upload_mbps),
+            ),
+            (
+                headroom_score(req.max_ping_ms / result.ping_ms),
+                format!("ping above {} ms", req.max_ping_ms),
+            ),
+            (
+                headroom_score(req.max_jitter_ms / result.jitter_ms),
+                format!("jitter above {} ms", req.max_jitter_ms),
+            ),
+            (
+                headroom_score(req.max_packet_loss_percent / result.packet_loss_percent),
+                format!("packet loss above {}%", req.max_packet_loss_percent),
+            ),
+        ];
+
+        let (score, weakest) = metrics
+            .into_iter()
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .unwrap_or((100.0, String::new()));
+        let suitable = score >= Self::PASS_SCORE as f64;
+
+        UseCaseVerdict {
+            use_case: *self,
+            score: score.round() as u8,
+            suitable,
+            limited_by: (!suitable).then_some(weakest),
+        }
+    }
+}
+
+/// Score one metric from its ratio to the requirement (>= 1 means met).
+/// A zero requirement or a zero measurement against a limit gives an infinite ratio.
+fn headroom_score(ratio: f64) -> f64 {
+    if ratio.is_nan() || ratio >= 2.0 {
+        // NaN is 0/0, e.g. no loss measured against a zero-loss limit
+        100.0
+    } else if ratio >= 1.0 {
+        UseCase::PASS_SCORE as f64 + 30.0 * (ratio - 1.0)
+    } else {
+        UseCase::PASS_SCORE as f64 * ratio.max(0.0)
+    }
+}
+
+/// Suitability scores for the everyday use cases, shown under
+/// "What can I do with this connection?" and included in JSON output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UseCaseScores {
+    pub video_calls: UseCaseVerdict,
+    pub online_gaming: UseCaseVerdict,
+    pub cloud_gaming: UseCaseVerdict,
+    pub streaming_4k: UseCaseVerdict,
+    pub streaming_8k: UseCaseVerdict,
+    pub large_uploads: UseCaseVerdict,
+}
+
+impl UseCaseScores {
+    pub fn from_result(result: &SpeedTestResult) -> Self {
+        let [video_calls, online_gaming, cloud_gaming, streaming_4k, streaming_8k, large_uploads] =
+            UseCase::ALL.map(|use_case| use_case.verdict(result));
+
+        Self {
+            video_calls,
+            online_gaming,
+            cloud_gaming,
+            streaming_4k,
+            streaming_8k,
+            large_uploads,
+        }
+    }
+
+    /// Verdicts in display order
+    pub fn iter(&self) -> impl Iterator<Item = &UseCaseVerdict> {
+        [
+            &self.video_calls,
+            &self.online_gaming,
+            &self.cloud_gaming,
+            &self.streaming_4k,
+            &self.streaming_8k,
+            &self.large_uploads,
+        ]
+        .into_iter()
+    }
+}
+
+/// Distribution of the latency samples collected during the latency and jitter phases
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatencyStats {
+    pub sample_count: usize,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub histogram: Vec<LatencyBucket>,
+}
+
+/// A single histogram bucket covering `[lower_ms, upper_ms)`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatencyBucket {
+    pub lower_ms: f64,
+    pub upper_ms: f64,
+    pub count: usize,
+}
+
+impl LatencyStats {
+    /// Target number of histogram buckets; the width is rounded to a readable step
+    const TARGET_BUCKETS: f64 = 10.0;
+
+    /// Build statistics from raw samples, returns None when there are no samples
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let min_ms = sorted[0];
+        let max_ms = sorted[sorted.len() - 1];
+        let avg_ms = sorted.iter().sum::<f64>() / sorted.len() as f64;
+
+        Some(Self {
+            sample_count: sorted.len(),
+            min_ms,
+            avg_ms,
+            p95_ms: Self::percentile(&sorted, 95.0),
+            p99_ms: Self::percentile(&sorted, 99.0),
+            max_ms,
+            histogram: Self::build_histogram(&sorted, min_ms, max_ms),
+        })
+    }
+
+    /// Nearest-rank percentile over already sorted samples
+    fn percentile(sorted: &[f64], p: f64) -> f64 {
+        let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    fn build_histogram(sorted: &[f64], min_ms: f64, max_ms: f64) -> Vec<LatencyBucket> {
+        // Pick a 1/2/5 x 10^n bucket width so labels stay readable
+        let raw_width = ((max_ms - min_ms) / Self::TARGET_BUCKETS).max(1.0);
+        let magnitude = 10f64.powf(raw_width.log10().floor());
+        let width = [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|step| step * magnitude)
+            .find(|w| *w >= raw_width)
+            .unwrap_or(10.0 * magnitude);
+
+        let start = (min_ms / width).floor() * width;
+        let bucket_count = (((max_ms - start) / width).floor() as usize) + 1;
+
+        let mut histogram: Vec<LatencyBucket> = (0..bucket_count)
+            .map(|i| LatencyBucket {
+                lower_ms: start + i as f64 * width,
+                upper_ms: start + (i + 1) as f64 * width,
+                count: 0,
+            })
+            .collect();
+
+        for sample in sorted {
+            let index = (((sample - start) / width).floor() as usize).min(bucket_count - 1);
+            histogram[index].count += 1;
+        }
+
+        histogram
+    }
+}
+
+/// Represents a test server for speed testing
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TestServer {
     pub name: String,
     pub url: String,
@@ -85,11 +1178,53 @@ pub struct TestServer {
     pub distance_km: Option<f64>,
     pub latency_ms: Option<f64>,
     pub provider: ServerProvider,
+    #[serde(default)]
+    pub protocol: ServerProtocol,
     pub capabilities: ServerCapabilities,
     pub quality_score: Option<f64>,
     pub country_code: Option<String>,
     pub city: Option<String>,
     pub is_backup: bool,
+    /// Paths of a `custom` server
+    #[serde(default)]
+    pub endpoints: Option<CustomEndpoints>,
+}
+
+impl TestServer {
+    /// Host name of the server's URL, the whole URL if it has none
+    pub fn host(&self) -> String {
+        url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .unwrap_or_else(|| self.url.clone())
+    }
+
+    /// Requests this server answers for transfers and latency probes
+    pub fn backend(&self) -> &'static dyn TestProvider {
+        backend::for_protocol(self.protocol)
+    }
+}
+
+/// Endpoints a server offers for throughput tests
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerProtocol {
+    /// `__down?bytes=N` and `__up`, as served by speed.cloudflare.com
+    #[default]
+    #[strum(to_string = "cloudflare")]
+    Cloudflare,
+    /// `garbage.php` and `empty.php` of a LibreSpeed backend
+    #[strum(to_string = "librespeed")]
+    LibreSpeed,
+    /// Signed URLs of Netflix caches from fast.com, with `/range/0-N` downloads
+    #[strum(to_string = "fast")]
+    Fast,
+    /// `download?size=N`, `upload` and `hello` of a speedtest.net server
+    #[strum(to_string = "ookla")]
+    Ookla,
+    /// Paths from a server list's `[servers.endpoints]` table
+    #[strum(to_string = "custom")]
+    Custom,
 }
 
 /// Different server providers for speed testing
@@ -99,11 +1234,129 @@ pub enum ServerProvider {
     Google,
     Netflix,
     Ookla,
+    /// Listed in the user's `--server-list` file
+    SelfHosted,
     Custom(String),
 }
 
-/// Server capabilities for different types of tests
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::fmt::Display for ServerProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerProvider::Cloudflare => write!(f, "Cloudflare"),
+            ServerProvider::Google => write!(f, "Google"),
+            ServerProvider::Netflix => write!(f, "Netflix"),
+            ServerProvider::Ookla => write!(f, "Ookla"),
+            ServerProvider::SelfHosted => write!(f, "Self-hosted"),
+            ServerProvider::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl std::str::FromStr for ServerProvider {
+    type Err = String;
+
+    /// Known providers by name in any case, any other name is a custom provider
+    /// such as a speedtest.net sponsor
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "" => Err("Provider name is empty".to_string()),
+            "cloudflare" => Ok(ServerProvider::Cloudflare),
+            "google" => Ok(ServerProvider::Google),
+            "netflix" => Ok(ServerProvider::Netflix),
+            "ookla" => Ok(ServerProvider::Ookla),
+            "self-hosted" | "selfhosted" => Ok(ServerProvider::SelfHosted),
+            _ => Ok(ServerProvider::Custom(name.to_string())),
+        }
+    }
+}
+
+impl ServerProvider {
+    /// Same provider, custom names compared without regard to case
+    pub fn matches(&self, other: &ServerProvider) -> bool {
+        match (self, other) {
+            (ServerProvider::Custom(a), ServerProvider::Custom(b)) => a.eq_ignore_ascii_case(b),
+            _ => self == other,
+        }
+    }
+}
+
+/// Which servers may join the pool, the `[servers]` table in config.toml with
+/// `--only-provider` and `--exclude-provider` replacing its provider lists
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ServerFilter {
+    /// Providers allowed into the pool, every provider when empty
+    #[serde(with = "provider_names")]
+    pub only_providers: Vec<ServerProvider>,
+    /// Providers never tested against
+    #[serde(with = "provider_names")]
+    pub exclude_providers: Vec<ServerProvider>,
+    /// Hostnames never tested against, each also excluding its subdomains
+    pub exclude_hosts: Vec<String>,
+}
+
+impl ServerFilter {
+    pub fn allows(&self, server: &TestServer) -> bool {
+        if !self.only_providers.is_empty()
+            && !self
+                .only_providers
+                .iter()
+                .any(|p| p.matches(&server.provider))
+        {
+            return false;
+        }
+        if self
+            .exclude_providers
+            .iter()
+            .any(|p| p.matches(&server.provider))
+        {
+            return false;
+        }
+
+        let host = url::Url::parse(&server.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+        match host {
+            Some(host) => !self.exclude_hosts.iter().any(|excluded| {
+                let excluded = excluded.trim().trim_start_matches('.').to_ascii_lowercase();
+                host == excluded || host.ends_with(&format!(".{}", excluded))
+            }),
+            None => true,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.only_providers.is_empty()
+            && self.exclude_providers.is_empty()
+            && self.exclude_hosts.is_empty()
+    }
+}
+
+/// Providers written by name in config files, e.g. `["Google", "Netflix"]`
+mod provider_names {
+    use super::ServerProvider;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        providers: &[ServerProvider],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(providers.iter().map(ToString::to_string))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<ServerProvider>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|name| name.parse().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// Server capabilities for different types of tests
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ServerCapabilities {
     pub supports_download: bool,
     pub supports_upload: bool,
@@ -112,37 +1365,1729 @@ pub struct ServerCapabilities {
     pub geographic_weight: f64, // Higher means better for geographic tests
 }
 
+impl ServerCapabilities {
+    pub fn supports(&self, phase: SpeedPhase) -> bool {
+        match phase {
+            SpeedPhase::Download => self.supports_download,
+            SpeedPhase::Upload => self.supports_upload,
+        }
+    }
+}
+
+/// Servers from a `--server-list` file, used alongside or instead of the public pool
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServerList {
+    /// Test only against the listed servers instead of adding them to the pool
+    #[serde(default)]
+    pub replace: bool,
+    pub servers: Vec<CustomServer>,
+}
+
+impl ServerList {
+    pub fn load_from(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read server list {}: {}", path.display(), e))?;
+        Self::parse(&contents)
+            .map_err(|e| format!("Invalid server list {}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let list: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+        if list.servers.is_empty() {
+            return Err("no [[servers]] entries".to_string());
+        }
+        for server in &list.servers {
+            let url = url::Url::parse(&server.url)
+                .map_err(|e| format!("server '{}': invalid url: {}", server.name, e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!(
+                    "server '{}': url must be http or https",
+                    server.name
+                ));
+            }
+            if (server.protocol == ServerProtocol::Custom) != server.endpoints.is_some() {
+                return Err(format!(
+                    "server '{}': protocol \"custom\" goes with a [servers.endpoints] table",
+                    server.name
+                ));
+            }
+            if server.latitude.is_some() != server.longitude.is_some() {
+                return Err(format!(
+                    "server '{}': set both latitude and longitude or neither",
+                    server.name
+                ));
+            }
+        }
+        Ok(list)
+    }
+}
+
+/// One `[[servers]]` entry of a server list
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomServer {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub protocol: ServerProtocol,
+    /// Shown next to the name, e.g. "Frankfurt office"
+    pub location: Option<String>,
+    /// Coordinates to rank the server by distance, without them it is ranked on latency
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub country_code: Option<String>,
+    #[serde(default = "CustomServer::enabled")]
+    pub download: bool,
+    #[serde(default = "CustomServer::enabled")]
+    pub upload: bool,
+    #[serde(default = "CustomServer::default_max_test_size_mb")]
+    pub max_test_size_mb: u64,
+    /// Required with `protocol = "custom"`
+    pub endpoints: Option<CustomEndpoints>,
+}
+
+/// Where a `custom` server answers, each a path below its URL or a URL of its own
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomEndpoints {
+    /// Download of about `{bytes}` bytes, or a fixed file without the placeholder
+    pub download: String,
+    /// Accepts and discards a POSTed body
+    pub upload: String,
+    /// Answers HEAD quickly, the server's URL when unset
+    pub latency: Option<String>,
+    /// WebSocket timed with pings alongside the HTTP probes, over TLS only
+    pub websocket: Option<String>,
+}
+
+impl CustomServer {
+    fn enabled() -> bool {
+        true
+    }
+
+    fn default_max_test_size_mb() -> u64 {
+        2000
+    }
+}
+
 /// Represents detailed network diagnostics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkDiagnostics {
     pub gateway_ip: Option<IpAddr>,
     pub dns_servers: Vec<IpAddr>,
+    /// Average over the names in `dns_lookups` that resolved
     pub dns_response_time_ms: f64,
+    /// How long each configured name took to resolve with the system's resolvers
+    #[serde(default)]
+    pub dns_lookups: Vec<DnsLookup>,
+    /// A, AAAA and CNAME chain of each name, each asked twice to expose resolver caching
+    #[serde(default)]
+    pub dns_records: Vec<DnsRecordTiming>,
+    /// Hops to the first trace target, `routes` holds every target
     pub route_hops: Vec<RouteHop>,
     pub is_ipv6_available: bool,
     pub connection_type: Option<String>,
     pub network_interface: Option<String>,
+    /// TLS handshakes against the test servers and any `--tls-host`
+    #[serde(default)]
+    pub tls_checks: Vec<TlsCheck>,
+    /// Public resolvers timed over plain and encrypted DNS
+    #[serde(default)]
+    pub resolver_benchmarks: Vec<ResolverBenchmark>,
+    /// DNSSEC validation and DNS interception checks
+    #[serde(default)]
+    pub dns_security: DnsSecurityCheck,
+    /// Paths to each of the trace targets
+    #[serde(default)]
+    pub routes: Vec<TracedRoute>,
+    /// Networks hosting the speed test servers
+    #[serde(default)]
+    pub server_networks: Vec<HostNetwork>,
+    /// Round trips to the gateway next to those to the internet
+    #[serde(default)]
+    pub latency_baseline: LatencyBaseline,
+    /// System clock compared with an NTP server, `None` when no server answered
+    #[serde(default)]
+    pub clock_check: Option<ClockCheck>,
+    /// IPv6 against IPv4 on dual-stack sites, raced the way browsers do
+    #[serde(default)]
+    pub happy_eyeballs: Vec<HappyEyeballsCheck>,
+    /// Windows TCP, offload and power settings, None on other platforms
+    #[serde(default)]
+    pub windows_stack: Option<WindowsStackCheck>,
+}
+
+/// Speed test and diagnostics of one full analysis (`--mode full`), with the
+/// conclusions only the two together support
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullAnalysisResult {
+    pub timestamp: DateTime<Utc>,
+    pub speed: SpeedTestResult,
+    pub diagnostics: NetworkDiagnostics,
+    pub correlations: Vec<Correlation>,
+    /// Ranked by [`crate::modules::analysis::diagnose`], which also weighs history
+    #[serde(default)]
+    pub causes: Vec<ProbableCause>,
+}
+
+impl FullAnalysisResult {
+    /// Name lookups slower than this are worth a look
+    pub const SLOW_DNS_MS: f64 = 100.0;
+    /// Ping above this hurts calls and games
+    pub const HIGH_PING_MS: f64 = 50.0;
+    /// Loss during the speed test above this, in percent
+    pub const LOSS_PERCENT: f64 = 1.0;
+    /// Download below this on Wi-Fi suggests the radio link limits the line
+    pub const SLOW_WIRELESS_MBPS: f64 = 25.0;
+
+    pub fn new(speed: SpeedTestResult, diagnostics: NetworkDiagnostics) -> Self {
+        Self {
+            timestamp: speed.timestamp,
+            correlations: Self::correlate(&speed, &diagnostics),
+            causes: Vec::new(),
+            speed,
+            diagnostics,
+        }
+    }
+
+    /// Conclusions drawn from figures of both runs together
+    pub fn correlate(
+        speed: &SpeedTestResult,
+        diagnostics: &NetworkDiagnostics,
+    ) -> Vec<Correlation> {
+        let mut correlations = Vec::new();
+        let high_ping = speed.ping_ms > Self::HIGH_PING_MS;
+        let baseline = &diagnostics.latency_baseline;
+
+        if diagnostics.dns_response_time_ms > Self::SLOW_DNS_MS && high_ping {
+            correlations.push(Correlation::SlowResolver);
+        }
+        match baseline.bottleneck() {
+            Some(Bottleneck::Home) if high_ping => correlations.push(Correlation::HomeLatency),
+            Some(Bottleneck::Upstream) if high_ping => {
+                correlations.push(Correlation::UpstreamLatency)
+            }
+            _ => {}
+        }
+        if speed.packet_loss_percent > Self::LOSS_PERCENT
+            && baseline
+                .gateway
+                .as_ref()
+                .is_some_and(|gateway| gateway.lost == 0)
+        {
+            correlations.push(Correlation::UpstreamLoss);
+        }
+        let wireless = diagnostics
+            .connection_type
+            .as_deref()
+            .is_some_and(|kind| kind.to_ascii_lowercase().contains("wireless"));
+        if wireless && speed.download_mbps < Self::SLOW_WIRELESS_MBPS {
+            correlations.push(Correlation::SlowWireless);
+        }
+
+        correlations
+    }
+
+    /// The analysis as printed with `--json`, the speed test with its use-case scores
+    pub fn report(&self) -> FullAnalysisReport<'_> {
+        FullAnalysisReport {
+            timestamp: self.timestamp,
+            speed: self.speed.report(),
+            diagnostics: &self.diagnostics,
+            correlations: &self.correlations,
+            causes: &self.causes,
+        }
+    }
+}
+
+/// A full analysis as printed with `--json`
+#[derive(Debug, Clone, Serialize)]
+pub struct FullAnalysisReport<'a> {
+    pub timestamp: DateTime<Utc>,
+    pub speed: ResultReport<'a>,
+    pub diagnostics: &'a NetworkDiagnostics,
+    pub correlations: &'a [Correlation],
+    pub causes: &'a [ProbableCause],
+}
+
+/// What speed test and diagnostics figures point at together
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum Correlation {
+    /// Slow name lookups next to a high ping
+    #[strum(
+        to_string = "Slow DNS and high ping: the resolver is distant or overloaded, try a public one"
+    )]
+    SlowResolver,
+    /// High ping with a slow or jittery gateway
+    #[strum(to_string = "High ping starts at the router: Wi-Fi or the router adds the delay")]
+    HomeLatency,
+    /// High ping with a healthy gateway
+    #[strum(to_string = "High ping with a healthy router: the delay is on the line or at the ISP")]
+    UpstreamLatency,
+    /// Loss in the speed test while the gateway answered every probe
+    #[strum(
+        to_string = "Packet loss while the router answered every probe: loss is beyond your network"
+    )]
+    UpstreamLoss,
+    /// Low download over Wi-Fi
+    #[strum(
+        to_string = "Low download over Wi-Fi: test over a cable to see if the radio link is the limit"
+    )]
+    SlowWireless,
+}
+
+/// A diagnostics run kept in history, so its figures can be trended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsRecord {
+    pub timestamp: DateTime<Utc>,
+    pub diagnostics: NetworkDiagnostics,
+}
+
+impl DiagnosticsRecord {
+    pub fn new(diagnostics: NetworkDiagnostics) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            diagnostics,
+        }
+    }
+
+    /// Hops to the first trace target
+    pub fn route_length(&self) -> usize {
+        self.diagnostics.route_hops.len()
+    }
+
+    /// Median round trip to the gateway
+    pub fn gateway_ms(&self) -> Option<f64> {
+        self.diagnostics
+            .latency_baseline
+            .gateway
+            .as_ref()
+            .and_then(|link| link.median_ms)
+    }
+
+    /// Median round trip to the internet
+    pub fn internet_ms(&self) -> Option<f64> {
+        self.diagnostics
+            .latency_baseline
+            .internet
+            .as_ref()
+            .and_then(|link| link.median_ms)
+    }
+}
+
+/// One name resolved during diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DnsLookup {
+    pub domain: String,
+    /// `None` when the lookup failed
+    pub time_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// What a record timing resolved
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRecordKind {
+    #[strum(to_string = "A")]
+    A,
+    #[strum(to_string = "AAAA")]
+    Aaaa,
+    /// Every CNAME from the name to its canonical name, one query per hop
+    #[strum(to_string = "CNAME chain")]
+    CnameChain,
+}
+
+/// One record type of one name queried twice in a row: the resolver may have to
+/// fetch the first answer, the second should come from its cache
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DnsRecordTiming {
+    pub domain: String,
+    pub kind: DnsRecordKind,
+    /// `None` when the query failed
+    pub cold_ms: Option<f64>,
+    pub warm_ms: Option<f64>,
+    /// Addresses for A and AAAA, the names passed through for a CNAME chain
+    pub answers: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl DnsRecordTiming {
+    /// Milliseconds the resolver's cache saved on the repeated query
+    pub fn cache_saving_ms(&self) -> Option<f64> {
+        Some(self.cold_ms? - self.warm_ms?)
+    }
+}
+
+/// Latency inside the home network compared with latency to the internet
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyBaseline {
+    pub gateway: Option<LinkLatency>,
+    pub internet: Option<LinkLatency>,
+}
+
+/// Where a latency problem sits, seen from the gateway baseline
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum Bottleneck {
+    /// Wi-Fi or the router itself adds the delay
+    #[strum(to_string = "home network")]
+    Home,
+    /// The line or the ISP beyond the router adds the delay
+    #[strum(to_string = "upstream")]
+    Upstream,
+}
+
+impl LatencyBaseline {
+    /// Gateway round trips above this point at Wi-Fi or the router, wired LANs answer in 1-2 ms
+    pub const HOME_LATENCY_MS: f64 = 15.0;
+    pub const HOME_JITTER_MS: f64 = 10.0;
+    /// Internet round trips above this, with a healthy gateway, point upstream
+    pub const UPSTREAM_LATENCY_MS: f64 = 100.0;
+    pub const UPSTREAM_JITTER_MS: f64 = 30.0;
+
+    /// Which side of the router is slowing things down, `None` when both look healthy
+    pub fn bottleneck(&self) -> Option<Bottleneck> {
+        if let Some(gateway) = &self.gateway {
+            if gateway
+                .median_ms
+                .is_some_and(|ms| ms > Self::HOME_LATENCY_MS)
+                || gateway.jitter_ms > Self::HOME_JITTER_MS
+                || gateway.lost > 0
+            {
+                return Some(Bottleneck::Home);
+            }
+        }
+
+        let internet = self.internet.as_ref()?;
+        let home_ms = self
+            .gateway
+            .as_ref()
+            .and_then(|gateway| gateway.median_ms)
+            .unwrap_or(0.0);
+        (internet
+            .median_ms
+            .is_some_and(|ms| ms - home_ms > Self::UPSTREAM_LATENCY_MS)
+            || internet.jitter_ms > Self::UPSTREAM_JITTER_MS
+            || internet.lost > 0)
+            .then_some(Bottleneck::Upstream)
+    }
+}
+
+/// Repeated TCP handshakes with one host
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkLatency {
+    pub address: IpAddr,
+    pub port: u16,
+    pub sent: usize,
+    pub lost: usize,
+    pub median_ms: Option<f64>,
+    /// Mean difference between consecutive answered probes
+    pub jitter_ms: f64,
+}
+
+impl LinkLatency {
+    /// Summarise probe results in the order they were sent, `None` marking lost probes
+    pub fn from_probes(address: IpAddr, port: u16, probes: &[Option<f64>]) -> Self {
+        let answered: Vec<f64> = probes.iter().flatten().copied().collect();
+
+        Self {
+            address,
+            port,
+            sent: probes.len(),
+            lost: probes.len() - answered.len(),
+            median_ms: (!answered.is_empty()).then(|| median(&answered)),
+            jitter_ms: consecutive_jitter(&answered),
+        }
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        percent_lost(self.lost, self.sent)
+    }
+}
+
+/// A host the user cares about, such as a work VPN gateway or a game server,
+/// listed as `[[endpoints]]` in config.toml
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportantEndpoint {
+    pub name: String,
+    pub host: String,
+    /// TCP port the handshakes are timed against
+    #[serde(default = "ImportantEndpoint::default_port")]
+    pub port: u16,
+}
+
+impl ImportantEndpoint {
+    fn default_port() -> u16 {
+        443
+    }
+}
+
+/// Which services the speed test asks for the tester's location, the
+/// `[geolocation]` table in config.toml
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GeoSettings {
+    /// Set to false to ask only the custom providers
+    pub builtin: bool,
+    /// Built-in providers to skip by name, e.g. `["ip-api.com"]`
+    pub disabled: Vec<String>,
+    /// Self-hosted or other services, each a `[[geolocation.custom]]` table
+    pub custom: Vec<CustomGeoProvider>,
+}
+
+impl Default for GeoSettings {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            disabled: Vec::new(),
+            custom: Vec::new(),
+        }
+    }
+}
+
+/// A geolocation service answering a GET of `url` with JSON, its fields located
+/// by JSON pointers such as `/location/city`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomGeoProvider {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "CustomGeoProvider::default_country")]
+    pub country: String,
+    #[serde(default = "CustomGeoProvider::default_city")]
+    pub city: String,
+    /// Coordinates may be numbers or numeric strings
+    #[serde(default = "CustomGeoProvider::default_latitude")]
+    pub latitude: String,
+    #[serde(default = "CustomGeoProvider::default_longitude")]
+    pub longitude: String,
+    #[serde(default)]
+    pub isp: Option<String>,
+}
+
+impl CustomGeoProvider {
+    fn default_country() -> String {
+        "/country".to_string()
+    }
+
+    fn default_city() -> String {
+        "/city".to_string()
+    }
+
+    fn default_latitude() -> String {
+        "/latitude".to_string()
+    }
+
+    fn default_longitude() -> String {
+        "/longitude".to_string()
+    }
+}
+
+/// Handshake latency to an important endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EndpointLatency {
+    pub name: String,
+    pub host: String,
+    /// None when the host name did not resolve
+    pub latency: Option<LinkLatency>,
+}
+
+/// The path to one traceroute target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracedRoute {
+    pub target: String,
+    pub hops: Vec<RouteHop>,
+    /// How the route differs from the previous diagnostics run, if it changed
+    #[serde(default)]
+    pub change: Option<RouteChange>,
+}
+
+/// Whether DNS answers can be trusted on this network
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsSecurityCheck {
+    /// System resolver the DNSSEC check was run against
+    pub system_resolver: Option<IpAddr>,
+    /// Whether that resolver refuses domains with broken signatures, `None` if it couldn't be asked
+    pub dnssec_validating: Option<bool>,
+    /// Domains whose system answers share no address with the trusted resolver's
+    pub mismatched_domains: Vec<String>,
+    pub issues: Vec<DnsIssue>,
+}
+
+/// Signs of an unvalidated or tampered DNS path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsIssue {
+    /// The system resolver answers for domains with broken DNSSEC signatures
+    #[strum(serialize = "DNSSEC not validated")]
+    DnssecNotValidated,
+    /// Lookups of non-existent domains return addresses, usually an ISP ad or search page
+    #[strum(serialize = "NXDOMAIN rewritten")]
+    NxdomainRewritten,
+    /// The system resolver returns different addresses than a trusted resolver
+    #[strum(serialize = "Answers differ from trusted resolver")]
+    AnswersDiffer,
+    /// Plain DNS sent to a public resolver is answered by something else on the path
+    #[strum(serialize = "DNS queries intercepted")]
+    QueriesIntercepted,
+}
+
+/// How DNS queries reach the resolver
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsTransport {
+    #[strum(serialize = "UDP")]
+    Udp,
+    #[strum(serialize = "DoH")]
+    Doh,
+    #[strum(serialize = "DoT")]
+    Dot,
+}
+
+impl DnsTransport {
+    pub fn is_encrypted(self) -> bool {
+        self != DnsTransport::Udp
+    }
+}
+
+/// Query times of one public resolver over one transport
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolverBenchmark {
+    pub provider: String,
+    pub transport: DnsTransport,
+    /// Median over the successful queries, after a warm-up query
+    pub median_ms: Option<f64>,
+    pub successful_queries: usize,
+    pub queries: usize,
+    /// Why no query succeeded
+    pub error: Option<String>,
+}
+
+/// System clock compared with an NTP server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClockCheck {
+    pub server: String,
+    /// How far the server's clock is ahead of ours, negative when ours runs fast
+    pub offset_ms: f64,
+    pub round_trip_ms: f64,
+}
+
+impl ClockCheck {
+    /// Skew above this puts history entries at misleading times
+    pub const SKEW_WARNING_MS: f64 = 1000.0;
+
+    pub fn is_skewed(&self) -> bool {
+        self.offset_ms.abs() > Self::SKEW_WARNING_MS
+    }
+}
+
+/// Address family of a connection
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    #[strum(to_string = "IPv4")]
+    Ipv4,
+    #[strum(to_string = "IPv6")]
+    Ipv6,
+}
+
+impl IpFamily {
+    pub fn of(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(_) => IpFamily::Ipv4,
+            IpAddr::V6(_) => IpFamily::Ipv6,
+        }
+    }
+}
+
+/// Connections to one dual-stack site over each family on its own and raced
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HappyEyeballsCheck {
+    pub host: String,
+    /// Family the system resolver lists first, which clients try first
+    pub preferred: Option<IpFamily>,
+    pub ipv6_connect_ms: Option<f64>,
+    pub ipv4_connect_ms: Option<f64>,
+    /// Why IPv6 didn't connect, including sites without AAAA records
+    pub ipv6_error: Option<String>,
+    pub ipv4_error: Option<String>,
+    /// Family that connected first when IPv6 got a head start
+    pub winner: Option<IpFamily>,
+    pub race_ms: Option<f64>,
+    /// Time until a client trying IPv6 first gets connected over IPv4 once IPv6 fails
+    pub fallback_ms: Option<f64>,
+    pub issues: Vec<HappyEyeballsIssue>,
+}
+
+/// Dual-stack problems that stall page loads
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum HappyEyeballsIssue {
+    /// AAAA records resolve but IPv6 connections fail
+    #[strum(to_string = "IPv6 broken")]
+    BrokenIpv6,
+    /// IPv6 fails only after a long wait, so clients stall before trying IPv4
+    #[strum(to_string = "Slow IPv4 fallback")]
+    SlowFallback,
+    /// IPv6 works but loses the race despite its head start
+    #[strum(to_string = "IPv6 slower than IPv4")]
+    SlowIpv6,
+}
+
+/// Windows network settings that commonly hold a connection back, read through WMI
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WindowsStackCheck {
+    /// Receive window auto-tuning, e.g. "Normal" or "Disabled"
+    pub autotuning_level: Option<String>,
+    /// Receive-side scaling for the whole stack
+    pub rss_enabled: Option<bool>,
+    /// Wi-Fi power saving mode of the active power plan, on mains power
+    pub wifi_power_saving_ac: Option<WifiPowerSaving>,
+    /// The same on battery
+    pub wifi_power_saving_dc: Option<WifiPowerSaving>,
+    /// Physical adapters that are up
+    pub adapters: Vec<AdapterSettings>,
+    pub issues: Vec<WindowsStackIssue>,
+}
+
+/// Offload and power settings of one network adapter
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdapterSettings {
+    pub name: String,
+    pub description: String,
+    pub wireless: bool,
+    pub rss_enabled: Option<bool>,
+    /// Large send offload, on when enabled for IPv4 or IPv6
+    pub lso_enabled: Option<bool>,
+    /// Whether Windows may turn the adapter off to save power
+    pub power_saving: Option<bool>,
+}
+
+/// Power Saving Mode under Wireless Adapter Settings in the power plan
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum WifiPowerSaving {
+    #[strum(to_string = "Maximum Performance")]
+    MaximumPerformance,
+    #[strum(to_string = "Low Power Saving")]
+    LowPowerSaving,
+    #[strum(to_string = "Medium Power Saving")]
+    MediumPowerSaving,
+    #[strum(to_string = "Maximum Power Saving")]
+    MaximumPowerSaving,
+}
+
+/// Windows settings known to cost throughput or drop connections
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowsStackIssue {
+    /// The receive window can't grow, capping downloads on fast or distant links
+    #[strum(to_string = "Auto-tuning restricted")]
+    AutoTuningRestricted,
+    /// Receive processing is stuck on one CPU core
+    #[strum(to_string = "RSS disabled")]
+    RssDisabled,
+    /// Windows may switch a network adapter off while idle
+    #[strum(to_string = "Adapter power saving")]
+    AdapterPowerSaving,
+    /// The Wi-Fi adapter dozes between packets, adding latency and jitter
+    #[strum(to_string = "Wi-Fi power saving")]
+    WifiPowerSaving,
+}
+
+/// Result of a TLS handshake against one host
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TlsCheck {
+    pub host: String,
+    pub port: u16,
+    /// Negotiated version, e.g. "TLS 1.3"
+    pub protocol: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub handshake_ms: Option<f64>,
+    pub certificate_subject: Option<String>,
+    pub certificate_issuer: Option<String>,
+    pub certificate_expires: Option<DateTime<Utc>>,
+    /// Why the handshake failed, if it did
+    pub error: Option<String>,
+    pub issues: Vec<TlsIssue>,
+}
+
+/// Problems worth flagging in a TLS check
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsIssue {
+    #[strum(serialize = "Handshake failed")]
+    HandshakeFailed,
+    /// The server only offers TLS 1.1 or older
+    #[strum(serialize = "Outdated TLS version")]
+    OutdatedProtocol,
+    #[strum(serialize = "Certificate expires soon")]
+    CertificateExpiringSoon,
+    #[strum(serialize = "Certificate expired")]
+    CertificateExpired,
+}
+
+/// Best server of an earlier speed test, reused while it still answers about as fast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedServer {
+    pub server: TestServer,
+    pub latency_ms: f64,
+    pub selected_at: DateTime<Utc>,
+}
+
+/// Hop addresses of a traced route, kept in history to spot routing changes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RouteSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub target: String,
+    /// Address per hop, `None` where the hop didn't answer
+    pub hops: Vec<Option<IpAddr>>,
+}
+
+impl RouteSnapshot {
+    pub fn new(target: &str, hops: &[RouteHop]) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            target: target.to_string(),
+            hops: hops.iter().map(|hop| hop.address).collect(),
+        }
+    }
+
+    /// Hops answered by a different router than in `previous`, or present in only
+    /// one of the routes. Silent hops are not compared, they say nothing about the path.
+    pub fn diff(&self, previous: &RouteSnapshot) -> Option<RouteChange> {
+        let hop_count = self.hops.len().max(previous.hops.len());
+        let changed_hops: Vec<u32> = (0..hop_count)
+            .filter(|&i| match (self.hops.get(i), previous.hops.get(i)) {
+                (Some(Some(current)), Some(Some(before))) => current != before,
+                (Some(Some(_)), None) | (None, Some(Some(_))) => true,
+                _ => false,
+            })
+            .map(|i| i as u32 + 1)
+            .collect();
+
+        (!changed_hops.is_empty()).then(|| RouteChange {
+            previous_timestamp: previous.timestamp,
+            previous_hops: previous.hops.clone(),
+            changed_hops,
+        })
+    }
+}
+
+/// Difference between a traced route and the one from the previous run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RouteChange {
+    pub previous_timestamp: DateTime<Utc>,
+    pub previous_hops: Vec<Option<IpAddr>>,
+    /// Hop numbers whose router changed
+    pub changed_hops: Vec<u32>,
+}
+
+/// One probe in ping monitor mode, `latency_ms` is `None` when it got no answer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PingSample {
+    pub timestamp: DateTime<Utc>,
+    pub target: String,
+    pub latency_ms: Option<f64>,
+}
+
+/// Represents a single hop in a network route
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteHop {
+    pub hop_number: u32,
+    pub address: Option<IpAddr>,
+    pub hostname: Option<String>,
+    pub response_time_ms: Option<f64>,
+    /// Autonomous system announcing the hop's address
+    #[serde(default)]
+    pub network: Option<AsnInfo>,
+    /// Approximate physical location of the hop
+    #[serde(default)]
+    pub location: Option<HopLocation>,
+}
+
+/// Where an address is geolocated, accurate to the city at best
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HopLocation {
+    pub country: String,
+    pub country_code: String,
+    pub city: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Autonomous system an address belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AsnInfo {
+    pub asn: u32,
+    /// Holder of the AS, e.g. "GOOGLE"
+    pub organization: String,
+}
+
+/// Address, PTR name and network of a test server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HostNetwork {
+    pub host: String,
+    pub address: Option<IpAddr>,
+    pub hostname: Option<String>,
+    pub network: Option<AsnInfo>,
+}
+
+/// Subscribed internet plan as advertised by the ISP, e.g. 500/50 Mbps. Both speeds
+/// are positive, since results are divided by them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "PlanFields")]
+pub struct AdvertisedPlan {
+    pub download_mbps: f64,
+    pub upload_mbps: f64,
+    /// Warn when a result reaches less than this percentage of either advertised speed
+    pub alert_below_percent: f64,
+}
+
+/// An `AdvertisedPlan` as written in the config file, before its speeds are checked
+#[derive(Deserialize)]
+struct PlanFields {
+    download_mbps: f64,
+    upload_mbps: f64,
+    #[serde(default = "AdvertisedPlan::default_alert_below_percent")]
+    alert_below_percent: f64,
+}
+
+impl TryFrom<PlanFields> for AdvertisedPlan {
+    type Error = String;
+
+    fn try_from(fields: PlanFields) -> Result<Self, Self::Error> {
+        if fields.download_mbps > 0.0 && fields.upload_mbps > 0.0 {
+            Ok(Self {
+                download_mbps: fields.download_mbps,
+                upload_mbps: fields.upload_mbps,
+                alert_below_percent: fields.alert_below_percent,
+            })
+        } else {
+            Err(format!(
+                "advertised plan speeds must be above 0 Mbps, got {}/{}",
+                fields.download_mbps, fields.upload_mbps
+            ))
+        }
+    }
+}
+
+/// How close a single result came to the advertised plan
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PlanAttainment {
+    pub download_percent: f64,
+    pub upload_percent: f64,
+}
+
+impl AdvertisedPlan {
+    pub const DEFAULT_ALERT_BELOW_PERCENT: f64 = 80.0;
+
+    fn default_alert_below_percent() -> f64 {
+        Self::DEFAULT_ALERT_BELOW_PERCENT
+    }
+
+    pub fn attainment(&self, result: &SpeedTestResult) -> PlanAttainment {
+        PlanAttainment {
+            download_percent: self.download_percent(result.download_mbps),
+            upload_percent: self.upload_percent(result.upload_mbps),
+        }
+    }
+
+    /// True when either direction falls below the alert threshold
+    pub fn is_below_alert(&self, attainment: &PlanAttainment) -> bool {
+        attainment.download_percent < self.alert_below_percent
+            || attainment.upload_percent < self.alert_below_percent
+    }
+
+    /// Measured download as a percentage of the advertised speed
+    pub fn download_percent(&self, download_mbps: f64) -> f64 {
+        download_mbps / self.download_mbps * 100.0
+    }
+
+    /// Measured upload as a percentage of the advertised speed
+    pub fn upload_percent(&self, upload_mbps: f64) -> f64 {
+        upload_mbps / self.upload_mbps * 100.0
+    }
+}
+
+impl std::fmt::Display for AdvertisedPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} Mbps", self.download_mbps, self.upload_mbps)
+    }
+}
+
+impl std::str::FromStr for AdvertisedPlan {
+    type Err = String;
+
+    /// Parse `DOWN/UP` in Mbps, e.g. `500/50`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid plan '{}', expected DOWN/UP in Mbps (e.g. 500/50)",
+                s
+            )
+        };
+        let (down, up) = s.split_once('/').ok_or_else(invalid)?;
+        let down: f64 = down.trim().parse().map_err(|_| invalid())?;
+        let up: f64 = up.trim().parse().map_err(|_| invalid())?;
+
+        PlanFields {
+            download_mbps: down,
+            upload_mbps: up,
+            alert_below_percent: Self::DEFAULT_ALERT_BELOW_PERCENT,
+        }
+        .try_into()
+        .map_err(|_| invalid())
+    }
+}
+
+/// An amount of data such as `200MB` or `1.5GiB`, stored in bytes and written as text
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct DataSize(pub u64);
+
+impl TryFrom<String> for DataSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<DataSize> for String {
+    fn from(size: DataSize) -> Self {
+        format!("{}B", size.0)
+    }
+}
+
+impl std::fmt::Display for DataSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let adjusted =
+            byte_unit::Byte::from_u64(self.0).get_appropriate_unit(byte_unit::UnitType::Decimal);
+        write!(f, "{:.1}", adjusted)
+    }
+}
+
+impl std::str::FromStr for DataSize {
+    type Err = String;
+
+    /// Parse a size with a decimal or binary unit, e.g. `200MB`, `2 GB` or `1GiB`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        byte_unit::Byte::parse_str(s, true)
+            .map(|bytes| Self(bytes.as_u64()))
+            .map_err(|_| format!("Invalid size '{}', expected e.g. 200MB or 2GB", s))
+    }
+}
+
+/// A transfer rate such as `50mbps`, stored in bits per second
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BitRate(pub u64);
+
+impl BitRate {
+    pub fn bytes_per_second(&self) -> f64 {
+        self.0 as f64 / 8.0
+    }
+}
+
+impl std::fmt::Display for BitRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            bits if bits >= 1_000_000_000 => write!(f, "{} Gbps", bits as f64 / 1e9),
+            bits if bits >= 1_000_000 => write!(f, "{} Mbps", bits as f64 / 1e6),
+            bits if bits >= 1_000 => write!(f, "{} kbps", bits as f64 / 1e3),
+            bits => write!(f, "{} bps", bits),
+        }
+    }
+}
+
+impl std::str::FromStr for BitRate {
+    type Err = String;
+
+    /// Parse a rate in bits per second, e.g. `50mbps`, `500 kbps` or `1Gbit/s`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid rate '{}', expected e.g. 50mbps or 1gbps", s);
+        let lower = s.trim().to_ascii_lowercase();
+        let split = lower
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(lower.len());
+        let (number, unit) = lower.split_at(split);
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+
+        let scale = match unit.trim() {
+            "bps" | "bit/s" => 1e0,
+            "kbps" | "kbit/s" => 1e3,
+            "mbps" | "mbit/s" => 1e6,
+            "gbps" | "gbit/s" => 1e9,
+            _ => return Err(invalid()),
+        };
+        let bits = (number * scale).round();
+        if bits >= 1.0 {
+            Ok(Self(bits as u64))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Aggregation server that results are POSTed to after each test, see `reporting`
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReportTarget {
+    pub url: String,
+    /// Sent as a bearer token; never serialized back out
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+    /// Identifies this machine or site on the server, defaults to the hostname
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+impl std::fmt::Debug for ReportTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReportTarget")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("client_id", &self.client_id)
+            .finish()
+    }
+}
+
+/// SMTP server monitor mode mails alerts through, see `email`
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmailSettings {
+    pub server: String,
+    /// 465 for implicit TLS, anything else upgrades with STARTTLS; defaults to 587
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Also read from `NETRUNNER_SMTP_USERNAME`
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Only read from `NETRUNNER_SMTP_PASSWORD`, never from or to the file
+    #[serde(skip)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Batch alerts into one mail an hour instead of sending each right away
+    #[serde(default)]
+    pub digest: bool,
+}
+
+impl std::fmt::Debug for EmailSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailSettings")
+            .field("server", &self.server)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("digest", &self.digest)
+            .finish()
+    }
+}
+
+/// Router speed tests poll over SNMP, see `localnet::WanPoller`
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SnmpSettings {
+    /// Router address, the default gateway when not set
+    pub host: Option<String>,
+    /// Defaults to 161
+    pub port: Option<u16>,
+    /// SNMPv2c community, also read from `NETRUNNER_SNMP_COMMUNITY`; `public`
+    /// when neither it nor `username` is set
+    pub community: Option<String>,
+    /// SNMPv3 user, switches to v3 with authentication and no privacy
+    pub username: Option<String>,
+    pub auth_protocol: SnmpAuthProtocol,
+    /// Only read from `NETRUNNER_SNMP_PASSWORD`, never from or to the file
+    #[serde(skip)]
+    pub password: Option<String>,
+    /// `ifName` or `ifDescr` of the WAN interface, guessed from the names when not set
+    pub interface: Option<String>,
+}
+
+impl std::fmt::Debug for SnmpSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnmpSettings")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("community", &self.community.as_ref().map(|_| "<redacted>"))
+            .field("username", &self.username)
+            .field("auth_protocol", &self.auth_protocol)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("interface", &self.interface)
+            .finish()
+    }
+}
+
+/// SNMPv3 authentication protocol, HMAC-SHA-96 or HMAC-SHA-256-192
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum SnmpAuthProtocol {
+    #[default]
+    #[strum(to_string = "SHA")]
+    Sha,
+    #[strum(to_string = "SHA-256")]
+    Sha256,
+}
+
+/// A local time of day such as `22:30`, written as `HH:MM`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(s.trim(), "%H:%M")
+            .map(Self)
+            .map_err(|_| format!("Invalid time '{}', expected HH:MM such as 22:30", s))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+/// What monitor mode does during quiet hours
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietAction {
+    /// No test at all
+    #[default]
+    #[strum(to_string = "skip tests")]
+    Skip,
+    /// A single latency probe in place of the full test, which still catches outages
+    #[strum(to_string = "latency probes only")]
+    Latency,
+}
+
+/// A window of local time in which monitor mode leaves the line alone, a
+/// `[[quiet_hours]]` table in `config.toml`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuietWindow {
+    pub start: TimeOfDay,
+    /// Before `start` for a window that runs past midnight
+    pub end: TimeOfDay,
+    /// Days the window starts on, e.g. `["mon", "fri"]`; every day when empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    #[serde(default)]
+    pub action: QuietAction,
+}
+
+impl QuietWindow {
+    /// Whether local time `at` falls inside the window
+    pub fn contains(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start.0 <= self.end.0 {
+            time >= self.start.0 && time < self.end.0 && starts_on(at.weekday())
+        } else if time >= self.start.0 {
+            starts_on(at.weekday())
+        } else {
+            // Past midnight, in the window that started the day before
+            time < self.end.0 && starts_on(at.weekday().pred())
+        }
+    }
+}
+
+impl std::fmt::Display for QuietWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)?;
+        if !self.days.is_empty() {
+            let days: Vec<String> = self.days.iter().map(Weekday::to_string).collect();
+            write!(f, " {}", days.join(","))?;
+        }
+        write!(f, " ({})", self.action)
+    }
+}
+
+/// Normal results the adaptive interval compares new ones with
+const ADAPTIVE_BASELINE_RESULTS: usize = 10;
+/// Results needed in the baseline before speed and ping are judged against it
+const ADAPTIVE_MIN_BASELINE: usize = 3;
+
+/// Monitor mode interval that stretches while results are steady and drops to
+/// the minimum when one looks wrong, between `--min-interval` and `--max-interval`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveInterval {
+    pub min: Duration,
+    pub max: Duration,
+    current: Duration,
+    /// Download speed and ping of recent normal results, newest last
+    baseline: VecDeque<(f64, f64)>,
+    /// Consecutive results off the baseline, newest last. As many as the baseline
+    /// needs mean a lasting change, like a new plan, and they replace it.
+    shift: Vec<(f64, f64)>,
+}
+
+impl AdaptiveInterval {
+    pub fn new(start: Duration, min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            current: start.clamp(min, max),
+            baseline: VecDeque::with_capacity(ADAPTIVE_BASELINE_RESULTS),
+            shift: Vec::with_capacity(ADAPTIVE_MIN_BASELINE),
+        }
+    }
+
+    /// Time until the next test
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Record a monitor test, `None` when it failed, and adjust the interval.
+    /// Returns whether the result was an anomaly: a failure, a result below the
+    /// plan, a download under 75% of the recent median or a ping over twice it.
+    /// Speed or ping off the median in a row for as many results as the baseline
+    /// needs becomes the new baseline, so the interval stretches again.
+    pub fn record(&mut self, result: Option<&SpeedTestResult>, below_plan: bool) -> bool {
+        let anomaly = match result {
+            None => true,
+            Some(_) if below_plan => true,
+            Some(result) if self.baseline.len() >= ADAPTIVE_MIN_BASELINE => {
+                let downloads: Vec<f64> = self.baseline.iter().map(|(down, _)| *down).collect();
+                let pings: Vec<f64> = self.baseline.iter().map(|(_, ping)| *ping).collect();
+                result.download_mbps < median(&downloads) * 0.75
+                    || result.ping_ms > median(&pings) * 2.0 + 10.0
+            }
+            Some(_) => false,
+        };
+
+        match result {
+            Some(result) if !anomaly => {
+                self.shift.clear();
+                if self.baseline.len() == ADAPTIVE_BASELINE_RESULTS {
+                    self.baseline.pop_front();
+                }
+                self.baseline
+                    .push_back((result.download_mbps, result.ping_ms));
+                self.current = self.current.mul_f64(1.5).min(self.max);
+            }
+            Some(result) if !below_plan => {
+                self.shift.push((result.download_mbps, result.ping_ms));
+                if self.shift.len() == ADAPTIVE_MIN_BASELINE {
+                    self.baseline = self.shift.drain(..).collect();
+                }
+                self.current = self.min;
+            }
+            _ => self.current = self.min,
+        }
+        anomaly
+    }
+}
+
+/// Phone push service monitor alerts go to, see `push`
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushTarget {
+    /// URL of an ntfy topic
+    Ntfy(String),
+    /// Pushover application token and user key
+    Pushover { token: String, user: String },
+}
+
+impl std::fmt::Debug for PushTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Anyone who knows an ntfy topic can read it, so it is as secret as a token
+        match self {
+            PushTarget::Ntfy(_) => f.debug_tuple("Ntfy").field(&"<redacted>").finish(),
+            PushTarget::Pushover { .. } => f
+                .debug_struct("Pushover")
+                .field("token", &"<redacted>")
+                .field("user", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+/// What monitor mode alerts about
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// The test or one of its phases failed
+    #[strum(to_string = "Outage")]
+    Outage,
+    /// The result fell under the advertised plan's alert threshold
+    #[strum(to_string = "Below plan")]
+    BelowPlan,
+}
+
+/// One monitor cycle worth telling someone about
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MonitorAlert {
+    pub timestamp: DateTime<Utc>,
+    pub kind: AlertKind,
+    pub message: String,
+}
+
+/// Configuration for the speed test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestConfig {
+    pub server_url: String,
+    pub test_size_mb: u64,
+    pub timeout_seconds: u64,
+    pub json_output: bool,
+    pub animation_enabled: bool,
+    pub detail_level: DetailLevel,
+    pub max_servers: usize,
+    pub visual_style: VisualStyle,
+    /// Courtesy cap on full tests per rolling hour against public servers (0 = unlimited)
+    pub max_tests_per_hour: u32,
+    pub theme: ThemeName,
+    /// Where to write a shareable results card (.svg or .png), if requested
+    pub export_image: Option<std::path::PathBuf>,
+    /// Sign exported files with this machine's key, writing `<file>.sig` beside them
+    pub sign_exports: bool,
+    /// Advertised plan speeds to compare results against
+    pub plan: Option<AdvertisedPlan>,
+    /// Complete tests per speed test run, summarised by their medians when above 1
+    pub runs: u32,
+    /// Pause between runs
+    pub pause_seconds: u64,
+    /// Extra attempts after a failed test before giving up
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each further one
+    pub retry_backoff_seconds: u64,
+    /// Aggregation server to upload each result to
+    pub report_to: Option<ReportTarget>,
+    /// Where anonymous results go, None unless telemetry was switched on
+    pub telemetry_url: Option<String>,
+    /// Where monitor mode mails outages and results below the plan
+    pub email: Option<EmailSettings>,
+    /// Router whose WAN counters are polled during speed tests
+    pub snmp: Option<SnmpSettings>,
+    /// Phone push services monitor mode sends the same alerts to
+    pub push_targets: Vec<PushTarget>,
+    /// Windows of local time in which monitor mode skips its tests
+    pub quiet_hours: Vec<QuietWindow>,
+    /// Recorded with every result to tell machines apart in aggregated data
+    pub host_label: Option<String>,
+    pub site: Option<String>,
+    /// Labels recorded with every result, to filter history by
+    pub tags: Vec<String>,
+    /// Extra `host[:port]` targets for the TLS diagnostics
+    pub tls_hosts: Vec<String>,
+    /// Hosts of the speed test servers, looked up during diagnostics
+    pub server_hosts: Vec<String>,
+    /// Hosts the diagnostics traceroute maps, `server` standing for the speed test servers
+    pub trace_targets: Vec<String>,
+    pub trace: TraceOptions,
+    /// Names diagnostics resolves to time DNS, from `--dns-domains` or `dns_domains`
+    /// in config.toml
+    pub dns_domains: Vec<String>,
+    /// Code point marked on speed test connections, None leaves them best effort
+    pub dscp: Option<Dscp>,
+    /// Servers and protocol speed tests measure with
+    pub provider: SpeedProvider,
+    /// How the latency and jitter phases probe the test server
+    pub probes: ProbeOptions,
+    /// Most data one speed test may transfer, split between download and upload
+    pub max_data: Option<DataSize>,
+    /// Throughput each phase is throttled to
+    pub max_rate: Option<BitRate>,
+    /// Warn once speed tests have used more than this in a calendar month
+    pub monthly_data_budget: Option<DataSize>,
+    /// Hosts whose latency is measured at the end of every speed test
+    pub endpoints: Vec<ImportantEndpoint>,
+    /// Where to fetch the medians results are compared with, None for the built-in ones
+    pub medians_url: Option<String>,
+    /// How long each geolocation service may take to answer
+    pub geo_service_timeout_seconds: u64,
+    /// How long to wait for any geolocation service before using the default location
+    pub geo_timeout_seconds: u64,
+    pub geolocation: GeoSettings,
+    /// Servers from `--server-list` or the `server_list` config key
+    pub server_list: Option<ServerList>,
+    /// Providers and hosts kept out of the server pool
+    pub server_filter: ServerFilter,
+    /// Server chosen in the interactive menu, tested against instead of the pool
+    pub pinned_server: Option<TestServer>,
+    /// Select the best server from the full pool even when the last one still answers
+    pub reselect_server: bool,
+    /// Abort instead of warning when the line already carries traffic before the test
+    pub require_idle: bool,
+    /// Write a `progress` event with the countdown to stderr each second of a
+    /// throughput phase, as one JSON object per line
+    pub progress_events: bool,
+    /// Prompts, the menu and full-screen views are allowed; off with
+    /// `--non-interactive` or when stdin is not a terminal
+    pub interactive: bool,
+}
+
+/// How the diagnostics traceroute probes the path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TraceOptions {
+    pub max_hops: u8,
+    /// Probes sent for each TTL; extra probes ride out routers that rate-limit ICMP
+    pub probes_per_hop: u8,
+    /// How long each probe waits for its answer
+    pub probe_timeout: std::time::Duration,
+    pub protocol: TraceProtocol,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            max_hops: 30,
+            probes_per_hop: 3,
+            probe_timeout: std::time::Duration::from_secs(1),
+            protocol: TraceProtocol::Icmp,
+        }
+    }
+}
+
+/// How the latency and jitter phases probe the test server. Proxies that count
+/// requests per client can take the default bursts of probes for a flood,
+/// fewer, slower probes keep the test under their thresholds.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ProbeOptions {
+    /// Requests timing the latency phase
+    pub latency_probes: u32,
+    /// Requests timing jitter and packet loss
+    pub jitter_probes: u32,
+    /// Pause after each latency probe and between the starts of jitter probes,
+    /// None for each phase's own
+    pub spacing: Option<std::time::Duration>,
+    /// Most probes per minute to one server, None for no limit
+    pub max_per_minute: Option<u32>,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            latency_probes: 10,
+            jitter_probes: 20,
+            spacing: None,
+            max_per_minute: None,
+        }
+    }
+}
+
+impl ProbeOptions {
+    pub const LATENCY_SPACING: std::time::Duration = std::time::Duration::from_millis(100);
+    pub const JITTER_SPACING: std::time::Duration = std::time::Duration::from_millis(50);
+
+    pub fn latency_spacing(&self) -> std::time::Duration {
+        self.spacing.unwrap_or(Self::LATENCY_SPACING)
+    }
+
+    pub fn jitter_spacing(&self) -> std::time::Duration {
+        self.spacing.unwrap_or(Self::JITTER_SPACING)
+    }
+
+    /// Shortest time between the starts of two probes to one server
+    pub fn min_interval(&self) -> Option<std::time::Duration> {
+        self.max_per_minute
+            .map(|per_minute| std::time::Duration::from_secs(60) / per_minute.max(1))
+    }
+
+    /// One line on what the probes to `host` will send, for debug output
+    pub fn plan(&self, host: &str) -> String {
+        let mut plan = format!(
+            "{} latency probes {} ms apart, {} jitter probes {} ms apart to {}",
+            self.latency_probes,
+            self.latency_spacing().as_millis(),
+            self.jitter_probes,
+            self.jitter_spacing().as_millis(),
+            host
+        );
+        if let Some(per_minute) = self.max_per_minute {
+            plan.push_str(&format!(", at most {} per minute", per_minute));
+        }
+        plan
+    }
+}
+
+/// Packets a traceroute probes with; networks filtering ICMP often pass UDP or TCP
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceProtocol {
+    #[default]
+    Icmp,
+    /// Datagrams to the port, answered by port unreachable at the destination
+    Udp(u16),
+    /// SYNs to the port, TCP 443 follows the path HTTPS traffic takes
+    Tcp(u16),
+}
+
+impl TraceProtocol {
+    /// Classic traceroute port, unlikely to have anything listening
+    pub const DEFAULT_UDP_PORT: u16 = 33434;
+    pub const DEFAULT_TCP_PORT: u16 = 443;
+}
+
+impl std::fmt::Display for TraceProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Icmp => write!(f, "icmp"),
+            Self::Udp(port) => write!(f, "udp:{}", port),
+            Self::Tcp(port) => write!(f, "tcp:{}", port),
+        }
+    }
+}
+
+impl std::str::FromStr for TraceProtocol {
+    type Err = String;
+
+    /// Parse `icmp`, `udp[:PORT]` or `tcp[:PORT]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid trace protocol '{}', expected icmp, udp[:PORT] or tcp[:PORT]",
+                s
+            )
+        };
+        let (name, port) = match s.split_once(':') {
+            Some((name, port)) => (name, Some(port.parse::<u16>().map_err(|_| invalid())?)),
+            None => (s, None),
+        };
+        if port == Some(0) {
+            return Err(invalid());
+        }
+
+        match (name.to_ascii_lowercase().as_str(), port) {
+            ("icmp", None) => Ok(Self::Icmp),
+            ("udp", port) => Ok(Self::Udp(port.unwrap_or(Self::DEFAULT_UDP_PORT))),
+            ("tcp", port) => Ok(Self::Tcp(port.unwrap_or(Self::DEFAULT_TCP_PORT))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Differentiated Services code point marked on test traffic, the upper six bits
+/// of the IPv4 TOS byte or IPv6 traffic class
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "u8")]
+pub struct Dscp(u8);
+
+impl Dscp {
+    /// Expedited forwarding, the usual class for voice
+    pub const EF: Dscp = Dscp(46);
+
+    pub fn new(value: u8) -> Option<Self> {
+        (value < 64).then_some(Self(value))
+    }
+
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Value for `IP_TOS` / `IPV6_TCLASS`, ECN bits left clear
+    pub fn tos(self) -> u32 {
+        u32::from(self.0) << 2
+    }
+}
+
+impl TryFrom<u8> for Dscp {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::new(value).ok_or_else(|| format!("DSCP {} is out of range 0-63", value))
+    }
+}
+
+impl std::fmt::Display for Dscp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            46 => write!(f, "EF"),
+            value if value % 8 == 0 => write!(f, "CS{}", value / 8),
+            value if matches!((value / 8, value % 8), (1..=4, 2 | 4 | 6)) => {
+                write!(f, "AF{}{}", value / 8, (value % 8) / 2)
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+impl std::str::FromStr for Dscp {
+    type Err = String;
+
+    /// Parse `EF`, `CS0`-`CS7`, `AF11`-`AF43` or a numeric code point 0-63
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid DSCP '{}', expected EF, CS0-CS7, AF11-AF43 or a number 0-63",
+                s
+            )
+        };
+        let name = s.to_ascii_uppercase();
+        let digit = |c: Option<char>| c.and_then(|c| c.to_digit(10)).map(|d| d as u8);
+
+        let value = if name == "EF" {
+            Some(Self::EF.0)
+        } else if let Some(class) = name.strip_prefix("CS") {
+            let mut chars = class.chars();
+            match (digit(chars.next()), chars.next()) {
+                (Some(class @ 0..=7), None) => Some(class * 8),
+                _ => None,
+            }
+        } else if let Some(class) = name.strip_prefix("AF") {
+            let mut chars = class.chars();
+            match (digit(chars.next()), digit(chars.next()), chars.next()) {
+                (Some(class @ 1..=4), Some(drop @ 1..=3), None) => Some(class * 8 + drop * 2),
+                _ => None,
+            }
+        } else {
+            name.parse::<u8>().ok()
+        };
+
+        value.and_then(Self::new).ok_or_else(invalid)
+    }
 }
 
-/// Represents a single hop in a network route
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RouteHop {
-    pub hop_number: u32,
-    pub address: Option<IpAddr>,
-    pub hostname: Option<String>,
-    pub response_time_ms: Option<f64>,
+/// Built-in color themes, see `theme::Theme` for the palettes
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display, EnumString,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Cyberpunk,
+    Minimal,
+    LightTerminal,
+    ColorblindSafe,
 }
 
-/// Configuration for the speed test
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TestConfig {
-    pub server_url: String,
-    pub test_size_mb: u64,
-    pub timeout_seconds: u64,
-    pub json_output: bool,
-    pub animation_enabled: bool,
-    pub detail_level: DetailLevel,
-    pub max_servers: usize,
+/// How live throughput is visualised during the transfer phases
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display, EnumString,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum VisualStyle {
+    /// Scrolling area chart of recent samples
+    #[default]
+    Graph,
+    /// Semi-circular speedometer
+    Gauge,
+    /// A single status line
+    Minimal,
+}
+
+/// Which servers and protocol a speed test measures with
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Display, EnumString,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedProvider {
+    /// HTTP transfers against the best servers of the built-in pool
+    #[default]
+    Auto,
+    /// Measurement Lab's ndt7 servers, one WebSocket per direction
+    Mlab,
+    /// The Netflix caches fast.com tests against
+    Fast,
 }
 
 /// Level of detail for test output
@@ -160,6 +3105,15 @@ pub enum DetailLevel {
     Debug,
 }
 
+/// Names timed in diagnostics when none are configured
+pub const DEFAULT_DNS_DOMAINS: [&str; 5] = [
+    "google.com",
+    "amazon.com",
+    "facebook.com",
+    "microsoft.com",
+    "apple.com",
+];
+
 impl Default for TestConfig {
     fn default() -> Self {
         Self {
@@ -170,6 +3124,48 @@ impl Default for TestConfig {
             animation_enabled: true,
             detail_level: DetailLevel::Standard,
             max_servers: 3,
+            visual_style: VisualStyle::Graph,
+            max_tests_per_hour: 12,
+            theme: ThemeName::Cyberpunk,
+            export_image: None,
+            sign_exports: false,
+            plan: None,
+            runs: 1,
+            pause_seconds: 30,
+            retries: 0,
+            retry_backoff_seconds: 10,
+            report_to: None,
+            telemetry_url: None,
+            email: None,
+            snmp: None,
+            push_targets: Vec::new(),
+            quiet_hours: Vec::new(),
+            host_label: None,
+            site: None,
+            tags: Vec::new(),
+            tls_hosts: Vec::new(),
+            server_hosts: Vec::new(),
+            trace_targets: vec!["8.8.8.8".to_string()],
+            trace: TraceOptions::default(),
+            dns_domains: DEFAULT_DNS_DOMAINS.map(str::to_string).to_vec(),
+            dscp: None,
+            provider: SpeedProvider::Auto,
+            probes: ProbeOptions::default(),
+            max_data: None,
+            max_rate: None,
+            monthly_data_budget: None,
+            endpoints: Vec::new(),
+            medians_url: None,
+            geo_service_timeout_seconds: 5,
+            geo_timeout_seconds: 8,
+            geolocation: GeoSettings::default(),
+            server_list: None,
+            server_filter: ServerFilter::default(),
+            pinned_server: None,
+            reselect_server: false,
+            require_idle: false,
+            progress_events: false,
+            interactive: false,
         }
     }
 }
@@ -221,6 +3217,718 @@ mod tests {
         assert_eq!(quality, ConnectionQuality::Average);
     }
 
+    #[test]
+    fn test_connection_quality_from_metrics() {
+        // Clean line keeps the speed/ping rating
+        assert_eq!(
+            ConnectionQuality::from_metrics(150.0, 25.0, 15.0, 2.0, 0.0),
+            ConnectionQuality::Excellent
+        );
+        // 2% loss or 40 ms jitter cost one level each
+        assert_eq!(
+            ConnectionQuality::from_metrics(150.0, 25.0, 15.0, 2.0, 2.0),
+            ConnectionQuality::Good
+        );
+        assert_eq!(
+            ConnectionQuality::from_metrics(150.0, 25.0, 15.0, 40.0, 0.0),
+            ConnectionQuality::Good
+        );
+        assert_eq!(
+            ConnectionQuality::from_metrics(150.0, 25.0, 15.0, 40.0, 2.0),
+            ConnectionQuality::Average
+        );
+        // Heavy loss costs two levels, but never rates a working line as Failed
+        assert_eq!(
+            ConnectionQuality::from_metrics(150.0, 25.0, 15.0, 120.0, 10.0),
+            ConnectionQuality::VeryPoor
+        );
+        assert_eq!(
+            ConnectionQuality::from_metrics(0.0, 0.0, 0.0, 0.0, 0.0),
+            ConnectionQuality::Failed
+        );
+    }
+
+    #[test]
+    fn test_phase_countdown() {
+        use std::time::Duration;
+
+        let countdown = PhaseCountdown::new(
+            SpeedPhase::Download,
+            Duration::from_secs(15),
+            Duration::from_millis(5_500),
+            Duration::from_secs(18),
+        );
+        assert_eq!(countdown.phase_remaining_seconds, 9.5);
+        assert_eq!(countdown.eta_seconds, 27.5);
+        assert_eq!(countdown.summary(), "10s left, result in ~28s");
+
+        // A phase running over its time counts as done, not negative
+        let late = PhaseCountdown::new(
+            SpeedPhase::Upload,
+            Duration::from_secs(15),
+            Duration::from_secs(16),
+            Duration::from_secs(3),
+        );
+        assert_eq!(late.phase_remaining_seconds, 0.0);
+        assert_eq!(late.eta_seconds, 3.0);
+
+        assert_eq!(
+            serde_json::to_value(late).unwrap(),
+            serde_json::json!({
+                "event": "progress",
+                "phase": "upload",
+                "phase_remaining_seconds": 0.0,
+                "eta_seconds": 3.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_probe_options() {
+        use std::time::Duration;
+
+        let default = ProbeOptions::default();
+        assert_eq!(default.latency_spacing(), Duration::from_millis(100));
+        assert_eq!(default.jitter_spacing(), Duration::from_millis(50));
+        assert_eq!(default.min_interval(), None);
+        assert_eq!(
+            default.plan("speed.cloudflare.com"),
+            "10 latency probes 100 ms apart, 20 jitter probes 50 ms apart to speed.cloudflare.com"
+        );
+
+        let polite = ProbeOptions {
+            latency_probes: 5,
+            jitter_probes: 5,
+            spacing: Some(Duration::from_secs(1)),
+            max_per_minute: Some(30),
+        };
+        assert_eq!(polite.jitter_spacing(), Duration::from_secs(1));
+        assert_eq!(polite.min_interval(), Some(Duration::from_secs(2)));
+        assert!(polite
+            .plan("example.com")
+            .ends_with(", at most 30 per minute"));
+    }
+
+    #[test]
+    fn test_use_case_scores() {
+        let result = SpeedTestResult {
+            download_mbps: 40.0,
+            upload_mbps: 5.0,
+            ping_ms: 70.0,
+            jitter_ms: 10.0,
+            packet_loss_percent: 0.0,
+            ..Default::default()
+        };
+
+        let scores = result.use_case_scores();
+        assert_eq!(scores.iter().count(), UseCase::ALL.len());
+
+        let gaming = &scores.online_gaming;
+        assert_eq!(gaming.use_case, UseCase::Gaming);
+        assert!(!gaming.suitable);
+        assert_eq!(gaming.limited_by.as_deref(), Some("ping above 50 ms"));
+        assert!(gaming.score < UseCase::PASS_SCORE);
+
+        // Comfortably above every video call requirement
+        assert!(scores.video_calls.suitable);
+        assert_eq!(scores.video_calls.limited_by, None);
+        assert!(scores.video_calls.score > UseCase::PASS_SCORE);
+
+        // 40 of 25 Mbps: met with 60% headroom
+        assert!(scores.streaming_4k.suitable);
+        assert_eq!(scores.streaming_4k.score, 88);
+        assert!(!scores.streaming_8k.suitable);
+        assert_eq!(
+            scores.large_uploads.limited_by.as_deref(),
+            Some("upload below 20 Mbps")
+        );
+        assert_eq!(UseCase::Streaming4K.to_string(), "4K streaming");
+    }
+
+    #[test]
+    fn test_aggregate_result_medians() {
+        assert!(AggregateResult::from_runs(&[]).is_none());
+
+        let now = Utc::now();
+        let runs: Vec<_> = [
+            (100.0, 10.0, 15.0),
+            (300.0, 30.0, 10.0),
+            (200.0, 20.0, 40.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (down, up, ping))| SpeedTestResult {
+            timestamp: now + chrono::Duration::seconds(i as i64),
+            download_mbps: *down,
+            upload_mbps: *up,
+            ping_ms: *ping,
+            server_location: format!("Server {}", i % 2),
+            test_duration_seconds: 10.0,
+            ..Default::default()
+        })
+        .collect();
+
+        let aggregate = AggregateResult::from_runs(&runs).unwrap();
+        assert_eq!(aggregate.run_count, 3);
+        assert_eq!(aggregate.median.download_mbps, 200.0);
+        assert_eq!(aggregate.median.upload_mbps, 20.0);
+        assert_eq!(aggregate.median.ping_ms, 15.0);
+        assert_eq!(aggregate.median.test_duration_seconds, 30.0);
+        assert_eq!(aggregate.median.timestamp, runs[2].timestamp);
+        assert_eq!(aggregate.median.server_location, "Multiple servers");
+        assert_eq!(aggregate.median.quality, ConnectionQuality::Excellent);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn test_interarrival_jitter() {
+        assert_eq!(interarrival_jitter(&[]), 0.0);
+        assert_eq!(interarrival_jitter(&[20.0]), 0.0);
+        // A steady stream has none, however high its latency
+        assert_eq!(interarrival_jitter(&[80.0; 10]), 0.0);
+
+        // Each step adds a sixteenth of the difference to what is left
+        let jitter = interarrival_jitter(&[20.0, 36.0, 20.0]);
+        assert!((jitter - (1.0 + 15.0 / 16.0)).abs() < 1e-9);
+
+        // Alternating transits converge on the size of the swing
+        let swinging: Vec<f64> = (0..400)
+            .map(|i| if i % 2 == 0 { 20.0 } else { 30.0 })
+            .collect();
+        assert!((interarrival_jitter(&swinging) - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_consecutive_jitter() {
+        assert_eq!(consecutive_jitter(&[]), 0.0);
+        assert_eq!(consecutive_jitter(&[20.0]), 0.0);
+        assert_eq!(consecutive_jitter(&[20.0, 30.0, 25.0]), 7.5);
+        assert_eq!(percent_lost(0, 0), 0.0);
+        assert_eq!(percent_lost(1, 4), 25.0);
+    }
+
+    #[test]
+    fn test_one_way_delay() {
+        // Our clock runs 500 ms behind, 12 ms up and 4 ms down
+        let clock = ClockCheck {
+            server: "time.example".to_string(),
+            offset_ms: 500.0,
+            round_trip_ms: 6.0,
+        };
+        let probes: Vec<(f64, f64, f64)> = (0..5)
+            .map(|i| {
+                let sent = 1_700_000_000_000.0 + i as f64 * 50.0;
+                (sent, sent + 500.0 + 12.0, sent + 16.0)
+            })
+            .collect();
+        let delay = OneWayDelay::from_probes(&probes, &clock).unwrap();
+        assert!((delay.upstream_ms - 12.0).abs() < 1e-6);
+        assert!((delay.downstream_ms - 4.0).abs() < 1e-6);
+        assert!((delay.asymmetry_ms() - 8.0).abs() < 1e-6);
+        assert_eq!(delay.uncertainty_ms, 4.0);
+        assert_eq!(delay.samples, 5);
+
+        // A clock known only to 20 ms cannot split a 16 ms round trip
+        let vague = ClockCheck {
+            round_trip_ms: 40.0,
+            ..clock.clone()
+        };
+        assert!(OneWayDelay::from_probes(&probes, &vague).is_none());
+
+        // A server clock a second behind puts the answer before the question
+        let wrong: Vec<(f64, f64, f64)> = probes
+            .iter()
+            .map(|&(sent, stamped, received)| (sent, stamped - 1000.0, received))
+            .collect();
+        assert!(OneWayDelay::from_probes(&wrong, &clock).is_none());
+        assert!(OneWayDelay::from_probes(&[], &clock).is_none());
+    }
+
+    #[test]
+    fn test_result_report_json() {
+        let result = SpeedTestResult {
+            download_mbps: 120.0,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(result.report()).unwrap();
+
+        // Stored fields stay at the top level, scores are added alongside
+        assert_eq!(json["download_mbps"], 120.0);
+        assert_eq!(json["use_cases"]["streaming_8k"]["suitable"], true);
+        assert_eq!(json["use_cases"]["large_uploads"]["suitable"], false);
+    }
+
+    #[test]
+    fn test_connection_quality_from_throughput() {
+        assert_eq!(
+            ConnectionQuality::from_download_mbps(100.0),
+            ConnectionQuality::Excellent
+        );
+        assert_eq!(
+            ConnectionQuality::from_download_mbps(30.0),
+            ConnectionQuality::Average
+        );
+        assert_eq!(
+            ConnectionQuality::from_upload_mbps(30.0),
+            ConnectionQuality::Excellent
+        );
+        assert_eq!(
+            ConnectionQuality::from_upload_mbps(1.0),
+            ConnectionQuality::VeryPoor
+        );
+        assert_eq!(
+            ConnectionQuality::from_upload_mbps(0.0),
+            ConnectionQuality::Failed
+        );
+    }
+
+    #[test]
+    fn test_theme_name_parsing() {
+        use std::str::FromStr;
+
+        assert_eq!(
+            ThemeName::from_str("light-terminal").unwrap(),
+            ThemeName::LightTerminal
+        );
+        assert_eq!(ThemeName::ColorblindSafe.to_string(), "colorblind-safe");
+        assert_eq!(
+            serde_json::to_string(&ThemeName::ColorblindSafe).unwrap(),
+            "\"colorblind-safe\""
+        );
+    }
+
+    #[test]
+    fn test_visual_style_parsing() {
+        use std::str::FromStr;
+
+        assert_eq!(VisualStyle::from_str("gauge").unwrap(), VisualStyle::Gauge);
+        assert_eq!(VisualStyle::from_str("graph").unwrap(), VisualStyle::Graph);
+        assert_eq!(VisualStyle::Minimal.to_string(), "minimal");
+        assert!(VisualStyle::from_str("dial").is_err());
+    }
+
+    #[test]
+    fn test_advertised_plan_parsing() {
+        let plan: AdvertisedPlan = "500/50".parse().unwrap();
+        assert_eq!(plan.download_mbps, 500.0);
+        assert_eq!(plan.upload_mbps, 50.0);
+        assert_eq!(plan.to_string(), "500/50 Mbps");
+        assert_eq!(plan.download_percent(400.0), 80.0);
+        assert_eq!(plan.upload_percent(25.0), 50.0);
+
+        assert!("500".parse::<AdvertisedPlan>().is_err());
+        assert!("fast/slow".parse::<AdvertisedPlan>().is_err());
+        assert!("0/50".parse::<AdvertisedPlan>().is_err());
+    }
+
+    #[test]
+    fn test_phase_failures() {
+        assert_eq!(PhaseOutcome::Measured(0.4).mbps(), Some(0.4));
+        assert_eq!(PhaseOutcome::Failed("stalled".to_string()).mbps(), None);
+
+        let mut result = SpeedTestResult {
+            download_mbps: 0.0,
+            upload_mbps: 12.0,
+            ..Default::default()
+        };
+        assert!(!result.has_failures());
+        result.failures.push(PhaseFailure {
+            phase: SpeedPhase::Download,
+            reason: "no data transferred".to_string(),
+        });
+        assert!(result.has_failures());
+
+        let json = serde_json::to_value(result.report()).unwrap();
+        assert_eq!(json["failures"][0]["phase"], "download");
+    }
+
+    #[test]
+    fn test_clock_check_skew() {
+        let check = |offset_ms| ClockCheck {
+            server: "pool.ntp.org".to_string(),
+            offset_ms,
+            round_trip_ms: 20.0,
+        };
+        assert!(!check(120.0).is_skewed());
+        assert!(check(-4500.0).is_skewed());
+        assert!(check(ClockCheck::SKEW_WARNING_MS + 1.0).is_skewed());
+    }
+
+    #[test]
+    fn test_baseline_deviation() {
+        let run = |download_mbps: f64, upload_mbps: f64, ping_ms: f64| SpeedTestResult {
+            download_mbps,
+            upload_mbps,
+            ping_ms,
+            ..Default::default()
+        };
+        let baseline = Baseline {
+            name: "fiber".to_string(),
+            created_at: Utc::now(),
+            multi_stream: run(200.0, 0.0, 10.0),
+            single_stream: run(50.0, 0.0, 10.0),
+        };
+
+        let deviation = baseline.deviation(&run(150.0, 20.0, 25.0));
+        assert_eq!(deviation.baseline, "fiber");
+        assert_eq!(deviation.download_percent, Some(-25.0));
+        assert_eq!(deviation.upload_percent, None);
+        assert_eq!(deviation.ping_ms, 15.0);
+        assert_eq!(baseline.single_stream_percent(), Some(25.0));
+    }
+
+    #[test]
+    fn test_dns_record_cache_saving() {
+        let mut timing = DnsRecordTiming {
+            domain: "example.com".to_string(),
+            kind: DnsRecordKind::CnameChain,
+            cold_ms: Some(42.0),
+            warm_ms: Some(2.0),
+            answers: vec!["edge.example.net".to_string()],
+            error: None,
+        };
+        assert_eq!(timing.cache_saving_ms(), Some(40.0));
+        assert_eq!(timing.kind.to_string(), "CNAME chain");
+
+        timing.warm_ms = None;
+        assert_eq!(timing.cache_saving_ms(), None);
+    }
+
+    #[test]
+    fn test_full_analysis_correlations() {
+        let diagnostics =
+            |dns_response_time_ms: f64, gateway_probes: &[Option<f64>], kind: &str| {
+                NetworkDiagnostics {
+                    gateway_ip: None,
+                    dns_servers: vec![],
+                    dns_response_time_ms,
+                    dns_lookups: vec![],
+                    dns_records: vec![],
+                    route_hops: vec![],
+                    is_ipv6_available: false,
+                    connection_type: Some(kind.to_string()),
+                    network_interface: None,
+                    tls_checks: vec![],
+                    resolver_benchmarks: vec![],
+                    dns_security: DnsSecurityCheck::default(),
+                    routes: vec![],
+                    server_networks: vec![],
+                    latency_baseline: LatencyBaseline {
+                        gateway: Some(LinkLatency::from_probes(
+                            IpAddr::from([192, 168, 1, 1]),
+                            80,
+                            gateway_probes,
+                        )),
+                        internet: None,
+                    },
+                    clock_check: None,
+                    happy_eyeballs: vec![],
+                    windows_stack: None,
+                }
+            };
+        let speed = |download_mbps: f64, ping_ms: f64, packet_loss_percent: f64| SpeedTestResult {
+            download_mbps,
+            ping_ms,
+            packet_loss_percent,
+            ..Default::default()
+        };
+        let healthy_gateway = [Some(1.0), Some(1.5)];
+
+        let healthy = FullAnalysisResult::new(
+            speed(300.0, 12.0, 0.0),
+            diagnostics(20.0, &healthy_gateway, "Wired (Ethernet)"),
+        );
+        assert!(healthy.correlations.is_empty());
+        assert_eq!(healthy.timestamp, healthy.speed.timestamp);
+
+        // Slow lookups alone are not enough, they need the high ping next to them
+        let slow_dns = FullAnalysisResult::correlate(
+            &speed(300.0, 12.0, 0.0),
+            &diagnostics(250.0, &healthy_gateway, "Wired (Ethernet)"),
+        );
+        assert!(slow_dns.is_empty());
+        let resolver = FullAnalysisResult::correlate(
+            &speed(300.0, 90.0, 0.0),
+            &diagnostics(250.0, &healthy_gateway, "Wired (Ethernet)"),
+        );
+        assert_eq!(resolver, vec![Correlation::SlowResolver]);
+
+        let wifi = FullAnalysisResult::correlate(
+            &speed(12.0, 80.0, 0.0),
+            &diagnostics(20.0, &[Some(40.0), Some(90.0)], "Wireless (Wi-Fi)"),
+        );
+        assert_eq!(
+            wifi,
+            vec![Correlation::HomeLatency, Correlation::SlowWireless]
+        );
+
+        let upstream_loss = FullAnalysisResult::correlate(
+            &speed(300.0, 12.0, 3.0),
+            &diagnostics(20.0, &healthy_gateway, "Wired (Ethernet)"),
+        );
+        assert_eq!(upstream_loss, vec![Correlation::UpstreamLoss]);
+
+        let json = serde_json::to_value(healthy.report()).unwrap();
+        assert!(json["speed"]["use_cases"].is_object());
+        assert_eq!(json["correlations"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_latency_baseline_bottleneck() {
+        let gateway = IpAddr::from([192, 168, 1, 1]);
+        let internet = IpAddr::from([1, 1, 1, 1]);
+
+        let link = LinkLatency::from_probes(gateway, 80, &[Some(2.0), None, Some(4.0), Some(3.0)]);
+        assert_eq!(link.sent, 4);
+        assert_eq!(link.lost, 1);
+        assert_eq!(link.median_ms, Some(3.0));
+        assert_eq!(link.jitter_ms, 1.5);
+        assert_eq!(link.loss_percent(), 25.0);
+
+        let healthy = LatencyBaseline {
+            gateway: Some(LinkLatency::from_probes(
+                gateway,
+                80,
+                &[Some(1.0), Some(1.5)],
+            )),
+            internet: Some(LinkLatency::from_probes(
+                internet,
+                443,
+                &[Some(20.0), Some(22.0)],
+            )),
+        };
+        assert_eq!(healthy.bottleneck(), None);
+
+        let slow_wifi = LatencyBaseline {
+            gateway: Some(LinkLatency::from_probes(
+                gateway,
+                80,
+                &[Some(40.0), Some(90.0)],
+            )),
+            ..healthy.clone()
+        };
+        assert_eq!(slow_wifi.bottleneck(), Some(Bottleneck::Home));
+
+        let slow_isp = LatencyBaseline {
+            internet: Some(LinkLatency::from_probes(
+                internet,
+                443,
+                &[Some(180.0), Some(190.0)],
+            )),
+            ..healthy.clone()
+        };
+        assert_eq!(slow_isp.bottleneck(), Some(Bottleneck::Upstream));
+
+        let unreachable = LatencyBaseline {
+            gateway: None,
+            internet: Some(LinkLatency::from_probes(internet, 443, &[None, None])),
+        };
+        assert_eq!(unreachable.bottleneck(), Some(Bottleneck::Upstream));
+    }
+
+    #[test]
+    fn test_trace_protocol_parsing() {
+        assert_eq!("icmp".parse::<TraceProtocol>(), Ok(TraceProtocol::Icmp));
+        assert_eq!(
+            "udp".parse::<TraceProtocol>(),
+            Ok(TraceProtocol::Udp(33434))
+        );
+        assert_eq!("TCP".parse::<TraceProtocol>(), Ok(TraceProtocol::Tcp(443)));
+        assert_eq!(
+            "tcp:80".parse::<TraceProtocol>(),
+            Ok(TraceProtocol::Tcp(80))
+        );
+        assert_eq!(TraceProtocol::Udp(53).to_string(), "udp:53");
+
+        assert!("icmp:1".parse::<TraceProtocol>().is_err());
+        assert!("tcp:0".parse::<TraceProtocol>().is_err());
+        assert!("sctp".parse::<TraceProtocol>().is_err());
+    }
+
+    #[test]
+    fn test_server_list_parsing() {
+        let list = ServerList::parse(
+            r#"
+                replace = true
+
+                [[servers]]
+                name = "HQ"
+                url = "https://speed.corp.example/"
+                protocol = "librespeed"
+                location = "Frankfurt, DE"
+                latitude = 50.11
+                longitude = 8.68
+                upload = false
+
+                [[servers]]
+                name = "Branch"
+                url = "http://10.20.0.5:8080"
+
+                [[servers]]
+                name = "Lab"
+                url = "https://lab.corp.example"
+                protocol = "custom"
+                endpoints = { download = "blob?size={bytes}", upload = "sink" }
+            "#,
+        )
+        .unwrap();
+        assert!(list.replace);
+        assert_eq!(list.servers[0].protocol, ServerProtocol::LibreSpeed);
+        assert!(list.servers[0].download && !list.servers[0].upload);
+        assert_eq!(list.servers[1].protocol, ServerProtocol::Cloudflare);
+        assert_eq!(list.servers[1].max_test_size_mb, 2000);
+        assert_eq!(list.servers[2].protocol, ServerProtocol::Custom);
+        assert_eq!(
+            list.servers[2]
+                .endpoints
+                .as_ref()
+                .map(|e| e.upload.as_str()),
+            Some("sink")
+        );
+
+        assert!(ServerList::parse("replace = true").is_err());
+        assert!(ServerList::parse("[[servers]]\nname = \"x\"\nurl = \"ftp://x\"").is_err());
+        assert!(ServerList::parse(
+            "[[servers]]\nname = \"x\"\nurl = \"https://x\"\nlatitude = 1.0"
+        )
+        .is_err());
+        // Custom servers need their endpoints and only they take them
+        assert!(ServerList::parse(
+            "[[servers]]\nname = \"x\"\nurl = \"https://x\"\nprotocol = \"custom\""
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_server_filter() {
+        let server = |provider: ServerProvider, url: &str| TestServer {
+            name: provider.to_string(),
+            url: url.to_string(),
+            location: "Global".to_string(),
+            distance_km: None,
+            latency_ms: None,
+            provider,
+            protocol: ServerProtocol::Cloudflare,
+            capabilities: ServerCapabilities {
+                supports_download: true,
+                supports_upload: true,
+                supports_latency: true,
+                max_test_size_mb: 1000,
+                geographic_weight: 1.0,
+            },
+            quality_score: None,
+            country_code: None,
+            city: None,
+            is_backup: false,
+            endpoints: None,
+        };
+        let cloudflare = server(ServerProvider::Cloudflare, "https://speed.cloudflare.com");
+        let google = server(ServerProvider::Google, "https://www.google.com");
+        let sponsor = server(
+            ServerProvider::Custom("Example Fiber".to_string()),
+            "https://speed.example.net:8080",
+        );
+
+        assert_eq!("google".parse(), Ok(ServerProvider::Google));
+        assert_eq!("Self-Hosted".parse(), Ok(ServerProvider::SelfHosted));
+        assert!("  ".parse::<ServerProvider>().is_err());
+
+        let filter = ServerFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.allows(&google));
+
+        let filter = ServerFilter {
+            only_providers: vec![
+                "cloudflare".parse().unwrap(),
+                "example fiber".parse().unwrap(),
+            ],
+            ..ServerFilter::default()
+        };
+        assert!(filter.allows(&cloudflare));
+        assert!(filter.allows(&sponsor));
+        assert!(!filter.allows(&google));
+
+        let filter: ServerFilter =
+            toml::from_str("exclude_providers = [\"Google\"]\nexclude_hosts = [\"example.net\"]\n")
+                .unwrap();
+        assert_eq!(filter.exclude_providers, vec![ServerProvider::Google]);
+        assert!(filter.allows(&cloudflare));
+        assert!(!filter.allows(&google));
+        // Subdomains of an excluded host are excluded with it
+        assert!(!filter.allows(&sponsor));
+        assert!(filter.allows(&server(
+            ServerProvider::SelfHosted,
+            "https://notexample.net"
+        )));
+    }
+
+    #[test]
+    fn test_data_size_and_rate_parsing() {
+        assert_eq!("200MB".parse::<DataSize>(), Ok(DataSize(200_000_000)));
+        assert_eq!("1 GiB".parse::<DataSize>(), Ok(DataSize(1 << 30)));
+        assert_eq!(DataSize(1_500_000_000).to_string(), "1.5 GB");
+        assert!("lots".parse::<DataSize>().is_err());
+
+        assert_eq!("50mbps".parse::<BitRate>(), Ok(BitRate(50_000_000)));
+        assert_eq!("2.5 Mbps".parse::<BitRate>(), Ok(BitRate(2_500_000)));
+        assert_eq!("1Gbit/s".parse::<BitRate>(), Ok(BitRate(1_000_000_000)));
+        assert_eq!(BitRate(50_000_000).to_string(), "50 Mbps");
+        assert_eq!(BitRate(50_000_000).bytes_per_second(), 6_250_000.0);
+        assert!("50".parse::<BitRate>().is_err());
+        assert!("0mbps".parse::<BitRate>().is_err());
+        assert!("50MB".parse::<BitRate>().is_err());
+    }
+
+    #[test]
+    fn test_dscp_parsing() {
+        assert_eq!("EF".parse::<Dscp>().map(Dscp::value), Ok(46));
+        assert_eq!("cs1".parse::<Dscp>().map(Dscp::value), Ok(8));
+        assert_eq!("AF41".parse::<Dscp>().map(Dscp::value), Ok(34));
+        assert_eq!("AF13".parse::<Dscp>().map(Dscp::value), Ok(14));
+        assert_eq!("26".parse::<Dscp>().map(Dscp::value), Ok(26));
+        assert_eq!(Dscp::EF.tos(), 0xb8);
+
+        assert_eq!(Dscp::new(34).unwrap().to_string(), "AF41");
+        assert_eq!(Dscp::new(0).unwrap().to_string(), "CS0");
+        assert_eq!(Dscp::new(2).unwrap().to_string(), "2");
+
+        assert!("AF51".parse::<Dscp>().is_err());
+        assert!("CS8".parse::<Dscp>().is_err());
+        assert!("64".parse::<Dscp>().is_err());
+
+        // Stored code points are checked the same way
+        assert_eq!(serde_json::from_str::<Dscp>("46").unwrap(), Dscp::EF);
+        assert!(serde_json::from_str::<Dscp>("64").is_err());
+    }
+
+    #[test]
+    fn test_plan_attainment_alert() {
+        let plan: AdvertisedPlan = "500/50".parse().unwrap();
+        let result = SpeedTestResult {
+            download_mbps: 450.0,
+            upload_mbps: 35.0,
+            ..Default::default()
+        };
+
+        let attainment = plan.attainment(&result);
+        assert_eq!(attainment.download_percent, 90.0);
+        assert_eq!(attainment.upload_percent, 70.0);
+        // Upload is under the default 80% threshold
+        assert!(plan.is_below_alert(&attainment));
+
+        let lenient = AdvertisedPlan {
+            alert_below_percent: 50.0,
+            ..plan
+        };
+        assert!(!lenient.is_below_alert(&attainment));
+    }
+
     #[test]
     fn test_speed_test_result_default() {
         let result = SpeedTestResult::default();
@@ -238,6 +3946,46 @@ mod tests {
         assert_eq!(result.isp, None);
     }
 
+    #[test]
+    fn test_speed_test_result_freshness() {
+        let mut result = SpeedTestResult::default();
+        assert!(result.is_fresher_than(std::time::Duration::from_secs(60)));
+
+        result.timestamp = Utc::now() - chrono::Duration::minutes(20);
+        assert!(!result.is_fresher_than(std::time::Duration::from_secs(15 * 60)));
+        assert!(result.is_fresher_than(std::time::Duration::from_secs(30 * 60)));
+    }
+
+    #[test]
+    fn test_latency_stats_from_samples() {
+        assert!(LatencyStats::from_samples(&[]).is_none());
+
+        let samples: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        let stats = LatencyStats::from_samples(&samples).unwrap();
+
+        assert_eq!(stats.sample_count, 100);
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.max_ms, 100.0);
+        assert_eq!(stats.avg_ms, 50.5);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.p99_ms, 99.0);
+
+        // Every sample lands in exactly one bucket
+        let counted: usize = stats.histogram.iter().map(|b| b.count).sum();
+        assert_eq!(counted, 100);
+        assert!(stats.histogram.first().unwrap().lower_ms <= stats.min_ms);
+        assert!(stats.histogram.last().unwrap().upper_ms > stats.max_ms);
+    }
+
+    #[test]
+    fn test_latency_stats_single_sample() {
+        let stats = LatencyStats::from_samples(&[12.5]).unwrap();
+        assert_eq!(stats.p95_ms, 12.5);
+        assert_eq!(stats.p99_ms, 12.5);
+        assert_eq!(stats.histogram.len(), 1);
+        assert_eq!(stats.histogram[0].count, 1);
+    }
+
     #[test]
     fn test_test_config_default() {
         let config = TestConfig::default();
@@ -251,6 +3999,193 @@ mod tests {
         assert_eq!(config.max_servers, 3);
     }
 
+    #[test]
+    fn test_engine_info() {
+        let config = TestConfig {
+            timeout_seconds: 45,
+            max_data: Some(DataSize(500_000_000)),
+            retries: 2,
+            ..TestConfig::default()
+        };
+        let engine = EngineInfo::current(&config, Some("HTTP/2.0".to_string()), 50, 10);
+
+        assert_eq!(engine.version, env!("CARGO_PKG_VERSION"));
+        assert!(engine.git_hash.as_ref().is_none_or(|hash| !hash.is_empty()));
+        assert_eq!(engine.download_connections, 50);
+        assert_eq!(engine.parameters.timeout_seconds, 45);
+        assert_eq!(engine.parameters.max_data_bytes, Some(500_000_000));
+        assert_eq!(engine.parameters.max_rate_bps, None);
+        assert!(!engine.parameters.pinned_server);
+
+        let result = SpeedTestResult {
+            engine: Some(engine),
+            ..Default::default()
+        };
+        let json: serde_json::Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["engine"]["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["engine"]["protocol"], "HTTP/2.0");
+        assert_eq!(json["engine"]["parameters"]["retries"], 2);
+    }
+
+    #[test]
+    fn test_wan_verdict() {
+        let rate = |average_mbps, peak_mbps| WanRate {
+            average_mbps,
+            peak_mbps,
+        };
+        assert_eq!(
+            rate(480.0, 495.0).verdict(470.0, Some(500.0), Some(1000)),
+            WanVerdict::LinkSaturated
+        );
+        assert_eq!(
+            rate(950.0, 990.0).verdict(400.0, None, Some(1000)),
+            WanVerdict::LinkSaturated
+        );
+        assert_eq!(
+            rate(300.0, 320.0).verdict(150.0, Some(500.0), None),
+            WanVerdict::SharedWithOtherTraffic
+        );
+        assert_eq!(
+            rate(160.0, 170.0).verdict(150.0, Some(500.0), Some(1000)),
+            WanVerdict::LocalBottleneck
+        );
+        assert_eq!(
+            rate(160.0, 170.0).verdict(150.0, None, Some(1000)),
+            WanVerdict::Inconclusive
+        );
+    }
+
+    #[test]
+    fn test_other_traffic() {
+        let wan = WanUsage {
+            interface: "wan".to_string(),
+            port_speed_mbps: None,
+            download: Some(WanRate {
+                average_mbps: 290.0,
+                peak_mbps: 310.0,
+            }),
+            upload: Some(WanRate {
+                average_mbps: 19.0,
+                peak_mbps: 20.0,
+            }),
+        };
+        let mut result = SpeedTestResult {
+            download_mbps: 110.0,
+            upload_mbps: 20.0,
+            ..Default::default()
+        };
+        let other = OtherTraffic::from_wan(&wan, &result, Some(3));
+        assert_eq!(other.download_mbps, Some(180.0));
+        // The WAN can't carry less than the test, rounding aside
+        assert_eq!(other.upload_mbps, Some(0.0));
+        assert!(other.affected(&result));
+
+        result.other_traffic = Some(other);
+        assert!(result.shared_line());
+
+        // A couple of Mbps on top of the test is background noise
+        result.download_mbps = 288.0;
+        result.other_traffic = Some(OtherTraffic::from_wan(&wan, &result, None));
+        assert!(!result.shared_line());
+    }
+
+    #[test]
+    fn test_quiet_window() {
+        let window: QuietWindow = toml::from_str(
+            "start = \"22:00\"\nend = \"06:30\"\ndays = [\"fri\", \"Saturday\"]\naction = \"latency\"",
+        )
+        .unwrap();
+        assert_eq!(window.action, QuietAction::Latency);
+        assert_eq!(
+            window.to_string(),
+            "22:00-06:30 Fri,Sat (latency probes only)"
+        );
+        // 2025-03-07 is a Friday
+        let at = |day: u32, hour: u32, minute: u32| {
+            chrono::NaiveDate::from_ymd_opt(2025, 3, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        assert!(window.contains(at(7, 23, 0)));
+        assert!(window.contains(at(8, 6, 0)));
+        assert!(!window.contains(at(8, 6, 30)));
+        assert!(!window.contains(at(7, 5, 0)));
+        assert!(window.contains(at(9, 1, 0)));
+        assert!(!window.contains(at(10, 1, 0)));
+
+        let daytime = QuietWindow {
+            start: TimeOfDay::try_from("09:00".to_string()).unwrap(),
+            end: TimeOfDay::try_from("17:00".to_string()).unwrap(),
+            days: Vec::new(),
+            action: QuietAction::Skip,
+        };
+        assert!(daytime.contains(at(10, 9, 0)));
+        assert!(!daytime.contains(at(10, 17, 0)));
+        assert!(TimeOfDay::try_from("25:00".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_interval() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let mut interval = AdaptiveInterval::new(minutes(30), minutes(5), minutes(120));
+        let result = |download_mbps: f64, ping_ms: f64| SpeedTestResult {
+            download_mbps,
+            ping_ms,
+            ..Default::default()
+        };
+
+        for expected in [45, 67, 101, 120] {
+            assert!(!interval.record(Some(&result(100.0, 20.0)), false));
+            assert_eq!(interval.current().as_secs() / 60, expected);
+        }
+        assert!(interval.record(Some(&result(60.0, 20.0)), false));
+        assert_eq!(interval.current(), minutes(5));
+        assert!(!interval.record(Some(&result(90.0, 35.0)), false));
+        assert!(interval.record(Some(&result(95.0, 60.0)), false));
+        assert!(interval.record(Some(&result(100.0, 20.0)), true));
+        assert!(interval.record(None, false));
+        assert_eq!(interval.current(), minutes(5));
+
+        // Too few results yet to judge speed against
+        let mut fresh = AdaptiveInterval::new(minutes(1), minutes(5), minutes(10));
+        assert_eq!(fresh.current(), minutes(5));
+        assert!(!fresh.record(Some(&result(1.0, 500.0)), false));
+    }
+
+    #[test]
+    fn test_adaptive_interval_follows_lasting_change() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let mut interval = AdaptiveInterval::new(minutes(30), minutes(5), minutes(120));
+        let result = |download_mbps: f64| SpeedTestResult {
+            download_mbps,
+            ping_ms: 20.0,
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            interval.record(Some(&result(100.0)), false);
+        }
+
+        // After a downgrade to 50 Mbps the first results are anomalies, a failure
+        // in between doesn't restart the count
+        assert!(interval.record(Some(&result(50.0)), false));
+        assert!(interval.record(None, false));
+        assert!(interval.record(Some(&result(50.0)), false));
+        assert!(interval.record(Some(&result(50.0)), false));
+        assert_eq!(interval.current(), minutes(5));
+        // Then they are what the line does now
+        assert!(!interval.record(Some(&result(50.0)), false));
+        assert!(interval.current() > minutes(5));
+        assert!(interval.record(Some(&result(30.0)), false));
+
+        // A single normal result in between ends the streak
+        let mut interval = AdaptiveInterval::new(minutes(30), minutes(5), minutes(120));
+        for download in [100.0, 100.0, 100.0, 50.0, 50.0, 100.0, 50.0] {
+            interval.record(Some(&result(download)), false);
+        }
+        assert!(interval.record(Some(&result(50.0)), false));
+    }
+
     #[test]
     fn test_detail_level_ordering() {
         assert!(DetailLevel::Basic < DetailLevel::Standard);
@@ -267,6 +4202,7 @@ mod tests {
             distance_km: Some(150.5),
             latency_ms: Some(25.0),
             provider: ServerProvider::Custom("Test".to_string()),
+            protocol: ServerProtocol::Cloudflare,
             capabilities: ServerCapabilities {
                 supports_download: true,
                 supports_upload: true,
@@ -278,6 +4214,7 @@ mod tests {
             country_code: Some("US".to_string()),
             city: Some("Test City".to_string()),
             is_backup: false,
+            endpoints: None,
         };
 
         assert_eq!(server.name, "Test Server");
@@ -294,6 +4231,8 @@ mod tests {
             address: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
             hostname: Some("gateway.example.com".to_string()),
             response_time_ms: Some(15.5),
+            network: None,
+            location: None,
         };
 
         assert_eq!(hop.hop_number, 5);
@@ -301,4 +4240,24 @@ mod tests {
         assert_eq!(hop.hostname, Some("gateway.example.com".to_string()));
         assert_eq!(hop.response_time_ms, Some(15.5));
     }
+
+    #[test]
+    fn test_route_snapshot_diff() {
+        let ip = |last: u8| Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)));
+        let route = |hops: Vec<Option<IpAddr>>| RouteSnapshot {
+            timestamp: Utc::now(),
+            target: "8.8.8.8".to_string(),
+            hops,
+        };
+        let previous = route(vec![ip(1), ip(2), None, ip(4)]);
+
+        // A hop that stayed silent this time is not a change
+        assert_eq!(route(vec![ip(1), None, ip(3), ip(4)]).diff(&previous), None);
+
+        let change = route(vec![ip(1), ip(9), None, ip(4), ip(5)])
+            .diff(&previous)
+            .unwrap();
+        assert_eq!(change.changed_hops, [2, 5]);
+        assert_eq!(change.previous_hops, previous.hops);
+    }
 }
@@ -9,7 +9,11 @@ use std::io::{self, Write};
 use std::thread;
 use std::time::Duration;
 
-use crate::modules::types::TestConfig;
+use crate::modules::terminal::{Symbols, TerminalCapabilities};
+use crate::modules::theme::Theme;
+use crate::modules::types::{
+    ConnectionQuality, LatencyStats, PhaseCountdown, TestConfig, VisualStyle,
+};
 
 // Bandwidth monitor state for real-time graph
 #[derive(Clone)]
@@ -19,12 +23,26 @@ pub struct BandwidthMonitor {
     pub peak_speed: Arc<RwLock<f64>>,
     pub is_final: Arc<RwLock<bool>>,
     pub throbber_frame: Arc<RwLock<usize>>,
+    /// Time left in the phase and the test, shown until the phase is final
+    pub countdown: Arc<RwLock<Option<PhaseCountdown>>>,
     #[allow(dead_code)]
     pub title: String,
     pub label: String,
+    pub style: VisualStyle,
+    /// Maps a throughput to the quality band used to colour the gauge
+    pub quality_band: fn(f64) -> ConnectionQuality,
+    pub theme: Theme,
+    pub symbols: &'static Symbols,
 }
 
 impl BandwidthMonitor {
+    const GRAPH_WIDTH: usize = 80;
+    const GRAPH_HEIGHT: usize = 8;
+    const GAUGE_WIDTH: usize = 49;
+    const GAUGE_ROWS: usize = 12;
+    /// Inner edge of the gauge arc relative to its outer radius
+    const GAUGE_INNER_RADIUS: f64 = 0.72;
+
     pub fn new(title: String, label: String) -> Self {
         Self {
             speed_history: Arc::new(RwLock::new(Vec::new())),
@@ -32,8 +50,13 @@ impl BandwidthMonitor {
             peak_speed: Arc::new(RwLock::new(0.0)),
             is_final: Arc::new(RwLock::new(false)),
             throbber_frame: Arc::new(RwLock::new(0)),
+            countdown: Arc::new(RwLock::new(None)),
             title,
             label,
+            style: VisualStyle::Graph,
+            quality_band: ConnectionQuality::from_download_mbps,
+            theme: Theme::default(),
+            symbols: TerminalCapabilities::get().symbols(),
         }
     }
 
@@ -56,126 +79,119 @@ impl BandwidthMonitor {
         }
     }
 
+    pub async fn set_countdown(&self, countdown: PhaseCountdown) {
+        *self.countdown.write().await = Some(countdown);
+    }
+
     pub async fn mark_final(&self) {
         let mut is_final = self.is_final.write().await;
         *is_final = true;
     }
 
     pub async fn render_live(&self) -> io::Result<()> {
-        let history = self.speed_history.read().await;
-        let current = self.current_speed.read().await;
-        let peak = self.peak_speed.read().await;
-        let is_final = self.is_final.read().await;
-        let frame = self.throbber_frame.read().await;
+        self.render(false).await
+    }
 
-        // Display speed with throbber or checkmark
-        let throbber_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-        let indicator = if *is_final {
-            "✓"
-        } else {
-            &throbber_chars[*frame].to_string()
-        };
+    pub async fn render_live_update(&self) -> io::Result<()> {
+        self.render(true).await
+    }
 
-        println!(
-            "{} {}: {}",
-            indicator.bright_cyan(),
-            self.label.bright_blue().bold(),
-            format!("{:.1} Mbps", current).bright_green().bold()
-        );
-        println!();
-        println!(
-            "{} {}",
-            "Peak:".bright_cyan(),
-            format!("{:.1} Mbps", peak).bright_cyan()
-        );
-        println!();
+    /// Number of terminal lines a single frame occupies, used to rewind before redrawing
+    fn frame_height(&self) -> usize {
+        match self.style {
+            VisualStyle::Graph => 4 + Self::GRAPH_HEIGHT + 1,
+            VisualStyle::Gauge => 2 + Self::GAUGE_ROWS + 1,
+            VisualStyle::Minimal => 1,
+        }
+    }
 
-        // Create filled area graph
-        let max_val = if history.is_empty() {
-            1.0
-        } else {
-            history.iter().cloned().fold(0.0f64, f64::max).max(1.0)
+    async fn render(&self, rewind: bool) -> io::Result<()> {
+        let history = self.speed_history.read().await;
+        let current = *self.current_speed.read().await;
+        let peak = *self.peak_speed.read().await;
+        let is_final = *self.is_final.read().await;
+        let frame = *self.throbber_frame.read().await;
+        let countdown = match *self.countdown.read().await {
+            Some(countdown) if !is_final => format!("  ⏱ {}", countdown.summary())
+                .color(self.theme.primary)
+                .to_string(),
+            _ => String::new(),
         };
-        let width = 80; // Full terminal width
-        let height = 8; // Height of graph
 
-        // Generate graph lines with filled area
-        for row in (0..height).rev() {
-            let threshold = (row as f64 / height as f64) * max_val;
-            print!("│");
-
-            if history.is_empty() {
-                // Show empty graph
-                for _ in 0..width {
-                    print!(" ");
-                }
-            } else {
-                // Take the most recent samples up to width
-                let samples_to_show = history.len().min(width);
-                let start_idx = history.len().saturating_sub(width);
-
-                for i in start_idx..history.len() {
-                    let speed = history[i];
-                    let char = if speed >= threshold { "█" } else { " " };
-                    print!("{}", char.bright_yellow());
-                }
-
-                // Fill remaining space if we have fewer samples than width
-                for _ in 0..(width - samples_to_show) {
-                    print!(" ");
-                }
+        if rewind {
+            // Move cursor up over the previous frame, clear it and return to its start
+            let height = self.frame_height();
+            print!("\x1B[{}A", height);
+            for _ in 0..height {
+                print!("\x1B[2K"); // Clear line
+                print!("\x1B[1B"); // Move down 1 line
             }
-
-            println!();
+            print!("\x1B[{}A", height);
         }
 
-        // Bottom axis
-        print!("└");
-        for _ in 0..width {
-            print!("─");
+        // Display speed with throbber or checkmark
+        let spinner = self.symbols.spinner;
+        let indicator = if is_final {
+            self.symbols.check
+        } else {
+            spinner[frame % spinner.len()]
+        };
+
+        match self.style {
+            VisualStyle::Graph => {
+                self.print_speed_header(indicator, current, peak, &countdown);
+                self.print_area_graph(&history);
+            }
+            VisualStyle::Gauge => {
+                println!(
+                    "{} {}{}",
+                    indicator.color(self.theme.primary),
+                    self.label.color(self.theme.label).bold(),
+                    countdown
+                );
+                println!();
+                self.print_gauge(current, peak);
+            }
+            VisualStyle::Minimal => {
+                println!(
+                    "{} {}: {}  {}{}",
+                    indicator.color(self.theme.primary),
+                    self.label.color(self.theme.label).bold(),
+                    format!("{:.1} Mbps", current)
+                        .color(self.theme.value)
+                        .bold(),
+                    format!("(peak {:.1} Mbps)", peak).color(self.theme.primary),
+                    countdown
+                );
+            }
         }
-        println!();
 
         std::io::stdout().flush()?;
         Ok(())
     }
 
-    pub async fn render_live_update(&self) -> io::Result<()> {
-        let history = self.speed_history.read().await;
-        let current = self.current_speed.read().await;
-        let peak = self.peak_speed.read().await;
-        let is_final = self.is_final.read().await;
-        let frame = self.throbber_frame.read().await;
-
-        // Move cursor up 13 lines and clear them
-        print!("\x1B[13A"); // Move up 13 lines
-        for _ in 0..13 {
-            print!("\x1B[2K"); // Clear line
-            print!("\x1B[1B"); // Move down 1 line
-        }
-        print!("\x1B[13A"); // Move back up to start position
-
-        // Display speed with throbber or checkmark
-        let throbber_chars = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
-        let indicator = if *is_final {
-            "✓"
-        } else {
-            &throbber_chars[*frame].to_string()
-        };
-
+    fn print_speed_header(&self, indicator: &str, current: f64, peak: f64, countdown: &str) {
         println!(
             "{} {}: {}",
-            indicator.bright_cyan(),
-            self.label.bright_blue().bold(),
-            format!("{:.1} Mbps", current).bright_green().bold()
+            indicator.color(self.theme.primary),
+            self.label.color(self.theme.label).bold(),
+            format!("{:.1} Mbps", current)
+                .color(self.theme.value)
+                .bold()
         );
         println!();
         println!(
-            "{} {}",
-            "Peak:".bright_cyan(),
-            format!("{:.1} Mbps", peak).bright_cyan()
+            "{} {}{}",
+            "Peak:".color(self.theme.primary),
+            format!("{:.1} Mbps", peak).color(self.theme.primary),
+            countdown
         );
         println!();
+    }
+
+    fn print_area_graph(&self, history: &[f64]) {
+        let width = Self::GRAPH_WIDTH;
+        let height = Self::GRAPH_HEIGHT;
 
         // Create filled area graph
         let max_val = if history.is_empty() {
@@ -183,67 +199,173 @@ impl BandwidthMonitor {
         } else {
             history.iter().cloned().fold(0.0f64, f64::max).max(1.0)
         };
-        let width = 80;
-        let height = 8;
 
         // Generate graph lines with filled area
         for row in (0..height).rev() {
             let threshold = (row as f64 / height as f64) * max_val;
-            print!("│");
-
-            if history.is_empty() {
-                for _ in 0..width {
-                    print!(" ");
-                }
-            } else {
-                let samples_to_show = history.len().min(width);
-                let start_idx = history.len().saturating_sub(width);
-
-                for i in start_idx..history.len() {
-                    let speed = history[i];
-                    let char = if speed >= threshold { "█" } else { " " };
-                    print!("{}", char.bright_yellow());
-                }
-
-                for _ in 0..(width - samples_to_show) {
-                    print!(" ");
-                }
+            print!("{}", self.symbols.vertical);
+
+            // Take the most recent samples up to width
+            let start_idx = history.len().saturating_sub(width);
+            let shown = &history[start_idx..];
+
+            for speed in shown {
+                let char = if *speed >= threshold {
+                    self.symbols.block
+                } else {
+                    " "
+                };
+                print!("{}", char.color(self.theme.graph));
             }
 
+            // Fill remaining space if we have fewer samples than width
+            print!("{}", " ".repeat(width - shown.len()));
             println!();
         }
 
         // Bottom axis
-        print!("└");
-        for _ in 0..width {
-            print!("─");
+        println!(
+            "{}{}",
+            self.symbols.corner,
+            self.symbols.horizontal.repeat(width)
+        );
+    }
+
+    fn print_gauge(&self, current: f64, peak: f64) {
+        let scale_max = Self::gauge_scale(peak);
+        let cells = Self::gauge_cells(current / scale_max);
+
+        for row in cells {
+            let line: String = row
+                .into_iter()
+                .map(|cell| match cell {
+                    GaugeCell::Empty => " ".to_string(),
+                    GaugeCell::Track => self.symbols.shade.dimmed().to_string(),
+                    GaugeCell::Filled(fraction) => {
+                        let band = (self.quality_band)(fraction * scale_max);
+                        self.symbols
+                            .block
+                            .color(self.theme.quality_color(band))
+                            .to_string()
+                    }
+                })
+                .collect();
+            println!("{}", line);
         }
-        println!();
 
-        std::io::stdout().flush()?;
-        Ok(())
+        // Scale labels at both ends with the current reading centred underneath the arc
+        let reading = format!("{:.1} Mbps", current);
+        let max_label = format!("{:.0}", scale_max);
+        let padding = Self::GAUGE_WIDTH.saturating_sub(1 + reading.len() + max_label.len());
+        let left = padding / 2;
+        println!(
+            "{}{}{}{}{}",
+            "0".dimmed(),
+            " ".repeat(left),
+            reading
+                .color(self.theme.quality_color((self.quality_band)(current)))
+                .bold(),
+            " ".repeat(padding - left),
+            max_label.dimmed()
+        );
+    }
+
+    /// Pick a round full-scale value that leaves some headroom above the peak
+    fn gauge_scale(peak: f64) -> f64 {
+        const STEPS: [f64; 10] = [
+            10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+        ];
+        STEPS
+            .iter()
+            .copied()
+            .find(|step| *step >= peak * 1.1)
+            .unwrap_or(STEPS[STEPS.len() - 1])
     }
+
+    /// Lay out the semi-circular arc, filling it clockwise from the left up to `fill`
+    fn gauge_cells(fill: f64) -> Vec<Vec<GaugeCell>> {
+        let fill = fill.clamp(0.0, 1.0);
+        // Terminal cells are roughly twice as tall as they are wide
+        let radius_x = Self::GAUGE_WIDTH as f64 / 2.0;
+        let radius_y = Self::GAUGE_ROWS as f64;
+
+        (0..Self::GAUGE_ROWS)
+            .map(|row| {
+                let y = (Self::GAUGE_ROWS - row) as f64 - 0.5;
+                (0..Self::GAUGE_WIDTH)
+                    .map(|col| {
+                        let x = col as f64 + 0.5 - radius_x;
+                        let distance = ((x / radius_x).powi(2) + (y / radius_y).powi(2)).sqrt();
+                        if !(Self::GAUGE_INNER_RADIUS..=1.0).contains(&distance) {
+                            return GaugeCell::Empty;
+                        }
+
+                        // 0.0 at the left end of the arc, 1.0 at the right end
+                        let fraction =
+                            1.0 - (y / radius_y).atan2(x / radius_x) / std::f64::consts::PI;
+                        if fraction <= fill {
+                            GaugeCell::Filled(fraction)
+                        } else {
+                            GaugeCell::Track
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GaugeCell {
+    Empty,
+    Track,
+    /// Filled arc cell, carrying its position along the arc (0.0-1.0)
+    Filled(f64),
 }
 
 pub struct UI {
     term: Term,
     multi_progress: MultiProgress,
+    visual_style: VisualStyle,
+    theme: Theme,
+    symbols: &'static Symbols,
 }
 
 impl UI {
-    pub fn new(_config: TestConfig) -> Self {
+    pub fn new(config: TestConfig) -> Self {
+        let capabilities = TerminalCapabilities::get();
         Self {
             term: Term::stdout(),
             multi_progress: MultiProgress::new(),
+            visual_style: config.visual_style,
+            theme: Theme::new(config.theme).for_terminal(&capabilities),
+            symbols: capabilities.symbols(),
         }
     }
 
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn symbols(&self) -> &'static Symbols {
+        self.symbols
+    }
+
+    /// Wrap a status message in the cyberpunk brackets (ASCII on limited terminals)
+    fn bracketed(&self, message: &str) -> String {
+        format!("{} {} {}", self.symbols.open, message, self.symbols.close)
+    }
+
     pub fn clear_screen(&self) -> io::Result<()> {
+        // Escape sequences would only end up as noise in logs and pipes
+        if !TerminalCapabilities::get().is_tty {
+            return Ok(());
+        }
         self.term.clear_screen()
     }
 
     pub fn show_welcome_banner(&self) -> io::Result<()> {
-        self.term.clear_screen()?;
+        self.clear_screen()?;
 
         let banner = r#"
  _   _ ______ _______ _____  _    _ _   _ _   _ ______ _____
@@ -255,22 +377,35 @@ impl UI {
 
         "#;
 
-        println!("{}", banner.bright_cyan());
+        let theme = &self.theme;
+        println!("{}", banner.color(theme.primary));
 
-        println!("{}", "SYSTEM STATUS".bright_magenta().bold());
-        println!("{}", "⟨⟨⟨ NEURAL INTERFACE: ONLINE ⟩⟩⟩".bright_green());
-        println!("{}", "⟨⟨⟨ NETWORK SCANNER: INITIALIZED ⟩⟩⟩".bright_green());
-        println!("{}", "⟨⟨⟨ QUANTUM DIAGNOSTICS: READY ⟩⟩⟩".bright_green());
+        println!("{}", "SYSTEM STATUS".color(theme.accent).bold());
+        println!(
+            "{}",
+            self.bracketed("NEURAL INTERFACE: ONLINE")
+                .color(theme.value)
+        );
+        println!(
+            "{}",
+            self.bracketed("NETWORK SCANNER: INITIALIZED")
+                .color(theme.value)
+        );
+        println!(
+            "{}",
+            self.bracketed("QUANTUM DIAGNOSTICS: READY")
+                .color(theme.value)
+        );
         println!();
         println!(
             "{}",
             ">>> JACK IN AND ANALYZE YOUR DIGITAL HIGHWAY <<<"
-                .bright_yellow()
+                .color(theme.highlight)
                 .bold()
         );
         println!(
             "{}",
-            ">>> DATA FLOWS | PACKET STREAMS | NEURAL PATHS <<<".bright_blue()
+            ">>> DATA FLOWS | PACKET STREAMS | NEURAL PATHS <<<".color(theme.label)
         );
         println!();
 
@@ -285,7 +420,7 @@ impl UI {
                     "{spinner:.green} {msg} [{bar:40.cyan/blue}] {percent}% [{elapsed_precise}]",
                 )
                 .unwrap()
-                .progress_chars("━━╸─"),
+                .progress_chars(self.symbols.progress_chars),
         );
         pb.set_message(message.to_string());
         pb
@@ -295,9 +430,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_cyan} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_cyan")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -308,9 +446,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_green} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_green")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -321,9 +462,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_blue} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_blue")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -334,9 +478,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_magenta} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_magenta")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -347,9 +494,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_green} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_green")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(100));
@@ -360,9 +510,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_cyan} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_cyan")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -373,9 +526,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_green} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_green")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -386,9 +542,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_yellow} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_yellow")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -399,9 +558,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_cyan} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_cyan")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -412,9 +574,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_yellow} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_yellow")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -425,9 +590,12 @@ impl UI {
         let pb = self.multi_progress.add(ProgressBar::new_spinner());
         pb.set_style(
             ProgressStyle::default_spinner()
-                .template("{spinner:.bright_yellow} {msg}")
+                .template(&format!(
+                    "{{spinner:.{}}} {{msg}}",
+                    self.theme.spinner_color("bright_yellow")
+                ))
                 .unwrap()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+                .tick_strings(self.symbols.spinner),
         );
         pb.set_message(message.to_string());
         pb.enable_steady_tick(Duration::from_millis(80));
@@ -439,25 +607,33 @@ impl UI {
         println!(
             "{}",
             format!(">>> {} <<<", title.to_uppercase())
-                .bright_magenta()
+                .color(self.theme.accent)
                 .bold()
         );
         Ok(())
     }
 
     pub fn show_error(&self, message: &str) -> io::Result<()> {
-        println!("{} {}", "ERROR:".bright_red().bold(), message.bright_red());
+        println!(
+            "{} {}",
+            "ERROR:".color(self.theme.error).bold(),
+            message.color(self.theme.error)
+        );
         Ok(())
     }
 
     pub fn show_info(&self, message: &str) -> io::Result<()> {
-        println!("{} {}", "INFO:".bright_blue().bold(), message.bright_blue());
+        println!(
+            "{} {}",
+            "INFO:".color(self.theme.label).bold(),
+            message.color(self.theme.label)
+        );
         Ok(())
     }
 
     pub fn show_typing_effect(&self, text: &str) -> io::Result<()> {
         for char in text.chars() {
-            print!("{}", char.to_string().bright_green());
+            print!("{}", char.to_string().color(self.theme.value));
             std::io::stdout().flush()?;
             thread::sleep(Duration::from_millis(50));
         }
@@ -466,13 +642,13 @@ impl UI {
     }
 
     pub fn show_matrix_effect(&self, lines: usize) -> io::Result<()> {
-        let matrix_chars = ["0", "1", "⠁", "⠂", "⠄", "⡀", "⢀", "⠠", "⠐", "⠈"];
+        let matrix_chars = self.symbols.matrix;
 
         for _ in 0..lines {
-            print!("{}", "█".bright_green());
+            print!("{}", self.symbols.block.color(self.theme.value));
             for _ in 0..60 {
                 let idx = rand::rng().random_range(0..matrix_chars.len());
-                print!("{}", matrix_chars[idx].bright_green());
+                print!("{}", matrix_chars[idx].color(self.theme.value));
                 thread::sleep(Duration::from_millis(20));
             }
             println!();
@@ -482,11 +658,11 @@ impl UI {
 
     pub fn show_pulse_text(&self, text: &str, pulses: usize) -> io::Result<()> {
         for _ in 0..pulses {
-            print!("\r{}", text.bright_cyan().bold());
+            print!("\r{}", text.color(self.theme.primary).bold());
             std::io::stdout().flush()?;
             thread::sleep(Duration::from_millis(500));
 
-            print!("\r{}", text.bright_blue());
+            print!("\r{}", text.color(self.theme.label));
             std::io::stdout().flush()?;
             thread::sleep(Duration::from_millis(500));
         }
@@ -496,22 +672,121 @@ impl UI {
 
     pub fn show_connection_establishing(&self) -> io::Result<()> {
         let steps = [
-            "⟨⟨⟨ INITIALIZING NEURAL INTERFACE ⟩⟩⟩",
-            "⟨⟨⟨ SCANNING NETWORK TOPOLOGY ⟩⟩⟩",
-            "⟨⟨⟨ ESTABLISHING QUANTUM TUNNEL ⟩⟩⟩",
-            "⟨⟨⟨ CALIBRATING DATA STREAMS ⟩⟩⟩",
-            "⟨⟨⟨ CONNECTION ESTABLISHED ⟩⟩⟩",
+            "INITIALIZING NEURAL INTERFACE",
+            "SCANNING NETWORK TOPOLOGY",
+            "ESTABLISHING QUANTUM TUNNEL",
+            "CALIBRATING DATA STREAMS",
+            "CONNECTION ESTABLISHED",
         ];
 
         for step in steps.iter() {
-            println!("{}", step.bright_magenta());
+            println!("{}", self.bracketed(step).color(self.theme.accent));
             thread::sleep(Duration::from_millis(800));
         }
         println!();
         Ok(())
     }
 
-    pub fn create_bandwidth_monitor(&self, title: &str, label: &str) -> BandwidthMonitor {
-        BandwidthMonitor::new(title.to_string(), label.to_string())
+    pub fn show_latency_histogram(&self, stats: &LatencyStats) -> io::Result<()> {
+        const BAR_WIDTH: usize = 40;
+
+        println!(
+            "{} {}",
+            "Latency Distribution".color(self.theme.accent).bold(),
+            format!("({} samples)", stats.sample_count).dimmed()
+        );
+
+        let max_count = stats
+            .histogram
+            .iter()
+            .map(|bucket| bucket.count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        for bucket in &stats.histogram {
+            let filled = (bucket.count * BAR_WIDTH).div_ceil(max_count);
+            let label = format!("{:>6.0}-{:<6.0}ms", bucket.lower_ms, bucket.upper_ms);
+            println!(
+                "{} {}{}{} {}",
+                label.color(self.theme.primary),
+                self.symbols.vertical,
+                self.symbols.block.repeat(filled).color(self.theme.graph),
+                " ".repeat(BAR_WIDTH - filled),
+                bucket.count
+            );
+        }
+
+        println!(
+            "{} {:.1}  {} {:.1}  {} {:.1}  {} {:.1}  {} {:.1} ms",
+            "min".color(self.theme.label),
+            stats.min_ms,
+            "avg".color(self.theme.label),
+            stats.avg_ms,
+            "p95".color(self.theme.label),
+            stats.p95_ms,
+            "p99".color(self.theme.label),
+            stats.p99_ms,
+            "max".color(self.theme.label),
+            stats.max_ms
+        );
+
+        Ok(())
+    }
+
+    pub fn create_bandwidth_monitor(
+        &self,
+        title: &str,
+        label: &str,
+        quality_band: fn(f64) -> ConnectionQuality,
+    ) -> BandwidthMonitor {
+        BandwidthMonitor {
+            style: self.visual_style,
+            quality_band,
+            theme: self.theme,
+            symbols: self.symbols,
+            ..BandwidthMonitor::new(title.to_string(), label.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(cells: &[Vec<GaugeCell>], filled: bool) -> usize {
+        cells
+            .iter()
+            .flatten()
+            .filter(|cell| match cell {
+                GaugeCell::Filled(_) => filled,
+                GaugeCell::Track => !filled,
+                GaugeCell::Empty => false,
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_gauge_fill_follows_value() {
+        let empty = BandwidthMonitor::gauge_cells(0.0);
+        let half = BandwidthMonitor::gauge_cells(0.5);
+        let full = BandwidthMonitor::gauge_cells(1.0);
+
+        assert_eq!(empty.len(), BandwidthMonitor::GAUGE_ROWS);
+        assert_eq!(count(&empty, true), 0);
+        assert_eq!(count(&full, false), 0);
+
+        // The arc is symmetric, so half a scale fills roughly half the cells
+        let total = count(&full, true);
+        let filled = count(&half, true);
+        assert!(filled.abs_diff(total / 2) <= BandwidthMonitor::GAUGE_ROWS);
+    }
+
+    #[test]
+    fn test_gauge_scale_leaves_headroom() {
+        assert_eq!(BandwidthMonitor::gauge_scale(0.0), 10.0);
+        assert_eq!(BandwidthMonitor::gauge_scale(95.0), 250.0);
+        assert_eq!(BandwidthMonitor::gauge_scale(400.0), 500.0);
+        assert_eq!(BandwidthMonitor::gauge_scale(50_000.0), 10000.0);
     }
 }
@@ -0,0 +1,119 @@
+//! WebSocket Connections and Latency
+//!
+//! Games and calls keep one connection open, so what they feel is the round trip
+//! on a warm connection, not the TCP and TLS handshakes an HTTP probe may pay.
+//! Servers whose backend offers a WebSocket are timed both ways: how long the
+//! connection took to set up, then ping frames on it. Every WebSocket server has
+//! to answer pings with pongs, whatever it speaks on top.
+
+use futures::{SinkExt, StreamExt};
+use rustls::pki_types::ServerName;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::modules::resolver::ResolverCache;
+use crate::modules::tls;
+use crate::modules::types::{median, LatencyStats, WebSocketLatency};
+
+pub type Socket = WebSocketStream<TlsStream<TcpStream>>;
+
+/// Open a WebSocket to a `wss://` URL, offering `subprotocol` when given
+pub async fn connect(
+    url: &str,
+    resolver: &ResolverCache,
+    subprotocol: Option<&'static str>,
+    config: Option<WebSocketConfig>,
+) -> Result<Socket, Box<dyn std::error::Error>> {
+    let parsed = url::Url::parse(url)?;
+    if parsed.scheme() != "wss" {
+        return Err(format!("{} is not a wss:// URL", url).into());
+    }
+    let host = parsed
+        .host_str()
+        .ok_or("WebSocket URL has no host")?
+        .to_string();
+    let port = parsed.port().unwrap_or(443);
+
+    let addresses = resolver
+        .lookup_target(&format!("{}:{}", host, port))
+        .await?;
+    let tcp = TcpStream::connect(addresses.as_slice()).await?;
+    tcp.set_nodelay(true)?;
+
+    let mut tls_config = tls::client_config()?;
+    tls_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let stream = TlsConnector::from(Arc::new(tls_config))
+        .connect(ServerName::try_from(host)?, tcp)
+        .await?;
+
+    let mut request = url.into_client_request()?;
+    if let Some(subprotocol) = subprotocol {
+        request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static(subprotocol),
+        );
+    }
+    let (socket, _) = tokio_tungstenite::client_async_with_config(request, stream, config).await?;
+    Ok(socket)
+}
+
+/// Time the setup of a WebSocket to `url`, then `pings` pings on it with `spacing`
+/// after each. Host names should already be in `resolver`, so setup is not a
+/// DNS lookup.
+pub async fn measure_latency(
+    url: &str,
+    resolver: &ResolverCache,
+    pings: u32,
+    spacing: Duration,
+    timeout: Duration,
+) -> Result<WebSocketLatency, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let mut socket = tokio::time::timeout(timeout, connect(url, resolver, None, None))
+        .await
+        .map_err(|_| "WebSocket setup timed out")??;
+    let setup_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut rtts = Vec::new();
+    let mut unanswered = 0;
+    for sequence in 0..pings.max(1) {
+        let payload = sequence.to_be_bytes();
+        let sent = Instant::now();
+        socket.send(Message::Ping(payload.to_vec().into())).await?;
+        match tokio::time::timeout(timeout, pong(&mut socket, &payload)).await {
+            Ok(Ok(())) => rtts.push(sent.elapsed().as_secs_f64() * 1000.0),
+            Ok(Err(e)) => return Err(e),
+            Err(_) => unanswered += 1,
+        }
+        tokio::time::sleep(spacing).await;
+    }
+    let _ = socket.close(None).await;
+
+    let rtt_stats = LatencyStats::from_samples(&rtts).ok_or("no ping was answered")?;
+    Ok(WebSocketLatency {
+        setup_ms,
+        rtt_ms: median(&rtts),
+        rtt_stats,
+        unanswered,
+    })
+}
+
+/// Wait for the pong to the ping carrying `payload`, passing over anything the
+/// server sends meanwhile and late pongs to earlier pings
+async fn pong(socket: &mut Socket, payload: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            Message::Pong(data) if data.as_ref() == payload => return Ok(()),
+            Message::Close(_) => break,
+            _ => continue,
+        }
+    }
+    Err("the server closed the WebSocket".into())
+}
@@ -0,0 +1,247 @@
+//! Windows Network Stack Checks
+//!
+//! Looks for Windows settings that commonly slow a connection down or drop it:
+//! receive window auto-tuning, receive-side scaling, large send offload, adapters
+//! Windows may switch off to save power, and Wi-Fi power saving in the active
+//! power plan. Everything is read in one PowerShell run through the NetAdapter
+//! and power plan WMI classes, whose values, unlike `netsh` output, are not
+//! translated into the system language.
+
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::modules::types::{
+    AdapterSettings, WifiPowerSaving, WindowsStackCheck, WindowsStackIssue,
+};
+
+/// Power plan subgroup and setting of the Wi-Fi power saving mode
+pub const WIRELESS_SUBGROUP: &str = "19cbb8fa-5279-450e-9fac-8a3d5fedd0c1";
+pub const WIRELESS_POWER_SETTING: &str = "12bbebe6-58d6-4636-95bb-3217ef867c1a";
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Prints the settings as one JSON object, see `RawStack`. Enum values are turned
+/// into strings, which ConvertTo-Json would otherwise write as numbers.
+const SCRIPT: &str = r#"
+$ErrorActionPreference = 'SilentlyContinue'
+$plan = (Get-CimInstance -Namespace root\cimv2\power -ClassName Win32_PowerPlan -Filter 'IsActive=True').InstanceID -replace '^.*\\', ''
+$wifi = @(Get-CimInstance -Namespace root\cimv2\power -ClassName Win32_PowerSettingDataIndex |
+    Where-Object { $_.InstanceID -like "*$plan*12bbebe6-58d6-4636-95bb-3217ef867c1a*" } |
+    ForEach-Object { [pscustomobject]@{ source = ($_.InstanceID -split '\\')[2]; value = [int]$_.SettingIndexValue } })
+$adapters = @(Get-NetAdapter -Physical | Where-Object Status -eq 'Up' | ForEach-Object {
+    $lso = Get-NetAdapterLso -Name $_.Name
+    [pscustomobject]@{
+        name = $_.Name
+        description = $_.InterfaceDescription
+        media = "$($_.PhysicalMediaType)"
+        rss = "$((Get-NetAdapterRss -Name $_.Name).Enabled)"
+        lso_ipv4 = "$($lso.IPv4Enabled)"
+        lso_ipv6 = "$($lso.IPv6Enabled)"
+        power_saving = "$((Get-NetAdapterPowerManagement -Name $_.Name).AllowComputerToTurnOffDevice)"
+    }
+})
+[pscustomobject]@{
+    autotuning = "$((Get-NetTCPSetting -SettingName Internet).AutoTuningLevelLocal)"
+    rss = "$((Get-NetOffloadGlobalSetting).ReceiveSideScaling)"
+    wifi_power_saving = $wifi
+    adapters = $adapters
+} | ConvertTo-Json -Depth 4 -Compress
+"#;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawStack {
+    autotuning: String,
+    rss: String,
+    wifi_power_saving: Vec<RawPowerIndex>,
+    adapters: Vec<RawAdapter>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawPowerIndex {
+    /// `AC` or `DC`
+    source: String,
+    value: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawAdapter {
+    name: String,
+    description: String,
+    media: String,
+    rss: String,
+    lso_ipv4: String,
+    lso_ipv6: String,
+    power_saving: String,
+}
+
+/// Read the settings, None on other platforms or when PowerShell didn't answer
+pub async fn check() -> Option<WindowsStackCheck> {
+    if !cfg!(windows) {
+        return None;
+    }
+
+    let output = tokio::time::timeout(
+        TIMEOUT,
+        tokio::process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Settings and their issues from the script's output
+fn parse(json: &str) -> Option<WindowsStackCheck> {
+    let raw: RawStack = serde_json::from_str(json.trim()).ok()?;
+
+    let power_saving = |source: &str| {
+        raw.wifi_power_saving
+            .iter()
+            .find(|index| index.source.eq_ignore_ascii_case(source))
+            .and_then(|index| wifi_power_saving(index.value))
+    };
+    let mut check = WindowsStackCheck {
+        autotuning_level: Some(raw.autotuning.trim().to_string()).filter(|level| !level.is_empty()),
+        rss_enabled: flag(&raw.rss),
+        wifi_power_saving_ac: power_saving("AC"),
+        wifi_power_saving_dc: power_saving("DC"),
+        adapters: raw
+            .adapters
+            .iter()
+            .map(|adapter| AdapterSettings {
+                name: adapter.name.clone(),
+                description: adapter.description.clone(),
+                wireless: adapter.media.contains("802.11") || adapter.media.contains("Wireless"),
+                rss_enabled: flag(&adapter.rss),
+                lso_enabled: match (flag(&adapter.lso_ipv4), flag(&adapter.lso_ipv6)) {
+                    (None, None) => None,
+                    (ipv4, ipv6) => Some(ipv4 == Some(true) || ipv6 == Some(true)),
+                },
+                power_saving: flag(&adapter.power_saving),
+            })
+            .collect(),
+        issues: Vec::new(),
+    };
+    check.issues = issues(&check);
+    Some(check)
+}
+
+/// `True`/`Enabled` or `False`/`Disabled`; anything else, e.g. `Unsupported`, is unknown
+fn flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "enabled" => Some(true),
+        "false" | "disabled" => Some(false),
+        _ => None,
+    }
+}
+
+fn wifi_power_saving(index: u32) -> Option<WifiPowerSaving> {
+    match index {
+        0 => Some(WifiPowerSaving::MaximumPerformance),
+        1 => Some(WifiPowerSaving::LowPowerSaving),
+        2 => Some(WifiPowerSaving::MediumPowerSaving),
+        3 => Some(WifiPowerSaving::MaximumPowerSaving),
+        _ => None,
+    }
+}
+
+fn issues(check: &WindowsStackCheck) -> Vec<WindowsStackIssue> {
+    let mut issues = Vec::new();
+
+    // "Normal" and "Experimental" let the window grow, the rest cap it
+    if check.autotuning_level.as_deref().is_some_and(|level| {
+        ["disabled", "highlyrestricted", "restricted"]
+            .contains(&level.to_ascii_lowercase().as_str())
+    }) {
+        issues.push(WindowsStackIssue::AutoTuningRestricted);
+    }
+    if check.rss_enabled == Some(false)
+        || check
+            .adapters
+            .iter()
+            .any(|adapter| adapter.rss_enabled == Some(false))
+    {
+        issues.push(WindowsStackIssue::RssDisabled);
+    }
+    if check
+        .adapters
+        .iter()
+        .any(|adapter| adapter.power_saving == Some(true))
+    {
+        issues.push(WindowsStackIssue::AdapterPowerSaving);
+    }
+    // Only worth mentioning while a Wi-Fi adapter is in use
+    if check.adapters.iter().any(|adapter| adapter.wireless)
+        && [check.wifi_power_saving_ac, check.wifi_power_saving_dc]
+            .into_iter()
+            .flatten()
+            .any(|mode| mode != WifiPowerSaving::MaximumPerformance)
+    {
+        issues.push(WindowsStackIssue::WifiPowerSaving);
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_healthy_stack() {
+        let json = r#"{"autotuning":"Normal","rss":"Enabled",
+            "wifi_power_saving":[{"source":"AC","value":0},{"source":"DC","value":2}],
+            "adapters":[{"name":"Ethernet","description":"Intel(R) Ethernet Connection I219-V",
+                "media":"802.3","rss":"True","lso_ipv4":"True","lso_ipv6":"False","power_saving":"Disabled"}]}"#;
+
+        let check = parse(json).unwrap();
+        assert_eq!(check.autotuning_level.as_deref(), Some("Normal"));
+        assert_eq!(check.rss_enabled, Some(true));
+        assert_eq!(
+            check.wifi_power_saving_dc,
+            Some(WifiPowerSaving::MediumPowerSaving)
+        );
+        assert_eq!(check.adapters.len(), 1);
+        assert!(!check.adapters[0].wireless);
+        assert_eq!(check.adapters[0].lso_enabled, Some(true));
+        assert_eq!(check.adapters[0].power_saving, Some(false));
+        assert!(
+            check.issues.is_empty(),
+            "Wi-Fi power saving doesn't matter without a Wi-Fi adapter"
+        );
+    }
+
+    #[test]
+    fn test_parse_problem_stack() {
+        let json = r#"{"autotuning":"Disabled","rss":"Enabled","wifi_power_saving":[{"source":"AC","value":3}],
+            "adapters":[{"name":"Wi-Fi","description":"Realtek 8822CE","media":"Native 802.11",
+                "rss":"False","lso_ipv4":"","lso_ipv6":"","power_saving":"Enabled"}]}"#;
+
+        let check = parse(json).unwrap();
+        assert!(check.adapters[0].wireless);
+        assert_eq!(check.adapters[0].lso_enabled, None);
+        assert_eq!(
+            check.issues,
+            vec![
+                WindowsStackIssue::AutoTuningRestricted,
+                WindowsStackIssue::RssDisabled,
+                WindowsStackIssue::AdapterPowerSaving,
+                WindowsStackIssue::WifiPowerSaving,
+            ]
+        );
+
+        assert_eq!(parse("").map(|check| check.issues), None);
+        assert_eq!(
+            parse("{}").unwrap(),
+            WindowsStackCheck::default(),
+            "cmdlets missing on older Windows leave everything unknown"
+        );
+    }
+}
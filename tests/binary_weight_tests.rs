@@ -58,9 +58,10 @@ fn test_excluded_files_not_in_package() {
     }
 }
 
-/// Verify that the total number of packaged files stays reasonable.
-/// If this test fails, someone likely added a large directory that
-/// should be in the exclude list.
+/// Verify that the number of packaged files stays reasonable.
+/// Sources grow by a file with every module and all of them ship, so only
+/// the files outside `src/` are counted. If this test fails, someone likely
+/// added a large directory that should be in the exclude list.
 #[test]
 fn test_package_file_count_reasonable() {
     let output = Command::new("cargo")
@@ -69,12 +70,16 @@ fn test_package_file_count_reasonable() {
         .expect("Failed to run cargo package --list");
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let file_count = stdout.lines().count();
+    let file_count = stdout
+        .lines()
+        .filter(|file| !file.starts_with("src/"))
+        .count();
 
-    // We expect roughly 30-40 files. Fail if it balloons past 50.
+    // We expect roughly 25 files besides the sources: manifests, docs, tests,
+    // benches and examples. Fail if it balloons past 35.
     assert!(
-        file_count <= 50,
-        "Package contains {file_count} files — expected ≤ 50. \
+        file_count <= 35,
+        "Package contains {file_count} files outside src/ — expected ≤ 35. \
          Check if new directories need to be added to the exclude list in Cargo.toml."
     );
 }
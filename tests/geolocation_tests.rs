@@ -3,8 +3,9 @@
 //! These tests verify that the geolocation services work correctly
 //! and handle various edge cases and failures gracefully.
 
+use netrunner_cli::modules::geolocation;
 use netrunner_cli::modules::speed_test::{GeoLocation, SpeedTest};
-use netrunner_cli::modules::types::{DetailLevel, TestConfig};
+use netrunner_cli::modules::types::{CustomGeoProvider, DetailLevel, GeoSettings, TestConfig};
 use std::time::Duration;
 
 fn ensure_crypto_provider() {
@@ -21,6 +22,7 @@ fn create_geo_test_config() -> TestConfig {
         animation_enabled: false,
         detail_level: DetailLevel::Standard,
         max_servers: 2,
+        ..Default::default()
     }
 }
 
@@ -476,6 +478,49 @@ async fn test_geolocation_service_names() {
     }
 }
 
+#[test]
+fn test_geolocation_provider_selection() {
+    let names = |settings: &GeoSettings| -> Vec<String> {
+        geolocation::providers(settings)
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect()
+    };
+
+    let defaults = GeoSettings::default();
+    assert_eq!(
+        names(&defaults),
+        [
+            "ipapi.co",
+            "ip-api.com",
+            "ipinfo.io",
+            "freegeoip.app",
+            "ipwhois.app"
+        ]
+    );
+
+    let trimmed = GeoSettings {
+        disabled: vec!["IP-API.com".to_string(), "ipwhois.app".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(names(&trimmed), ["ipapi.co", "ipinfo.io", "freegeoip.app"]);
+
+    let private = GeoSettings {
+        builtin: false,
+        custom: vec![CustomGeoProvider {
+            name: "office".to_string(),
+            url: "https://geo.example.internal/".to_string(),
+            country: "/country".to_string(),
+            city: "/city".to_string(),
+            latitude: "/lat".to_string(),
+            longitude: "/lon".to_string(),
+            isp: None,
+        }],
+        ..Default::default()
+    };
+    assert_eq!(names(&private), ["office"]);
+}
+
 // Helper function to test continent determination
 fn determine_continent(lat: f64, lon: f64) -> String {
     if lat > 15.0 && lon > -130.0 && lon < -50.0 {
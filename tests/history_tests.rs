@@ -1,4 +1,5 @@
 use chrono::Utc;
+use netrunner_cli::modules::history::HistoryStorage;
 use netrunner_cli::modules::types::{ConnectionQuality, SpeedTestResult};
 use std::net::{IpAddr, Ipv4Addr};
 
@@ -44,6 +45,7 @@ fn test_speed_test_result_creation() {
         quality: ConnectionQuality::Good,
         test_duration_seconds: 12.5,
         isp: Some("Test ISP".to_string()),
+        ..Default::default()
     };
 
     assert_eq!(test_result.download_mbps, 75.5);
@@ -95,3 +97,16 @@ fn test_connection_quality_display() {
     assert_eq!(format!("{}", ConnectionQuality::VeryPoor), "Very Poor");
     assert_eq!(format!("{}", ConnectionQuality::Failed), "Failed");
 }
+
+#[test]
+fn test_disabled_history_keeps_nothing() {
+    // Nothing else in this test binary opens the history
+    HistoryStorage::disable();
+
+    let storage = HistoryStorage::new().unwrap();
+    storage.save_result(&SpeedTestResult::default()).unwrap();
+    assert_eq!(storage.count().unwrap(), 1);
+
+    let reopened = HistoryStorage::new().unwrap();
+    assert_eq!(reopened.count().unwrap(), 0);
+}
@@ -27,6 +27,7 @@ async fn test_speed_test_with_custom_config() {
         animation_enabled: false,
         detail_level: DetailLevel::Basic,
         max_servers: 1,
+        ..Default::default()
     };
 
     let speed_test = SpeedTest::new(config);
@@ -72,6 +73,7 @@ async fn test_speed_test_timeout() {
         animation_enabled: false,
         detail_level: DetailLevel::Basic,
         max_servers: 1,
+        ..Default::default()
     };
 
     let speed_test = SpeedTest::new(config).unwrap();
@@ -96,6 +98,7 @@ async fn test_speed_test_result_structure() {
         animation_enabled: false,
         detail_level: DetailLevel::Standard,
         max_servers: 1,
+        ..Default::default()
     };
 
     let speed_test = SpeedTest::new(config).unwrap();
@@ -128,6 +131,7 @@ async fn test_multiple_speed_tests() {
         animation_enabled: false,
         detail_level: DetailLevel::Basic,
         max_servers: 1,
+        ..Default::default()
     };
 
     // Run multiple tests to ensure consistency
@@ -2,8 +2,8 @@ use chrono::Utc;
 use netrunner_cli::modules::{
     speed_test::SpeedTest,
     types::{
-        ConnectionQuality, DetailLevel, ServerCapabilities, ServerProvider, SpeedTestResult,
-        TestConfig, TestServer,
+        ConnectionQuality, DetailLevel, ServerCapabilities, ServerProtocol, ServerProvider,
+        SpeedTestResult, TestConfig, TestServer,
     },
 };
 use std::time::Duration;
@@ -22,6 +22,7 @@ fn create_test_config() -> TestConfig {
         animation_enabled: false,
         detail_level: DetailLevel::Standard,
         max_servers: 2,
+        ..Default::default()
     }
 }
 
@@ -34,6 +35,7 @@ fn create_mock_server() -> TestServer {
         distance_km: Some(100.0),
         latency_ms: Some(50.0),
         provider: ServerProvider::Custom("Test".to_string()),
+        protocol: ServerProtocol::Cloudflare,
         capabilities: ServerCapabilities {
             supports_download: true,
             supports_upload: true,
@@ -45,6 +47,7 @@ fn create_mock_server() -> TestServer {
         country_code: Some("US".to_string()),
         city: Some("Test City".to_string()),
         is_backup: false,
+        endpoints: None,
     }
 }
 
@@ -156,6 +159,7 @@ async fn test_speed_test_result_validation() {
         quality: ConnectionQuality::Good,
         test_duration_seconds: 15.5,
         isp: Some("Test ISP".to_string()),
+        ..Default::default()
     };
 
     // Verify all fields are properly set
@@ -342,6 +346,7 @@ async fn test_json_serialization() {
         quality: ConnectionQuality::Excellent,
         test_duration_seconds: 12.34,
         isp: Some("Test ISP".to_string()),
+        ..Default::default()
     };
 
     // Test JSON serialization
@@ -380,6 +385,7 @@ async fn test_performance_metrics_integration() {
         quality: ConnectionQuality::Good,
         test_duration_seconds: 10.0,
         isp: None,
+        ..Default::default()
     };
 
     // Verify that quality assessment considers all metrics appropriately
@@ -584,6 +590,7 @@ async fn test_multiple_server_providers() {
         ServerProvider::Cloudflare,
         ServerProvider::Google,
         ServerProvider::Netflix,
+        ServerProvider::SelfHosted,
         ServerProvider::Custom("TestProvider".to_string()),
     ];
 
@@ -599,6 +606,9 @@ async fn test_multiple_server_providers() {
             ServerProvider::Google => assert_eq!(server.provider, ServerProvider::Google),
             ServerProvider::Netflix => assert_eq!(server.provider, ServerProvider::Netflix),
             ServerProvider::Ookla => assert_eq!(server.provider, ServerProvider::Ookla),
+            ServerProvider::SelfHosted => {
+                assert_eq!(server.provider, ServerProvider::SelfHosted)
+            }
             ServerProvider::Custom(ref name) => {
                 if let ServerProvider::Custom(ref server_name) = server.provider {
                     assert_eq!(server_name, name);
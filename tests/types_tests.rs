@@ -95,6 +95,7 @@ fn test_speed_test_result_with_values() {
         quality: ConnectionQuality::Good,
         test_duration_seconds: 12.5,
         isp: Some("Test ISP".to_string()),
+        ..Default::default()
     };
 
     assert_eq!(result.download_mbps, 75.5);
@@ -120,6 +121,7 @@ fn test_test_server_creation() {
         distance_km: Some(150.5),
         latency_ms: Some(25.0),
         provider: ServerProvider::Custom("Test".to_string()),
+        protocol: ServerProtocol::Cloudflare,
         capabilities: ServerCapabilities {
             supports_download: true,
             supports_upload: true,
@@ -131,6 +133,7 @@ fn test_test_server_creation() {
         country_code: Some("US".to_string()),
         city: Some("Test City".to_string()),
         is_backup: false,
+        endpoints: None,
     };
 
     assert_eq!(server.name, "Test Server");
@@ -163,6 +166,7 @@ fn test_test_config_custom() {
         animation_enabled: false,
         detail_level: DetailLevel::Detailed,
         max_servers: 5,
+        ..Default::default()
     };
 
     assert_eq!(config.server_url, "https://custom.server.com");
@@ -211,6 +215,8 @@ fn test_route_hop_creation() {
         address: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
         hostname: Some("gateway.example.com".to_string()),
         response_time_ms: Some(15.5),
+        network: None,
+        location: None,
     };
 
     assert_eq!(hop.hop_number, 5);
@@ -228,10 +234,21 @@ fn test_network_diagnostics_creation() {
             IpAddr::V4(Ipv4Addr::new(8, 8, 4, 4)),
         ],
         dns_response_time_ms: 25.0,
+        dns_lookups: vec![],
+        dns_records: vec![],
         route_hops: vec![],
         is_ipv6_available: true,
         connection_type: Some("Ethernet".to_string()),
         network_interface: Some("eth0".to_string()),
+        tls_checks: vec![],
+        resolver_benchmarks: vec![],
+        dns_security: Default::default(),
+        routes: vec![],
+        server_networks: vec![],
+        latency_baseline: Default::default(),
+        clock_check: None,
+        happy_eyeballs: vec![],
+        windows_stack: None,
     };
 
     assert_eq!(
@@ -260,6 +277,7 @@ fn test_enhanced_speed_test_result_with_jitter_and_packet_loss() {
         quality: ConnectionQuality::Good,
         test_duration_seconds: 25.4,
         isp: Some("Enhanced ISP Provider".to_string()),
+        ..Default::default()
     };
 
     // Verify enhanced metrics are properly stored
@@ -305,6 +323,7 @@ fn test_test_server_with_quality_metrics() {
         distance_km: Some(245.8),
         latency_ms: Some(18.5),
         provider: ServerProvider::Custom("ExampleNet".to_string()),
+        protocol: ServerProtocol::Cloudflare,
         capabilities: ServerCapabilities {
             supports_download: true,
             supports_upload: true,
@@ -316,6 +335,7 @@ fn test_test_server_with_quality_metrics() {
         country_code: Some("US".to_string()),
         city: Some("San Francisco".to_string()),
         is_backup: false,
+        endpoints: None,
     };
 
     // Test all fields are properly set
@@ -339,6 +359,7 @@ fn test_comprehensive_serialization() {
         distance_km: Some(123.45),
         latency_ms: Some(67.89),
         provider: ServerProvider::Custom("TestProvider".to_string()),
+        protocol: ServerProtocol::Cloudflare,
         capabilities: ServerCapabilities {
             supports_download: true,
             supports_upload: false,
@@ -350,6 +371,7 @@ fn test_comprehensive_serialization() {
         country_code: Some("TEST".to_string()),
         city: Some("Test City".to_string()),
         is_backup: true,
+        endpoints: None,
     };
 
     // Test JSON serialization and deserialization